@@ -0,0 +1,25 @@
+// runs `examples/embed.ez` with a few functions of this program, from the root
+// of the repository: `cargo run --example embed`
+
+use std::error::Error;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut engine = ez::Engine::new();
+    engine
+        .register_fn("host_log", |message: String| println!("[host] {}", message))
+        .register_fn("host_sum", |numbers: Vec<i64>| numbers.iter().sum::<i64>())
+        .register_fn("host_repeat", |text: String, times: i64| {
+            text.repeat(times.max(0) as usize)
+        })
+        .register_fn("host_squares", |count: i64| {
+            (1..=count).map(|n| n * n).collect::<Vec<_>>()
+        });
+
+    engine.eval_file("examples/embed.ez")?;
+
+    // errors come back rendered like the command line shows them
+    if let Err(error) = engine.eval("host_log(1);") {
+        println!("{}", error);
+    }
+    Ok(())
+}
@@ -0,0 +1,46 @@
+// a lint of this program's own, run by the compiler on the programs it
+// evaluates: `cargo run --example plugin`
+
+use std::error::Error;
+
+use ez::ast::{Expr, Program};
+use ez::diagnostics::Diagnostic;
+use ez::plugin::Plugin;
+
+// declarations named like `tmp_x` are for trying things out, not for keeping
+struct NoTemporaries;
+
+impl Plugin for NoTemporaries {
+    fn name(&self) -> &'static str {
+        "no_temporaries"
+    }
+
+    fn parsed(&self, program: &mut Program) -> Vec<Diagnostic> {
+        program
+            .statements()
+            .filter_map(|statement| match statement {
+                Expr::Declaration { identifier, .. } if identifier.name.starts_with("tmp_") => {
+                    let message = format!("`{}` is a temporary", identifier.name);
+                    Some(
+                        Diagnostic::error("P0001", message)
+                            .with_label(identifier.span, "rename it before keeping it"),
+                    )
+                }
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut engine = ez::Engine::new();
+    engine.register_plugin(NoTemporaries);
+
+    engine.eval("total := 1 + 2;\nprintln(total);")?;
+
+    // errors come back rendered like the compiler's own
+    if let Err(error) = engine.eval("tmp_total := 1 + 2;\nprintln(tmp_total);") {
+        println!("{}", error);
+    }
+    Ok(())
+}
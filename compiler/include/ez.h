@@ -0,0 +1,66 @@
+// the front of the ez compiler for C. A file is parsed with `ez_parse`, or
+// checked along with the files it uses with `ez_check`, into an analysis that
+// has to be freed with `ez_analysis_free`. The strings and bytes it hands out
+// stay valid until then. The functions take null instead of an analysis, and
+// give back nothing for it
+
+#ifndef EZ_H
+#define EZ_H
+
+// made by cbindgen from src/capi.rs, don't change it by hand
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+typedef enum EzSeverity {
+  EZ_SEVERITY_ERROR,
+  EZ_SEVERITY_WARNING,
+} EzSeverity;
+
+typedef struct EzAnalysis EzAnalysis;
+
+typedef struct EzLocation {
+  size_t line;
+  size_t column;
+  size_t start;
+  size_t end;
+} EzLocation;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+struct EzAnalysis *ez_parse(const char *name, const char *source);
+
+struct EzAnalysis *ez_check(const char *name, const char *source);
+
+void ez_analysis_free(struct EzAnalysis *analysis);
+
+bool ez_analysis_parsed(const struct EzAnalysis *analysis);
+
+const uint8_t *ez_analysis_ast(const struct EzAnalysis *analysis, size_t *len);
+
+const char *ez_analysis_rendered(const struct EzAnalysis *analysis);
+
+const char *ez_analysis_json(const struct EzAnalysis *analysis);
+
+size_t ez_diagnostic_count(const struct EzAnalysis *analysis);
+
+enum EzSeverity ez_diagnostic_severity(const struct EzAnalysis *analysis, size_t index);
+
+const char *ez_diagnostic_code(const struct EzAnalysis *analysis, size_t index);
+
+const char *ez_diagnostic_message(const struct EzAnalysis *analysis, size_t index);
+
+const char *ez_diagnostic_file(const struct EzAnalysis *analysis, size_t index);
+
+struct EzLocation ez_diagnostic_location(const struct EzAnalysis *analysis, size_t index);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* EZ_H */
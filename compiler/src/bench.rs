@@ -0,0 +1,100 @@
+use std::time::Duration;
+
+use ez_check::profile::{columns, duration};
+
+// how `ez bench` runs a function: a few times first, untimed, so what it uses
+// is allocated and cached, then timed
+pub const WARMUP: usize = 5;
+pub const RUNS: usize = 30;
+
+// what the timed runs of a benchmark came to. Runs far from the others, like
+// ones a collection or another process got in the way of, are left out
+pub struct Summary {
+    pub name: String,
+    pub runs: usize,
+    pub outliers: usize,
+    pub mean: Duration,
+    pub deviation: Duration,
+    pub median: Duration,
+    pub min: Duration,
+    pub max: Duration,
+}
+
+// the sample at `fraction` of the way through the sorted ones
+fn quantile(sorted: &[Duration], fraction: f64) -> Duration {
+    let index = fraction * (sorted.len() - 1) as f64;
+    let (low, high) = (index.floor() as usize, index.ceil() as usize);
+    let weight = index - low as f64;
+    sorted[low].mul_f64(1.0 - weight) + sorted[high].mul_f64(weight)
+}
+
+impl Summary {
+    // the outliers are the samples more than one and a half times the
+    // interquartile range below the first quartile or above the third
+    pub fn new(name: impl Into<String>, mut samples: Vec<Duration>) -> Self {
+        assert!(!samples.is_empty(), "a benchmark runs at least once");
+        samples.sort();
+
+        let (first, third) = (quantile(&samples, 0.25), quantile(&samples, 0.75));
+        let fence = (third - first).mul_f64(1.5);
+        let low = first.saturating_sub(fence);
+        let high = third + fence;
+        let runs = samples.len();
+        samples.retain(|sample| (low..=high).contains(sample));
+
+        let mean = samples.iter().sum::<Duration>() / samples.len() as u32;
+        let variance = samples
+            .iter()
+            .map(|sample| (sample.as_secs_f64() - mean.as_secs_f64()).powi(2))
+            .sum::<f64>()
+            / samples.len() as f64;
+
+        Self {
+            name: name.into(),
+            runs,
+            outliers: runs - samples.len(),
+            mean,
+            deviation: Duration::from_secs_f64(variance.sqrt()),
+            median: quantile(&samples, 0.5),
+            min: samples[0],
+            max: samples[samples.len() - 1],
+        }
+    }
+}
+
+// a row for every benchmark, compared to the fastest one
+pub fn table(summaries: &[Summary]) -> String {
+    let Some(fastest) = summaries.iter().map(|summary| summary.mean).min() else {
+        return String::new();
+    };
+
+    let header = [
+        "benchmark",
+        "mean",
+        "± deviation",
+        "median",
+        "min",
+        "max",
+        "runs",
+        "relative",
+    ];
+    let mut rows = vec![header.map(String::from).to_vec()];
+    for summary in summaries {
+        let relative = summary.mean.as_secs_f64() / fastest.as_secs_f64().max(f64::MIN_POSITIVE);
+        rows.push(vec![
+            summary.name.clone(),
+            duration(summary.mean),
+            format!("± {}", duration(summary.deviation)),
+            duration(summary.median),
+            duration(summary.min),
+            duration(summary.max),
+            match summary.outliers {
+                0 => summary.runs.to_string(),
+                outliers => format!("{} ({} outliers)", summary.runs, outliers),
+            },
+            format!("{:.2}x", relative),
+        ]);
+    }
+
+    columns(&rows)
+}
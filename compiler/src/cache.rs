@@ -0,0 +1,91 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use ez_diagnostics::SourceMap;
+
+// next to the programs, a record for each of them
+pub const DIR: &str = ".ez-cache";
+
+// FNV-1a, so the hashes stay the same from one build of the compiler to the
+// next, the standard library's hasher doesn't promise that
+fn hash(content: &[u8]) -> u64 {
+    content.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+// the compiler that checked a program, a different one could find other
+// problems in it
+fn compiler() -> String {
+    let built = std::env::current_exe()
+        .and_then(fs::metadata)
+        .and_then(|metadata| metadata.modified())
+        .map(|time| format!("{:?}", time))
+        .unwrap_or_default();
+    format!("{} {}", env!("CARGO_PKG_VERSION"), built)
+}
+
+// remembers the programs that were checked without a diagnostic, along with
+// the hash of every file they're made of, the files they use too. Checking
+// one again is skipped until one of those files changes, or the settings it
+// was checked with do
+pub struct Cache {
+    record: PathBuf,
+    settings: String,
+}
+
+impl Cache {
+    // `settings` are what changes the diagnostics of a program, like the lint
+    // levels
+    pub fn new(entry: &Path, settings: &str) -> Self {
+        // not a `.ez` file, or it would be compiled with the others of the directory
+        let dir = entry.parent().unwrap_or(Path::new("")).join(DIR);
+        let name = entry.file_name().unwrap_or_default().to_string_lossy();
+
+        Self {
+            record: dir.join(format!("{}.check", name)),
+            settings: format!("{} {}", compiler(), settings),
+        }
+    }
+
+    // the record is the settings on the first line, then a line for each file,
+    // its hash and its path
+    pub fn is_fresh(&self) -> bool {
+        let Ok(record) = fs::read_to_string(&self.record) else {
+            return false;
+        };
+
+        let mut lines = record.lines();
+        if lines.next() != Some(self.settings.as_str()) {
+            return false;
+        }
+
+        let mut files = 0;
+        for line in lines {
+            let Some((recorded, path)) = line.split_once(' ') else {
+                return false;
+            };
+            match fs::read(path) {
+                Ok(content) if format!("{:016x}", hash(&content)) == recorded => files += 1,
+                _ => return false,
+            }
+        }
+
+        files > 0
+    }
+
+    pub fn store(&self, sources: &SourceMap) -> io::Result<()> {
+        let mut record = format!("{}\n", self.settings);
+        for file in sources.files() {
+            let path = fs::canonicalize(&file.name)?;
+            let line = format!("{:016x} {}\n", hash(file.source.as_bytes()), path.display());
+            record.push_str(&line);
+        }
+
+        if let Some(dir) = self.record.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&self.record, record)
+    }
+}
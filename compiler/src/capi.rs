@@ -0,0 +1,290 @@
+// the front of the compiler for programs that aren't written in Rust, like
+// editors, through functions C can call. `include/ez.h` declares them, it's
+// made from this file with
+//
+//     cbindgen --config cbindgen.toml --output include/ez.h
+//
+// A file is parsed, or checked, into an analysis, which has its diagnostics
+// and tree. What an analysis hands out stays valid until it's freed
+#![allow(clippy::missing_safety_doc)]
+
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+use ez_ast::{binary, Arena};
+use ez_check::json::Json;
+use ez_diagnostics::render::Renderer;
+use ez_diagnostics::{line_col, Diagnostic, DiagnosticSink, Severity, SourceMap};
+use ez_parser::Parser;
+
+use crate::session::Session;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EzSeverity {
+    Error,
+    Warning,
+}
+
+// where a diagnostic is, in the file of its primary label. Lines and columns
+// start at 1, the offsets are in bytes from the start of the file. All of it
+// is 0 for a diagnostic that isn't about a place in a file
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EzLocation {
+    pub line: usize,
+    pub column: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+struct Entry {
+    severity: EzSeverity,
+    code: CString,
+    message: CString,
+    file: CString,
+    location: EzLocation,
+}
+
+pub struct EzAnalysis {
+    parsed: bool,
+    // the tree as `ast::binary` writes it, empty when the file doesn't parse
+    ast: Vec<u8>,
+    diagnostics: Vec<Entry>,
+    // every diagnostic, like the command line shows them without colors
+    rendered: CString,
+    // every diagnostic, as a list of JSON objects
+    json: CString,
+}
+
+// C strings end at the first nul, the text after one would be lost
+fn c_string(text: &str) -> CString {
+    CString::new(text.replace('\0', "\u{fffd}")).expect("the nuls were replaced")
+}
+
+unsafe fn text(pointer: *const c_char) -> Option<String> {
+    if pointer.is_null() {
+        return None;
+    }
+    Some(CStr::from_ptr(pointer).to_string_lossy().into_owned())
+}
+
+impl EzAnalysis {
+    fn new(sources: &SourceMap, sink: &DiagnosticSink, ast: Option<Vec<u8>>) -> Self {
+        let mut diagnostics = vec![];
+        let mut json = vec![];
+        for diagnostic in sink.diagnostics() {
+            let entry = Self::entry(sources, diagnostic);
+            let location = entry.location;
+            json.push(Json::Object(vec![
+                (
+                    "severity".into(),
+                    Json::String(diagnostic.severity.to_string()),
+                ),
+                ("code".into(), Json::String(diagnostic.code.into())),
+                ("message".into(), Json::String(diagnostic.message.clone())),
+                (
+                    "file".into(),
+                    Json::String(entry.file.to_string_lossy().into()),
+                ),
+                ("line".into(), Json::Number(location.line as f64)),
+                ("column".into(), Json::Number(location.column as f64)),
+                ("start".into(), Json::Number(location.start as f64)),
+                ("end".into(), Json::Number(location.end as f64)),
+            ]));
+            diagnostics.push(entry);
+        }
+
+        Self {
+            parsed: ast.is_some(),
+            ast: ast.unwrap_or_default(),
+            diagnostics,
+            rendered: c_string(&sink.render(&Renderer::default(), sources)),
+            json: c_string(&Json::List(json).to_string()),
+        }
+    }
+
+    fn entry(sources: &SourceMap, diagnostic: &Diagnostic) -> Entry {
+        let (localized, file) = sources.localize(diagnostic);
+        let primary = localized
+            .spans
+            .iter()
+            .find(|label| label.primary)
+            .or(localized.spans.first());
+        let location = match primary {
+            Some(label) => {
+                let (line, column) = line_col(&file.source, label.span.start);
+                EzLocation {
+                    line,
+                    column,
+                    start: label.span.start,
+                    end: label.span.end,
+                }
+            }
+            None => EzLocation::default(),
+        };
+
+        Entry {
+            severity: match diagnostic.severity {
+                Severity::Error => EzSeverity::Error,
+                Severity::Warning => EzSeverity::Warning,
+            },
+            code: c_string(diagnostic.code),
+            message: c_string(&diagnostic.message),
+            file: c_string(&file.name),
+            location,
+        }
+    }
+
+    // the file on its own, the files it uses aren't read
+    fn parse(name: String, source: String) -> Self {
+        let sources = SourceMap::single(name, source.as_str());
+        let mut sink = DiagnosticSink::default();
+        let arena = Arena::new();
+        let ast = match Parser::new(&source, &arena).parse_program() {
+            Ok(program) => Some(binary::encode(&program)),
+            Err(error) => {
+                sink.push(error);
+                None
+            }
+        };
+
+        Self::new(&sources, &sink, ast)
+    }
+
+    // the file and the ones it uses, read from next to it, checked and lowered
+    // like `ez check` does
+    fn check(name: String, source: String) -> Self {
+        let mut session = Session::new();
+        let mut sink = DiagnosticSink::default();
+        let arena = Arena::new();
+        let program = session.load(&arena, &name, source, &mut sink);
+        if let Some(program) = &program {
+            if let Some((resolutions, types)) = crate::check(program, &[], &mut sink) {
+                if !sink.has_errors() {
+                    crate::lower(program, &resolutions, &types, &mut sink);
+                }
+            }
+        }
+
+        let ast = program.as_ref().map(binary::encode);
+        Self::new(&session.sources, &sink, ast)
+    }
+
+    unsafe fn get<'a>(analysis: *const EzAnalysis) -> Option<&'a EzAnalysis> {
+        analysis.as_ref()
+    }
+
+    unsafe fn diagnostic<'a>(analysis: *const EzAnalysis, index: usize) -> Option<&'a Entry> {
+        Self::get(analysis)?.diagnostics.get(index)
+    }
+}
+
+// a panic mustn't unwind into C, it gives back no analysis instead
+unsafe fn analyze(
+    name: *const c_char,
+    source: *const c_char,
+    analyze: fn(String, String) -> EzAnalysis,
+) -> *mut EzAnalysis {
+    let (Some(name), Some(source)) = (text(name), text(source)) else {
+        return ptr::null_mut();
+    };
+    match panic::catch_unwind(AssertUnwindSafe(|| analyze(name, source))) {
+        Ok(analysis) => Box::into_raw(Box::new(analysis)),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+// null when the name or the source is, or when the compiler panicked
+#[no_mangle]
+pub unsafe extern "C" fn ez_parse(name: *const c_char, source: *const c_char) -> *mut EzAnalysis {
+    analyze(name, source, EzAnalysis::parse)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn ez_check(name: *const c_char, source: *const c_char) -> *mut EzAnalysis {
+    analyze(name, source, EzAnalysis::check)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn ez_analysis_free(analysis: *mut EzAnalysis) {
+    if !analysis.is_null() {
+        drop(Box::from_raw(analysis));
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn ez_analysis_parsed(analysis: *const EzAnalysis) -> bool {
+    EzAnalysis::get(analysis).is_some_and(|analysis| analysis.parsed)
+}
+
+// the tree in the binary format of the compiler, its length goes in `len`
+#[no_mangle]
+pub unsafe extern "C" fn ez_analysis_ast(
+    analysis: *const EzAnalysis,
+    len: *mut usize,
+) -> *const u8 {
+    let ast = EzAnalysis::get(analysis).map_or(&[][..], |analysis| &analysis.ast);
+    if !len.is_null() {
+        *len = ast.len();
+    }
+    ast.as_ptr()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn ez_analysis_rendered(analysis: *const EzAnalysis) -> *const c_char {
+    EzAnalysis::get(analysis).map_or(ptr::null(), |analysis| analysis.rendered.as_ptr())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn ez_analysis_json(analysis: *const EzAnalysis) -> *const c_char {
+    EzAnalysis::get(analysis).map_or(ptr::null(), |analysis| analysis.json.as_ptr())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn ez_diagnostic_count(analysis: *const EzAnalysis) -> usize {
+    EzAnalysis::get(analysis).map_or(0, |analysis| analysis.diagnostics.len())
+}
+
+// the diagnostics are numbered from 0 in the order they were reported, past
+// the last one there's no text and the severity is an error
+#[no_mangle]
+pub unsafe extern "C" fn ez_diagnostic_severity(
+    analysis: *const EzAnalysis,
+    index: usize,
+) -> EzSeverity {
+    EzAnalysis::diagnostic(analysis, index).map_or(EzSeverity::Error, |entry| entry.severity)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn ez_diagnostic_code(
+    analysis: *const EzAnalysis,
+    index: usize,
+) -> *const c_char {
+    EzAnalysis::diagnostic(analysis, index).map_or(ptr::null(), |entry| entry.code.as_ptr())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn ez_diagnostic_message(
+    analysis: *const EzAnalysis,
+    index: usize,
+) -> *const c_char {
+    EzAnalysis::diagnostic(analysis, index).map_or(ptr::null(), |entry| entry.message.as_ptr())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn ez_diagnostic_file(
+    analysis: *const EzAnalysis,
+    index: usize,
+) -> *const c_char {
+    EzAnalysis::diagnostic(analysis, index).map_or(ptr::null(), |entry| entry.file.as_ptr())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn ez_diagnostic_location(
+    analysis: *const EzAnalysis,
+    index: usize,
+) -> EzLocation {
+    EzAnalysis::diagnostic(analysis, index).map_or(EzLocation::default(), |entry| entry.location)
+}
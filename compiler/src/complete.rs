@@ -0,0 +1,458 @@
+use ez_ast::BaseType;
+use ez_check::resolver::{Builtin, DefId, DefKind};
+use ez_check::typecheck::TypeChecker;
+use ez_lexer::{Lexer, Symbol, Token, TokenKind};
+
+use crate::query::Analysis;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CompletionKind {
+    Keyword,
+    Variable,
+    Parameter,
+    Function,
+    Enum,
+    Variant,
+    Module,
+    Method,
+    Constant,
+    Type,
+}
+
+// something that can be written where the cursor is
+#[derive(Debug, Clone, PartialEq)]
+pub struct Completion {
+    pub label: String,
+    pub kind: CompletionKind,
+    // the type, when it's known
+    pub detail: Option<String>,
+}
+
+// the keywords a statement starts with, `else` and `catch` come after a block
+const STATEMENT: [&str; 10] = [
+    "if", "while", "for", "match", "return", "break", "continue", "enum", "mut", "try",
+];
+const TOP_LEVEL: [&str; 2] = ["use", "test"];
+const AFTER_BLOCK: [&str; 2] = ["else", "catch"];
+const VALUE: [&str; 3] = ["fn", "true", "false"];
+const TYPES: [&str; 5] = ["int", "float", "string", "bool", "void"];
+
+// what the text before the cursor is about to be, from its tokens
+enum Context {
+    // the name of a token, a variant or a method, after a `.`
+    Member(Token),
+    Type,
+    Statement { top_level: bool, after_block: bool },
+    Value,
+}
+
+fn context(tokens: &[Token]) -> Context {
+    let mut depth = 0usize;
+    let mut brackets = vec![];
+    for token in tokens {
+        match token.kind {
+            TokenKind::LeftCurly => depth += 1,
+            TokenKind::RightCurly => depth = depth.saturating_sub(1),
+            TokenKind::LeftParen | TokenKind::LeftBracket => brackets.push(&token.kind),
+            TokenKind::RightParen | TokenKind::RightBracket => {
+                brackets.pop();
+            }
+            _ => {}
+        }
+    }
+
+    let Some(last) = tokens.last() else {
+        return Context::Statement {
+            top_level: true,
+            after_block: false,
+        };
+    };
+    match last.kind {
+        TokenKind::Dot if tokens.len() > 1 => Context::Member(tokens[tokens.len() - 2].clone()),
+        TokenKind::As => Context::Type,
+        // the `:` of a parameter, in a map it's followed by a value
+        TokenKind::Colon if brackets.last() == Some(&&TokenKind::LeftParen) => Context::Type,
+        TokenKind::Semi | TokenKind::LeftCurly | TokenKind::RightCurly => Context::Statement {
+            top_level: depth == 0,
+            after_block: last.kind == TokenKind::RightCurly,
+        },
+        _ => Context::Value,
+    }
+}
+
+// the source without the line of the offset, and the offset the line starts at.
+// What's being typed is what usually keeps a program from parsing or resolving,
+// without it the rest of the program can be checked to find what's in scope
+pub fn without_line(source: &str, offset: usize) -> (String, usize) {
+    let start = source[..offset]
+        .rfind('\n')
+        .map_or(0, |newline| newline + 1);
+    let end = source[offset..]
+        .find('\n')
+        .map_or(source.len(), |newline| offset + newline);
+
+    (format!("{}{}", &source[..start], &source[end..]), start)
+}
+
+fn kind_of(kind: DefKind) -> CompletionKind {
+    match kind {
+        DefKind::Variable | DefKind::Host => CompletionKind::Variable,
+        DefKind::Function | DefKind::Builtin(_) => CompletionKind::Function,
+        DefKind::Param => CompletionKind::Parameter,
+        DefKind::Enum => CompletionKind::Enum,
+        DefKind::Module(_) | DefKind::Import(_) => CompletionKind::Module,
+    }
+}
+
+// the completions at `offset` of the file of the analysis, for the name being
+// typed there, if any. `source` is the text of the file as it is, `scope` the
+// offset of the analysis the names in scope are found at, which differ when
+// the analysis is of the file `without_line`
+pub fn complete(analysis: &Analysis, source: &str, offset: usize, scope: usize) -> Vec<Completion> {
+    let mut tokens: Vec<Token> = Lexer::new(&source[..offset]).collect();
+    let prefix = match tokens.last() {
+        Some(token) if token.span.end == offset && token.value.chars().all(is_name) => {
+            let prefix = token.value.to_string();
+            tokens.pop();
+            prefix
+        }
+        _ => String::new(),
+    };
+
+    let resolutions = analysis.checked.resolutions.as_ref();
+    let types = analysis.checked.types.as_ref();
+    let in_scope: Vec<DefId> = resolutions
+        .map(|resolutions| resolutions.declarations_in_scope(scope))
+        .unwrap_or_default();
+    let name = |id: &DefId| resolutions.map(|resolutions| resolutions.definition(*id));
+    let detail = |id: &DefId| {
+        types
+            .and_then(|types| types.definitions.get(id))
+            .map(|basetype| basetype.to_string())
+    };
+    let of = |id: &DefId| {
+        let definition = name(id)?;
+        Some(Completion {
+            label: definition.name.to_string(),
+            kind: kind_of(definition.kind),
+            detail: detail(id),
+        })
+    };
+    let keywords = |words: &[&str]| {
+        words
+            .iter()
+            .map(|word| Completion {
+                label: word.to_string(),
+                kind: CompletionKind::Keyword,
+                detail: None,
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let mut completions = vec![];
+    match context(&tokens) {
+        Context::Member(receiver) => {
+            let found = in_scope
+                .iter()
+                .find(|id| name(id).is_some_and(|definition| definition.name == receiver.value));
+            if let Some(id) = found {
+                completions = members(analysis, *id);
+            }
+        }
+
+        Context::Type => {
+            completions = TYPES
+                .iter()
+                .map(|name| Completion {
+                    label: name.to_string(),
+                    kind: CompletionKind::Type,
+                    detail: None,
+                })
+                .collect();
+            completions.extend(
+                in_scope
+                    .iter()
+                    .filter(|id| {
+                        name(id).is_some_and(|definition| definition.kind == DefKind::Enum)
+                    })
+                    .filter_map(of),
+            );
+        }
+
+        Context::Statement {
+            top_level,
+            after_block,
+        } => {
+            completions = in_scope.iter().filter_map(of).collect();
+            completions.extend(keywords(&STATEMENT));
+            if top_level {
+                completions.extend(keywords(&TOP_LEVEL));
+            }
+            if after_block {
+                completions.extend(keywords(&AFTER_BLOCK));
+            }
+        }
+
+        Context::Value => {
+            completions = in_scope.iter().filter_map(of).collect();
+            completions.extend(keywords(&VALUE));
+        }
+    }
+
+    completions.retain(|completion| completion.label.starts_with(&prefix));
+    completions
+}
+
+fn is_name(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+// what comes after the name of the definition and a `.`: the variants of an
+// enum, the members of a module, or the methods of a value of its type
+fn members(analysis: &Analysis, id: DefId) -> Vec<Completion> {
+    let Some(resolutions) = analysis.checked.resolutions.as_ref() else {
+        return vec![];
+    };
+    let types = analysis.checked.types.as_ref();
+    let definition = resolutions.definition(id);
+    let function = |builtin: &Builtin| Completion {
+        label: builtin.name().to_string(),
+        kind: if builtin.is_method() {
+            CompletionKind::Method
+        } else {
+            CompletionKind::Function
+        },
+        detail: None,
+    };
+
+    match definition.kind {
+        DefKind::Enum => {
+            let variants = types.and_then(|types| types.enums.get(&definition.name));
+            variants
+                .into_iter()
+                .flatten()
+                .map(|variant| {
+                    let payload: Vec<String> = variant
+                        .payload
+                        .iter()
+                        .map(|basetype| basetype.to_string())
+                        .collect();
+                    Completion {
+                        label: variant.identifier.name.to_string(),
+                        kind: CompletionKind::Variant,
+                        detail: (!payload.is_empty())
+                            .then(|| format!("{}({})", definition.name, payload.join(", "))),
+                    }
+                })
+                .collect()
+        }
+
+        DefKind::Module(module) => {
+            let mut members: Vec<Completion> = module.functions().iter().map(function).collect();
+            members.extend(module.constants().iter().map(|(name, _)| Completion {
+                label: name.to_string(),
+                kind: CompletionKind::Constant,
+                detail: Some("float".into()),
+            }));
+            members
+        }
+
+        DefKind::Import(module) => exported(analysis, module)
+            .into_iter()
+            .map(|id| {
+                let definition = resolutions.definition(id);
+                Completion {
+                    label: definition.name.to_string(),
+                    kind: kind_of(definition.kind),
+                    detail: types
+                        .and_then(|types| types.definitions.get(&id))
+                        .map(|basetype| basetype.to_string()),
+                }
+            })
+            .collect(),
+
+        _ => {
+            let basetype = types.and_then(|types| types.definitions.get(&id));
+            basetype
+                .map(|basetype| Builtin::methods(basetype).iter().map(function).collect())
+                .unwrap_or_default()
+        }
+    }
+}
+
+// the top level declarations of a file the program uses, without the files it
+// uses itself. They're in the scope of the file, which is in the one every file
+// is in
+fn exported(analysis: &Analysis, module: usize) -> Vec<DefId> {
+    let Some(resolutions) = analysis.checked.resolutions.as_ref() else {
+        return vec![];
+    };
+    let Some(path) = analysis.checked.modules.get(module) else {
+        return vec![];
+    };
+    let Some(file) = analysis
+        .sources
+        .files()
+        .iter()
+        .find(|file| file.name == *path)
+    else {
+        return vec![];
+    };
+    let end = file.start + file.source.len();
+
+    resolutions
+        .scopes
+        .iter()
+        .filter(|scope| {
+            scope
+                .parent
+                .is_some_and(|parent| resolutions.scopes[parent].span.end == usize::MAX)
+                && file.start <= scope.span.start
+                && scope.span.end <= end
+        })
+        .flat_map(|scope| &scope.definitions)
+        .map(|(id, _)| *id)
+        .filter(|id| !matches!(resolutions.definition(*id).kind, DefKind::Import(_)))
+        .collect()
+}
+
+// the function being called where the cursor is in its arguments, written the
+// way it's declared, like `add(a: int, b: int) int`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Signature {
+    pub label: String,
+    // where each parameter is in the label
+    pub parameters: Vec<(usize, usize)>,
+    // the parameter of the argument the cursor is in
+    pub active: usize,
+    pub docs: String,
+}
+
+// the `(` of the call the end of the tokens is in the arguments of, and how many
+// arguments come before the one it's in. Brackets closed before it are skipped,
+// like the ones of the calls among the arguments
+fn call(tokens: &[Token]) -> Option<(usize, usize)> {
+    let mut depth = 0usize;
+    let mut commas = 0;
+    for (index, token) in tokens.iter().enumerate().rev() {
+        match token.kind {
+            TokenKind::RightParen | TokenKind::RightBracket | TokenKind::RightCurly => depth += 1,
+            TokenKind::LeftParen if depth == 0 => return Some((index, commas)),
+            // in a list, or in the body of a function given as an argument
+            TokenKind::LeftBracket | TokenKind::LeftCurly | TokenKind::Semi if depth == 0 => {
+                return None;
+            }
+            TokenKind::LeftParen | TokenKind::LeftBracket | TokenKind::LeftCurly => depth -= 1,
+            TokenKind::Comma if depth == 0 => commas += 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+// the signature of the call the offset is in the arguments of, with `scope` the
+// offset the names in scope are found at, like for `complete`. The builtins are
+// checked by how they're called, they have no parameters to show, but the
+// methods of values do
+pub fn signature(
+    analysis: &Analysis,
+    source: &str,
+    offset: usize,
+    scope: usize,
+) -> Option<Signature> {
+    let tokens: Vec<Token> = Lexer::new(&source[..offset]).collect();
+    let (paren, active) = call(&tokens)?;
+    let callee = tokens[..paren].last()?;
+    if callee.kind != TokenKind::Identifier {
+        return None;
+    }
+    let receiver = match tokens[..paren].iter().rev().nth(1) {
+        Some(dot) if dot.kind == TokenKind::Dot => Some(tokens[..paren].iter().rev().nth(2)?),
+        _ => None,
+    };
+
+    let resolutions = analysis.checked.resolutions.as_ref()?;
+    let types = analysis.checked.types.as_ref()?;
+    let named = |name: &Symbol| {
+        resolutions
+            .declarations_in_scope(scope)
+            .into_iter()
+            .find(|id| resolutions.definition(*id).name == *name)
+    };
+    let function = |id: DefId| match types.definitions.get(&id) {
+        Some(BaseType::Function {
+            params,
+            return_type,
+        }) => {
+            let params: Vec<String> = params
+                .iter()
+                .map(|param| match param.identifier.name.as_str() {
+                    "" => param.basetype.to_string(),
+                    name => format!("{}: {}", name, param.basetype),
+                })
+                .collect();
+            Some((params, (**return_type).clone(), Some(id)))
+        }
+        _ => None,
+    };
+
+    let (params, result, id) = match receiver {
+        None => {
+            let id = named(&callee.value.symbol())?;
+            match resolutions.definition(id).kind {
+                DefKind::Builtin(_) => return None,
+                _ => function(id)?,
+            }
+        }
+        Some(receiver) => {
+            let owner = named(&receiver.value.symbol())?;
+            let definition = resolutions.definition(owner);
+            match definition.kind {
+                DefKind::Module(_) => return None,
+                DefKind::Enum => {
+                    let variants = types.enums.get(&definition.name)?;
+                    let variant = variants
+                        .iter()
+                        .find(|variant| variant.identifier.name == callee.value)?;
+                    let payload = variant.payload.iter().map(|basetype| basetype.to_string());
+                    let result = BaseType::Named(definition.name);
+                    (payload.collect(), result, None)
+                }
+                DefKind::Import(module) => {
+                    let id = exported(analysis, module)
+                        .into_iter()
+                        .find(|id| resolutions.definition(*id).name == callee.value)?;
+                    function(id)?
+                }
+                _ => {
+                    let basetype = types.definitions.get(&owner)?;
+                    let builtin = Builtin::method(basetype, &callee.value)?;
+                    let (params, result) = TypeChecker::method_signature(builtin, basetype);
+                    let params = params.iter().map(|basetype| basetype.to_string());
+                    (params.collect(), result, None)
+                }
+            }
+        }
+    };
+
+    let mut label = format!("{}(", callee.value);
+    let mut parameters = vec![];
+    for (index, param) in params.iter().enumerate() {
+        if index > 0 {
+            label.push_str(", ");
+        }
+        parameters.push((label.len(), label.len() + param.len()));
+        label.push_str(param);
+    }
+    label.push(')');
+    if result != BaseType::Void {
+        label.push_str(&format!(" {}", result));
+    }
+
+    Some(Signature {
+        label,
+        parameters,
+        active,
+        docs: id.map(|id| analysis.docs(id)).unwrap_or_default(),
+    })
+}
@@ -0,0 +1,347 @@
+use std::collections::HashSet;
+use std::fmt::Write;
+
+use ez_ast::{BaseType, Expr, Identifier, Param, Program, ValueExpr, Variant};
+use ez_check::resolver::Resolutions;
+use ez_check::typecheck::Types;
+use ez_diagnostics::{SourceFile, SourceMap};
+use ez_lexer::{Lexer, Span};
+
+// what `ez doc` writes, a page to open in a browser or one to publish with the
+// rest of a project's Markdown
+#[derive(Clone, Copy, PartialEq, Default)]
+pub enum DocFormat {
+    #[default]
+    Html,
+    Markdown,
+}
+
+impl DocFormat {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "html" => Some(DocFormat::Html),
+            "markdown" | "md" => Some(DocFormat::Markdown),
+            _ => None,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            DocFormat::Html => "html",
+            DocFormat::Markdown => "md",
+        }
+    }
+}
+
+// the declarations that get documented, the ones at the top of a file that
+// can't change: functions, enums and constants
+enum Item<'a> {
+    Function {
+        name: &'a str,
+        params: &'a [Param],
+        return_type: &'a BaseType,
+    },
+    Enum {
+        name: &'a str,
+        variants: &'a [Variant],
+    },
+    Constant {
+        identifier: &'a Identifier,
+        basetype: Option<&'a BaseType>,
+    },
+}
+
+impl Item<'_> {
+    fn name(&self) -> &str {
+        match self {
+            Item::Function { name, .. } | Item::Enum { name, .. } => name,
+            Item::Constant { identifier, .. } => &identifier.name,
+        }
+    }
+}
+
+// the documentation of a checked program, from the `///` comments right above
+// its declarations. The files it uses are documented along with it, and the
+// enums in the signatures link to their own documentation
+pub struct Documenter<'a> {
+    sources: &'a SourceMap,
+    resolutions: &'a Resolutions,
+    types: &'a Types,
+    format: DocFormat,
+    // the enums that are documented, the others aren't linked
+    enums: HashSet<&'a str>,
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// the `///` lines right above the span, without their slashes. The language
+// server shows them too
+pub fn docs(file: &SourceFile, span: Span) -> String {
+    let comments = Lexer::new(&file.source).with_offset(file.start).comments();
+    let text = |span: Span| &file.source[span.start - file.start..span.end - file.start];
+
+    let mut lines = vec![];
+    let mut end = span.start;
+    for comment in comments.iter().rev() {
+        if comment.end > end {
+            continue;
+        }
+        let Some(line) = text(*comment).strip_prefix("///") else {
+            break;
+        };
+        if !text(Span::new(comment.end, end)).trim().is_empty() {
+            break;
+        }
+        lines.push(line.strip_prefix(' ').unwrap_or(line));
+        end = comment.start;
+    }
+
+    lines.reverse();
+    lines.join("\n")
+}
+
+// the text of `///` comments, in HTML a paragraph for every run of lines and
+// `code` in backticks. Markdown is what they're written in already
+fn html_docs(docs: &str) -> String {
+    let mut out = String::new();
+    for paragraph in docs.split("\n\n").filter(|text| !text.trim().is_empty()) {
+        let mut text = String::new();
+        for (i, piece) in escape(paragraph.trim()).split('`').enumerate() {
+            match i % 2 {
+                0 => text.push_str(piece),
+                _ => text.push_str(&format!("<code>{}</code>", piece)),
+            }
+        }
+        let _ = writeln!(out, "<p>{}</p>", text);
+    }
+
+    out
+}
+
+impl<'a> Documenter<'a> {
+    pub fn new(
+        sources: &'a SourceMap,
+        resolutions: &'a Resolutions,
+        types: &'a Types,
+        format: DocFormat,
+    ) -> Self {
+        Self {
+            sources,
+            resolutions,
+            types,
+            format,
+            enums: HashSet::new(),
+        }
+    }
+
+    fn items(body: &'a [Expr]) -> Vec<(Item<'a>, Span)> {
+        body.iter()
+            .filter_map(|expr| {
+                let item = match expr {
+                    Expr::Declaration {
+                        identifier,
+                        mutable: false,
+                        basetype,
+                        value,
+                        ..
+                    } => match value.as_deref() {
+                        Some(ValueExpr::Function {
+                            params,
+                            return_type,
+                            ..
+                        }) => Item::Function {
+                            name: &identifier.name,
+                            params,
+                            return_type,
+                        },
+                        _ => Item::Constant {
+                            identifier,
+                            basetype: basetype.as_ref(),
+                        },
+                    },
+                    Expr::Enum {
+                        identifier,
+                        variants,
+                        ..
+                    } => Item::Enum {
+                        name: &identifier.name,
+                        variants,
+                    },
+                    _ => return None,
+                };
+                Some((item, expr.span()))
+            })
+            .collect()
+    }
+
+    // enums that are documented link to it. Signatures are HTML in Markdown
+    // too, it doesn't have links in code
+    fn basetype(&self, basetype: &BaseType) -> String {
+        match basetype {
+            BaseType::Named(name) if self.enums.contains(name.as_str()) => {
+                format!("<a href=\"#{}\">{}</a>", name, name)
+            }
+            BaseType::Function {
+                params,
+                return_type,
+            } => {
+                let params: Vec<String> = params
+                    .iter()
+                    .map(|param| self.basetype(&param.basetype))
+                    .collect();
+                match **return_type {
+                    BaseType::Void => format!("fn ({})", params.join(", ")),
+                    _ => format!("fn ({}) {}", params.join(", "), self.basetype(return_type)),
+                }
+            }
+            BaseType::List(item) => format!("[{}]", self.basetype(item)),
+            BaseType::Map(key, value) => {
+                format!("[{}: {}]", self.basetype(key), self.basetype(value))
+            }
+            basetype => basetype.to_string(),
+        }
+    }
+
+    // like the declaration is written, without the body of functions
+    fn signature(&self, item: &Item) -> String {
+        match item {
+            Item::Function {
+                name,
+                params,
+                return_type,
+            } => {
+                let params: Vec<String> = params
+                    .iter()
+                    .map(|param| {
+                        format!(
+                            "{}: {}",
+                            param.identifier.name,
+                            self.basetype(&param.basetype)
+                        )
+                    })
+                    .collect();
+                let mut signature = format!("{} := fn ({})", name, params.join(", "));
+                if **return_type != BaseType::Void {
+                    signature.push_str(&format!(" {}", self.basetype(return_type)));
+                }
+                signature
+            }
+            Item::Enum { name, variants } => {
+                let variants: Vec<String> = variants
+                    .iter()
+                    .map(|variant| {
+                        let name = &variant.identifier.name;
+                        if variant.payload.is_empty() {
+                            return name.to_string();
+                        }
+                        let payload: Vec<String> = variant
+                            .payload
+                            .iter()
+                            .map(|basetype| self.basetype(basetype))
+                            .collect();
+                        format!("{}({})", name, payload.join(", "))
+                    })
+                    .collect();
+                format!("enum {} {{ {} }}", name, variants.join(", "))
+            }
+            // the type a constant was given, or the one it was found to have
+            Item::Constant {
+                identifier,
+                basetype,
+            } => {
+                let found = self
+                    .resolutions
+                    .defined_at(identifier)
+                    .and_then(|id| self.types.definitions.get(&id));
+                match basetype.or(found) {
+                    Some(basetype) => format!("{}: {}", identifier.name, self.basetype(basetype)),
+                    None => identifier.name.to_string(),
+                }
+            }
+        }
+    }
+
+    pub fn document(mut self, title: &str, program: &'a Program) -> String {
+        let files: Vec<_> = program.files().map(|file| Self::items(file.body)).collect();
+        for items in &files {
+            for (item, _) in items {
+                if let Item::Enum { name, .. } = item {
+                    self.enums.insert(name);
+                }
+            }
+        }
+
+        let mut out = String::new();
+        match self.format {
+            DocFormat::Html => {
+                let title = escape(title);
+                let _ = writeln!(out, "<!DOCTYPE html>\n<html>\n<head>");
+                let _ = writeln!(out, "<meta charset=\"utf-8\">\n<title>{}</title>", title);
+                let _ = writeln!(out, "</head>\n<body>\n<h1>{}</h1>", title);
+            }
+            DocFormat::Markdown => {
+                let _ = writeln!(out, "# {}", title);
+            }
+        }
+
+        // the program first, then the files it uses
+        for items in files.iter().rev() {
+            let Some((_, first)) = items.first() else {
+                continue;
+            };
+            self.file(self.sources.file(first.start), items, &mut out);
+        }
+
+        if self.format == DocFormat::Html {
+            let _ = writeln!(out, "</body>\n</html>");
+        }
+        out
+    }
+
+    fn file(&self, source: &SourceFile, items: &[(Item, Span)], out: &mut String) {
+        let name = &source.name;
+        match self.format {
+            DocFormat::Html => {
+                let _ = writeln!(out, "<h2>{}</h2>", escape(name));
+            }
+            DocFormat::Markdown => {
+                let _ = writeln!(out, "\n## {}", name);
+            }
+        }
+
+        for (item, span) in items {
+            let docs = docs(source, *span);
+            let signature = self.signature(item);
+            // enums are linked to by their name, the others are only headings
+            let anchor = match item {
+                Item::Enum { name, .. } => name.to_string(),
+                item => format!("{}.{}", name.trim_end_matches(".ez"), item.name()),
+            };
+
+            match self.format {
+                DocFormat::Html => {
+                    let _ = writeln!(
+                        out,
+                        "<h3 id=\"{}\">{}</h3>\n<pre><code>{}</code></pre>",
+                        escape(&anchor),
+                        escape(item.name()),
+                        signature
+                    );
+                    out.push_str(&html_docs(&docs));
+                }
+                DocFormat::Markdown => {
+                    let _ = writeln!(out, "\n### <a id=\"{}\"></a>{}\n", anchor, item.name());
+                    let _ = writeln!(out, "<pre>{}</pre>", signature);
+                    if !docs.is_empty() {
+                        let _ = writeln!(out, "\n{}", docs);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,152 @@
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use ez_ast::Arena;
+use ez_codegen::host::{HostFunction, IntoHostFunction};
+use ez_codegen::interp::{self, Interpreter};
+use ez_diagnostics::render::Renderer;
+use ez_diagnostics::DiagnosticSink;
+
+use crate::plugin::{Plugin, Plugins};
+use crate::session::Session;
+
+#[derive(Debug)]
+pub enum EngineError {
+    Io { path: PathBuf, error: io::Error },
+    // the diagnostics of a program that didn't pass the checks or stopped with
+    // an error, rendered like the command line does without colors
+    Program(String),
+}
+
+impl fmt::Display for EngineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EngineError::Io { path, error } => {
+                write!(f, "couldn't read `{}`: {}", path.display(), error)
+            }
+            EngineError::Program(diagnostics) => write!(f, "{}", diagnostics.trim_end()),
+        }
+    }
+}
+
+impl Error for EngineError {}
+
+// runs programs from another Rust program, which can give them functions of its
+// own to call:
+//
+//     let mut engine = ez::Engine::new();
+//     engine.register_fn("host_log", |message: String| println!("{}", message));
+//     engine.eval_file("script.ez")?;
+#[derive(Default)]
+pub struct Engine {
+    hosts: Vec<Arc<HostFunction>>,
+    args: Vec<String>,
+    plugins: Plugins,
+}
+
+impl Engine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // the function can take and give back ints, floats, bools, strings and
+    // lists of them, as `i64`, `f64`, `bool`, `String` and `Vec`. Programs see
+    // it like a builtin, so their own definitions of the name shadow it
+    pub fn register_fn<Args, F: IntoHostFunction<Args>>(
+        &mut self,
+        name: &str,
+        function: F,
+    ) -> &mut Self {
+        self.hosts.push(Arc::new(HostFunction::new(name, function)));
+        self
+    }
+
+    // the plugin's passes run on every program, after the ones registered
+    // before it
+    pub fn register_plugin(&mut self, plugin: impl Plugin + 'static) -> &mut Self {
+        self.plugins.add(plugin);
+        self
+    }
+
+    // what `args()` gives back to the programs
+    pub fn set_args(&mut self, args: Vec<String>) -> &mut Self {
+        self.args = args;
+        self
+    }
+
+    // the files the program uses are found from the working directory
+    pub fn eval(&self, source: &str) -> Result<(), EngineError> {
+        self.run("<eval>", source.to_string())
+    }
+
+    pub fn eval_file(&self, path: impl AsRef<Path>) -> Result<(), EngineError> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path).map_err(|error| EngineError::Io {
+            path: path.to_path_buf(),
+            error,
+        })?;
+        self.run(&path.display().to_string(), source)
+    }
+
+    // like `ez run`, the program runs on a thread with a stack big enough for
+    // the interpreter
+    fn run(&self, file: &str, source: String) -> Result<(), EngineError> {
+        let mut sink = DiagnosticSink::default();
+        let mut session = Session::new();
+        let arena = Arena::new();
+        let program = session.load(&arena, file, source, &mut sink);
+        let report = |sink: &DiagnosticSink| {
+            EngineError::Program(sink.render(&Renderer::for_terminal(false), &session.sources))
+        };
+
+        let Some(mut program) = program else {
+            return Err(report(&sink));
+        };
+
+        self.plugins.parsed(&mut program, &mut sink);
+        if sink.has_errors() {
+            return Err(report(&sink));
+        }
+
+        let checked = crate::check(&program, &self.hosts, &mut sink);
+        let Some((resolutions, types)) = checked.filter(|_| !sink.has_errors()) else {
+            return Err(report(&sink));
+        };
+
+        self.plugins
+            .checked(&program, &resolutions, &types, &mut sink);
+        if sink.has_errors() {
+            return Err(report(&sink));
+        }
+
+        let mut hir = crate::lower(&program, &resolutions, &types, &mut sink);
+        self.plugins.lowered(&mut hir, &mut sink);
+        if sink.has_errors() {
+            return Err(report(&sink));
+        }
+
+        let result = std::thread::scope(|scope| {
+            std::thread::Builder::new()
+                .stack_size(interp::STACK_SIZE)
+                .spawn_scoped(scope, || {
+                    Interpreter::new(&hir)
+                        .with_args(self.args.clone())
+                        .with_hosts(&self.hosts)
+                        .run()
+                })
+                .expect("failed to start the interpreter")
+                .join()
+                .expect("the interpreter panicked")
+        });
+
+        result.map_err(|error| {
+            let mut sink = DiagnosticSink::default();
+            sink.push(error);
+            report(&sink)
+        })
+    }
+}
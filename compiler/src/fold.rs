@@ -0,0 +1,106 @@
+use ez_lexer::{Lexer, Span, Token, TokenKind};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FoldKind {
+    // what's between brackets, like the body of a function or a list
+    Region,
+    // comments on lines that follow each other
+    Comment,
+    // `use`s that follow each other
+    Imports,
+}
+
+// a part of a file an editor can collapse, it spans more than one line
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Fold {
+    // a region is from its opening bracket to its closing one
+    pub span: Span,
+    pub kind: FoldKind,
+}
+
+// the parts of the file that can be folded, in the order they start. They're
+// found from the tokens rather than the tree, so a file that doesn't parse, like
+// one being typed, still folds
+pub fn folds(source: &str) -> Vec<Fold> {
+    let multiline = |span: Span| source[span.start..span.end].contains('\n');
+    let tokens: Vec<Token> = Lexer::new(source).collect();
+    let mut folds = vec![];
+
+    let mut open: Vec<&Token> = vec![];
+    for token in &tokens {
+        match token.kind {
+            TokenKind::LeftCurly | TokenKind::LeftBracket | TokenKind::LeftParen => {
+                open.push(token)
+            }
+            TokenKind::RightCurly | TokenKind::RightBracket | TokenKind::RightParen => {
+                // a bracket closing nothing is left to the parser to report
+                if let Some(left) = open.pop() {
+                    let span = left.span.to(token.span);
+                    if multiline(span) {
+                        folds.push(Fold {
+                            span,
+                            kind: FoldKind::Region,
+                        });
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // comments are in a group when there's only whitespace between them, and
+    // a single line break
+    let mut comments = Lexer::new(source).comments().into_iter();
+    if let Some(first) = comments.next() {
+        let mut group = first;
+        for comment in comments {
+            let between = &source[group.end..comment.start];
+            if between.trim().is_empty() && between.matches('\n').count() == 1 {
+                group = group.to(comment);
+                continue;
+            }
+            if multiline(group) {
+                folds.push(Fold {
+                    span: group,
+                    kind: FoldKind::Comment,
+                });
+            }
+            group = comment;
+        }
+        if multiline(group) {
+            folds.push(Fold {
+                span: group,
+                kind: FoldKind::Comment,
+            });
+        }
+    }
+
+    // a `use` goes up to its `;`, the next one has to come right after it
+    let mut imports: Option<Span> = None;
+    let mut tokens = tokens.iter().peekable();
+    while let Some(token) = tokens.next() {
+        if token.kind != TokenKind::Use {
+            continue;
+        }
+        let Some(end) = tokens.by_ref().find(|token| token.kind == TokenKind::Semi) else {
+            break;
+        };
+        let span = token.span.to(end.span);
+        imports = Some(imports.map_or(span, |imports| imports.to(span)));
+
+        if !tokens
+            .peek()
+            .is_some_and(|next| next.kind == TokenKind::Use)
+        {
+            if let Some(span) = imports.take().filter(|span| multiline(*span)) {
+                folds.push(Fold {
+                    span,
+                    kind: FoldKind::Imports,
+                });
+            }
+        }
+    }
+
+    folds.sort_by_key(|fold| fold.span.start);
+    folds
+}
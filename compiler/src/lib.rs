@@ -0,0 +1,110 @@
+// the compiler as a library, `main.rs` is the command line on top of it and
+// `Engine` runs programs from other Rust programs. The stages are usable on
+// their own, and the types they hand each other are at the root:
+//
+//     let sources = ez::SourceMap::single("main.ez", source);
+//     let arena = ez::Arena::new();
+//     let program = ez::Parser::new(source, &arena).parse_program()?;
+//     let mut sink = ez::DiagnosticSink::default();
+//     if let Some((resolutions, types)) = ez::check(&program, &[], &mut sink) {
+//         let hir = ez::lower(&program, &resolutions, &types, &mut sink);
+//         let script = ez::JsBackend::new(&hir, &sources).compile();
+//     }
+//
+// `Lexer` turns a file into tokens and `Parser` into a `Program`, with its
+// nodes in an `Arena`, `Session` does it for a file and the ones it uses.
+// `check` resolves the names and types of a program and runs every check,
+// `lower` turns a program that passed into the HIR, which `Interpreter` runs
+// and the backends compile, and `ssa::build` into the SSA form `optimize`
+// works on and `bytecode::compile` turns into instructions on registers,
+// which `peephole` rewrites and `ezb` writes as bytes, with what `escape`
+// finds doesn't outlive its function made in its frame. `query::Database`
+// runs them as queries that keep their results across changes, for the
+// language server, and `complete` finds what can be typed at a position for
+// it and the signature of the call it's in, `outline` what a file declares
+// and `fold` the parts of it that can be collapsed. `capi` has the front of
+// the compiler for C, and `parser::tree_sitter` the grammar of the language
+// for editors. `playground` checks and runs a program given as text in a
+// sandbox, which `serve` does over HTTP. The modules have the rest of each
+// stage, like its errors
+
+pub mod bench;
+pub mod cache;
+pub mod capi;
+pub mod complete;
+pub mod doc;
+mod engine;
+pub mod fold;
+pub mod lsp;
+pub mod manifest;
+pub mod outline;
+pub mod package;
+pub mod playground;
+pub mod plugin;
+pub mod query;
+pub mod repl;
+pub mod serve;
+pub mod session;
+pub mod watch;
+
+use std::sync::Arc;
+
+// the stages are crates of their own, they're here under the names they had
+// when they were modules of this one
+pub use ez_ast as ast;
+pub use ez_check::{
+    cfg, dataflow, escape, hir, json, lint, log, optimize, profile, resolver, rules, semantic, ssa,
+    typecheck,
+};
+#[cfg(feature = "llvm")]
+pub use ez_codegen::llvm;
+pub use ez_codegen::{bytecode, c, ezb, gc, host, interp, js, link, peephole, vm, wasm};
+pub use ez_diagnostics as diagnostics;
+pub use ez_diagnostics::render;
+pub use ez_lexer as lexer;
+pub use ez_parser as parser;
+pub use ez_parser::formatter;
+
+pub use ez_ast::{Arena, BaseType, Expr, Identifier, Program, ValueExpr};
+pub use ez_check::hir::Hir;
+pub use ez_check::resolver::{Resolutions, Resolver};
+pub use ez_check::typecheck::{TypeChecker, Types};
+pub use ez_check::{lower, lower_timed};
+pub use ez_codegen::c::CBackend;
+pub use ez_codegen::host::{EzType, FromValue, HostFunction, IntoHostFunction, IntoValue};
+pub use ez_codegen::interp::{Interpreter, RuntimeError};
+pub use ez_codegen::js::JsBackend;
+pub use ez_codegen::wasm::WasmBackend;
+pub use ez_diagnostics::{Diagnostic, DiagnosticSink, FileId, Location, SourceMap};
+pub use ez_lexer::{Lexer, Span, Token, TokenKind};
+pub use ez_parser::{ParseError, Parser};
+
+pub use crate::engine::{Engine, EngineError};
+pub use crate::session::Session;
+
+use ez_check::profile::Passes;
+
+// runs every check on the program, the results of resolution and type checking
+// are returned when they succeeded, for lowering. `hosts` are the functions an
+// embedding program gave it
+pub fn check(
+    program: &Program,
+    hosts: &[Arc<HostFunction>],
+    sink: &mut DiagnosticSink,
+) -> Option<(Resolutions, Types)> {
+    check_timed(program, hosts, sink, &mut Passes::new())
+}
+
+// `check`, with the time and memory of each of its passes recorded
+pub fn check_timed(
+    program: &Program,
+    hosts: &[Arc<HostFunction>],
+    sink: &mut DiagnosticSink,
+    passes: &mut Passes,
+) -> Option<(Resolutions, Types)> {
+    let hosts: Vec<(String, BaseType)> = hosts
+        .iter()
+        .map(|host| (host.name.clone(), host.basetype()))
+        .collect();
+    ez_check::check_timed(program, &hosts, sink, passes)
+}
@@ -0,0 +1,925 @@
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+use ez_ast::Arena;
+use ez_check::json::Json;
+use ez_check::resolver::{Access, DefKind};
+use ez_check::semantic::{self, Modifier, Role};
+use ez_diagnostics::{Diagnostic, LintLevels, NoteKind, Severity};
+use ez_diagnostics::{FileId, Position, SourceFile, SourceMap};
+use ez_lexer::Span;
+use ez_parser::ParserOptions;
+
+use crate::complete::{self, CompletionKind};
+use crate::fold::{self, FoldKind};
+use crate::manifest::Manifest;
+use crate::outline::{self, Symbol, SymbolKind};
+use crate::package::{self, Package};
+use crate::query::{Analysis, Database};
+
+// the errors of JSON-RPC the server answers with
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+
+// the kinds of symbols, of highlights and of text document sync, as the
+// protocol numbers them
+const FULL_SYNC: i64 = 1;
+const SYMBOL_MODULE: i64 = 2;
+const SYMBOL_ENUM: i64 = 10;
+const SYMBOL_FUNCTION: i64 = 12;
+const SYMBOL_VARIABLE: i64 = 13;
+const SYMBOL_CONSTANT: i64 = 14;
+const SYMBOL_ENUM_MEMBER: i64 = 22;
+const HIGHLIGHT_READ: i64 = 2;
+const HIGHLIGHT_WRITE: i64 = 3;
+
+// the kinds of completions, the protocol has none for types, classes are the
+// closest
+fn completion_kind(kind: CompletionKind) -> i64 {
+    match kind {
+        CompletionKind::Method => 2,
+        CompletionKind::Function => 3,
+        CompletionKind::Variable | CompletionKind::Parameter => 6,
+        CompletionKind::Type => 7,
+        CompletionKind::Module => 9,
+        CompletionKind::Enum => 13,
+        CompletionKind::Keyword => 14,
+        CompletionKind::Variant => 20,
+        CompletionKind::Constant => 21,
+    }
+}
+
+fn object(entries: Vec<(&str, Json)>) -> Json {
+    Json::Object(
+        entries
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), value))
+            .collect(),
+    )
+}
+
+fn string(value: impl Into<String>) -> Json {
+    Json::String(value.into())
+}
+
+fn number(value: i64) -> Json {
+    Json::Number(value as f64)
+}
+
+// `file:///a%20b/c.ez` is `/a b/c.ez`
+fn uri_path(uri: &str) -> PathBuf {
+    let path = uri.strip_prefix("file://").unwrap_or(uri);
+    let bytes = path.as_bytes();
+    let mut decoded = vec![];
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = path
+            .get(i + 1..i + 3)
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+
+    PathBuf::from(String::from_utf8_lossy(&decoded).into_owned())
+}
+
+fn path_uri(path: &Path) -> String {
+    let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let mut uri = "file://".to_string();
+    for byte in path.to_string_lossy().bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'_' | b'.' | b'~' => {
+                uri.push(byte as char)
+            }
+            byte => uri.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    uri
+}
+
+fn position(file: &SourceFile, offset: usize) -> Json {
+    let position = file.position(offset);
+    object(vec![
+        ("line", number(position.line as i64)),
+        ("character", number(position.character as i64)),
+    ])
+}
+
+// the spans are of the file, not of the map it's in
+fn range(file: &SourceFile, span: Span) -> Json {
+    object(vec![
+        ("start", position(file, span.start)),
+        ("end", position(file, span.end)),
+    ])
+}
+
+// the byte offset of a position in the file
+fn offset(file: &SourceFile, position: &Json) -> Option<usize> {
+    file.offset_at(Position {
+        line: position.get("line")?.as_number()? as usize,
+        character: position.get("character")?.as_number()? as usize,
+    })
+}
+
+// the lint levels, parser options and dependencies of the project the file is
+// in, if any
+fn project(path: &Path) -> (LintLevels, ParserOptions, Vec<Package>) {
+    let mut levels = LintLevels::default();
+
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let Some(manifest) =
+        Manifest::find(dir).and_then(|found| Manifest::load(&dir.join(found)).ok())
+    else {
+        return (levels, ParserOptions::default(), vec![]);
+    };
+
+    for (lint, level) in &manifest.lints {
+        match *lint {
+            "warnings" => levels.set_all(*level),
+            lint => levels.set(lint, *level),
+        }
+    }
+    let packages = package::resolve(&manifest).unwrap_or_default();
+    (levels, manifest.options, packages)
+}
+
+// the diagnostics of a file, as the protocol has them. The other labels are
+// the information related to it, like where something was declared
+fn diagnostic(diagnostic: &Diagnostic, file: &SourceFile, uri: &str) -> Json {
+    let label = diagnostic
+        .spans
+        .iter()
+        .find(|label| label.primary)
+        .or(diagnostic.spans.first());
+    let end = file.source.len();
+    let span = label.map_or(Span::new(end, end), |label| label.span);
+
+    let mut message = diagnostic.message.clone();
+    if let Some(label) = label.filter(|label| !label.message.is_empty()) {
+        message.push_str(&format!(": {}", label.message));
+    }
+    for note in &diagnostic.notes {
+        let kind = match note.kind {
+            NoteKind::Note => "note",
+            NoteKind::Help => "help",
+        };
+        message.push_str(&format!("\n{}: {}", kind, note.message));
+    }
+
+    let severity = match diagnostic.severity {
+        Severity::Error => 1,
+        Severity::Warning => 2,
+    };
+
+    let related = diagnostic
+        .spans
+        .iter()
+        .filter(|other| !label.is_some_and(|label| std::ptr::eq(label, *other)))
+        .map(|other| {
+            object(vec![
+                (
+                    "location",
+                    object(vec![
+                        ("uri", string(uri)),
+                        ("range", range(file, other.span)),
+                    ]),
+                ),
+                ("message", string(&other.message)),
+            ])
+        })
+        .collect();
+
+    object(vec![
+        ("range", range(file, span)),
+        ("severity", number(severity)),
+        ("code", string(diagnostic.code)),
+        ("source", string("ez")),
+        ("message", string(message)),
+        ("relatedInformation", Json::List(related)),
+    ])
+}
+
+// the suggestions of the diagnostic, each an edit of the document that fixes it
+fn quick_fixes(diagnostic: &Diagnostic, file: &SourceFile, uri: &str) -> Vec<Json> {
+    diagnostic
+        .suggestions()
+        .map(|(message, suggestion)| {
+            let edit = object(vec![
+                ("range", range(file, suggestion.span)),
+                ("newText", string(&suggestion.replacement)),
+            ]);
+            object(vec![
+                ("title", string(message)),
+                ("kind", string("quickfix")),
+                (
+                    "diagnostics",
+                    Json::List(vec![self::diagnostic(diagnostic, file, uri)]),
+                ),
+                (
+                    "edit",
+                    object(vec![(
+                        "changes",
+                        object(vec![(uri, Json::List(vec![edit]))]),
+                    )]),
+                ),
+            ])
+        })
+        .collect()
+}
+
+// the outline of the document, the declarations nested in others are their
+// children. Tests are shown as functions, the protocol has no kind for them
+fn symbols(declared: &[Symbol], file: &SourceFile) -> Vec<Json> {
+    declared
+        .iter()
+        .map(|symbol| {
+            let kind = match symbol.kind {
+                SymbolKind::Module => SYMBOL_MODULE,
+                SymbolKind::Function | SymbolKind::Test => SYMBOL_FUNCTION,
+                SymbolKind::Constant => SYMBOL_CONSTANT,
+                SymbolKind::Variable => SYMBOL_VARIABLE,
+                SymbolKind::Enum => SYMBOL_ENUM,
+                SymbolKind::Variant => SYMBOL_ENUM_MEMBER,
+            };
+            object(vec![
+                ("name", string(&symbol.name)),
+                ("kind", number(kind)),
+                ("range", range(file, symbol.span)),
+                ("selectionRange", range(file, symbol.selection)),
+                ("children", Json::List(symbols(&symbol.children, file))),
+            ])
+        })
+        .collect()
+}
+
+// the lines of the document that can be collapsed. The line a region's closing
+// bracket is on stays, the ones of comments and imports all go but the first
+fn folding_ranges(file: &SourceFile) -> Vec<Json> {
+    fold::folds(&file.source)
+        .into_iter()
+        .filter_map(|fold| {
+            let start = file.position(fold.span.start).line;
+            let end = file.position(fold.span.end).line;
+            let (end, kind) = match fold.kind {
+                FoldKind::Region => (end - 1, "region"),
+                FoldKind::Comment => (end, "comment"),
+                FoldKind::Imports => (end, "imports"),
+            };
+            (end > start).then(|| {
+                object(vec![
+                    ("startLine", number(start as i64)),
+                    ("endLine", number(end as i64)),
+                    ("kind", string(kind)),
+                ])
+            })
+        })
+        .collect()
+}
+
+// an editor's view of ez, over stdin and stdout. What's found compiling a
+// document is kept until it changes, or a file it uses does, the files that
+// aren't open are read from disk
+#[derive(Default)]
+pub struct Server {
+    // the text of the open documents, by uri
+    documents: HashMap<String, String>,
+    database: Database,
+    // the files each document has diagnostics in, they're cleared when the
+    // diagnostics are gone
+    published: HashMap<String, Vec<String>>,
+    shutdown: bool,
+}
+
+impl Server {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // the messages start with their length, `None` once the input ends
+    fn read(input: &mut impl BufRead) -> io::Result<Option<String>> {
+        let mut length = None;
+        loop {
+            let mut header = String::new();
+            if input.read_line(&mut header)? == 0 {
+                return Ok(None);
+            }
+
+            let header = header.trim_end();
+            if header.is_empty() {
+                break;
+            }
+            if let Some(value) = header.strip_prefix("Content-Length:") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+
+        let Some(length) = length else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "a message has no Content-Length",
+            ));
+        };
+        let mut body = vec![0; length];
+        input.read_exact(&mut body)?;
+        Ok(Some(String::from_utf8_lossy(&body).into_owned()))
+    }
+
+    fn send(output: &mut impl Write, message: Json) -> io::Result<()> {
+        let text = message.to_string();
+        write!(output, "Content-Length: {}\r\n\r\n{}", text.len(), text)?;
+        output.flush()
+    }
+
+    fn response(id: Json, result: Result<Json, (i64, String)>) -> Json {
+        let mut entries = vec![("jsonrpc", string("2.0")), ("id", id)];
+        match result {
+            Ok(result) => entries.push(("result", result)),
+            Err((code, message)) => entries.push((
+                "error",
+                object(vec![("code", number(code)), ("message", string(message))]),
+            )),
+        }
+        object(entries)
+    }
+
+    fn notification(method: &str, params: Json) -> Json {
+        object(vec![
+            ("jsonrpc", string("2.0")),
+            ("method", string(method)),
+            ("params", params),
+        ])
+    }
+
+    // returns once the client says to exit, or closes the input
+    pub fn run(mut self, mut input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+        while let Some(text) = Self::read(&mut input)? {
+            let message = match Json::parse(&text) {
+                Ok(message) => message,
+                Err(error) => {
+                    let error = Err((PARSE_ERROR, error));
+                    Self::send(&mut output, Self::response(Json::Null, error))?;
+                    continue;
+                }
+            };
+
+            let method = message
+                .get("method")
+                .and_then(Json::as_str)
+                .unwrap_or_default();
+            if method == "exit" {
+                return Ok(());
+            }
+
+            let params = message.get("params").unwrap_or(&Json::Null);
+            let notifications = self.notify(method, params);
+            for notification in notifications {
+                Self::send(&mut output, notification)?;
+            }
+
+            // requests have an id, notifications don't get an answer
+            if let Some(id) = message.get("id") {
+                let id = id.clone();
+                let result = self.request(method, params);
+                Self::send(&mut output, Self::response(id, result))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // what the client says about documents, answered with their diagnostics
+    fn notify(&mut self, method: &str, params: &Json) -> Vec<Json> {
+        let document = params.get("textDocument");
+        let Some(uri) = document
+            .and_then(|document| document.get("uri"))
+            .and_then(Json::as_str)
+        else {
+            return vec![];
+        };
+        let uri = uri.to_string();
+
+        match method {
+            "textDocument/didOpen" => {
+                let text = document.and_then(|document| document.get("text"));
+                let text = text.and_then(Json::as_str).unwrap_or_default();
+                self.documents.insert(uri.clone(), text.to_string());
+                self.database.open(&uri_path(&uri), text.to_string());
+            }
+            // the whole text is sent, the last change has it
+            "textDocument/didChange" => {
+                let change = match params.get("contentChanges") {
+                    Some(Json::List(changes)) => changes.last(),
+                    _ => None,
+                };
+                if let Some(text) = change
+                    .and_then(|change| change.get("text"))
+                    .and_then(Json::as_str)
+                {
+                    self.documents.insert(uri.clone(), text.to_string());
+                    self.database.open(&uri_path(&uri), text.to_string());
+                }
+            }
+            // the files it uses could have been saved too
+            "textDocument/didSave" => self.database.reload(),
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+                self.database.close(&uri_path(&uri));
+                let files = self.published.remove(&uri).unwrap_or_default();
+                return files
+                    .iter()
+                    .map(|file| Self::publish(file, vec![]))
+                    .collect();
+            }
+            _ => return vec![],
+        }
+
+        self.diagnose(&uri)
+    }
+
+    fn publish(uri: &str, diagnostics: Vec<Json>) -> Json {
+        Self::notification(
+            "textDocument/publishDiagnostics",
+            object(vec![
+                ("uri", string(uri)),
+                ("diagnostics", Json::List(diagnostics)),
+            ]),
+        )
+    }
+
+    // the diagnostics go to the file they're in, which can be one the
+    // document uses
+    fn diagnose(&mut self, uri: &str) -> Vec<Json> {
+        let Some(analysis) = self.analyze(uri) else {
+            return vec![];
+        };
+
+        let mut files: Vec<(String, Vec<Json>)> = vec![(uri.to_string(), vec![])];
+        for found in &analysis.checked.diagnostics {
+            let (localized, file) = analysis.sources.localize(found);
+            let file_uri = match file.id {
+                FileId::ENTRY => uri.to_string(),
+                _ => path_uri(Path::new(&file.name)),
+            };
+
+            let json = diagnostic(&localized, file, &file_uri);
+            match files.iter_mut().find(|(name, _)| *name == file_uri) {
+                Some((_, diagnostics)) => diagnostics.push(json),
+                None => files.push((file_uri, vec![json])),
+            }
+        }
+
+        let before = self.published.remove(uri).unwrap_or_default();
+        let mut notifications: Vec<Json> = before
+            .iter()
+            .filter(|file| !files.iter().any(|(name, _)| name == *file))
+            .map(|file| Self::publish(file, vec![]))
+            .collect();
+
+        self.published.insert(
+            uri.to_string(),
+            files.iter().map(|(name, _)| name.clone()).collect(),
+        );
+        notifications.extend(
+            files
+                .into_iter()
+                .map(|(name, diagnostics)| Self::publish(&name, diagnostics)),
+        );
+        notifications
+    }
+
+    fn analyze(&mut self, uri: &str) -> Option<Analysis> {
+        if !self.documents.contains_key(uri) {
+            return None;
+        }
+
+        let path = uri_path(uri);
+        let (levels, options, packages) = project(&path);
+        self.database.check(&path, &levels, &options, &packages)
+    }
+
+    fn request(&mut self, method: &str, params: &Json) -> Result<Json, (i64, String)> {
+        if self.shutdown {
+            return Err((INVALID_REQUEST, "the server is shutting down".into()));
+        }
+
+        let uri = params
+            .get("textDocument")
+            .and_then(|document| document.get("uri"))
+            .and_then(Json::as_str)
+            .unwrap_or_default();
+
+        match method {
+            "initialize" => Ok(object(vec![
+                (
+                    "capabilities",
+                    object(vec![
+                        (
+                            "textDocumentSync",
+                            object(vec![
+                                ("openClose", Json::Bool(true)),
+                                ("change", number(FULL_SYNC)),
+                            ]),
+                        ),
+                        ("documentSymbolProvider", Json::Bool(true)),
+                        ("hoverProvider", Json::Bool(true)),
+                        ("definitionProvider", Json::Bool(true)),
+                        ("referencesProvider", Json::Bool(true)),
+                        ("documentHighlightProvider", Json::Bool(true)),
+                        ("foldingRangeProvider", Json::Bool(true)),
+                        ("codeActionProvider", Json::Bool(true)),
+                        (
+                            "completionProvider",
+                            object(vec![("triggerCharacters", Json::List(vec![string(".")]))]),
+                        ),
+                        (
+                            "signatureHelpProvider",
+                            object(vec![(
+                                "triggerCharacters",
+                                Json::List(vec![string("("), string(",")]),
+                            )]),
+                        ),
+                        (
+                            "semanticTokensProvider",
+                            object(vec![
+                                (
+                                    "legend",
+                                    object(vec![
+                                        (
+                                            "tokenTypes",
+                                            Json::List(
+                                                Role::ALL.map(|role| string(role.name())).into(),
+                                            ),
+                                        ),
+                                        (
+                                            "tokenModifiers",
+                                            Json::List(
+                                                Modifier::ALL
+                                                    .map(|modifier| string(modifier.name()))
+                                                    .into(),
+                                            ),
+                                        ),
+                                    ]),
+                                ),
+                                ("full", Json::Bool(true)),
+                            ]),
+                        ),
+                    ]),
+                ),
+                (
+                    "serverInfo",
+                    object(vec![
+                        ("name", string("ez")),
+                        ("version", string(env!("CARGO_PKG_VERSION"))),
+                    ]),
+                ),
+            ])),
+
+            "shutdown" => {
+                self.shutdown = true;
+                Ok(Json::Null)
+            }
+
+            "textDocument/documentSymbol" => {
+                let Some(text) = self.documents.get(uri) else {
+                    return Ok(Json::Null);
+                };
+                let sources = SourceMap::single(uri, text.clone());
+                let arena = Arena::new();
+                let path = uri_path(uri);
+                let (_, options, _) = project(&path);
+                let symbols = match self.database.parse(&path, &options, &arena) {
+                    Some(program) => {
+                        symbols(&outline::outline(&program), sources.get(FileId::ENTRY))
+                    }
+                    None => vec![],
+                };
+                Ok(Json::List(symbols))
+            }
+
+            "textDocument/foldingRange" => {
+                let Some(text) = self.documents.get(uri) else {
+                    return Ok(Json::Null);
+                };
+                let sources = SourceMap::single(uri, text.clone());
+                Ok(Json::List(folding_ranges(sources.get(FileId::ENTRY))))
+            }
+
+            "textDocument/hover" => Ok(self.hover(uri, params).unwrap_or(Json::Null)),
+
+            "textDocument/definition" => Ok(self.definition(uri, params).unwrap_or(Json::Null)),
+
+            "textDocument/references" => Ok(self.references(uri, params).unwrap_or(Json::Null)),
+
+            "textDocument/documentHighlight" => {
+                Ok(self.highlights(uri, params).unwrap_or(Json::Null))
+            }
+
+            "textDocument/codeAction" => Ok(self.code_actions(uri, params).unwrap_or(Json::Null)),
+
+            "textDocument/completion" => Ok(self.completion(uri, params).unwrap_or(Json::Null)),
+
+            "textDocument/signatureHelp" => {
+                Ok(self.signature_help(uri, params).unwrap_or(Json::Null))
+            }
+
+            "textDocument/semanticTokens/full" => {
+                Ok(self.semantic_tokens(uri).unwrap_or(Json::Null))
+            }
+
+            method => Err((METHOD_NOT_FOUND, format!("`{}` isn't supported", method))),
+        }
+    }
+
+    // the kind, name and type of what's under the cursor, and its documentation,
+    // or the type of the value there
+    fn hover(&mut self, uri: &str, params: &Json) -> Option<Json> {
+        let analysis = self.analyze(uri)?;
+        let file = analysis.sources.get(FileId::ENTRY);
+        let offset = offset(file, params.get("position")?)?;
+        let found = analysis.type_at(file.start + offset)?;
+
+        let mut text = match (found.definition, &found.basetype) {
+            (Some(id), basetype) => {
+                let definition = analysis.checked.resolutions.as_ref()?.definition(id);
+                let kind = match definition.kind {
+                    DefKind::Variable if definition.mutable => "mut",
+                    DefKind::Variable => "variable",
+                    DefKind::Function => "function",
+                    DefKind::Param => "parameter",
+                    DefKind::Enum => "enum",
+                    DefKind::Builtin(_) => "builtin",
+                    DefKind::Module(_) | DefKind::Import(_) => "module",
+                    DefKind::Host => "host function",
+                };
+                match basetype {
+                    Some(basetype) => format!("{} {}: {}", kind, definition.name, basetype),
+                    None => format!("{} {}", kind, definition.name),
+                }
+            }
+            (None, Some(basetype)) => basetype.to_string(),
+            (None, None) => return None,
+        };
+        text = format!("```ez\n{}\n```", text);
+        if !found.docs.is_empty() {
+            text = format!("{}\n\n{}", text, found.docs);
+        }
+
+        let span = Span::new(found.span.start - file.start, found.span.end - file.start);
+        Some(object(vec![
+            (
+                "contents",
+                object(vec![("kind", string("markdown")), ("value", string(text))]),
+            ),
+            ("range", range(file, span)),
+        ]))
+    }
+
+    // the document, the offset of the cursor in it, and the document checked
+    // with the offset the names in scope at the cursor are found at. A document
+    // that doesn't check, like one with a name or a call half typed, is checked
+    // again without the line of the cursor to find them, and then put back
+    fn analyze_around(
+        &mut self,
+        uri: &str,
+        params: &Json,
+    ) -> Option<(String, usize, Analysis, usize)> {
+        let text = self.documents.get(uri)?.clone();
+        let mut analysis = self.analyze(uri)?;
+        let offset = offset(analysis.sources.get(FileId::ENTRY), params.get("position")?)?;
+        let mut scope = offset;
+        if analysis.checked.resolutions.is_none() {
+            let path = uri_path(uri);
+            let (without, start) = complete::without_line(&text, offset);
+            self.database.open(&path, without);
+            if let Some(checked) = self.analyze(uri) {
+                analysis = checked;
+                scope = start;
+            }
+            self.database.open(&path, text.clone());
+        }
+        Some((text, offset, analysis, scope))
+    }
+
+    // what can be typed at the cursor
+    fn completion(&mut self, uri: &str, params: &Json) -> Option<Json> {
+        let (text, offset, analysis, scope) = self.analyze_around(uri, params)?;
+        let items = complete::complete(&analysis, &text, offset, scope)
+            .into_iter()
+            .map(|completion| {
+                let mut item = vec![
+                    ("label", string(completion.label)),
+                    ("kind", number(completion_kind(completion.kind))),
+                ];
+                if let Some(detail) = completion.detail {
+                    item.push(("detail", string(detail)));
+                }
+                object(item)
+            })
+            .collect();
+        Some(Json::List(items))
+    }
+
+    // the signature of the call the cursor is in the arguments of, with the
+    // parameter of the argument it's in
+    fn signature_help(&mut self, uri: &str, params: &Json) -> Option<Json> {
+        let (text, offset, analysis, scope) = self.analyze_around(uri, params)?;
+        let signature = complete::signature(&analysis, &text, offset, scope)?;
+
+        let parameters = signature
+            .parameters
+            .iter()
+            .map(|(start, end)| {
+                let label = vec![number(*start as i64), number(*end as i64)];
+                object(vec![("label", Json::List(label))])
+            })
+            .collect();
+        let mut information = vec![
+            ("label", string(signature.label)),
+            ("parameters", Json::List(parameters)),
+        ];
+        if !signature.docs.is_empty() {
+            information.push((
+                "documentation",
+                object(vec![
+                    ("kind", string("markdown")),
+                    ("value", string(signature.docs)),
+                ]),
+            ));
+        }
+
+        Some(object(vec![
+            ("signatures", Json::List(vec![object(information)])),
+            ("activeSignature", number(0)),
+            ("activeParameter", number(signature.active as i64)),
+        ]))
+    }
+
+    // the quick fixes of the diagnostics of the document in the range
+    fn code_actions(&mut self, uri: &str, params: &Json) -> Option<Json> {
+        let analysis = self.analyze(uri)?;
+        let entry = analysis.sources.get(FileId::ENTRY);
+        let range = params.get("range")?;
+        let start = offset(entry, range.get("start")?)?;
+        let end = offset(entry, range.get("end")?)?;
+
+        let mut actions = vec![];
+        for found in &analysis.checked.diagnostics {
+            let (localized, file) = analysis.sources.localize(found);
+            let span = localized
+                .spans
+                .iter()
+                .find(|label| label.primary)
+                .map_or(Span::default(), |label| label.span);
+            if file.id == FileId::ENTRY && span.start <= end && start <= span.end {
+                actions.extend(quick_fixes(&localized, file, uri));
+            }
+        }
+        Some(Json::List(actions))
+    }
+
+    // the tokens of the document by what they are, each as five numbers: its
+    // line and start from the ones of the token before it, its length, its role
+    // and its modifiers. A token over more than one line, like a string, is
+    // given line by line, editors can't take it whole
+    fn semantic_tokens(&mut self, uri: &str) -> Option<Json> {
+        let analysis = self.analyze(uri)?;
+        let file = analysis.sources.get(FileId::ENTRY);
+        let resolutions = analysis.checked.resolutions.as_ref();
+
+        let mut data = vec![];
+        let mut last = Position {
+            line: 0,
+            character: 0,
+        };
+        for token in semantic::classify(&file.source, file.start, resolutions) {
+            let start = token.span.start - file.start;
+            let text = &file.source[start..token.span.end - file.start];
+            let first = file.position(start);
+            for (index, piece) in text.split('\n').enumerate() {
+                let position = Position {
+                    line: first.line + index,
+                    character: if index == 0 { first.character } else { 0 },
+                };
+                let length: usize = piece
+                    .trim_end_matches('\r')
+                    .chars()
+                    .map(char::len_utf16)
+                    .sum();
+                if length == 0 {
+                    continue;
+                }
+
+                let character = if position.line == last.line {
+                    position.character - last.character
+                } else {
+                    position.character
+                };
+                data.extend([
+                    position.line - last.line,
+                    character,
+                    length,
+                    token.role.index(),
+                    token.modifiers as usize,
+                ]);
+                last = position;
+            }
+        }
+
+        Some(object(vec![(
+            "data",
+            Json::List(data.into_iter().map(|n| number(n as i64)).collect()),
+        )]))
+    }
+
+    // where what's under the cursor is declared, in whichever file it is
+    // every identifier naming what the one at the cursor does, in the files of
+    // the program, the declarations too unless the editor says otherwise
+    fn references(&mut self, uri: &str, params: &Json) -> Option<Json> {
+        let analysis = self.analyze(uri)?;
+        let file = analysis.sources.get(FileId::ENTRY);
+        let offset = offset(file, params.get("position")?)?;
+        let declarations = params
+            .get("context")
+            .and_then(|context| context.get("includeDeclaration"))
+            .and_then(Json::as_bool)
+            .unwrap_or(true);
+
+        let locations = analysis
+            .references(file.start + offset)
+            .into_iter()
+            .filter(|reference| declarations || reference.access != Access::Declaration)
+            .map(|reference| {
+                let file = analysis.sources.file(reference.span.start);
+                let span = Span::new(
+                    reference.span.start - file.start,
+                    reference.span.end - file.start,
+                );
+                let file_uri = match file.id {
+                    FileId::ENTRY => uri.to_string(),
+                    _ => path_uri(Path::new(&file.name)),
+                };
+                object(vec![
+                    ("uri", string(file_uri)),
+                    ("range", range(file, span)),
+                ])
+            })
+            .collect();
+        Some(Json::List(locations))
+    }
+
+    // the references in the document to what's at the cursor, by whether they
+    // read or write it. A declaration gives it its value, it's a write
+    fn highlights(&mut self, uri: &str, params: &Json) -> Option<Json> {
+        let analysis = self.analyze(uri)?;
+        let file = analysis.sources.get(FileId::ENTRY);
+        let offset = offset(file, params.get("position")?)?;
+        let end = file.start + file.source.len();
+
+        let highlights = analysis
+            .references(file.start + offset)
+            .into_iter()
+            .filter(|reference| file.start <= reference.span.start && reference.span.end <= end)
+            .map(|reference| {
+                let kind = match reference.access {
+                    Access::Read => HIGHLIGHT_READ,
+                    Access::Write | Access::Declaration => HIGHLIGHT_WRITE,
+                };
+                let span = Span::new(
+                    reference.span.start - file.start,
+                    reference.span.end - file.start,
+                );
+                object(vec![("range", range(file, span)), ("kind", number(kind))])
+            })
+            .collect();
+        Some(Json::List(highlights))
+    }
+
+    fn definition(&mut self, uri: &str, params: &Json) -> Option<Json> {
+        let analysis = self.analyze(uri)?;
+        let file = analysis.sources.get(FileId::ENTRY);
+        let offset = offset(file, params.get("position")?)?;
+        let definition = analysis.definition(file.start + offset)?;
+
+        let file = analysis.sources.file(definition.start);
+        let span = Span::new(definition.start - file.start, definition.end - file.start);
+        let file_uri = match file.id {
+            FileId::ENTRY => uri.to_string(),
+            _ => path_uri(Path::new(&file.name)),
+        };
+
+        Some(object(vec![
+            ("uri", string(file_uri)),
+            ("range", range(file, span)),
+        ]))
+    }
+}
@@ -1,29 +1,1987 @@
-mod lexer;
-mod parser;
+use ez::ast::Arena;
+use ez::bench::{self, Summary};
+use ez::c::CBackend;
+use ez::cache::Cache;
+use ez::diagnostics::{self, Diagnostic, DiagnosticSink, Level, LintLevels, Severity, Suggestion};
+use ez::doc::{DocFormat, Documenter};
+use ez::formatter;
+use ez::hir::Hir;
+use ez::interp::{self, Interpreter};
+use ez::js::JsBackend;
+use ez::lexer::{Lexer, Span};
+use ez::link::{self, Scratch};
+use ez::lint::LINTS;
 
-use crate::parser::{ParseError, Parser};
+#[cfg(feature = "llvm")]
+use ez::llvm::{self, LlvmBackend};
+use ez::log::LogLevel;
+use ez::lsp::Server;
+use ez::manifest::{self, Manifest};
+use ez::outline;
+use ez::package;
+use ez::parser::{tree_sitter, Edition, Feature, ParserOptions};
+use ez::playground::Limits;
+use ez::profile::{Counting, Passes};
+use ez::render::{ColorChoice, Renderer};
+use ez::repl::Repl;
+use ez::resolver::{Access, Resolutions};
+use ez::rules::Registry;
+use ez::serve;
+use ez::session::{self, Session};
+use ez::wasm::WasmBackend;
+use ez::watch::Watcher;
+use ez::SourceMap;
+use ez::{bytecode, cfg, dataflow, ezb, optimize, peephole, ssa};
+use std::collections::BTreeMap;
+use std::fmt::Write;
 use std::fs;
+use std::io::{self, IsTerminal, Read};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
 
-fn main() {
-    let content = fs::read_to_string("examples/basic.ez").expect("failed to read file");
-    println!("source:");
-    println!("{}", content);
-    println!();
+// counts what the compiler allocates, for the memory `--time-passes` shows
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+#[derive(PartialEq)]
+enum Mode {
+    // only report what's wrong with the program, or emit one of its
+    // representations
+    Check,
+    Run,
+    // read programs from the terminal and run them as they're typed
+    Repl,
+    // compile the program for the target
+    Build,
+    // print the files back the canonical way
+    Format,
+    // check the program along with the style rules
+    Lint,
+    // answer an editor over stdin and stdout
+    Lsp,
+    // write the documentation of the program and the files it uses
+    Doc,
+    // run the tests of the program and the files it uses
+    Test,
+    // time the functions marked `@bench`
+    Bench,
+    // check and run the programs sent over HTTP, for a playground
+    Serve,
+    // write the grammar of the language for tree-sitter
+    Grammar,
+    // list where a name is declared, read and written
+    Refs,
+    // print the bytecode of `.ezb` files for people to read
+    Disasm,
+}
+
+#[derive(PartialEq)]
+enum Target {
+    // an executable for the machine the compiler runs on, built with llvm when
+    // ez has the feature and through the C backend otherwise
+    Native,
+    Wasm,
+    // C source to compile with the system's C compiler
+    C,
+    Js,
+}
+
+// the representations of the program the compiler can write out instead of
+// doing what the mode says
+#[derive(PartialEq, Clone, Copy)]
+enum Emit {
+    Tokens,
+    Ast,
+    // along with the control-flow graphs of the functions
+    Hir,
+    // static single assignment form, with blocks of instructions in place of
+    // nested values
+    Ssa,
+    // the instructions on registers the SSA form compiles to
+    Bytecode,
+    // llvm's, only when ez has the feature
+    Ir,
+    // of the machine the compiler runs on, through llvm or the C compiler
+    Asm,
+    // the declarations of the file, nested in the ones they're in
+    Outline,
+}
+
+impl Target {
+    fn parse(name: &str) -> Option<Target> {
+        match name {
+            "native" => Some(Target::Native),
+            "wasm" => Some(Target::Wasm),
+            "c" => Some(Target::C),
+            "js" => Some(Target::Js),
+            _ => None,
+        }
+    }
+
+    // of the file written when no output is given, next to the source file
+    fn extension(&self) -> &'static str {
+        match self {
+            Target::Native => "",
+            Target::Wasm => "wasm",
+            Target::C => "c",
+            Target::Js => "js",
+        }
+    }
+}
+
+impl Emit {
+    // of the file written in the output directory
+    fn extension(&self) -> &'static str {
+        match self {
+            Emit::Tokens => "tokens",
+            Emit::Ast => "ast",
+            Emit::Hir => "hir",
+            Emit::Ssa => "ssa",
+            Emit::Bytecode => "ezb",
+            Emit::Ir => "ll",
+            Emit::Asm => "s",
+            Emit::Outline => "outline",
+        }
+    }
+}
+
+// what went wrong, the process exits with its code so scripts can tell. A
+// panic of the compiler itself exits with 101
+#[derive(Clone, Copy)]
+enum Failure {
+    // a file couldn't be read or written
+    Io,
+    // the command line didn't make sense
+    Usage,
+    // the program couldn't be lexed or parsed
+    Syntax,
+    // the program has type errors, or other errors the checks found
+    Check,
+    // the program stopped with an error while it ran
+    Runtime,
+    // the program passed the checks but couldn't be compiled or linked
+    Build,
+    // `fmt --check` found files that would change
+    Unformatted,
+    // `test` ran tests that failed
+    Tests,
+}
+
+impl Failure {
+    fn code(self) -> i32 {
+        match self {
+            Failure::Io => 1,
+            Failure::Usage => 2,
+            Failure::Syntax => 3,
+            Failure::Check => 4,
+            Failure::Runtime => 5,
+            Failure::Build => 6,
+            Failure::Unformatted => 7,
+            Failure::Tests => 8,
+        }
+    }
+}
+
+const INTERNAL_ERROR: i32 = 101;
+
+// the path of stdin, programs can be piped in
+const STDIN: &str = "-";
+
+struct Options {
+    // `run` only takes one, the values after it are the arguments of the program
+    paths: Vec<String>,
+    levels: LintLevels,
+    // the edition and features the files are parsed with
+    parser: ParserOptions,
+    mode: Mode,
+    target: Target,
+    emit: Option<Emit>,
+    // for `emit`, the representations are printed without one, or when it's `-`
+    output: Option<PathBuf>,
+    // where the outputs go, named after the files they're compiled from
+    out_dir: Option<PathBuf>,
+    color: ColorChoice,
+    // do it again every time one of the files changes
+    watch: bool,
+    // for `fmt`, only say which files would change instead of changing them
+    check: bool,
+    // for `check` and `lint`, make the edits the diagnostics suggest
+    fix: bool,
+    // how hard the SSA form is optimized, from `-O0` to `-O2`
+    optimize: u8,
+    // what `doc` writes
+    doc_format: DocFormat,
+    // print the time and memory each pass of the compiler took
+    time_passes: bool,
+    run: RunOptions,
+    // when no files were given, the project being compiled
+    project: Option<Manifest>,
+    // for `refs`, the name whose references are listed
+    name: Option<String>,
+    // where `serve` listens, and how long the programs it runs can take
+    listen: String,
+    limits: Limits,
+}
+
+// the ones that only matter when the program is run by the interpreter
+#[derive(Default)]
+struct RunOptions {
+    // what the program gets from `args()`
+    args: Vec<String>,
+    log_level: Option<LogLevel>,
+    // how many objects are made between collections
+    gc_threshold: Option<usize>,
+    gc_stats: bool,
+}
+
+// the command line, every part of it optional, is
+//
+//     ez [watch] [command] [files] [args] [options] [-- args]
+//
+// without a command the files are only checked. A directory stands for the
+// programs in it, a file given as `-` is read from stdin, and the files a
+// program uses with `use` are compiled along with it. Without files, the
+// project with an `ez.toml` in the working directory, or above it, is
+// compiled, with the target, output, edition, features and lint levels it
+// sets unless the command line sets them too. With `watch`, they're compiled
+// again whenever the files or the `.ez` files in the directories given change.
+// The commands are
+//
+//     check              report what's wrong with the programs. It and `lint`
+//                        remember the programs without a diagnostic in a
+//                        `.ez-cache` directory next to them, and skip them
+//                        until one of their files changes
+//     lint               `check` with the style rules too, which are lints
+//                        like the others
+//     run                interpret the HIR of the program, not its bytecode.
+//                        The values after the file are passed to it,
+//                        everything after `--` too, even flags
+//     build              compile the programs for the target
+//     repl               read programs from the terminal and run them as
+//                        they're typed
+//     lex, parse, outline
+//                        `--emit tokens`, `--emit ast` and `--emit outline`,
+//                        the outline lists what the file declares, with the
+//                        functions nested in others
+//     fmt                rewrite the files the canonical way, every file of a
+//                        directory or of the project
+//     lsp                a language server for editors, speaking LSP over
+//                        stdin and stdout
+//     doc                write the documentation of the program and the files
+//                        it uses, from the `///` comments above its functions,
+//                        enums and constants
+//     test               run the `test "name" { }` blocks of the program and
+//                        the files it uses, each after the declarations of the
+//                        program, and fail if one did
+//     bench              time the functions marked `@bench` the same way, and
+//                        compare them
+//     serve              a playground over HTTP, with a page at `/` and the
+//                        programs posted to `/compile` and `/run` checked, or
+//                        run in a sandbox
+//     grammar            print the grammar of ez for tree-sitter, or write it
+//                        to the output
+//     refs name          list where the name is declared, read and written in
+//                        the program and the files it uses, once it checks
+//     disasm             print `.ezb` files disassembled, with the text of the
+//                        lines when the files they were compiled from are
+//                        still where they were
+//
+// and the options, the ones taking a value can also be written as
+// `--target=js`
+//
+//     --target native|wasm|c|js
+//                        what `build` compiles to
+//     -o output          where the output is written, `-o -` prints it
+//     --out-dir dir      write each file's output there, named after the file,
+//                        like `out/main.js` or `out/main.hir`. With `grammar`,
+//                        the `grammar.js` and `queries/highlights.scm` of a
+//                        tree-sitter grammar
+//     --emit what        `tokens`, `ast`, `outline`, `hir`, `ssa`, `bytecode`,
+//                        `ir` or `asm`, written to the output or printed, or
+//                        `js`, which is `build --target js`. The bytecode is
+//                        written as bytes, to a `.ezb` file, and printed
+//                        disassembled, with its constant pool and the line of
+//                        the source above the instructions compiled from it
+//     -O[level]          optimize the SSA form, and the bytecode compiled from
+//                        it. `-O1` removes what nothing needs and rewrites the
+//                        bytecode a few instructions at a time, `-O2` or `-O`
+//                        propagates constants first
+//     --check            with `fmt`, fail when a file would change instead
+//     --fix              with `check` and `lint`, make the edits the
+//                        diagnostics suggest in the files, like adding a
+//                        `mut`, and say what was fixed before reporting what's
+//                        left
+//     --format html|markdown
+//                        what `doc` writes, HTML without it
+//     --time-passes      print the time each pass of the compiler took, and
+//                        the memory it allocated, once the file is compiled
+//     --color auto|always|never
+//                        whether the diagnostics are colored, `auto` colors
+//                        them on a terminal
+//     --log-level level  what the program logs, only with `run`, `test` and
+//                        `bench`, compiled programs read `EZ_LOG` instead
+//     --gc-threshold n   how many objects the interpreter makes between
+//                        collections, only with `run`, `test` and `bench`
+//     --gc-stats         write what the collector did to stderr when the
+//                        program is done, only with `run`, `test` and `bench`
+//     --listen address   where `serve` listens, 127.0.0.1:8080 without it
+//     --time-limit seconds
+//                        how long `serve` runs a program at most, 5 without it
+//     --edition edition  the edition the files are parsed as
+//     --feature name     enable syntax that's still experimental, it can be
+//                        given more than once
+//     -A, -W, -D lint    allow, warn about or deny a lint. The levels are
+//                        applied in order, so `-D warnings -A unused_variables`
+//                        denies every lint but one
+fn parse_args(args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut paths = vec![];
+    // applied once the ones of the manifest are
+    let mut lints = vec![];
+    let mut target = None;
+    let mut emit = None;
+    let mut output = None;
+    let mut out_dir = None;
+    let mut color = ColorChoice::default();
+    let mut run = RunOptions::default();
+    let mut check = false;
+    let mut fix = false;
+    let mut optimize = 0;
+    let mut doc_format = None;
+    let mut time_passes = false;
+    let mut listen = None;
+    let mut time_limit = None;
+    let mut edition = None;
+    let mut features = vec![];
+    let mut name = None;
+    let mut args = args.peekable();
+
+    let watch = args.next_if(|arg| arg == "watch").is_some();
+    let subcommands = [
+        "check", "run", "build", "lex", "parse", "outline", "repl", "fmt", "lint", "lsp", "doc",
+        "test", "bench", "serve", "grammar", "refs", "disasm",
+    ];
+    let mut mode = match args
+        .next_if(|arg| subcommands.contains(&arg.as_str()))
+        .as_deref()
+    {
+        Some("run") => Mode::Run,
+        Some("build") => Mode::Build,
+        Some("repl") => Mode::Repl,
+        Some("fmt") => Mode::Format,
+        Some("lint") => Mode::Lint,
+        Some("lsp") => Mode::Lsp,
+        Some("doc") => Mode::Doc,
+        Some("test") => Mode::Test,
+        Some("bench") => Mode::Bench,
+        Some("serve") => Mode::Serve,
+        Some("grammar") => Mode::Grammar,
+        Some("refs") => Mode::Refs,
+        Some("disasm") => Mode::Disasm,
+        Some("lex") => {
+            emit = Some(Emit::Tokens);
+            Mode::Check
+        }
+        Some("parse") => {
+            emit = Some(Emit::Ast);
+            Mode::Check
+        }
+        Some("outline") => {
+            emit = Some(Emit::Outline);
+            Mode::Check
+        }
+        _ => Mode::Check,
+    };
+
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            run.args.extend(args.by_ref());
+            break;
+        }
+
+        let (arg, value) = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => (flag.to_string(), Some(value.into())),
+            _ => (arg, None),
+        };
+
+        if arg == "--target" || arg == "--emit" {
+            let Some(name) = value.or_else(|| args.next()) else {
+                return Err(format!("`{}` needs the name of a target", arg));
+            };
+
+            let representation = match name.as_str() {
+                "tokens" => Some(Emit::Tokens),
+                "ast" => Some(Emit::Ast),
+                "hir" => Some(Emit::Hir),
+                "ssa" => Some(Emit::Ssa),
+                "ir" if cfg!(feature = "llvm") => Some(Emit::Ir),
+                "ir" => return Err("ez was built without llvm, so there's no IR to emit".into()),
+                "bytecode" => Some(Emit::Bytecode),
+                "asm" => Some(Emit::Asm),
+                "outline" => Some(Emit::Outline),
+                _ => None,
+            };
+
+            match representation {
+                Some(representation) if arg == "--emit" => emit = Some(representation),
+                _ => {
+                    let Some(parsed) = Target::parse(&name) else {
+                        return Err(format!("unknown target `{}`", name));
+                    };
+                    target = Some(parsed);
+
+                    if arg == "--emit" {
+                        mode = Mode::Build;
+                    }
+                }
+            }
+            continue;
+        }
+
+        if arg == "-o" || arg == "--output" {
+            let Some(path) = value.or_else(|| args.next()) else {
+                return Err(format!("`{}` needs the path of the output", arg));
+            };
+            output = Some(path.into());
+            continue;
+        }
+
+        // `-O` alone is the highest level
+        if let Some(level) = arg.strip_prefix("-O") {
+            optimize = match level {
+                "" => optimize::MAX_LEVEL,
+                level => level
+                    .parse()
+                    .ok()
+                    .filter(|level| *level <= optimize::MAX_LEVEL)
+                    .ok_or_else(|| {
+                        format!("`-O` takes a level from 0 to {}", optimize::MAX_LEVEL)
+                    })?,
+            };
+            continue;
+        }
+
+        if arg == "--out-dir" {
+            let Some(dir) = value.or_else(|| args.next()) else {
+                return Err(format!("`{}` needs the path of a directory", arg));
+            };
+            out_dir = Some(dir.into());
+            continue;
+        }
+
+        if arg == "--color" {
+            let Some(name) = value.or_else(|| args.next()) else {
+                return Err(format!("`{}` needs `auto`, `always` or `never`", arg));
+            };
+            let Some(choice) = ColorChoice::parse(&name) else {
+                return Err(format!(
+                    "unknown color choice `{}`, expected auto, always or never",
+                    name
+                ));
+            };
+            color = choice;
+            continue;
+        }
+
+        if arg == "--format" {
+            let Some(name) = value.or_else(|| args.next()) else {
+                return Err(format!("`{}` needs `html` or `markdown`", arg));
+            };
+            let Some(format) = DocFormat::parse(&name) else {
+                return Err(format!(
+                    "unknown documentation format `{}`, expected html or markdown",
+                    name
+                ));
+            };
+            doc_format = Some(format);
+            continue;
+        }
+
+        if arg == "--log-level" {
+            let Some(name) = value.or_else(|| args.next()) else {
+                return Err(format!("`{}` needs the name of a level", arg));
+            };
+            let Some(level) = LogLevel::parse(&name) else {
+                return Err(format!(
+                    "unknown log level `{}`, expected debug, info, warn, error or off",
+                    name
+                ));
+            };
+            run.log_level = Some(level);
+            continue;
+        }
+
+        if arg == "--gc-threshold" {
+            let Some(count) = value.or_else(|| args.next()) else {
+                return Err(format!("`{}` needs a number of objects", arg));
+            };
+            match count.parse() {
+                Ok(count) if count > 0 => run.gc_threshold = Some(count),
+                _ => return Err(format!("`{}` isn't a positive number of objects", count)),
+            }
+            continue;
+        }
+
+        if arg == "--listen" {
+            let Some(address) = value.or_else(|| args.next()) else {
+                return Err(format!("`{}` needs an address, like `0.0.0.0:8080`", arg));
+            };
+            listen = Some(address);
+            continue;
+        }
+
+        if arg == "--time-limit" {
+            let Some(seconds) = value.or_else(|| args.next()) else {
+                return Err(format!("`{}` needs a number of seconds", arg));
+            };
+            match seconds.parse::<f64>() {
+                Ok(seconds) if seconds > 0.0 && seconds.is_finite() => {
+                    time_limit = Some(Duration::from_secs_f64(seconds))
+                }
+                _ => return Err(format!("`{}` isn't a positive number of seconds", seconds)),
+            }
+            continue;
+        }
+
+        if arg == "--edition" {
+            let Some(name) = value.or_else(|| args.next()) else {
+                return Err(format!("`{}` needs the name of an edition", arg));
+            };
+            let Some(parsed) = Edition::parse(&name) else {
+                return Err(format!("unknown edition `{}`", name));
+            };
+            edition = Some(parsed);
+            continue;
+        }
+
+        if arg == "--feature" {
+            let Some(name) = value.or_else(|| args.next()) else {
+                return Err(format!("`{}` needs the name of a feature", arg));
+            };
+            let Some(feature) = Feature::parse(&name) else {
+                return Err(format!("unknown feature `{}`", name));
+            };
+            features.push(feature);
+            continue;
+        }
+
+        if arg == "--check" {
+            check = true;
+            continue;
+        }
+
+        if arg == "--fix" {
+            fix = true;
+            continue;
+        }
+
+        if arg == "--time-passes" {
+            time_passes = true;
+            continue;
+        }
+
+        if arg == "--gc-stats" {
+            run.gc_stats = true;
+            continue;
+        }
+
+        let level = match arg.as_str() {
+            "-A" => Level::Allow,
+            "-W" => Level::Warn,
+            "-D" => Level::Deny,
+            flag if flag.starts_with('-') && flag != STDIN => {
+                return Err(format!("unknown flag `{}`", flag))
+            }
+            _ if mode == Mode::Run && !paths.is_empty() => {
+                run.args.push(arg);
+                continue;
+            }
+            _ if mode == Mode::Refs && name.is_none() => {
+                name = Some(arg);
+                continue;
+            }
+            _ => {
+                paths.push(arg);
+                continue;
+            }
+        };
+
+        let Some(lint) = args.next() else {
+            return Err(format!("`{}` needs the name of a lint", arg));
+        };
+
+        // lints can be spelled with dashes too, like `unused-variables`
+        let lint = lint.replace('-', "_");
+        if lint == "warnings" {
+            lints.push(("warnings", level));
+            continue;
+        }
+
+        let Some(lint) = LINTS.iter().find(|known| **known == lint) else {
+            return Err(format!("unknown lint `{}`", lint));
+        };
+        lints.push((lint, level));
+    }
+
+    // the editor sends the files, a project is found for each of them
+    if mode == Mode::Lsp && (watch || !paths.is_empty()) {
+        return Err("the language server takes no files, the editor sends them".into());
+    }
+
+    if mode == Mode::Serve && (watch || !paths.is_empty()) {
+        return Err("the playground takes no files, they're posted to it".into());
+    }
+
+    if mode == Mode::Grammar && (watch || !paths.is_empty()) {
+        return Err("the grammar takes no files, it's the one of every program".into());
+    }
+
+    if mode == Mode::Disasm && (watch || paths.is_empty()) {
+        return Err("`disasm` needs the `.ezb` files to print, and can't watch them".into());
+    }
+
+    if mode == Mode::Refs && name.is_none() {
+        return Err("`refs` needs the name to find the references of".into());
+    }
+
+    if (listen.is_some() || time_limit.is_some()) && mode != Mode::Serve {
+        return Err("only `serve` listens and runs programs with a time limit".into());
+    }
+
+    // without files, the project the working directory is in is compiled
+    let mut project = None;
+    if paths.is_empty() && !matches!(mode, Mode::Repl | Mode::Lsp | Mode::Serve | Mode::Grammar) {
+        let dir = std::env::current_dir()
+            .map_err(|error| format!("couldn't find the working directory: {}", error))?;
+        let Some(path) = Manifest::find(&dir) else {
+            return Err(format!(
+                "no file was given, pass the path of one or `-` to read it from stdin, or \
+                 run ez in a project with an `{}`",
+                manifest::FILE
+            ));
+        };
+
+        // all of a project is formatted, not only its entry
+        let manifest = Manifest::load(&path).map_err(|error| error.to_string())?;
+        match mode {
+            Mode::Format => paths.push(Path::new(".").join(&manifest.dir).display().to_string()),
+            _ => paths.push(manifest.entry.display().to_string()),
+        }
+        project = Some(manifest);
+    }
+
+    let target = match (
+        target,
+        project.as_ref().and_then(|project| project.target.as_ref()),
+    ) {
+        (Some(target), _) => target,
+        (None, Some(name)) => Target::parse(name)
+            .ok_or_else(|| format!("unknown target `{}` in `{}`", name, manifest::FILE))?,
+        (None, None) => Target::Native,
+    };
+
+    // the command line goes last, so it overrides the manifest
+    let mut levels = LintLevels::default();
+    let project_lints = project.iter().flat_map(|project| &project.lints);
+    for (lint, level) in project_lints.chain(&lints) {
+        match *lint {
+            "warnings" => levels.set_all(*level),
+            lint => levels.set(lint, *level),
+        }
+    }
+
+    // the edition of the command line replaces the project's, and the features
+    // are added to its own
+    let mut parser = project
+        .as_ref()
+        .map(|project| project.options.clone())
+        .unwrap_or_default();
+    if let Some(edition) = edition {
+        parser.edition = edition;
+    }
+    parser.features.extend(features);
+
+    if watch && mode == Mode::Repl {
+        return Err("the REPL can't be watched".into());
+    }
+
+    if watch && mode == Mode::Format {
+        return Err("formatting can't be watched, it changes the files watched".into());
+    }
+
+    if check && mode != Mode::Format {
+        return Err("only `fmt` can `--check`".into());
+    }
+
+    if fix && (!matches!(mode, Mode::Check | Mode::Lint) || emit.is_some()) {
+        return Err("only `check` and `lint` can `--fix`".into());
+    }
+
+    if fix && watch {
+        return Err("fixing can't be watched, it changes the files watched".into());
+    }
+
+    // the backends compile the HIR, only the SSA form and its bytecode are optimized
+    if optimize > 0 && !matches!(emit, Some(Emit::Ssa | Emit::Bytecode)) {
+        return Err(
+            "only the SSA form is optimized, `-O` needs `--emit ssa` or `--emit bytecode`".into(),
+        );
+    }
+
+    if time_passes
+        && matches!(
+            mode,
+            Mode::Repl | Mode::Format | Mode::Lsp | Mode::Serve | Mode::Grammar | Mode::Disasm
+        )
+    {
+        return Err("only files that are compiled have passes to time".into());
+    }
+
+    if doc_format.is_some() && mode != Mode::Doc {
+        return Err("only `doc` has a `--format`".into());
+    }
+
+    if emit.is_some()
+        && matches!(
+            mode,
+            Mode::Format | Mode::Lint | Mode::Doc | Mode::Grammar | Mode::Refs | Mode::Disasm
+        )
+    {
+        return Err("representations can only be emitted when checking or building".into());
+    }
+
+    if watch && paths.iter().any(|path| path == STDIN) {
+        return Err("stdin can't be watched".into());
+    }
+
+    if fix && paths.iter().any(|path| path == STDIN) {
+        return Err("stdin can't be fixed, there's no file to change".into());
+    }
+
+    if paths.iter().filter(|path| *path == STDIN).count() > 1 {
+        return Err("stdin can only be read once".into());
+    }
+
+    if output.is_some() && paths.len() > 1 {
+        return Err("only one file can be built when the output is given".into());
+    }
+
+    if output.as_deref() == Some(Path::new(STDIN))
+        && emit.is_none()
+        && !matches!(mode, Mode::Doc | Mode::Grammar)
+    {
+        return Err(
+            "only emitted representations, documentation and the grammar can be printed with \
+             `-o -`"
+                .into(),
+        );
+    }
+
+    if output.is_some() && out_dir.is_some() {
+        return Err("`-o` and `--out-dir` can't be used together".into());
+    }
+
+    if emit.is_some() && matches!(mode, Mode::Run | Mode::Repl | Mode::Test | Mode::Bench) {
+        return Err("representations can only be emitted when checking or building".into());
+    }
+
+    if (output.is_some() || out_dir.is_some())
+        && emit.is_none()
+        && !matches!(mode, Mode::Build | Mode::Doc | Mode::Grammar)
+    {
+        return Err(
+            "only builds, documentation, the grammar and emitted representations have an output"
+                .into(),
+        );
+    }
+
+    if output.is_none()
+        && out_dir.is_none()
+        && emit.is_none()
+        && mode == Mode::Build
+        && paths.iter().any(|path| path == STDIN)
+    {
+        return Err(
+            "a program read from stdin needs an output to be built, given with `-o` or \
+             `--out-dir`"
+                .into(),
+        );
+    }
+
+    if !run.args.is_empty() && mode != Mode::Run {
+        return Err("only programs that are run can be given arguments".into());
+    }
+
+    let interpreted = matches!(mode, Mode::Run | Mode::Test | Mode::Bench);
+    if run.log_level.is_some() && !interpreted {
+        return Err(
+            "only programs that are run, tested or benchmarked can be given a log level".into(),
+        );
+    }
+
+    if (run.gc_threshold.is_some() || run.gc_stats) && !interpreted {
+        return Err(
+            "only programs that are run, tested or benchmarked have a garbage collector".into(),
+        );
+    }
+
+    Ok(Options {
+        paths,
+        levels,
+        parser,
+        mode,
+        target,
+        emit,
+        output,
+        out_dir,
+        color,
+        watch,
+        check,
+        fix,
+        optimize,
+        doc_format: doc_format.unwrap_or_default(),
+        time_passes,
+        run,
+        project,
+        name,
+        listen: listen.unwrap_or_else(|| serve::ADDRESS.to_string()),
+        limits: Limits {
+            time: time_limit.or(Limits::default().time),
+            ..Limits::default()
+        },
+    })
+}
+
+// the values of the program can't leave the thread it runs on, so they're
+// printed there
+fn interpreter<'a>(hir: &'a Hir, options: &RunOptions) -> Interpreter<'a> {
+    let mut interpreter = Interpreter::new(hir).with_args(options.args.clone());
+    if let Some(log_level) = options.log_level {
+        interpreter = interpreter.with_log_level(log_level);
+    }
+    if let Some(threshold) = options.gc_threshold {
+        interpreter = interpreter.with_gc_threshold(threshold);
+    }
+    if options.gc_stats {
+        interpreter = interpreter.with_gc_stats();
+    }
+    interpreter
+}
+
+fn run(hir: &Hir, options: &RunOptions) -> Result<(), Diagnostic> {
+    let result = std::thread::scope(|scope| {
+        std::thread::Builder::new()
+            .stack_size(interp::STACK_SIZE)
+            .spawn_scoped(scope, || interpreter(hir, options).run())
+            .expect("failed to start the interpreter")
+            .join()
+            .expect("the interpreter panicked")
+    });
+
+    Ok(result?)
+}
+
+// every test runs in an interpreter of its own, so they don't see what the
+// others changed. The failures are shown once they all ran
+fn test(hir: &Hir, sources: &SourceMap, options: &Options) -> Result<(), Failure> {
+    let failures = std::thread::scope(|scope| {
+        std::thread::Builder::new()
+            .stack_size(interp::STACK_SIZE)
+            .spawn_scoped(scope, || {
+                println!("running {} test(s)", hir.tests.len());
+                let mut failures = vec![];
+                for test in &hir.tests {
+                    match interpreter(hir, &options.run).run_test(test) {
+                        Ok(()) => println!("test {} ... ok", test.name),
+                        Err(error) => {
+                            println!("test {} ... FAILED", test.name);
+                            failures.push(Diagnostic::from(error));
+                        }
+                    }
+                }
+                failures
+            })
+            .expect("failed to start the interpreter")
+            .join()
+            .expect("the interpreter panicked")
+    });
+
+    let renderer = Renderer::for_terminal(options.color.enabled(io::stdout().is_terminal()));
+    let passed = hir.tests.len() - failures.len();
+    if failures.is_empty() {
+        println!("\ntest result: ok. {} passed, 0 failed", passed);
+        return Ok(());
+    }
+
+    println!("\nfailures:\n");
+    let mut sink = DiagnosticSink::default();
+    sink.extend(failures);
+    print!("{}", sink.render(&renderer, sources));
+
+    let message = format!("{} passed, {} failed", passed, sink.count(Severity::Error));
+    print!("{}", renderer.summary(Severity::Error, &message));
+    Err(Failure::Tests)
+}
+
+// every benchmark gets an interpreter of its own, like tests, and the table
+// is printed once they all ran
+fn bench(hir: &Hir, options: &RunOptions) -> Result<(), Diagnostic> {
+    let summaries = std::thread::scope(|scope| {
+        std::thread::Builder::new()
+            .stack_size(interp::STACK_SIZE)
+            .spawn_scoped(scope, || {
+                let mut summaries = vec![];
+                for bench in &hir.benches {
+                    println!("benchmarking {}", bench.name);
+                    let samples =
+                        interpreter(hir, options).run_bench(bench, bench::WARMUP, bench::RUNS)?;
+                    summaries.push(Summary::new(bench.name.as_str(), samples));
+                }
+                Ok::<_, Diagnostic>(summaries)
+            })
+            .expect("failed to start the interpreter")
+            .join()
+            .expect("the interpreter panicked")
+    })?;
+
+    match summaries.is_empty() {
+        true => println!("no functions are marked `@bench`"),
+        false => print!("\n{}", bench::table(&summaries)),
+    }
+    Ok(())
+}
+
+// what's written to the output, for the targets that aren't linked
+fn build_wasm(hir: &Hir) -> Result<Vec<u8>, Diagnostic> {
+    Ok(WasmBackend::new(hir).compile()?)
+}
+
+fn build_c(hir: &Hir, sources: &SourceMap) -> Result<Vec<u8>, Diagnostic> {
+    Ok(CBackend::new(hir, sources).compile()?.into_bytes())
+}
+
+fn build_js(hir: &Hir, sources: &SourceMap) -> Result<Vec<u8>, Diagnostic> {
+    Ok(JsBackend::new(hir, sources).compile().into_bytes())
+}
+
+// the object file is linked with the startup code by the system's C compiler
+#[cfg(feature = "llvm")]
+fn build_native(
+    hir: &Hir,
+    path: &str,
+    _sources: &SourceMap,
+    output: &Path,
+) -> Result<(), Diagnostic> {
+    let scratch = Scratch::new();
+    let context = llvm::Context::create();
+    let module = LlvmBackend::new(&context, hir, path).compile()?;
+
+    let object = scratch.path("program.o");
+    llvm::write_object(&module, &object)?;
+    link::link(&scratch, &object, output)?;
+
+    Ok(())
+}
+
+// without llvm, the program goes through C
+#[cfg(not(feature = "llvm"))]
+fn build_native(
+    hir: &Hir,
+    _path: &str,
+    sources: &SourceMap,
+    output: &Path,
+) -> Result<(), Diagnostic> {
+    let scratch = Scratch::new();
+    let source = scratch.path("program.c");
+    fs::write(&source, build_c(hir, sources)?).expect("failed to write the C file");
+    link::compile_c(&source, output)?;
+
+    Ok(())
+}
+
+// one token a line, with where it starts, unknown characters are tokens too
+fn tokens(content: &str) -> String {
+    let mut out = String::new();
+    let mut lexer = Lexer::new(content);
+    while let Some(token) = lexer.tokenize() {
+        let (line, column) = diagnostics::line_col(content, token.span.start);
+        let _ = writeln!(
+            out,
+            "{}:{} {:?} {:?}",
+            line, column, token.kind, token.value
+        );
+    }
+
+    out
+}
+
+fn describe_hir(hir: &Hir, sources: &SourceMap) -> String {
+    let mut out = hir.to_string();
+    for function in cfg::functions(hir) {
+        let location = sources.location(function.value.span);
+        let _ = writeln!(out, "\ncfg of the function at {}:", location);
+        let _ = write!(out, "{}", dataflow::describe(hir, &function.cfg));
+    }
+
+    out
+}
+
+#[cfg(feature = "llvm")]
+fn llvm_ir(hir: &Hir, path: &str) -> Result<String, Diagnostic> {
+    let context = llvm::Context::create();
+    let module = LlvmBackend::new(&context, hir, path).compile()?;
+    Ok(module.print_to_string().to_string())
+}
+
+#[cfg(not(feature = "llvm"))]
+fn llvm_ir(_hir: &Hir, _path: &str) -> Result<String, Diagnostic> {
+    unreachable!("`--emit ir` needs llvm, the flags are checked for it")
+}
 
-    let mut parser = Parser::new(&content);
+#[cfg(feature = "llvm")]
+fn assembly(hir: &Hir, path: &str, _sources: &SourceMap) -> Result<String, Diagnostic> {
+    let context = llvm::Context::create();
+    let module = LlvmBackend::new(&context, hir, path).compile()?;
+    Ok(llvm::assembly(&module)?)
+}
+
+#[cfg(not(feature = "llvm"))]
+fn assembly(hir: &Hir, _path: &str, sources: &SourceMap) -> Result<String, Diagnostic> {
+    let scratch = Scratch::new();
+    let source = scratch.path("program.c");
+    let output = scratch.path("program.s");
+    fs::write(&source, build_c(hir, sources)?).expect("failed to write the C file");
+    link::assemble_c(&source, &output)?;
+
+    Ok(fs::read_to_string(&output).expect("failed to read the assembly"))
+}
+
+// the file written for the input, `-o` when it's given. Otherwise it's named
+// after the input, or the project, and goes in the output directory, or next
+// to the input, or where the manifest says
+fn artifact(input: &str, extension: &str, options: &Options) -> PathBuf {
+    if let Some(output) = &options.output {
+        return output.clone();
+    }
+
+    let name = match &options.project {
+        Some(project) => PathBuf::from(&project.name),
+        None if input == STDIN => PathBuf::from("stdin"),
+        None => PathBuf::from(Path::new(input).file_stem().unwrap_or_default()),
+    };
+
+    match (&options.out_dir, &options.project) {
+        (Some(dir), _) => dir.join(name).with_extension(extension),
+        (None, Some(project)) => project
+            .output
+            .clone()
+            .unwrap_or_else(|| project.dir.join(name).with_extension(extension)),
+        (None, None) => Path::new(input).with_extension(extension),
+    }
+}
+
+// whether what's emitted is printed rather than written to the output
+fn prints_emitted(options: &Options) -> bool {
+    match &options.output {
+        Some(output) => output.as_os_str() == STDIN,
+        None => options.out_dir.is_none(),
+    }
+}
+
+// to the output when there is one, printed otherwise
+fn write_emitted(output: &[u8], input: &str, emit: Emit, options: &Options) -> Result<(), Failure> {
+    if prints_emitted(options) {
+        return io::Write::write_all(&mut io::stdout(), output).map_err(|error| {
+            report_error(&format!("couldn't print the output: {}", error), options);
+            Failure::Io
+        });
+    }
+
+    write_output(&artifact(input, emit.extension(), options), output, options)
+}
+
+fn write_output(path: &Path, output: &[u8], options: &Options) -> Result<(), Failure> {
+    fs::write(path, output).map_err(|error| {
+        let message = format!("couldn't write `{}`: {}", path.display(), error);
+        report_error(&message, options);
+        Failure::Io
+    })
+}
+
+// the bytecode of the files, with the lines of the sources they were compiled
+// from when they're where the module says
+fn disassemble(options: &Options) -> Result<(), String> {
+    for (index, path) in options.paths.iter().enumerate() {
+        let bytes = match path.as_str() {
+            STDIN => {
+                let mut bytes = vec![];
+                io::stdin()
+                    .read_to_end(&mut bytes)
+                    .map_err(|error| format!("couldn't read stdin: {}", error))?;
+                bytes
+            }
+            path => {
+                fs::read(path).map_err(|error| format!("couldn't read `{}`: {}", path, error))?
+            }
+        };
+        let module =
+            ezb::decode(&bytes).map_err(|error| format!("couldn't read `{}`: {}", path, error))?;
+        let sources: Vec<Option<String>> = module
+            .files
+            .iter()
+            .map(|file| fs::read_to_string(file).ok())
+            .collect();
+
+        if index > 0 {
+            println!();
+        }
+        print!("{}", module.disassemble(&sources));
+    }
+    Ok(())
+}
+
+// next to the input, or the project's manifest, unless it's given. A program
+// from stdin has its documentation printed, like with `-o -`
+fn write_documentation(text: &str, input: &str, options: &Options) -> Result<(), Failure> {
+    let extension = options.doc_format.extension();
+    let output = match (&options.output, &options.out_dir, &options.project) {
+        (Some(output), _, _) if output.as_os_str() == STDIN => None,
+        (None, None, _) if input == STDIN => None,
+        (None, None, Some(project)) => {
+            Some(project.dir.join(&project.name).with_extension(extension))
+        }
+        _ => Some(artifact(input, extension, options)),
+    };
+    let Some(output) = output else {
+        print!("{}", text);
+        return Ok(());
+    };
+
+    fs::write(&output, text).map_err(|error| {
+        let message = format!("couldn't write `{}`: {}", output.display(), error);
+        report_error(&message, options);
+        Failure::Io
+    })?;
+    println!("wrote {}", output.display());
+    Ok(())
+}
+
+// printed, unless an output is given. A directory gets the files of a
+// tree-sitter grammar, which `tree-sitter generate` can be run in
+fn write_grammar(options: &Options) -> Result<(), String> {
+    let mut files = vec![];
+    match (&options.output, &options.out_dir) {
+        (Some(output), _) if output.as_os_str() != STDIN => {
+            files.push((output.clone(), tree_sitter::grammar()))
+        }
+        (_, Some(dir)) => {
+            files.push((dir.join("grammar.js"), tree_sitter::grammar()));
+            files.push((
+                dir.join("queries/highlights.scm"),
+                tree_sitter::highlights(),
+            ));
+        }
+        _ => print!("{}", tree_sitter::grammar()),
+    }
+
+    for (path, text) in files {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)
+                .map_err(|error| format!("couldn't create `{}`: {}", dir.display(), error))?;
+        }
+        fs::write(&path, text)
+            .map_err(|error| format!("couldn't write `{}`: {}", path.display(), error))?;
+        println!("wrote {}", path.display());
+    }
+    Ok(())
+}
+
+// errors that aren't about the program go to stderr
+fn report_error(message: &str, options: &Options) {
+    let renderer = Renderer::for_terminal(options.color.enabled(io::stderr().is_terminal()));
+    eprint!("{}", renderer.summary(Severity::Error, message));
+}
+
+// files are read whole, stdin until it's closed
+fn read_source(path: &str) -> Result<String, String> {
+    if path == STDIN {
+        let mut content = String::new();
+        return match io::stdin().read_to_string(&mut content) {
+            Ok(_) => Ok(content),
+            Err(error) => Err(format!("couldn't read stdin: {}", error)),
+        };
+    }
+
+    fs::read_to_string(path).map_err(|error| format!("couldn't read `{}`: {}", path, error))
+}
+
+// what checking a program came to. Nothing is printed yet, programs are
+// checked on threads of their own and reported in the order they were given
+enum Checked {
+    // it checked clean before, and none of its files changed
+    Cached,
+    // tokens and syntax trees are emitted without checking the program
+    Emitted(Emit, String),
+    // it couldn't be read, or its project's packages couldn't be found
+    Failed(String, Failure),
+    Program(Box<Frontend>),
+}
+
+struct Frontend {
+    sink: DiagnosticSink,
+    session: Session,
+    // when it passed the checks
+    lowered: Option<Hir>,
+    documentation: Option<String>,
+    // for `refs`, the list of them
+    references: Option<String>,
+    // what it fails with when there are errors
+    failure: Failure,
+    cache: Option<Cache>,
+    // with `--fix`, what was fixed in its files before it was checked this way
+    fixed: Vec<String>,
+}
+
+// the stack of the threads programs are checked on, parsing and checking
+// recurse as deep as the program nests, like on the main thread
+const CHECK_STACK_SIZE: usize = 8 * 1024 * 1024;
+
+// the programs are checked on a thread for every core, then reported and run
+// or built in order, each as soon as the ones before it are done. A file that
+// doesn't compile doesn't stop the ones after it, the first failure is returned.
+// Programs being fixed are checked one at a time, they can share files. The
+// memory `--time-passes` shows is the whole process's, so programs being timed
+// are checked and finished one at a time, on one thread, with nothing else
+// running
+fn compile_all(paths: &[String], options: &Options) -> Option<Failure> {
+    if options.time_passes {
+        return thread::scope(|scope| {
+            thread::Builder::new()
+                .stack_size(CHECK_STACK_SIZE)
+                .spawn_scoped(scope, || {
+                    paths.iter().fold(None, |failed, path| {
+                        let mut passes = passes(options);
+                        let checked = check_or_fix(path, options, &mut passes);
+                        let result = finish(path, checked, options, &mut passes);
+                        eprint!("{}", passes.table());
+                        failed.or(result.err())
+                    })
+                })
+                .expect("failed to start a thread to check programs on")
+                .join()
+                .expect("a thread checking programs stopped")
+        });
+    }
+
+    let cores = thread::available_parallelism().map_or(1, |count| count.get());
+    let workers = if options.fix { 1 } else { cores }.min(paths.len());
+    let next = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::channel();
+
+    let mut failed = None;
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            let sender = sender.clone();
+            let (next, options) = (&next, options);
+            thread::Builder::new()
+                .stack_size(CHECK_STACK_SIZE)
+                .spawn_scoped(scope, move || loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(path) = paths.get(index) else {
+                        break;
+                    };
+                    let mut passes = passes(options);
+                    let checked = check_or_fix(path, options, &mut passes);
+                    if sender.send((index, checked, passes)).is_err() {
+                        break;
+                    }
+                })
+                .expect("failed to start a thread to check programs on");
+        }
+        drop(sender);
+
+        // the ones checked before their turn wait for it
+        let mut waiting = BTreeMap::new();
+        for (index, path) in paths.iter().enumerate() {
+            while !waiting.contains_key(&index) {
+                let (done, checked, passes) =
+                    receiver.recv().expect("a thread checking programs stopped");
+                waiting.insert(done, (checked, passes));
+            }
+
+            let (checked, mut passes) = waiting.remove(&index).unwrap();
+            let result = finish(path, checked, options, &mut passes);
+            if let Err(failure) = result {
+                failed = failed.or(Some(failure));
+            }
+        }
+    });
+
+    failed
+}
+
+fn check_or_fix(path: &str, options: &Options, passes: &mut Passes) -> Checked {
+    if options.fix {
+        check_fixing(path, options, passes)
+    } else {
+        check(path, options, passes)
+    }
+}
+
+fn passes(options: &Options) -> Passes {
+    match options.time_passes {
+        true => Passes::enabled(),
+        false => Passes::new(),
+    }
+}
+
+// checks the program, and lowers it when the checks passed
+fn check(path: &str, options: &Options, passes: &mut Passes) -> Checked {
+    // a program that was checked before without a diagnostic isn't checked again
+    // until it changes. It's not used when the passes are timed, there would be
+    // none
+    let cached = matches!(options.mode, Mode::Check | Mode::Lint)
+        && options.emit.is_none()
+        && path != STDIN
+        && !options.time_passes;
+    let cache = cached.then(|| {
+        let mode = if options.mode == Mode::Lint {
+            "lint"
+        } else {
+            "check"
+        };
+        let settings = format!("{} {} {}", mode, options.levels.key(), options.parser.key());
+        Cache::new(Path::new(path), &settings)
+    });
+    if cache.as_ref().is_some_and(Cache::is_fresh) {
+        return Checked::Cached;
+    }
+
+    let content = match read_source(path) {
+        Ok(content) => content,
+        Err(message) => return Checked::Failed(message, Failure::Io),
+    };
+
+    // what diagnostics and compiled programs call the file
+    let input = path;
+    let path = if path == STDIN { "<stdin>" } else { path };
+    if let Some(emit @ Emit::Tokens) = options.emit {
+        return Checked::Emitted(emit, passes.time("lex", || tokens(&content)));
+    }
+
+    // the files the program uses are parsed along with it
+    let mut sink = DiagnosticSink::with_levels(options.levels.clone());
+    let mut session = Session::new().with_options(options.parser.clone());
+    if let Some(project) = &options.project {
+        match package::resolve(project) {
+            Ok(packages) => session = session.with_packages(packages),
+            Err(error) => return Checked::Failed(error.to_string(), Failure::Io),
+        }
+    }
+
+    let mut lowered = None;
+    let mut documentation = None;
+    let mut references = None;
+    let mut failure = Failure::Check;
+    let arena = Arena::new();
+    let loaded = passes.time("lex and parse", || {
+        session.load(&arena, path, content, &mut sink)
+    });
+    match loaded {
+        Some(program) if options.emit == Some(Emit::Ast) => {
+            return Checked::Emitted(Emit::Ast, format!("{:#?}\n", program));
+        }
+        Some(program) if options.emit == Some(Emit::Outline) => {
+            let symbols = outline::outline(&program);
+            return Checked::Emitted(Emit::Outline, outline::render(&symbols, &session.sources));
+        }
+        Some(program) => {
+            let checked = ez::check_timed(&program, &[], &mut sink, passes);
+            if options.mode == Mode::Lint {
+                passes.time("style", || sink.extend(Registry::default().check(&program)));
+            }
+
+            if let (Some((resolutions, types)), false) = (checked, sink.has_errors()) {
+                if options.mode == Mode::Doc {
+                    let title = match &options.project {
+                        Some(project) => project.name.clone(),
+                        None if input == STDIN => "stdin".into(),
+                        None => Path::new(path)
+                            .file_stem()
+                            .unwrap_or_default()
+                            .to_string_lossy()
+                            .into(),
+                    };
+                    let documenter =
+                        Documenter::new(&session.sources, &resolutions, &types, options.doc_format);
+                    let text = passes.time("doc", || documenter.document(&title, &program));
+                    documentation = Some(text);
+                }
+                // only `refs` is given a name
+                if let Some(name) = &options.name {
+                    let listed = self::references(name, &resolutions, &session.sources);
+                    match listed {
+                        Some(text) => references = Some(text),
+                        None => {
+                            let message = format!("`{}` isn't declared in `{}`", name, path);
+                            return Checked::Failed(message, Failure::Check);
+                        }
+                    }
+                }
+                lowered = Some(ez::lower_timed(
+                    &program,
+                    &resolutions,
+                    &types,
+                    &mut sink,
+                    passes,
+                ));
+            }
+        }
+        None => failure = Failure::Syntax,
+    }
+
+    Checked::Program(Box::new(Frontend {
+        sink,
+        session,
+        lowered,
+        documentation,
+        references,
+        failure,
+        cache,
+        fixed: vec![],
+    }))
+}
+
+// where the name is declared and used in the files of the program, a line each
+// with whether it's read or written there, and the line it's on. Each of the
+// declarations with the name is listed with its own uses. `None` when there's
+// none
+fn references(name: &str, resolutions: &Resolutions, sources: &SourceMap) -> Option<String> {
+    let mut text = String::new();
+    for (index, id) in resolutions.find_declaration(name).enumerate() {
+        if index > 0 {
+            text.push('\n');
+        }
+        for reference in resolutions.references(id) {
+            let file = sources.file(reference.span.start);
+            let start = reference.span.start - file.start;
+            let line_start = file.source[..start]
+                .rfind('\n')
+                .map_or(0, |newline| newline + 1);
+            let line_end = file.source[start..]
+                .find('\n')
+                .map_or(file.source.len(), |newline| start + newline);
+            let access = match reference.access {
+                Access::Declaration => "declared",
+                Access::Read => "read",
+                Access::Write => "written",
+            };
+            let _ = writeln!(
+                text,
+                "{}: {}: {}",
+                sources.location(reference.span),
+                access,
+                file.source[line_start..line_end].trim()
+            );
+        }
+    }
+    (!text.is_empty()).then_some(text)
+}
+
+// how many times a program is fixed and checked again at most, a fix can let
+// the program be checked further and find more to fix, like a `;` that was
+// missing
+const FIX_ROUNDS: usize = 8;
+
+// checks the program and makes the edits its diagnostics suggest to its files,
+// then checks it again, until there's nothing left to fix
+fn check_fixing(path: &str, options: &Options, passes: &mut Passes) -> Checked {
+    let mut fixed = vec![];
+    let mut round = 0;
     loop {
-        let result = parser.parse();
-        match result {
-            Ok(expr) => {
-                println!("EXPR: {:?}", expr);
+        let mut checked = check(path, options, passes);
+        let Checked::Program(frontend) = &mut checked else {
+            return checked;
+        };
+        let made = if round < FIX_ROUNDS {
+            match fix(frontend) {
+                Ok(made) => made,
+                Err(message) => return Checked::Failed(message, Failure::Io),
             }
+        } else {
+            vec![]
+        };
 
-            Err(ParseError::NoMoreTokens) => break,
+        if made.is_empty() {
+            frontend.fixed = fixed;
+            return checked;
+        }
+        fixed.extend(made);
+        round += 1;
+    }
+}
+
+// makes the suggestions of the diagnostics of the program to its own files, the
+// ones of packages are left alone. Says what each one fixed
+fn fix(frontend: &Frontend) -> Result<Vec<String>, String> {
+    let sources = &frontend.session.sources;
+    let ours = |span: Span| {
+        let file = sources.file(span.start);
+        !frontend.session.in_package(Path::new(&file.name))
+    };
+    let suggested: Vec<(&Diagnostic, &Suggestion)> = frontend
+        .sink
+        .diagnostics()
+        .iter()
+        .flat_map(|diagnostic| {
+            diagnostic
+                .suggestions()
+                .map(move |(_, suggestion)| (diagnostic, suggestion))
+        })
+        .filter(|(_, suggestion)| ours(suggestion.span))
+        .collect();
+    let made = diagnostics::compatible(suggested.iter().map(|(_, suggestion)| *suggestion));
+
+    for (file, text) in sources.apply(made.iter().copied()) {
+        let name = &sources.get(file).name;
+        fs::write(name, text).map_err(|error| format!("couldn't fix `{}`: {}", name, error))?;
+    }
 
-            Err(e) => {
-                println!("ERROR: {:?}", e);
-                break;
+    Ok(suggested
+        .iter()
+        .filter(|(_, suggestion)| made.iter().any(|made| std::ptr::eq(*made, *suggestion)))
+        .map(|(diagnostic, suggestion)| {
+            format!(
+                "{}: fixed {}",
+                sources.location(suggestion.span),
+                diagnostic.message
+            )
+        })
+        .collect())
+}
+
+// reports what checking the program found, and does what the mode says with it
+fn finish(
+    path: &str,
+    checked: Checked,
+    options: &Options,
+    passes: &mut Passes,
+) -> Result<(), Failure> {
+    let frontend = match checked {
+        Checked::Cached => return Ok(()),
+        Checked::Emitted(emit, text) => return write_emitted(text.as_bytes(), path, emit, options),
+        Checked::Failed(message, failure) => {
+            report_error(&message, options);
+            return Err(failure);
+        }
+        Checked::Program(frontend) => *frontend,
+    };
+    let Frontend {
+        sink,
+        session,
+        lowered,
+        documentation,
+        references,
+        failure,
+        cache,
+        fixed,
+    } = frontend;
+
+    if let Some(dir) = &options.out_dir {
+        fs::create_dir_all(dir).map_err(|error| {
+            let message = format!("couldn't create `{}`: {}", dir.display(), error);
+            report_error(&message, options);
+            Failure::Io
+        })?;
+    }
+
+    let input = path;
+    let path = if path == STDIN { "<stdin>" } else { path };
+    let sources = &session.sources;
+    let renderer = Renderer::for_terminal(options.color.enabled(io::stdout().is_terminal()));
+    for line in &fixed {
+        println!("{}", line);
+    }
+    print!("{}", sink.render(&renderer, sources));
+
+    let warnings = sink.count(Severity::Warning);
+    if warnings > 0 {
+        let message = format!("{} warning(s) emitted", warnings);
+        print!("{}", renderer.summary(Severity::Warning, &message));
+    }
+
+    if sink.has_errors() {
+        let message = format!(
+            "aborting due to {} previous error(s)",
+            sink.count(Severity::Error)
+        );
+        print!("{}", renderer.summary(Severity::Error, &message));
+        return Err(failure);
+    }
+
+    // it's only a cache, when it can't be written the program is checked again
+    if let (Some(cache), 0) = (&cache, warnings) {
+        let _ = cache.store(sources);
+    }
+
+    if let Some(text) = documentation {
+        return write_documentation(&text, input, options);
+    }
+
+    if let Some(text) = references {
+        print!("{}", text);
+        return Ok(());
+    }
+
+    let Some(hir) = lowered else {
+        return Ok(());
+    };
+
+    let result = match (options.emit, &options.mode) {
+        (Some(emit), _) => {
+            // the bytecode is written as bytes, and printed disassembled
+            let output = passes.time("codegen", || match emit {
+                Emit::Hir => Ok(describe_hir(&hir, sources).into_bytes()),
+                Emit::Ssa => {
+                    let mut program = ssa::build(&hir);
+                    optimize::run(&mut program, options.optimize);
+                    Ok(program.to_string().into_bytes())
+                }
+                Emit::Bytecode => {
+                    let mut program = ssa::build(&hir);
+                    optimize::run(&mut program, options.optimize);
+                    let mut module = bytecode::compile(&program, sources);
+                    if options.optimize > 0 {
+                        peephole::run(&mut module);
+                    }
+                    if !prints_emitted(options) {
+                        return Ok(ezb::encode(&module));
+                    }
+                    let texts: Vec<Option<String>> = sources
+                        .files()
+                        .iter()
+                        .map(|file| Some(file.source.clone()))
+                        .collect();
+                    Ok(module.disassemble(&texts).to_string().into_bytes())
+                }
+                Emit::Ir => llvm_ir(&hir, path).map(String::into_bytes),
+                Emit::Asm => assembly(&hir, path, sources).map(String::into_bytes),
+                Emit::Tokens | Emit::Ast | Emit::Outline => {
+                    unreachable!("they're emitted before checking")
+                }
+            });
+
+            match output {
+                Ok(output) => return write_emitted(&output, input, emit, options),
+                Err(diagnostic) => Err((diagnostic, Failure::Build)),
+            }
+        }
+        (
+            None,
+            Mode::Check
+            | Mode::Lint
+            | Mode::Repl
+            | Mode::Format
+            | Mode::Lsp
+            | Mode::Doc
+            | Mode::Serve
+            | Mode::Grammar
+            | Mode::Refs
+            | Mode::Disasm,
+        ) => return Ok(()),
+        (None, Mode::Run) => passes
+            .time("run", || run(&hir, &options.run))
+            .map_err(|error| (error, Failure::Runtime)),
+        (None, Mode::Test) => return passes.time("test", || test(&hir, sources, options)),
+        (None, Mode::Bench) => passes
+            .time("bench", || bench(&hir, &options.run))
+            .map_err(|error| (error, Failure::Runtime)),
+        (None, Mode::Build) => {
+            // a project's output is named after it, next to its manifest
+            let output = artifact(input, options.target.extension(), options);
+
+            // a native program is written by the C compiler, the others here
+            let built = passes.time("codegen", || match options.target {
+                Target::Native => build_native(&hir, path, sources, &output).map(|()| None),
+                Target::Wasm => build_wasm(&hir).map(Some),
+                Target::C => build_c(&hir, sources).map(Some),
+                Target::Js => build_js(&hir, sources).map(Some),
+            });
+            match built {
+                Ok(built) => {
+                    if let Some(built) = built {
+                        write_output(&output, &built, options)?;
+                    }
+                    println!("wrote {}", output.display());
+                    Ok(())
+                }
+                Err(error) => Err((error, Failure::Build)),
             }
         }
+    };
+
+    if let Err((diagnostic, failure)) = result {
+        let mut sink = DiagnosticSink::default();
+        sink.push(diagnostic);
+        print!("{}", sink.render(&renderer, sources));
+        return Err(failure);
+    }
+
+    Ok(())
+}
+
+// prints the file back the canonical way, in place, or to stdout for stdin.
+// With `--check`, the files are only compared
+fn format(path: &str, options: &Options) -> Result<(), Failure> {
+    let content = read_source(path).map_err(|message| {
+        report_error(&message, options);
+        Failure::Io
+    })?;
+
+    let name = if path == STDIN { "<stdin>" } else { path };
+    let formatted = match formatter::format_with(&content, options.parser.clone()) {
+        Ok(formatted) => formatted,
+        Err(error) => {
+            let mut sink = DiagnosticSink::default();
+            sink.push(error);
+            let renderer =
+                Renderer::for_terminal(options.color.enabled(io::stdout().is_terminal()));
+            print!(
+                "{}",
+                sink.render(&renderer, &SourceMap::single(name, content))
+            );
+            return Err(Failure::Syntax);
+        }
+    };
+
+    if path == STDIN && !options.check {
+        print!("{}", formatted);
+        return Ok(());
+    }
+
+    if formatted == content {
+        return Ok(());
+    }
+
+    if options.check {
+        println!("{} isn't formatted", name);
+        return Err(Failure::Unformatted);
+    }
+
+    fs::write(path, formatted).map_err(|error| {
+        report_error(&format!("couldn't write `{}`: {}", path, error), options);
+        Failure::Io
+    })
+}
+
+// a directory stands for every file in it, except for the dependencies a
+// project vendored
+fn format_files(options: &Options) -> ! {
+    let vendor = options
+        .project
+        .as_ref()
+        .and_then(|project| fs::canonicalize(project.dir.join(package::VENDOR)).ok());
+
+    let mut failed = None;
+    for path in &options.paths {
+        let files = match Path::new(path).is_dir() {
+            true => session::sources(Path::new(path)),
+            false => vec![PathBuf::from(path)],
+        };
+
+        for file in files {
+            let vendored = vendor.as_ref().is_some_and(|vendor| {
+                fs::canonicalize(&file).is_ok_and(|file| file.starts_with(vendor))
+            });
+            if vendored {
+                continue;
+            }
+
+            if let Err(failure) = format(&file.display().to_string(), options) {
+                failed = failed.or(Some(failure));
+            }
+        }
+    }
+
+    std::process::exit(failed.map_or(0, Failure::code));
+}
+
+// a directory stands for the programs in it, the files it has that no other
+// file in it uses
+fn programs(path: &str) -> Vec<String> {
+    if !Path::new(path).is_dir() {
+        return vec![path.to_string()];
+    }
+
+    session::programs(Path::new(path))
+        .iter()
+        .map(|program| program.display().to_string())
+        .collect()
+}
+
+// what the programs use, wherever it is, and the files used that don't exist
+// yet, so creating them compiles the programs again
+fn used(programs: &[String], options: &Options) -> Vec<PathBuf> {
+    let mut session = Session::new().with_options(options.parser.clone());
+    if let Some(project) = &options.project {
+        if let Ok(packages) = package::resolve(project) {
+            session = session.with_packages(packages);
+        }
+    }
+
+    let arena = Arena::new();
+    for program in programs {
+        if let Ok(source) = fs::read_to_string(program) {
+            session.load(&arena, program, source, &mut DiagnosticSink::default());
+        }
+    }
+    session.used().to_vec()
+}
+
+// compiles the files, then again after every change, until the process is
+// killed. A program that's run has to finish before changes are picked up
+fn watch(options: &Options) -> ! {
+    // all of a project is watched, not only its entry, but not the
+    // dependencies it vendored
+    let mut roots: Vec<PathBuf> = options.paths.iter().map(PathBuf::from).collect();
+    roots.extend(options.project.iter().map(|project| project.dir.clone()));
+    let vendor = options
+        .project
+        .iter()
+        .filter_map(|project| fs::canonicalize(project.dir.join(package::VENDOR)).ok())
+        .collect();
+    let mut watcher = Watcher::new(roots.clone()).with_ignored(vendor);
+    loop {
+        let paths: Vec<String> = options
+            .paths
+            .iter()
+            .flat_map(|path| programs(path))
+            .filter(|path| Path::new(path).exists())
+            .collect();
+        compile_all(&paths, options);
+
+        let mut watched = roots.clone();
+        watched.extend(used(&paths, options));
+        watcher.set_roots(watched);
+        let files = watcher.files();
+        match files.len() {
+            0 => println!("there are no files to compile yet, waiting for some"),
+            count => println!("watching {} file(s) for changes", count),
+        }
+
+        let changed = watcher.wait();
+        if io::stdout().is_terminal() {
+            print!("\x1b[2J\x1b[H");
+        }
+        let changed: Vec<String> = changed
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect();
+        println!("---- {} changed ----", changed.join(", "));
+    }
+}
+
+fn main() {
+    // the interpreter and the REPL run on threads of their own, a panic there
+    // ends the process right away instead of unwinding into `main`
+    let report = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        report(info);
+        eprintln!("error: the compiler crashed, this is a bug in ez");
+        std::process::exit(INTERNAL_ERROR);
+    }));
+
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("error: {}", message);
+            std::process::exit(Failure::Usage.code());
+        }
+    };
+
+    // the REPL needs the deep stack of the interpreter, like `run`
+    if options.mode == Mode::Repl {
+        std::thread::Builder::new()
+            .stack_size(interp::STACK_SIZE)
+            .spawn(move || Repl::new().with_color(options.color).run())
+            .expect("failed to start the REPL")
+            .join()
+            .expect("the REPL panicked");
+        return;
+    }
+
+    if options.mode == Mode::Lsp {
+        let result = Server::new().run(io::stdin().lock(), io::stdout().lock());
+        if let Err(error) = result {
+            report_error(&format!("the language server stopped: {}", error), &options);
+            std::process::exit(Failure::Io.code());
+        }
+        return;
+    }
+
+    if options.mode == Mode::Serve {
+        // a program that crashes the compiler only ends the thread answering
+        // for it, not the server
+        let _ = std::panic::take_hook();
+        let result = serve::Server::bind(&options.listen).and_then(|server| {
+            let server = server.with_limits(options.limits);
+            eprintln!("the playground is at http://{}", server.address()?);
+            server.run()
+        });
+        if let Err(error) = result {
+            report_error(&format!("the playground stopped: {}", error), &options);
+            std::process::exit(Failure::Io.code());
+        }
+        return;
+    }
+
+    if options.mode == Mode::Grammar {
+        if let Err(error) = write_grammar(&options) {
+            report_error(&error, &options);
+            std::process::exit(Failure::Io.code());
+        }
+        return;
+    }
+
+    if options.mode == Mode::Disasm {
+        if let Err(error) = disassemble(&options) {
+            report_error(&error, &options);
+            std::process::exit(Failure::Io.code());
+        }
+        return;
+    }
+
+    if options.watch {
+        watch(&options);
+    }
+
+    if options.mode == Mode::Format {
+        format_files(&options);
+    }
+
+    let mut failed = None;
+    let mut paths = vec![];
+    for path in &options.paths {
+        let programs = programs(path);
+        if programs.is_empty() {
+            report_error(&format!("there are no programs in `{}`", path), &options);
+            failed = Some(Failure::Io);
+        }
+        paths.extend(programs);
+    }
+
+    if options.output.is_some() && paths.len() > 1 {
+        eprintln!("error: only one program can be built when the output is given");
+        std::process::exit(Failure::Usage.code());
+    }
+
+    if let Some(failure) = compile_all(&paths, &options) {
+        failed = failed.or(Some(failure));
+    }
+
+    if let Some(failure) = failed {
+        std::process::exit(failure.code());
     }
 }
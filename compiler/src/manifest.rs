@@ -0,0 +1,542 @@
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use ez_check::lint::LINTS;
+use ez_diagnostics::Level;
+use ez_parser::{Edition, Feature, ParserOptions};
+
+// the file a project is described by, at its root
+pub const FILE: &str = "ez.toml";
+
+// a project, described by its `ez.toml`:
+//
+//     [package]
+//     name = "shapes"
+//     entry = "src/main.ez"
+//     edition = "2024"
+//     features = ["trailing_commas"]
+//
+//     [build]
+//     target = "js"
+//     output = "out/shapes.js"
+//
+//     [lints]
+//     unused_variables = "deny"
+//
+//     [dependencies]
+//     geometry = { path = "../geometry" }
+//     colors = { git = "https://example.com/colors.git", rev = "v1" }
+//
+// only the name is required. The entry defaults to `main.ez`, and the output
+// to the name with the extension of the target, both next to the manifest.
+// The edition defaults to the latest, and no feature is enabled unless listed
+#[derive(Debug)]
+pub struct Manifest {
+    // where the manifest is, its paths are relative to it
+    pub dir: PathBuf,
+    pub name: String,
+    pub entry: PathBuf,
+    // the edition and features its files are parsed with
+    pub options: ParserOptions,
+    // checked by the command line, which knows the targets
+    pub target: Option<String>,
+    pub output: Option<PathBuf>,
+    // in the order they're written, `warnings` sets every lint
+    pub lints: Vec<(&'static str, Level)>,
+    pub dependencies: Vec<Dependency>,
+}
+
+// another project the program uses, by its name
+#[derive(Debug, Clone)]
+pub struct Dependency {
+    pub name: String,
+    pub source: Source,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Source {
+    // relative to the manifest
+    Path(PathBuf),
+    // `rev` is a branch, a tag or a commit, the default branch without one
+    Git { url: String, rev: Option<String> },
+}
+
+#[derive(Debug)]
+pub enum ManifestError {
+    Io {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+    // the line is 1-based
+    Syntax {
+        path: PathBuf,
+        line: usize,
+        message: String,
+    },
+    Invalid {
+        path: PathBuf,
+        message: String,
+    },
+}
+
+impl fmt::Display for ManifestError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ManifestError::Io { path, error } => {
+                write!(f, "couldn't read `{}`: {}", path.display(), error)
+            }
+            ManifestError::Syntax {
+                path,
+                line,
+                message,
+            } => write!(f, "{}:{}: {}", path.display(), line, message),
+            ManifestError::Invalid { path, message } => {
+                write!(f, "{}: {}", path.display(), message)
+            }
+        }
+    }
+}
+
+// the values of the part of TOML manifests use: strings without escapes other
+// than `\"`, `\\`, `\n` and `\t`, integers, booleans, arrays and inline tables
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    String(String),
+    Integer(i64),
+    Bool(bool),
+    Array(Vec<Value>),
+    Table(Vec<(String, Value)>),
+}
+
+impl Value {
+    fn kind(&self) -> &'static str {
+        match self {
+            Value::String(_) => "a string",
+            Value::Integer(_) => "an integer",
+            Value::Bool(_) => "a boolean",
+            Value::Array(_) => "an array",
+            Value::Table(_) => "a table",
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Table(entries) => entries
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+}
+
+struct ValueParser<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+}
+
+impl ValueParser<'_> {
+    fn skip_spaces(&mut self) {
+        while self.chars.next_if(|c| *c == ' ' || *c == '\t').is_some() {}
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        self.skip_spaces();
+        match self.chars.next() {
+            Some(c) if c == expected => Ok(()),
+            Some(c) => Err(format!("expected `{}`, found `{}`", expected, c)),
+            None => Err(format!("expected `{}`", expected)),
+        }
+    }
+
+    fn key(&mut self) -> Result<String, String> {
+        self.skip_spaces();
+        if self.chars.peek() == Some(&'"') {
+            return self.string();
+        }
+
+        let mut key = String::new();
+        while let Some(c) = self
+            .chars
+            .next_if(|c| c.is_ascii_alphanumeric() || *c == '_' || *c == '-')
+        {
+            key.push(c);
+        }
+
+        if key.is_empty() {
+            return Err("expected a key".into());
+        }
+        Ok(key)
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.chars.next();
+        let mut value = String::new();
+        loop {
+            match self.chars.next() {
+                Some('"') => return Ok(value),
+                Some('\\') => match self.chars.next() {
+                    Some('"') => value.push('"'),
+                    Some('\\') => value.push('\\'),
+                    Some('n') => value.push('\n'),
+                    Some('t') => value.push('\t'),
+                    Some(c) => return Err(format!("unknown escape `\\{}`", c)),
+                    None => return Err("unterminated string".into()),
+                },
+                Some(c) => value.push(c),
+                None => return Err("unterminated string".into()),
+            }
+        }
+    }
+
+    // values that span lines aren't supported, arrays and tables included
+    fn value(&mut self) -> Result<Value, String> {
+        self.skip_spaces();
+        match self.chars.peek() {
+            Some('"') => self.string().map(Value::String),
+            Some('[') => {
+                self.chars.next();
+                let mut items = vec![];
+                loop {
+                    self.skip_spaces();
+                    if self.chars.next_if_eq(&']').is_some() {
+                        return Ok(Value::Array(items));
+                    }
+                    items.push(self.value()?);
+                    self.skip_spaces();
+                    if self.chars.next_if_eq(&',').is_none() {
+                        self.expect(']')?;
+                        return Ok(Value::Array(items));
+                    }
+                }
+            }
+            Some('{') => {
+                self.chars.next();
+                let mut entries = vec![];
+                self.skip_spaces();
+                if self.chars.next_if_eq(&'}').is_some() {
+                    return Ok(Value::Table(entries));
+                }
+                loop {
+                    let key = self.key()?;
+                    self.expect('=')?;
+                    entries.push((key, self.value()?));
+                    self.skip_spaces();
+                    if self.chars.next_if_eq(&',').is_none() {
+                        self.expect('}')?;
+                        return Ok(Value::Table(entries));
+                    }
+                }
+            }
+            Some(_) => {
+                let mut word = String::new();
+                while let Some(c) = self
+                    .chars
+                    .next_if(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '+' || *c == '_')
+                {
+                    word.push(c);
+                }
+
+                match word.as_str() {
+                    "true" => Ok(Value::Bool(true)),
+                    "false" => Ok(Value::Bool(false)),
+                    _ => word
+                        .replace('_', "")
+                        .parse()
+                        .map(Value::Integer)
+                        .map_err(|_| format!("invalid value `{}`", word)),
+                }
+            }
+            None => Err("expected a value".into()),
+        }
+    }
+
+    // only a comment can come after a value
+    fn end(&mut self) -> Result<(), String> {
+        self.skip_spaces();
+        match self.chars.next() {
+            None | Some('#') => Ok(()),
+            Some(c) => Err(format!("unexpected `{}` after the value", c)),
+        }
+    }
+}
+
+// the sections of the document as tables, keys outside of a section are in the
+// one named ""
+pub fn parse_toml(text: &str) -> Result<Vec<(String, Value)>, (usize, String)> {
+    let mut sections: Vec<(String, Value)> = vec![(String::new(), Value::Table(vec![]))];
+
+    for (index, line) in text.lines().enumerate() {
+        let error = |message: String| (index + 1, message);
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+
+        let mut parser = ValueParser {
+            chars: trimmed.chars().peekable(),
+        };
+
+        if parser.chars.next_if_eq(&'[').is_some() {
+            let name = parser.key().map_err(error)?;
+            parser.expect(']').map_err(error)?;
+            parser.end().map_err(error)?;
+            if sections.iter().any(|(section, _)| *section == name) {
+                return Err(error(format!("the section `{}` is there twice", name)));
+            }
+            sections.push((name, Value::Table(vec![])));
+            continue;
+        }
+
+        let key = parser.key().map_err(error)?;
+        parser.expect('=').map_err(error)?;
+        let value = parser.value().map_err(error)?;
+        parser.end().map_err(error)?;
+
+        let Some((_, Value::Table(entries))) = sections.last_mut() else {
+            unreachable!("sections are tables");
+        };
+        if entries.iter().any(|(name, _)| *name == key) {
+            return Err(error(format!("the key `{}` is there twice", key)));
+        }
+        entries.push((key, value));
+    }
+
+    Ok(sections)
+}
+
+impl Manifest {
+    // the manifest of the directory, or of the closest directory above it
+    // that has one. The path is relative to the directory, so the ones of the
+    // files of the project are short in diagnostics
+    pub fn find(dir: &Path) -> Option<PathBuf> {
+        let depth = dir
+            .ancestors()
+            .position(|ancestor| ancestor.join(FILE).is_file())?;
+
+        let mut path: PathBuf = std::iter::repeat_n("..", depth).collect();
+        path.push(FILE);
+        Some(path)
+    }
+
+    // programs use dependencies by their name, so it has to be one they can write
+    fn dependency(name: &str, value: &Value, dir: &Path) -> Result<Dependency, String> {
+        let mut chars = name.chars();
+        let is_identifier = chars
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !is_identifier {
+            return Err(format!(
+                "the dependency `{}` needs a name programs can use, like `{}`",
+                name,
+                name.replace('-', "_")
+            ));
+        }
+
+        let string = |key: &str| match value.get(key) {
+            None => Ok(None),
+            Some(Value::String(value)) => Ok(Some(value.clone())),
+            Some(value) => Err(format!(
+                "`{}` of the dependency `{}` should be a string, not {}",
+                key,
+                name,
+                value.kind()
+            )),
+        };
+
+        let Value::Table(entries) = value else {
+            return Err(format!(
+                "the dependency `{}` should be a table, like `{{ path = \"../{}\" }}`",
+                name, name
+            ));
+        };
+        if let Some((key, _)) = entries
+            .iter()
+            .find(|(key, _)| !["path", "git", "rev"].contains(&key.as_str()))
+        {
+            return Err(format!(
+                "unknown key `{}` for the dependency `{}`",
+                key, name
+            ));
+        }
+
+        // they're given to git, which would take one starting with `-` as an option
+        for key in ["git", "rev"] {
+            if string(key)?.is_some_and(|value| value.starts_with('-')) {
+                return Err(format!(
+                    "`{}` of the dependency `{}` can't start with `-`",
+                    key, name
+                ));
+            }
+        }
+
+        let source = match (string("path")?, string("git")?, string("rev")?) {
+            (Some(path), None, None) => Source::Path(dir.join(path)),
+            (None, Some(url), rev) => Source::Git { url, rev },
+            (Some(_), None, Some(_)) => {
+                return Err(format!(
+                    "the dependency `{}` has a `rev`, which only git dependencies can have",
+                    name
+                ))
+            }
+            (Some(_), Some(_), _) => {
+                return Err(format!(
+                    "the dependency `{}` has both a `path` and a `git`, it can only have one",
+                    name
+                ))
+            }
+            (None, None, _) => {
+                return Err(format!(
+                    "the dependency `{}` needs a `path` or a `git`",
+                    name
+                ))
+            }
+        };
+
+        Ok(Dependency {
+            name: name.to_string(),
+            source,
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<Manifest, ManifestError> {
+        let text = fs::read_to_string(path).map_err(|error| ManifestError::Io {
+            path: path.to_path_buf(),
+            error,
+        })?;
+
+        let sections = parse_toml(&text).map_err(|(line, message)| ManifestError::Syntax {
+            path: path.to_path_buf(),
+            line,
+            message,
+        })?;
+
+        let invalid = |message: String| ManifestError::Invalid {
+            path: path.to_path_buf(),
+            message,
+        };
+        let section = |name: &str| {
+            sections
+                .iter()
+                .find(|(section, _)| section == name)
+                .map(|(_, table)| table)
+        };
+        // a string in a section, when it's there
+        let string = |name: &str, key: &str| match section(name).and_then(|table| table.get(key)) {
+            None => Ok(None),
+            Some(Value::String(value)) => Ok(Some(value.clone())),
+            Some(value) => Err(invalid(format!(
+                "`{}.{}` should be a string, not {}",
+                name,
+                key,
+                value.kind()
+            ))),
+        };
+
+        // the lints are checked on their own
+        for (name, table) in &sections {
+            let keys: &[&str] = match name.as_str() {
+                "" => &[],
+                "package" => &["name", "entry", "edition", "features"],
+                "build" => &["target", "output"],
+                "lints" | "dependencies" => continue,
+                _ => return Err(invalid(format!("unknown section `{}`", name))),
+            };
+
+            let Value::Table(entries) = table else {
+                unreachable!("sections are tables");
+            };
+            if let Some((key, _)) = entries
+                .iter()
+                .find(|(key, _)| !keys.contains(&key.as_str()))
+            {
+                return Err(match name.as_str() {
+                    "" => invalid(format!("`{}` should be in a section", key)),
+                    _ => invalid(format!("unknown key `{}.{}`", name, key)),
+                });
+            }
+        }
+
+        let dir = path.parent().unwrap_or(Path::new("")).to_path_buf();
+        let Some(name) = string("package", "name")? else {
+            return Err(invalid(
+                "the package needs a name, like `name = \"app\"`".into(),
+            ));
+        };
+        let entry = dir.join(string("package", "entry")?.unwrap_or("main.ez".into()));
+
+        let mut options = ParserOptions::default();
+        if let Some(edition) = string("package", "edition")? {
+            options.edition = Edition::parse(&edition)
+                .ok_or_else(|| invalid(format!("unknown edition `{}`", edition)))?;
+        }
+        match section("package").and_then(|table| table.get("features")) {
+            None => {}
+            Some(Value::Array(features)) => {
+                for feature in features {
+                    let Value::String(name) = feature else {
+                        return Err(invalid(format!(
+                            "the features should be strings, not {}",
+                            feature.kind()
+                        )));
+                    };
+                    let feature = Feature::parse(name)
+                        .ok_or_else(|| invalid(format!("unknown feature `{}`", name)))?;
+                    options.features.push(feature);
+                }
+            }
+            Some(value) => {
+                return Err(invalid(format!(
+                    "`package.features` should be an array, not {}",
+                    value.kind()
+                )))
+            }
+        }
+
+        let target = string("build", "target")?;
+        let output = string("build", "output")?.map(|output| dir.join(output));
+
+        let mut lints = vec![];
+        if let Some(Value::Table(entries)) = section("lints") {
+            for (lint, level) in entries {
+                let lint = lint.replace('-', "_");
+                let lint = match LINTS.iter().find(|known| **known == lint) {
+                    Some(lint) => *lint,
+                    None if lint == "warnings" => "warnings",
+                    None => return Err(invalid(format!("unknown lint `{}`", lint))),
+                };
+
+                let level = match level {
+                    Value::String(level) => Level::parse(level),
+                    _ => None,
+                };
+                let Some(level) = level else {
+                    return Err(invalid(format!(
+                        "the level of `{}` should be \"allow\", \"warn\" or \"deny\"",
+                        lint
+                    )));
+                };
+                lints.push((lint, level));
+            }
+        }
+
+        let mut dependencies = vec![];
+        if let Some(Value::Table(entries)) = section("dependencies") {
+            for (name, value) in entries {
+                dependencies.push(Self::dependency(name, value, &dir).map_err(invalid)?);
+            }
+        }
+
+        Ok(Manifest {
+            dir,
+            name,
+            entry,
+            options,
+            target,
+            output,
+            lints,
+            dependencies,
+        })
+    }
+}
@@ -0,0 +1,201 @@
+use std::fmt::Write;
+
+use ez_ast::{Expr, Program, ValueExpr};
+use ez_diagnostics::SourceMap;
+use ez_lexer::Span;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SymbolKind {
+    // a file the program uses
+    Module,
+    Function,
+    // a top level declaration that isn't `mut`
+    Constant,
+    Variable,
+    Enum,
+    Variant,
+    Test,
+}
+
+impl SymbolKind {
+    // how it's written in the outline `ez outline` prints
+    pub fn keyword(self) -> &'static str {
+        match self {
+            SymbolKind::Module => "use",
+            SymbolKind::Function => "fn",
+            SymbolKind::Constant => "const",
+            SymbolKind::Variable => "mut",
+            SymbolKind::Enum => "enum",
+            SymbolKind::Variant => "variant",
+            SymbolKind::Test => "test",
+        }
+    }
+}
+
+// a declaration of a file, with the ones nested in it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    // all of it, and its name
+    pub span: Span,
+    pub selection: Span,
+    pub children: Vec<Symbol>,
+}
+
+// what the file of the program declares, in order: the files it uses, then its
+// top level declarations with the variants of its enums, and the functions
+// declared in functions and tests however deep they're nested
+pub fn outline(program: &Program) -> Vec<Symbol> {
+    let mut symbols: Vec<Symbol> = program
+        .uses
+        .iter()
+        .map(|using| Symbol {
+            name: using
+                .path
+                .iter()
+                .map(|identifier| identifier.name.as_str())
+                .collect::<Vec<_>>()
+                .join("."),
+            kind: SymbolKind::Module,
+            span: using.span,
+            selection: using.name().span,
+            children: vec![],
+        })
+        .collect();
+
+    for expr in program.body {
+        let symbol = match expr {
+            Expr::Declaration {
+                identifier,
+                mutable,
+                value,
+                ..
+            } => {
+                let (kind, children) = match value.as_deref() {
+                    Some(ValueExpr::Function { body, .. }) => {
+                        (SymbolKind::Function, functions(body))
+                    }
+                    _ if *mutable => (SymbolKind::Variable, vec![]),
+                    _ => (SymbolKind::Constant, vec![]),
+                };
+                Symbol {
+                    name: identifier.name.to_string(),
+                    kind,
+                    span: expr.span(),
+                    selection: identifier.span,
+                    children,
+                }
+            }
+
+            Expr::Enum {
+                identifier,
+                variants,
+                span,
+            } => Symbol {
+                name: identifier.name.to_string(),
+                kind: SymbolKind::Enum,
+                span: *span,
+                selection: identifier.span,
+                children: variants
+                    .iter()
+                    .map(|variant| Symbol {
+                        name: variant.identifier.name.to_string(),
+                        kind: SymbolKind::Variant,
+                        span: variant.identifier.span,
+                        selection: variant.identifier.span,
+                        children: vec![],
+                    })
+                    .collect(),
+            },
+
+            // the name of a test is a string, there's only all of it to select
+            Expr::Test { name, body, span } => Symbol {
+                name: name.clone(),
+                kind: SymbolKind::Test,
+                span: *span,
+                selection: *span,
+                children: functions(body),
+            },
+
+            _ => continue,
+        };
+        symbols.push(symbol);
+    }
+
+    symbols
+}
+
+// the functions declared in a body, in its blocks too, each with its own
+fn functions(body: &[Expr]) -> Vec<Symbol> {
+    let mut symbols = vec![];
+    for expr in body {
+        match expr {
+            Expr::Declaration {
+                identifier,
+                value: Some(ValueExpr::Function { body, .. }),
+                ..
+            } => symbols.push(Symbol {
+                name: identifier.name.to_string(),
+                kind: SymbolKind::Function,
+                span: expr.span(),
+                selection: identifier.span,
+                children: functions(body),
+            }),
+
+            Expr::If {
+                body, else_body, ..
+            } => {
+                symbols.extend(functions(body));
+                symbols.extend(else_body.map(functions).unwrap_or_default());
+            }
+
+            Expr::While { body, .. }
+            | Expr::For { body, .. }
+            | Expr::Block { body, .. }
+            | Expr::Test { body, .. } => symbols.extend(functions(body)),
+
+            Expr::Try { body, handler, .. } => {
+                symbols.extend(functions(body));
+                symbols.extend(functions(handler));
+            }
+
+            Expr::Match { arms, .. } => {
+                for arm in arms.iter() {
+                    symbols.extend(functions(arm.body));
+                }
+            }
+
+            _ => {}
+        }
+    }
+    symbols
+}
+
+// the outline as text, a symbol a line with where it's declared, the nested ones
+// indented under it
+pub fn render(symbols: &[Symbol], sources: &SourceMap) -> String {
+    fn render_into(output: &mut String, symbols: &[Symbol], sources: &SourceMap, depth: usize) {
+        for symbol in symbols {
+            let location = sources.locate(symbol.selection.start);
+            let name = match symbol.kind {
+                SymbolKind::Test => format!("{:?}", symbol.name),
+                _ => symbol.name.clone(),
+            };
+            let _ = writeln!(
+                output,
+                "{}{} {} {}:{}",
+                "  ".repeat(depth),
+                symbol.kind.keyword(),
+                name,
+                location.line,
+                location.column
+            );
+            render_into(output, &symbol.children, sources, depth + 1);
+        }
+    }
+
+    let mut output = String::new();
+    render_into(&mut output, symbols, sources, 0);
+    output
+}
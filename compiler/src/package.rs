@@ -0,0 +1,180 @@
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::manifest::{self, Dependency, Manifest, ManifestError, Source};
+
+// where the git dependencies of a project are cloned, next to its manifest
+pub const VENDOR: &str = "vendor";
+
+// a dependency once it's on disk
+#[derive(Debug, Clone, PartialEq)]
+pub struct Package {
+    pub name: String,
+    pub dir: PathBuf,
+    // the file `use name;` stands for, the other files of the package are
+    // used like `use name.file;`
+    pub entry: PathBuf,
+}
+
+#[derive(Debug)]
+pub enum PackageError {
+    Manifest(ManifestError),
+    // the directory of the dependency has no manifest
+    NotAPackage {
+        name: String,
+        dir: PathBuf,
+    },
+    // `output` is what git printed
+    Git {
+        name: String,
+        url: String,
+        output: String,
+    },
+    // two dependencies of the same name that aren't the same project
+    Conflict {
+        name: String,
+        first: PathBuf,
+        second: PathBuf,
+    },
+}
+
+impl fmt::Display for PackageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PackageError::Manifest(error) => write!(f, "{}", error),
+            PackageError::NotAPackage { name, dir } => write!(
+                f,
+                "the dependency `{}` has no `{}` in `{}`",
+                name,
+                manifest::FILE,
+                dir.display()
+            ),
+            PackageError::Git { name, url, output } => write!(
+                f,
+                "couldn't fetch the dependency `{}` from `{}`: {}",
+                name,
+                url,
+                output.trim_end()
+            ),
+            PackageError::Conflict {
+                name,
+                first,
+                second,
+            } => write!(
+                f,
+                "there are two dependencies named `{}`, in `{}` and `{}`",
+                name,
+                first.display(),
+                second.display()
+            ),
+        }
+    }
+}
+
+fn git(args: &[&str]) -> Result<(), String> {
+    let output = Command::new("git")
+        .args(args)
+        .output()
+        .map_err(|error| format!("couldn't run `git`: {}", error))?;
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).into_owned())
+    }
+}
+
+// a clone that didn't get to the revision is removed, so it's fetched again
+fn fetch(
+    dependency: &Dependency,
+    url: &str,
+    rev: Option<&str>,
+    dir: &Path,
+) -> Result<(), PackageError> {
+    let error = |output: String| PackageError::Git {
+        name: dependency.name.clone(),
+        url: url.to_string(),
+        output,
+    };
+
+    let path = dir.to_string_lossy();
+    // the url is never an option and the revision never a file, the manifest
+    // doesn't take either starting with `-`
+    git(&["clone", "--quiet", "--", url, &path]).map_err(error)?;
+    if let Some(rev) = rev {
+        if let Err(output) = git(&["-C", &path, "checkout", "--quiet", rev, "--"]) {
+            let _ = fs::remove_dir_all(dir);
+            return Err(error(output));
+        }
+    }
+
+    Ok(())
+}
+
+fn same(first: &Path, second: &Path) -> bool {
+    match (fs::canonicalize(first), fs::canonicalize(second)) {
+        (Ok(first), Ok(second)) => first == second,
+        _ => first == second,
+    }
+}
+
+// the dependencies of the project and the ones of those, every file of the
+// program can use any of them. Git dependencies are cloned into the vendor
+// directory of the project when they aren't there yet, removing one from there
+// fetches it again
+pub fn resolve(project: &Manifest) -> Result<Vec<Package>, PackageError> {
+    let vendor = project.dir.join(VENDOR);
+    let mut packages: Vec<Package> = vec![];
+    let mut pending = project.dependencies.clone();
+    pending.reverse();
+
+    while let Some(dependency) = pending.pop() {
+        let dir = match &dependency.source {
+            Source::Path(path) => path.clone(),
+            Source::Git { url, rev } => {
+                let dir = vendor.join(&dependency.name);
+                if !dir.exists() {
+                    fetch(&dependency, url, rev.as_deref(), &dir)?;
+                }
+                dir
+            }
+        };
+
+        if let Some(package) = packages
+            .iter()
+            .find(|package| package.name == dependency.name)
+        {
+            if same(&package.dir, &dir) {
+                continue;
+            }
+
+            return Err(PackageError::Conflict {
+                name: dependency.name,
+                first: package.dir.clone(),
+                second: dir,
+            });
+        }
+
+        let path = dir.join(manifest::FILE);
+        if !path.is_file() {
+            return Err(PackageError::NotAPackage {
+                name: dependency.name,
+                dir,
+            });
+        }
+
+        // the path dependencies of a dependency are relative to it, its git
+        // ones go in the vendor directory of the project too
+        let manifest = Manifest::load(&path).map_err(PackageError::Manifest)?;
+        pending.extend(manifest.dependencies.into_iter().rev());
+        packages.push(Package {
+            name: dependency.name,
+            dir,
+            entry: manifest.entry,
+        });
+    }
+
+    Ok(packages)
+}
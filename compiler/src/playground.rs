@@ -0,0 +1,151 @@
+use std::cell::{Cell, RefCell};
+use std::io::{self, Write};
+use std::path::Path;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use ez_ast::Arena;
+use ez_check::hir::Hir;
+use ez_codegen::interp::Interpreter;
+use ez_diagnostics::render::Renderer;
+use ez_diagnostics::DiagnosticSink;
+
+use crate::session::{Files, Session};
+
+// what the program is called in its diagnostics
+pub const FILE: &str = "playground.ez";
+
+// a program of the playground is a single file, it can't use others
+struct Alone;
+
+impl Files for Alone {
+    fn read(&mut self, _path: &Path) -> io::Result<String> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "a program in the playground can't use other files",
+        ))
+    }
+}
+
+// what a program prints and logs, up to a limit, the rest is dropped
+#[derive(Clone)]
+struct Output {
+    printed: Rc<RefCell<Vec<u8>>>,
+    dropped: Rc<Cell<bool>>,
+    limit: usize,
+}
+
+impl Write for Output {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        let mut printed = self.printed.borrow_mut();
+        let room = self.limit.saturating_sub(printed.len());
+        if bytes.len() > room {
+            self.dropped.set(true);
+        }
+        printed.extend_from_slice(&bytes[..bytes.len().min(room)]);
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// how much a program can take of the machine running it
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    // how long it runs before it's stopped, it isn't without a clock
+    pub time: Option<Duration>,
+    // the bytes of what it prints and logs that are kept
+    pub output: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            time: Some(Duration::from_secs(5)),
+            output: 64 * 1024,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Outcome {
+    // without an error, warnings don't count
+    pub passed: bool,
+    // like the command line shows them without colors, the one the program
+    // stopped with included
+    pub diagnostics: String,
+    // what the program printed and logged
+    pub output: String,
+    // all of it went past the limit and was cut
+    pub truncated: bool,
+}
+
+// the program checked and lowered, the HIR is only there when it passed
+fn lower(source: &str, session: &mut Session, sink: &mut DiagnosticSink) -> Option<Hir> {
+    let arena = Arena::new();
+    let program = session.load_from(&mut Alone, &arena, FILE, source.to_string(), sink)?;
+    let (resolutions, types) = crate::check(&program, &[], sink)?;
+    if sink.has_errors() {
+        return None;
+    }
+
+    let hir = crate::lower(&program, &resolutions, &types, sink);
+    (!sink.has_errors()).then_some(hir)
+}
+
+// what `ez check` says about the program
+pub fn compile(source: &str) -> Outcome {
+    let mut session = Session::new();
+    let mut sink = DiagnosticSink::default();
+    lower(source, &mut session, &mut sink);
+
+    Outcome {
+        passed: !sink.has_errors(),
+        diagnostics: sink.render(&Renderer::default(), &session.sources),
+        ..Outcome::default()
+    }
+}
+
+// the program checked and run by the interpreter, in a sandbox, so it can't
+// touch anything outside of itself, and within the limits. It needs the deep
+// stack of the interpreter, like `ez run`
+pub fn run(source: &str, limits: Limits) -> Outcome {
+    let mut session = Session::new();
+    let mut sink = DiagnosticSink::default();
+    let Some(hir) = lower(source, &mut session, &mut sink) else {
+        return Outcome {
+            passed: false,
+            diagnostics: sink.render(&Renderer::default(), &session.sources),
+            ..Outcome::default()
+        };
+    };
+
+    let output = Output {
+        printed: Rc::default(),
+        dropped: Rc::default(),
+        limit: limits.output,
+    };
+    let mut interpreter = Interpreter::new(&hir)
+        .with_output(output.clone())
+        .with_logs(output.clone())
+        .with_sandbox();
+    if let Some(time) = limits.time {
+        interpreter = interpreter.with_deadline(Instant::now() + time);
+    }
+    let result = interpreter.run();
+
+    let passed = result.is_ok();
+    if let Err(error) = result {
+        sink.push(error);
+    }
+
+    let printed = output.printed.borrow();
+    Outcome {
+        passed,
+        diagnostics: sink.render(&Renderer::default(), &session.sources),
+        output: String::from_utf8_lossy(&printed).into_owned(),
+        truncated: output.dropped.get(),
+    }
+}
@@ -0,0 +1,81 @@
+use ez_ast::Program;
+use ez_check::hir::Hir;
+use ez_check::resolver::Resolutions;
+use ez_check::typecheck::Types;
+use ez_diagnostics::{Diagnostic, DiagnosticSink};
+
+// a pass of a program embedding ez, run by the compiler along with its own.
+// Each phase is optional: once the program is parsed, where it can still be
+// changed before it's checked, once it passed the checks, with the names and
+// types that were found, and once it's lowered, where the HIR can be changed
+// before it's run. What a phase reports is reported like the compiler's own
+// diagnostics, an error stops the program before the next phase
+pub trait Plugin: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    fn parsed(&self, _program: &mut Program) -> Vec<Diagnostic> {
+        vec![]
+    }
+
+    fn checked(
+        &self,
+        _program: &Program,
+        _resolutions: &Resolutions,
+        _types: &Types,
+    ) -> Vec<Diagnostic> {
+        vec![]
+    }
+
+    fn lowered(&self, _hir: &mut Hir) -> Vec<Diagnostic> {
+        vec![]
+    }
+}
+
+// the plugins of a compilation, run in the order they were added
+#[derive(Default)]
+pub struct Plugins {
+    plugins: Vec<Box<dyn Plugin>>,
+}
+
+impl Plugins {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_plugin(mut self, plugin: impl Plugin + 'static) -> Self {
+        self.add(plugin);
+        self
+    }
+
+    pub fn add(&mut self, plugin: impl Plugin + 'static) {
+        self.plugins.push(Box::new(plugin));
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.plugins.iter().map(|plugin| plugin.name())
+    }
+
+    pub fn parsed(&self, program: &mut Program, sink: &mut DiagnosticSink) {
+        for plugin in &self.plugins {
+            sink.extend(plugin.parsed(program));
+        }
+    }
+
+    pub fn checked(
+        &self,
+        program: &Program,
+        resolutions: &Resolutions,
+        types: &Types,
+        sink: &mut DiagnosticSink,
+    ) {
+        for plugin in &self.plugins {
+            sink.extend(plugin.checked(program, resolutions, types));
+        }
+    }
+
+    pub fn lowered(&self, hir: &mut Hir, sink: &mut DiagnosticSink) {
+        for plugin in &self.plugins {
+            sink.extend(plugin.lowered(hir));
+        }
+    }
+}
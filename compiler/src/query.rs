@@ -0,0 +1,441 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use ez_ast::{Arena, BaseType, Program};
+use ez_check::resolver::{DefId, DefKind, Reference, Resolutions, Resolver};
+use ez_check::typecheck::Types;
+use ez_diagnostics::{Diagnostic, DiagnosticSink, LintLevels, SourceMap};
+use ez_lexer::{Lexer, Span, Token};
+use ez_parser::{Parser, ParserOptions};
+
+use crate::doc;
+use crate::package::Package;
+use crate::session::{Files, Session};
+
+// a point in the history of the inputs, every change to them makes a new one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub struct Revision(u64);
+
+// the text of a file, or why it couldn't be read
+type Text = Result<String, (io::ErrorKind, String)>;
+
+struct Source {
+    text: Text,
+    // the revision it last changed in, and the last one it was read in
+    changed: Revision,
+    verified: Revision,
+}
+
+// what was found from other results, remembered until one of them changes
+struct Memo<T> {
+    value: Rc<T>,
+    changed: Revision,
+    verified: Revision,
+}
+
+struct Lexed {
+    tokens: Rc<[Token]>,
+    // the files after this one start where it ends, their spans move when it
+    // gets longer or shorter, even if its tokens stay the same
+    len: usize,
+}
+
+// what checking a program found, besides the files it's made of
+#[derive(Debug)]
+pub struct Checked {
+    // the files of the program, in the order they were loaded
+    pub files: Vec<String>,
+    // the files it uses, by the index of their module
+    pub modules: Vec<String>,
+    pub diagnostics: Vec<Diagnostic>,
+    pub resolutions: Option<Resolutions>,
+    pub types: Option<Types>,
+}
+
+struct Check {
+    checked: Memo<Checked>,
+    // what the result was found from, the settings it was checked with and the
+    // files it read, the ones that couldn't be read too
+    levels: LintLevels,
+    options: ParserOptions,
+    packages: Vec<Package>,
+    read: Vec<PathBuf>,
+}
+
+// what's at an offset of a program, for a hover
+#[derive(Debug)]
+pub struct TypeAt {
+    pub span: Span,
+    // `None` when it couldn't be typed
+    pub basetype: Option<BaseType>,
+    // what the name there declares or uses, when it's one
+    pub definition: Option<DefId>,
+    // the `///` comments above its declaration
+    pub docs: String,
+}
+
+pub struct Analysis {
+    // the files of the program, as they are now
+    pub sources: SourceMap,
+    pub checked: Rc<Checked>,
+}
+
+// the stages of the compiler as queries, from the text of files to their tokens,
+// to their trees and what's found checking them. Results are remembered, and
+// found again only when what they were found from changes, so a change to a
+// file doesn't check the programs that don't use it again. Tokens that come out
+// the same stop a change there, like one to a comment. Trees aren't remembered,
+// they live in an arena, but they're made again the same from the same tokens,
+// with the same node ids, so what was found for them still holds
+#[derive(Default)]
+pub struct Database {
+    revision: Revision,
+    // the files given instead of the ones on disk, like the ones open in an
+    // editor, by canonical path
+    open: HashMap<PathBuf, String>,
+    sources: HashMap<PathBuf, Source>,
+    tokens: HashMap<PathBuf, Memo<Lexed>>,
+    checks: HashMap<PathBuf, Check>,
+}
+
+impl Database {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn revision(&self) -> Revision {
+        self.revision
+    }
+
+    fn bump(&mut self) {
+        self.revision = Revision(self.revision.0 + 1);
+    }
+
+    // the file reads as the text given until it's closed
+    pub fn open(&mut self, path: &Path, text: String) {
+        self.open.insert(Session::key(path), text);
+        self.bump();
+    }
+
+    pub fn close(&mut self, path: &Path) {
+        self.open.remove(&Session::key(path));
+        self.bump();
+    }
+
+    // the files on disk are read again, they could have changed
+    pub fn reload(&mut self) {
+        self.bump();
+    }
+
+    // the text of the file, once for every revision
+    fn source(&mut self, path: &Path) -> &Source {
+        let key = Session::key(path);
+        let revision = self.revision;
+        let text = match self.sources.get(&key) {
+            Some(source) if source.verified == revision => None,
+            _ => Some(match self.open.get(&key) {
+                Some(text) => Ok(text.clone()),
+                None => fs::read_to_string(&key).map_err(|error| (error.kind(), error.to_string())),
+            }),
+        };
+
+        let source = self.sources.entry(key).or_insert(Source {
+            text: Err((io::ErrorKind::NotFound, String::new())),
+            changed: revision,
+            verified: revision,
+        });
+        if let Some(text) = text {
+            if text != source.text {
+                source.text = text;
+                source.changed = revision;
+            }
+            source.verified = revision;
+        }
+        source
+    }
+
+    pub fn text(&mut self, path: &Path) -> io::Result<&str> {
+        match &self.source(path).text {
+            Ok(text) => Ok(text),
+            Err((kind, message)) => Err(io::Error::new(*kind, message.clone())),
+        }
+    }
+
+    // the tokens of the file, its spans start at 0. `None` when it can't be read
+    pub fn tokens(&mut self, path: &Path) -> Option<Rc<[Token]>> {
+        let revision = self.revision;
+        let source = self.source(path);
+        let (changed, text) = match &source.text {
+            Ok(text) => (source.changed, text.clone()),
+            Err(_) => return None,
+        };
+
+        let key = Session::key(path);
+        if let Some(memo) = self.tokens.get_mut(&key) {
+            if memo.verified == revision || changed <= memo.verified {
+                memo.verified = revision;
+                return Some(memo.value.tokens.clone());
+            }
+        }
+
+        let lexed = Lexed {
+            tokens: Lexer::new(&text).collect(),
+            len: text.len(),
+        };
+        let memo = self.tokens.entry(key).or_insert(Memo {
+            value: Rc::new(Lexed {
+                tokens: Rc::new([]),
+                len: 0,
+            }),
+            changed: revision,
+            verified: revision,
+        });
+        if lexed.tokens != memo.value.tokens || lexed.len != memo.value.len {
+            memo.value = Rc::new(lexed);
+            memo.changed = revision;
+        }
+        memo.verified = revision;
+        Some(memo.value.tokens.clone())
+    }
+
+    // the revision the file last changed in, as far as what's found from its
+    // tokens is concerned
+    fn changed(&mut self, path: &Path) -> Revision {
+        match self.tokens(path) {
+            Some(_) => self.tokens[&Session::key(path)].changed,
+            None => self.source(path).changed,
+        }
+    }
+
+    // the tree of a file on its own, in the arena
+    pub fn parse<'a>(
+        &mut self,
+        path: &Path,
+        options: &ParserOptions,
+        arena: &'a Arena,
+    ) -> Option<Program<'a>> {
+        let tokens = self.tokens(path)?;
+        let source = self.source(path).text.clone().ok()?;
+        Parser::lexed(&source, &tokens, arena)
+            .with_options(options.clone())
+            .parse_program()
+            .ok()
+    }
+
+    // the program the file is the entry of, checked and lowered along with the
+    // files it uses. `None` when the file can't be read
+    pub fn check(
+        &mut self,
+        path: &Path,
+        levels: &LintLevels,
+        options: &ParserOptions,
+        packages: &[Package],
+    ) -> Option<Analysis> {
+        let key = Session::key(path);
+        let revision = self.revision;
+        if let Some(check) = self.checks.get(&key) {
+            let settings =
+                check.levels == *levels && check.options == *options && check.packages == packages;
+            let verified = check.checked.verified;
+            let read = check.read.clone();
+            if settings && read.iter().all(|file| self.changed(file) <= verified) {
+                let check = self.checks.get_mut(&key).unwrap();
+                check.checked.verified = revision;
+                let checked = check.checked.value.clone();
+                return Some(self.analysis(checked));
+            }
+        }
+
+        let text = self.text(path).ok()?.to_string();
+        let name = path.display().to_string();
+        let mut session = Session::new()
+            .with_packages(packages.to_vec())
+            .with_options(options.clone());
+        let mut sink = DiagnosticSink::with_levels(levels.clone());
+        let arena = Arena::new();
+        let mut reads = Reads {
+            database: self,
+            read: vec![key.clone()],
+        };
+        let program = session.load_from(&mut reads, &arena, &name, text, &mut sink);
+        let read = reads.read;
+
+        let mut resolutions = None;
+        let mut types = None;
+        if let Some(program) = &program {
+            match crate::check(program, &[], &mut sink) {
+                Some((resolved, typed)) => {
+                    if !sink.has_errors() {
+                        crate::lower(program, &resolved, &typed, &mut sink);
+                    }
+                    resolutions = Some(resolved);
+                    types = Some(typed);
+                }
+                // names can still be looked up when the types are wrong
+                None => resolutions = Resolver::new().resolve(program).ok(),
+            }
+        }
+
+        let checked = Rc::new(Checked {
+            files: session
+                .sources
+                .files()
+                .iter()
+                .map(|file| file.name.clone())
+                .collect(),
+            modules: program
+                .iter()
+                .flat_map(|program| &program.modules)
+                .map(|module| module.path.clone())
+                .collect(),
+            diagnostics: sink.diagnostics().to_vec(),
+            resolutions,
+            types,
+        });
+        self.checks.insert(
+            key,
+            Check {
+                checked: Memo {
+                    value: checked.clone(),
+                    changed: revision,
+                    verified: revision,
+                },
+                levels: levels.clone(),
+                options: options.clone(),
+                packages: packages.to_vec(),
+                read,
+            },
+        );
+        Some(self.analysis(checked))
+    }
+
+    // the files are laid out again with their text of now, which can differ from
+    // the one they were checked with by what doesn't change their tokens, like
+    // comments, but has the same length
+    fn analysis(&mut self, checked: Rc<Checked>) -> Analysis {
+        let mut sources = SourceMap::new();
+        for name in &checked.files {
+            let text = self.text(Path::new(name)).unwrap_or_default().to_string();
+            sources.add(name, text);
+        }
+
+        Analysis { sources, checked }
+    }
+}
+
+impl Analysis {
+    // the type of the binding or the value at the offset, with the docs of what
+    // a name declares. A name that isn't a value, like an enum, has no type
+    pub fn type_at(&self, offset: usize) -> Option<TypeAt> {
+        let resolutions = self.checked.resolutions.as_ref();
+        let types = self.checked.types.as_ref();
+
+        let name = resolutions.and_then(|resolutions| {
+            let node = resolutions.identifier_at(offset)?;
+            Some((
+                node,
+                resolutions.spans[&node],
+                resolutions.definition_of(node)?,
+            ))
+        });
+        let Some((node, span, id)) = name else {
+            let (span, basetype) = types?.at(offset)?;
+            return Some(TypeAt {
+                span,
+                basetype: Some(basetype.clone()),
+                definition: None,
+                docs: String::new(),
+            });
+        };
+
+        let basetype = types.and_then(|types| {
+            types
+                .values
+                .get(&node)
+                .or_else(|| types.definitions.get(&id))
+        });
+
+        Some(TypeAt {
+            span,
+            basetype: basetype.cloned(),
+            definition: Some(id),
+            docs: self.docs(id),
+        })
+    }
+
+    // the `///` comments above the declaration of the definition
+    pub fn docs(&self, id: DefId) -> String {
+        let Some(resolutions) = self.checked.resolutions.as_ref() else {
+            return String::new();
+        };
+
+        // builtins are declared nowhere, there's nothing above them
+        let declared = resolutions.definition(id).span;
+        if declared == Span::default() {
+            return String::new();
+        }
+        let file = self.sources.file(declared.start);
+        let line = file.source[..declared.start - file.start]
+            .rfind('\n')
+            .map_or(0, |newline| newline + 1);
+        doc::docs(file, Span::new(file.start + line, declared.end))
+    }
+
+    // the identifiers declaring and using what the identifier at the offset
+    // names, in every file of the program
+    pub fn references(&self, offset: usize) -> Vec<Reference> {
+        let Some(resolutions) = self.checked.resolutions.as_ref() else {
+            return vec![];
+        };
+        let id = resolutions
+            .identifier_at(offset)
+            .and_then(|node| resolutions.definition_of(node));
+        id.map(|id| resolutions.references(id)).unwrap_or_default()
+    }
+
+    // where what the identifier at the offset names is declared, among the
+    // files of the program. A file that's used is where it starts, builtins
+    // aren't declared anywhere
+    pub fn definition(&self, offset: usize) -> Option<Span> {
+        let resolutions = self.checked.resolutions.as_ref()?;
+        let node = resolutions.identifier_at(offset)?;
+        let definition = resolutions.definition(resolutions.definition_of(node)?);
+
+        match definition.kind {
+            DefKind::Builtin(_) | DefKind::Module(_) | DefKind::Host => None,
+            DefKind::Import(module) => {
+                let path = self.checked.modules.get(module)?;
+                let file = self
+                    .sources
+                    .files()
+                    .iter()
+                    .find(|file| file.name == *path)?;
+                Some(Span::new(file.start, file.start))
+            }
+            DefKind::Variable | DefKind::Function | DefKind::Param | DefKind::Enum => {
+                Some(definition.span)
+            }
+        }
+    }
+}
+
+// the files a session loads from the database, remembered as what its result
+// depends on
+struct Reads<'d> {
+    database: &'d mut Database,
+    read: Vec<PathBuf>,
+}
+
+impl Files for Reads<'_> {
+    fn read(&mut self, path: &Path) -> io::Result<String> {
+        self.read.push(Session::key(path));
+        self.database.text(path).map(str::to_string)
+    }
+
+    fn lexed(&mut self, path: &Path) -> Option<Rc<[Token]>> {
+        self.database.tokens(path)
+    }
+}
@@ -0,0 +1,180 @@
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::rc::Rc;
+
+use ez_ast::{Arena, Program};
+use ez_check::hir::Hir;
+use ez_codegen::interp::{Env, Interpreter};
+use ez_diagnostics::render::{ColorChoice, Renderer};
+use ez_diagnostics::SourceMap;
+use ez_diagnostics::{Diagnostic, DiagnosticSink, Level, LintLevels};
+use ez_lexer::{Lexer, TokenKind};
+use ez_parser::Parser;
+
+// what the inputs are called in diagnostics
+const FILE: &str = "<repl>";
+
+// every input is checked along with the ones accepted before it, as if they
+// were one program, but only its own statements are run, with the top level
+// variables kept from input to input
+pub struct Repl {
+    // the inputs that were accepted so far
+    source: String,
+    // how many top level statements of the program already ran
+    executed: usize,
+    globals: Rc<Env<'static>>,
+    color: bool,
+}
+
+impl Default for Repl {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Repl {
+    pub fn new() -> Self {
+        Self {
+            source: String::new(),
+            executed: 0,
+            globals: Rc::default(),
+            color: ColorChoice::Auto.enabled(io::stdout().is_terminal()),
+        }
+    }
+
+    pub fn with_color(mut self, color: ColorChoice) -> Self {
+        self.color = color.enabled(io::stdout().is_terminal());
+        self
+    }
+
+    // an input with unclosed braces, brackets or parentheses continues on the next line
+    fn is_unfinished(input: &str) -> bool {
+        let mut lexer = Lexer::new(input);
+        let mut depth = 0;
+        while let Some(token) = lexer.tokenize() {
+            match token.kind {
+                TokenKind::LeftCurly | TokenKind::LeftParen | TokenKind::LeftBracket => depth += 1,
+                TokenKind::RightCurly | TokenKind::RightParen | TokenKind::RightBracket => {
+                    depth -= 1
+                }
+                _ => {}
+            }
+        }
+
+        depth > 0
+    }
+
+    fn report(&self, sink: &DiagnosticSink, source: &str) {
+        print!(
+            "{}",
+            sink.render(
+                &Renderer::for_terminal(self.color),
+                &SourceMap::single(FILE, source)
+            )
+        );
+    }
+
+    // expressions don't need a `;` when they're typed on their own, so an input
+    // that doesn't parse is tried again with one
+    fn parse<'a>(
+        &self,
+        arena: &'a Arena,
+        input: &str,
+    ) -> Result<(String, Program<'a>), Diagnostic> {
+        let source = format!("{}{}", self.source, input);
+        let error = match Parser::new(&source, arena).parse_program() {
+            Ok(program) => return Ok((source, program)),
+            Err(error) => error,
+        };
+
+        let terminated = format!("{}{};\n", self.source, input.trim_end());
+        match Parser::new(&terminated, arena).parse_program() {
+            Ok(program) => Ok((terminated, program)),
+            Err(_) => Err(error.into()),
+        }
+    }
+
+    fn eval(&mut self, input: &str) {
+        // the next input is often what uses a variable, so lints would only be noise
+        let mut levels = LintLevels::default();
+        levels.set_all(Level::Allow);
+        let mut sink = DiagnosticSink::with_levels(levels);
+
+        let arena = Arena::new();
+        let (source, program) = match self.parse(&arena, input) {
+            Ok(parsed) => parsed,
+            Err(diagnostic) => {
+                sink.push(diagnostic);
+                let source = format!("{}{}", self.source, input);
+                self.report(&sink, &source);
+                return;
+            }
+        };
+
+        let checked = crate::check(&program, &[], &mut sink);
+        let hir = match (checked, sink.has_errors()) {
+            (Some((resolutions, types)), false) => {
+                crate::lower(&program, &resolutions, &types, &mut sink)
+            }
+            _ => {
+                self.report(&sink, &source);
+                return;
+            }
+        };
+
+        if sink.has_errors() {
+            self.report(&sink, &source);
+            return;
+        }
+
+        // functions made by an input point into its HIR, so the HIR of every
+        // input that ran lives as long as the session
+        let hir: &'static Hir = Box::leak(Box::new(hir));
+        match Interpreter::with_globals(hir, self.globals.clone()).run_from(self.executed) {
+            Ok(values) => {
+                for value in values {
+                    println!("{}", value);
+                }
+
+                self.source = source;
+                self.executed = hir.body.len();
+            }
+            Err(error) => {
+                sink.push(error);
+                self.report(&sink, &source);
+            }
+        }
+    }
+
+    pub fn run(mut self) {
+        let stdin = io::stdin();
+        let interactive = stdin.is_terminal();
+        let mut input = String::new();
+
+        loop {
+            if interactive {
+                print!("{}", if input.is_empty() { "> " } else { ". " });
+                io::stdout().flush().expect("failed to write the prompt");
+            }
+
+            let mut line = String::new();
+            match stdin.lock().read_line(&mut line) {
+                Ok(0) | Err(_) => break,
+                Ok(_) => input.push_str(&line),
+            }
+
+            if Self::is_unfinished(&input) {
+                continue;
+            }
+
+            if !input.trim().is_empty() {
+                self.eval(&input);
+            }
+            input.clear();
+        }
+
+        // what's left when the input ends is reported as unfinished
+        if !input.trim().is_empty() {
+            self.eval(&input);
+        }
+    }
+}
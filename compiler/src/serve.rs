@@ -0,0 +1,270 @@
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use ez_check::json::Json;
+use ez_codegen::interp;
+
+use crate::playground::{self, Limits, Outcome};
+
+// the address `ez serve` listens on without `--listen`
+pub const ADDRESS: &str = "127.0.0.1:8080";
+
+// the most programs checked or run at once, the others are turned away
+const MAX_RUNNING: usize = 8;
+
+// the biggest program taken, and the most the headers of a request can take
+const MAX_BODY: usize = 64 * 1024;
+const MAX_HEAD: usize = 16 * 1024;
+
+// a client that stops sending or reading is dropped after this long
+const IDLE: Duration = Duration::from_secs(10);
+
+// a page to write programs in and run them, served at `/`
+const PAGE: &str = r#"<!doctype html>
+<meta charset="utf-8">
+<title>ez playground</title>
+<style>
+  body { font: 14px sans-serif; margin: 2em; }
+  textarea, pre { width: 100%; box-sizing: border-box; font: 13px monospace; }
+  textarea { height: 20em; }
+</style>
+<textarea id="source">println("hello");</textarea>
+<p><button id="run">Run</button> <button id="compile">Check</button></p>
+<pre id="output"></pre>
+<script>
+  for (const action of ["run", "compile"]) {
+    document.getElementById(action).onclick = async () => {
+      const source = document.getElementById("source").value;
+      const response = await fetch("/" + action, { method: "POST", body: source });
+      const outcome = await response.json();
+      document.getElementById("output").textContent =
+        outcome.error ?? outcome.output + outcome.diagnostics;
+    };
+  }
+</script>
+"#;
+
+struct Response {
+    status: &'static str,
+    content_type: &'static str,
+    body: String,
+}
+
+impl Response {
+    fn json(status: &'static str, json: Json) -> Self {
+        Self {
+            status,
+            content_type: "application/json",
+            body: json.to_string(),
+        }
+    }
+
+    fn error(status: &'static str, message: &str) -> Self {
+        let message = Json::String(message.to_string());
+        Self::json(status, Json::Object(vec![("error".into(), message)]))
+    }
+
+    fn outcome(outcome: Outcome) -> Self {
+        Self::json(
+            "200 OK",
+            Json::Object(vec![
+                ("passed".into(), Json::Bool(outcome.passed)),
+                ("diagnostics".into(), Json::String(outcome.diagnostics)),
+                ("output".into(), Json::String(outcome.output)),
+                ("truncated".into(), Json::Bool(outcome.truncated)),
+            ]),
+        )
+    }
+
+    // pages from other origins can use the playground too
+    fn write(&self, stream: &mut impl Write) -> io::Result<()> {
+        write!(
+            stream,
+            "HTTP/1.1 {}\r\nContent-Type: {}; charset=utf-8\r\nContent-Length: {}\r\n\
+             Access-Control-Allow-Origin: *\r\nAccess-Control-Allow-Methods: GET, POST\r\n\
+             Connection: close\r\n\r\n{}",
+            self.status,
+            self.content_type,
+            self.body.len(),
+            self.body
+        )?;
+        stream.flush()
+    }
+}
+
+struct Request {
+    method: String,
+    path: String,
+    body: String,
+}
+
+// the request line, the headers up to the empty line, and the body as long as
+// the `Content-Length` says. A request the server can't take is answered with
+// why
+fn read_request(stream: &TcpStream) -> Result<Request, Response> {
+    let bad = |message: &str| Response::error("400 Bad Request", message);
+    let mut reader = BufReader::new(stream.take(MAX_HEAD as u64));
+
+    let mut line = String::new();
+    reader
+        .read_line(&mut line)
+        .map_err(|_| bad("the request couldn't be read"))?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(path)) = (parts.next(), parts.next()) else {
+        return Err(bad("the request line should have a method and a path"));
+    };
+    let (method, path) = (method.to_string(), path.to_string());
+
+    let mut len = 0;
+    loop {
+        line.clear();
+        match reader.read_line(&mut line) {
+            Ok(0) => return Err(bad("the headers don't end")),
+            Ok(_) if line.trim_end().is_empty() => break,
+            Ok(_) => {}
+            Err(_) => return Err(bad("the headers couldn't be read")),
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                len = value
+                    .trim()
+                    .parse()
+                    .map_err(|_| bad("the `Content-Length` isn't a number"))?;
+            }
+        }
+    }
+
+    if len > MAX_BODY {
+        return Err(Response::error(
+            "413 Payload Too Large",
+            &format!("programs can be up to {} bytes", MAX_BODY),
+        ));
+    }
+
+    // what was read past the headers is the start of the body
+    let mut body = reader.buffer().to_vec();
+    body.truncate(len);
+    let rest = len - body.len();
+    reader
+        .into_inner()
+        .into_inner()
+        .take(rest as u64)
+        .read_to_end(&mut body)
+        .map_err(|_| bad("the body couldn't be read"))?;
+    if body.len() < len {
+        return Err(bad("the body is shorter than its `Content-Length`"));
+    }
+    let body = String::from_utf8(body).map_err(|_| bad("the program isn't UTF-8"))?;
+
+    Ok(Request { method, path, body })
+}
+
+// a playground over HTTP, for a page to check and run programs on a server:
+//
+//     POST /compile   the program as the body, gives back its diagnostics
+//     POST /run       the program as the body, runs it in a sandbox
+//     GET /           a page to write programs in and run them
+//
+// The answers are JSON objects with the fields of `playground::Outcome`, or an
+// `error` saying what was wrong with the request. Every connection is a single
+// request, handled on a thread of its own, and programs run within the limits
+pub struct Server {
+    listener: TcpListener,
+    limits: Limits,
+    running: Arc<AtomicUsize>,
+}
+
+impl Server {
+    pub fn bind(address: &str) -> io::Result<Self> {
+        Ok(Self {
+            listener: TcpListener::bind(address)?,
+            limits: Limits::default(),
+            running: Arc::default(),
+        })
+    }
+
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    pub fn address(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    pub fn run(&self) -> io::Result<()> {
+        for stream in self.listener.incoming() {
+            let Ok(stream) = stream else {
+                continue;
+            };
+            let _ = stream.set_read_timeout(Some(IDLE));
+            let _ = stream.set_write_timeout(Some(IDLE));
+
+            let running = self.running.clone();
+            let limits = self.limits;
+            // the interpreter needs its deep stack, like `ez run`
+            thread::Builder::new()
+                .stack_size(interp::STACK_SIZE)
+                .spawn(move || handle(stream, limits, &running))?;
+        }
+        Ok(())
+    }
+}
+
+// one of the programs that can run at once, given back when it's dropped, even
+// when the compiler panicked
+struct Slot<'r>(&'r AtomicUsize);
+
+impl<'r> Slot<'r> {
+    fn take(running: &'r AtomicUsize) -> Option<Self> {
+        let slot = Slot(running);
+        (running.fetch_add(1, Ordering::SeqCst) < MAX_RUNNING).then_some(slot)
+    }
+}
+
+impl Drop for Slot<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+fn handle(mut stream: TcpStream, limits: Limits, running: &AtomicUsize) {
+    let response = match read_request(&stream) {
+        Ok(request) => respond(request, limits, running),
+        Err(response) => response,
+    };
+    let _ = response.write(&mut stream);
+}
+
+fn respond(request: Request, limits: Limits, running: &AtomicUsize) -> Response {
+    let path = request.path.split('?').next().unwrap_or_default();
+    match (request.method.as_str(), path) {
+        ("GET", "/") => Response {
+            status: "200 OK",
+            content_type: "text/html",
+            body: PAGE.to_string(),
+        },
+        ("POST", "/compile" | "/run") => {
+            let slot = Slot::take(running);
+            if slot.is_none() {
+                return Response::error(
+                    "503 Service Unavailable",
+                    "too many programs are running, try again in a moment",
+                );
+            }
+            Response::outcome(match path {
+                "/run" => playground::run(&request.body, limits),
+                _ => playground::compile(&request.body),
+            })
+        }
+        (_, "/" | "/compile" | "/run") => Response::error(
+            "405 Method Not Allowed",
+            "`/` is read with GET, the others with POST",
+        ),
+        _ => Response::error("404 Not Found", "there's `/`, `/compile` and `/run`"),
+    }
+}
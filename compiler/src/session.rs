@@ -0,0 +1,335 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use ez_ast::{Arena, Module, Program, Use};
+use ez_diagnostics::{Diagnostic, DiagnosticSink, SourceMap};
+use ez_lexer::{Span, Token};
+use ez_parser::{Parser, ParserOptions};
+
+use crate::cache;
+use crate::package::Package;
+
+#[derive(Debug)]
+pub enum SessionError {
+    Unreadable {
+        path: String,
+        error: io::Error,
+        span: Span,
+    },
+    // a file that ends up using itself
+    Cycle {
+        path: String,
+        span: Span,
+    },
+}
+
+impl From<SessionError> for Diagnostic {
+    fn from(error: SessionError) -> Self {
+        match error {
+            SessionError::Unreadable { path, error, span } => {
+                Diagnostic::error("E0005", format!("couldn't read `{}`: {}", path, error))
+                    .with_label(span, "used here")
+            }
+
+            SessionError::Cycle { path, span } => {
+                Diagnostic::error("E0006", format!("`{}` ends up using itself", path))
+                    .with_label(span, "used here")
+                    .with_help("move what the files share into a file of its own")
+            }
+        }
+    }
+}
+
+// where a session gets the files it loads, the disk unless it's given
+// something that knows them better, like the files open in an editor
+pub trait Files {
+    fn read(&mut self, path: &Path) -> io::Result<String>;
+
+    // the tokens of a file, when they're known already
+    fn lexed(&mut self, _path: &Path) -> Option<Rc<[Token]>> {
+        None
+    }
+}
+
+pub struct Disk;
+
+impl Files for Disk {
+    fn read(&mut self, path: &Path) -> io::Result<String> {
+        fs::read_to_string(path)
+    }
+}
+
+// the file used, from the directory of the file using it
+fn used_file(using: &Use, dir: &Path) -> PathBuf {
+    let mut path = dir.to_path_buf();
+    path.extend(using.path.iter().map(|identifier| identifier.name.as_str()));
+    path.set_extension("ez");
+    path
+}
+
+// parses a program along with the files it uses. `use a.b;` is the file
+// `a/b.ez` next to the file using it, unless `a` is a package, then it's the
+// file `b.ez` of the package. Every file is loaded once however many files use
+// it
+#[derive(Debug, Default)]
+pub struct Session {
+    pub sources: SourceMap,
+    packages: Vec<Package>,
+    options: ParserOptions,
+    // by canonical path, the index of the module once it's loaded
+    loaded: HashMap<PathBuf, usize>,
+    // the files being loaded, each one uses the next
+    loading: Vec<PathBuf>,
+    // every file a `use` named, whether it could be read or not
+    used: Vec<PathBuf>,
+    failed: bool,
+}
+
+impl Session {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // the dependencies of the project, every file can use them
+    pub fn with_packages(mut self, packages: Vec<Package>) -> Self {
+        self.packages = packages;
+        self
+    }
+
+    // the edition and features every file is parsed with
+    pub fn with_options(mut self, options: ParserOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    fn file(&self, using: &Use, dir: &Path) -> PathBuf {
+        let package = self
+            .packages
+            .iter()
+            .find(|package| package.name == *using.path[0].name);
+
+        match package {
+            Some(package) if using.path.len() == 1 => package.entry.clone(),
+            Some(package) => {
+                let mut path = package.dir.clone();
+                path.extend(
+                    using.path[1..]
+                        .iter()
+                        .map(|identifier| identifier.name.as_str()),
+                );
+                path.set_extension("ez");
+                path
+            }
+            None => used_file(using, dir),
+        }
+    }
+
+    // the files the programs loaded use, and the ones those use, including the
+    // ones that couldn't be read
+    pub fn used(&self) -> &[PathBuf] {
+        &self.used
+    }
+
+    // whether the file is one of a package the project depends on, rather than
+    // one of the project's own
+    pub fn in_package(&self, path: &Path) -> bool {
+        let path = Session::key(path);
+        self.packages
+            .iter()
+            .any(|package| path.starts_with(Session::key(&package.dir)))
+    }
+
+    // the same file can be named in more than one way, like `./a.ez` and `a.ez`
+    pub(crate) fn key(path: &Path) -> PathBuf {
+        fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+    }
+
+    fn parse<'a>(
+        &mut self,
+        files: &mut dyn Files,
+        arena: &'a Arena,
+        name: &str,
+        source: String,
+        sink: &mut DiagnosticSink,
+    ) -> Option<Program<'a>> {
+        let tokens = files.lexed(Path::new(name));
+        let start = self.sources.add(name, source);
+        let source = &self.sources.files().last().unwrap().source;
+
+        let parser = match &tokens {
+            Some(tokens) => Parser::lexed(source, tokens, arena),
+            None => Parser::new(source, arena),
+        };
+        match parser
+            .with_offset(start)
+            .with_options(self.options.clone())
+            .parse_program()
+        {
+            Ok(program) => Some(program),
+            Err(error) => {
+                sink.push(error);
+                self.failed = true;
+                None
+            }
+        }
+    }
+
+    // the files used are added to `modules`, after the ones they use
+    fn load_uses<'a>(
+        &mut self,
+        files: &mut dyn Files,
+        arena: &'a Arena,
+        uses: &mut [Use],
+        dir: &Path,
+        modules: &mut Vec<Module<'a>>,
+        sink: &mut DiagnosticSink,
+    ) {
+        for using in uses {
+            let path = self.file(using, dir);
+            let name = path.display().to_string();
+            let key = Self::key(&path);
+            if self.loading.contains(&key) {
+                sink.push(SessionError::Cycle {
+                    path: name,
+                    span: using.span,
+                });
+                self.failed = true;
+                continue;
+            }
+
+            if let Some(index) = self.loaded.get(&key) {
+                using.module = Some(*index);
+                continue;
+            }
+
+            if !self.used.contains(&path) {
+                self.used.push(path.clone());
+            }
+            let source = match files.read(&path) {
+                Ok(source) => source,
+                Err(error) => {
+                    sink.push(SessionError::Unreadable {
+                        path: name,
+                        error,
+                        span: using.span,
+                    });
+                    self.failed = true;
+                    continue;
+                }
+            };
+
+            let Some(mut program) = self.parse(files, arena, &name, source, sink) else {
+                continue;
+            };
+
+            self.loading.push(key.clone());
+            let dir = path.parent().unwrap_or(Path::new("")).to_path_buf();
+            self.load_uses(files, arena, &mut program.uses, &dir, modules, sink);
+            self.loading.pop();
+
+            let index = modules.len();
+            modules.push(Module {
+                path: name,
+                program,
+            });
+            self.loaded.insert(key, index);
+            using.module = Some(index);
+        }
+    }
+
+    // `None` when a file couldn't be read or parsed, the errors are in the sink.
+    // The program and the files it uses are parsed into the arena
+    pub fn load<'a>(
+        &mut self,
+        arena: &'a Arena,
+        name: &str,
+        source: String,
+        sink: &mut DiagnosticSink,
+    ) -> Option<Program<'a>> {
+        self.load_from(&mut Disk, arena, name, source, sink)
+    }
+
+    // `load`, with the files used read from `files`
+    pub fn load_from<'a>(
+        &mut self,
+        files: &mut dyn Files,
+        arena: &'a Arena,
+        name: &str,
+        source: String,
+        sink: &mut DiagnosticSink,
+    ) -> Option<Program<'a>> {
+        let mut program = self.parse(files, arena, name, source, sink)?;
+
+        let path = Path::new(name);
+        self.loading.push(Self::key(path));
+        let dir = path.parent().unwrap_or(Path::new("")).to_path_buf();
+        let mut modules = vec![];
+        self.load_uses(files, arena, &mut program.uses, &dir, &mut modules, sink);
+        self.loading.pop();
+
+        program.modules = modules;
+        if self.failed {
+            return None;
+        }
+
+        Some(program)
+    }
+}
+
+// the `.ez` files of a directory and the ones under it, links to directories
+// aren't followed, they can lead back up to it, and caches aren't looked into
+fn sources_in(dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Ok(kind) = entry.file_type() else {
+            continue;
+        };
+        if kind.is_dir() {
+            if entry.file_name() != cache::DIR {
+                sources_in(&path, files);
+            }
+        } else if path.extension().is_some_and(|extension| extension == "ez") {
+            files.push(path);
+        }
+    }
+}
+
+// every `.ez` file in a directory and under it, in order
+pub fn sources(dir: &Path) -> Vec<PathBuf> {
+    let mut files = vec![];
+    sources_in(dir, &mut files);
+    files.sort();
+    files
+}
+
+// the programs in a directory, its files that no other file in it uses. The
+// files that are used get compiled along with the programs using them. Files
+// that don't parse are kept, so their errors get reported
+pub fn programs(dir: &Path) -> Vec<PathBuf> {
+    let mut files = sources(dir);
+    let mut used = vec![];
+    for file in &files {
+        let Ok(source) = fs::read_to_string(file) else {
+            continue;
+        };
+        let arena = Arena::new();
+        let Ok(program) = Parser::new(&source, &arena).parse_program() else {
+            continue;
+        };
+
+        let dir = file.parent().unwrap_or(Path::new(""));
+        for using in &program.uses {
+            used.push(Session::key(&used_file(using, dir)));
+        }
+    }
+
+    files.retain(|file| !used.contains(&Session::key(file)));
+    files
+}
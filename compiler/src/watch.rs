@@ -0,0 +1,159 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use crate::cache;
+
+// how often the files are looked at
+const POLL: Duration = Duration::from_millis(200);
+
+// editors often write a file more than once when saving, or save several files
+// at a time, so changes are only reported once nothing changed for this long
+const DEBOUNCE: Duration = Duration::from_millis(100);
+
+// watches `.ez` files for changes by polling their modification times, there's
+// no portable way to be told about them without extra dependencies. The roots
+// can be files or directories, files created in a directory later on are picked
+// up, and so are the files given that don't exist yet. Directories are looked
+// into without following links, and the caches and the ignored ones are left
+// alone
+pub struct Watcher {
+    roots: Vec<PathBuf>,
+    // canonical, like the dependencies a project vendored
+    ignored: Vec<PathBuf>,
+    // `None` for a root file that doesn't exist
+    times: HashMap<PathBuf, Option<SystemTime>>,
+}
+
+impl Watcher {
+    pub fn new(roots: Vec<PathBuf>) -> Self {
+        let times = Self::scan(&roots, &[]);
+        Self {
+            roots,
+            ignored: vec![],
+            times,
+        }
+    }
+
+    pub fn with_ignored(mut self, ignored: Vec<PathBuf>) -> Self {
+        self.ignored = ignored;
+        self.times = Self::scan(&self.roots, &self.ignored);
+        self
+    }
+
+    // watches other roots from now on. The files that were already watched
+    // keep their times, so what changed since the last scan is still reported,
+    // and the new ones aren't reported as changed
+    pub fn set_roots(&mut self, roots: Vec<PathBuf>) {
+        let times = Self::scan(&roots, &self.ignored);
+        self.times = times
+            .into_iter()
+            .map(|(path, time)| match self.times.get(&path) {
+                Some(known) => (path, *known),
+                None => (path, time),
+            })
+            .collect();
+        self.roots = roots;
+    }
+
+    // the files that exist right now, sorted so they're always compiled in the
+    // same order
+    pub fn files(&self) -> Vec<PathBuf> {
+        let mut files: Vec<PathBuf> = self
+            .times
+            .iter()
+            .filter(|(_, time)| time.is_some())
+            .map(|(path, _)| path.clone())
+            .collect();
+        files.sort();
+        files
+    }
+
+    fn modified(path: &Path) -> Option<SystemTime> {
+        fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    }
+
+    fn scan_dir(dir: &Path, ignored: &[PathBuf], times: &mut HashMap<PathBuf, Option<SystemTime>>) {
+        let Ok(entries) = fs::read_dir(dir) else {
+            return;
+        };
+
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let Ok(kind) = entry.file_type() else {
+                continue;
+            };
+            if kind.is_dir() {
+                let skipped = entry.file_name() == cache::DIR
+                    || fs::canonicalize(&path).is_ok_and(|path| ignored.contains(&path));
+                if !skipped {
+                    Self::scan_dir(&path, ignored, times);
+                }
+            } else if path.extension().is_some_and(|extension| extension == "ez") {
+                let time = Self::modified(&path);
+                times.insert(path, time);
+            }
+        }
+    }
+
+    fn scan(roots: &[PathBuf], ignored: &[PathBuf]) -> HashMap<PathBuf, Option<SystemTime>> {
+        let mut times = HashMap::new();
+        for root in roots {
+            if root.is_dir() {
+                Self::scan_dir(root, ignored, &mut times);
+            } else {
+                times.insert(root.clone(), Self::modified(root));
+            }
+        }
+
+        times
+    }
+
+    // the files that were changed, created or removed since the last scan
+    fn changes(&mut self) -> Vec<PathBuf> {
+        let times = Self::scan(&self.roots, &self.ignored);
+        let mut changed: Vec<PathBuf> = times
+            .iter()
+            .filter(|(path, time)| self.times.get(*path) != Some(*time))
+            .map(|(path, _)| path.clone())
+            .chain(
+                self.times
+                    .keys()
+                    .filter(|path| !times.contains_key(*path))
+                    .cloned(),
+            )
+            .collect();
+
+        changed.sort();
+        self.times = times;
+        changed
+    }
+
+    // blocks until something changed, and gives back what did
+    pub fn wait(&mut self) -> Vec<PathBuf> {
+        loop {
+            thread::sleep(POLL);
+            let mut changed = self.changes();
+            if changed.is_empty() {
+                continue;
+            }
+
+            loop {
+                thread::sleep(DEBOUNCE);
+                let more = self.changes();
+                if more.is_empty() {
+                    break;
+                }
+                changed.extend(more);
+            }
+
+            changed.sort();
+            changed.dedup();
+            return changed;
+        }
+    }
+}
@@ -0,0 +1,166 @@
+// programs run by the interpreter and compiled by the backends print the same.
+// The C backend needs a C compiler, `cc` or the one `CC` names, and the
+// JavaScript one `node`
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+// a directory of its own for each test, they run at the same time
+fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("ez-backends-{}-{}", std::process::id(), name));
+    fs::create_dir_all(&dir).expect("the scratch directory should be made");
+    dir
+}
+
+fn ez(args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_ez"))
+        .args(args)
+        .output()
+        .expect("ez should run");
+    assert!(
+        output.status.success(),
+        "`ez {}` failed: {}",
+        args.join(" "),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).expect("the output should be text")
+}
+
+fn interpreted(dir: &Path, source: &str) -> String {
+    let file = dir.join("main.ez");
+    fs::write(&file, source).unwrap();
+    ez(&["run", file.to_str().unwrap()])
+}
+
+fn compiled_c(dir: &Path, source: &str) -> String {
+    let file = dir.join("main.ez");
+    let c = dir.join("main.c");
+    let executable = dir.join("main");
+    fs::write(&file, source).unwrap();
+    ez(&[
+        "build",
+        file.to_str().unwrap(),
+        "--target",
+        "c",
+        "-o",
+        c.to_str().unwrap(),
+    ]);
+    ez::link::compile_c(&c, &executable).expect("the C should compile");
+
+    let output = Command::new(&executable)
+        .output()
+        .expect("the program should run");
+    String::from_utf8(output.stdout).expect("the output should be text")
+}
+
+fn compiled_js(dir: &Path, source: &str) -> String {
+    let file = dir.join("main.ez");
+    let script = dir.join("main.js");
+    fs::write(&file, source).unwrap();
+    ez(&[
+        "build",
+        file.to_str().unwrap(),
+        "--target",
+        "js",
+        "-o",
+        script.to_str().unwrap(),
+    ]);
+
+    let output = Command::new("node")
+        .arg(&script)
+        .output()
+        .expect("node should run the script");
+    String::from_utf8(output.stdout).expect("the output should be text")
+}
+
+// the shortest digits, in exponent form out of 1e-4 to 1e16, like `{:?}`
+const FLOATS: &str = r#"
+println(250.0 as string);
+println(100000.0 as string);
+println(10000000000000000.0 as string);
+println(15000000000000000.0 as string);
+println(9999999999999998.0 as string);
+println(0.0000001 as string);
+println(0.00001 as string);
+println(0.00015 as string);
+println(0.0001 as string);
+println(0.1 as string);
+println((0.1 + 0.2) as string);
+println((1.0 / 3.0) as string);
+println(123.456 as string);
+println(1.0 as string);
+println(0.0 as string);
+println(-0.0 as string);
+println(-2.5 as string);
+"#;
+
+const PRINTED: &str = "250.0
+100000.0
+1e16
+1.5e16
+9999999999999998.0
+1e-7
+1e-5
+0.00015
+0.0001
+0.1
+0.30000000000000004
+0.3333333333333333
+123.456
+1.0
+0.0
+-0.0
+-2.5
+";
+
+#[test]
+fn floats_in_c() {
+    let dir = scratch("floats_in_c");
+    assert_eq!(interpreted(&dir, FLOATS), PRINTED);
+    assert_eq!(compiled_c(&dir, FLOATS), PRINTED);
+    let _ = fs::remove_dir_all(dir);
+}
+
+#[test]
+fn floats_in_js() {
+    let dir = scratch("floats_in_js");
+    assert_eq!(interpreted(&dir, FLOATS), PRINTED);
+    assert_eq!(compiled_js(&dir, FLOATS), PRINTED);
+    let _ = fs::remove_dir_all(dir);
+}
+
+// C has trigraphs, `??!` is `|` and `??=` is `#` in a string
+const STRINGS: &str = r#"
+println("what??! ok??= (??) ??/ \ é");
+"#;
+
+#[test]
+fn strings_in_c() {
+    let dir = scratch("strings_in_c");
+    let printed = interpreted(&dir, STRINGS);
+    assert_eq!(printed, "what??! ok??= (??) ??/ \\ é\n");
+    assert_eq!(compiled_c(&dir, STRINGS), printed);
+    let _ = fs::remove_dir_all(dir);
+}
+
+// an output that can't be written is an error of its own, not a crash
+#[test]
+fn unwritable_output() {
+    let dir = scratch("unwritable_output");
+    let file = dir.join("main.ez");
+    fs::write(&file, "println(\"hi\");\n").unwrap();
+
+    for target in ["c", "js", "wasm"] {
+        let output = dir.join("missing").join("main");
+        let output = Command::new(env!("CARGO_BIN_EXE_ez"))
+            .args(["build", file.to_str().unwrap(), "--target", target, "-o"])
+            .arg(&output)
+            .output()
+            .expect("ez should run");
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert_eq!(output.status.code(), Some(1), "{}: {}", target, stderr);
+        assert!(stderr.contains("couldn't write"), "{}: {}", target, stderr);
+    }
+    let _ = fs::remove_dir_all(dir);
+}
@@ -0,0 +1,37 @@
+// what `ez check --fix` makes of a file, and what it leaves alone
+
+use std::fs;
+use std::process::Command;
+
+// the file after `ez check --fix`
+fn fixed(name: &str, source: &str) -> String {
+    let dir = std::env::temp_dir().join(format!("ez-fix-{}-{}", std::process::id(), name));
+    fs::create_dir_all(&dir).expect("the scratch directory should be made");
+    let file = dir.join("main.ez");
+    fs::write(&file, source).unwrap();
+
+    Command::new(env!("CARGO_BIN_EXE_ez"))
+        .args(["check", "--fix", file.to_str().unwrap()])
+        .output()
+        .expect("ez should run");
+    let fixed = fs::read_to_string(&file).unwrap();
+    let _ = fs::remove_dir_all(dir);
+    fixed
+}
+
+// a `;` is added at the end of a line, or of the file, where it was forgotten
+#[test]
+fn missing_semi() {
+    assert_eq!(
+        fixed("missing_semi", "x := 1\nprintln(x as string)"),
+        "x := 1;\nprintln(x as string);"
+    );
+}
+
+// on the same line, what comes next can be what's wrong, a `;` would only make
+// another error out of it
+#[test]
+fn same_line() {
+    let source = "x := 1e5\nprintln(x as string);\n";
+    assert_eq!(fixed("same_line", source), source);
+}
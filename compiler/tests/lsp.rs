@@ -0,0 +1,67 @@
+// the language server answering requests about a document, the messages it
+// gets and sends framed like an editor does
+
+use ez::lsp::Server;
+
+const URI: &str = "file:///main.ez";
+
+fn message(text: &str) -> String {
+    format!("Content-Length: {}\r\n\r\n{}", text.len(), text)
+}
+
+fn request(id: usize, method: &str, line: usize, character: usize) -> String {
+    message(&format!(
+        r#"{{"jsonrpc":"2.0","id":{},"method":"{}","params":{{"textDocument":{{"uri":"{}"}},"position":{{"line":{},"character":{}}},"context":{{"includeDeclaration":true}}}}}}"#,
+        id, method, URI, line, character
+    ))
+}
+
+// the answers to the requests, by their id, without the notifications
+fn answers(source: &str, requests: &[String]) -> Vec<String> {
+    let open = format!(
+        r#"{{"jsonrpc":"2.0","method":"textDocument/didOpen","params":{{"textDocument":{{"uri":"{}","text":{:?}}}}}}}"#,
+        URI, source
+    );
+    let mut input = message(&open);
+    for request in requests {
+        input.push_str(request);
+    }
+    input.push_str(&message(r#"{"jsonrpc":"2.0","method":"exit"}"#));
+
+    let mut output = vec![];
+    Server::new()
+        .run(input.as_bytes(), &mut output)
+        .expect("the server should answer");
+    let output = String::from_utf8(output).expect("the answers should be text");
+    output
+        .split("Content-Length: ")
+        .filter_map(|message| message.split_once("\r\n\r\n"))
+        .map(|(_, body)| body.to_string())
+        .filter(|body| body.contains(r#""id":"#))
+        .collect()
+}
+
+// the builtins are declared before the program, nowhere in it, so they're
+// never what's at its start
+#[test]
+fn first_token() {
+    let source = "x := 1;\nprintln(x as string);\n";
+    let answers = answers(
+        source,
+        &[
+            request(1, "textDocument/hover", 0, 0),
+            request(2, "textDocument/definition", 0, 0),
+            request(3, "textDocument/references", 0, 0),
+        ],
+    );
+
+    assert_eq!(answers.len(), 3, "{:?}", answers);
+    assert!(answers[0].contains("variable x: int"), "{}", answers[0]);
+    assert!(!answers[0].contains("Json"), "{}", answers[0]);
+
+    let declaration = r#""start":{"line":0,"character":0},"end":{"line":0,"character":1}"#;
+    assert!(answers[1].contains(declaration), "{}", answers[1]);
+    assert!(answers[2].contains(declaration), "{}", answers[2]);
+    let used = r#""start":{"line":1,"character":8},"end":{"line":1,"character":9}"#;
+    assert!(answers[2].contains(used), "{}", answers[2]);
+}
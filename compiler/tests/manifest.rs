@@ -0,0 +1,41 @@
+// what `ez.toml` can't say
+
+use std::fs;
+
+use ez::manifest::{Manifest, ManifestError};
+
+fn load(name: &str, text: &str) -> Result<Manifest, ManifestError> {
+    let dir = std::env::temp_dir().join(format!("ez-manifest-{}-{}", std::process::id(), name));
+    fs::create_dir_all(&dir).expect("the scratch directory should be made");
+    let path = dir.join("ez.toml");
+    fs::write(&path, text).unwrap();
+    let manifest = Manifest::load(&path);
+    let _ = fs::remove_dir_all(dir);
+    manifest
+}
+
+// the url and the revision of a git dependency are given to git, they can't
+// be taken for its options
+#[test]
+fn git_options() {
+    let dependencies = [
+        r#"colors = { git = "--upload-pack=touch /tmp/pwned", rev = "v1" }"#,
+        r#"colors = { git = "https://example.com/colors.git", rev = "--orphan=x" }"#,
+    ];
+    for dependency in dependencies {
+        let text = format!(
+            "[package]\nname = \"app\"\n\n[dependencies]\n{}\n",
+            dependency
+        );
+        match load("git_options", &text) {
+            Err(ManifestError::Invalid { message, .. }) => {
+                assert!(message.contains("can't start with `-`"), "{}", message)
+            }
+            other => panic!("{:?} for {}", other, dependency),
+        }
+    }
+
+    let text = "[package]\nname = \"app\"\n\n[dependencies]\n\
+                colors = { git = \"https://example.com/colors.git\", rev = \"v1\" }\n";
+    assert!(load("git_options", text).is_ok());
+}
@@ -0,0 +1,60 @@
+// what the playground gives back of a program it runs, kept within its limits
+
+use ez::playground::{self, Limits};
+
+// logs are part of what the program shows, not of the server's stderr
+#[test]
+fn logs() {
+    let outcome = playground::run(
+        "println(\"hi\");\nlog_error(\"oops\");\n",
+        Limits::default(),
+    );
+    assert!(outcome.passed, "{}", outcome.diagnostics);
+    assert_eq!(outcome.output, "hi\n[error] oops\n");
+    assert!(!outcome.truncated);
+}
+
+#[test]
+fn logs_within_the_limit() {
+    let limits = Limits {
+        output: 16,
+        ..Limits::default()
+    };
+    let source = "for i in 0..100 {\n    log_error(\"again\");\n}\n";
+    let outcome = playground::run(source, limits);
+    assert!(outcome.passed, "{}", outcome.diagnostics);
+    assert_eq!(outcome.output, "[error] again\n[e");
+    assert!(outcome.truncated);
+}
+
+// the variables of the program aren't shown, only what it printed
+#[test]
+fn printed() {
+    let outcome = playground::run("x := 1;\nprintln(x as string);\n", Limits::default());
+    assert!(outcome.passed, "{}", outcome.diagnostics);
+    assert_eq!(outcome.output, "1\n");
+}
+
+// a program nested deeper than the parser goes is an error, it can't take the
+// stack of the server, and so is a long chain of operators
+#[test]
+fn too_deep() {
+    let sources = [
+        format!("x := {}1{};", "(".repeat(30_000), ")".repeat(30_000)),
+        format!("x := 1{};", " + 1".repeat(15_000)),
+    ];
+    for source in &sources {
+        assert!(source.len() < 64 * 1024);
+        for outcome in [
+            playground::compile(source),
+            playground::run(source, Limits::default()),
+        ] {
+            assert!(!outcome.passed);
+            assert!(
+                outcome.diagnostics.contains("nested too deeply"),
+                "{}",
+                outcome.diagnostics
+            );
+        }
+    }
+}
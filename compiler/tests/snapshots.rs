@@ -0,0 +1,265 @@
+// every program of `examples/` and every file of `tests/cases/` is lexed,
+// parsed and checked, and what comes out is compared with the snapshot of it
+// under `tests/snapshots/`. The files of `tests/cases/` are run by `ez run`
+// too, the examples reach outside of themselves and take their time. When the
+// output changes on purpose, the snapshots are written again with
+//
+//     BLESS=1 cargo test --test snapshots
+
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use ez::diagnostics::line_col;
+use ez::render::Renderer;
+use ez::{Arena, DiagnosticSink, Lexer, Session};
+
+fn root() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("..")
+}
+
+fn tests() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests")
+}
+
+// one token a line, like `ez check --emit tokens`
+fn tokens(source: &str) -> String {
+    let mut out = String::new();
+    for token in Lexer::new(source) {
+        let (line, column) = line_col(source, token.span.start);
+        let _ = writeln!(
+            out,
+            "{}:{} {:?} {:?}",
+            line, column, token.kind, token.value
+        );
+    }
+    out
+}
+
+// the lines `after` changed from `before`, removed ones with a `-` and added
+// ones with a `+`, under the function and the block they're in
+fn diff(before: &str, after: &str) -> String {
+    let before: Vec<&str> = before.lines().collect();
+    let after: Vec<&str> = after.lines().collect();
+
+    // the longest common subsequence of the lines that follow each position
+    let mut common = vec![vec![0; after.len() + 1]; before.len() + 1];
+    for i in (0..before.len()).rev() {
+        for j in (0..after.len()).rev() {
+            common[i][j] = if before[i] == after[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    // the headers are printed once, before the first change under them
+    let mut headers: [Option<&str>; 2] = [None, None];
+    let line = |out: &mut String, headers: &mut [Option<&str>; 2], prefix: char, text: &str| {
+        for header in headers.iter_mut().filter_map(Option::take) {
+            let _ = writeln!(out, "  {}", header);
+        }
+        let _ = writeln!(out, "{} {}", prefix, text);
+    };
+
+    let mut out = String::new();
+    let (mut i, mut j) = (0, 0);
+    while i < before.len() || j < after.len() {
+        if i < before.len() && j < after.len() && before[i] == after[j] {
+            if before[i].starts_with("fn ") {
+                headers = [Some(before[i]), None];
+            } else if before[i].starts_with("bb") {
+                headers[1] = Some(before[i]);
+            }
+            i += 1;
+            j += 1;
+        } else if j == after.len() || (i < before.len() && common[i + 1][j] >= common[i][j + 1]) {
+            line(&mut out, &mut headers, '-', before[i]);
+            i += 1;
+        } else {
+            line(&mut out, &mut headers, '+', after[j]);
+            j += 1;
+        }
+    }
+    out
+}
+
+// what `ez run` printed on stdout and stderr, and the code it exited with
+fn run(path: &Path) -> String {
+    let root = root();
+    let output = Command::new(env!("CARGO_BIN_EXE_ez"))
+        .arg("run")
+        .arg(path.strip_prefix(fs::canonicalize(&root).unwrap()).unwrap())
+        .args(["--color", "never"])
+        .current_dir(&root)
+        .env_remove("EZ_LOG")
+        .output()
+        .expect("ez should run");
+
+    format!(
+        "--- run, exit code {}\n--- stdout\n{}--- stderr\n{}",
+        output
+            .status
+            .code()
+            .map_or("none".to_string(), |code| code.to_string()),
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    )
+}
+
+// the tokens of the file, the tree of the program and the diagnostics of
+// checking it, the files it uses included. When it passed the checks, the
+// program in SSA form too, how `-O2` changes it and the bytecode it compiles to,
+// before and after the peephole pass, disassembled with its lines after. Then
+// what running it gave, when it's `run`
+fn snapshot(path: &Path, run: bool) -> String {
+    let source = fs::read_to_string(path).expect("the file should be readable");
+    let name = path.display().to_string();
+
+    let mut sink = DiagnosticSink::default();
+    let mut session = Session::new();
+    let arena = Arena::new();
+    let mut ssa = None;
+    let ast = match session.load(&arena, &name, source.clone(), &mut sink) {
+        Some(program) => {
+            let ast = format!("{:#?}\n", program);
+            // the tree written as bytes reads back the same
+            let bytes = ez::ast::binary::encode(&program);
+            let decoded = ez::ast::binary::decode(&bytes, &Arena::new())
+                .map(|decoded| format!("{:#?}\n", decoded));
+            assert_eq!(
+                decoded.as_ref(),
+                Ok(&ast),
+                "{} doesn't read back from its bytes",
+                name
+            );
+            if let Some((resolutions, types)) = ez::check(&program, &[], &mut sink) {
+                if !sink.has_errors() {
+                    let hir = ez::lower(&program, &resolutions, &types, &mut sink);
+                    let mut built = ez::ssa::build(&hir);
+                    let before = built.to_string();
+                    // the text reads back the same
+                    let parsed = ez::ssa::Program::parse(&before).map(|parsed| parsed.to_string());
+                    assert_eq!(
+                        parsed.as_ref(),
+                        Ok(&before),
+                        "{} doesn't read back from its SSA form",
+                        name
+                    );
+                    ez::optimize::run(&mut built, 2);
+                    let mut module = ez::bytecode::compile(&built, &session.sources);
+                    let bytecode = module.to_string();
+                    ez::peephole::run(&mut module);
+                    // and the bytecode written as bytes
+                    let bytes = ez::ezb::encode(&module);
+                    let decoded = ez::ezb::decode(&bytes).map(|decoded| ez::ezb::encode(&decoded));
+                    assert_eq!(
+                        decoded.as_ref(),
+                        Ok(&bytes),
+                        "{} doesn't read back from its bytecode",
+                        name
+                    );
+                    let sources: Vec<Option<String>> = session
+                        .sources
+                        .files()
+                        .iter()
+                        .map(|file| Some(file.source.clone()))
+                        .collect();
+                    let bytecode = (bytecode, module.disassemble(&sources).to_string());
+                    ssa = Some((diff(&before, &built.to_string()), before, bytecode));
+                }
+            }
+            ast
+        }
+        None => "none, it doesn't parse\n".to_string(),
+    };
+    let diagnostics = sink.render(&Renderer::default(), &session.sources);
+
+    let mut out = format!(
+        "--- tokens\n{}--- ast\n{}--- diagnostics\n{}",
+        tokens(&source),
+        ast,
+        diagnostics
+    );
+    if let Some((optimized, ssa, (bytecode, peephole))) = ssa {
+        let _ = write!(
+            out,
+            "--- ssa\n{}--- ssa -O2\n{}--- bytecode\n{}--- bytecode -O2\n{}",
+            ssa, optimized, bytecode, peephole
+        );
+    }
+    if run {
+        out.push_str(&self::run(path));
+    }
+    // the paths are the same wherever the repository is
+    let root = format!("{}/", fs::canonicalize(root()).unwrap().display());
+    out.replace(&root, "")
+}
+
+// the snapshot of a file, by its path under `dir`
+fn check(
+    file: &Path,
+    dir: &Path,
+    snapshots: &Path,
+    run: bool,
+    bless: bool,
+    failures: &mut Vec<String>,
+) {
+    let relative = file.strip_prefix(dir).unwrap();
+    let expected_path = snapshots.join(relative).with_extension("snap");
+    let actual = snapshot(file, run);
+
+    if bless {
+        fs::create_dir_all(expected_path.parent().unwrap()).unwrap();
+        fs::write(&expected_path, actual).unwrap();
+        return;
+    }
+
+    let Ok(expected) = fs::read_to_string(&expected_path) else {
+        failures.push(format!("{}: no snapshot", file.display()));
+        return;
+    };
+    if expected != actual {
+        let line = expected
+            .lines()
+            .zip(actual.lines())
+            .position(|(expected, actual)| expected != actual)
+            .unwrap_or_else(|| expected.lines().count().min(actual.lines().count()));
+        failures.push(format!(
+            "{}: differs from {} at line {}\n  expected: {}\n  found:    {}",
+            file.display(),
+            expected_path.display(),
+            line + 1,
+            expected.lines().nth(line).unwrap_or("<end>"),
+            actual.lines().nth(line).unwrap_or("<end>"),
+        ));
+    }
+}
+
+#[test]
+fn snapshots() {
+    let bless = std::env::var_os("BLESS").is_some();
+    let root = fs::canonicalize(root()).unwrap();
+    let tests = fs::canonicalize(tests()).unwrap();
+
+    let examples = root.join("examples");
+    let cases = tests.join("cases");
+    let mut failures = vec![];
+    for file in ez::session::programs(&examples) {
+        let snapshots = tests.join("snapshots").join("examples");
+        check(&file, &examples, &snapshots, false, bless, &mut failures);
+    }
+    for file in ez::session::sources(&cases) {
+        let snapshots = tests.join("snapshots").join("cases");
+        check(&file, &cases, &snapshots, true, bless, &mut failures);
+    }
+
+    assert!(
+        failures.is_empty(),
+        "{} snapshot(s) don't match, run with BLESS=1 if the changes are right:\n{}",
+        failures.len(),
+        failures.join("\n")
+    );
+}
@@ -0,0 +1,125 @@
+// every program of `examples/` and every file of `tests/cases/` that passes
+// the checks is run by the interpreter, and compiled to bytecode by the VM,
+// and they have to print the same and fail with the same error. The bytecode
+// is compiled from the SSA form at every level of optimization, so the passes
+// can't change what a program does, and the one `--emit bytecode` writes, with
+// `-O2` and the peephole pass, is run read back from its bytes too. They run
+// in a sandbox, so what reaches outside of the program fails the same way for
+// all of them
+
+use std::cell::RefCell;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use ez::{Arena, Diagnostic, DiagnosticSink, Hir, Interpreter, RuntimeError, Session};
+
+// what a run printed, with the message of the error it stopped with
+#[derive(Clone, Default)]
+struct Output(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Output {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(bytes)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn run<'a>(hir: &'a Hir, run: impl FnOnce(Interpreter<'a>) -> Result<(), RuntimeError>) -> String {
+    let output = Output::default();
+    let interpreter = Interpreter::new(hir)
+        .with_output(output.clone())
+        .with_logs(output.clone())
+        .with_sandbox();
+    let error = run(interpreter).err().map(Diagnostic::from);
+
+    let mut printed = String::from_utf8_lossy(&output.0.borrow()).into_owned();
+    if let Some(error) = error {
+        printed.push_str(&format!("error: {}\n", error.message));
+    }
+    printed
+}
+
+// what each way of running the program printed, the interpreter first. None
+// when the program doesn't pass the checks
+fn runs(path: &Path) -> Option<Vec<(String, String)>> {
+    let source = fs::read_to_string(path).expect("the file should be readable");
+    let name = path.display().to_string();
+    let mut sink = DiagnosticSink::default();
+    let mut session = Session::new();
+    let arena = Arena::new();
+    let program = session.load(&arena, &name, source, &mut sink)?;
+    let (resolutions, types) = ez::check(&program, &[], &mut sink)?;
+    if sink.has_errors() {
+        return None;
+    }
+    let hir = ez::lower(&program, &resolutions, &types, &mut sink);
+
+    let interpreted = run(&hir, Interpreter::run);
+    let mut runs = vec![("interpreted".to_string(), interpreted)];
+    let mut module = None;
+    for level in 0..=ez::optimize::MAX_LEVEL {
+        let mut built = ez::ssa::build(&hir);
+        ez::optimize::run(&mut built, level);
+        let compiled = ez::bytecode::compile(&built, &session.sources);
+        let executed = run(&hir, |interpreter| interpreter.run_module(&compiled));
+        runs.push((format!("-O{}", level), executed));
+        module = Some(compiled);
+    }
+
+    let mut module = module.expect("there's a level of optimization");
+    ez::peephole::run(&mut module);
+    let module = ez::ezb::decode(&ez::ezb::encode(&module)).expect("the bytecode reads back");
+    let executed = run(&hir, |interpreter| interpreter.run_module(&module));
+    runs.push(("-O2, peephole, .ezb".to_string(), executed));
+    Some(runs)
+}
+
+fn programs() -> Vec<PathBuf> {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let mut programs = ez::session::programs(&root.join("../examples"));
+    programs.extend(ez::session::sources(&root.join("tests/cases")));
+    programs
+}
+
+#[test]
+fn same_as_the_interpreter() {
+    // the programs recurse as deep as they would with `ez run`
+    let failures = std::thread::Builder::new()
+        .stack_size(ez::interp::STACK_SIZE)
+        .spawn(|| {
+            let mut failures = vec![];
+            for path in programs() {
+                let Some(runs) = runs(&path) else {
+                    continue;
+                };
+                let (_, interpreted) = &runs[0];
+                for (how, executed) in &runs[1..] {
+                    if executed != interpreted {
+                        failures.push(format!(
+                            "{} with {}\n--- interpreted\n{}--- executed\n{}",
+                            path.display(),
+                            how,
+                            interpreted,
+                            executed
+                        ));
+                    }
+                }
+            }
+            failures
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+
+    assert!(
+        failures.is_empty(),
+        "{} run(s) of bytecode differ from the interpreter:\n{}",
+        failures.len(),
+        failures.join("\n")
+    );
+}
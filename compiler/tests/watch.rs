@@ -0,0 +1,43 @@
+// what `ez watch` looks at in a directory
+
+use std::fs;
+use std::path::PathBuf;
+
+use ez::watch::Watcher;
+
+// a link back up to the directory, a cache and vendored dependencies are left
+// out, only the files of the project are watched
+#[cfg(unix)]
+#[test]
+fn left_out() {
+    let dir = std::env::temp_dir().join(format!("ez-watch-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    for sub in [".ez-cache", "vendor/colors", "src"] {
+        fs::create_dir_all(dir.join(sub)).expect("the scratch directory should be made");
+    }
+    for file in [
+        "main.ez",
+        "src/shapes.ez",
+        ".ez-cache/old.ez",
+        "vendor/colors/colors.ez",
+    ] {
+        fs::write(dir.join(file), "x := 1;\n").unwrap();
+    }
+    std::os::unix::fs::symlink("..", dir.join("src/up")).unwrap();
+
+    let vendor = fs::canonicalize(dir.join("vendor")).unwrap();
+    let files = Watcher::new(vec![dir.clone()])
+        .with_ignored(vec![vendor])
+        .files();
+    let sources = ez::session::sources(&dir);
+    let _ = fs::remove_dir_all(&dir);
+
+    let expected: Vec<PathBuf> = vec![dir.join("main.ez"), dir.join("src/shapes.ez")];
+    assert_eq!(files, expected);
+    let expected: Vec<PathBuf> = vec![
+        dir.join("main.ez"),
+        dir.join("src/shapes.ez"),
+        dir.join("vendor/colors/colors.ez"),
+    ];
+    assert_eq!(sources, expected);
+}
@@ -0,0 +1,207 @@
+use std::alloc::{self, Layout};
+use std::cell::{Cell, RefCell};
+use std::mem;
+use std::ptr::{self, NonNull};
+
+use crate::{Expr, Identifier, MatchArm, NodeId, Param, Pattern, ValueExpr, Variant};
+
+// how big the first chunk is, the next ones are twice the size of the last,
+// up to the most a chunk is made of, so not much of the last one is wasted
+const CHUNK: usize = 16 * 1024;
+const MAX_CHUNK: usize = 1024 * 1024;
+
+// what every chunk is aligned to, enough for any node of the tree
+const ALIGN: usize = 16;
+
+// the nodes of a tree, allocated one after the other in big chunks instead of
+// a `Box` or a `Vec` each, and freed all at once with the arena. Nodes owning
+// something of their own, like the text of a string, are dropped along with it
+pub struct Arena {
+    chunks: RefCell<Vec<(NonNull<u8>, Layout)>>,
+    // the free space of the last chunk
+    next: Cell<*mut u8>,
+    end: Cell<*mut u8>,
+    destructors: RefCell<Vec<Destructor>>,
+    // the id of the next node, the first is 1, 0 is left for made up names
+    ids: Cell<u32>,
+}
+
+struct Destructor {
+    start: *mut u8,
+    len: usize,
+    drop: unsafe fn(*mut u8, usize),
+}
+
+unsafe fn drop_slice<T>(start: *mut u8, len: usize) {
+    ptr::drop_in_place(ptr::slice_from_raw_parts_mut(start.cast::<T>(), len));
+}
+
+// what the arena holds, the nodes of the tree. They're dropped with it, in no
+// particular order, which is only sound for types whose drop doesn't read
+// what they borrow: the nodes only free what they own. The arena doesn't know
+// the types it holds, so the borrow checker can't check that for it, and
+// nothing outside the crate can add one
+pub trait Node: sealed::Node {}
+
+mod sealed {
+    pub trait Node {}
+}
+
+macro_rules! nodes {
+    ($($node:ty),*) => {
+        $(
+            impl sealed::Node for $node {}
+            impl Node for $node {}
+        )*
+    };
+}
+
+nodes!(
+    Expr<'_>,
+    ValueExpr<'_>,
+    (ValueExpr<'_>, ValueExpr<'_>),
+    Pattern<'_>,
+    MatchArm<'_>,
+    Param,
+    Variant,
+    Identifier
+);
+
+impl Default for Arena {
+    fn default() -> Self {
+        Self {
+            chunks: RefCell::new(vec![]),
+            next: Cell::new(ptr::null_mut()),
+            end: Cell::new(ptr::null_mut()),
+            destructors: RefCell::new(vec![]),
+            ids: Cell::new(1),
+        }
+    }
+}
+
+impl Arena {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn next_id(&self) -> NodeId {
+        let id = self.ids.get();
+        self.ids.set(id + 1);
+        NodeId(id)
+    }
+
+    // a node read from somewhere else keeps its id, the ones made after it
+    // get higher ones
+    pub fn keep_id(&self, id: NodeId) -> NodeId {
+        if id.0 >= self.ids.get() {
+            self.ids.set(id.0 + 1);
+        }
+        id
+    }
+
+    /// Only nodes of the tree go in, a type dropped after what it borrows
+    /// doesn't compile:
+    ///
+    /// ```compile_fail
+    /// struct Loud<'a>(&'a Vec<u64>);
+    ///
+    /// impl Drop for Loud<'_> {
+    ///     fn drop(&mut self) {
+    ///         println!("{}", self.0[0]);
+    ///     }
+    /// }
+    ///
+    /// let arena = ez_ast::Arena::new();
+    /// let numbers = vec![1];
+    /// arena.alloc(Loud(&numbers));
+    /// drop(numbers);
+    /// ```
+    pub fn alloc<T: Node>(&self, value: T) -> &T {
+        let start = self.reserve(Layout::new::<T>()).cast::<T>();
+        // the space is free, and aligned and big enough for a `T`
+        unsafe { start.write(value) };
+        self.destroy_later::<T>(start.cast(), 1);
+        // the space isn't handed out again until the arena is dropped
+        unsafe { &*start }
+    }
+
+    // the items are moved out of the vector, which is freed
+    pub fn alloc_slice<T: Node>(&self, mut items: Vec<T>) -> &[T] {
+        let len = items.len();
+        let layout = Layout::array::<T>(len).expect("a slice too big to allocate");
+        let start = self.reserve(layout).cast::<T>();
+        // the space is free and big enough for the items, they're owned
+        // by the arena once they're copied, so the vector mustn't drop them
+        unsafe {
+            ptr::copy_nonoverlapping(items.as_ptr(), start, len);
+            items.set_len(0);
+        }
+        self.destroy_later::<T>(start.cast(), len);
+        // the items were just written there
+        unsafe { std::slice::from_raw_parts(start, len) }
+    }
+
+    fn destroy_later<T>(&self, start: *mut u8, len: usize) {
+        if mem::needs_drop::<T>() && len > 0 {
+            self.destructors.borrow_mut().push(Destructor {
+                start,
+                len,
+                drop: drop_slice::<T>,
+            });
+        }
+    }
+
+    fn reserve(&self, layout: Layout) -> *mut u8 {
+        assert!(
+            layout.align() <= ALIGN,
+            "nodes are aligned to at most {ALIGN}"
+        );
+        if layout.size() == 0 {
+            return NonNull::<u128>::dangling().as_ptr().cast();
+        }
+
+        let next = self.next.get();
+        if !next.is_null() {
+            let start = next.wrapping_add(next.align_offset(layout.align()));
+            if (self.end.get() as usize).saturating_sub(start as usize) >= layout.size() {
+                self.next.set(start.wrapping_add(layout.size()));
+                return start;
+            }
+        }
+
+        let start = self.grow(layout.size());
+        self.next.set(start.wrapping_add(layout.size()));
+        start
+    }
+
+    fn grow(&self, size: usize) -> *mut u8 {
+        let mut chunks = self.chunks.borrow_mut();
+        let last = chunks.last().map_or(CHUNK / 2, |(_, layout)| layout.size());
+        let layout = Layout::from_size_align(size.max((last * 2).min(MAX_CHUNK)), ALIGN)
+            .expect("a chunk too big to allocate");
+
+        // the layout isn't empty
+        let start = unsafe { alloc::alloc(layout) };
+        let Some(start) = NonNull::new(start) else {
+            alloc::handle_alloc_error(layout);
+        };
+        chunks.push((start, layout));
+        self.end.set(start.as_ptr().wrapping_add(layout.size()));
+        start.as_ptr()
+    }
+}
+
+impl Drop for Arena {
+    fn drop(&mut self) {
+        // the nodes are dropped before any chunk is freed, the references from
+        // one to another stay valid while they are
+        for destructor in self.destructors.get_mut().drain(..).rev() {
+            // the items were written there and not dropped yet
+            unsafe { (destructor.drop)(destructor.start, destructor.len) };
+        }
+        for (start, layout) in self.chunks.get_mut().drain(..) {
+            // the chunk was allocated with this layout
+            unsafe { alloc::dealloc(start.as_ptr(), layout) };
+        }
+    }
+}
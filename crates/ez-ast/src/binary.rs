@@ -0,0 +1,910 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use ez_lexer::{Span, Symbol, TokenKind};
+
+use crate::{
+    Arena, BaseType, Expr, Identifier, MatchArm, Module, Node, NodeId, Param, Pattern, Program,
+    Use, ValueExpr, Variant,
+};
+
+// a tree written as bytes, to be kept by a build cache or read by another tool
+// without parsing its files again. It starts with the magic bytes and the
+// version of the format, which changes with every change to how the tree is
+// written, a tree written by another version isn't read. Then come the names
+// of the tree, each written once, and the tree, with the names as their index
+// in there. Numbers are varints, the ids of the nodes are kept, so what was
+// found about them by their id still holds for the tree read back
+pub const MAGIC: [u8; 4] = *b"ezat";
+pub const VERSION: u16 = 1;
+
+#[derive(Debug, PartialEq)]
+pub enum DecodeError {
+    // it doesn't start with the magic bytes
+    NotAnAst,
+    // written with another version of the format
+    Version(u16),
+    // it ends in the middle of the tree
+    Truncated,
+    // a byte that can't be where it is, what was being read
+    Invalid(&'static str),
+    // the tree is read, but there are more bytes after it
+    Trailing,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::NotAnAst => write!(f, "not a syntax tree written by ez"),
+            DecodeError::Version(version) => write!(
+                f,
+                "the tree was written with version {} of the format, this is version {}",
+                version, VERSION
+            ),
+            DecodeError::Truncated => write!(f, "the tree ends too early"),
+            DecodeError::Invalid(what) => write!(f, "invalid {} in the tree", what),
+            DecodeError::Trailing => write!(f, "bytes left after the tree"),
+        }
+    }
+}
+
+// the operators of the tree, by their index in the bytes
+const OPERATORS: [TokenKind; 17] = [
+    TokenKind::Plus,
+    TokenKind::Minus,
+    TokenKind::Times,
+    TokenKind::DividedBy,
+    TokenKind::Equals,
+    TokenKind::Not,
+    TokenKind::NotEquals,
+    TokenKind::GreaterThan,
+    TokenKind::GreaterOrEquals,
+    TokenKind::LowerThan,
+    TokenKind::LowerOrEquals,
+    TokenKind::Or,
+    TokenKind::And,
+    TokenKind::BitAnd,
+    TokenKind::BitOr,
+    TokenKind::BitXor,
+    TokenKind::BitNot,
+];
+
+pub fn encode(program: &Program) -> Vec<u8> {
+    let mut writer = Writer::default();
+    writer.program(program);
+
+    let mut out = MAGIC.to_vec();
+    out.extend(VERSION.to_le_bytes());
+    let mut names = Writer::default();
+    names.usize(writer.symbols.len());
+    for symbol in &writer.symbols {
+        names.str(symbol.as_str());
+    }
+    out.extend(names.out);
+    out.extend(writer.out);
+    out
+}
+
+// the tree is read into the arena. The nodes keep the ids they were written
+// with, the arena gives the ones it makes later higher ones
+pub fn decode<'a>(bytes: &[u8], arena: &'a Arena) -> Result<Program<'a>, DecodeError> {
+    if bytes.len() < MAGIC.len() || bytes[..MAGIC.len()] != MAGIC {
+        return Err(DecodeError::NotAnAst);
+    }
+    let mut reader = Reader {
+        bytes,
+        at: MAGIC.len(),
+        symbols: vec![],
+        arena,
+    };
+    let version = u16::from_le_bytes([reader.byte()?, reader.byte()?]);
+    if version != VERSION {
+        return Err(DecodeError::Version(version));
+    }
+
+    let names = reader.len()?;
+    for _ in 0..names {
+        let name = reader.string()?;
+        reader.symbols.push(Symbol::intern(&name));
+    }
+    let program = reader.program()?;
+    if reader.at != bytes.len() {
+        return Err(DecodeError::Trailing);
+    }
+    Ok(program)
+}
+
+#[derive(Default)]
+struct Writer {
+    out: Vec<u8>,
+    // the names in the order they were first written, and their index
+    symbols: Vec<Symbol>,
+    indices: HashMap<Symbol, usize>,
+}
+
+impl Writer {
+    fn byte(&mut self, byte: u8) {
+        self.out.push(byte);
+    }
+
+    // seven bits a byte, the high bit says there's another
+    fn u64(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.byte(value as u8 | 0x80);
+            value >>= 7;
+        }
+        self.byte(value as u8);
+    }
+
+    fn usize(&mut self, value: usize) {
+        self.u64(value as u64);
+    }
+
+    // small negative numbers are small too
+    fn i64(&mut self, value: i64) {
+        self.u64(((value << 1) ^ (value >> 63)) as u64);
+    }
+
+    fn bool(&mut self, value: bool) {
+        self.byte(value as u8);
+    }
+
+    fn str(&mut self, text: &str) {
+        self.usize(text.len());
+        self.out.extend(text.as_bytes());
+    }
+
+    fn symbol(&mut self, symbol: Symbol) {
+        let next = self.symbols.len();
+        let index = *self.indices.entry(symbol).or_insert(next);
+        if index == next {
+            self.symbols.push(symbol);
+        }
+        self.usize(index);
+    }
+
+    // the end as the length, it's smaller
+    fn span(&mut self, span: Span) {
+        self.usize(span.start);
+        self.usize(span.end.saturating_sub(span.start));
+    }
+
+    fn id(&mut self, id: NodeId) {
+        self.u64(id.0 as u64);
+    }
+
+    fn operator(&mut self, operator: &TokenKind) {
+        let index = OPERATORS
+            .iter()
+            .position(|other| other == operator)
+            .expect("the operators of the tree are all in the table");
+        self.usize(index);
+    }
+
+    fn option<T>(&mut self, value: Option<T>, write: impl FnOnce(&mut Self, T)) {
+        match value {
+            Some(value) => {
+                self.byte(1);
+                write(self, value);
+            }
+            None => self.byte(0),
+        }
+    }
+
+    fn list<'t, T: 't>(
+        &mut self,
+        items: impl ExactSizeIterator<Item = &'t T>,
+        mut write: impl FnMut(&mut Self, &'t T),
+    ) {
+        self.usize(items.len());
+        for item in items {
+            write(self, item);
+        }
+    }
+
+    fn program(&mut self, program: &Program) {
+        self.list(program.body.iter(), Self::expr);
+        self.list(program.uses.iter(), Self::use_);
+        self.list(program.modules.iter(), |this, module: &Module| {
+            this.str(&module.path);
+            this.program(&module.program);
+        });
+    }
+
+    fn use_(&mut self, use_: &Use) {
+        self.list(use_.path.iter(), Self::identifier);
+        self.span(use_.span);
+        self.option(use_.module, Self::usize);
+    }
+
+    fn identifier(&mut self, identifier: &Identifier) {
+        self.symbol(identifier.name);
+        self.span(identifier.span);
+        self.id(identifier.id);
+    }
+
+    fn param(&mut self, param: &Param) {
+        self.identifier(&param.identifier);
+        self.basetype(&param.basetype);
+    }
+
+    fn basetype(&mut self, basetype: &BaseType) {
+        match basetype {
+            BaseType::Void => self.byte(0),
+            BaseType::Int => self.byte(1),
+            BaseType::Float => self.byte(2),
+            BaseType::String => self.byte(3),
+            BaseType::Bool => self.byte(4),
+            BaseType::Function {
+                params,
+                return_type,
+            } => {
+                self.byte(5);
+                self.list(params.iter(), Self::param);
+                self.basetype(return_type);
+            }
+            BaseType::Named(name) => {
+                self.byte(6);
+                self.symbol(*name);
+            }
+            BaseType::List(item) => {
+                self.byte(7);
+                self.basetype(item);
+            }
+            BaseType::Map(key, value) => {
+                self.byte(8);
+                self.basetype(key);
+                self.basetype(value);
+            }
+        }
+    }
+
+    fn value(&mut self, value: &ValueExpr) {
+        match value {
+            ValueExpr::Int { value, span, id } => {
+                self.byte(0);
+                self.i64(*value);
+                self.span(*span);
+                self.id(*id);
+            }
+            ValueExpr::Float { value, span, id } => {
+                self.byte(1);
+                self.out.extend(value.to_bits().to_le_bytes());
+                self.span(*span);
+                self.id(*id);
+            }
+            ValueExpr::String { value, span, id } => {
+                self.byte(2);
+                self.str(value);
+                self.span(*span);
+                self.id(*id);
+            }
+            ValueExpr::Bool { value, span, id } => {
+                self.byte(3);
+                self.bool(*value);
+                self.span(*span);
+                self.id(*id);
+            }
+            ValueExpr::Identifier(identifier) => {
+                self.byte(4);
+                self.identifier(identifier);
+            }
+            ValueExpr::Binary {
+                left,
+                right,
+                operator,
+                id,
+            } => {
+                self.byte(5);
+                self.value(left);
+                self.value(right);
+                self.operator(operator);
+                self.id(*id);
+            }
+            ValueExpr::Unary {
+                operand,
+                operator,
+                span,
+                id,
+            } => {
+                self.byte(6);
+                self.value(operand);
+                self.operator(operator);
+                self.span(*span);
+                self.id(*id);
+            }
+            ValueExpr::Call {
+                callee,
+                args,
+                span,
+                id,
+            } => {
+                self.byte(7);
+                self.value(callee);
+                self.list(args.iter(), Self::value);
+                self.span(*span);
+                self.id(*id);
+            }
+            ValueExpr::Cast {
+                value,
+                basetype,
+                span,
+                id,
+            } => {
+                self.byte(8);
+                self.value(value);
+                self.basetype(basetype);
+                self.span(*span);
+                self.id(*id);
+            }
+            ValueExpr::Field { value, field, id } => {
+                self.byte(9);
+                self.value(value);
+                self.identifier(field);
+                self.id(*id);
+            }
+            ValueExpr::List { items, span, id } => {
+                self.byte(10);
+                self.list(items.iter(), Self::value);
+                self.span(*span);
+                self.id(*id);
+            }
+            ValueExpr::Map { entries, span, id } => {
+                self.byte(11);
+                self.list(entries.iter(), |this, (key, value)| {
+                    this.value(key);
+                    this.value(value);
+                });
+                self.span(*span);
+                self.id(*id);
+            }
+            ValueExpr::Function {
+                params,
+                return_type,
+                body,
+                span,
+                id,
+            } => {
+                self.byte(12);
+                self.list(params.iter(), Self::param);
+                self.basetype(return_type);
+                self.list(body.iter(), Self::expr);
+                self.span(*span);
+                self.id(*id);
+            }
+        }
+    }
+
+    fn pattern(&mut self, pattern: &Pattern) {
+        match pattern {
+            Pattern::Wildcard(span) => {
+                self.byte(0);
+                self.span(*span);
+            }
+            Pattern::Binding(identifier) => {
+                self.byte(1);
+                self.identifier(identifier);
+            }
+            Pattern::Int { value, span } => {
+                self.byte(2);
+                self.i64(*value);
+                self.span(*span);
+            }
+            Pattern::String { value, span } => {
+                self.byte(3);
+                self.str(value);
+                self.span(*span);
+            }
+            Pattern::Bool { value, span } => {
+                self.byte(4);
+                self.bool(*value);
+                self.span(*span);
+            }
+            Pattern::Variant {
+                enum_name,
+                variant,
+                fields,
+                span,
+            } => {
+                self.byte(5);
+                self.identifier(enum_name);
+                self.identifier(variant);
+                self.list(fields.iter(), Self::pattern);
+                self.span(*span);
+            }
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Declaration {
+                identifier,
+                mutable,
+                basetype,
+                value,
+                attributes,
+            } => {
+                self.byte(0);
+                self.identifier(identifier);
+                self.bool(*mutable);
+                self.option(basetype.as_ref(), Self::basetype);
+                self.option(*value, Self::value);
+                self.list(attributes.iter(), Self::identifier);
+            }
+            Expr::Assign {
+                target,
+                operator,
+                value,
+            } => {
+                self.byte(1);
+                self.value(target);
+                self.option(operator.as_ref(), Self::operator);
+                self.value(value);
+            }
+            Expr::Return { value, span } => {
+                self.byte(2);
+                self.option(value.as_ref(), Self::value);
+                self.span(*span);
+            }
+            Expr::If {
+                condition,
+                body,
+                else_body,
+                span,
+            } => {
+                self.byte(3);
+                self.value(condition);
+                self.list(body.iter(), Self::expr);
+                self.option(*else_body, |this, body| this.list(body.iter(), Self::expr));
+                self.span(*span);
+            }
+            Expr::While {
+                condition,
+                body,
+                span,
+            } => {
+                self.byte(4);
+                self.value(condition);
+                self.list(body.iter(), Self::expr);
+                self.span(*span);
+            }
+            Expr::For {
+                binding,
+                start,
+                end,
+                body,
+                span,
+            } => {
+                self.byte(5);
+                self.identifier(binding);
+                self.value(start);
+                self.option(end.as_ref(), Self::value);
+                self.list(body.iter(), Self::expr);
+                self.span(*span);
+            }
+            Expr::Break(span) => {
+                self.byte(6);
+                self.span(*span);
+            }
+            Expr::Continue(span) => {
+                self.byte(7);
+                self.span(*span);
+            }
+            Expr::Enum {
+                identifier,
+                variants,
+                span,
+            } => {
+                self.byte(8);
+                self.identifier(identifier);
+                self.list(variants.iter(), |this, variant: &Variant| {
+                    this.identifier(&variant.identifier);
+                    this.list(variant.payload.iter(), Self::basetype);
+                });
+                self.span(*span);
+            }
+            Expr::Match { value, arms, span } => {
+                self.byte(9);
+                self.value(value);
+                self.list(arms.iter(), |this, arm: &MatchArm| {
+                    this.pattern(&arm.pattern);
+                    this.list(arm.body.iter(), Self::expr);
+                    this.span(arm.span);
+                });
+                self.span(*span);
+            }
+            Expr::Block { body, span } => {
+                self.byte(10);
+                self.list(body.iter(), Self::expr);
+                self.span(*span);
+            }
+            Expr::Try {
+                body,
+                error,
+                handler,
+                span,
+            } => {
+                self.byte(11);
+                self.list(body.iter(), Self::expr);
+                self.identifier(error);
+                self.list(handler.iter(), Self::expr);
+                self.span(*span);
+            }
+            Expr::Test { name, body, span } => {
+                self.byte(12);
+                self.str(name);
+                self.list(body.iter(), Self::expr);
+                self.span(*span);
+            }
+            Expr::Value(value) => {
+                self.byte(13);
+                self.value(value);
+            }
+        }
+    }
+}
+
+struct Reader<'b, 'a> {
+    bytes: &'b [u8],
+    at: usize,
+    symbols: Vec<Symbol>,
+    arena: &'a Arena,
+}
+
+impl<'a> Reader<'_, 'a> {
+    fn byte(&mut self) -> Result<u8, DecodeError> {
+        let byte = *self.bytes.get(self.at).ok_or(DecodeError::Truncated)?;
+        self.at += 1;
+        Ok(byte)
+    }
+
+    fn u64(&mut self) -> Result<u64, DecodeError> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte < 0x80 {
+                return Ok(value);
+            }
+        }
+        Err(DecodeError::Invalid("number"))
+    }
+
+    fn usize(&mut self) -> Result<usize, DecodeError> {
+        usize::try_from(self.u64()?).map_err(|_| DecodeError::Invalid("number"))
+    }
+
+    // the length of something that follows, every item of it is at least a
+    // byte, so a broken one can't make it allocate more than what's left
+    fn len(&mut self) -> Result<usize, DecodeError> {
+        let len = self.usize()?;
+        if len > self.bytes.len() - self.at {
+            return Err(DecodeError::Truncated);
+        }
+        Ok(len)
+    }
+
+    fn i64(&mut self) -> Result<i64, DecodeError> {
+        let value = self.u64()?;
+        Ok((value >> 1) as i64 ^ -((value & 1) as i64))
+    }
+
+    fn f64(&mut self) -> Result<f64, DecodeError> {
+        let mut bits = [0; 8];
+        for byte in &mut bits {
+            *byte = self.byte()?;
+        }
+        Ok(f64::from_bits(u64::from_le_bytes(bits)))
+    }
+
+    fn bool(&mut self) -> Result<bool, DecodeError> {
+        match self.byte()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(DecodeError::Invalid("bool")),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, DecodeError> {
+        let len = self.len()?;
+        let bytes = &self.bytes[self.at..self.at + len];
+        self.at += len;
+        String::from_utf8(bytes.to_vec()).map_err(|_| DecodeError::Invalid("string"))
+    }
+
+    fn symbol(&mut self) -> Result<Symbol, DecodeError> {
+        let index = self.usize()?;
+        self.symbols
+            .get(index)
+            .copied()
+            .ok_or(DecodeError::Invalid("name"))
+    }
+
+    fn span(&mut self) -> Result<Span, DecodeError> {
+        let start = self.usize()?;
+        let len = self.usize()?;
+        let end = start.checked_add(len).ok_or(DecodeError::Invalid("span"))?;
+        Ok(Span::new(start, end))
+    }
+
+    fn id(&mut self) -> Result<NodeId, DecodeError> {
+        let id = u32::try_from(self.u64()?).map_err(|_| DecodeError::Invalid("id"))?;
+        Ok(self.arena.keep_id(NodeId(id)))
+    }
+
+    fn operator(&mut self) -> Result<TokenKind, DecodeError> {
+        let index = self.usize()?;
+        OPERATORS
+            .get(index)
+            .cloned()
+            .ok_or(DecodeError::Invalid("operator"))
+    }
+
+    fn option<T>(
+        &mut self,
+        read: impl FnOnce(&mut Self) -> Result<T, DecodeError>,
+    ) -> Result<Option<T>, DecodeError> {
+        match self.byte()? {
+            0 => Ok(None),
+            1 => read(self).map(Some),
+            _ => Err(DecodeError::Invalid("option")),
+        }
+    }
+
+    fn vec<T>(
+        &mut self,
+        mut read: impl FnMut(&mut Self) -> Result<T, DecodeError>,
+    ) -> Result<Vec<T>, DecodeError> {
+        let len = self.len()?;
+        (0..len).map(|_| read(self)).collect()
+    }
+
+    fn slice<T: Node>(
+        &mut self,
+        read: impl FnMut(&mut Self) -> Result<T, DecodeError>,
+    ) -> Result<&'a [T], DecodeError> {
+        let items = self.vec(read)?;
+        Ok(self.arena.alloc_slice(items))
+    }
+
+    fn program(&mut self) -> Result<Program<'a>, DecodeError> {
+        Ok(Program {
+            body: self.slice(Self::expr)?,
+            uses: self.vec(Self::use_)?,
+            modules: self.vec(|this| {
+                Ok(Module {
+                    path: this.string()?,
+                    program: this.program()?,
+                })
+            })?,
+        })
+    }
+
+    fn use_(&mut self) -> Result<Use, DecodeError> {
+        let path = self.vec(Self::identifier)?;
+        if path.is_empty() {
+            return Err(DecodeError::Invalid("use"));
+        }
+        Ok(Use {
+            path,
+            span: self.span()?,
+            module: self.option(Self::usize)?,
+        })
+    }
+
+    fn identifier(&mut self) -> Result<Identifier, DecodeError> {
+        Ok(Identifier {
+            name: self.symbol()?,
+            span: self.span()?,
+            id: self.id()?,
+        })
+    }
+
+    fn param(&mut self) -> Result<Param, DecodeError> {
+        Ok(Param {
+            identifier: self.identifier()?,
+            basetype: self.basetype()?,
+        })
+    }
+
+    fn basetype(&mut self) -> Result<BaseType, DecodeError> {
+        Ok(match self.byte()? {
+            0 => BaseType::Void,
+            1 => BaseType::Int,
+            2 => BaseType::Float,
+            3 => BaseType::String,
+            4 => BaseType::Bool,
+            5 => BaseType::Function {
+                params: self.vec(Self::param)?,
+                return_type: Box::new(self.basetype()?),
+            },
+            6 => BaseType::Named(self.symbol()?),
+            7 => BaseType::List(Box::new(self.basetype()?)),
+            8 => BaseType::Map(Box::new(self.basetype()?), Box::new(self.basetype()?)),
+            _ => return Err(DecodeError::Invalid("type")),
+        })
+    }
+
+    fn boxed(&mut self) -> Result<&'a ValueExpr<'a>, DecodeError> {
+        let value = self.value()?;
+        Ok(self.arena.alloc(value))
+    }
+
+    fn value(&mut self) -> Result<ValueExpr<'a>, DecodeError> {
+        Ok(match self.byte()? {
+            0 => ValueExpr::Int {
+                value: self.i64()?,
+                span: self.span()?,
+                id: self.id()?,
+            },
+            1 => ValueExpr::Float {
+                value: self.f64()?,
+                span: self.span()?,
+                id: self.id()?,
+            },
+            2 => ValueExpr::String {
+                value: self.string()?,
+                span: self.span()?,
+                id: self.id()?,
+            },
+            3 => ValueExpr::Bool {
+                value: self.bool()?,
+                span: self.span()?,
+                id: self.id()?,
+            },
+            4 => ValueExpr::Identifier(self.identifier()?),
+            5 => ValueExpr::Binary {
+                left: self.boxed()?,
+                right: self.boxed()?,
+                operator: self.operator()?,
+                id: self.id()?,
+            },
+            6 => ValueExpr::Unary {
+                operand: self.boxed()?,
+                operator: self.operator()?,
+                span: self.span()?,
+                id: self.id()?,
+            },
+            7 => ValueExpr::Call {
+                callee: self.boxed()?,
+                args: self.slice(Self::value)?,
+                span: self.span()?,
+                id: self.id()?,
+            },
+            8 => ValueExpr::Cast {
+                value: self.boxed()?,
+                basetype: self.basetype()?,
+                span: self.span()?,
+                id: self.id()?,
+            },
+            9 => ValueExpr::Field {
+                value: self.boxed()?,
+                field: self.identifier()?,
+                id: self.id()?,
+            },
+            10 => ValueExpr::List {
+                items: self.slice(Self::value)?,
+                span: self.span()?,
+                id: self.id()?,
+            },
+            11 => ValueExpr::Map {
+                entries: self.slice(|this| Ok((this.value()?, this.value()?)))?,
+                span: self.span()?,
+                id: self.id()?,
+            },
+            12 => ValueExpr::Function {
+                params: self.slice(Self::param)?,
+                return_type: self.basetype()?,
+                body: self.slice(Self::expr)?,
+                span: self.span()?,
+                id: self.id()?,
+            },
+            _ => return Err(DecodeError::Invalid("value")),
+        })
+    }
+
+    fn pattern(&mut self) -> Result<Pattern<'a>, DecodeError> {
+        Ok(match self.byte()? {
+            0 => Pattern::Wildcard(self.span()?),
+            1 => Pattern::Binding(self.identifier()?),
+            2 => Pattern::Int {
+                value: self.i64()?,
+                span: self.span()?,
+            },
+            3 => Pattern::String {
+                value: self.string()?,
+                span: self.span()?,
+            },
+            4 => Pattern::Bool {
+                value: self.bool()?,
+                span: self.span()?,
+            },
+            5 => Pattern::Variant {
+                enum_name: self.identifier()?,
+                variant: self.identifier()?,
+                fields: self.slice(Self::pattern)?,
+                span: self.span()?,
+            },
+            _ => return Err(DecodeError::Invalid("pattern")),
+        })
+    }
+
+    fn expr(&mut self) -> Result<Expr<'a>, DecodeError> {
+        Ok(match self.byte()? {
+            0 => Expr::Declaration {
+                identifier: self.identifier()?,
+                mutable: self.bool()?,
+                basetype: self.option(Self::basetype)?,
+                value: self.option(Self::boxed)?,
+                attributes: self.slice(Self::identifier)?,
+            },
+            1 => Expr::Assign {
+                target: self.value()?,
+                operator: self.option(Self::operator)?,
+                value: self.value()?,
+            },
+            2 => Expr::Return {
+                value: self.option(Self::value)?,
+                span: self.span()?,
+            },
+            3 => Expr::If {
+                condition: self.value()?,
+                body: self.slice(Self::expr)?,
+                else_body: self.option(|this| this.slice(Self::expr))?,
+                span: self.span()?,
+            },
+            4 => Expr::While {
+                condition: self.value()?,
+                body: self.slice(Self::expr)?,
+                span: self.span()?,
+            },
+            5 => Expr::For {
+                binding: self.identifier()?,
+                start: self.value()?,
+                end: self.option(Self::value)?,
+                body: self.slice(Self::expr)?,
+                span: self.span()?,
+            },
+            6 => Expr::Break(self.span()?),
+            7 => Expr::Continue(self.span()?),
+            8 => Expr::Enum {
+                identifier: self.identifier()?,
+                variants: self.slice(|this| {
+                    Ok(Variant {
+                        identifier: this.identifier()?,
+                        payload: this.vec(Self::basetype)?,
+                    })
+                })?,
+                span: self.span()?,
+            },
+            9 => Expr::Match {
+                value: self.value()?,
+                arms: self.slice(|this| {
+                    Ok(MatchArm {
+                        pattern: this.pattern()?,
+                        body: this.slice(Self::expr)?,
+                        span: this.span()?,
+                    })
+                })?,
+                span: self.span()?,
+            },
+            10 => Expr::Block {
+                body: self.slice(Self::expr)?,
+                span: self.span()?,
+            },
+            11 => Expr::Try {
+                body: self.slice(Self::expr)?,
+                error: self.identifier()?,
+                handler: self.slice(Self::expr)?,
+                span: self.span()?,
+            },
+            12 => Expr::Test {
+                name: self.string()?,
+                body: self.slice(Self::expr)?,
+                span: self.span()?,
+            },
+            13 => Expr::Value(self.value()?),
+            _ => return Err(DecodeError::Invalid("statement")),
+        })
+    }
+}
@@ -0,0 +1,460 @@
+use std::fmt;
+
+use ez_lexer::{Span, Symbol, TokenKind};
+
+pub use crate::arena::{Arena, Node};
+
+mod arena;
+pub mod binary;
+
+// names and values get an id from the arena they're parsed into, unique among
+// the files of a program, so what the passes find out about them is kept in
+// tables of their own instead of in the tree. The default id is the one of
+// names the compiler makes up, which aren't in any file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct NodeId(pub u32);
+
+#[derive(Debug, Clone)]
+pub struct Identifier {
+    pub name: Symbol,
+    pub span: Span,
+    pub id: NodeId,
+}
+
+#[derive(Debug, Clone)]
+pub struct Param {
+    pub identifier: Identifier,
+    pub basetype: BaseType,
+}
+
+#[derive(Debug, Clone)]
+pub enum BaseType {
+    Void,
+    Int,
+    Float,
+    String,
+    Bool,
+    Function {
+        params: Vec<Param>,
+        return_type: Box<BaseType>,
+    },
+    // user defined types, like enums, referred to by name
+    Named(Symbol),
+    // `[int]`
+    List(Box<BaseType>),
+    // `[string: int]`, keys are ints, strings or bools
+    Map(Box<BaseType>, Box<BaseType>),
+}
+
+// parameter names are not part of a function's type, only their types are compared
+impl BaseType {
+    pub fn is_numeric(&self) -> bool {
+        matches!(self, BaseType::Int | BaseType::Float)
+    }
+
+    // the types that can be the keys of a map
+    pub fn is_hashable(&self) -> bool {
+        matches!(self, BaseType::Int | BaseType::String | BaseType::Bool)
+    }
+}
+
+impl fmt::Display for BaseType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BaseType::Void => write!(f, "void"),
+            BaseType::Int => write!(f, "int"),
+            BaseType::Float => write!(f, "float"),
+            BaseType::String => write!(f, "string"),
+            BaseType::Bool => write!(f, "bool"),
+            BaseType::Function {
+                params,
+                return_type,
+            } => {
+                write!(f, "fn(")?;
+                for (i, param) in params.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", param.basetype)?;
+                }
+                write!(f, ") {}", return_type)
+            }
+            BaseType::Named(name) => write!(f, "{}", name),
+            BaseType::List(item) => write!(f, "[{}]", item),
+            BaseType::Map(key, value) => write!(f, "[{}: {}]", key, value),
+        }
+    }
+}
+
+impl PartialEq for BaseType {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (
+                BaseType::Function {
+                    params: a_params,
+                    return_type: a_return,
+                },
+                BaseType::Function {
+                    params: b_params,
+                    return_type: b_return,
+                },
+            ) => {
+                a_params.len() == b_params.len()
+                    && a_params
+                        .iter()
+                        .zip(b_params)
+                        .all(|(a, b)| a.basetype == b.basetype)
+                    && a_return == b_return
+            }
+            (BaseType::Named(a), BaseType::Named(b)) => a == b,
+            (BaseType::List(a), BaseType::List(b)) => a == b,
+            (BaseType::Map(a_key, a_value), BaseType::Map(b_key, b_value)) => {
+                a_key == b_key && a_value == b_value
+            }
+            _ => std::mem::discriminant(self) == std::mem::discriminant(other),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ValueExpr<'a> {
+    Int {
+        value: i64,
+        span: Span,
+        id: NodeId,
+    },
+
+    Float {
+        value: f64,
+        span: Span,
+        id: NodeId,
+    },
+
+    String {
+        value: String,
+        span: Span,
+        id: NodeId,
+    },
+
+    Bool {
+        value: bool,
+        span: Span,
+        id: NodeId,
+    },
+
+    Identifier(Identifier),
+
+    Binary {
+        left: &'a ValueExpr<'a>,
+        right: &'a ValueExpr<'a>,
+        operator: TokenKind,
+        id: NodeId,
+    },
+
+    Unary {
+        operand: &'a ValueExpr<'a>,
+        operator: TokenKind,
+        span: Span,
+        id: NodeId,
+    },
+
+    Call {
+        callee: &'a ValueExpr<'a>,
+        args: &'a [ValueExpr<'a>],
+        span: Span,
+        id: NodeId,
+    },
+
+    // `value as type`
+    Cast {
+        value: &'a ValueExpr<'a>,
+        basetype: BaseType,
+        span: Span,
+        id: NodeId,
+    },
+
+    // `value.field`, for now only used to refer to enum variants: `Color.Red`
+    Field {
+        value: &'a ValueExpr<'a>,
+        field: Identifier,
+        id: NodeId,
+    },
+
+    // `[a, b]`
+    List {
+        items: &'a [ValueExpr<'a>],
+        span: Span,
+        id: NodeId,
+    },
+
+    // `[key: value]`, `[:]` when it's empty
+    Map {
+        entries: &'a [(ValueExpr<'a>, ValueExpr<'a>)],
+        span: Span,
+        id: NodeId,
+    },
+
+    Function {
+        params: &'a [Param],
+        return_type: BaseType,
+        body: &'a [Expr<'a>],
+        span: Span,
+        id: NodeId,
+    },
+}
+
+impl ValueExpr<'_> {
+    pub fn span(&self) -> Span {
+        match self {
+            ValueExpr::Int { span, .. }
+            | ValueExpr::Float { span, .. }
+            | ValueExpr::String { span, .. }
+            | ValueExpr::Bool { span, .. }
+            | ValueExpr::Unary { span, .. }
+            | ValueExpr::Call { span, .. }
+            | ValueExpr::Cast { span, .. }
+            | ValueExpr::List { span, .. }
+            | ValueExpr::Map { span, .. }
+            | ValueExpr::Function { span, .. } => *span,
+            ValueExpr::Identifier(ident) => ident.span,
+            ValueExpr::Binary { left, right, .. } => left.span().to(right.span()),
+            ValueExpr::Field { value, field, .. } => value.span().to(field.span),
+        }
+    }
+
+    pub fn id(&self) -> NodeId {
+        match self {
+            ValueExpr::Int { id, .. }
+            | ValueExpr::Float { id, .. }
+            | ValueExpr::String { id, .. }
+            | ValueExpr::Bool { id, .. }
+            | ValueExpr::Binary { id, .. }
+            | ValueExpr::Unary { id, .. }
+            | ValueExpr::Call { id, .. }
+            | ValueExpr::Cast { id, .. }
+            | ValueExpr::Field { id, .. }
+            | ValueExpr::List { id, .. }
+            | ValueExpr::Map { id, .. }
+            | ValueExpr::Function { id, .. } => *id,
+            ValueExpr::Identifier(identifier) => identifier.id,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Variant {
+    pub identifier: Identifier,
+    pub payload: Vec<BaseType>,
+}
+
+#[derive(Debug)]
+pub enum Pattern<'a> {
+    Wildcard(Span),
+    Binding(Identifier),
+    Int {
+        value: i64,
+        span: Span,
+    },
+    String {
+        value: String,
+        span: Span,
+    },
+    Bool {
+        value: bool,
+        span: Span,
+    },
+    // `Enum.Variant` or `Enum.Variant(a, b)`
+    Variant {
+        enum_name: Identifier,
+        variant: Identifier,
+        fields: &'a [Pattern<'a>],
+        span: Span,
+    },
+}
+
+impl Pattern<'_> {
+    pub fn span(&self) -> Span {
+        match self {
+            Pattern::Wildcard(span)
+            | Pattern::Int { span, .. }
+            | Pattern::String { span, .. }
+            | Pattern::Bool { span, .. }
+            | Pattern::Variant { span, .. } => *span,
+            Pattern::Binding(identifier) => identifier.span,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct MatchArm<'a> {
+    pub pattern: Pattern<'a>,
+    pub body: &'a [Expr<'a>],
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub enum Expr<'a> {
+    Declaration {
+        identifier: Identifier,
+        mutable: bool,
+        basetype: Option<BaseType>,
+        // `None` for `name type;`, the variable has to be assigned before it's read
+        value: Option<&'a ValueExpr<'a>>,
+        // the `@name`s written above it, only top level declarations have them
+        attributes: &'a [Identifier],
+    },
+
+    // `target = value;`, compound assignments like `+=` keep their binary operator
+    Assign {
+        target: ValueExpr<'a>,
+        operator: Option<TokenKind>,
+        value: ValueExpr<'a>,
+    },
+
+    Return {
+        value: Option<ValueExpr<'a>>,
+        span: Span,
+    },
+
+    // `else if` is stored as an else body containing a single `If`
+    If {
+        condition: ValueExpr<'a>,
+        body: &'a [Expr<'a>],
+        else_body: Option<&'a [Expr<'a>]>,
+        span: Span,
+    },
+
+    While {
+        condition: ValueExpr<'a>,
+        body: &'a [Expr<'a>],
+        span: Span,
+    },
+
+    // `for i in start..end {}`, the end is excluded. Without an end it's
+    // `for item in start {}`, going through a list, or the keys of a map
+    For {
+        binding: Identifier,
+        start: ValueExpr<'a>,
+        end: Option<ValueExpr<'a>>,
+        body: &'a [Expr<'a>],
+        span: Span,
+    },
+
+    Break(Span),
+    Continue(Span),
+
+    // `enum Name { A, B(int, string) }`
+    Enum {
+        identifier: Identifier,
+        variants: &'a [Variant],
+        span: Span,
+    },
+
+    Match {
+        value: ValueExpr<'a>,
+        arms: &'a [MatchArm<'a>],
+        span: Span,
+    },
+
+    Block {
+        body: &'a [Expr<'a>],
+        span: Span,
+    },
+
+    // `try { } catch e { }`, a runtime error in the body runs the handler with
+    // its message in `error`
+    Try {
+        body: &'a [Expr<'a>],
+        error: Identifier,
+        handler: &'a [Expr<'a>],
+        span: Span,
+    },
+
+    // `test "name" { }`, only at the top level. It's run by `ez test`, with
+    // the declarations of its file, and skipped otherwise
+    Test {
+        name: String,
+        body: &'a [Expr<'a>],
+        span: Span,
+    },
+
+    // a value used as a statement, e.g. a call
+    Value(ValueExpr<'a>),
+}
+
+// `use shapes.circle;` makes the file `shapes/circle.ez` next to the one using
+// it available as `circle`
+#[derive(Debug)]
+pub struct Use {
+    pub path: Vec<Identifier>,
+    pub span: Span,
+    // the index of the file among the modules of the program, filled in by the
+    // session that loads it
+    pub module: Option<usize>,
+}
+
+impl Use {
+    // the name the module is used by
+    pub fn name(&self) -> &Identifier {
+        self.path.last().expect("a use has at least one name")
+    }
+}
+
+#[derive(Debug)]
+pub struct Module<'a> {
+    // like `shapes/circle.ez`
+    pub path: String,
+    pub program: Program<'a>,
+}
+
+// the nodes of a program are in the arena it was parsed into, only the lists
+// of a file are its own
+#[derive(Debug, Default)]
+pub struct Program<'a> {
+    pub body: &'a [Expr<'a>],
+    pub uses: Vec<Use>,
+    // every file the program uses, directly or not, the ones a file uses come
+    // before it. Their own `modules` are empty, their uses point in here
+    pub modules: Vec<Module<'a>>,
+}
+
+impl<'a> Program<'a> {
+    // the modules first, then the program itself
+    pub fn files(&self) -> impl Iterator<Item = &Program<'a>> {
+        self.modules
+            .iter()
+            .map(|module| &module.program)
+            .chain(std::iter::once(self))
+    }
+
+    // the top level statements of every file, in the order they run
+    pub fn statements(&self) -> impl Iterator<Item = &'a Expr<'a>> + '_ {
+        self.files().flat_map(|program| program.body)
+    }
+}
+
+impl Expr<'_> {
+    pub fn span(&self) -> Span {
+        match self {
+            Expr::Declaration {
+                identifier, value, ..
+            } => match value {
+                Some(value) => identifier.span.to(value.span()),
+                None => identifier.span,
+            },
+            Expr::Assign { target, value, .. } => target.span().to(value.span()),
+            Expr::Block { span, .. }
+            | Expr::Return { span, .. }
+            | Expr::If { span, .. }
+            | Expr::While { span, .. }
+            | Expr::For { span, .. }
+            | Expr::Enum { span, .. }
+            | Expr::Match { span, .. }
+            | Expr::Try { span, .. }
+            | Expr::Test { span, .. }
+            | Expr::Break(span)
+            | Expr::Continue(span) => *span,
+            Expr::Value(value) => value.span(),
+        }
+    }
+}
@@ -0,0 +1,683 @@
+use std::collections::HashSet;
+
+use ez_lexer::Span;
+
+use crate::hir::{Hir, MatchArm, Pattern, Stmt, Value, ValueKind};
+use crate::resolver::DefId;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BlockId(pub usize);
+
+// what the analyses care about in a statement: which locals it writes and which
+// ones it reads
+#[derive(Debug)]
+pub enum Instr<'a> {
+    // a `let` or an assignment to a local, `span` is the name being written
+    Assign {
+        local: DefId,
+        value: &'a Value,
+        span: Span,
+    },
+    // a local getting a value from outside the body, like a parameter or a
+    // pattern binding
+    Bind {
+        local: DefId,
+        span: Span,
+    },
+    // a local declared without a value, it holds nothing until it's assigned
+    Declare {
+        local: DefId,
+        span: Span,
+    },
+    // a value that's only read: conditions, returned values, assignments to
+    // things that aren't locals
+    Eval(&'a Value),
+}
+
+// how a block picks which of its successors to go to
+#[derive(Debug, Default)]
+pub enum Exit<'a> {
+    // to its only successor, or out of the function when it has none
+    #[default]
+    Goto,
+    // on the value of its last instruction, to the first successor when it's
+    // true and to the second one otherwise
+    Branch,
+    // to the successor of the first arm matching the value of its last
+    // instruction
+    Match(&'a [MatchArm]),
+    // out of the function, with the value of its last instruction when there's
+    // one
+    Return(bool),
+    // to the second successor, whose first instruction can fail and go to the
+    // handler of the `try`, the first successor
+    Try,
+}
+
+#[derive(Debug, Default)]
+pub struct Block<'a> {
+    pub instrs: Vec<Instr<'a>>,
+    // a block without successors leaves the function
+    pub successors: Vec<BlockId>,
+    pub exit: Exit<'a>,
+}
+
+// the control-flow graph of a function body, the first block is the entry
+#[derive(Debug)]
+pub struct Cfg<'a> {
+    pub blocks: Vec<Block<'a>>,
+    // the locals declared in the body, parameters included, but not the ones of
+    // nested functions
+    pub locals: HashSet<DefId>,
+}
+
+impl Cfg<'_> {
+    // whether each block can be reached from the entry
+    pub fn reachable(&self) -> Vec<bool> {
+        let mut reachable = vec![false; self.blocks.len()];
+        let mut stack = vec![BlockId(0)];
+        while let Some(block) = stack.pop() {
+            if std::mem::replace(&mut reachable[block.0], true) {
+                continue;
+            }
+
+            stack.extend(&self.blocks[block.0].successors);
+        }
+
+        reachable
+    }
+
+    // the blocks reachable from the entry, each one before its successors
+    // unless the edge goes back up a loop
+    pub fn reverse_postorder(&self) -> Vec<BlockId> {
+        let mut visited = vec![false; self.blocks.len()];
+        let mut order = vec![];
+        // a block is left once all of its successors have been
+        let mut stack = vec![(BlockId(0), 0)];
+        visited[0] = true;
+        while let Some((block, next)) = stack.pop() {
+            match self.blocks[block.0].successors.get(next) {
+                Some(successor) => {
+                    stack.push((block, next + 1));
+                    if !std::mem::replace(&mut visited[successor.0], true) {
+                        stack.push((*successor, 0));
+                    }
+                }
+                None => order.push(block),
+            }
+        }
+
+        order.reverse();
+        order
+    }
+
+    // the immediate dominator of every block, the last block every path from
+    // the entry goes through before it. The entry and the blocks that can't be
+    // reached have none
+    pub fn dominators(&self) -> Vec<Option<BlockId>> {
+        let order = self.reverse_postorder();
+        let predecessors = self.predecessors();
+        let mut position = vec![usize::MAX; self.blocks.len()];
+        for (index, block) in order.iter().enumerate() {
+            position[block.0] = index;
+        }
+
+        let mut dominators = vec![None; self.blocks.len()];
+        dominators[0] = Some(BlockId(0));
+
+        // walks up from both blocks until they meet, the one later in the order
+        // can't dominate the other
+        let intersect = |dominators: &[Option<BlockId>], mut left: BlockId, mut right: BlockId| {
+            while left != right {
+                while position[left.0] > position[right.0] {
+                    left = dominators[left.0].expect("the block was already visited");
+                }
+                while position[right.0] > position[left.0] {
+                    right = dominators[right.0].expect("the block was already visited");
+                }
+            }
+            left
+        };
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for block in &order[1..] {
+                let mut dominator = None;
+                for predecessor in &predecessors[block.0] {
+                    if dominators[predecessor.0].is_none() {
+                        continue;
+                    }
+                    dominator = Some(match dominator {
+                        Some(dominator) => intersect(&dominators, *predecessor, dominator),
+                        None => *predecessor,
+                    });
+                }
+
+                if dominators[block.0] != dominator {
+                    dominators[block.0] = dominator;
+                    changed = true;
+                }
+            }
+        }
+
+        dominators[0] = None;
+        dominators
+    }
+
+    pub fn predecessors(&self) -> Vec<Vec<BlockId>> {
+        let mut predecessors = vec![vec![]; self.blocks.len()];
+        for (index, block) in self.blocks.iter().enumerate() {
+            for successor in &block.successors {
+                predecessors[successor.0].push(BlockId(index));
+            }
+        }
+
+        predecessors
+    }
+}
+
+// the locals a value reads, nested functions count as reading everything they
+// refer to, since they could be called at any point after being created
+pub fn uses(value: &Value, locals: &mut HashSet<DefId>) {
+    match &value.kind {
+        ValueKind::Int(_)
+        | ValueKind::Float(_)
+        | ValueKind::String(_)
+        | ValueKind::Bool(_)
+        | ValueKind::Variant { .. } => {}
+
+        ValueKind::Local(id) => {
+            locals.insert(*id);
+        }
+
+        ValueKind::Binary { left, right, .. } => {
+            uses(left, locals);
+            uses(right, locals);
+        }
+
+        ValueKind::Unary { operand, .. } | ValueKind::Cast { value: operand } => {
+            uses(operand, locals)
+        }
+
+        ValueKind::Call { callee, args } => {
+            uses(callee, locals);
+            for arg in args {
+                uses(arg, locals);
+            }
+        }
+
+        ValueKind::Builtin { args, .. } | ValueKind::List(args) => {
+            for arg in args {
+                uses(arg, locals);
+            }
+        }
+
+        ValueKind::Map(entries) => {
+            for (key, value) in entries {
+                uses(key, locals);
+                uses(value, locals);
+            }
+        }
+
+        ValueKind::Function { body, .. } => {
+            for stmt in body {
+                stmt_uses(stmt, locals);
+            }
+        }
+    }
+}
+
+fn stmt_uses(stmt: &Stmt, locals: &mut HashSet<DefId>) {
+    match stmt {
+        Stmt::Let { value, .. } | Stmt::Value(value) => uses(value, locals),
+        Stmt::Assign { target, value } => {
+            uses(target, locals);
+            uses(value, locals);
+        }
+        Stmt::Return(value) => {
+            if let Some(value) = value {
+                uses(value, locals);
+            }
+        }
+        Stmt::If {
+            condition,
+            body,
+            else_body,
+        } => {
+            uses(condition, locals);
+            for stmt in body.iter().chain(else_body) {
+                stmt_uses(stmt, locals);
+            }
+        }
+        Stmt::While {
+            condition,
+            body,
+            step,
+        } => {
+            uses(condition, locals);
+            for stmt in body.iter().chain(step) {
+                stmt_uses(stmt, locals);
+            }
+        }
+        Stmt::Match { value, arms } => {
+            uses(value, locals);
+            for stmt in arms.iter().flat_map(|arm| &arm.body) {
+                stmt_uses(stmt, locals);
+            }
+        }
+        Stmt::Try { body, handler, .. } => {
+            for stmt in body.iter().chain(handler) {
+                stmt_uses(stmt, locals);
+            }
+        }
+        Stmt::Block(body) => {
+            for stmt in body {
+                stmt_uses(stmt, locals);
+            }
+        }
+        Stmt::Declare { .. } | Stmt::Break | Stmt::Continue => {}
+    }
+}
+
+impl Instr<'_> {
+    pub fn defines(&self) -> Option<DefId> {
+        self.site().map(|(local, _)| local)
+    }
+
+    // the local written and where
+    pub fn site(&self) -> Option<(DefId, Span)> {
+        match self {
+            Instr::Assign { local, span, .. }
+            | Instr::Bind { local, span }
+            | Instr::Declare { local, span } => Some((*local, *span)),
+            Instr::Eval(_) => None,
+        }
+    }
+
+    pub fn uses(&self) -> HashSet<DefId> {
+        let mut locals = HashSet::new();
+        match self {
+            Instr::Assign { value, .. } | Instr::Eval(value) => uses(value, &mut locals),
+            Instr::Bind { .. } | Instr::Declare { .. } => {}
+        }
+
+        locals
+    }
+}
+
+// where `break` and `continue` jump to in the innermost loop
+struct Loop {
+    step: BlockId,
+    exit: BlockId,
+}
+
+pub struct CfgBuilder<'a> {
+    blocks: Vec<Block<'a>>,
+    locals: HashSet<DefId>,
+    current: BlockId,
+    loops: Vec<Loop>,
+    // the handlers of the `try` bodies being built, the innermost one last
+    handlers: Vec<BlockId>,
+}
+
+impl<'a> CfgBuilder<'a> {
+    fn new() -> Self {
+        Self {
+            blocks: vec![Block::default()],
+            locals: HashSet::new(),
+            current: BlockId(0),
+            loops: vec![],
+            handlers: vec![],
+        }
+    }
+
+    fn new_block(&mut self) -> BlockId {
+        self.blocks.push(Block::default());
+        BlockId(self.blocks.len() - 1)
+    }
+
+    // every instruction in the body of a `try` can fail, so each one starts a
+    // block, and the one before it gets an edge to the handler, which only sees
+    // what was done before the instruction
+    fn push(&mut self, instr: Instr<'a>) {
+        if let Some(handler) = self.handlers.last().copied() {
+            let next = self.new_block();
+            self.goto(handler);
+            self.goto(next);
+            self.blocks[self.current.0].exit = Exit::Try;
+            self.current = next;
+        }
+
+        self.blocks[self.current.0].instrs.push(instr);
+    }
+
+    fn goto(&mut self, target: BlockId) {
+        self.blocks[self.current.0].successors.push(target);
+    }
+
+    // statements after a jump go into a block nothing flows into
+    fn jump(&mut self, target: Option<BlockId>) {
+        if let Some(target) = target {
+            self.goto(target);
+        }
+
+        self.current = self.new_block();
+    }
+
+    fn bind_pattern(&mut self, pattern: &Pattern, span: Span) {
+        match pattern {
+            Pattern::Binding(local) => {
+                self.locals.insert(*local);
+                self.push(Instr::Bind {
+                    local: *local,
+                    span,
+                });
+            }
+            Pattern::Variant { fields, .. } => {
+                for field in fields {
+                    self.bind_pattern(field, span);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn build_body(&mut self, body: &'a [Stmt]) {
+        for stmt in body {
+            self.build_stmt(stmt);
+        }
+    }
+
+    fn build_stmt(&mut self, stmt: &'a Stmt) {
+        match stmt {
+            Stmt::Let { local, value, span } => {
+                self.locals.insert(*local);
+                self.push(Instr::Assign {
+                    local: *local,
+                    value,
+                    span: *span,
+                });
+            }
+
+            Stmt::Declare { local, span } => {
+                self.locals.insert(*local);
+                self.push(Instr::Declare {
+                    local: *local,
+                    span: *span,
+                });
+            }
+
+            Stmt::Assign { target, value } => match target.kind {
+                ValueKind::Local(local) => self.push(Instr::Assign {
+                    local,
+                    value,
+                    span: target.span,
+                }),
+                _ => {
+                    self.push(Instr::Eval(target));
+                    self.push(Instr::Eval(value));
+                }
+            },
+
+            Stmt::Return(value) => {
+                if let Some(value) = value {
+                    self.push(Instr::Eval(value));
+                }
+
+                self.blocks[self.current.0].exit = Exit::Return(value.is_some());
+                self.jump(None);
+            }
+
+            Stmt::If {
+                condition,
+                body,
+                else_body,
+            } => {
+                self.push(Instr::Eval(condition));
+                let then_block = self.new_block();
+                let else_block = self.new_block();
+                let join = self.new_block();
+                self.goto(then_block);
+                self.goto(else_block);
+                self.blocks[self.current.0].exit = Exit::Branch;
+
+                self.current = then_block;
+                self.build_body(body);
+                self.goto(join);
+
+                self.current = else_block;
+                self.build_body(else_body);
+                self.goto(join);
+
+                self.current = join;
+            }
+
+            Stmt::While {
+                condition,
+                body,
+                step,
+            } => {
+                let header = self.new_block();
+                let body_block = self.new_block();
+                let step_block = self.new_block();
+                let exit = self.new_block();
+                self.goto(header);
+
+                self.current = header;
+                self.push(Instr::Eval(condition));
+                self.goto(body_block);
+                self.goto(exit);
+                self.blocks[self.current.0].exit = Exit::Branch;
+
+                self.loops.push(Loop {
+                    step: step_block,
+                    exit,
+                });
+                self.current = body_block;
+                self.build_body(body);
+                self.goto(step_block);
+                self.loops.pop();
+
+                self.current = step_block;
+                self.build_body(step);
+                self.goto(header);
+
+                self.current = exit;
+            }
+
+            Stmt::Match { value, arms } => {
+                self.push(Instr::Eval(value));
+                let start = self.current;
+                let join = self.new_block();
+
+                if arms.is_empty() {
+                    self.goto(join);
+                } else {
+                    self.blocks[start.0].exit = Exit::Match(arms);
+                }
+
+                for arm in arms {
+                    let arm_block = self.new_block();
+                    self.blocks[start.0].successors.push(arm_block);
+
+                    self.current = arm_block;
+                    self.bind_pattern(&arm.pattern, value.span);
+                    self.build_body(&arm.body);
+                    self.goto(join);
+                }
+
+                self.current = join;
+            }
+
+            Stmt::Try {
+                body,
+                error,
+                handler,
+                span,
+            } => {
+                let handler_block = self.new_block();
+                let join = self.new_block();
+
+                self.handlers.push(handler_block);
+                self.build_body(body);
+                self.handlers.pop();
+                self.goto(join);
+
+                self.current = handler_block;
+                self.locals.insert(*error);
+                self.push(Instr::Bind {
+                    local: *error,
+                    span: *span,
+                });
+                self.build_body(handler);
+                self.goto(join);
+
+                self.current = join;
+            }
+
+            Stmt::Break => {
+                let exit = self.loops.last().map(|current| current.exit);
+                self.jump(exit);
+            }
+
+            Stmt::Continue => {
+                let step = self.loops.last().map(|current| current.step);
+                self.jump(step);
+            }
+
+            Stmt::Block(body) => self.build_body(body),
+
+            Stmt::Value(value) => self.push(Instr::Eval(value)),
+        }
+    }
+
+    // the graph of a function, its parameters are bound on entry
+    pub fn build(params: &[(DefId, Span)], body: &'a [Stmt]) -> Cfg<'a> {
+        let mut builder = Self::new();
+        for (local, span) in params {
+            builder.locals.insert(*local);
+            builder.push(Instr::Bind {
+                local: *local,
+                span: *span,
+            });
+        }
+
+        builder.build_body(body);
+
+        Cfg {
+            blocks: builder.blocks,
+            locals: builder.locals,
+        }
+    }
+}
+
+// a function found in the HIR, along with the graph of its body
+pub struct Function<'a> {
+    pub value: &'a Value,
+    pub cfg: Cfg<'a>,
+}
+
+// builds the graphs of every function in the program, nested ones included
+pub fn functions(hir: &Hir) -> Vec<Function<'_>> {
+    let mut functions = vec![];
+    let tests = hir.tests.iter().flat_map(|test| &test.body);
+    for stmt in hir.body.iter().chain(tests) {
+        collect_stmt(stmt, &mut functions);
+    }
+
+    functions
+}
+
+fn collect_value<'a>(value: &'a Value, functions: &mut Vec<Function<'a>>) {
+    match &value.kind {
+        ValueKind::Binary { left, right, .. } => {
+            collect_value(left, functions);
+            collect_value(right, functions);
+        }
+        ValueKind::Unary { operand, .. } | ValueKind::Cast { value: operand } => {
+            collect_value(operand, functions)
+        }
+        ValueKind::Call { callee, args } => {
+            collect_value(callee, functions);
+            for arg in args {
+                collect_value(arg, functions);
+            }
+        }
+        ValueKind::Builtin { args, .. } | ValueKind::List(args) => {
+            for arg in args {
+                collect_value(arg, functions);
+            }
+        }
+        ValueKind::Map(entries) => {
+            for (key, value) in entries {
+                collect_value(key, functions);
+                collect_value(value, functions);
+            }
+        }
+        ValueKind::Function { params, body } => {
+            let params: Vec<(DefId, Span)> = params.iter().map(|id| (*id, value.span)).collect();
+            functions.push(Function {
+                value,
+                cfg: CfgBuilder::build(&params, body),
+            });
+
+            for stmt in body {
+                collect_stmt(stmt, functions);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn collect_stmt<'a>(stmt: &'a Stmt, functions: &mut Vec<Function<'a>>) {
+    match stmt {
+        Stmt::Let { value, .. } | Stmt::Value(value) => collect_value(value, functions),
+        Stmt::Assign { target, value } => {
+            collect_value(target, functions);
+            collect_value(value, functions);
+        }
+        Stmt::Return(value) => {
+            if let Some(value) = value {
+                collect_value(value, functions);
+            }
+        }
+        Stmt::If {
+            condition,
+            body,
+            else_body,
+        } => {
+            collect_value(condition, functions);
+            for stmt in body.iter().chain(else_body) {
+                collect_stmt(stmt, functions);
+            }
+        }
+        Stmt::While {
+            condition,
+            body,
+            step,
+        } => {
+            collect_value(condition, functions);
+            for stmt in body.iter().chain(step) {
+                collect_stmt(stmt, functions);
+            }
+        }
+        Stmt::Match { value, arms } => {
+            collect_value(value, functions);
+            for stmt in arms.iter().flat_map(|arm| &arm.body) {
+                collect_stmt(stmt, functions);
+            }
+        }
+        Stmt::Try { body, handler, .. } => {
+            for stmt in body.iter().chain(handler) {
+                collect_stmt(stmt, functions);
+            }
+        }
+        Stmt::Block(body) => {
+            for stmt in body {
+                collect_stmt(stmt, functions);
+            }
+        }
+        Stmt::Declare { .. } | Stmt::Break | Stmt::Continue => {}
+    }
+}
@@ -0,0 +1,208 @@
+use std::collections::{HashSet, VecDeque};
+
+use ez_lexer::Span;
+
+use crate::cfg::{BlockId, Cfg, Instr};
+use crate::hir::Hir;
+use crate::resolver::DefId;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
+    Forward,
+    Backward,
+}
+
+// a dataflow problem over the facts of a `Cfg`, facts only ever grow through
+// `join` and `transfer` has to be monotonic, so solving always terminates
+pub trait Analysis {
+    type Fact: Clone + PartialEq;
+
+    const DIRECTION: Direction;
+
+    // what every block starts out with
+    fn bottom(&self) -> Self::Fact;
+
+    // the fact at the entry of the function for forward analyses, and at its
+    // exits for backward ones
+    fn boundary(&self) -> Self::Fact;
+
+    fn join(&self, into: &mut Self::Fact, other: &Self::Fact);
+
+    fn transfer(&self, instr: &Instr, fact: &mut Self::Fact);
+}
+
+// the facts at the start and at the end of every block, in program order for
+// both directions
+#[derive(Debug)]
+pub struct Results<F> {
+    pub entry: Vec<F>,
+    pub exit: Vec<F>,
+}
+
+// finds the fixpoint with a worklist, blocks are revisited whenever the facts
+// flowing into them change
+pub fn solve<A: Analysis>(analysis: &A, cfg: &Cfg) -> Results<A::Fact> {
+    let count = cfg.blocks.len();
+    let predecessors = cfg.predecessors();
+    let mut results = Results {
+        entry: vec![analysis.bottom(); count],
+        exit: vec![analysis.bottom(); count],
+    };
+
+    let mut worklist: VecDeque<BlockId> = (0..count).map(BlockId).collect();
+    let mut queued = vec![true; count];
+
+    while let Some(block) = worklist.pop_front() {
+        queued[block.0] = false;
+        let instrs = &cfg.blocks[block.0].instrs;
+
+        let (changed, dependents) = match A::DIRECTION {
+            Direction::Forward => {
+                let mut fact = if block.0 == 0 {
+                    analysis.boundary()
+                } else {
+                    analysis.bottom()
+                };
+                for predecessor in &predecessors[block.0] {
+                    analysis.join(&mut fact, &results.exit[predecessor.0]);
+                }
+                results.entry[block.0] = fact.clone();
+
+                for instr in instrs {
+                    analysis.transfer(instr, &mut fact);
+                }
+
+                let changed = results.exit[block.0] != fact;
+                results.exit[block.0] = fact;
+                (changed, &cfg.blocks[block.0].successors)
+            }
+
+            Direction::Backward => {
+                let successors = &cfg.blocks[block.0].successors;
+                let mut fact = if successors.is_empty() {
+                    analysis.boundary()
+                } else {
+                    analysis.bottom()
+                };
+                for successor in successors {
+                    analysis.join(&mut fact, &results.entry[successor.0]);
+                }
+                results.exit[block.0] = fact.clone();
+
+                for instr in instrs.iter().rev() {
+                    analysis.transfer(instr, &mut fact);
+                }
+
+                let changed = results.entry[block.0] != fact;
+                results.entry[block.0] = fact;
+                (changed, &predecessors[block.0])
+            }
+        };
+
+        if changed {
+            for dependent in dependents {
+                if !queued[dependent.0] {
+                    queued[dependent.0] = true;
+                    worklist.push_back(*dependent);
+                }
+            }
+        }
+    }
+
+    results
+}
+
+// the locals whose current value might still be read
+pub struct Liveness;
+
+impl Analysis for Liveness {
+    type Fact = HashSet<DefId>;
+
+    const DIRECTION: Direction = Direction::Backward;
+
+    fn bottom(&self) -> Self::Fact {
+        HashSet::new()
+    }
+
+    fn boundary(&self) -> Self::Fact {
+        HashSet::new()
+    }
+
+    fn join(&self, into: &mut Self::Fact, other: &Self::Fact) {
+        into.extend(other);
+    }
+
+    fn transfer(&self, instr: &Instr, fact: &mut Self::Fact) {
+        if let Some(local) = instr.defines() {
+            fact.remove(&local);
+        }
+
+        fact.extend(instr.uses());
+    }
+}
+
+// which writes to locals might still be their current value, a write is the
+// local along with the span of the statement doing it
+pub struct ReachingDefinitions;
+
+impl Analysis for ReachingDefinitions {
+    type Fact = HashSet<(DefId, Span)>;
+
+    const DIRECTION: Direction = Direction::Forward;
+
+    fn bottom(&self) -> Self::Fact {
+        HashSet::new()
+    }
+
+    fn boundary(&self) -> Self::Fact {
+        HashSet::new()
+    }
+
+    fn join(&self, into: &mut Self::Fact, other: &Self::Fact) {
+        into.extend(other);
+    }
+
+    fn transfer(&self, instr: &Instr, fact: &mut Self::Fact) {
+        if let Some((local, span)) = instr.site() {
+            fact.retain(|(defined, _)| *defined != local);
+            fact.insert((local, span));
+        }
+    }
+}
+
+// the blocks of a graph with what both analyses found, for debugging
+pub fn describe(hir: &Hir, cfg: &Cfg) -> String {
+    let liveness = solve(&Liveness, cfg);
+    let reaching = solve(&ReachingDefinitions, cfg);
+
+    let name = |id: &DefId| format!("{}#{}", hir.local(*id).name, id.0);
+    let mut output = String::new();
+
+    for (index, block) in cfg.blocks.iter().enumerate() {
+        let successors: Vec<String> = block
+            .successors
+            .iter()
+            .map(|successor| format!("bb{}", successor.0))
+            .collect();
+
+        let mut live: Vec<String> = liveness.entry[index].iter().map(name).collect();
+        live.sort();
+
+        let mut definitions: Vec<String> = reaching.entry[index]
+            .iter()
+            .map(|(id, span)| format!("{}@{}", name(id), span.start))
+            .collect();
+        definitions.sort();
+
+        output.push_str(&format!(
+            "bb{}: {} instruction(s) -> [{}]\n    live: {}\n    reaching: {}\n",
+            index,
+            block.instrs.len(),
+            successors.join(", "),
+            live.join(", "),
+            definitions.join(", ")
+        ));
+    }
+
+    output
+}
@@ -0,0 +1,106 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::hir::Pattern;
+use crate::resolver::Builtin;
+use crate::ssa::{Function, Op, Terminator, ValueId};
+
+// the lists, maps and closures a function makes that nothing can reach once it
+// returns, so a backend can put them in the function's frame rather than on the
+// heap. A value escapes when it's returned, stored in a local kept in memory,
+// passed to a function, bound by a pattern or put in a list or a map the
+// function didn't make, and what's in a value escapes with it. Functions are
+// looked at one at a time, so passing a value to any function other than a
+// builtin counts as escaping
+pub fn local_allocations(function: &Function) -> HashSet<ValueId> {
+    // the values that escape when the one they're under does
+    let mut flows: HashMap<ValueId, Vec<ValueId>> = HashMap::new();
+    let mut escaping: Vec<ValueId> = vec![];
+    let mut allocations = HashSet::new();
+    let mut variants = HashSet::new();
+    // what `push`, `set` and `insert` put in a list or a map, known to be made
+    // here only once every instruction was seen
+    let mut stored: Vec<(ValueId, &[ValueId])> = vec![];
+
+    let instrs = function.blocks.iter().flat_map(|block| &block.instrs);
+    for instr in instrs {
+        let id = instr.id;
+        match &instr.op {
+            Op::List(items) => {
+                allocations.insert(id);
+                flows.entry(id).or_default().extend(items);
+            }
+            Op::Map(entries) => {
+                allocations.insert(id);
+                let values = entries.iter().flat_map(|(key, value)| [*key, *value]);
+                flows.entry(id).or_default().extend(values);
+            }
+            Op::Function(_) => {
+                allocations.insert(id);
+            }
+            Op::Variant { .. } => {
+                variants.insert(id);
+            }
+            Op::Phi(incoming) => {
+                let values = incoming.iter().map(|(_, value)| *value);
+                flows.entry(id).or_default().extend(values);
+            }
+            // an enum value holds what it's made with
+            Op::Call { callee, args } if variants.contains(callee) => {
+                flows.entry(id).or_default().extend(args);
+            }
+            Op::Call { args, .. } => escaping.extend(args),
+            // `get`, `pop`, `remove` and `keys` give back what's in the list or
+            // the map they're given, the other builtins give back something
+            // new, or nothing
+            Op::Builtin { builtin, args } => match (builtin, args.as_slice()) {
+                (Builtin::Get | Builtin::Pop | Builtin::Remove | Builtin::Keys, [from, ..]) => {
+                    flows.entry(id).or_default().push(*from);
+                }
+                (Builtin::Push | Builtin::Set | Builtin::Insert, [target, rest @ ..]) => {
+                    stored.push((*target, rest));
+                }
+                _ => {}
+            },
+            Op::Store(_, value) => escaping.push(*value),
+            _ => {}
+        }
+    }
+
+    // a list or a map from anywhere else, like a parameter or a global, can
+    // still be reached after the function returns
+    for (target, values) in stored {
+        if allocations.contains(&target) {
+            flows.entry(target).or_default().extend(values);
+        } else {
+            escaping.extend(values);
+        }
+    }
+
+    for block in &function.blocks {
+        match &block.terminator {
+            Terminator::Return(Some(value)) => escaping.push(*value),
+            Terminator::Match { value, arms } if arms.iter().any(|(pattern, _)| binds(pattern)) => {
+                escaping.push(*value)
+            }
+            _ => {}
+        }
+    }
+
+    let mut escaped = HashSet::new();
+    while let Some(value) = escaping.pop() {
+        if escaped.insert(value) {
+            escaping.extend(flows.get(&value).into_iter().flatten());
+        }
+    }
+
+    allocations.retain(|value| !escaped.contains(value));
+    allocations
+}
+
+fn binds(pattern: &Pattern) -> bool {
+    match pattern {
+        Pattern::Binding(_) => true,
+        Pattern::Variant { fields, .. } => fields.iter().any(binds),
+        Pattern::Wildcard | Pattern::Int(_) | Pattern::String(_) | Pattern::Bool(_) => false,
+    }
+}
@@ -0,0 +1,443 @@
+use ez_ast::{BaseType, Expr, MatchArm, Pattern, Program, ValueExpr};
+use ez_diagnostics::Diagnostic;
+use ez_lexer::Span;
+
+use crate::typecheck::Types;
+
+#[derive(Debug)]
+pub enum MatchError {
+    // `missing` is an example of a value none of the arms match
+    NonExhaustive { span: Span, missing: String },
+    UnreachableArm(Span),
+}
+
+impl From<MatchError> for Diagnostic {
+    fn from(error: MatchError) -> Self {
+        match error {
+            MatchError::NonExhaustive { span, missing } => Diagnostic::error(
+                "E0304",
+                format!("non-exhaustive match: `{}` not covered", missing),
+            )
+            .with_label(span, format!("pattern `{}` not covered", missing))
+            .with_help("add an arm for it, or a `_` arm to match everything else"),
+
+            MatchError::UnreachableArm(span) => {
+                Diagnostic::warning("W0004", "unreachable match arm")
+                    .with_label(span, "already covered by the arms before it")
+                    .for_lint("unreachable_patterns")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Constructor {
+    // the index of the variant in its enum
+    Variant(usize),
+    Bool(bool),
+    Int(i64),
+    String(String),
+}
+
+// patterns with everything that doesn't matter for coverage stripped, bindings
+// are just wildcards
+#[derive(Debug, Clone)]
+enum Pat {
+    Wild,
+    Constructor(Constructor, Vec<Pat>),
+}
+
+// checks that matches cover every possible value and that every arm can be
+// reached, using the usefulness algorithm from "Warnings for pattern matching"
+// (Maranget): a pattern is useful if it matches a value none of the patterns
+// before it do. An arm is unreachable when its pattern isn't useful, and a match
+// is exhaustive when a wildcard after all of its arms isn't useful.
+pub struct MatchChecker<'a> {
+    types: &'a Types,
+    errors: Vec<MatchError>,
+}
+
+impl<'a> MatchChecker<'a> {
+    pub fn new(types: &'a Types) -> Self {
+        Self {
+            types,
+            errors: vec![],
+        }
+    }
+
+    fn lower(&self, pattern: &Pattern, basetype: &BaseType) -> Pat {
+        match pattern {
+            Pattern::Wildcard(_) | Pattern::Binding(_) => Pat::Wild,
+            Pattern::Int { value, .. } => Pat::Constructor(Constructor::Int(*value), vec![]),
+            Pattern::String { value, .. } => {
+                Pat::Constructor(Constructor::String(value.clone()), vec![])
+            }
+            Pattern::Bool { value, .. } => Pat::Constructor(Constructor::Bool(*value), vec![]),
+            Pattern::Variant {
+                variant, fields, ..
+            } => {
+                let BaseType::Named(name) = basetype else {
+                    return Pat::Wild;
+                };
+
+                let variants = &self.types.enums[name];
+                let Some(index) = variants
+                    .iter()
+                    .position(|candidate| candidate.identifier.name == variant.name)
+                else {
+                    return Pat::Wild;
+                };
+
+                let fields = fields
+                    .iter()
+                    .zip(&variants[index].payload)
+                    .map(|(field, basetype)| self.lower(field, basetype))
+                    .collect();
+
+                Pat::Constructor(Constructor::Variant(index), fields)
+            }
+        }
+    }
+
+    // every constructor of a type, `None` when there are too many to list, like
+    // for ints and strings
+    fn constructors(&self, basetype: &BaseType) -> Option<Vec<Constructor>> {
+        match basetype {
+            BaseType::Bool => Some(vec![Constructor::Bool(true), Constructor::Bool(false)]),
+            BaseType::Named(name) => Some(
+                (0..self.types.enums.get(name)?.len())
+                    .map(Constructor::Variant)
+                    .collect(),
+            ),
+            _ => None,
+        }
+    }
+
+    // the types of the fields of a constructor
+    fn field_types(&self, constructor: &Constructor, basetype: &BaseType) -> Vec<BaseType> {
+        match (constructor, basetype) {
+            (Constructor::Variant(index), BaseType::Named(name)) => {
+                self.types.enums[name][*index].payload.clone()
+            }
+            _ => vec![],
+        }
+    }
+
+    // the rows that match the given constructor, with its fields in place of the
+    // first column
+    fn specialize(row: &[Pat], constructor: &Constructor, arity: usize) -> Option<Vec<Pat>> {
+        let (first, rest) = row.split_first()?;
+        let mut specialized = match first {
+            Pat::Wild => vec![Pat::Wild; arity],
+            Pat::Constructor(other, fields) if other == constructor => fields.clone(),
+            Pat::Constructor(..) => return None,
+        };
+
+        specialized.extend_from_slice(rest);
+        Some(specialized)
+    }
+
+    // the rows starting with a wildcard, without their first column
+    fn default_rows(matrix: &[Vec<Pat>]) -> Vec<Vec<Pat>> {
+        matrix
+            .iter()
+            .filter(|row| matches!(row.first(), Some(Pat::Wild)))
+            .map(|row| row[1..].to_vec())
+            .collect()
+    }
+
+    fn used_constructors(matrix: &[Vec<Pat>]) -> Vec<&Constructor> {
+        matrix
+            .iter()
+            .filter_map(|row| match row.first() {
+                Some(Pat::Constructor(constructor, _)) => Some(constructor),
+                _ => None,
+            })
+            .collect()
+    }
+
+    // every constructor of the type if the first column uses all of them
+    fn complete_signature(
+        &self,
+        matrix: &[Vec<Pat>],
+        basetype: &BaseType,
+    ) -> Option<Vec<Constructor>> {
+        let all = self.constructors(basetype)?;
+        let used = Self::used_constructors(matrix);
+
+        all.iter()
+            .all(|constructor| used.contains(&constructor))
+            .then_some(all)
+    }
+
+    fn is_useful(&self, matrix: &[Vec<Pat>], row: &[Pat], types: &[BaseType]) -> bool {
+        let Some((first, rest)) = row.split_first() else {
+            return matrix.is_empty();
+        };
+
+        let specialized_useful = |constructor: &Constructor| {
+            let fields = self.field_types(constructor, &types[0]);
+            let matrix: Vec<Vec<Pat>> = matrix
+                .iter()
+                .filter_map(|row| Self::specialize(row, constructor, fields.len()))
+                .collect();
+            let Some(row) = Self::specialize(row, constructor, fields.len()) else {
+                return false;
+            };
+
+            let types: Vec<BaseType> = fields.into_iter().chain(types[1..].to_vec()).collect();
+            self.is_useful(&matrix, &row, &types)
+        };
+
+        match first {
+            Pat::Constructor(constructor, _) => specialized_useful(constructor),
+            Pat::Wild => match self.complete_signature(matrix, &types[0]) {
+                Some(all) => all.iter().any(specialized_useful),
+                None => self.is_useful(&Self::default_rows(matrix), rest, &types[1..]),
+            },
+        }
+    }
+
+    // a row of patterns none of the rows of the matrix match, if there is one
+    fn witness(&self, matrix: &[Vec<Pat>], types: &[BaseType]) -> Option<Vec<Pat>> {
+        let Some((basetype, rest)) = types.split_first() else {
+            return matrix.is_empty().then(Vec::new);
+        };
+
+        if let Some(all) = self.complete_signature(matrix, basetype) {
+            return all.into_iter().find_map(|constructor| {
+                let fields = self.field_types(&constructor, basetype);
+                let arity = fields.len();
+                let matrix: Vec<Vec<Pat>> = matrix
+                    .iter()
+                    .filter_map(|row| Self::specialize(row, &constructor, arity))
+                    .collect();
+
+                let types: Vec<BaseType> = fields.into_iter().chain(rest.to_vec()).collect();
+                let mut witness = self.witness(&matrix, &types)?;
+                let remaining = witness.split_off(arity);
+
+                let mut row = vec![Pat::Constructor(constructor, witness)];
+                row.extend(remaining);
+                Some(row)
+            });
+        }
+
+        let mut witness = self.witness(&Self::default_rows(matrix), rest)?;
+
+        // for enums and bools, name one of the missing constructors rather than `_`
+        let used = Self::used_constructors(matrix);
+        let missing = self.constructors(basetype).and_then(|all| {
+            all.into_iter()
+                .find(|constructor| !used.contains(&constructor))
+        });
+        let first = match missing {
+            Some(constructor) => {
+                let arity = self.field_types(&constructor, basetype).len();
+                Pat::Constructor(constructor, vec![Pat::Wild; arity])
+            }
+            None => Pat::Wild,
+        };
+
+        witness.insert(0, first);
+        Some(witness)
+    }
+
+    fn describe(&self, pat: &Pat, basetype: &BaseType) -> String {
+        let Pat::Constructor(constructor, fields) = pat else {
+            return "_".to_string();
+        };
+
+        match constructor {
+            Constructor::Bool(value) => value.to_string(),
+            Constructor::Int(value) => value.to_string(),
+            Constructor::String(value) => format!("\"{}\"", value),
+            Constructor::Variant(index) => {
+                let BaseType::Named(name) = basetype else {
+                    return "_".to_string();
+                };
+
+                let variant = &self.types.enums[name][*index];
+                if fields.is_empty() {
+                    return format!("{}.{}", name, variant.identifier.name);
+                }
+
+                let fields: Vec<String> = fields
+                    .iter()
+                    .zip(&variant.payload)
+                    .map(|(field, basetype)| self.describe(field, basetype))
+                    .collect();
+
+                format!(
+                    "{}.{}({})",
+                    name,
+                    variant.identifier.name,
+                    fields.join(", ")
+                )
+            }
+        }
+    }
+
+    fn check_match(&mut self, value: &ValueExpr, arms: &[MatchArm]) {
+        // the type checker already reported why this value has no type
+        let Some(basetype) = self.types.of(value).cloned() else {
+            return;
+        };
+        let types = [basetype.clone()];
+
+        let mut matrix: Vec<Vec<Pat>> = vec![];
+        for arm in arms {
+            let row = vec![self.lower(&arm.pattern, &basetype)];
+            if !self.is_useful(&matrix, &row, &types) {
+                self.errors.push(MatchError::UnreachableArm(arm.span));
+            }
+
+            matrix.push(row);
+        }
+
+        if let Some(witness) = self.witness(&matrix, &types) {
+            self.errors.push(MatchError::NonExhaustive {
+                span: value.span(),
+                missing: self.describe(&witness[0], &basetype),
+            });
+        }
+
+        for expr in arms.iter().flat_map(|arm| arm.body) {
+            self.check_expr(expr);
+        }
+    }
+
+    fn check_value(&mut self, value: &ValueExpr) {
+        match value {
+            ValueExpr::Int { .. }
+            | ValueExpr::Float { .. }
+            | ValueExpr::String { .. }
+            | ValueExpr::Bool { .. }
+            | ValueExpr::Identifier(_) => {}
+
+            ValueExpr::Binary { left, right, .. } => {
+                self.check_value(left);
+                self.check_value(right);
+            }
+
+            ValueExpr::Unary { operand, .. } => self.check_value(operand),
+
+            ValueExpr::Cast { value, .. } => self.check_value(value),
+
+            ValueExpr::Field { value, .. } => self.check_value(value),
+
+            ValueExpr::Call { callee, args, .. } => {
+                self.check_value(callee);
+                for arg in args.iter() {
+                    self.check_value(arg);
+                }
+            }
+
+            ValueExpr::List { items, .. } => {
+                for item in items.iter() {
+                    self.check_value(item);
+                }
+            }
+
+            ValueExpr::Map { entries, .. } => {
+                for (key, value) in entries.iter() {
+                    self.check_value(key);
+                    self.check_value(value);
+                }
+            }
+
+            ValueExpr::Function { body, .. } => {
+                for expr in body.iter() {
+                    self.check_expr(expr);
+                }
+            }
+        }
+    }
+
+    fn check_expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Declaration { value, .. } => {
+                if let Some(value) = value {
+                    self.check_value(value);
+                }
+            }
+
+            Expr::Assign { target, value, .. } => {
+                self.check_value(target);
+                self.check_value(value);
+            }
+
+            Expr::Block { body, .. } | Expr::Test { body, .. } => {
+                for expr in body.iter() {
+                    self.check_expr(expr);
+                }
+            }
+
+            Expr::If {
+                condition,
+                body,
+                else_body,
+                ..
+            } => {
+                self.check_value(condition);
+                for expr in body.iter().chain(else_body.unwrap_or_default()) {
+                    self.check_expr(expr);
+                }
+            }
+
+            Expr::While {
+                condition, body, ..
+            } => {
+                self.check_value(condition);
+                for expr in body.iter() {
+                    self.check_expr(expr);
+                }
+            }
+
+            Expr::For {
+                start, end, body, ..
+            } => {
+                self.check_value(start);
+                if let Some(end) = end {
+                    self.check_value(end);
+                }
+                for expr in body.iter() {
+                    self.check_expr(expr);
+                }
+            }
+
+            Expr::Match { value, arms, .. } => {
+                self.check_value(value);
+                self.check_match(value, arms);
+            }
+
+            Expr::Try { body, handler, .. } => {
+                for expr in body.iter().chain(*handler) {
+                    self.check_expr(expr);
+                }
+            }
+
+            Expr::Break(_) | Expr::Continue(_) | Expr::Enum { .. } => {}
+
+            Expr::Value(value) => self.check_value(value),
+
+            Expr::Return { value, .. } => {
+                if let Some(value) = value {
+                    self.check_value(value);
+                }
+            }
+        }
+    }
+
+    pub fn check(mut self, program: &Program) -> Result<(), Vec<MatchError>> {
+        for expr in program.statements() {
+            self.check_expr(expr);
+        }
+
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(self.errors)
+        }
+    }
+}
@@ -0,0 +1,481 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use ez_ast::{BaseType, Variant};
+use ez_lexer::{Span, Symbol, TokenKind};
+
+use crate::resolver::{Builtin, DefId};
+
+// the typed and desugared form of a program, which is what the backends work
+// with: every value carries its type, names are replaced by the definition they
+// refer to, `for` loops are `while` loops and compound assignments are spelled out
+
+#[derive(Debug)]
+pub struct Local {
+    pub name: Symbol,
+    pub basetype: BaseType,
+    pub mutable: bool,
+}
+
+#[derive(Debug)]
+pub struct Value {
+    pub kind: ValueKind,
+    pub basetype: BaseType,
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub enum ValueKind {
+    Int(i64),
+    Float(f64),
+    String(String),
+    Bool(bool),
+    Local(DefId),
+    Binary {
+        operator: TokenKind,
+        left: Box<Value>,
+        right: Box<Value>,
+    },
+    Unary {
+        operator: TokenKind,
+        operand: Box<Value>,
+    },
+    Call {
+        callee: Box<Value>,
+        args: Vec<Value>,
+    },
+    Builtin {
+        builtin: Builtin,
+        args: Vec<Value>,
+    },
+    // converts the value to the type of the cast
+    Cast {
+        value: Box<Value>,
+    },
+    Function {
+        params: Vec<DefId>,
+        body: Vec<Stmt>,
+    },
+    // variants with a payload are functions building the value
+    Variant {
+        enum_name: Symbol,
+        index: usize,
+    },
+    List(Vec<Value>),
+    Map(Vec<(Value, Value)>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pattern {
+    Wildcard,
+    Binding(DefId),
+    Int(i64),
+    String(String),
+    Bool(bool),
+    Variant { index: usize, fields: Vec<Pattern> },
+}
+
+#[derive(Debug)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub body: Vec<Stmt>,
+}
+
+#[derive(Debug)]
+pub enum Stmt {
+    // `span` is the name being declared
+    Let {
+        local: DefId,
+        value: Value,
+        span: Span,
+    },
+    // a local declared without a value, it's assigned later on
+    Declare {
+        local: DefId,
+        span: Span,
+    },
+    Assign {
+        target: Value,
+        value: Value,
+    },
+    Return(Option<Value>),
+    If {
+        condition: Value,
+        body: Vec<Stmt>,
+        else_body: Vec<Stmt>,
+    },
+    // `step` runs at the end of every iteration, also when it's cut short by a
+    // `continue`, that's where the counter of a `for` loop is incremented
+    While {
+        condition: Value,
+        body: Vec<Stmt>,
+        step: Vec<Stmt>,
+    },
+    Match {
+        value: Value,
+        arms: Vec<MatchArm>,
+    },
+    // `error` is the string local the handler gets the message in, `span` is
+    // the whole statement
+    Try {
+        body: Vec<Stmt>,
+        error: DefId,
+        handler: Vec<Stmt>,
+        span: Span,
+    },
+    Break,
+    Continue,
+    Block(Vec<Stmt>),
+    Value(Value),
+}
+
+#[derive(Debug, Default)]
+pub struct Hir {
+    // indexed by `DefId`, lowering adds its own locals after the ones the
+    // resolver found
+    pub locals: Vec<Local>,
+    pub enums: HashMap<Symbol, Vec<Variant>>,
+    pub body: Vec<Stmt>,
+    // how many of the statements at the start of the body come from the files
+    // the program uses
+    pub imported: usize,
+    // the locals of the functions of the host, in the order they were given
+    pub hosts: Vec<DefId>,
+    // the tests of every file, only `ez test` runs them
+    pub tests: Vec<Test>,
+    // the functions marked `@bench`, only `ez bench` runs them
+    pub benches: Vec<Bench>,
+}
+
+#[derive(Debug)]
+pub struct Bench {
+    pub name: Symbol,
+    pub local: DefId,
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub struct Test {
+    pub name: String,
+    pub body: Vec<Stmt>,
+    pub span: Span,
+}
+
+impl Hir {
+    pub fn local(&self, id: DefId) -> &Local {
+        &self.locals[id.0]
+    }
+
+    // locals are printed with their id, since names can be shadowed
+    fn local_name(&self, id: DefId) -> String {
+        format!("{}#{}", self.local(id).name, id.0)
+    }
+
+    fn write_value(&self, f: &mut fmt::Formatter<'_>, value: &Value, indent: usize) -> fmt::Result {
+        match &value.kind {
+            ValueKind::Int(value) => write!(f, "{}", value),
+            ValueKind::Float(value) => write!(f, "{:?}", value),
+            ValueKind::String(value) => write!(f, "{:?}", value),
+            ValueKind::Bool(value) => write!(f, "{}", value),
+            ValueKind::Local(id) => write!(f, "{}", self.local_name(*id)),
+
+            ValueKind::Binary {
+                operator,
+                left,
+                right,
+            } => {
+                write!(f, "(")?;
+                self.write_value(f, left, indent)?;
+                write!(f, " {} ", operator.to_string().trim_matches('`'))?;
+                self.write_value(f, right, indent)?;
+                write!(f, ")")
+            }
+
+            ValueKind::Unary { operator, operand } => {
+                write!(f, "{}", operator.to_string().trim_matches('`'))?;
+                self.write_value(f, operand, indent)
+            }
+
+            ValueKind::Cast { value: inner } => {
+                write!(f, "(")?;
+                self.write_value(f, inner, indent)?;
+                write!(f, " as {})", value.basetype)
+            }
+
+            ValueKind::Call { callee, args } => {
+                self.write_value(f, callee, indent)?;
+                self.write_args(f, args, indent)
+            }
+
+            ValueKind::Builtin { builtin, args } => {
+                write!(f, "{}", builtin)?;
+                self.write_args(f, args, indent)
+            }
+
+            ValueKind::Function { params, body } => {
+                write!(f, "fn (")?;
+                for (index, param) in params.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(
+                        f,
+                        "{}: {}",
+                        self.local_name(*param),
+                        self.local(*param).basetype
+                    )?;
+                }
+                write!(f, ")")?;
+
+                if let BaseType::Function { return_type, .. } = &value.basetype {
+                    write!(f, " {}", return_type)?;
+                }
+
+                write!(f, " ")?;
+                self.write_body(f, body, indent)
+            }
+
+            ValueKind::Variant { enum_name, index } => {
+                write!(
+                    f,
+                    "{}.{}",
+                    enum_name, self.enums[enum_name][*index].identifier.name
+                )
+            }
+
+            ValueKind::List(items) => {
+                write!(f, "[")?;
+                for (index, item) in items.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    self.write_value(f, item, indent)?;
+                }
+                write!(f, "]")
+            }
+
+            ValueKind::Map(entries) if entries.is_empty() => write!(f, "[:]"),
+
+            ValueKind::Map(entries) => {
+                write!(f, "[")?;
+                for (index, (key, value)) in entries.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    self.write_value(f, key, indent)?;
+                    write!(f, ": ")?;
+                    self.write_value(f, value, indent)?;
+                }
+                write!(f, "]")
+            }
+        }
+    }
+
+    fn write_args(&self, f: &mut fmt::Formatter<'_>, args: &[Value], indent: usize) -> fmt::Result {
+        write!(f, "(")?;
+        for (index, arg) in args.iter().enumerate() {
+            if index > 0 {
+                write!(f, ", ")?;
+            }
+            self.write_value(f, arg, indent)?;
+        }
+        write!(f, ")")
+    }
+
+    fn write_pattern(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        pattern: &Pattern,
+        basetype: &BaseType,
+    ) -> fmt::Result {
+        match pattern {
+            Pattern::Wildcard => write!(f, "_"),
+            Pattern::Binding(id) => write!(f, "{}", self.local_name(*id)),
+            Pattern::Int(value) => write!(f, "{}", value),
+            Pattern::String(value) => write!(f, "{:?}", value),
+            Pattern::Bool(value) => write!(f, "{}", value),
+            Pattern::Variant { index, fields } => {
+                let BaseType::Named(enum_name) = basetype else {
+                    return write!(f, "_");
+                };
+
+                let variant = &self.enums[enum_name][*index];
+                write!(f, "{}.{}", enum_name, variant.identifier.name)?;
+                if fields.is_empty() {
+                    return Ok(());
+                }
+
+                write!(f, "(")?;
+                for (index, (field, basetype)) in fields.iter().zip(&variant.payload).enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    self.write_pattern(f, field, basetype)?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+
+    fn write_body(&self, f: &mut fmt::Formatter<'_>, body: &[Stmt], indent: usize) -> fmt::Result {
+        writeln!(f, "{{")?;
+        for stmt in body {
+            self.write_stmt(f, stmt, indent + 1)?;
+        }
+        write!(f, "{}}}", "    ".repeat(indent))
+    }
+
+    fn write_stmt(&self, f: &mut fmt::Formatter<'_>, stmt: &Stmt, indent: usize) -> fmt::Result {
+        write!(f, "{}", "    ".repeat(indent))?;
+
+        match stmt {
+            Stmt::Let { local, value, .. } => {
+                let local_def = self.local(*local);
+                let mutable = if local_def.mutable { "mut " } else { "" };
+                write!(
+                    f,
+                    "let {}{}: {} = ",
+                    mutable,
+                    self.local_name(*local),
+                    local_def.basetype
+                )?;
+                self.write_value(f, value, indent)?;
+                write!(f, ";")?;
+            }
+
+            Stmt::Declare { local, .. } => {
+                let local_def = self.local(*local);
+                let mutable = if local_def.mutable { "mut " } else { "" };
+                write!(
+                    f,
+                    "let {}{}: {};",
+                    mutable,
+                    self.local_name(*local),
+                    local_def.basetype
+                )?;
+            }
+
+            Stmt::Assign { target, value } => {
+                self.write_value(f, target, indent)?;
+                write!(f, " = ")?;
+                self.write_value(f, value, indent)?;
+                write!(f, ";")?;
+            }
+
+            Stmt::Return(value) => {
+                write!(f, "return")?;
+                if let Some(value) = value {
+                    write!(f, " ")?;
+                    self.write_value(f, value, indent)?;
+                }
+                write!(f, ";")?;
+            }
+
+            Stmt::If {
+                condition,
+                body,
+                else_body,
+            } => {
+                write!(f, "if ")?;
+                self.write_value(f, condition, indent)?;
+                write!(f, " ")?;
+                self.write_body(f, body, indent)?;
+                if !else_body.is_empty() {
+                    write!(f, " else ")?;
+                    self.write_body(f, else_body, indent)?;
+                }
+            }
+
+            Stmt::While {
+                condition,
+                body,
+                step,
+            } => {
+                write!(f, "while ")?;
+                self.write_value(f, condition, indent)?;
+                write!(f, " ")?;
+                self.write_body(f, body, indent)?;
+                if !step.is_empty() {
+                    write!(f, " step ")?;
+                    self.write_body(f, step, indent)?;
+                }
+            }
+
+            Stmt::Match { value, arms } => {
+                write!(f, "match ")?;
+                self.write_value(f, value, indent)?;
+                writeln!(f, " {{")?;
+                for arm in arms {
+                    write!(f, "{}", "    ".repeat(indent + 1))?;
+                    self.write_pattern(f, &arm.pattern, &value.basetype)?;
+                    write!(f, " ")?;
+                    self.write_body(f, &arm.body, indent + 1)?;
+                    writeln!(f)?;
+                }
+                write!(f, "{}}}", "    ".repeat(indent))?;
+            }
+
+            Stmt::Try {
+                body,
+                error,
+                handler,
+                ..
+            } => {
+                write!(f, "try ")?;
+                self.write_body(f, body, indent)?;
+                write!(f, " catch {} ", self.local_name(*error))?;
+                self.write_body(f, handler, indent)?;
+            }
+
+            Stmt::Break => write!(f, "break;")?,
+            Stmt::Continue => write!(f, "continue;")?,
+            Stmt::Block(body) => self.write_body(f, body, indent)?,
+
+            Stmt::Value(value) => {
+                self.write_value(f, value, indent)?;
+                write!(f, ";")?;
+            }
+        }
+
+        writeln!(f)
+    }
+}
+
+impl fmt::Display for Hir {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut enums: Vec<_> = self.enums.iter().collect();
+        enums.sort_by_key(|(name, _)| *name);
+
+        for (name, variants) in enums {
+            let variants: Vec<String> = variants
+                .iter()
+                .map(|variant| {
+                    if variant.payload.is_empty() {
+                        return variant.identifier.name.to_string();
+                    }
+
+                    let payload: Vec<String> = variant
+                        .payload
+                        .iter()
+                        .map(|basetype| basetype.to_string())
+                        .collect();
+                    format!("{}({})", variant.identifier.name, payload.join(", "))
+                })
+                .collect();
+
+            writeln!(f, "enum {} {{ {} }}", name, variants.join(", "))?;
+        }
+
+        for stmt in &self.body {
+            self.write_stmt(f, stmt, 0)?;
+        }
+
+        for test in &self.tests {
+            write!(f, "test {:?} ", test.name)?;
+            self.write_body(f, &test.body, 0)?;
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
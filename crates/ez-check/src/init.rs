@@ -0,0 +1,199 @@
+use std::collections::{HashMap, HashSet};
+
+use ez_diagnostics::Diagnostic;
+use ez_lexer::{Span, Symbol};
+
+use crate::cfg::{self, Cfg, CfgBuilder, Instr};
+use crate::dataflow::{self, Analysis, ReachingDefinitions};
+use crate::hir::{Hir, Value, ValueKind};
+use crate::resolver::DefId;
+
+#[derive(Debug)]
+pub enum InitError {
+    // `declared` is the declaration without a value
+    Uninitialized {
+        name: Symbol,
+        span: Span,
+        declared: Span,
+    },
+    // an immutable variable assigned after it already got its value
+    AssignedTwice {
+        name: Symbol,
+        span: Span,
+        first: Span,
+    },
+}
+
+impl From<InitError> for Diagnostic {
+    fn from(error: InitError) -> Self {
+        match error {
+            InitError::Uninitialized {
+                name,
+                span,
+                declared,
+            } => Diagnostic::error("E0305", format!("`{}` is used before being assigned", name))
+                .with_label(span, format!("`{}` is possibly uninitialized here", name))
+                .with_secondary(declared, "declared here without a value")
+                .with_help("assign it a value on every path that leads here"),
+
+            InitError::AssignedTwice { name, span, first } => Diagnostic::error(
+                "E0301",
+                format!("cannot assign twice to immutable `{}`", name),
+            )
+            .with_label(span, "cannot assign twice")
+            .with_secondary(first, "first assignment")
+            .with_help(format!(
+                "consider making `{}` mutable: `mut {}`",
+                name, name
+            )),
+        }
+    }
+}
+
+// checks that variables declared without a value are assigned on every path
+// before they're read, and that immutable ones are assigned only once. This runs
+// on the graphs of the program and of every function, with reaching definitions:
+// a declaration still reaching a read means a path skipped every assignment.
+pub struct InitChecker<'a> {
+    hir: &'a Hir,
+    errors: Vec<InitError>,
+}
+
+impl<'a> InitChecker<'a> {
+    pub fn new(hir: &'a Hir) -> Self {
+        Self {
+            hir,
+            errors: vec![],
+        }
+    }
+
+    // where a value reads the local, for pointing at it
+    fn read_at(value: &Value, local: DefId) -> Option<Span> {
+        let mut locals = HashSet::new();
+        cfg::uses(value, &mut locals);
+        if !locals.contains(&local) {
+            return None;
+        }
+
+        let span = match &value.kind {
+            ValueKind::Local(_) => return Some(value.span),
+            ValueKind::Binary { left, right, .. } => {
+                Self::read_at(left, local).or_else(|| Self::read_at(right, local))
+            }
+            ValueKind::Unary { operand, .. } | ValueKind::Cast { value: operand } => {
+                Self::read_at(operand, local)
+            }
+            ValueKind::Call { callee, args } => Self::read_at(callee, local)
+                .or_else(|| args.iter().find_map(|arg| Self::read_at(arg, local))),
+            ValueKind::Builtin { args, .. } | ValueKind::List(args) => {
+                args.iter().find_map(|arg| Self::read_at(arg, local))
+            }
+            ValueKind::Map(entries) => entries.iter().find_map(|(key, value)| {
+                Self::read_at(key, local).or_else(|| Self::read_at(value, local))
+            }),
+            _ => None,
+        };
+
+        // reads inside nested functions point at the whole function
+        Some(span.unwrap_or(value.span))
+    }
+
+    fn check_cfg(&mut self, cfg: &Cfg) {
+        // the declarations without a value
+        let declared: HashMap<DefId, Span> = cfg
+            .blocks
+            .iter()
+            .flat_map(|block| &block.instrs)
+            .filter_map(|instr| match instr {
+                Instr::Declare { local, span } => Some((*local, *span)),
+                _ => None,
+            })
+            .collect();
+
+        if declared.is_empty() {
+            return;
+        }
+
+        let reaching = dataflow::solve(&ReachingDefinitions, cfg);
+        let reachable = cfg.reachable();
+        let mut reported = HashSet::new();
+
+        for (index, block) in cfg.blocks.iter().enumerate() {
+            // unreachable code has its own lint
+            if !reachable[index] {
+                continue;
+            }
+
+            let mut fact = reaching.entry[index].clone();
+            for instr in &block.instrs {
+                let value = match instr {
+                    Instr::Assign { value, .. } | Instr::Eval(value) => Some(*value),
+                    Instr::Bind { .. } | Instr::Declare { .. } => None,
+                };
+
+                for local in instr.uses() {
+                    let Some(declaration) = declared.get(&local) else {
+                        continue;
+                    };
+
+                    if fact.contains(&(local, *declaration)) && reported.insert(local) {
+                        self.errors.push(InitError::Uninitialized {
+                            name: self.hir.local(local).name,
+                            span: value
+                                .and_then(|value| Self::read_at(value, local))
+                                .unwrap_or(*declaration),
+                            declared: *declaration,
+                        });
+                    }
+                }
+
+                if let Instr::Assign { local, span, .. } = instr {
+                    if let (Some(declaration), false) =
+                        (declared.get(local), self.hir.local(*local).mutable)
+                    {
+                        let first = fact
+                            .iter()
+                            .filter(|(defined, site)| defined == local && site != declaration)
+                            .map(|(_, site)| *site)
+                            .min_by_key(|site| site.start);
+
+                        if let Some(first) = first {
+                            self.errors.push(InitError::AssignedTwice {
+                                name: self.hir.local(*local).name,
+                                span: *span,
+                                first,
+                            });
+                        }
+                    }
+                }
+
+                ReachingDefinitions.transfer(instr, &mut fact);
+            }
+        }
+    }
+
+    pub fn check(mut self) -> Result<(), Vec<InitError>> {
+        let program = CfgBuilder::build(&[], &self.hir.body);
+        self.check_cfg(&program);
+
+        for test in &self.hir.tests {
+            let test = CfgBuilder::build(&[], &test.body);
+            self.check_cfg(&test);
+        }
+
+        for function in cfg::functions(self.hir) {
+            self.check_cfg(&function.cfg);
+        }
+
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            self.errors.sort_by_key(|error| match error {
+                InitError::Uninitialized { span, .. } | InitError::AssignedTwice { span, .. } => {
+                    span.start
+                }
+            });
+            Err(self.errors)
+        }
+    }
+}
@@ -0,0 +1,383 @@
+use std::fmt;
+
+use ez_ast::{BaseType, Identifier, NodeId, Variant};
+use ez_lexer::{Span, Symbol};
+
+// what `json_parse` gives back, the enum `Json` of the language. Numbers are
+// always floats, like in JavaScript, and objects keep the order of their keys
+#[derive(Debug, Clone)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    List(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    pub const NAME: &'static str = "Json";
+    pub const VARIANTS: [&'static str; 6] = ["Null", "Bool", "Number", "String", "List", "Object"];
+
+    // the declaration programs would write for it
+    pub fn variants() -> Vec<Variant> {
+        let json = || BaseType::Named(Json::NAME.into());
+        let payloads = [
+            vec![],
+            vec![BaseType::Bool],
+            vec![BaseType::Float],
+            vec![BaseType::String],
+            vec![BaseType::List(Box::new(json()))],
+            vec![BaseType::Map(Box::new(BaseType::String), Box::new(json()))],
+        ];
+
+        Json::VARIANTS
+            .iter()
+            .zip(payloads)
+            .map(|(name, payload)| Variant {
+                identifier: Identifier {
+                    name: Symbol::intern(name),
+                    span: Span::default(),
+                    id: NodeId::default(),
+                },
+                payload,
+            })
+            .collect()
+    }
+
+    // the position of its variant in `VARIANTS`
+    pub fn index(&self) -> usize {
+        match self {
+            Json::Null => 0,
+            Json::Bool(_) => 1,
+            Json::Number(_) => 2,
+            Json::String(_) => 3,
+            Json::List(_) => 4,
+            Json::Object(_) => 5,
+        }
+    }
+
+    // the error says what was wrong and where, counted in characters
+    pub fn parse(text: &str) -> Result<Json, String> {
+        let mut parser = JsonParser {
+            chars: text.chars().collect(),
+            position: 0,
+        };
+
+        let json = parser.value()?;
+        parser.skip_whitespace();
+        if parser.position < parser.chars.len() {
+            return Err(parser.error("the end of the text"));
+        }
+        Ok(json)
+    }
+}
+
+impl Json {
+    // the value of a key of an object
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(entries) => entries
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_number(&self) -> Option<f64> {
+        match self {
+            Json::Number(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Json::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+}
+
+// without whitespace, numbers without a fraction are written as integers
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(value) => write!(f, "{}", value),
+            Json::Number(value) if value.fract() == 0.0 && value.abs() < 1e15 => {
+                write!(f, "{}", *value as i64)
+            }
+            Json::Number(value) => write!(f, "{}", value),
+            Json::String(value) => {
+                let mut out = String::new();
+                write_string(value, &mut out);
+                write!(f, "{}", out)
+            }
+            Json::List(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            }
+            Json::Object(entries) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    let mut out = String::new();
+                    write_string(key, &mut out);
+                    write!(f, "{}:{}", out, value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+// quotes the string, escaping what JSON needs escaped
+pub fn write_string(value: &str, out: &mut String) {
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            '\u{8}' => out.push_str("\\b"),
+            '\u{c}' => out.push_str("\\f"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+struct JsonParser {
+    chars: Vec<char>,
+    position: usize,
+}
+
+impl JsonParser {
+    fn error(&self, expected: &str) -> String {
+        match self.chars.get(self.position) {
+            Some(found) => format!(
+                "expected {}, found `{}` at character {}",
+                expected,
+                found,
+                self.position + 1
+            ),
+            None => format!("expected {}, found the end of the text", expected),
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t' | '\n' | '\r')) {
+            self.position += 1;
+        }
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_whitespace();
+        if self.peek() == Some(c) {
+            self.position += 1;
+            return true;
+        }
+        false
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), String> {
+        if self.eat(c) {
+            return Ok(());
+        }
+        Err(self.error(&format!("`{}`", c)))
+    }
+
+    fn keyword(&mut self, keyword: &str, json: Json) -> Result<Json, String> {
+        for expected in keyword.chars() {
+            if self.peek() != Some(expected) {
+                return Err(self.error(&format!("`{}`", keyword)));
+            }
+            self.position += 1;
+        }
+        Ok(json)
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('n') => self.keyword("null", Json::Null),
+            Some('t') => self.keyword("true", Json::Bool(true)),
+            Some('f') => self.keyword("false", Json::Bool(false)),
+            Some('"') => Ok(Json::String(self.string()?)),
+            Some('-' | '0'..='9') => self.number(),
+            Some('[') => {
+                self.position += 1;
+                let mut items = vec![];
+                if !self.eat(']') {
+                    loop {
+                        items.push(self.value()?);
+                        if self.eat(']') {
+                            break;
+                        }
+                        if !self.eat(',') {
+                            return Err(self.error("`,` or `]`"));
+                        }
+                    }
+                }
+                Ok(Json::List(items))
+            }
+            Some('{') => {
+                self.position += 1;
+                let mut entries = vec![];
+                if !self.eat('}') {
+                    loop {
+                        self.skip_whitespace();
+                        if self.peek() != Some('"') {
+                            return Err(self.error("a key"));
+                        }
+                        let key = self.string()?;
+                        self.expect(':')?;
+                        entries.push((key, self.value()?));
+                        if self.eat('}') {
+                            break;
+                        }
+                        if !self.eat(',') {
+                            return Err(self.error("`,` or `}`"));
+                        }
+                    }
+                }
+                Ok(Json::Object(entries))
+            }
+            _ => Err(self.error("a value")),
+        }
+    }
+
+    fn digits(&mut self) -> usize {
+        let start = self.position;
+        while matches!(self.peek(), Some('0'..='9')) {
+            self.position += 1;
+        }
+        self.position - start
+    }
+
+    // no leading zeros, and digits on both sides of the `.`
+    fn number(&mut self) -> Result<Json, String> {
+        let start = self.position;
+        self.eat('-');
+        if self.peek() == Some('0') {
+            self.position += 1;
+        } else if self.digits() == 0 {
+            return Err(self.error("a digit"));
+        }
+
+        if self.peek() == Some('.') {
+            self.position += 1;
+            if self.digits() == 0 {
+                return Err(self.error("a digit"));
+            }
+        }
+
+        if matches!(self.peek(), Some('e' | 'E')) {
+            self.position += 1;
+            if matches!(self.peek(), Some('+' | '-')) {
+                self.position += 1;
+            }
+            if self.digits() == 0 {
+                return Err(self.error("a digit"));
+            }
+        }
+
+        let text: String = self.chars[start..self.position].iter().collect();
+        Ok(Json::Number(text.parse().expect("the number was checked")))
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.position += 1;
+        let mut value = String::new();
+        loop {
+            let Some(c) = self.peek() else {
+                return Err(self.error("`\"`"));
+            };
+            self.position += 1;
+
+            match c {
+                '"' => return Ok(value),
+                '\\' => {
+                    let escaped = match self.peek() {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('/') => '/',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('u') => {
+                            self.position += 1;
+                            value.push(self.unicode_escape()?);
+                            continue;
+                        }
+                        _ => return Err(self.error("an escape")),
+                    };
+                    self.position += 1;
+                    value.push(escaped);
+                }
+                c if (c as u32) < 0x20 => {
+                    self.position -= 1;
+                    return Err(self.error("`\"`"));
+                }
+                c => value.push(c),
+            }
+        }
+    }
+
+    fn hex(&mut self) -> Result<u32, String> {
+        let mut code = 0;
+        for _ in 0..4 {
+            let Some(digit) = self.peek().and_then(|c| c.to_digit(16)) else {
+                return Err(self.error("a hexadecimal digit"));
+            };
+            self.position += 1;
+            code = code * 16 + digit;
+        }
+        Ok(code)
+    }
+
+    // characters outside of the basic plane are written as two escapes, a
+    // surrogate without its other half becomes U+FFFD
+    fn unicode_escape(&mut self) -> Result<char, String> {
+        let code = self.hex()?;
+        if (0xd800..0xdc00).contains(&code) && self.chars[self.position..].starts_with(&['\\', 'u'])
+        {
+            let before = self.position;
+            self.position += 2;
+            let low = self.hex()?;
+            if (0xdc00..0xe000).contains(&low) {
+                let combined = 0x10000 + ((code - 0xd800) << 10) + (low - 0xdc00);
+                return Ok(char::from_u32(combined).expect("the surrogates were checked"));
+            }
+            self.position = before;
+        }
+
+        Ok(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER))
+    }
+}
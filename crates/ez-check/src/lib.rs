@@ -0,0 +1,137 @@
+// the checks of the compiler, from resolving names to lowering a program that
+// passed them into the HIR the backends compile, and building its SSA form,
+// optimizing it and finding what it allocates that doesn't escape
+
+pub mod cfg;
+pub mod dataflow;
+pub mod escape;
+mod exhaustiveness;
+pub mod hir;
+mod init;
+pub mod json;
+pub mod lint;
+pub mod log;
+mod lower;
+mod mutability;
+pub mod optimize;
+pub mod profile;
+pub mod resolver;
+mod returns;
+pub mod rules;
+pub mod semantic;
+pub mod ssa;
+pub mod template;
+pub mod typecheck;
+
+use ez_ast::{BaseType, Program};
+use ez_diagnostics::DiagnosticSink;
+
+use crate::exhaustiveness::MatchChecker;
+use crate::hir::Hir;
+use crate::init::InitChecker;
+use crate::lint::{UnreachableLint, UnusedAssignmentLint, UnusedLint};
+use crate::lower::Lowerer;
+use crate::mutability::MutabilityChecker;
+use crate::profile::Passes;
+use crate::resolver::{Resolutions, Resolver};
+use crate::returns::ReturnChecker;
+use crate::typecheck::{TypeChecker, Types};
+
+// runs every check on the program, the results of resolution and type checking
+// are returned when they succeeded, for lowering. `hosts` are the names and
+// types of the functions an embedding program gave it
+pub fn check(
+    program: &Program,
+    hosts: &[(String, BaseType)],
+    sink: &mut DiagnosticSink,
+) -> Option<(Resolutions, Types)> {
+    check_timed(program, hosts, sink, &mut Passes::new())
+}
+
+// `check`, with the time and memory of each of its passes recorded
+pub fn check_timed(
+    program: &Program,
+    hosts: &[(String, BaseType)],
+    sink: &mut DiagnosticSink,
+    passes: &mut Passes,
+) -> Option<(Resolutions, Types)> {
+    let names = hosts.iter().map(|(name, _)| name.clone()).collect();
+    let resolved = passes.time("resolve", || {
+        Resolver::new().with_hosts(names).resolve(program)
+    });
+    let resolutions = match resolved {
+        Ok(resolutions) => resolutions,
+        Err(errors) => {
+            sink.extend(errors);
+            return None;
+        }
+    };
+
+    // matches can only be checked once the types of their values are known
+    let typed = passes.time("typecheck", || {
+        TypeChecker::new(&resolutions)
+            .with_hosts(hosts)
+            .check(program)
+    });
+    let types = match typed {
+        Ok(types) => {
+            if let Err(errors) = passes.time("matches", || MatchChecker::new(&types).check(program))
+            {
+                sink.extend(errors);
+            }
+
+            Some(types)
+        }
+        Err(errors) => {
+            sink.extend(errors);
+            None
+        }
+    };
+
+    if let Err(errors) = passes.time("mutability", || {
+        MutabilityChecker::new(&resolutions).check(program)
+    }) {
+        sink.extend(errors);
+    }
+
+    if let Err(errors) = passes.time("returns", || ReturnChecker::new().check(program)) {
+        sink.extend(errors);
+    }
+
+    passes.time("lints", || {
+        sink.extend(UnusedLint::new(&resolutions).check(program));
+        sink.extend(UnreachableLint::new().check(program));
+    });
+
+    types.map(|types| (resolutions, types))
+}
+
+// lowers a program that passed `check`, and runs the checks that need the HIR
+pub fn lower(
+    program: &Program,
+    resolutions: &Resolutions,
+    types: &Types,
+    sink: &mut DiagnosticSink,
+) -> Hir {
+    lower_timed(program, resolutions, types, sink, &mut Passes::new())
+}
+
+// `lower`, with the time and memory of each of its passes recorded
+pub fn lower_timed(
+    program: &Program,
+    resolutions: &Resolutions,
+    types: &Types,
+    sink: &mut DiagnosticSink,
+    passes: &mut Passes,
+) -> Hir {
+    let hir = passes.time("lower", || Lowerer::new(resolutions, types).lower(program));
+
+    if let Err(errors) = passes.time("initialization", || InitChecker::new(&hir).check()) {
+        sink.extend(errors);
+    }
+
+    passes.time("hir lints", || {
+        sink.extend(UnusedAssignmentLint::new(&hir).check())
+    });
+    hir
+}
@@ -0,0 +1,546 @@
+use std::collections::HashSet;
+
+use ez_ast::{Expr, NodeId, Program, ValueExpr};
+use ez_diagnostics::Diagnostic;
+use ez_lexer::{Span, Symbol};
+
+use crate::cfg::{self, Instr};
+use crate::dataflow::{self, Analysis, Liveness};
+use crate::hir::{Hir, Value, ValueKind};
+use crate::resolver::{DefId, DefKind, Resolutions};
+use crate::returns::ReturnChecker;
+
+#[derive(Debug)]
+pub enum LintWarning {
+    UnusedVariable { name: Symbol, span: Span },
+    UnusedParameter { name: Symbol, span: Span },
+    UnreachableCode { span: Span },
+    UnusedAssignment { name: Symbol, span: Span },
+}
+
+// the names lints are allowed or denied by, `warnings` stands for all of them.
+// The last ones are the rules only `ez lint` checks
+pub const LINTS: [&str; 9] = [
+    "unused_variables",
+    "unused_parameters",
+    "unreachable_code",
+    "unreachable_patterns",
+    "unused_assignments",
+    "naming_conventions",
+    "shadowing",
+    "magic_numbers",
+    "long_functions",
+];
+
+impl LintWarning {
+    pub fn name(&self) -> &'static str {
+        match self {
+            LintWarning::UnusedVariable { .. } => "unused_variables",
+            LintWarning::UnusedParameter { .. } => "unused_parameters",
+            LintWarning::UnreachableCode { .. } => "unreachable_code",
+            LintWarning::UnusedAssignment { .. } => "unused_assignments",
+        }
+    }
+
+    pub fn help(&self) -> Option<String> {
+        match self {
+            LintWarning::UnusedVariable { name, .. }
+            | LintWarning::UnusedParameter { name, .. } => Some(format!(
+                "if this is intentional, prefix it with an underscore: `_{}`",
+                name
+            )),
+            LintWarning::UnreachableCode { .. } => None,
+            LintWarning::UnusedAssignment { .. } => {
+                Some("maybe it is overwritten before being read?".into())
+            }
+        }
+    }
+}
+
+impl From<LintWarning> for Diagnostic {
+    fn from(warning: LintWarning) -> Self {
+        let help = warning.help();
+        let suggestion = match &warning {
+            LintWarning::UnusedVariable { name, span }
+            | LintWarning::UnusedParameter { name, span } => Some((*span, format!("_{}", name))),
+            LintWarning::UnreachableCode { .. } | LintWarning::UnusedAssignment { .. } => None,
+        };
+        let name = warning.name();
+        let diagnostic = match warning {
+            LintWarning::UnusedVariable { name, span } => {
+                Diagnostic::warning("W0001", format!("unused variable `{}`", name))
+                    .with_label(span, "never read")
+            }
+
+            LintWarning::UnusedParameter { name, span } => {
+                Diagnostic::warning("W0002", format!("unused parameter `{}`", name))
+                    .with_label(span, "never read")
+            }
+
+            LintWarning::UnreachableCode { span } => {
+                Diagnostic::warning("W0003", "unreachable code")
+                    .with_label(span, "this is never executed")
+            }
+
+            LintWarning::UnusedAssignment { name, span } => Diagnostic::warning(
+                "W0005",
+                format!("value assigned to `{}` is never read", name),
+            )
+            .with_label(span, "this value is never read"),
+        };
+
+        let diagnostic = diagnostic.for_lint(name);
+        match (help, suggestion) {
+            (Some(help), Some((span, replacement))) => {
+                diagnostic.with_suggestion(help, span, replacement)
+            }
+            (Some(help), None) => diagnostic.with_help(help),
+            (None, _) => diagnostic,
+        }
+    }
+}
+
+// warns about bindings and parameters that are never read, names starting with
+// `_` are skipped, and so are top level declarations since they can be used from
+// outside
+pub struct UnusedLint<'a> {
+    resolutions: &'a Resolutions,
+    // plain assignments only write to a binding, they don't count as reading it
+    writes: HashSet<NodeId>,
+}
+
+impl<'a> UnusedLint<'a> {
+    pub fn new(resolutions: &'a Resolutions) -> Self {
+        Self {
+            resolutions,
+            writes: HashSet::new(),
+        }
+    }
+
+    fn collect_writes_value(&mut self, value: &ValueExpr) {
+        match value {
+            ValueExpr::Binary { left, right, .. } => {
+                self.collect_writes_value(left);
+                self.collect_writes_value(right);
+            }
+
+            ValueExpr::Unary { operand, .. } => self.collect_writes_value(operand),
+
+            ValueExpr::Cast { value, .. } => self.collect_writes_value(value),
+
+            ValueExpr::Field { value, .. } => self.collect_writes_value(value),
+
+            ValueExpr::Call { callee, args, .. } => {
+                self.collect_writes_value(callee);
+                for arg in args.iter() {
+                    self.collect_writes_value(arg);
+                }
+            }
+
+            ValueExpr::List { items, .. } => {
+                for item in items.iter() {
+                    self.collect_writes_value(item);
+                }
+            }
+
+            ValueExpr::Map { entries, .. } => {
+                for (key, value) in entries.iter() {
+                    self.collect_writes_value(key);
+                    self.collect_writes_value(value);
+                }
+            }
+
+            ValueExpr::Function { body, .. } => {
+                for expr in body.iter() {
+                    self.collect_writes(expr);
+                }
+            }
+
+            _ => {}
+        }
+    }
+
+    fn collect_writes(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Declaration { value, .. } => {
+                if let Some(value) = value {
+                    self.collect_writes_value(value);
+                }
+            }
+
+            Expr::Assign {
+                target,
+                operator,
+                value,
+            } => {
+                if let (ValueExpr::Identifier(identifier), None) = (target, operator) {
+                    self.writes.insert(identifier.id);
+                }
+
+                self.collect_writes_value(value);
+            }
+
+            Expr::Block { body, .. } | Expr::Test { body, .. } => {
+                for expr in body.iter() {
+                    self.collect_writes(expr);
+                }
+            }
+
+            Expr::If {
+                condition,
+                body,
+                else_body,
+                ..
+            } => {
+                self.collect_writes_value(condition);
+                for expr in body.iter().chain(else_body.unwrap_or_default()) {
+                    self.collect_writes(expr);
+                }
+            }
+
+            Expr::While {
+                condition, body, ..
+            } => {
+                self.collect_writes_value(condition);
+                for expr in body.iter() {
+                    self.collect_writes(expr);
+                }
+            }
+
+            Expr::For {
+                start, end, body, ..
+            } => {
+                self.collect_writes_value(start);
+                if let Some(end) = end {
+                    self.collect_writes_value(end);
+                }
+                for expr in body.iter() {
+                    self.collect_writes(expr);
+                }
+            }
+
+            Expr::Match { value, arms, .. } => {
+                self.collect_writes_value(value);
+                for expr in arms.iter().flat_map(|arm| arm.body) {
+                    self.collect_writes(expr);
+                }
+            }
+
+            Expr::Try { body, handler, .. } => {
+                for expr in body.iter().chain(*handler) {
+                    self.collect_writes(expr);
+                }
+            }
+
+            Expr::Break(_) | Expr::Continue(_) | Expr::Enum { .. } => {}
+
+            Expr::Value(value) => self.collect_writes_value(value),
+
+            Expr::Return { value, .. } => {
+                if let Some(value) = value {
+                    self.collect_writes_value(value);
+                }
+            }
+        }
+    }
+
+    pub fn check(mut self, program: &Program) -> Vec<LintWarning> {
+        for expr in program.statements() {
+            self.collect_writes(expr);
+        }
+
+        let read: HashSet<DefId> = self
+            .resolutions
+            .uses
+            .iter()
+            .filter(|(node, _)| !self.writes.contains(node))
+            .map(|(_, id)| *id)
+            .collect();
+
+        let top_level: HashSet<DefId> = program
+            .statements()
+            .filter_map(|expr| match expr {
+                Expr::Declaration { identifier, .. } | Expr::Enum { identifier, .. } => {
+                    self.resolutions.defined_at(identifier)
+                }
+                _ => None,
+            })
+            .collect();
+
+        let mut warnings = vec![];
+        for (index, definition) in self.resolutions.definitions.iter().enumerate() {
+            if read.contains(&DefId(index))
+                || definition.name.starts_with('_')
+                || top_level.contains(&DefId(index))
+            {
+                continue;
+            }
+
+            let name = definition.name;
+            let span = definition.span;
+            warnings.push(match definition.kind {
+                DefKind::Param => LintWarning::UnusedParameter { name, span },
+                DefKind::Variable | DefKind::Function => LintWarning::UnusedVariable { name, span },
+                DefKind::Enum
+                | DefKind::Builtin(_)
+                | DefKind::Module(_)
+                | DefKind::Host
+                | DefKind::Import(_) => continue,
+            });
+        }
+
+        warnings
+    }
+}
+
+// warns about statements that come after one that never falls through, like a
+// `return`, `break` or an `if` where both branches return
+#[derive(Default)]
+pub struct UnreachableLint {
+    warnings: Vec<LintWarning>,
+}
+
+impl UnreachableLint {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn check_body(&mut self, body: &[Expr]) {
+        for (index, expr) in body.iter().enumerate() {
+            self.check_expr(expr);
+
+            if ReturnChecker::diverges(expr) {
+                if let (Some(first), Some(last)) = (body.get(index + 1), body.last()) {
+                    self.warnings.push(LintWarning::UnreachableCode {
+                        span: first.span().to(last.span()),
+                    });
+                }
+
+                // there could still be nested functions in the unreachable part
+                for expr in &body[index + 1..] {
+                    self.check_expr(expr);
+                }
+
+                return;
+            }
+        }
+    }
+
+    fn check_value(&mut self, value: &ValueExpr) {
+        match value {
+            ValueExpr::Binary { left, right, .. } => {
+                self.check_value(left);
+                self.check_value(right);
+            }
+
+            ValueExpr::Unary { operand, .. } => self.check_value(operand),
+
+            ValueExpr::Cast { value, .. } => self.check_value(value),
+
+            ValueExpr::Field { value, .. } => self.check_value(value),
+
+            ValueExpr::Call { callee, args, .. } => {
+                self.check_value(callee);
+                for arg in args.iter() {
+                    self.check_value(arg);
+                }
+            }
+
+            ValueExpr::List { items, .. } => {
+                for item in items.iter() {
+                    self.check_value(item);
+                }
+            }
+
+            ValueExpr::Map { entries, .. } => {
+                for (key, value) in entries.iter() {
+                    self.check_value(key);
+                    self.check_value(value);
+                }
+            }
+
+            ValueExpr::Function { body, .. } => self.check_body(body),
+
+            _ => {}
+        }
+    }
+
+    fn check_expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Declaration { value, .. } => {
+                if let Some(value) = value {
+                    self.check_value(value);
+                }
+            }
+
+            Expr::Assign { target, value, .. } => {
+                self.check_value(target);
+                self.check_value(value);
+            }
+
+            Expr::Block { body, .. } | Expr::Test { body, .. } => self.check_body(body),
+
+            Expr::If {
+                condition,
+                body,
+                else_body,
+                ..
+            } => {
+                self.check_value(condition);
+                self.check_body(body);
+                if let Some(else_body) = else_body {
+                    self.check_body(else_body);
+                }
+            }
+
+            Expr::While {
+                condition, body, ..
+            } => {
+                self.check_value(condition);
+                self.check_body(body);
+            }
+
+            Expr::For {
+                start, end, body, ..
+            } => {
+                self.check_value(start);
+                if let Some(end) = end {
+                    self.check_value(end);
+                }
+                self.check_body(body);
+            }
+
+            Expr::Match { value, arms, .. } => {
+                self.check_value(value);
+                for arm in arms.iter() {
+                    self.check_body(arm.body);
+                }
+            }
+
+            Expr::Try { body, handler, .. } => {
+                self.check_body(body);
+                self.check_body(handler);
+            }
+
+            Expr::Break(_) | Expr::Continue(_) | Expr::Enum { .. } => {}
+
+            Expr::Value(value) => self.check_value(value),
+
+            Expr::Return { value, .. } => {
+                if let Some(value) = value {
+                    self.check_value(value);
+                }
+            }
+        }
+    }
+
+    pub fn check(mut self, program: &Program) -> Vec<LintWarning> {
+        for file in program.files() {
+            self.check_body(file.body);
+        }
+
+        self.warnings
+    }
+}
+
+// warns about values written to a local that get overwritten, or that the
+// function returns without reading, this works on the HIR since it needs the
+// control-flow graph of every function
+pub struct UnusedAssignmentLint<'a> {
+    hir: &'a Hir,
+}
+
+impl<'a> UnusedAssignmentLint<'a> {
+    pub fn new(hir: &'a Hir) -> Self {
+        Self { hir }
+    }
+
+    // the locals that nested functions refer to, these can be read whenever the
+    // function is called, which liveness doesn't know about
+    fn captured(value: &Value, locals: &mut HashSet<DefId>) {
+        match &value.kind {
+            ValueKind::Function { .. } => cfg::uses(value, locals),
+            ValueKind::Binary { left, right, .. } => {
+                Self::captured(left, locals);
+                Self::captured(right, locals);
+            }
+            ValueKind::Unary { operand, .. } | ValueKind::Cast { value: operand } => {
+                Self::captured(operand, locals)
+            }
+            ValueKind::Call { callee, args } => {
+                Self::captured(callee, locals);
+                for arg in args {
+                    Self::captured(arg, locals);
+                }
+            }
+            ValueKind::Builtin { args, .. } | ValueKind::List(args) => {
+                for arg in args {
+                    Self::captured(arg, locals);
+                }
+            }
+            ValueKind::Map(entries) => {
+                for (key, value) in entries {
+                    Self::captured(key, locals);
+                    Self::captured(value, locals);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    pub fn check(self) -> Vec<LintWarning> {
+        let mut warnings = vec![];
+
+        for function in cfg::functions(self.hir) {
+            let cfg = &function.cfg;
+            let instrs = cfg.blocks.iter().flat_map(|block| &block.instrs);
+
+            // locals that are never read at all are already reported as unused
+            let mut read = HashSet::new();
+            let mut captured = HashSet::new();
+            for instr in instrs {
+                read.extend(instr.uses());
+                if let Instr::Assign { value, .. } | Instr::Eval(value) = instr {
+                    Self::captured(value, &mut captured);
+                }
+            }
+
+            let liveness = dataflow::solve(&Liveness, cfg);
+            let reachable = cfg.reachable();
+
+            for (index, block) in cfg.blocks.iter().enumerate() {
+                // unreachable code has its own lint
+                if !reachable[index] {
+                    continue;
+                }
+
+                let mut live = liveness.exit[index].clone();
+                for instr in block.instrs.iter().rev() {
+                    if let Instr::Assign { local, value, span } = instr {
+                        let name = &self.hir.local(*local).name;
+                        let ignored = name.starts_with('_')
+                            || !cfg.locals.contains(local)
+                            || !read.contains(local)
+                            || captured.contains(local)
+                            || matches!(value.kind, ValueKind::Function { .. });
+
+                        if !ignored && !live.contains(local) {
+                            warnings.push(LintWarning::UnusedAssignment {
+                                name: *name,
+                                span: *span,
+                            });
+                        }
+                    }
+
+                    Liveness.transfer(instr, &mut live);
+                }
+            }
+        }
+
+        warnings.sort_by_key(|warning| match warning {
+            LintWarning::UnusedAssignment { span, .. } => span.start,
+            _ => 0,
+        });
+        warnings
+    }
+}
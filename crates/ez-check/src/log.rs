@@ -0,0 +1,58 @@
+use crate::resolver::Builtin;
+
+// how much `log_debug`, `log_info`, `log_warn` and `log_error` print, messages
+// below the level are dropped. It's `info` unless `EZ_LOG` or `--log-level`
+// says otherwise, and `off` drops everything
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default)]
+pub enum LogLevel {
+    Debug,
+    #[default]
+    Info,
+    Warn,
+    Error,
+    Off,
+}
+
+impl LogLevel {
+    pub const VARIABLE: &'static str = "EZ_LOG";
+    pub const ALL: [LogLevel; 5] = [
+        LogLevel::Debug,
+        LogLevel::Info,
+        LogLevel::Warn,
+        LogLevel::Error,
+        LogLevel::Off,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            LogLevel::Debug => "debug",
+            LogLevel::Info => "info",
+            LogLevel::Warn => "warn",
+            LogLevel::Error => "error",
+            LogLevel::Off => "off",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<LogLevel> {
+        let name = name.to_ascii_lowercase();
+        LogLevel::ALL.into_iter().find(|level| level.name() == name)
+    }
+
+    // a value of `EZ_LOG` that isn't a level is ignored
+    pub fn from_env() -> Option<LogLevel> {
+        std::env::var(LogLevel::VARIABLE)
+            .ok()
+            .and_then(|name| LogLevel::parse(&name))
+    }
+
+    // the level a logging builtin writes at
+    pub fn of(builtin: Builtin) -> Option<LogLevel> {
+        match builtin {
+            Builtin::LogDebug => Some(LogLevel::Debug),
+            Builtin::LogInfo => Some(LogLevel::Info),
+            Builtin::LogWarn => Some(LogLevel::Warn),
+            Builtin::LogError => Some(LogLevel::Error),
+            _ => None,
+        }
+    }
+}
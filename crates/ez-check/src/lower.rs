@@ -0,0 +1,557 @@
+use ez_ast::{BaseType, Expr, Identifier, Pattern, Program, ValueExpr};
+use ez_lexer::{Span, Symbol, TokenKind};
+
+use crate::hir::{self, Hir, Local, MatchArm, Stmt, Value, ValueKind};
+use crate::resolver::{Builtin, DefId, DefKind, Resolutions};
+use crate::typecheck::Types;
+
+// lowers the AST to HIR, this only runs on programs without errors, so every
+// name is resolved and every value has a type
+pub struct Lowerer<'a> {
+    resolutions: &'a Resolutions,
+    types: &'a Types,
+    locals: Vec<Local>,
+    tests: Vec<hir::Test>,
+    benches: Vec<hir::Bench>,
+}
+
+impl<'a> Lowerer<'a> {
+    pub fn new(resolutions: &'a Resolutions, types: &'a Types) -> Self {
+        let locals = resolutions
+            .definitions
+            .iter()
+            .enumerate()
+            .map(|(index, definition)| Local {
+                name: definition.name,
+                basetype: types
+                    .definitions
+                    .get(&DefId(index))
+                    .cloned()
+                    .unwrap_or(BaseType::Void),
+                mutable: definition.mutable,
+            })
+            .collect();
+
+        Self {
+            resolutions,
+            types,
+            locals,
+            tests: vec![],
+            benches: vec![],
+        }
+    }
+
+    // a local that doesn't exist in the source, used by desugarings
+    fn fresh(&mut self, name: &str, basetype: BaseType, mutable: bool) -> DefId {
+        self.locals.push(Local {
+            name: Symbol::intern(name),
+            basetype,
+            mutable,
+        });
+
+        DefId(self.locals.len() - 1)
+    }
+
+    fn used(&self, identifier: &Identifier) -> DefId {
+        self.resolutions
+            .resolve(identifier)
+            .expect("names are resolved before lowering")
+    }
+
+    fn declared(&self, identifier: &Identifier) -> DefId {
+        self.resolutions
+            .defined_at(identifier)
+            .expect("declarations are resolved before lowering")
+    }
+
+    fn basetype(&self, value: &ValueExpr) -> BaseType {
+        self.types
+            .of(value)
+            .cloned()
+            .expect("values are typed before lowering")
+    }
+
+    fn local(id: DefId, basetype: BaseType, span: Span) -> Value {
+        Value {
+            kind: ValueKind::Local(id),
+            basetype,
+            span,
+        }
+    }
+
+    fn variant_index(&self, enum_name: Symbol, variant: &Identifier) -> usize {
+        self.types.enums[&enum_name]
+            .iter()
+            .position(|candidate| candidate.identifier.name == variant.name)
+            .expect("variants are checked before lowering")
+    }
+
+    // the builtin a callee names, either directly, as a field of its module or
+    // as a method, which also gives back the value it's called on
+    fn builtin<'v>(
+        &self,
+        callee: &'v ValueExpr<'v>,
+    ) -> Option<(Builtin, Option<&'v ValueExpr<'v>>)> {
+        let (identifier, member) = match callee {
+            ValueExpr::Identifier(identifier) => (identifier, None),
+            // only values have a type, enums and modules don't
+            ValueExpr::Field { value, field, .. } => match (self.types.of(value), &**value) {
+                (Some(basetype), _) => {
+                    let method = Builtin::method(basetype, &field.name)?;
+                    return Some((method, Some(value)));
+                }
+                (None, ValueExpr::Identifier(identifier)) => (identifier, Some(field)),
+                _ => return None,
+            },
+            _ => return None,
+        };
+
+        let id = self.resolutions.resolve(identifier)?;
+        let builtin = match (self.resolutions.definition(id).kind, member) {
+            (DefKind::Builtin(builtin), None) => builtin,
+            (DefKind::Module(module), Some(member)) => module.function(&member.name)?,
+            _ => return None,
+        };
+
+        Some((builtin, None))
+    }
+
+    fn lower_value(&mut self, value: &ValueExpr) -> Value {
+        let basetype = self.basetype(value);
+        let span = value.span();
+
+        let kind = match value {
+            ValueExpr::Int { value, .. } => ValueKind::Int(*value),
+            ValueExpr::Float { value, .. } => ValueKind::Float(*value),
+            ValueExpr::String { value, .. } => ValueKind::String(value.clone()),
+            ValueExpr::Bool { value, .. } => ValueKind::Bool(*value),
+            ValueExpr::Identifier(identifier) => ValueKind::Local(self.used(identifier)),
+
+            ValueExpr::Binary {
+                left,
+                right,
+                operator,
+                ..
+            } => ValueKind::Binary {
+                operator: operator.clone(),
+                left: Box::new(self.lower_value(left)),
+                right: Box::new(self.lower_value(right)),
+            },
+
+            ValueExpr::Unary {
+                operand, operator, ..
+            } => ValueKind::Unary {
+                operator: operator.clone(),
+                operand: Box::new(self.lower_value(operand)),
+            },
+
+            ValueExpr::Cast { value, .. } => ValueKind::Cast {
+                value: Box::new(self.lower_value(value)),
+            },
+
+            ValueExpr::Call { callee, args, .. } => {
+                let args: Vec<Value> = args.iter().map(|arg| self.lower_value(arg)).collect();
+
+                // builtins are only ever called, so they don't need a value of their own
+                match self.builtin(callee) {
+                    // printing and logging with a template print what it formats to
+                    Some((
+                        builtin @ (Builtin::Print
+                        | Builtin::Println
+                        | Builtin::LogDebug
+                        | Builtin::LogInfo
+                        | Builtin::LogWarn
+                        | Builtin::LogError),
+                        _,
+                    )) if args.len() > 1 => {
+                        let text =
+                            Self::builtin_call(Builtin::Format, args, BaseType::String, span);
+                        ValueKind::Builtin {
+                            builtin,
+                            args: vec![text],
+                        }
+                    }
+                    Some((builtin, receiver)) => {
+                        let receiver = receiver.map(|receiver| self.lower_value(receiver));
+                        ValueKind::Builtin {
+                            builtin,
+                            args: receiver.into_iter().chain(args).collect(),
+                        }
+                    }
+                    None => ValueKind::Call {
+                        callee: Box::new(self.lower_value(callee)),
+                        args,
+                    },
+                }
+            }
+
+            ValueExpr::List { items, .. } => {
+                ValueKind::List(items.iter().map(|item| self.lower_value(item)).collect())
+            }
+
+            ValueExpr::Map { entries, .. } => ValueKind::Map(
+                entries
+                    .iter()
+                    .map(|(key, value)| (self.lower_value(key), self.lower_value(value)))
+                    .collect(),
+            ),
+
+            ValueExpr::Function { params, body, .. } => ValueKind::Function {
+                params: params
+                    .iter()
+                    .map(|param| self.declared(&param.identifier))
+                    .collect(),
+                body: self.lower_body(body),
+            },
+
+            // the type checker only allows fields on enums, where they name a
+            // variant, on modules, where they name a constant, and on the files
+            // the program uses, where they name what the file declares
+            ValueExpr::Field { value, field, .. } => {
+                let ValueExpr::Identifier(identifier) = &**value else {
+                    unreachable!("fields are only allowed on enums and modules");
+                };
+
+                let definition = self.resolutions.definition(self.used(identifier));
+                match definition.kind {
+                    DefKind::Import(_) => ValueKind::Local(self.used(field)),
+                    DefKind::Module(module) => {
+                        let constant = module
+                            .constant(&field.name)
+                            .expect("module members are checked before lowering");
+                        ValueKind::Float(constant)
+                    }
+                    _ => {
+                        let enum_name = definition.name;
+                        ValueKind::Variant {
+                            index: self.variant_index(enum_name, field),
+                            enum_name,
+                        }
+                    }
+                }
+            }
+        };
+
+        Value {
+            kind,
+            basetype,
+            span,
+        }
+    }
+
+    fn lower_pattern(&self, pattern: &Pattern) -> hir::Pattern {
+        match pattern {
+            Pattern::Wildcard(_) => hir::Pattern::Wildcard,
+            Pattern::Binding(identifier) => hir::Pattern::Binding(self.declared(identifier)),
+            Pattern::Int { value, .. } => hir::Pattern::Int(*value),
+            Pattern::String { value, .. } => hir::Pattern::String(value.clone()),
+            Pattern::Bool { value, .. } => hir::Pattern::Bool(*value),
+            Pattern::Variant {
+                enum_name,
+                variant,
+                fields,
+                ..
+            } => hir::Pattern::Variant {
+                index: self.variant_index(enum_name.name, variant),
+                fields: fields
+                    .iter()
+                    .map(|field| self.lower_pattern(field))
+                    .collect(),
+            },
+        }
+    }
+
+    fn lower_body(&mut self, body: &[Expr]) -> Vec<Stmt> {
+        body.iter()
+            .filter_map(|expr| self.lower_expr(expr))
+            .collect()
+    }
+
+    // a call to a builtin that doesn't exist in the source
+    fn builtin_call(builtin: Builtin, args: Vec<Value>, basetype: BaseType, span: Span) -> Value {
+        Value {
+            kind: ValueKind::Builtin { builtin, args },
+            basetype,
+            span,
+        }
+    }
+
+    // `for i in start..end { body }` becomes
+    //
+    //     {
+    //         let mut counter = start;
+    //         let end = end;
+    //         while counter < end { let i = counter; body } step { counter = counter + 1; }
+    //     }
+    //
+    // and `for item in items { body }` goes through the indices of the list, or
+    // of `items.keys()` for a map
+    //
+    //     {
+    //         let mut counter = 0;
+    //         let items = items;
+    //         while counter < items.len() { let item = items.get(counter); body } step { ... }
+    //     }
+    fn lower_for(
+        &mut self,
+        binding: &Identifier,
+        start: &ValueExpr,
+        end: Option<&ValueExpr>,
+        body: &[Expr],
+    ) -> Stmt {
+        // the values that don't exist in the source point at the binding
+        let span = binding.span;
+        let counter = self.fresh("counter", BaseType::Int, true);
+
+        let (start, end, end_type) = match end {
+            Some(end) => (
+                self.lower_value(start),
+                self.lower_value(end),
+                BaseType::Int,
+            ),
+            None => {
+                let mut items = self.lower_value(start);
+                if let BaseType::Map(key, _) = &items.basetype {
+                    let keys = BaseType::List(key.clone());
+                    items = Self::builtin_call(Builtin::Keys, vec![items], keys, span);
+                }
+
+                let zero = Value {
+                    kind: ValueKind::Int(0),
+                    basetype: BaseType::Int,
+                    span,
+                };
+                let end_type = items.basetype.clone();
+                (zero, items, end_type)
+            }
+        };
+
+        let end_name = if end_type == BaseType::Int {
+            "end"
+        } else {
+            "items"
+        };
+        let end_local = self.fresh(end_name, end_type.clone(), false);
+        let (limit, item) = match &end_type {
+            BaseType::List(item) => {
+                let items = Self::local(end_local, end_type.clone(), span);
+                let limit = Self::builtin_call(Builtin::Len, vec![items], BaseType::Int, span);
+
+                let items = Self::local(end_local, end_type.clone(), span);
+                let counter = Self::local(counter, BaseType::Int, span);
+                let item =
+                    Self::builtin_call(Builtin::Get, vec![items, counter], (**item).clone(), span);
+                (limit, item)
+            }
+            _ => (
+                Self::local(end_local, BaseType::Int, span),
+                Self::local(counter, BaseType::Int, span),
+            ),
+        };
+
+        let condition = Value {
+            kind: ValueKind::Binary {
+                operator: TokenKind::LowerThan,
+                left: Box::new(Self::local(counter, BaseType::Int, span)),
+                right: Box::new(limit),
+            },
+            basetype: BaseType::Bool,
+            span,
+        };
+
+        let increment = Stmt::Assign {
+            target: Self::local(counter, BaseType::Int, span),
+            value: Value {
+                kind: ValueKind::Binary {
+                    operator: TokenKind::Plus,
+                    left: Box::new(Self::local(counter, BaseType::Int, span)),
+                    right: Box::new(Value {
+                        kind: ValueKind::Int(1),
+                        basetype: BaseType::Int,
+                        span,
+                    }),
+                },
+                basetype: BaseType::Int,
+                span,
+            },
+        };
+
+        let mut loop_body = vec![Stmt::Let {
+            local: self.declared(binding),
+            value: item,
+            span,
+        }];
+        loop_body.extend(self.lower_body(body));
+
+        Stmt::Block(vec![
+            Stmt::Let {
+                local: counter,
+                value: start,
+                span,
+            },
+            Stmt::Let {
+                local: end_local,
+                value: end,
+                span,
+            },
+            Stmt::While {
+                condition,
+                body: loop_body,
+                step: vec![increment],
+            },
+        ])
+    }
+
+    // enum declarations don't lower to anything, they only live in `Hir::enums`,
+    // and tests only in `Hir::tests`
+    fn lower_expr(&mut self, expr: &Expr) -> Option<Stmt> {
+        let stmt = match expr {
+            Expr::Declaration {
+                identifier,
+                value,
+                attributes,
+                ..
+            } => {
+                if attributes.iter().any(|attribute| attribute.name == "bench") {
+                    self.benches.push(hir::Bench {
+                        name: identifier.name,
+                        local: self.declared(identifier),
+                        span: identifier.span,
+                    });
+                }
+
+                match value {
+                    Some(value) => Stmt::Let {
+                        local: self.declared(identifier),
+                        value: self.lower_value(value),
+                        span: identifier.span,
+                    },
+                    None => Stmt::Declare {
+                        local: self.declared(identifier),
+                        span: identifier.span,
+                    },
+                }
+            }
+
+            // `x += 1` is `x = x + 1`
+            Expr::Assign {
+                target,
+                operator,
+                value,
+            } => {
+                let lowered = self.lower_value(value);
+                let value = match operator {
+                    Some(operator) => Value {
+                        basetype: self.basetype(target),
+                        span: target.span().to(value.span()),
+                        kind: ValueKind::Binary {
+                            operator: operator.clone(),
+                            left: Box::new(self.lower_value(target)),
+                            right: Box::new(lowered),
+                        },
+                    },
+                    None => lowered,
+                };
+
+                Stmt::Assign {
+                    target: self.lower_value(target),
+                    value,
+                }
+            }
+
+            Expr::Return { value, .. } => {
+                Stmt::Return(value.as_ref().map(|value| self.lower_value(value)))
+            }
+
+            Expr::If {
+                condition,
+                body,
+                else_body,
+                ..
+            } => Stmt::If {
+                condition: self.lower_value(condition),
+                body: self.lower_body(body),
+                else_body: else_body
+                    .as_ref()
+                    .map(|else_body| self.lower_body(else_body))
+                    .unwrap_or_default(),
+            },
+
+            Expr::While {
+                condition, body, ..
+            } => Stmt::While {
+                condition: self.lower_value(condition),
+                body: self.lower_body(body),
+                step: vec![],
+            },
+
+            Expr::For {
+                binding,
+                start,
+                end,
+                body,
+                ..
+            } => self.lower_for(binding, start, end.as_ref(), body),
+
+            Expr::Match { value, arms, .. } => Stmt::Match {
+                value: self.lower_value(value),
+                arms: arms
+                    .iter()
+                    .map(|arm| MatchArm {
+                        pattern: self.lower_pattern(&arm.pattern),
+                        body: self.lower_body(arm.body),
+                    })
+                    .collect(),
+            },
+
+            Expr::Try {
+                body,
+                error,
+                handler,
+                span,
+            } => Stmt::Try {
+                body: self.lower_body(body),
+                error: self.declared(error),
+                handler: self.lower_body(handler),
+                span: *span,
+            },
+
+            Expr::Enum { .. } => return None,
+            Expr::Test { name, body, span } => {
+                let body = self.lower_body(body);
+                self.tests.push(hir::Test {
+                    name: name.clone(),
+                    body,
+                    span: *span,
+                });
+                return None;
+            }
+            Expr::Break(_) => Stmt::Break,
+            Expr::Continue(_) => Stmt::Continue,
+            Expr::Block { body, .. } => Stmt::Block(self.lower_body(body)),
+            Expr::Value(value) => Stmt::Value(self.lower_value(value)),
+        };
+
+        Some(stmt)
+    }
+
+    // the modules' statements go first, they run before the program's
+    pub fn lower(mut self, program: &Program) -> Hir {
+        let mut body = vec![];
+        for module in &program.modules {
+            body.extend(self.lower_body(module.program.body));
+        }
+        let imported = body.len();
+        body.extend(self.lower_body(program.body));
+
+        Hir {
+            locals: self.locals,
+            enums: self.types.enums.clone(),
+            body,
+            imported,
+            hosts: self.resolutions.hosts.clone(),
+            tests: self.tests,
+            benches: self.benches,
+        }
+    }
+}
@@ -0,0 +1,291 @@
+use std::collections::HashSet;
+
+use ez_ast::{Expr, Identifier, Program, ValueExpr};
+use ez_diagnostics::Diagnostic;
+use ez_lexer::Span;
+
+use crate::resolver::{DefKind, Resolutions};
+
+#[derive(Debug)]
+pub enum MutabilityError {
+    AssignToImmutable {
+        target: Identifier,
+        kind: DefKind,
+        declared: Span,
+        // declared with `:=`, so a `mut` in front of it makes it mutable
+        declaration: bool,
+    },
+    InvalidAssignTarget(Span),
+}
+
+impl MutabilityError {
+    pub fn help(&self) -> Option<String> {
+        match self {
+            MutabilityError::AssignToImmutable {
+                target,
+                kind: DefKind::Variable | DefKind::Function,
+                ..
+            } => Some(format!(
+                "consider making `{}` mutable: `mut {} := ...`",
+                target.name, target.name
+            )),
+            MutabilityError::AssignToImmutable {
+                kind: DefKind::Param,
+                ..
+            } => Some("parameters can't be assigned to, declare a `mut` copy of it instead".into()),
+            MutabilityError::AssignToImmutable {
+                kind:
+                    DefKind::Enum
+                    | DefKind::Builtin(_)
+                    | DefKind::Module(_)
+                    | DefKind::Host
+                    | DefKind::Import(_),
+                ..
+            }
+            | MutabilityError::InvalidAssignTarget(_) => None,
+        }
+    }
+}
+
+impl From<MutabilityError> for Diagnostic {
+    fn from(error: MutabilityError) -> Self {
+        let help = error.help();
+        let mut suggestion = None;
+        let diagnostic = match error {
+            MutabilityError::AssignToImmutable {
+                target,
+                kind,
+                declared,
+                declaration,
+            } => {
+                if declaration {
+                    suggestion = Some((Span::new(declared.start, declared.start), "mut "));
+                }
+
+                let diagnostic = Diagnostic::error(
+                    "E0301",
+                    format!("cannot assign twice to immutable `{}`", target.name),
+                )
+                .with_label(target.span, "cannot assign to it");
+
+                // functions of the host aren't declared in the program
+                match kind {
+                    DefKind::Host => diagnostic,
+                    _ => diagnostic.with_secondary(declared, "declared here"),
+                }
+            }
+
+            MutabilityError::InvalidAssignTarget(span) => {
+                Diagnostic::error("E0302", "invalid left-hand side of assignment")
+                    .with_label(span, "cannot assign to this")
+            }
+        };
+
+        match (help, suggestion) {
+            (Some(help), Some((span, replacement))) => {
+                diagnostic.with_suggestion(help, span, replacement)
+            }
+            (Some(help), None) => diagnostic.with_help(help),
+            (None, _) => diagnostic,
+        }
+    }
+}
+
+// rejects assignments to bindings that weren't declared with `mut`
+pub struct MutabilityChecker<'a> {
+    resolutions: &'a Resolutions,
+    errors: Vec<MutabilityError>,
+    // the names declared with `:=`, a function can assign one declared after it
+    declarations: HashSet<Span>,
+}
+
+impl<'a> MutabilityChecker<'a> {
+    pub fn new(resolutions: &'a Resolutions) -> Self {
+        Self {
+            resolutions,
+            errors: vec![],
+            declarations: HashSet::new(),
+        }
+    }
+
+    fn check_target(&mut self, target: &ValueExpr) {
+        let ValueExpr::Identifier(identifier) = target else {
+            self.errors
+                .push(MutabilityError::InvalidAssignTarget(target.span()));
+            return;
+        };
+
+        // unresolved names were already reported by the resolver
+        let Some(id) = self.resolutions.resolve(identifier) else {
+            return;
+        };
+
+        // variables declared without a value get assigned later, assigning them
+        // more than once is caught by the initialization check
+        let definition = self.resolutions.definition(id);
+        // the type checker already reports builtins used as anything but a callee,
+        // and modules used as anything but the value of a field
+        if let DefKind::Builtin(_) | DefKind::Module(_) | DefKind::Import(_) = definition.kind {
+            return;
+        }
+
+        if !definition.mutable && !self.resolutions.uninitialized.contains(&id) {
+            self.errors.push(MutabilityError::AssignToImmutable {
+                target: identifier.clone(),
+                kind: definition.kind,
+                declared: definition.span,
+                declaration: false,
+            });
+        }
+    }
+
+    fn check_value(&mut self, value: &ValueExpr) {
+        match value {
+            ValueExpr::Int { .. }
+            | ValueExpr::Float { .. }
+            | ValueExpr::String { .. }
+            | ValueExpr::Bool { .. }
+            | ValueExpr::Identifier(_) => {}
+
+            ValueExpr::Binary { left, right, .. } => {
+                self.check_value(left);
+                self.check_value(right);
+            }
+
+            ValueExpr::Unary { operand, .. } => self.check_value(operand),
+
+            ValueExpr::Cast { value, .. } => self.check_value(value),
+
+            ValueExpr::Field { value, .. } => self.check_value(value),
+
+            ValueExpr::Call { callee, args, .. } => {
+                self.check_value(callee);
+                for arg in args.iter() {
+                    self.check_value(arg);
+                }
+            }
+
+            ValueExpr::List { items, .. } => {
+                for item in items.iter() {
+                    self.check_value(item);
+                }
+            }
+
+            ValueExpr::Map { entries, .. } => {
+                for (key, value) in entries.iter() {
+                    self.check_value(key);
+                    self.check_value(value);
+                }
+            }
+
+            ValueExpr::Function { body, .. } => {
+                for expr in body.iter() {
+                    self.check_expr(expr);
+                }
+            }
+        }
+    }
+
+    fn check_expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Declaration {
+                identifier, value, ..
+            } => {
+                self.declarations.insert(identifier.span);
+                if let Some(value) = value {
+                    self.check_value(value);
+                }
+            }
+
+            Expr::Assign { target, value, .. } => {
+                self.check_target(target);
+                self.check_value(value);
+            }
+
+            Expr::Block { body, .. } | Expr::Test { body, .. } => {
+                for expr in body.iter() {
+                    self.check_expr(expr);
+                }
+            }
+
+            Expr::If {
+                condition,
+                body,
+                else_body,
+                ..
+            } => {
+                self.check_value(condition);
+                for expr in body.iter().chain(else_body.unwrap_or_default()) {
+                    self.check_expr(expr);
+                }
+            }
+
+            Expr::While {
+                condition, body, ..
+            } => {
+                self.check_value(condition);
+                for expr in body.iter() {
+                    self.check_expr(expr);
+                }
+            }
+
+            Expr::For {
+                start, end, body, ..
+            } => {
+                self.check_value(start);
+                if let Some(end) = end {
+                    self.check_value(end);
+                }
+                for expr in body.iter() {
+                    self.check_expr(expr);
+                }
+            }
+
+            Expr::Match { value, arms, .. } => {
+                self.check_value(value);
+                for expr in arms.iter().flat_map(|arm| arm.body) {
+                    self.check_expr(expr);
+                }
+            }
+
+            Expr::Try { body, handler, .. } => {
+                for expr in body.iter().chain(*handler) {
+                    self.check_expr(expr);
+                }
+            }
+
+            Expr::Break(_) | Expr::Continue(_) | Expr::Enum { .. } => {}
+
+            Expr::Value(value) => self.check_value(value),
+
+            Expr::Return { value, .. } => {
+                if let Some(value) = value {
+                    self.check_value(value);
+                }
+            }
+        }
+    }
+
+    pub fn check(mut self, program: &Program) -> Result<(), Vec<MutabilityError>> {
+        for expr in program.statements() {
+            self.check_expr(expr);
+        }
+
+        for error in &mut self.errors {
+            if let MutabilityError::AssignToImmutable {
+                declared,
+                declaration,
+                ..
+            } = error
+            {
+                *declaration = self.declarations.contains(declared);
+            }
+        }
+
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(self.errors)
+        }
+    }
+}
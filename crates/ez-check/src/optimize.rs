@@ -0,0 +1,551 @@
+use std::collections::{HashMap, HashSet};
+
+use ez_ast::BaseType;
+use ez_lexer::TokenKind;
+
+use crate::cfg::BlockId;
+use crate::hir::Pattern;
+use crate::ssa::{Block, Constant, Function, Instr, Op, Program, Terminator, ValueId};
+
+// the optimizations on the SSA form, the higher the level the more of them run:
+// `-O0` none, `-O1` removes the instructions nothing needs, `-O2` propagates
+// constants before, so the branches they decide and what's computed from them
+// go too
+pub const MAX_LEVEL: u8 = 2;
+
+pub fn run(program: &mut Program, level: u8) {
+    for function in &mut program.functions {
+        if level >= 2 {
+            propagate_constants(function);
+        }
+        if level >= 1 {
+            eliminate_dead_code(function);
+        }
+        function.renumber();
+    }
+}
+
+// what a value is known to be, it only ever goes down: from not known yet, to
+// a constant, to anything
+#[derive(Debug, Clone, PartialEq)]
+enum Lattice {
+    Unknown,
+    Constant(Constant),
+    Varying,
+}
+
+impl Lattice {
+    fn meet(&self, other: &Lattice) -> Lattice {
+        match (self, other) {
+            (Lattice::Unknown, other) | (other, Lattice::Unknown) => other.clone(),
+            (Lattice::Constant(left), Lattice::Constant(right)) if same(left, right) => {
+                self.clone()
+            }
+            _ => Lattice::Varying,
+        }
+    }
+}
+
+// floats are the same when their bits are, `0.0` and `-0.0` print differently
+fn same(left: &Constant, right: &Constant) -> bool {
+    match (left, right) {
+        (Constant::Float(left), Constant::Float(right)) => left.to_bits() == right.to_bits(),
+        _ => left == right,
+    }
+}
+
+// what an operator gives with constants, like the interpreter does. Nothing
+// when it would fail, it's left to fail when the program runs
+fn fold_binary(operator: &TokenKind, left: &Constant, right: &Constant) -> Option<Constant> {
+    let folded = match (operator, left, right) {
+        (TokenKind::Equals, left, right) => Constant::Bool(left == right),
+        (TokenKind::NotEquals, left, right) => Constant::Bool(left != right),
+        (TokenKind::Plus, Constant::String(left), Constant::String(right)) => {
+            Constant::String(format!("{}{}", left, right))
+        }
+
+        (operator, Constant::Int(left), Constant::Int(right)) => match operator {
+            TokenKind::Plus => Constant::Int(left.checked_add(*right)?),
+            TokenKind::Minus => Constant::Int(left.checked_sub(*right)?),
+            TokenKind::Times => Constant::Int(left.checked_mul(*right)?),
+            TokenKind::DividedBy => Constant::Int(left.checked_div(*right)?),
+            TokenKind::BitAnd => Constant::Int(left & right),
+            TokenKind::BitOr => Constant::Int(left | right),
+            TokenKind::BitXor => Constant::Int(left ^ right),
+            TokenKind::GreaterThan => Constant::Bool(left > right),
+            TokenKind::GreaterOrEquals => Constant::Bool(left >= right),
+            TokenKind::LowerThan => Constant::Bool(left < right),
+            TokenKind::LowerOrEquals => Constant::Bool(left <= right),
+            _ => return None,
+        },
+
+        (operator, Constant::Float(left), Constant::Float(right)) => match operator {
+            TokenKind::Plus => Constant::Float(left + right),
+            TokenKind::Minus => Constant::Float(left - right),
+            TokenKind::Times => Constant::Float(left * right),
+            TokenKind::DividedBy => Constant::Float(left / right),
+            TokenKind::GreaterThan => Constant::Bool(left > right),
+            TokenKind::GreaterOrEquals => Constant::Bool(left >= right),
+            TokenKind::LowerThan => Constant::Bool(left < right),
+            TokenKind::LowerOrEquals => Constant::Bool(left <= right),
+            _ => return None,
+        },
+
+        _ => return None,
+    };
+
+    Some(folded)
+}
+
+fn fold_unary(operator: &TokenKind, operand: &Constant) -> Option<Constant> {
+    match (operator, operand) {
+        (TokenKind::Minus, Constant::Int(operand)) => Some(Constant::Int(operand.checked_neg()?)),
+        (TokenKind::Minus, Constant::Float(operand)) => Some(Constant::Float(-operand)),
+        (TokenKind::Not, Constant::Bool(operand)) => Some(Constant::Bool(!operand)),
+        (TokenKind::BitNot, Constant::Int(operand)) => Some(Constant::Int(!operand)),
+        _ => None,
+    }
+}
+
+// casts to strings are left to the backends, which each print numbers their way
+fn fold_cast(value: &Constant, to: &BaseType) -> Option<Constant> {
+    match (value, to) {
+        (Constant::Int(value), BaseType::Float) => Some(Constant::Float(*value as f64)),
+        (Constant::Float(value), BaseType::Int) => Some(Constant::Int(*value as i64)),
+        (Constant::Bool(value), BaseType::Int) => Some(Constant::Int(*value as i64)),
+        (value, to) if constant_type(value) == *to => Some(value.clone()),
+        _ => None,
+    }
+}
+
+fn constant_type(constant: &Constant) -> BaseType {
+    match constant {
+        Constant::Int(_) => BaseType::Int,
+        Constant::Float(_) => BaseType::Float,
+        Constant::String(_) => BaseType::String,
+        Constant::Bool(_) => BaseType::Bool,
+    }
+}
+
+// the arm a constant goes to, patterns with bindings only match what was built
+// with a variant, which isn't a constant
+fn matches(pattern: &Pattern, value: &Constant) -> bool {
+    match (pattern, value) {
+        (Pattern::Wildcard | Pattern::Binding(_), _) => true,
+        (Pattern::Int(pattern), Constant::Int(value)) => pattern == value,
+        (Pattern::String(pattern), Constant::String(value)) => pattern == value,
+        (Pattern::Bool(pattern), Constant::Bool(value)) => pattern == value,
+        _ => false,
+    }
+}
+
+// where a value is read: an instruction, by its block and position, or the
+// terminator of a block
+#[derive(Clone, Copy)]
+enum Use {
+    Instr(BlockId, usize),
+    Terminator(BlockId),
+}
+
+// sparse conditional constant propagation: values are only followed along the
+// edges that can be taken given what's known so far, so a constant deciding a
+// branch keeps the other side, and what it assigns, out of the phis after it
+struct Propagation<'f> {
+    function: &'f Function,
+    values: Vec<Lattice>,
+    users: Vec<Vec<Use>>,
+    reached: Vec<bool>,
+    edges: HashSet<(BlockId, BlockId)>,
+    flow: Vec<(Option<BlockId>, BlockId)>,
+    changed: Vec<ValueId>,
+}
+
+impl<'f> Propagation<'f> {
+    fn new(function: &'f Function) -> Self {
+        let mut users = vec![vec![]; function.values];
+        for (index, block) in function.blocks.iter().enumerate() {
+            for (position, instr) in block.instrs.iter().enumerate() {
+                for operand in instr.op.operands() {
+                    users[operand.0].push(Use::Instr(BlockId(index), position));
+                }
+            }
+            if let Some(operand) = block.terminator.operand() {
+                users[operand.0].push(Use::Terminator(BlockId(index)));
+            }
+        }
+
+        Self {
+            function,
+            values: vec![Lattice::Unknown; function.values],
+            users,
+            reached: vec![false; function.blocks.len()],
+            edges: HashSet::new(),
+            flow: vec![(None, BlockId(0))],
+            changed: vec![],
+        }
+    }
+
+    fn solve(&mut self) {
+        loop {
+            if let Some((from, to)) = self.flow.pop() {
+                if let Some(from) = from {
+                    if !self.edges.insert((from, to)) {
+                        continue;
+                    }
+                }
+
+                // a new edge only changes the phis, unless the block wasn't
+                // reached before
+                let first = !std::mem::replace(&mut self.reached[to.0], true);
+                let block = &self.function.blocks[to.0];
+                for (position, instr) in block.instrs.iter().enumerate() {
+                    if first || matches!(instr.op, Op::Phi(_)) {
+                        self.visit(to, position);
+                    }
+                }
+                if first {
+                    self.visit_terminator(to);
+                }
+            } else if let Some(value) = self.changed.pop() {
+                for user in self.users[value.0].clone() {
+                    match user {
+                        Use::Instr(block, position) if self.reached[block.0] => {
+                            self.visit(block, position)
+                        }
+                        Use::Terminator(block) if self.reached[block.0] => {
+                            self.visit_terminator(block)
+                        }
+                        _ => {}
+                    }
+                }
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn visit(&mut self, block: BlockId, position: usize) {
+        let instr = &self.function.blocks[block.0].instrs[position];
+        let value = self.evaluate(block, instr);
+        let current = &self.values[instr.id.0];
+        let lowered = current.meet(&value);
+        if lowered != *current {
+            self.values[instr.id.0] = lowered;
+            self.changed.push(instr.id);
+        }
+    }
+
+    fn evaluate(&self, block: BlockId, instr: &Instr) -> Lattice {
+        let constant = |value: &ValueId| match &self.values[value.0] {
+            Lattice::Constant(constant) => Ok(constant),
+            other => Err(other.clone()),
+        };
+
+        let folded = match &instr.op {
+            Op::Const(constant) => return Lattice::Constant(constant.clone()),
+
+            Op::Phi(incoming) => {
+                return incoming
+                    .iter()
+                    .filter(|(from, _)| self.edges.contains(&(*from, block)))
+                    .fold(Lattice::Unknown, |lattice, (_, value)| {
+                        lattice.meet(&self.values[value.0])
+                    })
+            }
+
+            Op::Binary {
+                operator,
+                left,
+                right,
+            } => match (constant(left), constant(right)) {
+                (Ok(left), Ok(right)) => fold_binary(operator, left, right),
+                (Err(Lattice::Varying), _) | (_, Err(Lattice::Varying)) => None,
+                _ => return Lattice::Unknown,
+            },
+
+            Op::Unary { operator, operand } => match constant(operand) {
+                Ok(operand) => fold_unary(operator, operand),
+                Err(lattice) => return lattice,
+            },
+
+            Op::Cast(value) => match constant(value) {
+                Ok(value) => fold_cast(value, &instr.basetype),
+                Err(lattice) => return lattice,
+            },
+
+            // what comes from outside the function, calls and loads can be
+            // anything, and nothing is assumed about what a local holds before
+            // it's assigned
+            _ => None,
+        };
+
+        match folded {
+            Some(constant) => Lattice::Constant(constant),
+            None => Lattice::Varying,
+        }
+    }
+
+    fn visit_terminator(&mut self, block: BlockId) {
+        let from = Some(block);
+        match &self.function.blocks[block.0].terminator {
+            Terminator::Jump(target) => self.flow.push((from, *target)),
+
+            Terminator::Branch {
+                condition,
+                then,
+                otherwise,
+            } => match &self.values[condition.0] {
+                Lattice::Unknown => {}
+                Lattice::Constant(Constant::Bool(true)) => self.flow.push((from, *then)),
+                Lattice::Constant(Constant::Bool(false)) => self.flow.push((from, *otherwise)),
+                _ => {
+                    self.flow.push((from, *then));
+                    self.flow.push((from, *otherwise));
+                }
+            },
+
+            Terminator::Match { value, arms } => {
+                let taken = match &self.values[value.0] {
+                    Lattice::Unknown => return,
+                    Lattice::Constant(constant) => arms
+                        .iter()
+                        .find(|(pattern, _)| matches(pattern, constant))
+                        .map(|(_, target)| *target),
+                    Lattice::Varying => None,
+                };
+
+                match taken {
+                    Some(target) => self.flow.push((from, target)),
+                    None => self
+                        .flow
+                        .extend(arms.iter().map(|(_, target)| (from, *target))),
+                }
+            }
+
+            Terminator::Try { body, handler } => {
+                self.flow.push((from, *body));
+                self.flow.push((from, *handler));
+            }
+
+            Terminator::Return(_) => {}
+        }
+    }
+}
+
+// replaces the values found to be constants with them, turns the branches they
+// decide into jumps and removes the blocks that can't be reached anymore
+pub fn propagate_constants(function: &mut Function) {
+    let mut propagation = Propagation::new(function);
+    propagation.solve();
+    let Propagation {
+        values,
+        reached,
+        edges,
+        ..
+    } = propagation;
+
+    for (index, block) in function.blocks.iter_mut().enumerate() {
+        if !reached[index] {
+            continue;
+        }
+
+        for instr in &mut block.instrs {
+            if let (Lattice::Constant(constant), false) =
+                (&values[instr.id.0], matches!(instr.op, Op::Const(_)))
+            {
+                instr.op = Op::Const(constant.clone());
+            }
+        }
+        // the phis that became constants aren't phis anymore
+        block
+            .instrs
+            .sort_by_key(|instr| !matches!(instr.op, Op::Phi(_)));
+
+        let taken = |target: &BlockId| edges.contains(&(BlockId(index), *target));
+        let jump = match &mut block.terminator {
+            Terminator::Branch {
+                then, otherwise, ..
+            } if taken(then) != taken(otherwise) => {
+                Some(if taken(then) { *then } else { *otherwise })
+            }
+            // the arms before the one a constant goes to didn't match it, and
+            // the ones after it aren't tried
+            Terminator::Match { arms, .. } => {
+                arms.retain(|(_, target)| taken(target));
+                match arms.as_slice() {
+                    [(pattern, target)] if !binds(pattern) => Some(*target),
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+        if let Some(target) = jump {
+            block.terminator = Terminator::Jump(target);
+        }
+    }
+
+    // the phis only keep what comes from the edges that can be taken
+    for (index, block) in function.blocks.iter_mut().enumerate() {
+        for instr in &mut block.instrs {
+            if let Op::Phi(incoming) = &mut instr.op {
+                incoming.retain(|(from, _)| edges.contains(&(*from, BlockId(index))));
+            }
+        }
+    }
+
+    remove_blocks(function, &reached);
+    remove_trivial_phis(function);
+}
+
+fn binds(pattern: &Pattern) -> bool {
+    match pattern {
+        Pattern::Binding(_) => true,
+        Pattern::Variant { fields, .. } => fields.iter().any(binds),
+        _ => false,
+    }
+}
+
+// keeps the blocks that are, the entry is always kept so it's still the first
+fn remove_blocks(function: &mut Function, kept: &[bool]) {
+    let mut numbers = vec![None; function.blocks.len()];
+    let mut blocks: Vec<Block> = vec![];
+    for (index, block) in std::mem::take(&mut function.blocks).into_iter().enumerate() {
+        if kept[index] {
+            numbers[index] = Some(BlockId(blocks.len()));
+            blocks.push(block);
+        }
+    }
+
+    let number = |block: &mut BlockId| {
+        *block = numbers[block.0].expect("blocks that are kept only go to blocks that are");
+    };
+    for block in &mut blocks {
+        match &mut block.terminator {
+            Terminator::Jump(target) => number(target),
+            Terminator::Branch {
+                then, otherwise, ..
+            } => {
+                number(then);
+                number(otherwise);
+            }
+            Terminator::Match { arms, .. } => {
+                for (_, target) in arms {
+                    number(target);
+                }
+            }
+            Terminator::Try { body, handler } => {
+                number(body);
+                number(handler);
+            }
+            Terminator::Return(_) => {}
+        }
+
+        for instr in &mut block.instrs {
+            if let Op::Phi(incoming) = &mut instr.op {
+                for (from, _) in incoming {
+                    number(from);
+                }
+            }
+        }
+    }
+
+    function.blocks = blocks;
+}
+
+// a phi merging one value, itself aside, is that value
+fn remove_trivial_phis(function: &mut Function) {
+    let mut replaced: HashMap<ValueId, ValueId> = HashMap::new();
+    loop {
+        let mut found = None;
+        for instr in function.blocks.iter().flat_map(|block| &block.instrs) {
+            let Op::Phi(incoming) = &instr.op else {
+                continue;
+            };
+            let mut values = incoming
+                .iter()
+                .map(|(_, value)| *value)
+                .filter(|value| *value != instr.id);
+            if let Some(first) = values.next() {
+                if values.all(|value| value == first) {
+                    found = Some((instr.id, first));
+                    break;
+                }
+            }
+        }
+
+        let Some((phi, value)) = found else {
+            break;
+        };
+        replaced.insert(phi, value);
+        for block in &mut function.blocks {
+            block.instrs.retain(|instr| instr.id != phi);
+            let operands = block
+                .instrs
+                .iter_mut()
+                .flat_map(|instr| instr.op.operands_mut())
+                .chain(block.terminator.operand_mut());
+            for operand in operands {
+                if *operand == phi {
+                    *operand = value;
+                }
+            }
+        }
+    }
+}
+
+// whether removing the instruction would change what the program does, ints
+// overflowing or divided by zero stop it
+fn has_effects(instr: &Instr) -> bool {
+    match &instr.op {
+        Op::Call { .. } | Op::Builtin { .. } | Op::Store(..) => true,
+        Op::Binary { operator, .. } => {
+            instr.basetype == BaseType::Int
+                && matches!(
+                    operator,
+                    TokenKind::Plus | TokenKind::Minus | TokenKind::Times | TokenKind::DividedBy
+                )
+        }
+        Op::Unary { operator, .. } => {
+            instr.basetype == BaseType::Int && *operator == TokenKind::Minus
+        }
+        _ => false,
+    }
+}
+
+// removes the instructions whose values nothing needs, starting from what has
+// effects and what the terminators read
+pub fn eliminate_dead_code(function: &mut Function) {
+    let definitions: HashMap<ValueId, &Instr> = function
+        .blocks
+        .iter()
+        .flat_map(|block| &block.instrs)
+        .map(|instr| (instr.id, instr))
+        .collect();
+
+    let mut worklist: Vec<ValueId> = function
+        .blocks
+        .iter()
+        .flat_map(|block| {
+            let effects = block
+                .instrs
+                .iter()
+                .filter(|instr| has_effects(instr))
+                .map(|instr| instr.id);
+            effects.chain(block.terminator.operand())
+        })
+        .collect();
+
+    let mut live = HashSet::new();
+    while let Some(value) = worklist.pop() {
+        if !live.insert(value) {
+            continue;
+        }
+        if let Some(instr) = definitions.get(&value) {
+            worklist.extend(instr.op.operands());
+        }
+    }
+
+    for block in &mut function.blocks {
+        block.instrs.retain(|instr| live.contains(&instr.id));
+    }
+}
@@ -0,0 +1,181 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::fmt::Write;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+// the bytes allocated and not freed yet, the most there were since the last
+// pass started, and all of them ever allocated. They count every thread, so a
+// pass is only measured right with nothing else running
+static LIVE: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+// the system's allocator, counting what goes through it. Without it installed
+// as the global allocator, the passes only have their time
+pub struct Counting;
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let pointer = System.alloc(layout);
+        if !pointer.is_null() {
+            counted(layout.size());
+        }
+        pointer
+    }
+
+    unsafe fn dealloc(&self, pointer: *mut u8, layout: Layout) {
+        System.dealloc(pointer, layout);
+        LIVE.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+
+    unsafe fn realloc(&self, pointer: *mut u8, layout: Layout, size: usize) -> *mut u8 {
+        let moved = System.realloc(pointer, layout, size);
+        if !moved.is_null() {
+            LIVE.fetch_sub(layout.size(), Ordering::Relaxed);
+            counted(size);
+        }
+        moved
+    }
+}
+
+fn counted(size: usize) {
+    let live = LIVE.fetch_add(size, Ordering::Relaxed) + size;
+    PEAK.fetch_max(live, Ordering::Relaxed);
+    ALLOCATED.fetch_add(size, Ordering::Relaxed);
+}
+
+pub struct Pass {
+    pub name: &'static str,
+    pub time: Duration,
+    // the bytes it allocated, freed or not
+    pub allocated: usize,
+    // the most memory the compiler had while it ran
+    pub peak: usize,
+}
+
+// the passes the compiler went through, in order, for `--time-passes`. When
+// it's off, they're run without being measured
+#[derive(Default)]
+pub struct Passes {
+    enabled: bool,
+    passes: Vec<Pass>,
+}
+
+impl Passes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn enabled() -> Self {
+        Self {
+            enabled: true,
+            passes: vec![],
+        }
+    }
+
+    pub fn time<T>(&mut self, name: &'static str, pass: impl FnOnce() -> T) -> T {
+        if !self.enabled {
+            return pass();
+        }
+
+        PEAK.store(LIVE.load(Ordering::Relaxed), Ordering::Relaxed);
+        let allocated = ALLOCATED.load(Ordering::Relaxed);
+        let start = Instant::now();
+        let result = pass();
+
+        self.passes.push(Pass {
+            name,
+            time: start.elapsed(),
+            allocated: ALLOCATED.load(Ordering::Relaxed) - allocated,
+            peak: PEAK.load(Ordering::Relaxed),
+        });
+        result
+    }
+
+    pub fn passes(&self) -> &[Pass] {
+        &self.passes
+    }
+
+    // a row for every pass and their total. Without the counting allocator,
+    // there's no memory to show
+    pub fn table(&self) -> String {
+        let total: Duration = self.passes.iter().map(|pass| pass.time).sum();
+        let counted = ALLOCATED.load(Ordering::Relaxed) > 0;
+
+        let mut rows = vec![["pass", "time", "share", "allocated", "peak"]
+            .map(String::from)
+            .to_vec()];
+        for pass in &self.passes {
+            let share = pass.time.as_secs_f64() / total.as_secs_f64().max(f64::MIN_POSITIVE);
+            rows.push(vec![
+                pass.name.into(),
+                duration(pass.time),
+                format!("{:.1}%", share * 100.0),
+                bytes(pass.allocated, counted),
+                bytes(pass.peak, counted),
+            ]);
+        }
+        let allocated = self.passes.iter().map(|pass| pass.allocated).sum();
+        let peak = self.passes.iter().map(|pass| pass.peak).max().unwrap_or(0);
+        rows.push(vec![
+            "total".into(),
+            duration(total),
+            "100.0%".into(),
+            bytes(allocated, counted),
+            bytes(peak, counted),
+        ]);
+
+        columns(&rows)
+    }
+}
+
+fn bytes(bytes: usize, counted: bool) -> String {
+    if !counted {
+        return "-".into();
+    }
+
+    let bytes = bytes as f64;
+    match bytes {
+        b if b < 1024.0 => format!("{} B", b),
+        b if b < 1024.0 * 1024.0 => format!("{:.1} KiB", b / 1024.0),
+        b => format!("{:.1} MiB", b / 1024.0 / 1024.0),
+    }
+}
+
+// like `1.25 ms`, with the unit that keeps it between 1 and 1000
+pub fn duration(duration: Duration) -> String {
+    let nanos = duration.as_secs_f64() * 1e9;
+    match nanos {
+        n if n < 1e3 => format!("{:.0} ns", n),
+        n if n < 1e6 => format!("{:.2} µs", n / 1e3),
+        n if n < 1e9 => format!("{:.2} ms", n / 1e6),
+        n => format!("{:.2} s", n / 1e9),
+    }
+}
+
+// the rows lined up, the first column on the left and the numbers on the right
+pub fn columns(rows: &[Vec<String>]) -> String {
+    let widths: Vec<usize> = (0..rows[0].len())
+        .map(|column| {
+            rows.iter()
+                .map(|row| row[column].chars().count())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    let mut out = String::new();
+    for row in rows {
+        let mut line = String::new();
+        for (column, (cell, width)) in row.iter().zip(&widths).enumerate() {
+            let padding = " ".repeat(width - cell.chars().count());
+            match column {
+                0 => line.push_str(&format!("{}{}", cell, padding)),
+                _ => line.push_str(&format!("  {}{}", padding, cell)),
+            }
+        }
+        let _ = writeln!(out, "{}", line.trim_end());
+    }
+
+    out
+}
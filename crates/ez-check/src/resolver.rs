@@ -0,0 +1,1174 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use ez_ast::{BaseType, Expr, Identifier, NodeId, Param, Pattern, Program, ValueExpr, Variant};
+use ez_diagnostics::Diagnostic;
+use ez_lexer::{Span, Symbol};
+
+use crate::json::Json;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DefId(pub usize);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DefKind {
+    Variable,
+    Function,
+    Param,
+    Enum,
+    Builtin(Builtin),
+    Module(Module),
+    // a function of the program ez is embedded in
+    Host,
+    // a file the program uses, the index of its module
+    Import(usize),
+}
+
+// the functions that come with the language, they can only be called
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Builtin {
+    Print,
+    Println,
+    Format,
+    ReadFile,
+    WriteFile,
+    ReadLines,
+    WriteLines,
+    Args,
+    RunCommand,
+    JsonParse,
+    JsonStringify,
+    RegexMatch,
+    RegexFindAll,
+    RegexReplace,
+    EnvGet,
+    EnvSet,
+    Now,
+    Clock,
+    Sleep,
+    Random,
+    RandomInt,
+    Seed,
+    TcpConnect,
+    TcpListen,
+    TcpAccept,
+    Read,
+    Write,
+    Close,
+    Assert,
+    Panic,
+    LogDebug,
+    LogInfo,
+    LogWarn,
+    LogError,
+    Sqrt,
+    Abs,
+    Pow,
+    Floor,
+    Ceil,
+    Min,
+    Max,
+    // methods, called like `name.len()`, some of them work on more than one type
+    Len,
+    Substring,
+    Contains,
+    ToUpper,
+    ToLower,
+    Trim,
+    ParseInt,
+    ParseFloat,
+    Split,
+    Push,
+    Pop,
+    Get,
+    Set,
+    Insert,
+    Remove,
+    Keys,
+}
+
+impl Builtin {
+    // the ones that aren't in a module
+    pub const ALL: [Builtin; 34] = [
+        Builtin::Print,
+        Builtin::Println,
+        Builtin::Format,
+        Builtin::ReadFile,
+        Builtin::WriteFile,
+        Builtin::ReadLines,
+        Builtin::WriteLines,
+        Builtin::Args,
+        Builtin::RunCommand,
+        Builtin::JsonParse,
+        Builtin::JsonStringify,
+        Builtin::RegexMatch,
+        Builtin::RegexFindAll,
+        Builtin::RegexReplace,
+        Builtin::EnvGet,
+        Builtin::EnvSet,
+        Builtin::Now,
+        Builtin::Clock,
+        Builtin::Sleep,
+        Builtin::Random,
+        Builtin::RandomInt,
+        Builtin::Seed,
+        Builtin::TcpConnect,
+        Builtin::TcpListen,
+        Builtin::TcpAccept,
+        Builtin::Read,
+        Builtin::Write,
+        Builtin::Close,
+        Builtin::Assert,
+        Builtin::Panic,
+        Builtin::LogDebug,
+        Builtin::LogInfo,
+        Builtin::LogWarn,
+        Builtin::LogError,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Builtin::Print => "print",
+            Builtin::Println => "println",
+            Builtin::Format => "format",
+            Builtin::ReadFile => "read_file",
+            Builtin::WriteFile => "write_file",
+            Builtin::ReadLines => "read_lines",
+            Builtin::WriteLines => "write_lines",
+            Builtin::Args => "args",
+            Builtin::RunCommand => "run_command",
+            Builtin::JsonParse => "json_parse",
+            Builtin::JsonStringify => "json_stringify",
+            Builtin::RegexMatch => "regex_match",
+            Builtin::RegexFindAll => "regex_find_all",
+            Builtin::RegexReplace => "regex_replace",
+            Builtin::EnvGet => "env_get",
+            Builtin::EnvSet => "env_set",
+            Builtin::Now => "now",
+            Builtin::Clock => "clock",
+            Builtin::Sleep => "sleep",
+            Builtin::Random => "random",
+            Builtin::RandomInt => "random_int",
+            Builtin::Seed => "seed",
+            Builtin::TcpConnect => "tcp_connect",
+            Builtin::TcpListen => "tcp_listen",
+            Builtin::TcpAccept => "tcp_accept",
+            Builtin::Read => "read",
+            Builtin::Write => "write",
+            Builtin::Close => "close",
+            Builtin::Assert => "assert",
+            Builtin::Panic => "panic",
+            Builtin::LogDebug => "log_debug",
+            Builtin::LogInfo => "log_info",
+            Builtin::LogWarn => "log_warn",
+            Builtin::LogError => "log_error",
+            Builtin::Sqrt => "sqrt",
+            Builtin::Abs => "abs",
+            Builtin::Pow => "pow",
+            Builtin::Floor => "floor",
+            Builtin::Ceil => "ceil",
+            Builtin::Min => "min",
+            Builtin::Max => "max",
+            Builtin::Len => "len",
+            Builtin::Substring => "substring",
+            Builtin::Contains => "contains",
+            Builtin::ToUpper => "to_upper",
+            Builtin::ToLower => "to_lower",
+            Builtin::Trim => "trim",
+            Builtin::ParseInt => "parse_int",
+            Builtin::ParseFloat => "parse_float",
+            Builtin::Split => "split",
+            Builtin::Push => "push",
+            Builtin::Pop => "pop",
+            Builtin::Get => "get",
+            Builtin::Set => "set",
+            Builtin::Insert => "insert",
+            Builtin::Remove => "remove",
+            Builtin::Keys => "keys",
+        }
+    }
+
+    pub fn module(self) -> Option<Module> {
+        match self {
+            Builtin::Sqrt
+            | Builtin::Abs
+            | Builtin::Pow
+            | Builtin::Floor
+            | Builtin::Ceil
+            | Builtin::Min
+            | Builtin::Max => Some(Module::Math),
+            _ => None,
+        }
+    }
+
+    pub fn is_method(self) -> bool {
+        self.module().is_none() && !Builtin::ALL.contains(&self)
+    }
+
+    // the method of the given name on values of the type
+    pub fn method(basetype: &BaseType, name: &str) -> Option<Builtin> {
+        Self::methods(basetype)
+            .iter()
+            .copied()
+            .find(|method| method.name() == name)
+    }
+
+    // every method of values of the type
+    pub fn methods(basetype: &BaseType) -> &'static [Builtin] {
+        match basetype {
+            BaseType::String => &[
+                Builtin::Len,
+                Builtin::Substring,
+                Builtin::Contains,
+                Builtin::ToUpper,
+                Builtin::ToLower,
+                Builtin::Trim,
+                Builtin::ParseInt,
+                Builtin::ParseFloat,
+                Builtin::Split,
+            ],
+            BaseType::List(_) => &[
+                Builtin::Len,
+                Builtin::Push,
+                Builtin::Pop,
+                Builtin::Get,
+                Builtin::Set,
+                Builtin::Remove,
+                Builtin::Contains,
+            ],
+            BaseType::Map(..) => &[
+                Builtin::Len,
+                Builtin::Insert,
+                Builtin::Get,
+                Builtin::Remove,
+                Builtin::Contains,
+                Builtin::Keys,
+            ],
+            _ => &[],
+        }
+    }
+
+    // the builtin printed as the text, like `len` or `math.sqrt`
+    pub fn parse(text: &str) -> Option<Builtin> {
+        if let Some((module, name)) = text.split_once('.') {
+            return Module::ALL
+                .iter()
+                .find(|other| other.name() == module)
+                .and_then(|module| module.function(name));
+        }
+
+        let methods = [
+            BaseType::String,
+            BaseType::List(Box::new(BaseType::Void)),
+            BaseType::Map(Box::new(BaseType::Void), Box::new(BaseType::Void)),
+        ];
+        let methods = methods.iter().flat_map(Builtin::methods);
+        Builtin::ALL
+            .iter()
+            .chain(methods)
+            .find(|builtin| builtin.name() == text)
+            .copied()
+    }
+}
+
+// with the module, like `math.sqrt`
+impl fmt::Display for Builtin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.module() {
+            Some(module) => write!(f, "{}.{}", module.name(), self.name()),
+            None => write!(f, "{}", self.name()),
+        }
+    }
+}
+
+// builtins grouped under a name, their members are used like fields
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Module {
+    Math,
+}
+
+impl Module {
+    pub const ALL: [Module; 1] = [Module::Math];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Module::Math => "math",
+        }
+    }
+
+    pub fn functions(self) -> &'static [Builtin] {
+        match self {
+            Module::Math => &[
+                Builtin::Sqrt,
+                Builtin::Abs,
+                Builtin::Pow,
+                Builtin::Floor,
+                Builtin::Ceil,
+                Builtin::Min,
+                Builtin::Max,
+            ],
+        }
+    }
+
+    pub fn function(self, name: &str) -> Option<Builtin> {
+        self.functions()
+            .iter()
+            .copied()
+            .find(|function| function.name() == name)
+    }
+
+    pub fn constant(self, name: &str) -> Option<f64> {
+        self.constants()
+            .iter()
+            .find(|(constant, _)| *constant == name)
+            .map(|(_, value)| *value)
+    }
+
+    pub fn constants(self) -> &'static [(&'static str, f64)] {
+        match self {
+            Module::Math => &[("pi", std::f64::consts::PI), ("e", std::f64::consts::E)],
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Definition {
+    pub name: Symbol,
+    pub span: Span,
+    pub kind: DefKind,
+    pub mutable: bool,
+}
+
+#[derive(Debug)]
+pub enum ResolveError {
+    UnknownIdentifier(Identifier),
+    // `kind` says what was defined twice, like "function" or "parameter"
+    Duplicate {
+        kind: &'static str,
+        name: Symbol,
+        first: Span,
+        second: Span,
+    },
+    // the enums that come with the language can't be declared again
+    BuiltinEnum(Identifier),
+    // a name the file used as `module` doesn't declare at its top level
+    UnknownMember {
+        module: Symbol,
+        member: Identifier,
+    },
+    UnknownAttribute(Identifier),
+    // `@bench` on something else than a function without parameters
+    NotBenchable {
+        attribute: Identifier,
+        declaration: Identifier,
+    },
+}
+
+// what can be written as `@name` above a top level declaration
+pub const ATTRIBUTES: [&str; 1] = ["bench"];
+
+impl From<ResolveError> for Diagnostic {
+    fn from(error: ResolveError) -> Self {
+        match error {
+            ResolveError::UnknownIdentifier(identifier) => Diagnostic::error(
+                "E0101",
+                format!("cannot find `{}` in this scope", identifier.name),
+            )
+            .with_label(identifier.span, "not found in this scope"),
+
+            ResolveError::Duplicate {
+                kind,
+                name,
+                first,
+                second,
+            } => Diagnostic::error(
+                "E0102",
+                format!("the {} `{}` is defined multiple times", kind, name),
+            )
+            .with_label(second, format!("`{}` redefined here", name))
+            .with_secondary(first, format!("previous definition of `{}` here", name)),
+
+            ResolveError::BuiltinEnum(identifier) => Diagnostic::error(
+                "E0103",
+                format!(
+                    "the enum `{}` already comes with the language",
+                    identifier.name
+                ),
+            )
+            .with_label(identifier.span, "declared again here")
+            .with_help("give this enum another name"),
+
+            ResolveError::UnknownMember { module, member } => Diagnostic::error(
+                "E0104",
+                format!("cannot find `{}` in the module `{}`", member.name, module),
+            )
+            .with_label(member.span, format!("not found in `{}`", module)),
+
+            ResolveError::UnknownAttribute(identifier) => {
+                Diagnostic::error("E0105", format!("unknown attribute `@{}`", identifier.name))
+                    .with_label(identifier.span, "not an attribute")
+                    .with_help(format!(
+                        "the attributes are `@{}`",
+                        ATTRIBUTES.join("`, `@")
+                    ))
+            }
+
+            ResolveError::NotBenchable {
+                attribute,
+                declaration,
+            } => Diagnostic::error(
+                "E0106",
+                format!("`{}` can't be benchmarked", declaration.name),
+            )
+            .with_label(declaration.span, "only functions without parameters can be")
+            .with_secondary(attribute.span, "benchmarked because of this"),
+        }
+    }
+}
+
+// the part of the program a block, a function or a file makes, with what's
+// declared in it and the offset each declaration is visible from
+#[derive(Debug)]
+pub struct Scope {
+    pub span: Span,
+    pub parent: Option<usize>,
+    pub definitions: Vec<(DefId, usize)>,
+}
+
+// how an identifier refers to its definition
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Declaration,
+    Read,
+    Write,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Reference {
+    pub span: Span,
+    pub access: Access,
+}
+
+// def-use links, kept around for the passes that come after resolution
+#[derive(Debug, Default)]
+pub struct Resolutions {
+    pub definitions: Vec<Definition>,
+    // by the id of the identifier using or declaring the definition
+    pub uses: HashMap<NodeId, DefId>,
+    pub declarations: HashMap<NodeId, DefId>,
+    // where those identifiers are, to find the one under the cursor
+    pub spans: HashMap<NodeId, Span>,
+    // variables declared without a value, like `x int;`
+    pub uninitialized: HashSet<DefId>,
+    // the uses that are assigned to, like the `x` of `x = 1;` or `x += 1;`
+    pub writes: HashSet<NodeId>,
+    // the functions of the host, in the order they were given
+    pub hosts: Vec<DefId>,
+    // in the order they were entered, a scope comes after the one it's in
+    pub scopes: Vec<Scope>,
+}
+
+impl Resolutions {
+    pub fn definition(&self, id: DefId) -> &Definition {
+        &self.definitions[id.0]
+    }
+
+    // the definition an identifier use resolved to, if any
+    pub fn resolve(&self, identifier: &Identifier) -> Option<DefId> {
+        self.uses.get(&identifier.id).copied()
+    }
+
+    // the definition introduced by the identifier
+    pub fn defined_at(&self, identifier: &Identifier) -> Option<DefId> {
+        self.declarations.get(&identifier.id).copied()
+    }
+
+    // the definition the identifier with the id uses or declares
+    pub fn definition_of(&self, node: NodeId) -> Option<DefId> {
+        self.uses
+            .get(&node)
+            .or_else(|| self.declarations.get(&node))
+            .copied()
+    }
+
+    // the identifier at the offset, as it's used or declared, the innermost
+    // one when they're nested like a module and its member
+    pub fn identifier_at(&self, offset: usize) -> Option<NodeId> {
+        self.uses
+            .keys()
+            .chain(self.declarations.keys())
+            .map(|node| (*node, self.spans[node]))
+            .filter(|(_, span)| span.start <= offset && offset <= span.end)
+            .min_by_key(|(_, span)| span.end - span.start)
+            .map(|(node, _)| node)
+    }
+
+    // what the program declares with the name, in the order it's declared. A
+    // name can be declared more than once, in other scopes or shadowing itself
+    pub fn find_declaration(&self, name: &str) -> impl Iterator<Item = DefId> {
+        let mut declarations: Vec<(Span, DefId)> = self
+            .declarations
+            .values()
+            .filter(|id| self.definition(**id).name == name)
+            .map(|id| (self.definition(*id).span, *id))
+            .collect();
+        declarations.sort_by_key(|(span, _)| span.start);
+        declarations.into_iter().map(|(_, id)| id)
+    }
+
+    // the identifiers declaring and using what the identifier with the id does,
+    // the declaration included, in the order they're in the program
+    pub fn references_of(&self, node: NodeId) -> Vec<Span> {
+        let Some(id) = self.definition_of(node) else {
+            return vec![];
+        };
+        self.references(id)
+            .into_iter()
+            .map(|reference| reference.span)
+            .collect()
+    }
+
+    // the identifiers declaring and using the definition in every file of the
+    // program, in the order they're in it, with whether they read or write it
+    pub fn references(&self, id: DefId) -> Vec<Reference> {
+        let mut references: Vec<Reference> = self
+            .uses
+            .iter()
+            .chain(&self.declarations)
+            .filter(|(_, used)| **used == id)
+            .map(|(node, _)| {
+                let access = if self.declarations.contains_key(node) {
+                    Access::Declaration
+                } else if self.writes.contains(node) {
+                    Access::Write
+                } else {
+                    Access::Read
+                };
+                Reference {
+                    span: self.spans[node],
+                    access,
+                }
+            })
+            .collect();
+        references.sort_by_key(|reference| reference.span.start);
+        references
+    }
+
+    // the definitions that can be named at the offset, from the innermost scope
+    // out, without the ones shadowed there. An offset between files, in their
+    // comments or blank lines, is taken to be at the end of the file before it
+    pub fn declarations_in_scope(&self, offset: usize) -> Vec<DefId> {
+        let contains = |scope: &Scope| scope.span.start <= offset && offset <= scope.span.end;
+
+        // a scope is entered after the ones it's in, so the last one around the
+        // offset is the innermost
+        let Some(mut innermost) = self.scopes.iter().rposition(contains) else {
+            return vec![];
+        };
+        if self.scopes[innermost].span.end == usize::MAX {
+            let file = self
+                .scopes
+                .iter()
+                .rposition(|scope| scope.parent == Some(innermost) && scope.span.start <= offset);
+            innermost = file.unwrap_or(innermost);
+        }
+
+        let mut names = HashSet::new();
+        let mut visible = vec![];
+        let mut scope = Some(innermost);
+        while let Some(index) = scope {
+            let definitions = &self.scopes[index].definitions;
+            for (id, from) in definitions.iter().rev() {
+                if *from <= offset && names.insert(self.definition(*id).name) {
+                    visible.push(*id);
+                }
+            }
+            scope = self.scopes[index].parent;
+        }
+
+        visible
+    }
+
+    // the names the compiler makes up aren't anywhere in the files, an editor
+    // can't point at them
+    fn used(&mut self, identifier: &Identifier, id: DefId) {
+        if identifier.id == NodeId::default() {
+            return;
+        }
+        self.uses.insert(identifier.id, id);
+        self.spans.insert(identifier.id, identifier.span);
+    }
+}
+
+#[derive(Default)]
+pub struct Resolver {
+    // every function body and `{}` block gets its own scope, which is dropped at
+    // the end of it, so its declarations aren't visible outside
+    scopes: Vec<HashMap<Symbol, DefId>>,
+    // the index of each of those scopes in the resolutions
+    entered: Vec<usize>,
+    resolutions: Resolutions,
+    errors: Vec<ResolveError>,
+    hosts: Vec<String>,
+    // the top level declarations of every module, by name
+    exports: Vec<HashMap<Symbol, DefId>>,
+}
+
+impl Resolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // the names of the functions of the host, they go with the builtins
+    pub fn with_hosts(mut self, hosts: Vec<String>) -> Self {
+        self.hosts = hosts;
+        self
+    }
+
+    // declaring a name that already exists shadows it, even in the same scope: uses
+    // that come after it see the new definition, previous ones keep the old one.
+    // Functions and enums are the exception, they can't be redefined in the scope
+    // they were defined in
+    fn define(&mut self, identifier: &Identifier, kind: DefKind, mutable: bool) -> DefId {
+        let is_item = |kind: DefKind| matches!(kind, DefKind::Function | DefKind::Enum);
+        let scope = self.scopes.last().expect("there should always be a scope");
+        if let Some(previous) = scope.get(&identifier.name) {
+            let previous = self.resolutions.definition(*previous);
+            if is_item(kind) && is_item(previous.kind) {
+                self.errors.push(ResolveError::Duplicate {
+                    kind: if kind == DefKind::Enum {
+                        "enum"
+                    } else {
+                        "function"
+                    },
+                    name: identifier.name,
+                    first: previous.span,
+                    second: identifier.span,
+                });
+            }
+        }
+
+        let id = DefId(self.resolutions.definitions.len());
+        self.resolutions.definitions.push(Definition {
+            name: identifier.name,
+            span: identifier.span,
+            kind,
+            mutable,
+        });
+        // like the builtins, which have no span to find them at
+        if identifier.id != NodeId::default() {
+            self.resolutions.declarations.insert(identifier.id, id);
+            self.resolutions
+                .spans
+                .insert(identifier.id, identifier.span);
+        }
+
+        self.scopes
+            .last_mut()
+            .expect("there should always be a scope")
+            .insert(identifier.name, id);
+        let scope = *self.entered.last().expect("there should always be a scope");
+        self.resolutions.scopes[scope]
+            .definitions
+            .push((id, identifier.span.start));
+
+        id
+    }
+
+    // a declaration is visible from its name on, unless it says otherwise
+    fn visible_from(&mut self, id: DefId, offset: usize) {
+        let scope = *self.entered.last().expect("there should always be a scope");
+        if let Some(definition) = self.resolutions.scopes[scope]
+            .definitions
+            .iter_mut()
+            .rfind(|(defined, _)| *defined == id)
+        {
+            definition.1 = offset;
+        }
+    }
+
+    fn enter(&mut self, span: Span) {
+        self.resolutions.scopes.push(Scope {
+            span,
+            parent: self.entered.last().copied(),
+            definitions: vec![],
+        });
+        self.entered.push(self.resolutions.scopes.len() - 1);
+        self.scopes.push(HashMap::new());
+    }
+
+    fn leave(&mut self) {
+        self.scopes.pop();
+        self.entered.pop();
+    }
+
+    fn lookup(&self, name: Symbol) -> Option<DefId> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(&name).copied())
+    }
+
+    // the module a value names, and its name
+    fn imported(&self, value: &ValueExpr) -> Option<(usize, Symbol)> {
+        let ValueExpr::Identifier(identifier) = value else {
+            return None;
+        };
+
+        let id = self.resolutions.resolve(identifier)?;
+        match self.resolutions.definition(id).kind {
+            DefKind::Import(module) => Some((module, identifier.name)),
+            _ => None,
+        }
+    }
+
+    fn kind_of(value: Option<&ValueExpr>) -> DefKind {
+        match value {
+            Some(ValueExpr::Function { .. }) => DefKind::Function,
+            _ => DefKind::Variable,
+        }
+    }
+
+    // names that have to be unique within a list, like parameters or variants
+    fn check_unique<'i>(
+        &mut self,
+        kind: &'static str,
+        identifiers: impl IntoIterator<Item = &'i Identifier>,
+    ) {
+        let mut seen: HashMap<Symbol, Span> = HashMap::new();
+        for identifier in identifiers {
+            if let Some(first) = seen.insert(identifier.name, identifier.span) {
+                self.errors.push(ResolveError::Duplicate {
+                    kind,
+                    name: identifier.name,
+                    first,
+                    second: identifier.span,
+                });
+            }
+        }
+    }
+
+    fn define_enum(&mut self, identifier: &Identifier, variants: &[Variant]) -> DefId {
+        if identifier.name == Json::NAME {
+            self.errors
+                .push(ResolveError::BuiltinEnum(identifier.clone()));
+        }
+        self.check_unique(
+            "variant",
+            variants.iter().map(|variant| &variant.identifier),
+        );
+        self.define(identifier, DefKind::Enum, false)
+    }
+
+    fn resolve_function(&mut self, params: &[Param], body: &[Expr], span: Span) {
+        self.enter(span);
+
+        self.check_unique("parameter", params.iter().map(|param| &param.identifier));
+        for param in params {
+            self.define(&param.identifier, DefKind::Param, false);
+        }
+
+        for expr in body {
+            self.resolve_expr(expr);
+        }
+
+        self.leave();
+    }
+
+    fn resolve_value(&mut self, value: &ValueExpr) {
+        match value {
+            ValueExpr::Int { .. }
+            | ValueExpr::Float { .. }
+            | ValueExpr::String { .. }
+            | ValueExpr::Bool { .. } => {}
+
+            ValueExpr::Identifier(identifier) => match self.lookup(identifier.name) {
+                Some(id) => {
+                    self.resolutions.used(identifier, id);
+                }
+                None => self
+                    .errors
+                    .push(ResolveError::UnknownIdentifier(identifier.clone())),
+            },
+
+            ValueExpr::Binary { left, right, .. } => {
+                self.resolve_value(left);
+                self.resolve_value(right);
+            }
+
+            ValueExpr::Unary { operand, .. } => self.resolve_value(operand),
+
+            ValueExpr::Cast { value, .. } => self.resolve_value(value),
+
+            // fields are looked up by the type checker, once the type of the value
+            // is known, except for the members of modules
+            ValueExpr::Field { value, field, .. } => {
+                self.resolve_value(value);
+                if let Some((module, name)) = self.imported(value) {
+                    match self.exports[module].get(&field.name) {
+                        Some(id) => {
+                            self.resolutions.used(field, *id);
+                        }
+                        None => self.errors.push(ResolveError::UnknownMember {
+                            module: name,
+                            member: field.clone(),
+                        }),
+                    }
+                }
+            }
+
+            ValueExpr::Call { callee, args, .. } => {
+                self.resolve_value(callee);
+                for arg in args.iter() {
+                    self.resolve_value(arg);
+                }
+            }
+
+            ValueExpr::Function {
+                params, body, span, ..
+            } => self.resolve_function(params, body, *span),
+
+            ValueExpr::List { items, .. } => {
+                for item in items.iter() {
+                    self.resolve_value(item);
+                }
+            }
+
+            ValueExpr::Map { entries, .. } => {
+                for (key, value) in entries.iter() {
+                    self.resolve_value(key);
+                    self.resolve_value(value);
+                }
+            }
+        }
+    }
+
+    fn resolve_pattern(&mut self, pattern: &Pattern) {
+        match pattern {
+            Pattern::Wildcard(_)
+            | Pattern::Int { .. }
+            | Pattern::String { .. }
+            | Pattern::Bool { .. } => {}
+
+            Pattern::Binding(identifier) => {
+                self.define(identifier, DefKind::Variable, false);
+            }
+
+            Pattern::Variant {
+                enum_name, fields, ..
+            } => {
+                self.resolve_value(&ValueExpr::Identifier(enum_name.clone()));
+                for field in fields.iter() {
+                    self.resolve_pattern(field);
+                }
+            }
+        }
+    }
+
+    fn resolve_block(&mut self, body: &[Expr], span: Span) {
+        self.enter(span);
+        for expr in body {
+            self.resolve_expr(expr);
+        }
+        self.leave();
+    }
+
+    fn resolve_expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Declaration {
+                identifier,
+                mutable,
+                value,
+                ..
+            } => match value.as_deref() {
+                // functions can refer to themselves, other values only see the previous bindings
+                Some(value @ ValueExpr::Function { .. }) => {
+                    self.define(identifier, DefKind::Function, *mutable);
+                    self.resolve_value(value);
+                }
+                Some(value) => {
+                    self.resolve_value(value);
+                    let id = self.define(identifier, DefKind::Variable, *mutable);
+                    self.visible_from(id, value.span().end);
+                }
+                None => {
+                    let id = self.define(identifier, DefKind::Variable, *mutable);
+                    self.resolutions.uninitialized.insert(id);
+                }
+            },
+
+            Expr::Assign { target, value, .. } => {
+                self.resolve_value(target);
+                self.resolve_value(value);
+                if let ValueExpr::Identifier(identifier) = target {
+                    self.resolutions.writes.insert(identifier.id);
+                }
+            }
+
+            Expr::Block { body, span } | Expr::Test { body, span, .. } => {
+                self.resolve_block(body, *span)
+            }
+
+            // the body ends where the else body starts, the spans of the blocks
+            // aren't kept
+            Expr::If {
+                condition,
+                body,
+                else_body,
+                span,
+            } => {
+                self.resolve_value(condition);
+                let start = condition.span().end;
+                let middle = match else_body.and_then(|else_body| else_body.first()) {
+                    Some(first) => first.span().start,
+                    None => span.end,
+                };
+                self.resolve_block(body, Span::new(start, middle));
+                if let Some(else_body) = else_body {
+                    self.resolve_block(else_body, Span::new(middle, span.end));
+                }
+            }
+
+            Expr::While {
+                condition,
+                body,
+                span,
+            } => {
+                self.resolve_value(condition);
+                self.resolve_block(body, Span::new(condition.span().end, span.end));
+            }
+
+            Expr::Enum {
+                identifier,
+                variants,
+                ..
+            } => {
+                self.define_enum(identifier, variants);
+            }
+
+            // the bindings of a pattern are only visible in the body of its arm
+            Expr::Match { value, arms, .. } => {
+                self.resolve_value(value);
+                for arm in arms.iter() {
+                    self.enter(arm.span);
+                    self.resolve_pattern(&arm.pattern);
+                    for expr in arm.body {
+                        self.resolve_expr(expr);
+                    }
+                    self.leave();
+                }
+            }
+
+            // the range is resolved outside of the loop, the binding is only visible in its body
+            Expr::For {
+                binding,
+                start,
+                end,
+                body,
+                span,
+            } => {
+                self.resolve_value(start);
+                if let Some(end) = end {
+                    self.resolve_value(end);
+                }
+
+                let range = end.as_ref().unwrap_or(start).span();
+                self.enter(Span::new(range.end, span.end));
+                let id = self.define(binding, DefKind::Variable, false);
+                self.visible_from(id, range.end);
+                for expr in body.iter() {
+                    self.resolve_expr(expr);
+                }
+                self.leave();
+            }
+
+            // the error is only visible in the handler
+            Expr::Try {
+                body,
+                error,
+                handler,
+                span,
+            } => {
+                self.resolve_block(body, Span::new(span.start, error.span.start));
+
+                self.enter(Span::new(error.span.start, span.end));
+                self.define(error, DefKind::Variable, false);
+                for expr in handler.iter() {
+                    self.resolve_expr(expr);
+                }
+                self.leave();
+            }
+
+            Expr::Break(_) | Expr::Continue(_) => {}
+
+            Expr::Value(value) => self.resolve_value(value),
+
+            Expr::Return { value, .. } => {
+                if let Some(value) = value {
+                    self.resolve_value(value);
+                }
+            }
+        }
+    }
+
+    fn check_attributes(
+        &mut self,
+        attributes: &[Identifier],
+        identifier: &Identifier,
+        value: Option<&ValueExpr>,
+    ) {
+        for attribute in attributes {
+            if !ATTRIBUTES.contains(&attribute.name.as_str()) {
+                self.errors
+                    .push(ResolveError::UnknownAttribute(attribute.clone()));
+                continue;
+            }
+
+            if !matches!(value, Some(ValueExpr::Function { params, .. }) if params.is_empty()) {
+                self.errors.push(ResolveError::NotBenchable {
+                    attribute: attribute.clone(),
+                    declaration: identifier.clone(),
+                });
+            }
+        }
+    }
+
+    // the top level of a file gets a scope of its own, with the modules it uses
+    // in it. Gives back what the file declares there
+    fn resolve_file(&mut self, program: &Program) -> HashMap<Symbol, DefId> {
+        let span = program
+            .uses
+            .iter()
+            .map(|using| using.span)
+            .chain(program.body.iter().map(Expr::span))
+            .reduce(Span::to)
+            .unwrap_or_default();
+        self.enter(span);
+
+        // top level functions can be used before they're declared
+        let mut items = HashMap::new();
+        for using in &program.uses {
+            if let Some(module) = using.module {
+                let id = self.define(using.name(), DefKind::Import(module), false);
+                self.visible_from(id, span.start);
+                items.insert(using.name().name, id);
+            }
+        }
+
+        let mut exports = HashMap::new();
+        for expr in program.body {
+            if let Expr::Declaration {
+                identifier,
+                mutable,
+                value,
+                attributes,
+                ..
+            } = expr
+            {
+                self.check_attributes(attributes, identifier, value.as_deref());
+
+                let kind = Self::kind_of(value.as_deref());
+                let id = self.define(identifier, kind, *mutable);
+                match value {
+                    None => {
+                        self.resolutions.uninitialized.insert(id);
+                    }
+                    Some(_) if kind == DefKind::Function => {
+                        self.visible_from(id, span.start);
+                        items.insert(identifier.name, id);
+                    }
+                    Some(value) => self.visible_from(id, value.span().end),
+                }
+                exports.insert(identifier.name, id);
+            }
+        }
+
+        // top level variables are only visible after they're declared, like in a
+        // block, also in the bodies of the functions before them
+        *self
+            .scopes
+            .last_mut()
+            .expect("there should always be a scope") = items;
+        for expr in program.body {
+            match expr {
+                Expr::Declaration {
+                    identifier, value, ..
+                } => {
+                    if let Some(value) = value {
+                        self.resolve_value(value);
+                    }
+
+                    if let Some(id) = self.resolutions.defined_at(identifier) {
+                        self.scopes
+                            .last_mut()
+                            .expect("there should always be a scope")
+                            .insert(identifier.name, id);
+                    }
+                }
+                Expr::Enum { .. } => {}
+                _ => self.resolve_expr(expr),
+            }
+        }
+
+        self.leave();
+        exports
+    }
+
+    pub fn resolve(mut self, program: &Program) -> Result<Resolutions, Vec<ResolveError>> {
+        // builtins get a scope of their own, so the program can shadow them
+        let everywhere = Span::new(0, usize::MAX);
+        self.enter(everywhere);
+        let builtins = Builtin::ALL.map(|builtin| (builtin.name(), DefKind::Builtin(builtin)));
+        let modules = Module::ALL.map(|module| (module.name(), DefKind::Module(module)));
+        let enums = [(Json::NAME, DefKind::Enum)];
+        for (name, kind) in builtins.into_iter().chain(modules).chain(enums) {
+            let identifier = Identifier {
+                name: name.into(),
+                span: Span::default(),
+                id: NodeId::default(),
+            };
+            self.define(&identifier, kind, false);
+        }
+
+        for name in std::mem::take(&mut self.hosts) {
+            let identifier = Identifier {
+                name: name.into(),
+                span: Span::default(),
+                id: NodeId::default(),
+            };
+            let id = self.define(&identifier, DefKind::Host, false);
+            self.resolutions.hosts.push(id);
+        }
+
+        // types are named the same in every file, so the top level enums of all
+        // of them share a scope, and can be used before they're declared
+        self.enter(everywhere);
+        for expr in program.statements() {
+            if let Expr::Enum {
+                identifier,
+                variants,
+                ..
+            } = expr
+            {
+                let id = self.define_enum(identifier, variants);
+                self.visible_from(id, 0);
+            }
+        }
+
+        // a module only sees the ones before it
+        for module in &program.modules {
+            let exports = self.resolve_file(&module.program);
+            self.exports.push(exports);
+        }
+        self.resolve_file(program);
+
+        self.leave();
+        self.leave();
+
+        if self.errors.is_empty() {
+            Ok(self.resolutions)
+        } else {
+            Err(self.errors)
+        }
+    }
+}
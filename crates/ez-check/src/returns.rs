@@ -0,0 +1,204 @@
+use ez_ast::{BaseType, Expr, Program, ValueExpr};
+use ez_diagnostics::Diagnostic;
+use ez_lexer::Span;
+
+// a function with a return type can reach the end of its body
+#[derive(Debug)]
+pub struct MissingReturn {
+    pub return_type: BaseType,
+    pub span: Span,
+}
+
+impl From<MissingReturn> for Diagnostic {
+    fn from(error: MissingReturn) -> Self {
+        Diagnostic::error("E0303", "function might not return a value").with_label(
+            error.span,
+            format!("expected to return `{}` on every path", error.return_type),
+        )
+    }
+}
+
+// makes sure every non-void function returns on all paths
+#[derive(Default)]
+pub struct ReturnChecker {
+    errors: Vec<MissingReturn>,
+}
+
+impl ReturnChecker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // whether control can never flow past the given statement, loops are never
+    // considered diverging since their condition might be false from the start
+    pub fn diverges(expr: &Expr) -> bool {
+        match expr {
+            Expr::Return { .. } | Expr::Break(_) | Expr::Continue(_) => true,
+            Expr::Block { body, .. } => body.iter().any(Self::diverges),
+            Expr::If {
+                body,
+                else_body: Some(else_body),
+                ..
+            } => body.iter().any(Self::diverges) && else_body.iter().any(Self::diverges),
+            // matches have to be exhaustive, so it's enough that every arm diverges
+            Expr::Match { arms, .. } => {
+                !arms.is_empty() && arms.iter().all(|arm| arm.body.iter().any(Self::diverges))
+            }
+            // the body can stop anywhere, so the handler has to diverge as well
+            Expr::Try { body, handler, .. } => {
+                body.iter().any(Self::diverges) && handler.iter().any(Self::diverges)
+            }
+            _ => false,
+        }
+    }
+
+    // checks the statements of a body, returns whether it diverges
+    fn check_body(&mut self, body: &[Expr]) -> bool {
+        let mut diverged = false;
+        for expr in body {
+            self.check_expr(expr);
+            diverged |= Self::diverges(expr);
+        }
+
+        diverged
+    }
+
+    fn check_value(&mut self, value: &ValueExpr) {
+        match value {
+            ValueExpr::Int { .. }
+            | ValueExpr::Float { .. }
+            | ValueExpr::String { .. }
+            | ValueExpr::Bool { .. }
+            | ValueExpr::Identifier(_) => {}
+
+            ValueExpr::Binary { left, right, .. } => {
+                self.check_value(left);
+                self.check_value(right);
+            }
+
+            ValueExpr::Unary { operand, .. } => self.check_value(operand),
+
+            ValueExpr::Cast { value, .. } => self.check_value(value),
+
+            ValueExpr::Field { value, .. } => self.check_value(value),
+
+            ValueExpr::Call { callee, args, .. } => {
+                self.check_value(callee);
+                for arg in args.iter() {
+                    self.check_value(arg);
+                }
+            }
+
+            ValueExpr::List { items, .. } => {
+                for item in items.iter() {
+                    self.check_value(item);
+                }
+            }
+
+            ValueExpr::Map { entries, .. } => {
+                for (key, value) in entries.iter() {
+                    self.check_value(key);
+                    self.check_value(value);
+                }
+            }
+
+            ValueExpr::Function {
+                return_type,
+                body,
+                span,
+                ..
+            } => {
+                let returns = self.check_body(body);
+                if !returns && *return_type != BaseType::Void {
+                    self.errors.push(MissingReturn {
+                        return_type: return_type.clone(),
+                        span: *span,
+                    });
+                }
+            }
+        }
+    }
+
+    fn check_expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Declaration { value, .. } => {
+                if let Some(value) = value {
+                    self.check_value(value);
+                }
+            }
+
+            Expr::Assign { target, value, .. } => {
+                self.check_value(target);
+                self.check_value(value);
+            }
+
+            Expr::Block { body, .. } | Expr::Test { body, .. } => {
+                self.check_body(body);
+            }
+
+            Expr::If {
+                condition,
+                body,
+                else_body,
+                ..
+            } => {
+                self.check_value(condition);
+                self.check_body(body);
+                if let Some(else_body) = else_body {
+                    self.check_body(else_body);
+                }
+            }
+
+            Expr::While {
+                condition, body, ..
+            } => {
+                self.check_value(condition);
+                self.check_body(body);
+            }
+
+            Expr::For {
+                start, end, body, ..
+            } => {
+                self.check_value(start);
+                if let Some(end) = end {
+                    self.check_value(end);
+                }
+                self.check_body(body);
+            }
+
+            Expr::Match { value, arms, .. } => {
+                self.check_value(value);
+                for arm in arms.iter() {
+                    self.check_body(arm.body);
+                }
+            }
+
+            Expr::Try { body, handler, .. } => {
+                self.check_body(body);
+                self.check_body(handler);
+            }
+
+            Expr::Break(_) | Expr::Continue(_) | Expr::Enum { .. } => {}
+
+            Expr::Value(value) => self.check_value(value),
+
+            Expr::Return { value, .. } => {
+                if let Some(value) = value {
+                    self.check_value(value);
+                }
+            }
+        }
+    }
+
+    pub fn check(mut self, program: &Program) -> Result<(), Vec<MissingReturn>> {
+        for expr in program.statements() {
+            self.check_expr(expr);
+        }
+
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(self.errors)
+        }
+    }
+}
@@ -0,0 +1,824 @@
+use std::collections::HashMap;
+
+use ez_ast::{Expr, Identifier, Param, Pattern, Program, ValueExpr};
+use ez_diagnostics::Diagnostic;
+use ez_lexer::{Span, Symbol, TokenKind};
+
+// the style rules `ez lint` checks on top of what `ez check` does. Each rule is
+// a lint of its own, so it's allowed or denied by its name like the others
+pub trait Rule {
+    fn name(&self) -> &'static str;
+    fn check(&self, program: &Program) -> Vec<Diagnostic>;
+}
+
+// the rules a lint runs, programs embedding ez can add their own
+pub struct Registry {
+    rules: Vec<Box<dyn Rule>>,
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Self::new()
+            .with_rule(NamingRule)
+            .with_rule(ShadowingRule)
+            .with_rule(MagicNumberRule)
+            .with_rule(LongFunctionRule::new(LongFunctionRule::MAX_STATEMENTS))
+    }
+}
+
+impl Registry {
+    // without any rule
+    pub fn new() -> Self {
+        Self { rules: vec![] }
+    }
+
+    pub fn with_rule(mut self, rule: impl Rule + 'static) -> Self {
+        self.rules.push(Box::new(rule));
+        self
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.rules.iter().map(|rule| rule.name())
+    }
+
+    // the diagnostics are tied to the rule that reported them, so their level
+    // is the one of the rule
+    pub fn check(&self, program: &Program) -> Vec<Diagnostic> {
+        self.rules
+            .iter()
+            .flat_map(|rule| {
+                rule.check(program)
+                    .into_iter()
+                    .map(|diagnostic| diagnostic.for_lint(rule.name()))
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug)]
+pub enum RuleWarning {
+    // `kind` is what's named, like `variable`
+    BadName {
+        kind: &'static str,
+        name: Symbol,
+        span: Span,
+        convention: &'static str,
+        suggestion: String,
+    },
+    Shadowing {
+        name: Symbol,
+        span: Span,
+        shadowed: Span,
+    },
+    MagicNumber {
+        text: String,
+        span: Span,
+    },
+    LongFunction {
+        name: Option<Symbol>,
+        span: Span,
+        statements: usize,
+        max: usize,
+    },
+}
+
+impl From<RuleWarning> for Diagnostic {
+    fn from(warning: RuleWarning) -> Self {
+        match warning {
+            RuleWarning::BadName {
+                kind,
+                name,
+                span,
+                convention,
+                suggestion,
+            } => Diagnostic::warning(
+                "W0006",
+                format!("the {} `{}` isn't in {}", kind, name, convention),
+            )
+            .with_label(span, format!("should be {}", convention))
+            .with_help(format!("rename it to `{}`", suggestion)),
+
+            RuleWarning::Shadowing {
+                name,
+                span,
+                shadowed,
+            } => Diagnostic::warning("W0007", format!("`{}` shadows an earlier binding", name))
+                .with_label(span, "declared again here")
+                .with_secondary(shadowed, "first declared here")
+                .with_help("give one of them another name"),
+
+            RuleWarning::MagicNumber { text, span } => {
+                Diagnostic::warning("W0008", format!("magic number `{}`", text))
+                    .with_label(span, "what it stands for isn't said")
+                    .with_help(format!(
+                        "declare it with a name first, like `limit := {};`",
+                        text
+                    ))
+            }
+
+            RuleWarning::LongFunction {
+                name,
+                span,
+                statements,
+                max,
+            } => {
+                let function = match name {
+                    Some(name) => format!("the function `{}`", name),
+                    None => "this function".to_string(),
+                };
+                Diagnostic::warning(
+                    "W0009",
+                    format!("{} has {} statements", function, statements),
+                )
+                .with_label(span, format!("more than {}", max))
+                .with_help("split it into smaller functions")
+            }
+        }
+    }
+}
+
+// variables, parameters and functions are snake_case, enums and their variants
+// UpperCamelCase
+pub struct NamingRule;
+
+fn is_snake_case(name: &str) -> bool {
+    name.chars()
+        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+}
+
+fn is_camel_case(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_uppercase()) && !name.contains('_')
+}
+
+fn to_snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    let mut previous: Option<char> = None;
+    for c in name.chars() {
+        if c.is_ascii_uppercase() {
+            if previous
+                .is_some_and(|previous| previous.is_ascii_lowercase() || previous.is_ascii_digit())
+            {
+                snake.push('_');
+            }
+            snake.push(c.to_ascii_lowercase());
+        } else {
+            snake.push(c);
+        }
+        previous = Some(c);
+    }
+    snake
+}
+
+fn to_camel_case(name: &str) -> String {
+    name.split('_')
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut chars = word.chars();
+            let first = chars.next().unwrap().to_ascii_uppercase();
+            std::iter::once(first).chain(chars).collect::<String>()
+        })
+        .collect()
+}
+
+impl NamingRule {
+    fn snake(kind: &'static str, identifier: &Identifier, warnings: &mut Vec<RuleWarning>) {
+        if !is_snake_case(&identifier.name) {
+            warnings.push(RuleWarning::BadName {
+                kind,
+                name: identifier.name,
+                span: identifier.span,
+                convention: "snake_case",
+                suggestion: to_snake_case(&identifier.name),
+            });
+        }
+    }
+
+    fn camel(kind: &'static str, identifier: &Identifier, warnings: &mut Vec<RuleWarning>) {
+        if !is_camel_case(&identifier.name) {
+            warnings.push(RuleWarning::BadName {
+                kind,
+                name: identifier.name,
+                span: identifier.span,
+                convention: "UpperCamelCase",
+                suggestion: to_camel_case(&identifier.name),
+            });
+        }
+    }
+
+    fn check_pattern(pattern: &Pattern, warnings: &mut Vec<RuleWarning>) {
+        match pattern {
+            Pattern::Binding(identifier) => Self::snake("variable", identifier, warnings),
+            Pattern::Variant { fields, .. } => {
+                for field in fields.iter() {
+                    Self::check_pattern(field, warnings);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn check_body(body: &[Expr], warnings: &mut Vec<RuleWarning>) {
+        for expr in body {
+            Self::check_expr(expr, warnings);
+        }
+    }
+
+    fn check_expr(expr: &Expr, warnings: &mut Vec<RuleWarning>) {
+        match expr {
+            Expr::Declaration {
+                identifier, value, ..
+            } => {
+                let kind = match value.as_deref() {
+                    Some(ValueExpr::Function { .. }) => "function",
+                    _ => "variable",
+                };
+                Self::snake(kind, identifier, warnings);
+                if let Some(value) = value {
+                    Self::check_value(value, warnings);
+                }
+            }
+
+            Expr::Enum {
+                identifier,
+                variants,
+                ..
+            } => {
+                Self::camel("enum", identifier, warnings);
+                for variant in variants.iter() {
+                    Self::camel("variant", &variant.identifier, warnings);
+                }
+            }
+
+            Expr::For { binding, body, .. } => {
+                Self::snake("variable", binding, warnings);
+                Self::check_body(body, warnings);
+            }
+
+            Expr::Try {
+                body,
+                error,
+                handler,
+                ..
+            } => {
+                Self::check_body(body, warnings);
+                Self::snake("variable", error, warnings);
+                Self::check_body(handler, warnings);
+            }
+
+            Expr::Match { value, arms, .. } => {
+                Self::check_value(value, warnings);
+                for arm in arms.iter() {
+                    Self::check_pattern(&arm.pattern, warnings);
+                    Self::check_body(arm.body, warnings);
+                }
+            }
+
+            Expr::If {
+                condition,
+                body,
+                else_body,
+                ..
+            } => {
+                Self::check_value(condition, warnings);
+                Self::check_body(body, warnings);
+                if let Some(else_body) = else_body {
+                    Self::check_body(else_body, warnings);
+                }
+            }
+
+            Expr::While {
+                condition, body, ..
+            } => {
+                Self::check_value(condition, warnings);
+                Self::check_body(body, warnings);
+            }
+
+            Expr::Block { body, .. } | Expr::Test { body, .. } => Self::check_body(body, warnings),
+
+            Expr::Assign { value, .. } | Expr::Value(value) => Self::check_value(value, warnings),
+
+            Expr::Return { value, .. } => {
+                if let Some(value) = value {
+                    Self::check_value(value, warnings);
+                }
+            }
+
+            Expr::Break(_) | Expr::Continue(_) => {}
+        }
+    }
+
+    // only functions declare names among values
+    fn check_value(value: &ValueExpr, warnings: &mut Vec<RuleWarning>) {
+        for_each_function(value, &mut |params, body, _| {
+            for param in params {
+                Self::snake("parameter", &param.identifier, warnings);
+            }
+            Self::check_body(body, warnings);
+        });
+    }
+}
+
+impl Rule for NamingRule {
+    fn name(&self) -> &'static str {
+        "naming_conventions"
+    }
+
+    fn check(&self, program: &Program) -> Vec<Diagnostic> {
+        let mut warnings = vec![];
+        for file in program.files() {
+            Self::check_body(file.body, &mut warnings);
+        }
+
+        warnings.into_iter().map(Diagnostic::from).collect()
+    }
+}
+
+// calls `f` with the parameters, body and span of the functions in a value
+// that aren't inside another function of it
+fn for_each_function<'v>(value: &'v ValueExpr, f: &mut impl FnMut(&'v [Param], &'v [Expr], Span)) {
+    match value {
+        ValueExpr::Function {
+            params, body, span, ..
+        } => f(params, body, *span),
+        ValueExpr::Binary { left, right, .. } => {
+            for_each_function(left, f);
+            for_each_function(right, f);
+        }
+        ValueExpr::Unary { operand: value, .. }
+        | ValueExpr::Cast { value, .. }
+        | ValueExpr::Field { value, .. } => for_each_function(value, f),
+        ValueExpr::Call { callee, args, .. } => {
+            for_each_function(callee, f);
+            for arg in args.iter() {
+                for_each_function(arg, f);
+            }
+        }
+        ValueExpr::List { items, .. } => {
+            for item in items.iter() {
+                for_each_function(item, f);
+            }
+        }
+        ValueExpr::Map { entries, .. } => {
+            for (key, value) in entries.iter() {
+                for_each_function(key, f);
+                for_each_function(value, f);
+            }
+        }
+        ValueExpr::Int { .. }
+        | ValueExpr::Float { .. }
+        | ValueExpr::String { .. }
+        | ValueExpr::Bool { .. }
+        | ValueExpr::Identifier(_) => {}
+    }
+}
+
+// a binding declared again while the first one is in scope, names starting
+// with `_` are skipped
+pub struct ShadowingRule;
+
+struct Shadowing {
+    scopes: Vec<HashMap<Symbol, Span>>,
+    warnings: Vec<RuleWarning>,
+}
+
+impl Shadowing {
+    fn declare(&mut self, identifier: &Identifier) {
+        if identifier.name.starts_with('_') {
+            return;
+        }
+
+        let shadowed = self
+            .scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(&identifier.name));
+        if let Some(shadowed) = shadowed {
+            self.warnings.push(RuleWarning::Shadowing {
+                name: identifier.name,
+                span: identifier.span,
+                shadowed: *shadowed,
+            });
+        }
+
+        self.scopes
+            .last_mut()
+            .expect("there's always a scope")
+            .insert(identifier.name, identifier.span);
+    }
+
+    // `bindings` are declared in the scope of the body, like parameters
+    fn scoped(&mut self, bindings: &[&Identifier], body: &[Expr]) {
+        self.scopes.push(HashMap::new());
+        for binding in bindings {
+            self.declare(binding);
+        }
+        for expr in body {
+            self.check_expr(expr);
+        }
+        self.scopes.pop();
+    }
+
+    fn bindings<'p>(pattern: &'p Pattern, bindings: &mut Vec<&'p Identifier>) {
+        match pattern {
+            Pattern::Binding(identifier) => bindings.push(identifier),
+            Pattern::Variant { fields, .. } => {
+                for field in fields.iter() {
+                    Self::bindings(field, bindings);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn check_value(&mut self, value: &ValueExpr) {
+        let mut functions = vec![];
+        for_each_function(value, &mut |params, body, _| functions.push((params, body)));
+        for (params, body) in functions {
+            let params: Vec<&Identifier> = params.iter().map(|param| &param.identifier).collect();
+            self.scoped(&params, body);
+        }
+    }
+
+    fn check_expr(&mut self, expr: &Expr) {
+        match expr {
+            // the value can refer to the binding being shadowed
+            Expr::Declaration {
+                identifier, value, ..
+            } => {
+                if let Some(value) = value {
+                    self.check_value(value);
+                }
+                self.declare(identifier);
+            }
+
+            Expr::For {
+                binding,
+                start,
+                end,
+                body,
+                ..
+            } => {
+                self.check_value(start);
+                if let Some(end) = end {
+                    self.check_value(end);
+                }
+                self.scoped(&[binding], body);
+            }
+
+            Expr::Try {
+                body,
+                error,
+                handler,
+                ..
+            } => {
+                self.scoped(&[], body);
+                self.scoped(&[error], handler);
+            }
+
+            Expr::Match { value, arms, .. } => {
+                self.check_value(value);
+                for arm in arms.iter() {
+                    let mut bindings = vec![];
+                    Self::bindings(&arm.pattern, &mut bindings);
+                    self.scoped(&bindings, arm.body);
+                }
+            }
+
+            Expr::If {
+                condition,
+                body,
+                else_body,
+                ..
+            } => {
+                self.check_value(condition);
+                self.scoped(&[], body);
+                if let Some(else_body) = else_body {
+                    self.scoped(&[], else_body);
+                }
+            }
+
+            Expr::While {
+                condition, body, ..
+            } => {
+                self.check_value(condition);
+                self.scoped(&[], body);
+            }
+
+            Expr::Block { body, .. } | Expr::Test { body, .. } => self.scoped(&[], body),
+
+            Expr::Assign { target, value, .. } => {
+                self.check_value(target);
+                self.check_value(value);
+            }
+
+            Expr::Value(value) => self.check_value(value),
+
+            Expr::Return { value, .. } => {
+                if let Some(value) = value {
+                    self.check_value(value);
+                }
+            }
+
+            Expr::Enum { .. } | Expr::Break(_) | Expr::Continue(_) => {}
+        }
+    }
+}
+
+impl Rule for ShadowingRule {
+    fn name(&self) -> &'static str {
+        "shadowing"
+    }
+
+    // every file has a scope of its own
+    fn check(&self, program: &Program) -> Vec<Diagnostic> {
+        let mut shadowing = Shadowing {
+            scopes: vec![],
+            warnings: vec![],
+        };
+        for file in program.files() {
+            shadowing.scoped(&[], file.body);
+        }
+
+        shadowing
+            .warnings
+            .into_iter()
+            .map(Diagnostic::from)
+            .collect()
+    }
+}
+
+// numbers other than 0 and 1 used without a name. A number given a name right
+// away is fine, and so are the items of a list or map that is
+pub struct MagicNumberRule;
+
+impl MagicNumberRule {
+    fn check_body(body: &[Expr], warnings: &mut Vec<RuleWarning>) {
+        for expr in body {
+            Self::check_expr(expr, warnings);
+        }
+    }
+
+    fn check_expr(expr: &Expr, warnings: &mut Vec<RuleWarning>) {
+        match expr {
+            Expr::Declaration {
+                value: Some(value), ..
+            } => Self::check_named(value, warnings),
+            Expr::Declaration { value: None, .. } => {}
+
+            Expr::Assign { target, value, .. } => {
+                Self::check_value(target, warnings);
+                Self::check_value(value, warnings);
+            }
+
+            Expr::Return { value, .. } => {
+                if let Some(value) = value {
+                    Self::check_value(value, warnings);
+                }
+            }
+
+            Expr::If {
+                condition,
+                body,
+                else_body,
+                ..
+            } => {
+                Self::check_value(condition, warnings);
+                Self::check_body(body, warnings);
+                if let Some(else_body) = else_body {
+                    Self::check_body(else_body, warnings);
+                }
+            }
+
+            Expr::While {
+                condition, body, ..
+            } => {
+                Self::check_value(condition, warnings);
+                Self::check_body(body, warnings);
+            }
+
+            Expr::For {
+                start, end, body, ..
+            } => {
+                Self::check_value(start, warnings);
+                if let Some(end) = end {
+                    Self::check_value(end, warnings);
+                }
+                Self::check_body(body, warnings);
+            }
+
+            Expr::Match { value, arms, .. } => {
+                Self::check_value(value, warnings);
+                for arm in arms.iter() {
+                    Self::check_body(arm.body, warnings);
+                }
+            }
+
+            Expr::Block { body, .. } => Self::check_body(body, warnings),
+
+            Expr::Try { body, handler, .. } => {
+                Self::check_body(body, warnings);
+                Self::check_body(handler, warnings);
+            }
+
+            Expr::Value(value) => Self::check_value(value, warnings),
+
+            // the values a test expects are fine as they are
+            Expr::Enum { .. } | Expr::Test { .. } | Expr::Break(_) | Expr::Continue(_) => {}
+        }
+    }
+
+    // the value of a declaration, what it's named after
+    fn check_named(value: &ValueExpr, warnings: &mut Vec<RuleWarning>) {
+        match value {
+            ValueExpr::Int { .. } | ValueExpr::Float { .. } => {}
+            ValueExpr::Unary {
+                operand, operator, ..
+            } if *operator == TokenKind::Minus => Self::check_named(operand, warnings),
+            ValueExpr::List { items, .. } => {
+                for item in items.iter() {
+                    Self::check_named(item, warnings);
+                }
+            }
+            ValueExpr::Map { entries, .. } => {
+                for (key, value) in entries.iter() {
+                    Self::check_named(key, warnings);
+                    Self::check_named(value, warnings);
+                }
+            }
+            value => Self::check_value(value, warnings),
+        }
+    }
+
+    fn check_value(value: &ValueExpr, warnings: &mut Vec<RuleWarning>) {
+        match value {
+            ValueExpr::Int { value, span, .. } if !matches!(value, 0 | 1) => {
+                warnings.push(RuleWarning::MagicNumber {
+                    text: value.to_string(),
+                    span: *span,
+                });
+            }
+            ValueExpr::Float { value, span, .. } if *value != 0.0 && *value != 1.0 => {
+                warnings.push(RuleWarning::MagicNumber {
+                    text: value.to_string(),
+                    span: *span,
+                });
+            }
+            ValueExpr::Binary { left, right, .. } => {
+                Self::check_value(left, warnings);
+                Self::check_value(right, warnings);
+            }
+            ValueExpr::Unary { operand: value, .. }
+            | ValueExpr::Cast { value, .. }
+            | ValueExpr::Field { value, .. } => Self::check_value(value, warnings),
+            ValueExpr::Call { callee, args, .. } => {
+                Self::check_value(callee, warnings);
+                for arg in args.iter() {
+                    Self::check_value(arg, warnings);
+                }
+            }
+            ValueExpr::List { items, .. } => {
+                for item in items.iter() {
+                    Self::check_value(item, warnings);
+                }
+            }
+            ValueExpr::Map { entries, .. } => {
+                for (key, value) in entries.iter() {
+                    Self::check_value(key, warnings);
+                    Self::check_value(value, warnings);
+                }
+            }
+            ValueExpr::Function { body, .. } => Self::check_body(body, warnings),
+            _ => {}
+        }
+    }
+}
+
+impl Rule for MagicNumberRule {
+    fn name(&self) -> &'static str {
+        "magic_numbers"
+    }
+
+    fn check(&self, program: &Program) -> Vec<Diagnostic> {
+        let mut warnings = vec![];
+        for file in program.files() {
+            Self::check_body(file.body, &mut warnings);
+        }
+
+        warnings.into_iter().map(Diagnostic::from).collect()
+    }
+}
+
+// functions with more statements than `max`, counting the ones in their
+// blocks but not the ones of the functions declared in them
+pub struct LongFunctionRule {
+    max: usize,
+}
+
+impl LongFunctionRule {
+    pub const MAX_STATEMENTS: usize = 50;
+
+    pub fn new(max: usize) -> Self {
+        Self { max }
+    }
+
+    fn count(body: &[Expr]) -> usize {
+        body.iter()
+            .map(|expr| {
+                1 + match expr {
+                    Expr::If {
+                        body, else_body, ..
+                    } => Self::count(body) + else_body.as_deref().map_or(0, Self::count),
+                    Expr::While { body, .. }
+                    | Expr::For { body, .. }
+                    | Expr::Block { body, .. } => Self::count(body),
+                    Expr::Match { arms, .. } => arms.iter().map(|arm| Self::count(arm.body)).sum(),
+                    Expr::Try { body, handler, .. } => Self::count(body) + Self::count(handler),
+                    _ => 0,
+                }
+            })
+            .sum()
+    }
+
+    // `name` is the one of the declaration the function is the value of
+    fn check_value(
+        &self,
+        value: &ValueExpr,
+        name: Option<&Identifier>,
+        warnings: &mut Vec<RuleWarning>,
+    ) {
+        for_each_function(value, &mut |_, body, span| {
+            let statements = Self::count(body);
+            if statements > self.max {
+                // the whole function would be too much to show, only its name
+                // or `fn` is
+                let span = match name {
+                    Some(name) => name.span,
+                    None => Span::new(span.start, span.start + 2),
+                };
+
+                warnings.push(RuleWarning::LongFunction {
+                    name: name.map(|name| name.name),
+                    span,
+                    statements,
+                    max: self.max,
+                });
+            }
+            self.check_body(body, warnings);
+        });
+    }
+
+    fn check_body(&self, body: &[Expr], warnings: &mut Vec<RuleWarning>) {
+        for expr in body {
+            match expr {
+                Expr::Declaration {
+                    identifier,
+                    value: Some(value),
+                    ..
+                } => self.check_value(value, Some(identifier), warnings),
+                Expr::Assign { value, .. } | Expr::Value(value) => {
+                    self.check_value(value, None, warnings)
+                }
+                Expr::Return {
+                    value: Some(value), ..
+                } => self.check_value(value, None, warnings),
+                Expr::If {
+                    body, else_body, ..
+                } => {
+                    self.check_body(body, warnings);
+                    if let Some(else_body) = else_body {
+                        self.check_body(else_body, warnings);
+                    }
+                }
+                Expr::While { body, .. }
+                | Expr::For { body, .. }
+                | Expr::Block { body, .. }
+                | Expr::Test { body, .. } => self.check_body(body, warnings),
+                Expr::Match { arms, .. } => {
+                    for arm in arms.iter() {
+                        self.check_body(arm.body, warnings);
+                    }
+                }
+                Expr::Try { body, handler, .. } => {
+                    self.check_body(body, warnings);
+                    self.check_body(handler, warnings);
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+impl Rule for LongFunctionRule {
+    fn name(&self) -> &'static str {
+        "long_functions"
+    }
+
+    fn check(&self, program: &Program) -> Vec<Diagnostic> {
+        let mut warnings = vec![];
+        for file in program.files() {
+            self.check_body(file.body, &mut warnings);
+        }
+
+        warnings.into_iter().map(Diagnostic::from).collect()
+    }
+}
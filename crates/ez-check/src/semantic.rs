@@ -0,0 +1,244 @@
+use std::collections::HashMap;
+
+use ez_lexer::{Lexer, Span, Token, TokenKind};
+
+use crate::resolver::{DefKind, Resolutions};
+
+// what a token is for, for editors to highlight it by what it means rather
+// than by how it's spelled. They're the token types of the language server
+// protocol, named as it names them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    Keyword,
+    Function,
+    Parameter,
+    Variable,
+    Type,
+    EnumMember,
+    Namespace,
+    Property,
+    Method,
+    // the name of an attribute, like `bench`
+    Decorator,
+    String,
+    Number,
+    Operator,
+    Comment,
+}
+
+impl Role {
+    // the order they're given to the editor in, a token says its role by index
+    pub const ALL: [Role; 14] = [
+        Role::Keyword,
+        Role::Function,
+        Role::Parameter,
+        Role::Variable,
+        Role::Type,
+        Role::EnumMember,
+        Role::Namespace,
+        Role::Property,
+        Role::Method,
+        Role::Decorator,
+        Role::String,
+        Role::Number,
+        Role::Operator,
+        Role::Comment,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Role::Keyword => "keyword",
+            Role::Function => "function",
+            Role::Parameter => "parameter",
+            Role::Variable => "variable",
+            Role::Type => "type",
+            Role::EnumMember => "enumMember",
+            Role::Namespace => "namespace",
+            Role::Property => "property",
+            Role::Method => "method",
+            Role::Decorator => "decorator",
+            Role::String => "string",
+            Role::Number => "number",
+            Role::Operator => "operator",
+            Role::Comment => "comment",
+        }
+    }
+
+    pub fn index(self) -> usize {
+        Role::ALL
+            .iter()
+            .position(|role| *role == self)
+            .expect("every role is in `ALL`")
+    }
+}
+
+// what more there is to say about a name, a token has a bit for each
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Modifier {
+    // where the name is declared, rather than used
+    Declaration,
+    // a variable that isn't `mut`
+    Readonly,
+    // the builtins and the modules of the language
+    DefaultLibrary,
+}
+
+impl Modifier {
+    pub const ALL: [Modifier; 3] = [
+        Modifier::Declaration,
+        Modifier::Readonly,
+        Modifier::DefaultLibrary,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Modifier::Declaration => "declaration",
+            Modifier::Readonly => "readonly",
+            Modifier::DefaultLibrary => "defaultLibrary",
+        }
+    }
+
+    pub fn bit(self) -> u32 {
+        1 << Modifier::ALL
+            .iter()
+            .position(|modifier| *modifier == self)
+            .expect("every modifier is in `ALL`")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SemanticToken {
+    pub span: Span,
+    pub role: Role,
+    // the bits of its modifiers
+    pub modifiers: u32,
+}
+
+impl SemanticToken {
+    pub fn has(&self, modifier: Modifier) -> bool {
+        self.modifiers & modifier.bit() != 0
+    }
+}
+
+// the names of the types the language has, they aren't declared anywhere
+const TYPES: [&str; 5] = ["void", "int", "float", "string", "bool"];
+
+// the tokens and comments of a file starting at `offset` among the files of a
+// program, in order. The names are classified by what they resolved to when
+// there are resolutions, by where they are when there aren't any or they
+// didn't resolve, like the fields and types. Brackets and delimiters are left
+// to the editor
+pub fn classify(
+    source: &str,
+    offset: usize,
+    resolutions: Option<&Resolutions>,
+) -> Vec<SemanticToken> {
+    let mut names = HashMap::new();
+    let mut enums = vec![];
+    if let Some(resolutions) = resolutions {
+        for (node, span) in &resolutions.spans {
+            if let Some(id) = resolutions.definition_of(*node) {
+                let declared = resolutions.declarations.contains_key(node);
+                names.insert(span.start, (id, declared));
+            }
+        }
+        enums = resolutions
+            .definitions
+            .iter()
+            .filter(|definition| definition.kind == DefKind::Enum)
+            .map(|definition| definition.name)
+            .collect();
+    }
+
+    let tokens: Vec<Token> = Lexer::new(source).with_offset(offset).collect();
+    let mut classified: Vec<SemanticToken> = vec![];
+    for (index, token) in tokens.iter().enumerate() {
+        let previous = |back: usize| index.checked_sub(back).map(|index| &tokens[index]);
+        let next = tokens.get(index + 1);
+        let token_of = |role: Role, modifiers: u32| SemanticToken {
+            span: token.span,
+            role,
+            modifiers,
+        };
+
+        let role = match token.kind {
+            TokenKind::Unknown => continue,
+            TokenKind::Integer | TokenKind::Float => Role::Number,
+            TokenKind::String => Role::String,
+            TokenKind::Identifier => {
+                if let Some((id, declared)) = names.get(&token.span.start) {
+                    let definition = resolutions.expect("names are resolved").definition(*id);
+                    let (role, mut modifiers) = match definition.kind {
+                        DefKind::Variable if definition.mutable => (Role::Variable, 0),
+                        DefKind::Variable => (Role::Variable, Modifier::Readonly.bit()),
+                        DefKind::Function => (Role::Function, 0),
+                        DefKind::Param => (Role::Parameter, Modifier::Readonly.bit()),
+                        DefKind::Enum => (Role::Type, 0),
+                        DefKind::Builtin(_) => (Role::Function, Modifier::DefaultLibrary.bit()),
+                        DefKind::Module(_) => (Role::Namespace, Modifier::DefaultLibrary.bit()),
+                        DefKind::Host => (Role::Function, 0),
+                        DefKind::Import(_) => (Role::Namespace, 0),
+                    };
+                    if *declared {
+                        modifiers |= Modifier::Declaration.bit();
+                    }
+                    classified.push(token_of(role, modifiers));
+                    continue;
+                }
+
+                // the variants of an enum are named through it, like `Color.Red`
+                let after_dot = previous(1).is_some_and(|dot| dot.kind == TokenKind::Dot);
+                let variant = after_dot
+                    && previous(2)
+                        .zip(classified.last())
+                        .is_some_and(|(owner, last)| {
+                            last.span == owner.span && last.role == Role::Type
+                        });
+                match token.value.as_str() {
+                    _ if variant => Role::EnumMember,
+                    _ if after_dot
+                        && next.is_some_and(|next| next.kind == TokenKind::LeftParen) =>
+                    {
+                        Role::Method
+                    }
+                    _ if after_dot => Role::Property,
+                    _ if previous(1).is_some_and(|at| at.kind == TokenKind::At) => Role::Decorator,
+                    // `test` is only a keyword in front of the name of a test
+                    "test" if next.is_some_and(|next| next.kind == TokenKind::String) => {
+                        Role::Keyword
+                    }
+                    name if TYPES.contains(&name) => {
+                        classified.push(token_of(Role::Type, Modifier::DefaultLibrary.bit()));
+                        continue;
+                    }
+                    _ if enums.contains(&token.value.symbol()) => Role::Type,
+                    _ => continue,
+                }
+            }
+            TokenKind::LeftCurly
+            | TokenKind::RightCurly
+            | TokenKind::LeftParen
+            | TokenKind::RightParen
+            | TokenKind::LeftBracket
+            | TokenKind::RightBracket
+            | TokenKind::Dot
+            | TokenKind::DotDot
+            | TokenKind::Comma
+            | TokenKind::Colon
+            | TokenKind::Semi => continue,
+            TokenKind::At => Role::Decorator,
+            ref kind if Lexer::keywords().any(|(_, keyword)| keyword == kind) => Role::Keyword,
+            _ => Role::Operator,
+        };
+        classified.push(token_of(role, 0));
+    }
+
+    let comments = Lexer::new(source).with_offset(offset).comments();
+    classified.extend(comments.into_iter().map(|span| SemanticToken {
+        span,
+        role: Role::Comment,
+        modifiers: 0,
+    }));
+    classified.sort_by_key(|token| token.span.start);
+    classified
+}
@@ -0,0 +1,1813 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use ez_ast::BaseType;
+use ez_lexer::{Span, Symbol, TokenKind};
+
+use crate::cfg::{self, BlockId, Cfg, CfgBuilder, Exit, Instr as CfgInstr};
+use crate::dataflow::{self, Liveness};
+use crate::hir::{Hir, Pattern, Stmt, Value, ValueKind};
+use crate::json::Json;
+use crate::resolver::{Builtin, DefId};
+
+// the program in static single assignment form, between the HIR and the
+// backends, so optimizations are written once for all of them. Every value is
+// defined by a single instruction, and where paths assigning a local
+// differently meet, a phi picks the value of the path that was taken. Locals
+// that nested functions capture stay in memory, they're loaded and stored.
+// It prints as text that `Program::parse` reads back, for tests of the passes
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ValueId(pub usize);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Constant {
+    Int(i64),
+    Float(f64),
+    String(String),
+    Bool(bool),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Op {
+    Const(Constant),
+    // a parameter, a binding of a pattern or the error a handler catches, its
+    // value comes from outside the instructions
+    Bind(DefId),
+    // what a local declared without a value holds until it's assigned
+    Undefined,
+    Binary {
+        operator: TokenKind,
+        left: ValueId,
+        right: ValueId,
+    },
+    Unary {
+        operator: TokenKind,
+        operand: ValueId,
+    },
+    Call {
+        callee: ValueId,
+        args: Vec<ValueId>,
+    },
+    Builtin {
+        builtin: Builtin,
+        args: Vec<ValueId>,
+    },
+    // converts the value to the type of the instruction
+    Cast(ValueId),
+    // a nested function, by its index in the program
+    Function(usize),
+    Variant {
+        enum_name: Symbol,
+        index: usize,
+    },
+    List(Vec<ValueId>),
+    Map(Vec<(ValueId, ValueId)>),
+    // the locals kept in memory: the ones nested functions capture, and the
+    // ones of the functions around
+    Load(DefId),
+    Store(DefId, ValueId),
+    // the value coming from the predecessor the block was entered from
+    Phi(Vec<(BlockId, ValueId)>),
+}
+
+// instructions of type void are only there for what they do, nothing reads
+// their value. The span is of the code the instruction comes from, the default
+// one for the ones the compiler makes up, like phis
+#[derive(Debug, Clone, PartialEq)]
+pub struct Instr {
+    pub id: ValueId,
+    pub op: Op,
+    pub basetype: BaseType,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Terminator {
+    Jump(BlockId),
+    Branch {
+        condition: ValueId,
+        then: BlockId,
+        otherwise: BlockId,
+    },
+    // to the block of the first pattern matching the value, the bindings are
+    // the first instructions of that block
+    Match {
+        value: ValueId,
+        arms: Vec<(Pattern, BlockId)>,
+    },
+    // on to `body`, where the instruction it starts with can fail and go to
+    // `handler` instead
+    Try {
+        body: BlockId,
+        handler: BlockId,
+    },
+    Return(Option<ValueId>),
+}
+
+// the phis of a block come before its other instructions
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block {
+    pub instrs: Vec<Instr>,
+    pub terminator: Terminator,
+}
+
+// the first block is the entry
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    // the program's body is `main`, tests are named after their description
+    // and functions after the local they're declared as
+    pub name: String,
+    pub params: Vec<(DefId, BaseType)>,
+    pub return_type: BaseType,
+    pub blocks: Vec<Block>,
+    // how many values the instructions define
+    pub values: usize,
+}
+
+// the program's body first, then its tests, then every function it declares
+// however deep they're nested
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Program {
+    pub functions: Vec<Function>,
+    // the names of the locals, and the variants of the enums with their
+    // payloads, to print them
+    pub locals: HashMap<DefId, Symbol>,
+    pub enums: HashMap<Symbol, Vec<(Symbol, Vec<BaseType>)>>,
+}
+
+impl Op {
+    // the values the instruction reads
+    pub fn operands(&self) -> Vec<ValueId> {
+        match self {
+            Op::Const(_)
+            | Op::Bind(_)
+            | Op::Undefined
+            | Op::Function(_)
+            | Op::Variant { .. }
+            | Op::Load(_) => vec![],
+            Op::Binary { left, right, .. } => vec![*left, *right],
+            Op::Unary { operand, .. } | Op::Cast(operand) | Op::Store(_, operand) => vec![*operand],
+            Op::Call { callee, args } => {
+                [*callee].into_iter().chain(args.iter().copied()).collect()
+            }
+            Op::Builtin { args, .. } | Op::List(args) => args.clone(),
+            Op::Map(entries) => entries
+                .iter()
+                .flat_map(|(key, value)| [*key, *value])
+                .collect(),
+            Op::Phi(incoming) => incoming.iter().map(|(_, value)| *value).collect(),
+        }
+    }
+
+    pub fn operands_mut(&mut self) -> Vec<&mut ValueId> {
+        match self {
+            Op::Const(_)
+            | Op::Bind(_)
+            | Op::Undefined
+            | Op::Function(_)
+            | Op::Variant { .. }
+            | Op::Load(_) => vec![],
+            Op::Binary { left, right, .. } => vec![left, right],
+            Op::Unary { operand, .. } | Op::Cast(operand) | Op::Store(_, operand) => vec![operand],
+            Op::Call { callee, args } => {
+                let mut operands = vec![callee];
+                operands.extend(args);
+                operands
+            }
+            Op::Builtin { args, .. } | Op::List(args) => args.iter_mut().collect(),
+            Op::Map(entries) => entries
+                .iter_mut()
+                .flat_map(|(key, value)| [key, value])
+                .collect(),
+            Op::Phi(incoming) => incoming.iter_mut().map(|(_, value)| value).collect(),
+        }
+    }
+}
+
+impl Terminator {
+    // the value it goes on
+    pub fn operand(&self) -> Option<ValueId> {
+        match self {
+            Terminator::Branch { condition, .. } => Some(*condition),
+            Terminator::Match { value, .. } | Terminator::Return(Some(value)) => Some(*value),
+            Terminator::Jump(_) | Terminator::Try { .. } | Terminator::Return(None) => None,
+        }
+    }
+
+    pub fn operand_mut(&mut self) -> Option<&mut ValueId> {
+        match self {
+            Terminator::Branch { condition, .. } => Some(condition),
+            Terminator::Match { value, .. } | Terminator::Return(Some(value)) => Some(value),
+            Terminator::Jump(_) | Terminator::Try { .. } | Terminator::Return(None) => None,
+        }
+    }
+}
+
+impl Block {
+    pub fn successors(&self) -> Vec<BlockId> {
+        match &self.terminator {
+            Terminator::Jump(target) => vec![*target],
+            Terminator::Branch {
+                then, otherwise, ..
+            } => vec![*then, *otherwise],
+            Terminator::Match { arms, .. } => arms.iter().map(|(_, block)| *block).collect(),
+            Terminator::Try { body, handler } => vec![*body, *handler],
+            Terminator::Return(_) => vec![],
+        }
+    }
+}
+
+impl Function {
+    pub fn basetype(&self, value: ValueId) -> Option<&BaseType> {
+        self.blocks
+            .iter()
+            .flat_map(|block| &block.instrs)
+            .find(|instr| instr.id == value)
+            .map(|instr| &instr.basetype)
+    }
+
+    pub fn predecessors(&self) -> Vec<Vec<BlockId>> {
+        let mut predecessors = vec![vec![]; self.blocks.len()];
+        for (index, block) in self.blocks.iter().enumerate() {
+            for successor in block.successors() {
+                predecessors[successor.0].push(BlockId(index));
+            }
+        }
+
+        predecessors
+    }
+
+    // numbers the values again in the order of the blocks, values are made out
+    // of order while building and gaps are left when instructions are removed
+    pub fn renumber(&mut self) {
+        let mut numbers = HashMap::new();
+        for instr in self.blocks.iter_mut().flat_map(|block| &mut block.instrs) {
+            let number = ValueId(numbers.len());
+            numbers.insert(instr.id, number);
+            instr.id = number;
+        }
+
+        for block in &mut self.blocks {
+            let operands = block
+                .instrs
+                .iter_mut()
+                .flat_map(|instr| instr.op.operands_mut())
+                .chain(block.terminator.operand_mut());
+            for operand in operands {
+                *operand = numbers[operand];
+            }
+        }
+
+        self.values = numbers.len();
+    }
+}
+
+// a function waiting to be built, the ones nested in a function are found while
+// building it
+#[derive(Clone)]
+struct Pending<'h> {
+    name: String,
+    params: &'h [DefId],
+    body: &'h [Stmt],
+    value: Option<&'h Value>,
+}
+
+pub fn build(hir: &Hir) -> Program {
+    let mut pending = vec![Pending {
+        name: "main".into(),
+        params: &[],
+        body: &hir.body,
+        value: None,
+    }];
+    pending.extend(hir.tests.iter().map(|test| Pending {
+        name: test.name.clone(),
+        params: &[],
+        body: &test.body,
+        value: None,
+    }));
+
+    let mut program = Program {
+        functions: vec![],
+        locals: hir
+            .locals
+            .iter()
+            .enumerate()
+            .map(|(index, local)| (DefId(index), local.name))
+            .collect(),
+        enums: hir
+            .enums
+            .iter()
+            .map(|(name, variants)| {
+                let variants = variants
+                    .iter()
+                    .map(|variant| (variant.identifier.name, variant.payload.clone()));
+                (*name, variants.collect())
+            })
+            .collect(),
+    };
+
+    // the tests run after the program's declarations, what they refer to has
+    // to be in memory like what nested functions capture
+    let mut shared = HashSet::new();
+    for test in &hir.tests {
+        let cfg = CfgBuilder::build(&[], &test.body);
+        for instr in cfg.blocks.iter().flat_map(|block| &block.instrs) {
+            shared.extend(instr.uses());
+            shared.extend(instr.defines());
+        }
+    }
+
+    while program.functions.len() < pending.len() {
+        let Pending {
+            name,
+            params,
+            body,
+            value,
+        } = pending[program.functions.len()].clone();
+
+        let span = value.map(|value| value.span).unwrap_or_default();
+        let sites: Vec<_> = params.iter().map(|param| (*param, span)).collect();
+        let cfg = CfgBuilder::build(&sites, body);
+
+        let return_type = match value.map(|value| &value.basetype) {
+            Some(BaseType::Function { return_type, .. }) => (**return_type).clone(),
+            _ => BaseType::Void,
+        };
+        let params = params
+            .iter()
+            .map(|param| (*param, hir.local(*param).basetype.clone()))
+            .collect();
+
+        if !program.functions.is_empty() {
+            shared.clear();
+        }
+        let mut builder = FunctionBuilder::new(hir, &cfg, &mut pending, &shared);
+        builder.build();
+        let mut function = Function {
+            name,
+            params,
+            return_type,
+            blocks: builder.blocks,
+            values: builder.values,
+        };
+        function.renumber();
+        program.functions.push(function);
+    }
+
+    program
+}
+
+struct FunctionBuilder<'h, 'c, 'p> {
+    hir: &'h Hir,
+    cfg: &'c Cfg<'h>,
+    pending: &'p mut Vec<Pending<'h>>,
+    blocks: Vec<Block>,
+    values: usize,
+    current: BlockId,
+    // the block of the graph each one of it that can be reached starts
+    numbers: Vec<Option<BlockId>>,
+    // the locals that are values, the others are in memory
+    promoted: HashSet<DefId>,
+    // the values of the locals where the blocks being built are, the current
+    // one last
+    definitions: HashMap<DefId, Vec<ValueId>>,
+    undefined: HashMap<DefId, ValueId>,
+    // the phis each block of the graph starts with, for the local they merge,
+    // and the block and position they're at
+    phis: HashMap<BlockId, Vec<(DefId, BlockId, usize)>>,
+    // of the code the next instruction comes from
+    span: Span,
+}
+
+impl<'h, 'c, 'p> FunctionBuilder<'h, 'c, 'p> {
+    fn new(
+        hir: &'h Hir,
+        cfg: &'c Cfg<'h>,
+        pending: &'p mut Vec<Pending<'h>>,
+        shared: &HashSet<DefId>,
+    ) -> Self {
+        // locals a nested function refers to can change whenever it's called
+        let mut captured = shared.clone();
+        for block in &cfg.blocks {
+            for instr in &block.instrs {
+                if let CfgInstr::Assign { value, .. } | CfgInstr::Eval(value) = instr {
+                    captures(value, &mut captured);
+                }
+            }
+        }
+
+        Self {
+            hir,
+            cfg,
+            pending,
+            blocks: vec![],
+            values: 0,
+            current: BlockId(0),
+            numbers: vec![None; cfg.blocks.len()],
+            promoted: cfg.locals.difference(&captured).copied().collect(),
+            definitions: HashMap::new(),
+            undefined: HashMap::new(),
+            phis: HashMap::new(),
+            span: Span::default(),
+        }
+    }
+
+    fn new_block(&mut self) -> BlockId {
+        self.blocks.push(Block {
+            instrs: vec![],
+            terminator: Terminator::Return(None),
+        });
+        BlockId(self.blocks.len() - 1)
+    }
+
+    fn emit(&mut self, op: Op, basetype: BaseType) -> ValueId {
+        let id = ValueId(self.values);
+        self.values += 1;
+        self.blocks[self.current.0].instrs.push(Instr {
+            id,
+            op,
+            basetype,
+            span: self.span,
+        });
+        id
+    }
+
+    // the value a local has where the current block is, a local that hasn't
+    // been assigned on the way there is undefined
+    fn read(&mut self, local: DefId) -> ValueId {
+        match self
+            .definitions
+            .get(&local)
+            .and_then(|values| values.last())
+        {
+            Some(value) => *value,
+            None => self.undefined(local),
+        }
+    }
+
+    fn undefined(&mut self, local: DefId) -> ValueId {
+        if let Some(value) = self.undefined.get(&local) {
+            return *value;
+        }
+
+        let id = ValueId(self.values);
+        self.values += 1;
+        let basetype = self.hir.local(local).basetype.clone();
+        // the entry has no phis to come after
+        self.blocks[0].instrs.insert(
+            0,
+            Instr {
+                id,
+                op: Op::Undefined,
+                basetype,
+                span: Span::default(),
+            },
+        );
+        self.undefined.insert(local, id);
+        id
+    }
+
+    fn define(&mut self, local: DefId, value: ValueId, defined: &mut Vec<DefId>) {
+        self.definitions.entry(local).or_default().push(value);
+        defined.push(local);
+    }
+
+    fn build(&mut self) {
+        let order = self.cfg.reverse_postorder();
+        for block in &order {
+            self.numbers[block.0] = Some(self.new_block());
+        }
+
+        let dominators = self.cfg.dominators();
+        self.insert_phis(&order, &dominators);
+        let mut children = vec![vec![]; self.cfg.blocks.len()];
+        for block in &order {
+            if let Some(dominator) = dominators[block.0] {
+                children[dominator.0].push(*block);
+            }
+        }
+
+        // the blocks are renamed going down the tree of dominators, the values
+        // a block defines are only seen by the blocks it dominates
+        enum Visit {
+            Enter(BlockId),
+            Leave(Vec<DefId>),
+        }
+
+        let mut visits = vec![Visit::Enter(BlockId(0))];
+        while let Some(visit) = visits.pop() {
+            match visit {
+                Visit::Enter(block) => {
+                    let defined = self.rename(block);
+                    visits.push(Visit::Leave(defined));
+                    visits.extend(
+                        children[block.0]
+                            .iter()
+                            .rev()
+                            .map(|child| Visit::Enter(*child)),
+                    );
+                }
+                Visit::Leave(defined) => {
+                    for local in defined {
+                        self.definitions.entry(local).or_default().pop();
+                    }
+                }
+            }
+        }
+    }
+
+    // a phi goes where paths assigning a local differently meet, the frontiers
+    // of the blocks assigning it, but only where the local is still read
+    fn insert_phis(&mut self, order: &[BlockId], dominators: &[Option<BlockId>]) {
+        let predecessors = self.cfg.predecessors();
+        let mut frontiers: Vec<HashSet<BlockId>> = vec![HashSet::new(); self.cfg.blocks.len()];
+        for block in order {
+            let reachable: Vec<_> = predecessors[block.0]
+                .iter()
+                .filter(|predecessor| self.numbers[predecessor.0].is_some())
+                .collect();
+            if reachable.len() < 2 {
+                continue;
+            }
+
+            for predecessor in reachable {
+                let mut runner = *predecessor;
+                while Some(runner) != dominators[block.0] {
+                    frontiers[runner.0].insert(*block);
+                    let Some(dominator) = dominators[runner.0] else {
+                        break;
+                    };
+                    runner = dominator;
+                }
+            }
+        }
+
+        let mut sites: HashMap<DefId, Vec<BlockId>> = HashMap::new();
+        for block in order {
+            for instr in &self.cfg.blocks[block.0].instrs {
+                if let Some(local) = instr
+                    .defines()
+                    .filter(|local| self.promoted.contains(local))
+                {
+                    sites.entry(local).or_default().push(*block);
+                }
+            }
+        }
+
+        let live = dataflow::solve(&Liveness, self.cfg).entry;
+        let mut sites: Vec<_> = sites.into_iter().collect();
+        sites.sort_by_key(|(local, _)| local.0);
+
+        for (local, mut worklist) in sites {
+            let mut placed = HashSet::new();
+            let mut visited: HashSet<BlockId> = worklist.iter().copied().collect();
+            while let Some(block) = worklist.pop() {
+                let mut frontier: Vec<_> = frontiers[block.0].iter().copied().collect();
+                frontier.sort_by_key(|block| block.0);
+                for target in frontier {
+                    if !live[target.0].contains(&local) || !placed.insert(target) {
+                        continue;
+                    }
+
+                    let number = self.numbers[target.0].expect("frontiers can be reached");
+                    self.current = number;
+                    let position = self.blocks[number.0].instrs.len();
+                    let basetype = self.hir.local(local).basetype.clone();
+                    self.emit(Op::Phi(vec![]), basetype);
+                    self.phis
+                        .entry(target)
+                        .or_default()
+                        .push((local, number, position));
+
+                    if visited.insert(target) {
+                        worklist.push(target);
+                    }
+                }
+            }
+        }
+    }
+
+    // builds the instructions of a block of the graph, and gives the phis of
+    // its successors the values the locals have at its end. Returns the locals
+    // it defined
+    fn rename(&mut self, block: BlockId) -> Vec<DefId> {
+        let cfg = self.cfg;
+        self.current = self.numbers[block.0].expect("only blocks that can be reached are renamed");
+        let mut defined = vec![];
+
+        for (local, number, position) in self.phis.get(&block).cloned().unwrap_or_default() {
+            let id = self.blocks[number.0].instrs[position].id;
+            self.define(local, id, &mut defined);
+        }
+
+        let mut last = None;
+        for instr in &cfg.blocks[block.0].instrs {
+            match *instr {
+                CfgInstr::Assign { local, value, span } => {
+                    let name = self.hir.local(local).name;
+                    let value = match &value.kind {
+                        ValueKind::Function { params, body } => {
+                            self.function(name.to_string(), params, body, value)
+                        }
+                        _ => self.value(value),
+                    };
+                    self.span = span;
+                    self.assign(local, value, &mut defined);
+                }
+                CfgInstr::Bind { local, span } => {
+                    self.span = span;
+                    let basetype = self.hir.local(local).basetype.clone();
+                    let value = self.emit(Op::Bind(local), basetype);
+                    self.assign(local, value, &mut defined);
+                }
+                CfgInstr::Declare { local, .. } => {
+                    if self.promoted.contains(&local) {
+                        let value = self.undefined(local);
+                        self.define(local, value, &mut defined);
+                    }
+                }
+                CfgInstr::Eval(value) => last = Some(self.value(value)),
+            }
+        }
+
+        let successors = &cfg.blocks[block.0].successors;
+        let number = |block: &BlockId| self.numbers[block.0].expect("successors can be reached");
+        let decided = || last.expect("the block ends with the value it goes on");
+        let terminator = match (&cfg.blocks[block.0].exit, successors.as_slice()) {
+            (Exit::Branch, [then, otherwise]) => Terminator::Branch {
+                condition: decided(),
+                then: number(then),
+                otherwise: number(otherwise),
+            },
+            (Exit::Match(arms), _) => Terminator::Match {
+                value: decided(),
+                arms: arms
+                    .iter()
+                    .zip(successors)
+                    .map(|(arm, successor)| (arm.pattern.clone(), number(successor)))
+                    .collect(),
+            },
+            (Exit::Try, [handler, body]) => Terminator::Try {
+                body: number(body),
+                handler: number(handler),
+            },
+            (Exit::Return(true), _) => Terminator::Return(Some(decided())),
+            (_, [target]) => Terminator::Jump(number(target)),
+            _ => Terminator::Return(None),
+        };
+        let exit = self.current;
+        self.blocks[exit.0].terminator = terminator;
+
+        for successor in successors {
+            for (local, number, position) in self.phis.get(successor).cloned().unwrap_or_default() {
+                let value = self.read(local);
+                if let Op::Phi(incoming) = &mut self.blocks[number.0].instrs[position].op {
+                    incoming.push((exit, value));
+                }
+            }
+        }
+
+        defined
+    }
+
+    fn assign(&mut self, local: DefId, value: ValueId, defined: &mut Vec<DefId>) {
+        if self.promoted.contains(&local) {
+            self.define(local, value, defined);
+        } else {
+            self.emit(Op::Store(local, value), BaseType::Void);
+        }
+    }
+
+    // a nested function is built after the one it's in
+    fn function(
+        &mut self,
+        name: String,
+        params: &'h [DefId],
+        body: &'h [Stmt],
+        value: &'h Value,
+    ) -> ValueId {
+        let index = self.pending.len();
+        self.pending.push(Pending {
+            name,
+            params,
+            body,
+            value: Some(value),
+        });
+        self.span = value.span;
+        self.emit(Op::Function(index), value.basetype.clone())
+    }
+
+    fn values(&mut self, values: &'h [Value]) -> Vec<ValueId> {
+        values.iter().map(|value| self.value(value)).collect()
+    }
+
+    fn value(&mut self, value: &'h Value) -> ValueId {
+        let basetype = value.basetype.clone();
+        let op = match &value.kind {
+            ValueKind::Int(constant) => Op::Const(Constant::Int(*constant)),
+            ValueKind::Float(constant) => Op::Const(Constant::Float(*constant)),
+            ValueKind::String(constant) => Op::Const(Constant::String(constant.clone())),
+            ValueKind::Bool(constant) => Op::Const(Constant::Bool(*constant)),
+
+            ValueKind::Local(local) if self.promoted.contains(local) => return self.read(*local),
+            ValueKind::Local(local) => Op::Load(*local),
+
+            // the right side is only evaluated when the left one doesn't decide
+            ValueKind::Binary {
+                operator: operator @ (TokenKind::And | TokenKind::Or),
+                left,
+                right,
+            } => {
+                let left = self.value(left);
+                let start = self.current;
+                let rest = self.new_block();
+                let join = self.new_block();
+                let (then, otherwise) = if *operator == TokenKind::And {
+                    (rest, join)
+                } else {
+                    (join, rest)
+                };
+                self.blocks[start.0].terminator = Terminator::Branch {
+                    condition: left,
+                    then,
+                    otherwise,
+                };
+
+                self.current = rest;
+                let right = self.value(right);
+                let end = self.current;
+                self.blocks[end.0].terminator = Terminator::Jump(join);
+
+                self.current = join;
+                Op::Phi(vec![(start, left), (end, right)])
+            }
+
+            ValueKind::Binary {
+                operator,
+                left,
+                right,
+            } => Op::Binary {
+                operator: operator.clone(),
+                left: self.value(left),
+                right: self.value(right),
+            },
+
+            ValueKind::Unary { operator, operand } => Op::Unary {
+                operator: operator.clone(),
+                operand: self.value(operand),
+            },
+
+            ValueKind::Call { callee, args } => Op::Call {
+                callee: self.value(callee),
+                args: self.values(args),
+            },
+
+            ValueKind::Builtin { builtin, args } => Op::Builtin {
+                builtin: *builtin,
+                args: self.values(args),
+            },
+
+            ValueKind::Cast { value } => Op::Cast(self.value(value)),
+
+            ValueKind::Function { params, body } => {
+                return self.function("fn".into(), params, body, value)
+            }
+
+            ValueKind::Variant { enum_name, index } => Op::Variant {
+                enum_name: *enum_name,
+                index: *index,
+            },
+
+            ValueKind::List(items) => Op::List(self.values(items)),
+
+            ValueKind::Map(entries) => Op::Map(
+                entries
+                    .iter()
+                    .map(|(key, value)| (self.value(key), self.value(value)))
+                    .collect(),
+            ),
+        };
+
+        self.span = value.span;
+        self.emit(op, basetype)
+    }
+}
+
+// the locals the functions nested in a value refer to
+fn captures(value: &Value, locals: &mut HashSet<DefId>) {
+    match &value.kind {
+        ValueKind::Function { .. } => cfg::uses(value, locals),
+        ValueKind::Binary { left, right, .. } => {
+            captures(left, locals);
+            captures(right, locals);
+        }
+        ValueKind::Unary { operand, .. } | ValueKind::Cast { value: operand } => {
+            captures(operand, locals)
+        }
+        ValueKind::Call { callee, args } => {
+            captures(callee, locals);
+            for arg in args {
+                captures(arg, locals);
+            }
+        }
+        ValueKind::Builtin { args, .. } | ValueKind::List(args) => {
+            for arg in args {
+                captures(arg, locals);
+            }
+        }
+        ValueKind::Map(entries) => {
+            for (key, value) in entries {
+                captures(key, locals);
+                captures(value, locals);
+            }
+        }
+        _ => {}
+    }
+}
+
+impl Program {
+    // locals are printed with their id, since names can be shadowed
+    fn local_name(&self, id: DefId) -> String {
+        match self.locals.get(&id) {
+            Some(name) => format!("{}#{}", name, id.0),
+            None => format!("#{}", id.0),
+        }
+    }
+
+    fn write_pattern(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        pattern: &Pattern,
+        basetype: Option<&BaseType>,
+    ) -> fmt::Result {
+        match pattern {
+            Pattern::Wildcard => write!(f, "_"),
+            Pattern::Binding(id) => write!(f, "{}", self.local_name(*id)),
+            Pattern::Int(value) => write!(f, "{}", value),
+            Pattern::String(value) => write!(f, "{:?}", value),
+            Pattern::Bool(value) => write!(f, "{}", value),
+            Pattern::Variant { index, fields } => {
+                let Some(BaseType::Named(enum_name)) = basetype else {
+                    return write!(f, "_");
+                };
+                let Some((name, payload)) = self
+                    .enums
+                    .get(enum_name)
+                    .and_then(|variants| variants.get(*index))
+                else {
+                    return write!(f, "_");
+                };
+
+                write!(f, "{}.{}", enum_name, name)?;
+                if fields.is_empty() {
+                    return Ok(());
+                }
+
+                write!(f, "(")?;
+                for (index, field) in fields.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    self.write_pattern(f, field, payload.get(index))?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+
+    fn write_instr(&self, f: &mut fmt::Formatter<'_>, instr: &Instr) -> fmt::Result {
+        let list = |values: &[ValueId]| {
+            let values: Vec<String> = values.iter().map(|value| format!("%{}", value.0)).collect();
+            values.join(", ")
+        };
+
+        write!(f, "    ")?;
+        if instr.basetype != BaseType::Void {
+            write!(f, "%{}: {} = ", instr.id.0, instr.basetype)?;
+        }
+
+        match &instr.op {
+            Op::Const(Constant::Int(value)) => write!(f, "{}", value)?,
+            Op::Const(Constant::Float(value)) => write!(f, "{:?}", value)?,
+            Op::Const(Constant::String(value)) => write!(f, "{:?}", value)?,
+            Op::Const(Constant::Bool(value)) => write!(f, "{}", value)?,
+            Op::Bind(local) => write!(f, "bind {}", self.local_name(*local))?,
+            Op::Undefined => write!(f, "undefined")?,
+            Op::Binary {
+                operator,
+                left,
+                right,
+            } => write!(
+                f,
+                "%{} {} %{}",
+                left.0,
+                operator.to_string().trim_matches('`'),
+                right.0
+            )?,
+            Op::Unary { operator, operand } => write!(
+                f,
+                "{}%{}",
+                operator.to_string().trim_matches('`'),
+                operand.0
+            )?,
+            Op::Call { callee, args } => write!(f, "call %{}({})", callee.0, list(args))?,
+            Op::Builtin { builtin, args } => write!(f, "{}({})", builtin, list(args))?,
+            Op::Cast(value) => write!(f, "%{} as {}", value.0, instr.basetype)?,
+            Op::Function(index) => write!(f, "fn @{}", index)?,
+            Op::Variant { enum_name, index } => {
+                match self
+                    .enums
+                    .get(enum_name)
+                    .and_then(|variants| variants.get(*index))
+                {
+                    Some((variant, _)) => write!(f, "{}.{}", enum_name, variant)?,
+                    None => write!(f, "{}.#{}", enum_name, index)?,
+                }
+            }
+            Op::List(items) => write!(f, "[{}]", list(items))?,
+            Op::Map(entries) if entries.is_empty() => write!(f, "[:]")?,
+            Op::Map(entries) => {
+                let entries: Vec<String> = entries
+                    .iter()
+                    .map(|(key, value)| format!("%{}: %{}", key.0, value.0))
+                    .collect();
+                write!(f, "[{}]", entries.join(", "))?
+            }
+            Op::Load(local) => write!(f, "load {}", self.local_name(*local))?,
+            Op::Store(local, value) => {
+                write!(f, "store {}, %{}", self.local_name(*local), value.0)?
+            }
+            Op::Phi(incoming) => {
+                let incoming: Vec<String> = incoming
+                    .iter()
+                    .map(|(block, value)| format!("bb{}: %{}", block.0, value.0))
+                    .collect();
+                write!(f, "phi [{}]", incoming.join(", "))?
+            }
+        }
+
+        writeln!(f)
+    }
+
+    fn write_terminator(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        function: &Function,
+        terminator: &Terminator,
+    ) -> fmt::Result {
+        write!(f, "    ")?;
+        match terminator {
+            Terminator::Jump(target) => write!(f, "jump bb{}", target.0)?,
+            Terminator::Branch {
+                condition,
+                then,
+                otherwise,
+            } => write!(
+                f,
+                "branch %{}, bb{}, bb{}",
+                condition.0, then.0, otherwise.0
+            )?,
+            Terminator::Match { value, arms } => {
+                let basetype = function.basetype(*value);
+                write!(f, "match %{} [", value.0)?;
+                for (index, (pattern, block)) in arms.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    self.write_pattern(f, pattern, basetype)?;
+                    write!(f, " => bb{}", block.0)?;
+                }
+                write!(f, "]")?;
+            }
+            Terminator::Try { body, handler } => {
+                write!(f, "try bb{}, catch bb{}", body.0, handler.0)?
+            }
+            Terminator::Return(Some(value)) => write!(f, "return %{}", value.0)?,
+            Terminator::Return(None) => write!(f, "return")?,
+        }
+
+        writeln!(f)
+    }
+}
+
+// the enums first, but `Json` which every program has, then the functions.
+// `Program::parse` reads it back
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut enums: Vec<_> = self
+            .enums
+            .iter()
+            .filter(|(name, _)| name.as_str() != Json::NAME)
+            .collect();
+        enums.sort_by_key(|(name, _)| name.as_str());
+        for (name, variants) in &enums {
+            let variants: Vec<String> = variants
+                .iter()
+                .map(|(variant, payload)| {
+                    if payload.is_empty() {
+                        return variant.to_string();
+                    }
+                    let payload: Vec<String> = payload.iter().map(BaseType::to_string).collect();
+                    format!("{}({})", variant, payload.join(", "))
+                })
+                .collect();
+            writeln!(f, "enum {} {{ {} }}", name, variants.join(", "))?;
+        }
+
+        for (index, function) in self.functions.iter().enumerate() {
+            if index > 0 || !enums.is_empty() {
+                writeln!(f)?;
+            }
+
+            let params: Vec<String> = function
+                .params
+                .iter()
+                .map(|(local, basetype)| format!("{}: {}", self.local_name(*local), basetype))
+                .collect();
+            writeln!(
+                f,
+                "fn @{} {:?}({}) {} {{",
+                index,
+                function.name,
+                params.join(", "),
+                function.return_type
+            )?;
+
+            for (index, block) in function.blocks.iter().enumerate() {
+                writeln!(f, "bb{}:", index)?;
+                for instr in &block.instrs {
+                    self.write_instr(f, instr)?;
+                }
+                self.write_terminator(f, function, &block.terminator)?;
+            }
+
+            writeln!(f, "}}")?;
+        }
+
+        Ok(())
+    }
+}
+
+// the operators the instructions print, by their text
+const BINARY_OPERATORS: [TokenKind; 13] = [
+    TokenKind::Plus,
+    TokenKind::Minus,
+    TokenKind::Times,
+    TokenKind::DividedBy,
+    TokenKind::Equals,
+    TokenKind::NotEquals,
+    TokenKind::GreaterThan,
+    TokenKind::GreaterOrEquals,
+    TokenKind::LowerThan,
+    TokenKind::LowerOrEquals,
+    TokenKind::BitAnd,
+    TokenKind::BitOr,
+    TokenKind::BitXor,
+];
+const UNARY_OPERATORS: [TokenKind; 3] = [TokenKind::Minus, TokenKind::Not, TokenKind::BitNot];
+
+impl Program {
+    // reads a program in the form it prints in, so passes can be tested on
+    // programs written by hand. What follows a `;` on a line is a comment. The
+    // error says what was expected, and on which line
+    pub fn parse(text: &str) -> Result<Program, String> {
+        let mut parser = SsaParser {
+            chars: text.chars().collect(),
+            position: 0,
+            program: Program {
+                functions: vec![],
+                locals: HashMap::new(),
+                enums: HashMap::from([(
+                    Symbol::intern(Json::NAME),
+                    Json::variants()
+                        .into_iter()
+                        .map(|variant| (variant.identifier.name, variant.payload))
+                        .collect(),
+                )]),
+            },
+        };
+
+        loop {
+            parser.skip_lines();
+            if parser.peek().is_none() {
+                break;
+            }
+            match parser.word().as_deref() {
+                Some("enum") => parser.enum_declaration()?,
+                Some("fn") => parser.function()?,
+                _ => return Err(parser.error("`enum` or `fn`")),
+            }
+        }
+
+        let count = parser.program.functions.len();
+        for function in &parser.program.functions {
+            check_references(function, count)?;
+        }
+        Ok(parser.program)
+    }
+}
+
+// what the text refers to has to be in it: the values, blocks and functions
+fn check_references(function: &Function, functions: usize) -> Result<(), String> {
+    let defined: HashSet<ValueId> = function
+        .blocks
+        .iter()
+        .flat_map(|block| &block.instrs)
+        .map(|instr| instr.id)
+        .collect();
+
+    for block in &function.blocks {
+        let operands = block
+            .instrs
+            .iter()
+            .flat_map(|instr| instr.op.operands())
+            .chain(block.terminator.operand());
+        for operand in operands {
+            if !defined.contains(&operand) {
+                return Err(format!(
+                    "%{} isn't defined in `{}`",
+                    operand.0, function.name
+                ));
+            }
+        }
+
+        let phis = block.instrs.iter().filter_map(|instr| match &instr.op {
+            Op::Phi(incoming) => Some(incoming.iter().map(|(block, _)| *block)),
+            _ => None,
+        });
+        for target in block.successors().into_iter().chain(phis.flatten()) {
+            if target.0 >= function.blocks.len() {
+                return Err(format!("there's no bb{} in `{}`", target.0, function.name));
+            }
+        }
+
+        for instr in &block.instrs {
+            if let Op::Function(index) = instr.op {
+                if index >= functions {
+                    return Err(format!("there's no function @{}", index));
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+struct SsaParser {
+    chars: Vec<char>,
+    position: usize,
+    program: Program,
+}
+
+impl SsaParser {
+    fn error(&self, expected: &str) -> String {
+        let line = self.chars[..self.position]
+            .iter()
+            .filter(|c| **c == '\n')
+            .count()
+            + 1;
+        match self.peek() {
+            Some('\n') => format!("expected {}, found the end of line {}", expected, line),
+            Some(found) => format!("expected {}, found `{}` on line {}", expected, found, line),
+            None => format!("expected {}, found the end of the text", expected),
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.position + offset).copied()
+    }
+
+    // the spaces and the comment up to the end of the line
+    fn skip_space(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t' | '\r')) {
+            self.position += 1;
+        }
+        if self.peek() == Some(';') {
+            while !matches!(self.peek(), Some('\n') | None) {
+                self.position += 1;
+            }
+        }
+    }
+
+    fn skip_lines(&mut self) {
+        loop {
+            self.skip_space();
+            if self.peek() != Some('\n') {
+                break;
+            }
+            self.position += 1;
+        }
+    }
+
+    fn end_line(&mut self) -> Result<(), String> {
+        self.skip_space();
+        match self.peek() {
+            Some('\n') => {
+                self.position += 1;
+                Ok(())
+            }
+            None => Ok(()),
+            Some(_) => Err(self.error("the end of the line")),
+        }
+    }
+
+    fn eat(&mut self, text: &str) -> bool {
+        self.skip_space();
+        let end = self.position + text.chars().count();
+        if end <= self.chars.len()
+            && self.chars[self.position..end]
+                .iter()
+                .copied()
+                .eq(text.chars())
+        {
+            self.position = end;
+            return true;
+        }
+        false
+    }
+
+    fn expect(&mut self, text: &str) -> Result<(), String> {
+        if self.eat(text) {
+            return Ok(());
+        }
+        Err(self.error(&format!("`{}`", text)))
+    }
+
+    fn is_word(c: char) -> bool {
+        c.is_alphanumeric() || c == '_'
+    }
+
+    fn word(&mut self) -> Option<String> {
+        self.skip_space();
+        let start = self.position;
+        while self.peek().is_some_and(Self::is_word) {
+            self.position += 1;
+        }
+        (self.position > start).then(|| self.chars[start..self.position].iter().collect())
+    }
+
+    // the word that follows, without reading it
+    fn peek_word(&mut self) -> Option<String> {
+        let position = self.position;
+        let word = self.word();
+        self.position = position;
+        word
+    }
+
+    fn expect_word(&mut self) -> Result<String, String> {
+        self.word().ok_or_else(|| self.error("a name"))
+    }
+
+    fn number(&mut self) -> Result<usize, String> {
+        self.skip_space();
+        let start = self.position;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.position += 1;
+        }
+        let digits: String = self.chars[start..self.position].iter().collect();
+        digits.parse().map_err(|_| {
+            self.position = start;
+            self.error("a number")
+        })
+    }
+
+    fn value(&mut self) -> Result<ValueId, String> {
+        self.expect("%")?;
+        Ok(ValueId(self.number()?))
+    }
+
+    fn values(&mut self, close: &str) -> Result<Vec<ValueId>, String> {
+        let mut values = vec![];
+        while !self.eat(close) {
+            if !values.is_empty() {
+                self.expect(",")?;
+            }
+            values.push(self.value()?);
+        }
+        Ok(values)
+    }
+
+    fn block(&mut self) -> Result<BlockId, String> {
+        self.expect("bb")?;
+        Ok(BlockId(self.number()?))
+    }
+
+    // `name#3`, or `#3` for a local without a name
+    fn local(&mut self) -> Result<DefId, String> {
+        let name = self.word();
+        self.expect("#")?;
+        let id = DefId(self.number()?);
+        if let Some(name) = name {
+            self.program.locals.insert(id, Symbol::intern(&name));
+        }
+        Ok(id)
+    }
+
+    // like Rust prints them, which is how they're printed
+    fn string(&mut self) -> Result<String, String> {
+        self.expect("\"")?;
+        let mut value = String::new();
+        loop {
+            let Some(c) = self.peek() else {
+                return Err(self.error("`\"`"));
+            };
+            self.position += 1;
+
+            match c {
+                '"' => return Ok(value),
+                '\\' => {
+                    let escaped = match self.peek() {
+                        Some('"') => '"',
+                        Some('\'') => '\'',
+                        Some('\\') => '\\',
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('0') => '\0',
+                        Some('u') => {
+                            self.position += 1;
+                            self.expect("{")?;
+                            let start = self.position;
+                            while self.peek().is_some_and(|c| c.is_ascii_hexdigit()) {
+                                self.position += 1;
+                            }
+                            let digits: String = self.chars[start..self.position].iter().collect();
+                            let escaped = u32::from_str_radix(&digits, 16)
+                                .ok()
+                                .and_then(char::from_u32)
+                                .ok_or_else(|| self.error("a unicode escape"))?;
+                            self.expect("}")?;
+                            value.push(escaped);
+                            continue;
+                        }
+                        _ => return Err(self.error("an escape")),
+                    };
+                    self.position += 1;
+                    value.push(escaped);
+                }
+                c => value.push(c),
+            }
+        }
+    }
+
+    // an int, or a float when it has a point or an exponent
+    fn number_constant(&mut self) -> Result<Constant, String> {
+        self.skip_space();
+        let start = self.position;
+        if self.peek() == Some('-') {
+            self.position += 1;
+        }
+        if self.eat("inf") {
+            let infinity = if start == self.position - 3 {
+                f64::INFINITY
+            } else {
+                f64::NEG_INFINITY
+            };
+            return Ok(Constant::Float(infinity));
+        }
+        while self
+            .peek()
+            .is_some_and(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '+'))
+        {
+            self.position += 1;
+        }
+
+        let text: String = self.chars[start..self.position].iter().collect();
+        let constant = if text.contains(['.', 'e', 'E']) {
+            text.parse().ok().map(Constant::Float)
+        } else {
+            text.parse().ok().map(Constant::Int)
+        };
+        constant.ok_or_else(|| {
+            self.position = start;
+            self.error("a number")
+        })
+    }
+
+    fn basetype(&mut self) -> Result<BaseType, String> {
+        if self.eat("[") {
+            let item = self.basetype()?;
+            if self.eat(":") {
+                let value = self.basetype()?;
+                self.expect("]")?;
+                return Ok(BaseType::Map(Box::new(item), Box::new(value)));
+            }
+            self.expect("]")?;
+            return Ok(BaseType::List(Box::new(item)));
+        }
+
+        let basetype = match self.expect_word()?.as_str() {
+            "void" => BaseType::Void,
+            "int" => BaseType::Int,
+            "float" => BaseType::Float,
+            "string" => BaseType::String,
+            "bool" => BaseType::Bool,
+            "fn" => {
+                self.expect("(")?;
+                let mut params = vec![];
+                while !self.eat(")") {
+                    if !params.is_empty() {
+                        self.expect(",")?;
+                    }
+                    params.push(ez_ast::Param {
+                        identifier: ez_ast::Identifier {
+                            name: "_".into(),
+                            span: Default::default(),
+                            id: Default::default(),
+                        },
+                        basetype: self.basetype()?,
+                    });
+                }
+                BaseType::Function {
+                    params,
+                    return_type: Box::new(self.basetype()?),
+                }
+            }
+            name => BaseType::Named(Symbol::intern(name)),
+        };
+        Ok(basetype)
+    }
+
+    // `enum Shape { Circle(float), Point }`
+    fn enum_declaration(&mut self) -> Result<(), String> {
+        let name = Symbol::intern(&self.expect_word()?);
+        self.expect("{")?;
+        let mut variants = vec![];
+        while !self.eat("}") {
+            if !variants.is_empty() {
+                self.expect(",")?;
+            }
+            let variant = Symbol::intern(&self.expect_word()?);
+            let mut payload = vec![];
+            if self.eat("(") {
+                while !self.eat(")") {
+                    if !payload.is_empty() {
+                        self.expect(",")?;
+                    }
+                    payload.push(self.basetype()?);
+                }
+            }
+            variants.push((variant, payload));
+        }
+        self.program.enums.insert(name, variants);
+        self.end_line()
+    }
+
+    // the index of the variant `Shape.Circle`, or `Shape.#0` the way variants
+    // print when their enum isn't known
+    fn variant(&mut self, enum_name: Symbol) -> Result<usize, String> {
+        if self.eat("#") {
+            return self.number();
+        }
+        let name = self.expect_word()?;
+        self.program
+            .enums
+            .get(&enum_name)
+            .and_then(|variants| {
+                variants
+                    .iter()
+                    .position(|(variant, _)| variant.as_str() == name)
+            })
+            .ok_or_else(|| format!("there's no variant `{}.{}`", enum_name, name))
+    }
+
+    fn function(&mut self) -> Result<(), String> {
+        let index = self.program.functions.len();
+        self.expect("@")?;
+        if self.number()? != index {
+            return Err(format!("expected the function @{} next", index));
+        }
+        let name = self.string()?;
+
+        self.expect("(")?;
+        let mut params = vec![];
+        while !self.eat(")") {
+            if !params.is_empty() {
+                self.expect(",")?;
+            }
+            let local = self.local()?;
+            self.expect(":")?;
+            params.push((local, self.basetype()?));
+        }
+        let return_type = self.basetype()?;
+        self.expect("{")?;
+        self.end_line()?;
+
+        // the void instructions print without their value, they get ones after
+        // the others
+        let mut blocks = vec![];
+        let mut unnamed = vec![];
+        loop {
+            self.skip_lines();
+            if self.eat("}") {
+                self.end_line()?;
+                break;
+            }
+
+            if self.block()?.0 != blocks.len() {
+                return Err(format!("expected bb{} next in `{}`", blocks.len(), name));
+            }
+            self.expect(":")?;
+            self.end_line()?;
+
+            let mut instrs = vec![];
+            let terminator = loop {
+                self.skip_lines();
+                if let Some(terminator) = self.terminator()? {
+                    break terminator;
+                }
+
+                let instr = if self.eat("%") {
+                    let id = ValueId(self.number()?);
+                    self.expect(":")?;
+                    let basetype = self.basetype()?;
+                    self.expect("=")?;
+                    let op = self.op(&basetype)?;
+                    Instr {
+                        id,
+                        op,
+                        basetype,
+                        span: Span::default(),
+                    }
+                } else {
+                    unnamed.push((blocks.len(), instrs.len()));
+                    let op = self.op(&BaseType::Void)?;
+                    Instr {
+                        id: ValueId(0),
+                        op,
+                        basetype: BaseType::Void,
+                        span: Span::default(),
+                    }
+                };
+                instrs.push(instr);
+                self.end_line()?;
+            };
+            self.end_line()?;
+            blocks.push(Block { instrs, terminator });
+        }
+
+        let mut values = blocks
+            .iter()
+            .flat_map(|block: &Block| &block.instrs)
+            .map(|instr| instr.id.0 + 1)
+            .max()
+            .unwrap_or(0);
+        for (block, instr) in unnamed {
+            blocks[block].instrs[instr].id = ValueId(values);
+            values += 1;
+        }
+
+        self.program.functions.push(Function {
+            name,
+            params,
+            return_type,
+            blocks,
+            values,
+        });
+        Ok(())
+    }
+
+    fn terminator(&mut self) -> Result<Option<Terminator>, String> {
+        let position = self.position;
+        let terminator = match self.word().as_deref() {
+            Some("jump") => Terminator::Jump(self.block()?),
+            Some("branch") => {
+                let condition = self.value()?;
+                self.expect(",")?;
+                let then = self.block()?;
+                self.expect(",")?;
+                let otherwise = self.block()?;
+                Terminator::Branch {
+                    condition,
+                    then,
+                    otherwise,
+                }
+            }
+            Some("match") => {
+                let value = self.value()?;
+                self.expect("[")?;
+                let mut arms = vec![];
+                while !self.eat("]") {
+                    if !arms.is_empty() {
+                        self.expect(",")?;
+                    }
+                    let pattern = self.pattern()?;
+                    self.expect("=>")?;
+                    arms.push((pattern, self.block()?));
+                }
+                Terminator::Match { value, arms }
+            }
+            Some("try") => {
+                let body = self.block()?;
+                self.expect(",")?;
+                self.expect("catch")?;
+                let handler = self.block()?;
+                Terminator::Try { body, handler }
+            }
+            Some("return") => {
+                self.skip_space();
+                if self.peek() == Some('%') {
+                    Terminator::Return(Some(self.value()?))
+                } else {
+                    Terminator::Return(None)
+                }
+            }
+            _ => {
+                self.position = position;
+                return Ok(None);
+            }
+        };
+        Ok(Some(terminator))
+    }
+
+    fn pattern(&mut self) -> Result<Pattern, String> {
+        self.skip_space();
+        match self.peek() {
+            Some('"') => return Ok(Pattern::String(self.string()?)),
+            Some('#') => return Ok(Pattern::Binding(self.local()?)),
+            Some('-' | '0'..='9') => {
+                return match self.number_constant()? {
+                    Constant::Int(value) => Ok(Pattern::Int(value)),
+                    _ => Err(self.error("an int")),
+                }
+            }
+            _ => {}
+        }
+
+        let position = self.position;
+        let name = self.expect_word()?;
+        match name.as_str() {
+            "_" => return Ok(Pattern::Wildcard),
+            "true" => return Ok(Pattern::Bool(true)),
+            "false" => return Ok(Pattern::Bool(false)),
+            _ => {}
+        }
+        if self.peek() == Some('#') {
+            self.position = position;
+            return Ok(Pattern::Binding(self.local()?));
+        }
+
+        self.expect(".")?;
+        let index = self.variant(Symbol::intern(&name))?;
+        let mut fields = vec![];
+        if self.eat("(") {
+            while !self.eat(")") {
+                if !fields.is_empty() {
+                    self.expect(",")?;
+                }
+                fields.push(self.pattern()?);
+            }
+        }
+        Ok(Pattern::Variant { index, fields })
+    }
+
+    // the operator of the text, one of the ones given
+    fn operator(&mut self, operators: &[TokenKind]) -> Result<TokenKind, String> {
+        self.skip_space();
+        let start = self.position;
+        while self.peek().is_some_and(|c| {
+            matches!(
+                c,
+                '+' | '-' | '*' | '/' | '=' | '!' | '<' | '>' | '&' | '|' | '^' | '~'
+            )
+        }) {
+            self.position += 1;
+        }
+        let text: String = self.chars[start..self.position].iter().collect();
+        let operator = operators
+            .iter()
+            .find(|operator| operator.to_string().trim_matches('`') == text);
+        operator.cloned().ok_or_else(|| {
+            self.position = start;
+            self.error("an operator")
+        })
+    }
+
+    fn op(&mut self, basetype: &BaseType) -> Result<Op, String> {
+        self.skip_space();
+        match (self.peek(), self.peek_at(1)) {
+            (Some('%'), _) => {
+                let left = self.value()?;
+                if self.peek_word().as_deref() == Some("as") {
+                    self.word();
+                    let to = self.basetype()?;
+                    if to != *basetype {
+                        let message = format!("a cast to `{}` can't be of type `{}`", to, basetype);
+                        return Err(message);
+                    }
+                    return Ok(Op::Cast(left));
+                }
+                let operator = self.operator(&BINARY_OPERATORS)?;
+                let right = self.value()?;
+                return Ok(Op::Binary {
+                    operator,
+                    left,
+                    right,
+                });
+            }
+            (Some('-' | '!' | '~'), Some('%')) => {
+                let operator = self.operator(&UNARY_OPERATORS)?;
+                let operand = self.value()?;
+                return Ok(Op::Unary { operator, operand });
+            }
+            (Some('"'), _) => return Ok(Op::Const(Constant::String(self.string()?))),
+            (Some('-' | '0'..='9'), _) => return Ok(Op::Const(self.number_constant()?)),
+            (Some('['), _) => {
+                self.position += 1;
+                if self.eat(":") {
+                    self.expect("]")?;
+                    return Ok(Op::Map(vec![]));
+                }
+                let mut items = vec![];
+                let mut entries = vec![];
+                while !self.eat("]") {
+                    if !items.is_empty() || !entries.is_empty() {
+                        self.expect(",")?;
+                    }
+                    let value = self.value()?;
+                    if self.eat(":") {
+                        entries.push((value, self.value()?));
+                    } else {
+                        items.push(value);
+                    }
+                }
+                return match (items.is_empty(), entries.is_empty()) {
+                    (_, true) => Ok(Op::List(items)),
+                    (true, false) => Ok(Op::Map(entries)),
+                    (false, false) => Err("a list can't have entries like a map".into()),
+                };
+            }
+            _ => {}
+        }
+
+        let word = self.expect_word()?;
+        let op = match word.as_str() {
+            "true" => Op::Const(Constant::Bool(true)),
+            "false" => Op::Const(Constant::Bool(false)),
+            "inf" => Op::Const(Constant::Float(f64::INFINITY)),
+            "NaN" => Op::Const(Constant::Float(f64::NAN)),
+            "undefined" => Op::Undefined,
+            "bind" => Op::Bind(self.local()?),
+            "load" => Op::Load(self.local()?),
+            "store" => {
+                let local = self.local()?;
+                self.expect(",")?;
+                Op::Store(local, self.value()?)
+            }
+            "call" => {
+                let callee = self.value()?;
+                self.expect("(")?;
+                Op::Call {
+                    callee,
+                    args: self.values(")")?,
+                }
+            }
+            "fn" => {
+                self.expect("@")?;
+                Op::Function(self.number()?)
+            }
+            "phi" => {
+                self.expect("[")?;
+                let mut incoming = vec![];
+                while !self.eat("]") {
+                    if !incoming.is_empty() {
+                        self.expect(",")?;
+                    }
+                    let block = self.block()?;
+                    self.expect(":")?;
+                    incoming.push((block, self.value()?));
+                }
+                Op::Phi(incoming)
+            }
+            _ if self.peek() == Some('.')
+                && self.program.enums.contains_key(&Symbol::intern(&word)) =>
+            {
+                self.position += 1;
+                let enum_name = Symbol::intern(&word);
+                Op::Variant {
+                    enum_name,
+                    index: self.variant(enum_name)?,
+                }
+            }
+            _ => {
+                let mut word = word;
+                if self.eat(".") {
+                    word = format!("{}.{}", word, self.expect_word()?);
+                }
+                let builtin = Builtin::parse(&word)
+                    .ok_or_else(|| format!("there's no builtin `{}`", word))?;
+                self.expect("(")?;
+                Op::Builtin {
+                    builtin,
+                    args: self.values(")")?,
+                }
+            }
+        };
+        Ok(op)
+    }
+}
@@ -0,0 +1,27 @@
+// the templates of `format` and `print`, like `"x = {}, y = {}"`. `{}` is
+// replaced by the next argument, `{{` and `}}` are braces of their own
+
+// the text between the placeholders, one more piece than there are of them. A
+// brace that's neither part of a placeholder nor doubled is an error, given as
+// its offset in the template
+pub fn pieces(template: &str) -> Result<Vec<String>, usize> {
+    let mut pieces = vec![];
+    let mut piece = String::new();
+    let mut chars = template.char_indices().peekable();
+
+    while let Some((offset, c)) = chars.next() {
+        match (c, chars.peek().map(|(_, next)| *next)) {
+            ('{', Some('}')) => pieces.push(std::mem::take(&mut piece)),
+            ('{', Some('{')) | ('}', Some('}')) => piece.push(c),
+            ('{' | '}', _) => return Err(offset),
+            _ => {
+                piece.push(c);
+                continue;
+            }
+        }
+        chars.next();
+    }
+
+    pieces.push(piece);
+    Ok(pieces)
+}
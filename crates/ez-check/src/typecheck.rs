@@ -0,0 +1,1768 @@
+use std::collections::HashMap;
+
+use ez_ast::{BaseType, Expr, Identifier, NodeId, Param, Pattern, Program, ValueExpr, Variant};
+use ez_diagnostics::Diagnostic;
+use ez_lexer::{Span, Symbol, TokenKind};
+
+use crate::json::Json;
+use crate::resolver::{Builtin, DefId, DefKind, Module, Resolutions};
+use crate::template;
+
+#[derive(Debug)]
+pub enum TypeError {
+    Mismatch {
+        expected: BaseType,
+        found: BaseType,
+        span: Span,
+    },
+    InvalidOperand {
+        operator: TokenKind,
+        found: BaseType,
+        span: Span,
+    },
+    NotCallable {
+        found: BaseType,
+        span: Span,
+    },
+    // `definition` is where the called function was declared, when it's known
+    ArgumentCount {
+        expected: usize,
+        found: usize,
+        span: Span,
+        definition: Option<Span>,
+    },
+    // `param` points at the parameter the argument was passed to
+    ArgumentType {
+        expected: BaseType,
+        found: BaseType,
+        span: Span,
+        // the parameters of the host's functions weren't declared in the program
+        param: Option<Span>,
+    },
+    // ints and floats are never converted implicitly
+    MixedArithmetic {
+        operator: TokenKind,
+        left: BaseType,
+        right: BaseType,
+        span: Span,
+    },
+    ReturnOutsideFunction(Span),
+    LoopControlOutsideLoop(Span),
+    UnknownVariant {
+        enum_name: Symbol,
+        variant: Identifier,
+    },
+    NoField {
+        found: BaseType,
+        field: Identifier,
+    },
+    // a variant pattern with the wrong number of fields
+    PatternArity {
+        expected: usize,
+        found: usize,
+        span: Span,
+    },
+    InvalidCast {
+        from: BaseType,
+        to: BaseType,
+        span: Span,
+    },
+    BuiltinValue {
+        builtin: Builtin,
+        span: Span,
+    },
+    NotPrintable {
+        found: BaseType,
+        span: Span,
+    },
+    ModuleValue {
+        module: Module,
+        span: Span,
+    },
+    UnknownMember {
+        module: Module,
+        member: Identifier,
+    },
+    // a file the program uses, its members are the values
+    ImportValue(Identifier),
+    NoMethod {
+        found: BaseType,
+        method: Identifier,
+    },
+    // builtins like `math.abs` work on ints and floats, but nothing else
+    NotNumeric {
+        builtin: Builtin,
+        found: BaseType,
+        span: Span,
+    },
+    // `[]` or `[:]` where nothing tells what it holds
+    EmptyCollection {
+        map: bool,
+        span: Span,
+    },
+    NotHashable {
+        found: BaseType,
+        span: Span,
+    },
+    NotIterable {
+        found: BaseType,
+        span: Span,
+    },
+    NotJson {
+        found: BaseType,
+        span: Span,
+    },
+    // the placeholders of templates are counted when the program is checked
+    TemplateNotLiteral(Span),
+    TemplateArgs {
+        placeholders: usize,
+        found: usize,
+        span: Span,
+    },
+    // a `{` or `}` that isn't part of a placeholder
+    TemplateBrace(Span),
+}
+
+impl From<TypeError> for Diagnostic {
+    fn from(error: TypeError) -> Self {
+        match error {
+            TypeError::Mismatch {
+                expected,
+                found,
+                span,
+            } => {
+                let diagnostic = Diagnostic::error("E0201", "mismatched types")
+                    .with_label(span, format!("expected `{}`, found `{}`", expected, found));
+
+                // numbers are never converted implicitly, but they can be cast
+                if can_cast(&found, &expected) && found.is_numeric() {
+                    diagnostic.with_help(format!("convert it explicitly with `as {}`", expected))
+                } else {
+                    diagnostic
+                }
+            }
+
+            TypeError::InvalidOperand {
+                operator,
+                found,
+                span,
+            } => Diagnostic::error(
+                "E0202",
+                format!("{} can't be applied to `{}`", operator, found),
+            )
+            .with_label(span, format!("this is of type `{}`", found)),
+
+            TypeError::NotCallable { found, span } => {
+                Diagnostic::error("E0203", format!("`{}` is not a function", found))
+                    .with_label(span, "called here")
+            }
+
+            TypeError::ArgumentCount {
+                expected,
+                found,
+                span,
+                definition,
+            } => {
+                let diagnostic = Diagnostic::error(
+                    "E0204",
+                    format!(
+                        "this function takes {} argument(s) but {} were given",
+                        expected, found
+                    ),
+                )
+                .with_label(span, format!("expected {} argument(s)", expected));
+
+                match definition {
+                    Some(definition) => diagnostic.with_secondary(definition, "declared here"),
+                    None => diagnostic,
+                }
+            }
+
+            TypeError::ArgumentType {
+                expected,
+                found,
+                span,
+                param,
+            } => {
+                let diagnostic = Diagnostic::error("E0205", "mismatched argument type")
+                    .with_label(span, format!("expected `{}`, found `{}`", expected, found));
+                match param {
+                    Some(param) => diagnostic.with_secondary(param, "parameter declared here"),
+                    None => diagnostic,
+                }
+            }
+
+            TypeError::MixedArithmetic {
+                operator,
+                left,
+                right,
+                span,
+            } => Diagnostic::error(
+                "E0206",
+                format!(
+                    "{} can't be applied to `{}` and `{}`",
+                    operator, left, right
+                ),
+            )
+            .with_label(span, "both sides need to be of the same type")
+            .with_note("ints and floats are never converted implicitly")
+            .with_help("convert one side with `as int` or `as float`"),
+
+            TypeError::ReturnOutsideFunction(span) => {
+                Diagnostic::error("E0207", "`return` outside of a function")
+                    .with_label(span, "not inside a function")
+            }
+
+            TypeError::LoopControlOutsideLoop(span) => {
+                Diagnostic::error("E0208", "`break` or `continue` outside of a loop")
+                    .with_label(span, "not inside a loop")
+            }
+
+            TypeError::UnknownVariant { enum_name, variant } => Diagnostic::error(
+                "E0209",
+                format!("no variant `{}` in enum `{}`", variant.name, enum_name),
+            )
+            .with_label(variant.span, "variant not found"),
+
+            TypeError::NoField { found, field } => Diagnostic::error(
+                "E0210",
+                format!("no field `{}` on type `{}`", field.name, found),
+            )
+            .with_label(field.span, "unknown field"),
+
+            TypeError::PatternArity {
+                expected,
+                found,
+                span,
+            } => Diagnostic::error(
+                "E0211",
+                format!(
+                    "this pattern has {} field(s) but the variant has {}",
+                    found, expected
+                ),
+            )
+            .with_label(span, format!("expected {} field(s)", expected)),
+
+            TypeError::InvalidCast { from, to, span } => {
+                Diagnostic::error("E0212", format!("`{}` can't be cast to `{}`", from, to))
+                    .with_label(span, "invalid cast")
+                    .with_note(
+                        "ints and floats can be cast to each other, bools to ints, \
+                         and all of them to `string`",
+                    )
+            }
+
+            TypeError::BuiltinValue { builtin, span } if builtin.is_method() => Diagnostic::error(
+                "E0213",
+                format!("the method `{}` can only be called", builtin),
+            )
+            .with_label(span, "used as a value")
+            .with_help(format!("call it, like `value.{}(...)`", builtin)),
+
+            TypeError::BuiltinValue { builtin, span } => Diagnostic::error(
+                "E0213",
+                format!("the builtin `{}` can only be called", builtin),
+            )
+            .with_label(span, "used as a value")
+            .with_help(format!("call it, like `{}(...)`", builtin)),
+
+            TypeError::NotPrintable { found, span } => {
+                Diagnostic::error("E0214", format!("`{}` values can't be printed", found))
+                    .with_label(span, format!("this is of type `{}`", found))
+                    .with_note("ints, floats, strings and bools can be printed")
+            }
+
+            TypeError::ModuleValue { module, span } => Diagnostic::error(
+                "E0215",
+                format!("the module `{}` isn't a value", module.name()),
+            )
+            .with_label(span, "used as a value")
+            .with_help(format!(
+                "use one of its members, like `{}`",
+                module.functions()[0]
+            )),
+
+            TypeError::UnknownMember { module, member } => Diagnostic::error(
+                "E0216",
+                format!(
+                    "cannot find `{}` in the module `{}`",
+                    member.name,
+                    module.name()
+                ),
+            )
+            .with_label(member.span, format!("not found in `{}`", module.name())),
+
+            TypeError::NoMethod { found, method } => Diagnostic::error(
+                "E0218",
+                format!("no method `{}` on type `{}`", method.name, found),
+            )
+            .with_label(method.span, "unknown method"),
+
+            TypeError::NotNumeric {
+                builtin,
+                found,
+                span,
+            } => Diagnostic::error(
+                "E0217",
+                format!("`{}` takes ints or floats, not `{}`", builtin, found),
+            )
+            .with_label(span, format!("this is of type `{}`", found)),
+
+            TypeError::EmptyCollection { map, span } => {
+                let (what, example) = if map {
+                    ("map", "counts [string: int] := [:];")
+                } else {
+                    ("list", "names [string] := [];")
+                };
+
+                Diagnostic::error(
+                    "E0219",
+                    format!("can't tell the type of this empty {}", what),
+                )
+                .with_label(span, "the type of its items is unknown")
+                .with_help(format!("give it a type, like `{}`", example))
+            }
+
+            TypeError::NotHashable { found, span } => Diagnostic::error(
+                "E0220",
+                format!("`{}` values can't be the keys of a map", found),
+            )
+            .with_label(span, format!("this is of type `{}`", found))
+            .with_note("keys can be ints, strings or bools"),
+
+            TypeError::NotIterable { found, span } => {
+                Diagnostic::error("E0221", format!("`{}` values can't be looped over", found))
+                    .with_label(span, format!("this is of type `{}`", found))
+                    .with_help("loop over a list, a map or a range, like `0..10`")
+            }
+
+            TypeError::NotJson { found, span } => Diagnostic::error(
+                "E0222",
+                format!("`{}` values can't be written as JSON", found),
+            )
+            .with_label(span, format!("this is of type `{}`", found))
+            .with_note(
+                "numbers, strings, bools, `Json` values, and lists and maps with \
+                 `string` keys of them can",
+            ),
+
+            TypeError::TemplateNotLiteral(span) => {
+                Diagnostic::error("E0223", "templates have to be string literals")
+                    .with_label(span, "not a string literal")
+                    .with_note("the placeholders of a template are counted before the program runs")
+                    .with_help("to print this value, use `\"{}\"` as the template")
+            }
+
+            TypeError::TemplateArgs {
+                placeholders,
+                found,
+                span,
+            } => Diagnostic::error(
+                "E0224",
+                format!(
+                    "the template has {} placeholder(s) but {} argument(s) were given",
+                    placeholders, found
+                ),
+            )
+            .with_label(
+                span,
+                format!("expected {} argument(s) after the template", placeholders),
+            ),
+
+            TypeError::TemplateBrace(span) => {
+                Diagnostic::error("E0225", "unmatched brace in the template")
+                    .with_label(span, "this brace isn't part of a `{}`")
+                    .with_help("write braces of their own twice, like `{{` and `}}`")
+            }
+
+            TypeError::ImportValue(identifier) => Diagnostic::error(
+                "E0226",
+                format!("the module `{}` isn't a value", identifier.name),
+            )
+            .with_label(identifier.span, "used as a value")
+            .with_help(format!(
+                "use what it declares, like `{}.name`",
+                identifier.name
+            )),
+        }
+    }
+}
+
+// which explicit conversions are allowed with `as`, besides casting a type to
+// itself:
+//
+//            | int | float | bool | string
+//     int    |  -  |  yes  |  no  |  yes
+//     float  | yes |   -   |  no  |  yes
+//     bool   | yes |  no   |  -   |  yes
+//     string | no  |  no   |  no  |   -
+//
+// floats are truncated towards zero when cast to ints, bools are 0 or 1, and
+// strings can't be cast to anything since that could fail
+pub fn can_cast(from: &BaseType, to: &BaseType) -> bool {
+    if from == to {
+        return true;
+    }
+
+    matches!(
+        (from, to),
+        (BaseType::Int, BaseType::Float)
+            | (BaseType::Float, BaseType::Int)
+            | (BaseType::Bool, BaseType::Int)
+            | (
+                BaseType::Int | BaseType::Float | BaseType::Bool,
+                BaseType::String
+            )
+    )
+}
+
+// lists and maps can be printed when what they hold can
+fn is_printable(basetype: &BaseType) -> bool {
+    match basetype {
+        BaseType::Int | BaseType::Float | BaseType::String | BaseType::Bool => true,
+        BaseType::List(item) => is_printable(item),
+        BaseType::Map(key, value) => is_printable(key) && is_printable(value),
+        _ => false,
+    }
+}
+
+// what `json_stringify` takes
+fn is_json(basetype: &BaseType) -> bool {
+    match basetype {
+        BaseType::Int | BaseType::Float | BaseType::String | BaseType::Bool => true,
+        BaseType::Named(name) => name == Json::NAME,
+        BaseType::List(item) => is_json(item),
+        BaseType::Map(key, value) => **key == BaseType::String && is_json(value),
+        _ => false,
+    }
+}
+
+// `None` is used for values whose type couldn't be figured out, so a single
+// error doesn't cascade into a bunch of other ones
+type Type = Option<BaseType>;
+
+// what the type checker found out, for the passes that need types
+#[derive(Debug, Default)]
+pub struct Types {
+    // the type of every value that could be typed, by its id
+    pub values: HashMap<NodeId, BaseType>,
+    // where those values are, to find the one under the cursor
+    pub spans: HashMap<NodeId, Span>,
+    pub definitions: HashMap<DefId, BaseType>,
+    pub enums: HashMap<Symbol, Vec<Variant>>,
+}
+
+impl Types {
+    pub fn of(&self, value: &ValueExpr) -> Option<&BaseType> {
+        self.values.get(&value.id())
+    }
+
+    // the innermost value at the offset that could be typed, with its type
+    pub fn at(&self, offset: usize) -> Option<(Span, &BaseType)> {
+        let (node, span) = self
+            .spans
+            .iter()
+            .filter(|(_, span)| span.start <= offset && offset <= span.end)
+            .min_by_key(|(_, span)| span.end - span.start)?;
+        Some((*span, self.values.get(node)?))
+    }
+}
+
+pub struct TypeChecker<'a> {
+    resolutions: &'a Resolutions,
+    output: Types,
+    return_types: Vec<BaseType>,
+    // how many loops the statement being checked is in, within the current function
+    loop_depth: usize,
+    errors: Vec<TypeError>,
+}
+
+impl<'a> TypeChecker<'a> {
+    pub fn new(resolutions: &'a Resolutions) -> Self {
+        Self {
+            resolutions,
+            output: Types {
+                enums: HashMap::from([(Symbol::intern(Json::NAME), Json::variants())]),
+                ..Types::default()
+            },
+            return_types: vec![],
+            loop_depth: 0,
+            errors: vec![],
+        }
+    }
+
+    // the functions of the host are known upfront, like top level functions
+    pub fn with_hosts(mut self, hosts: &[(String, BaseType)]) -> Self {
+        for (id, (_, basetype)) in self.resolutions.hosts.iter().zip(hosts) {
+            self.output.definitions.insert(*id, basetype.clone());
+        }
+        self
+    }
+
+    fn function_type(params: &[Param], return_type: &BaseType) -> BaseType {
+        BaseType::Function {
+            params: params.to_vec(),
+            return_type: Box::new(return_type.clone()),
+        }
+    }
+
+    fn expect_type(&mut self, expected: &BaseType, found: Type, span: Span) {
+        let Some(found) = found else { return };
+
+        if *expected != found {
+            self.errors.push(TypeError::Mismatch {
+                expected: expected.clone(),
+                found,
+                span,
+            });
+        }
+    }
+
+    fn check_unary(&mut self, operator: &TokenKind, operand: &ValueExpr) -> Type {
+        let found = self.check_value(operand)?;
+        let valid = match operator {
+            TokenKind::Minus => found.is_numeric(),
+            TokenKind::BitNot => found == BaseType::Int,
+            TokenKind::Not => found == BaseType::Bool,
+            _ => false,
+        };
+
+        if !valid {
+            self.errors.push(TypeError::InvalidOperand {
+                operator: operator.clone(),
+                found,
+                span: operand.span(),
+            });
+
+            return None;
+        }
+
+        Some(found)
+    }
+
+    fn check_binary(&mut self, operator: &TokenKind, left: &ValueExpr, right: &ValueExpr) -> Type {
+        let left_type = self.check_value(left);
+        let right_type = self.check_value(right);
+
+        let (operands, result) = match operator {
+            TokenKind::Plus if left_type == Some(BaseType::String) => {
+                (BaseType::String, BaseType::String)
+            }
+
+            TokenKind::Plus | TokenKind::Minus | TokenKind::Times | TokenKind::DividedBy => {
+                return self.check_numeric(operator, (left_type, left), (right_type, right));
+            }
+
+            TokenKind::GreaterThan
+            | TokenKind::GreaterOrEquals
+            | TokenKind::LowerThan
+            | TokenKind::LowerOrEquals => {
+                self.check_numeric(operator, (left_type, left), (right_type, right));
+                return Some(BaseType::Bool);
+            }
+
+            TokenKind::BitAnd | TokenKind::BitOr | TokenKind::BitXor => {
+                (BaseType::Int, BaseType::Int)
+            }
+
+            TokenKind::And | TokenKind::Or => (BaseType::Bool, BaseType::Bool),
+
+            // any type can be compared for equality, as long as both sides match
+            TokenKind::Equals | TokenKind::NotEquals => {
+                if let (Some(left_type), Some(_)) = (&left_type, &right_type) {
+                    let left_type = left_type.clone();
+                    self.expect_type(&left_type, right_type, right.span());
+                }
+
+                return Some(BaseType::Bool);
+            }
+
+            _ => return None,
+        };
+
+        for (found, value) in [(left_type, left), (right_type, right)] {
+            let Some(found) = found else { continue };
+
+            if found != operands {
+                self.errors.push(TypeError::InvalidOperand {
+                    operator: operator.clone(),
+                    found,
+                    span: value.span(),
+                });
+            }
+        }
+
+        Some(result)
+    }
+
+    // both sides need to be the same kind of number, the result is that type
+    fn check_numeric(
+        &mut self,
+        operator: &TokenKind,
+        (left_type, left): (Type, &ValueExpr),
+        (right_type, right): (Type, &ValueExpr),
+    ) -> Type {
+        let mut valid = true;
+        for (found, value) in [(&left_type, left), (&right_type, right)] {
+            let Some(found) = found else { continue };
+
+            if !found.is_numeric() {
+                valid = false;
+                self.errors.push(TypeError::InvalidOperand {
+                    operator: operator.clone(),
+                    found: found.clone(),
+                    span: value.span(),
+                });
+            }
+        }
+
+        if !valid {
+            return None;
+        }
+
+        match (left_type, right_type) {
+            (Some(left_type), Some(right_type)) if left_type != right_type => {
+                self.errors.push(TypeError::MixedArithmetic {
+                    operator: operator.clone(),
+                    left: left_type,
+                    right: right_type,
+                    span: left.span().to(right.span()),
+                });
+
+                None
+            }
+            (Some(found), _) | (_, Some(found)) => Some(found),
+            (None, None) => None,
+        }
+    }
+
+    // where the called value was declared, this is either a function or a variable
+    // holding one, like a parameter with a function type
+    fn definition_of(&self, callee: &ValueExpr) -> Option<Span> {
+        // the members of modules are resolved at the field
+        let identifier = match callee {
+            ValueExpr::Identifier(identifier) => identifier,
+            ValueExpr::Field { field, .. } => field,
+            _ => return None,
+        };
+
+        let id = self.resolutions.resolve(identifier)?;
+        match self.resolutions.definition(id) {
+            definition if definition.kind == DefKind::Host => None,
+            definition => Some(definition.span),
+        }
+    }
+
+    // the module the value names, like `math`
+    fn module(&self, value: &ValueExpr) -> Option<Module> {
+        let ValueExpr::Identifier(identifier) = value else {
+            return None;
+        };
+
+        let id = self.resolutions.resolve(identifier)?;
+        match self.resolutions.definition(id).kind {
+            DefKind::Module(module) => Some(module),
+            _ => None,
+        }
+    }
+
+    fn is_import(&self, value: &ValueExpr) -> bool {
+        let ValueExpr::Identifier(identifier) = value else {
+            return false;
+        };
+
+        self.resolutions
+            .resolve(identifier)
+            .is_some_and(|id| matches!(self.resolutions.definition(id).kind, DefKind::Import(_)))
+    }
+
+    // enums and modules are the values whose fields aren't methods, the modules
+    // can be the builtin ones or files
+    fn is_namespace(&self, value: &ValueExpr) -> bool {
+        let ValueExpr::Identifier(identifier) = value else {
+            return false;
+        };
+
+        self.resolutions.resolve(identifier).is_some_and(|id| {
+            matches!(
+                self.resolutions.definition(id).kind,
+                DefKind::Enum | DefKind::Module(_) | DefKind::Import(_)
+            )
+        })
+    }
+
+    // builtins are called by their name, or as a field of their module
+    fn builtin(&self, callee: &ValueExpr) -> Option<Builtin> {
+        match callee {
+            ValueExpr::Identifier(identifier) => {
+                let id = self.resolutions.resolve(identifier)?;
+                match self.resolutions.definition(id).kind {
+                    DefKind::Builtin(builtin) => Some(builtin),
+                    _ => None,
+                }
+            }
+            ValueExpr::Field { value, field, .. } => self.module(value)?.function(&field.name),
+            _ => None,
+        }
+    }
+
+    // the template is the first argument, its placeholders have to match the
+    // arguments after it
+    fn check_template(&mut self, args: &[ValueExpr], span: Span) {
+        let Some(template) = args.first() else {
+            return;
+        };
+        let ValueExpr::String {
+            value,
+            span: literal,
+            ..
+        } = template
+        else {
+            self.errors
+                .push(TypeError::TemplateNotLiteral(template.span()));
+            return;
+        };
+
+        match template::pieces(value) {
+            Ok(pieces) if pieces.len() != args.len() => self.errors.push(TypeError::TemplateArgs {
+                placeholders: pieces.len() - 1,
+                found: args.len() - 1,
+                span,
+            }),
+            Ok(_) => {}
+            // strings have no escapes, so the offset in the value is the one
+            // after the opening quote
+            Err(offset) => {
+                let brace = literal.start + 1 + offset;
+                self.errors
+                    .push(TypeError::TemplateBrace(Span::new(brace, brace + 1)));
+            }
+        }
+    }
+
+    // `print` and the logging ones take one value, `println` can also be called
+    // without one to only end the line. Given more than that, they take a
+    // template like `format`.
+    // The functions of `math` take floats, except `abs`, `min` and `max`, which
+    // take ints too and give back the type they were given. The ones on files,
+    // commands, regexes and the environment take strings, except for the lines
+    // given to `write_lines` and the arguments of commands. Sockets are ints,
+    // given back by the ones opening them. `json_parse` takes a string and
+    // `json_stringify` anything that can be written as JSON. `assert` takes a
+    // bool and maybe a message, `panic` only the message. `sleep`, `seed` and
+    // `random_int` take ints, and `args`, `now`, `clock` and `random` take nothing
+    fn check_builtin(&mut self, builtin: Builtin, args: &[ValueExpr], span: Span) -> Type {
+        // the lists given to `write_lines` and `run_command` can be empty
+        let lines = BaseType::List(Box::new(BaseType::String));
+        let arg_types: Vec<Type> = args
+            .iter()
+            .enumerate()
+            .map(|(index, arg)| match (builtin, index) {
+                (Builtin::WriteLines | Builtin::RunCommand, 1) => self.check_value_as(arg, &lines),
+                _ => self.check_value(arg),
+            })
+            .collect();
+
+        let allowed = match builtin {
+            Builtin::Args | Builtin::Now | Builtin::Clock | Builtin::Random => 0..=0,
+            // with more than one argument, the first is a template
+            Builtin::Println => 0..=usize::MAX,
+            Builtin::Print
+            | Builtin::Format
+            | Builtin::LogDebug
+            | Builtin::LogInfo
+            | Builtin::LogWarn
+            | Builtin::LogError => 1..=usize::MAX,
+            Builtin::ReadFile
+            | Builtin::ReadLines
+            | Builtin::EnvGet
+            | Builtin::JsonParse
+            | Builtin::JsonStringify
+            | Builtin::Sleep
+            | Builtin::Seed
+            | Builtin::TcpAccept
+            | Builtin::Read
+            | Builtin::Close
+            | Builtin::Panic
+            | Builtin::Sqrt
+            | Builtin::Abs
+            | Builtin::Floor
+            | Builtin::Ceil => 1..=1,
+            Builtin::WriteFile
+            | Builtin::WriteLines
+            | Builtin::EnvSet
+            | Builtin::RunCommand
+            | Builtin::RegexMatch
+            | Builtin::RegexFindAll
+            | Builtin::RandomInt
+            | Builtin::TcpConnect
+            | Builtin::TcpListen
+            | Builtin::Write
+            | Builtin::Pow
+            | Builtin::Min
+            | Builtin::Max => 2..=2,
+            // the message of an assertion is optional
+            Builtin::Assert => 1..=2,
+            Builtin::RegexReplace => 3..=3,
+            _ => unreachable!("methods are checked by `check_method`"),
+        };
+        if !allowed.contains(&args.len()) {
+            self.errors.push(TypeError::ArgumentCount {
+                expected: if args.len() < *allowed.start() {
+                    *allowed.start()
+                } else {
+                    *allowed.end()
+                },
+                found: args.len(),
+                span,
+                definition: None,
+            });
+        }
+
+        match builtin {
+            Builtin::Print
+            | Builtin::Println
+            | Builtin::Format
+            | Builtin::LogDebug
+            | Builtin::LogInfo
+            | Builtin::LogWarn
+            | Builtin::LogError => {
+                if builtin == Builtin::Format || args.len() > 1 {
+                    self.check_template(args, span);
+                }
+
+                for (arg, arg_type) in args.iter().zip(arg_types) {
+                    match arg_type {
+                        Some(found) if !is_printable(&found) => {
+                            self.errors.push(TypeError::NotPrintable {
+                                found,
+                                span: arg.span(),
+                            })
+                        }
+                        _ => {}
+                    }
+                }
+
+                match builtin {
+                    Builtin::Format => Some(BaseType::String),
+                    _ => Some(BaseType::Void),
+                }
+            }
+
+            Builtin::ReadFile
+            | Builtin::WriteFile
+            | Builtin::ReadLines
+            | Builtin::WriteLines
+            | Builtin::RunCommand
+            | Builtin::RegexMatch
+            | Builtin::RegexFindAll
+            | Builtin::RegexReplace
+            | Builtin::EnvGet
+            | Builtin::EnvSet => {
+                for (index, (arg, arg_type)) in args.iter().zip(arg_types).enumerate() {
+                    let expected = match (builtin, index) {
+                        (Builtin::WriteLines | Builtin::RunCommand, 1) => lines.clone(),
+                        _ => BaseType::String,
+                    };
+
+                    match arg_type {
+                        Some(found) if found != expected => self.errors.push(TypeError::Mismatch {
+                            expected,
+                            found,
+                            span: arg.span(),
+                        }),
+                        _ => {}
+                    }
+                }
+
+                match builtin {
+                    Builtin::ReadFile | Builtin::EnvGet => Some(BaseType::String),
+                    Builtin::ReadLines | Builtin::RegexFindAll => Some(lines),
+                    Builtin::RegexMatch => Some(BaseType::Bool),
+                    Builtin::RegexReplace => Some(BaseType::String),
+                    Builtin::RunCommand => Some(BaseType::Map(
+                        Box::new(BaseType::String),
+                        Box::new(BaseType::String),
+                    )),
+                    _ => Some(BaseType::Void),
+                }
+            }
+
+            Builtin::Args => Some(BaseType::List(Box::new(BaseType::String))),
+
+            Builtin::JsonParse => {
+                if let (Some(arg), Some(Some(found))) = (args.first(), arg_types.first()) {
+                    if *found != BaseType::String {
+                        self.errors.push(TypeError::Mismatch {
+                            expected: BaseType::String,
+                            found: found.clone(),
+                            span: arg.span(),
+                        });
+                    }
+                }
+                Some(BaseType::Named(Json::NAME.into()))
+            }
+            Builtin::JsonStringify => {
+                if let (Some(arg), Some(Some(found))) = (args.first(), arg_types.first()) {
+                    if !is_json(found) {
+                        self.errors.push(TypeError::NotJson {
+                            found: found.clone(),
+                            span: arg.span(),
+                        });
+                    }
+                }
+                Some(BaseType::String)
+            }
+            Builtin::Now => Some(BaseType::Int),
+            Builtin::Clock | Builtin::Random => Some(BaseType::Float),
+
+            Builtin::Sleep | Builtin::Seed | Builtin::RandomInt => {
+                for (arg, arg_type) in args.iter().zip(arg_types) {
+                    match arg_type {
+                        Some(BaseType::Int) | None => {}
+                        Some(found) => self.errors.push(TypeError::Mismatch {
+                            expected: BaseType::Int,
+                            found,
+                            span: arg.span(),
+                        }),
+                    }
+                }
+
+                match builtin {
+                    Builtin::RandomInt => Some(BaseType::Int),
+                    _ => Some(BaseType::Void),
+                }
+            }
+
+            // connecting and listening take a host and a port
+            Builtin::TcpConnect
+            | Builtin::TcpListen
+            | Builtin::TcpAccept
+            | Builtin::Read
+            | Builtin::Write
+            | Builtin::Close => {
+                let params = match builtin {
+                    Builtin::TcpConnect | Builtin::TcpListen => {
+                        vec![BaseType::String, BaseType::Int]
+                    }
+                    Builtin::Write => vec![BaseType::Int, BaseType::String],
+                    _ => vec![BaseType::Int],
+                };
+
+                for ((arg, arg_type), expected) in args.iter().zip(arg_types).zip(params) {
+                    match arg_type {
+                        Some(found) if found != expected => self.errors.push(TypeError::Mismatch {
+                            expected,
+                            found,
+                            span: arg.span(),
+                        }),
+                        _ => {}
+                    }
+                }
+
+                match builtin {
+                    Builtin::Read => Some(BaseType::String),
+                    Builtin::Write | Builtin::Close => Some(BaseType::Void),
+                    _ => Some(BaseType::Int),
+                }
+            }
+
+            Builtin::Assert | Builtin::Panic => {
+                let params = match builtin {
+                    Builtin::Assert => vec![BaseType::Bool, BaseType::String],
+                    _ => vec![BaseType::String],
+                };
+
+                for ((arg, arg_type), expected) in args.iter().zip(arg_types).zip(params) {
+                    match arg_type {
+                        Some(found) if found != expected => self.errors.push(TypeError::Mismatch {
+                            expected,
+                            found,
+                            span: arg.span(),
+                        }),
+                        _ => {}
+                    }
+                }
+
+                Some(BaseType::Void)
+            }
+
+            Builtin::Sqrt | Builtin::Pow | Builtin::Floor | Builtin::Ceil => {
+                for (arg, arg_type) in args.iter().zip(arg_types) {
+                    match arg_type {
+                        Some(BaseType::Float) | None => {}
+                        Some(found) => self.errors.push(TypeError::Mismatch {
+                            expected: BaseType::Float,
+                            found,
+                            span: arg.span(),
+                        }),
+                    }
+                }
+
+                Some(BaseType::Float)
+            }
+
+            // the other arguments have to be of the type of the first one
+            Builtin::Abs | Builtin::Min | Builtin::Max => {
+                let expected = arg_types.first().cloned().flatten()?;
+                if !expected.is_numeric() {
+                    self.errors.push(TypeError::NotNumeric {
+                        builtin,
+                        found: expected,
+                        span: args[0].span(),
+                    });
+                    return None;
+                }
+
+                for (arg, arg_type) in args.iter().zip(arg_types).skip(1) {
+                    match arg_type {
+                        Some(found) if found != expected => self.errors.push(TypeError::Mismatch {
+                            expected: expected.clone(),
+                            found,
+                            span: arg.span(),
+                        }),
+                        _ => {}
+                    }
+                }
+
+                Some(expected)
+            }
+
+            _ => unreachable!("methods are checked by `check_method`"),
+        }
+    }
+
+    // the methods of a type are builtins taking the value they're called on as
+    // their first argument
+    fn check_method(
+        &mut self,
+        receiver: &ValueExpr,
+        method: &Identifier,
+        args: &[ValueExpr],
+        span: Span,
+    ) -> Type {
+        let receiver_type = self.check_value(receiver);
+        let builtin = match &receiver_type {
+            Some(found) => {
+                let builtin = Builtin::method(found, &method.name);
+                if builtin.is_none() {
+                    self.errors.push(TypeError::NoMethod {
+                        found: found.clone(),
+                        method: method.clone(),
+                    });
+                }
+                builtin
+            }
+            None => None,
+        };
+
+        // the arguments are checked against the parameters, once they're known
+        let (Some(found), Some(builtin)) = (receiver_type, builtin) else {
+            for arg in args {
+                self.check_value(arg);
+            }
+            return None;
+        };
+
+        let (params, result) = Self::method_signature(builtin, &found);
+        let arg_types: Vec<Type> = args
+            .iter()
+            .zip(params.iter().map(Some).chain(std::iter::repeat(None)))
+            .map(|(arg, expected)| match expected {
+                Some(expected) => self.check_value_as(arg, expected),
+                None => self.check_value(arg),
+            })
+            .collect();
+
+        if params.len() != args.len() {
+            self.errors.push(TypeError::ArgumentCount {
+                expected: params.len(),
+                found: args.len(),
+                span,
+                definition: None,
+            });
+        }
+
+        for ((expected, arg), arg_type) in params.into_iter().zip(args).zip(arg_types) {
+            match arg_type {
+                Some(found) if found != expected => self.errors.push(TypeError::Mismatch {
+                    expected,
+                    found,
+                    span: arg.span(),
+                }),
+                _ => {}
+            }
+        }
+
+        Some(result)
+    }
+
+    // the parameters and the result of a method, besides the value it's called on
+    pub fn method_signature(builtin: Builtin, receiver: &BaseType) -> (Vec<BaseType>, BaseType) {
+        let list = |item: &BaseType| BaseType::List(Box::new(item.clone()));
+
+        match (builtin, receiver) {
+            (Builtin::Len, _) => (vec![], BaseType::Int),
+
+            (Builtin::Push, BaseType::List(item)) => (vec![(**item).clone()], BaseType::Void),
+            (Builtin::Pop, BaseType::List(item)) => (vec![], (**item).clone()),
+            (Builtin::Get | Builtin::Remove, BaseType::List(item)) => {
+                (vec![BaseType::Int], (**item).clone())
+            }
+            (Builtin::Set, BaseType::List(item)) => {
+                (vec![BaseType::Int, (**item).clone()], BaseType::Void)
+            }
+            (Builtin::Contains, BaseType::List(item)) => (vec![(**item).clone()], BaseType::Bool),
+
+            (Builtin::Insert, BaseType::Map(key, value)) => {
+                (vec![(**key).clone(), (**value).clone()], BaseType::Void)
+            }
+            (Builtin::Get | Builtin::Remove, BaseType::Map(key, value)) => {
+                (vec![(**key).clone()], (**value).clone())
+            }
+            (Builtin::Contains, BaseType::Map(key, _)) => (vec![(**key).clone()], BaseType::Bool),
+            (Builtin::Keys, BaseType::Map(key, _)) => (vec![], list(key)),
+
+            (Builtin::Substring, _) => (vec![BaseType::Int, BaseType::Int], BaseType::String),
+            (Builtin::Contains, _) => (vec![BaseType::String], BaseType::Bool),
+            (Builtin::Split, _) => (vec![BaseType::String], list(&BaseType::String)),
+            (Builtin::ParseInt, _) => (vec![], BaseType::Int),
+            (Builtin::ParseFloat, _) => (vec![], BaseType::Float),
+            _ => (vec![], BaseType::String),
+        }
+    }
+
+    fn check_call(&mut self, callee: &ValueExpr, args: &[ValueExpr], span: Span) -> Type {
+        if let Some(builtin) = self.builtin(callee) {
+            return self.check_builtin(builtin, args, span);
+        }
+
+        if let ValueExpr::Field { value, field, .. } = callee {
+            if !self.is_namespace(value) {
+                return self.check_method(value, field, args, span);
+            }
+        }
+
+        let callee_type = self.check_value(callee);
+        let params = match &callee_type {
+            Some(BaseType::Function { params, .. }) => params.clone(),
+            _ => vec![],
+        };
+        let arg_types: Vec<Type> = args
+            .iter()
+            .zip(params.iter().map(Some).chain(std::iter::repeat(None)))
+            .map(|(arg, param)| match param {
+                Some(param) => self.check_value_as(arg, &param.basetype),
+                None => self.check_value(arg),
+            })
+            .collect();
+
+        let callee_type = callee_type?;
+        let BaseType::Function {
+            params,
+            return_type,
+        } = &callee_type
+        else {
+            self.errors.push(TypeError::NotCallable {
+                found: callee_type,
+                span: callee.span(),
+            });
+
+            return None;
+        };
+
+        if params.len() != args.len() {
+            self.errors.push(TypeError::ArgumentCount {
+                expected: params.len(),
+                found: args.len(),
+                span,
+                definition: self.definition_of(callee),
+            });
+        }
+
+        for ((param, arg), arg_type) in params.iter().zip(args).zip(arg_types) {
+            let Some(arg_type) = arg_type else { continue };
+
+            if param.basetype != arg_type {
+                self.errors.push(TypeError::ArgumentType {
+                    expected: param.basetype.clone(),
+                    found: arg_type,
+                    span: arg.span(),
+                    param: Some(param.identifier.span).filter(|span| *span != Span::default()),
+                });
+            }
+        }
+
+        Some((**return_type).clone())
+    }
+
+    fn check_function(&mut self, params: &[Param], return_type: &BaseType, body: &[Expr]) {
+        for param in params {
+            self.declare(&param.identifier, param.basetype.clone());
+        }
+
+        // loops don't carry over into nested functions
+        let loop_depth = std::mem::take(&mut self.loop_depth);
+        self.return_types.push(return_type.clone());
+        for expr in body {
+            self.check_expr(expr);
+        }
+        self.return_types.pop();
+        self.loop_depth = loop_depth;
+    }
+
+    // `Enum.Variant` is either a value of the enum, or a function building one
+    // when the variant has a payload
+    fn check_field(&mut self, value: &ValueExpr, field: &Identifier) -> Type {
+        // a member of a file the program uses, declared like any other value
+        if self.is_import(value) {
+            let id = self.resolutions.resolve(field)?;
+            return self.output.definitions.get(&id).cloned();
+        }
+
+        // the functions of a module can only be called, like other builtins
+        if let Some(module) = self.module(value) {
+            if module.constant(&field.name).is_some() {
+                return Some(BaseType::Float);
+            }
+
+            match module.function(&field.name) {
+                Some(builtin) => self.errors.push(TypeError::BuiltinValue {
+                    builtin,
+                    span: value.span().to(field.span),
+                }),
+                None => self.errors.push(TypeError::UnknownMember {
+                    module,
+                    member: field.clone(),
+                }),
+            }
+            return None;
+        }
+
+        if let ValueExpr::Identifier(identifier) = value {
+            let id = self.resolutions.resolve(identifier)?;
+            let definition = self.resolutions.definition(id);
+
+            if definition.kind == DefKind::Enum {
+                let enum_type = BaseType::Named(definition.name);
+                let variant = self
+                    .output
+                    .enums
+                    .get(&definition.name)?
+                    .iter()
+                    .find(|variant| variant.identifier.name == field.name);
+
+                let Some(variant) = variant else {
+                    self.errors.push(TypeError::UnknownVariant {
+                        enum_name: definition.name,
+                        variant: field.clone(),
+                    });
+
+                    return None;
+                };
+
+                if variant.payload.is_empty() {
+                    return Some(enum_type);
+                }
+
+                let params = variant
+                    .payload
+                    .iter()
+                    .enumerate()
+                    .map(|(index, basetype)| Param {
+                        identifier: Identifier {
+                            name: index.to_string().into(),
+                            span: variant.identifier.span,
+                            id: NodeId::default(),
+                        },
+                        basetype: basetype.clone(),
+                    })
+                    .collect();
+
+                return Some(BaseType::Function {
+                    params,
+                    return_type: Box::new(enum_type),
+                });
+            }
+        }
+
+        let found = self.check_value(value)?;
+        match Builtin::method(&found, &field.name) {
+            Some(builtin) => self.errors.push(TypeError::BuiltinValue {
+                builtin,
+                span: value.span().to(field.span),
+            }),
+            None => self.errors.push(TypeError::NoField {
+                found,
+                field: field.clone(),
+            }),
+        }
+
+        None
+    }
+
+    fn check_pattern(&mut self, pattern: &Pattern, expected: Type) {
+        let Some(expected) = expected else { return };
+
+        match pattern {
+            Pattern::Wildcard(_) => {}
+
+            Pattern::Binding(identifier) => self.declare(identifier, expected),
+
+            Pattern::Int { span, .. } => self.expect_type(&expected, Some(BaseType::Int), *span),
+            Pattern::String { span, .. } => {
+                self.expect_type(&expected, Some(BaseType::String), *span)
+            }
+            Pattern::Bool { span, .. } => self.expect_type(&expected, Some(BaseType::Bool), *span),
+
+            Pattern::Variant {
+                enum_name,
+                variant,
+                fields,
+                span,
+            } => {
+                let found = BaseType::Named(enum_name.name);
+                if found != expected {
+                    self.expect_type(&expected, Some(found), *span);
+                    return;
+                }
+
+                let Some(variants) = self.output.enums.get(&enum_name.name) else {
+                    return;
+                };
+
+                let Some(definition) = variants
+                    .iter()
+                    .find(|candidate| candidate.identifier.name == variant.name)
+                else {
+                    self.errors.push(TypeError::UnknownVariant {
+                        enum_name: enum_name.name,
+                        variant: variant.clone(),
+                    });
+                    return;
+                };
+
+                let payload = definition.payload.clone();
+                if payload.len() != fields.len() {
+                    self.errors.push(TypeError::PatternArity {
+                        expected: payload.len(),
+                        found: fields.len(),
+                        span: *span,
+                    });
+                }
+
+                for (field, basetype) in fields.iter().zip(payload) {
+                    self.check_pattern(field, Some(basetype));
+                }
+            }
+        }
+    }
+
+    // empty lists and maps take the type that's expected of them, anything
+    // else is checked as usual
+    fn check_value_as(&mut self, value: &ValueExpr, expected: &BaseType) -> Type {
+        let empty = match (value, expected) {
+            (ValueExpr::List { items, .. }, BaseType::List(_)) => items.is_empty(),
+            (ValueExpr::Map { entries, .. }, BaseType::Map(..)) => entries.is_empty(),
+            _ => false,
+        };
+
+        if !empty {
+            return self.check_value(value);
+        }
+
+        if let BaseType::Map(key, _) = expected {
+            if !key.is_hashable() {
+                self.errors.push(TypeError::NotHashable {
+                    found: (**key).clone(),
+                    span: value.span(),
+                });
+            }
+        }
+
+        self.output.values.insert(value.id(), expected.clone());
+        self.output.spans.insert(value.id(), value.span());
+        Some(expected.clone())
+    }
+
+    // every item has to be of the type of the first one
+    fn check_items<'v>(&mut self, items: impl Iterator<Item = &'v ValueExpr<'v>>) -> Type {
+        let mut expected: Type = None;
+        for item in items {
+            let found = match &expected {
+                Some(expected) => {
+                    let expected = expected.clone();
+                    let found = self.check_value_as(item, &expected);
+                    self.expect_type(&expected, found.clone(), item.span());
+                    found
+                }
+                None => self.check_value(item),
+            };
+
+            if expected.is_none() {
+                expected = found;
+            }
+        }
+
+        expected
+    }
+
+    fn check_value(&mut self, value: &ValueExpr) -> Type {
+        let found = self.infer_value(value);
+        if let Some(found) = &found {
+            self.output.values.insert(value.id(), found.clone());
+            self.output.spans.insert(value.id(), value.span());
+        }
+
+        found
+    }
+
+    fn infer_value(&mut self, value: &ValueExpr) -> Type {
+        match value {
+            ValueExpr::Int { .. } => Some(BaseType::Int),
+            ValueExpr::Float { .. } => Some(BaseType::Float),
+            ValueExpr::String { .. } => Some(BaseType::String),
+            ValueExpr::Bool { .. } => Some(BaseType::Bool),
+
+            ValueExpr::Identifier(identifier) => {
+                let id = self.resolutions.resolve(identifier)?;
+                match self.resolutions.definition(id).kind {
+                    DefKind::Builtin(builtin) => {
+                        self.errors.push(TypeError::BuiltinValue {
+                            builtin,
+                            span: identifier.span,
+                        });
+                        return None;
+                    }
+                    DefKind::Module(module) => {
+                        self.errors.push(TypeError::ModuleValue {
+                            module,
+                            span: identifier.span,
+                        });
+                        return None;
+                    }
+                    DefKind::Import(_) => {
+                        self.errors.push(TypeError::ImportValue(identifier.clone()));
+                        return None;
+                    }
+                    _ => {}
+                }
+
+                self.output.definitions.get(&id).cloned()
+            }
+
+            ValueExpr::Binary {
+                left,
+                right,
+                operator,
+                ..
+            } => self.check_binary(operator, left, right),
+
+            ValueExpr::Unary {
+                operand, operator, ..
+            } => self.check_unary(operator, operand),
+
+            ValueExpr::Call {
+                callee, args, span, ..
+            } => self.check_call(callee, args, *span),
+
+            ValueExpr::Cast {
+                value,
+                basetype,
+                span,
+                ..
+            } => {
+                let found = self.check_value(value)?;
+                if !can_cast(&found, basetype) {
+                    self.errors.push(TypeError::InvalidCast {
+                        from: found,
+                        to: basetype.clone(),
+                        span: *span,
+                    });
+                }
+
+                Some(basetype.clone())
+            }
+
+            ValueExpr::Field { value, field, .. } => self.check_field(value, field),
+
+            ValueExpr::Function {
+                params,
+                return_type,
+                body,
+                ..
+            } => {
+                self.check_function(params, return_type, body);
+                Some(Self::function_type(params, return_type))
+            }
+
+            ValueExpr::List { items, span, .. } => {
+                if items.is_empty() {
+                    self.errors.push(TypeError::EmptyCollection {
+                        map: false,
+                        span: *span,
+                    });
+                    return None;
+                }
+
+                let item = self.check_items(items.iter())?;
+                Some(BaseType::List(Box::new(item)))
+            }
+
+            ValueExpr::Map { entries, span, .. } => {
+                if entries.is_empty() {
+                    self.errors.push(TypeError::EmptyCollection {
+                        map: true,
+                        span: *span,
+                    });
+                    return None;
+                }
+
+                let key = self.check_items(entries.iter().map(|(key, _)| key));
+                let value = self.check_items(entries.iter().map(|(_, value)| value));
+
+                let key = key?;
+                if !key.is_hashable() {
+                    self.errors.push(TypeError::NotHashable {
+                        found: key,
+                        span: entries[0].0.span(),
+                    });
+                    return None;
+                }
+
+                Some(BaseType::Map(Box::new(key), Box::new(value?)))
+            }
+        }
+    }
+
+    fn declare(&mut self, identifier: &Identifier, basetype: BaseType) {
+        if let Some(id) = self.resolutions.defined_at(identifier) {
+            self.output.definitions.insert(id, basetype);
+        }
+    }
+
+    fn check_expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Declaration {
+                identifier,
+                basetype,
+                value,
+                ..
+            } => {
+                // declarations without a value always have a type
+                let Some(value) = value else {
+                    if let Some(basetype) = basetype {
+                        self.declare(identifier, basetype.clone());
+                    }
+                    return;
+                };
+
+                // functions are typed before their body is checked, so they can call themselves
+                if let ValueExpr::Function {
+                    params,
+                    return_type,
+                    ..
+                } = &**value
+                {
+                    self.declare(identifier, Self::function_type(params, return_type));
+                }
+
+                let declared = match basetype {
+                    Some(basetype) => {
+                        let found = self.check_value_as(value, basetype);
+                        self.expect_type(basetype, found, value.span());
+                        Some(basetype.clone())
+                    }
+                    None => self.check_value(value),
+                };
+
+                if let Some(declared) = declared {
+                    self.declare(identifier, declared);
+                }
+            }
+
+            Expr::Block { body, .. } | Expr::Test { body, .. } => {
+                for expr in body.iter() {
+                    self.check_expr(expr);
+                }
+            }
+
+            Expr::If {
+                condition,
+                body,
+                else_body,
+                ..
+            } => {
+                let found = self.check_value(condition);
+                self.expect_type(&BaseType::Bool, found, condition.span());
+
+                for expr in body.iter().chain(else_body.unwrap_or_default()) {
+                    self.check_expr(expr);
+                }
+            }
+
+            Expr::Assign {
+                target,
+                operator,
+                value,
+            } => {
+                let expected = self.check_value(target);
+                let found = match (operator, &expected) {
+                    (Some(operator), _) => self.check_binary(operator, target, value),
+                    (None, Some(expected)) => self.check_value_as(value, expected),
+                    (None, None) => self.check_value(value),
+                };
+
+                if let Some(expected) = expected {
+                    self.expect_type(&expected, found, value.span());
+                }
+            }
+
+            Expr::While {
+                condition, body, ..
+            } => {
+                let found = self.check_value(condition);
+                self.expect_type(&BaseType::Bool, found, condition.span());
+
+                self.loop_depth += 1;
+                for expr in body.iter() {
+                    self.check_expr(expr);
+                }
+                self.loop_depth -= 1;
+            }
+
+            Expr::Enum {
+                identifier,
+                variants,
+                ..
+            } => {
+                self.output.enums.insert(identifier.name, variants.to_vec());
+            }
+
+            Expr::Match { value, arms, .. } => {
+                let found = self.check_value(value);
+                for arm in arms.iter() {
+                    self.check_pattern(&arm.pattern, found.clone());
+                    for expr in arm.body {
+                        self.check_expr(expr);
+                    }
+                }
+            }
+
+            Expr::For {
+                binding,
+                start,
+                end,
+                body,
+                ..
+            } => {
+                let item = match end {
+                    Some(end) => {
+                        for bound in [start, end] {
+                            let found = self.check_value(bound);
+                            self.expect_type(&BaseType::Int, found, bound.span());
+                        }
+
+                        Some(BaseType::Int)
+                    }
+
+                    // lists give their items, maps their keys
+                    None => match self.check_value(start) {
+                        Some(BaseType::List(item) | BaseType::Map(item, _)) => Some(*item),
+                        Some(found) => {
+                            self.errors.push(TypeError::NotIterable {
+                                found,
+                                span: start.span(),
+                            });
+                            None
+                        }
+                        None => None,
+                    },
+                };
+
+                if let Some(item) = item {
+                    self.declare(binding, item);
+                }
+
+                self.loop_depth += 1;
+                for expr in body.iter() {
+                    self.check_expr(expr);
+                }
+                self.loop_depth -= 1;
+            }
+
+            // the error is the message of the runtime error
+            Expr::Try {
+                body,
+                error,
+                handler,
+                ..
+            } => {
+                for expr in body.iter() {
+                    self.check_expr(expr);
+                }
+
+                self.declare(error, BaseType::String);
+                for expr in handler.iter() {
+                    self.check_expr(expr);
+                }
+            }
+
+            Expr::Break(span) | Expr::Continue(span) => {
+                if self.loop_depth == 0 {
+                    self.errors.push(TypeError::LoopControlOutsideLoop(*span));
+                }
+            }
+
+            Expr::Value(value) => {
+                self.check_value(value);
+            }
+
+            Expr::Return { value, span } => {
+                let Some(expected) = self.return_types.last().cloned() else {
+                    self.errors.push(TypeError::ReturnOutsideFunction(*span));
+                    return;
+                };
+
+                match value {
+                    Some(value) => {
+                        let found = self.check_value_as(value, &expected);
+                        self.expect_type(&expected, found, value.span());
+                    }
+                    None => self.expect_type(&expected, Some(BaseType::Void), *span),
+                }
+            }
+        }
+    }
+
+    pub fn check(mut self, program: &Program) -> Result<Types, Vec<TypeError>> {
+        // top level functions and enums can be used before they're declared, so
+        // they need to be known upfront
+        for expr in program.statements() {
+            match expr {
+                Expr::Declaration {
+                    identifier,
+                    value:
+                        Some(ValueExpr::Function {
+                            params,
+                            return_type,
+                            ..
+                        }),
+                    ..
+                } => self.declare(identifier, Self::function_type(params, return_type)),
+                Expr::Enum { .. } => self.check_expr(expr),
+                _ => {}
+            }
+        }
+
+        for expr in program.statements() {
+            self.check_expr(expr);
+        }
+
+        if self.errors.is_empty() {
+            Ok(self.output)
+        } else {
+            Err(self.errors)
+        }
+    }
+}
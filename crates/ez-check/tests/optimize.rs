@@ -0,0 +1,131 @@
+// every file of `tests/optimize/` is a program in SSA form, optimized at the
+// level its `; RUN: -O<level>` line gives and printed. Its `; CHECK:` lines
+// have to be found in what's printed, in order, a `; CHECK-NEXT:` on the line
+// right after the previous one, and a `; CHECK-NOT:` nowhere between the lines
+// found around it
+
+use std::fs;
+use std::path::Path;
+
+use ez_check::optimize;
+use ez_check::ssa::Program;
+
+enum Directive<'a> {
+    Check(&'a str),
+    Next(&'a str),
+    Not(&'a str),
+}
+
+fn directives(source: &str) -> Vec<Directive<'_>> {
+    source
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix(';'))
+        .filter_map(|comment| {
+            let comment = comment.trim();
+            if let Some(text) = comment.strip_prefix("CHECK:") {
+                Some(Directive::Check(text.trim()))
+            } else if let Some(text) = comment.strip_prefix("CHECK-NEXT:") {
+                Some(Directive::Next(text.trim()))
+            } else {
+                comment
+                    .strip_prefix("CHECK-NOT:")
+                    .map(|text| Directive::Not(text.trim()))
+            }
+        })
+        .collect()
+}
+
+fn level(source: &str) -> u8 {
+    let run = source
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("; RUN: -O"));
+    run.map_or(optimize::MAX_LEVEL, |level| {
+        level.trim().parse().expect("the level should be a number")
+    })
+}
+
+// what's wrong with the output, if anything
+fn check(source: &str, output: &str) -> Option<String> {
+    let lines: Vec<&str> = output.lines().collect();
+    // the first line not looked at yet, and the `CHECK-NOT`s waiting for the
+    // next line found
+    let mut next = 0;
+    let mut not: Vec<&str> = vec![];
+
+    for directive in directives(source) {
+        match directive {
+            Directive::Check(text) => {
+                let Some(found) = (next..lines.len()).find(|index| lines[*index].contains(text))
+                else {
+                    return Some(format!("no line with `{}`", text));
+                };
+                if let Some(error) = absent(&lines[next..found], &mut not) {
+                    return Some(error);
+                }
+                next = found + 1;
+            }
+            Directive::Next(text) => {
+                if !lines.get(next).is_some_and(|line| line.contains(text)) {
+                    return Some(format!(
+                        "the line after the last one found has no `{}`",
+                        text
+                    ));
+                }
+                next += 1;
+            }
+            Directive::Not(text) => not.push(text),
+        }
+    }
+
+    absent(&lines[next..], &mut not)
+}
+
+// the `CHECK-NOT`s in the lines between two found, they're done with after
+fn absent(lines: &[&str], not: &mut Vec<&str>) -> Option<String> {
+    for line in lines {
+        if let Some(text) = not.iter().find(|text| line.contains(**text)) {
+            return Some(format!("found `{}` in `{}`", text, line));
+        }
+    }
+    not.clear();
+    None
+}
+
+#[test]
+fn optimize() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("optimize");
+    let mut files: Vec<_> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "ssa"))
+        .collect();
+    files.sort();
+    assert!(!files.is_empty(), "there should be programs to optimize");
+
+    let mut failures = vec![];
+    for file in files {
+        let source = fs::read_to_string(&file).unwrap();
+        let mut program = match Program::parse(&source) {
+            Ok(program) => program,
+            Err(error) => {
+                failures.push(format!("{}: {}", file.display(), error));
+                continue;
+            }
+        };
+        optimize::run(&mut program, level(&source));
+
+        let output = program.to_string();
+        if let Some(error) = check(&source, &output) {
+            failures.push(format!("{}: {}\n{}", file.display(), error, output));
+        }
+    }
+
+    assert!(
+        failures.is_empty(),
+        "{} program(s) aren't optimized as expected:\n{}",
+        failures.len(),
+        failures.join("\n")
+    );
+}
@@ -0,0 +1,633 @@
+/* the runtime of ez programs compiled to C, copied to the top of every file */
+#ifndef EZ_RUNTIME_H
+#define EZ_RUNTIME_H
+
+/* for `setenv`, `clock_gettime`, `nanosleep`, `strcasecmp` and sockets,
+   which C99 doesn't have */
+#define _POSIX_C_SOURCE 200112L
+
+#include <errno.h>
+#include <inttypes.h>
+#include <math.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <time.h>
+
+#include <netdb.h>
+#include <strings.h>
+#include <sys/socket.h>
+#include <unistd.h>
+
+/* strings are NUL terminated, the ones built at runtime are never freed */
+typedef const char *ez_string;
+
+static void ez_panic(const char *message) {
+    fprintf(stderr, "error: %s\n", message);
+    exit(101);
+}
+
+/* `panic` and `assert` of the program, they also say where they were called.
+   What was printed before goes out first */
+static inline void ez_user_panic(ez_string message, const char *location) {
+    fflush(stdout);
+    fprintf(stderr, "error: the program panicked: %s\n --> %s\n", message, location);
+    exit(101);
+}
+
+/* an empty message is the same as none */
+static inline void ez_assert(bool condition, ez_string message, const char *location) {
+    if (condition) {
+        return;
+    }
+    fflush(stdout);
+    if (*message) {
+        fprintf(stderr, "error: assertion failed: %s\n --> %s\n", message, location);
+    } else {
+        fprintf(stderr, "error: assertion failed\n --> %s\n", location);
+    }
+    exit(101);
+}
+
+/* the levels of the `log_*` builtins in order, the messages below the one in
+   `EZ_LOG` are dropped. It's read the first time something is logged */
+static const char *ez_log_levels[] = {"debug", "info", "warn", "error", "off"};
+
+static inline void ez_log(int level, ez_string text) {
+    static int threshold = -1;
+    if (threshold == -1) {
+        const char *name = getenv("EZ_LOG");
+        threshold = 1;
+        for (int i = 0; name && i < 5; i++) {
+            if (strcasecmp(name, ez_log_levels[i]) == 0) {
+                threshold = i;
+            }
+        }
+    }
+    if (level < threshold) {
+        return;
+    }
+    fflush(stdout);
+    fprintf(stderr, "[%s] %s\n", ez_log_levels[level], text);
+}
+
+/* functions returning a value never fall off their end */
+#define ez_unreachable() abort()
+
+/* like the interpreter, arithmetic on ints stops the program on overflow */
+static inline int64_t ez_add(int64_t a, int64_t b) {
+    if ((b > 0 && a > INT64_MAX - b) || (b < 0 && a < INT64_MIN - b)) {
+        ez_panic("attempt to compute `+` with overflow");
+    }
+    return a + b;
+}
+
+static inline int64_t ez_sub(int64_t a, int64_t b) {
+    if ((b < 0 && a > INT64_MAX + b) || (b > 0 && a < INT64_MIN + b)) {
+        ez_panic("attempt to compute `-` with overflow");
+    }
+    return a - b;
+}
+
+static inline int64_t ez_mul(int64_t a, int64_t b) {
+    bool overflow;
+    if (a > 0) {
+        overflow = b > 0 ? a > INT64_MAX / b : b < INT64_MIN / a;
+    } else {
+        overflow = b > 0 ? a < INT64_MIN / b : a != 0 && b < INT64_MAX / a;
+    }
+    if (overflow) {
+        ez_panic("attempt to compute `*` with overflow");
+    }
+    return a * b;
+}
+
+static inline int64_t ez_div(int64_t a, int64_t b) {
+    if (b == 0) {
+        ez_panic("attempt to divide by zero");
+    }
+    if (a == INT64_MIN && b == -1) {
+        ez_panic("attempt to compute `/` with overflow");
+    }
+    return a / b;
+}
+
+static inline int64_t ez_neg(int64_t a) {
+    if (a == INT64_MIN) {
+        ez_panic("attempt to compute `-` with overflow");
+    }
+    return -a;
+}
+
+/* the functions of `math` that work on ints, `math.h` has the ones on floats */
+static inline int64_t ez_abs(int64_t a) {
+    return a < 0 ? ez_neg(a) : a;
+}
+
+static inline int64_t ez_min(int64_t a, int64_t b) {
+    return b < a ? b : a;
+}
+
+static inline int64_t ez_max(int64_t a, int64_t b) {
+    return b > a ? b : a;
+}
+
+/* floats are truncated, and saturate at the bounds of an int */
+static inline int64_t ez_float_to_int(double value) {
+    if (value != value) {
+        return 0;
+    }
+    if (value >= 9223372036854775807.0) {
+        return INT64_MAX;
+    }
+    if (value <= -9223372036854775808.0) {
+        return INT64_MIN;
+    }
+    return (int64_t)value;
+}
+
+static inline ez_string ez_concat(ez_string a, ez_string b) {
+    size_t a_len = strlen(a), b_len = strlen(b);
+    char *result = malloc(a_len + b_len + 1);
+    if (!result) {
+        ez_panic("out of memory");
+    }
+    memcpy(result, a, a_len);
+    memcpy(result + a_len, b, b_len + 1);
+    return result;
+}
+
+static inline bool ez_string_eq(ez_string a, ez_string b) {
+    return strcmp(a, b) == 0;
+}
+
+static inline ez_string ez_int_to_string(int64_t value) {
+    char *result = malloc(24);
+    if (!result) {
+        ez_panic("out of memory");
+    }
+    snprintf(result, 24, "%" PRId64, value);
+    return result;
+}
+
+/* the way the interpreter prints them: the shortest digits reading back as
+   the same float, with a `.0` when they're a whole number so it doesn't look
+   like an int, and with an exponent like `1e16` or `1.5e-7` below 1e-4 and
+   from 1e16 on */
+static inline ez_string ez_float_to_string(double value) {
+    char *result = malloc(40);
+    if (!result) {
+        ez_panic("out of memory");
+    }
+    if (isnan(value)) {
+        strcpy(result, "NaN");
+        return result;
+    }
+    if (isinf(value)) {
+        strcpy(result, value < 0 ? "-inf" : "inf");
+        return result;
+    }
+    if (value == 0) {
+        strcpy(result, signbit(value) ? "-0.0" : "0.0");
+        return result;
+    }
+
+    /* 17 digits always read back */
+    char digits[40];
+    int precision = 0;
+    for (;; precision++) {
+        snprintf(digits, sizeof digits, "%.*e", precision, value);
+        if (precision == 16 || strtod(digits, NULL) == value) {
+            break;
+        }
+    }
+    char *exponent = strchr(digits, 'e');
+    int power = atoi(exponent + 1);
+
+    double magnitude = fabs(value);
+    if (magnitude < 1e-4 || magnitude >= 1e16) {
+        *exponent = '\0';
+        snprintf(result, 40, "%se%d", digits, power);
+        return result;
+    }
+
+    /* as many decimals as the digits after the first need */
+    snprintf(result, 40, "%.*f", precision > power ? precision - power : 0, value);
+    if (!strchr(result, '.')) {
+        strcat(result, ".0");
+    }
+    return result;
+}
+
+static inline ez_string ez_bool_to_string(bool value) {
+    return value ? "true" : "false";
+}
+
+/* the methods of strings, which count in characters rather than bytes, and
+   only change the case of ASCII letters */
+static inline bool ez_is_continuation(char c) {
+    return ((unsigned char)c & 0xc0) == 0x80;
+}
+
+static inline bool ez_is_space(char c) {
+    return c == ' ' || c == '\t' || c == '\n' || c == '\f' || c == '\r';
+}
+
+static inline int64_t ez_len(ez_string s) {
+    int64_t length = 0;
+    for (; *s; s++) {
+        length += !ez_is_continuation(*s);
+    }
+    return length;
+}
+
+/* the address of the character at the index, which is at most the length */
+static inline ez_string ez_char_at(ez_string s, int64_t index) {
+    for (; index > 0; index--) {
+        s++;
+        while (ez_is_continuation(*s)) {
+            s++;
+        }
+    }
+    return s;
+}
+
+static inline ez_string ez_substring(ez_string s, int64_t start, int64_t end) {
+    if (start < 0 || start > end || end > ez_len(s)) {
+        char message[96];
+        snprintf(message, sizeof message,
+                 "the substring from %" PRId64 " to %" PRId64 " is out of bounds", start, end);
+        ez_panic(message);
+    }
+
+    ez_string from = ez_char_at(s, start), to = ez_char_at(from, end - start);
+    char *result = malloc(to - from + 1);
+    if (!result) {
+        ez_panic("out of memory");
+    }
+    memcpy(result, from, to - from);
+    result[to - from] = '\0';
+    return result;
+}
+
+static inline bool ez_contains(ez_string s, ez_string part) {
+    return strstr(s, part) != NULL;
+}
+
+static inline ez_string ez_copy(ez_string s, size_t length) {
+    char *result = malloc(length + 1);
+    if (!result) {
+        ez_panic("out of memory");
+    }
+    memcpy(result, s, length);
+    result[length] = '\0';
+    return result;
+}
+
+static inline ez_string ez_to_upper(ez_string s) {
+    char *result = (char *)ez_copy(s, strlen(s));
+    for (char *c = result; *c; c++) {
+        if (*c >= 'a' && *c <= 'z') {
+            *c -= 'a' - 'A';
+        }
+    }
+    return result;
+}
+
+static inline ez_string ez_to_lower(ez_string s) {
+    char *result = (char *)ez_copy(s, strlen(s));
+    for (char *c = result; *c; c++) {
+        if (*c >= 'A' && *c <= 'Z') {
+            *c += 'a' - 'A';
+        }
+    }
+    return result;
+}
+
+static inline ez_string ez_trim(ez_string s) {
+    while (ez_is_space(*s)) {
+        s++;
+    }
+    size_t length = strlen(s);
+    while (length > 0 && ez_is_space(s[length - 1])) {
+        length--;
+    }
+    return ez_copy(s, length);
+}
+
+static void ez_invalid_number(ez_string s, const char *type) {
+    char *message = malloc(strlen(s) + 32);
+    if (!message) {
+        ez_panic("out of memory");
+    }
+    sprintf(message, "`%s` isn't a valid `%s`", s, type);
+    ez_panic(message);
+}
+
+/* only plain decimal numbers, without spaces around them */
+static inline int64_t ez_parse_int(ez_string s) {
+    char *end;
+    errno = 0;
+    long long value = strtoll(s, &end, 10);
+    if (*s == '\0' || ez_is_space(*s) || *end != '\0' || errno == ERANGE) {
+        ez_invalid_number(s, "int");
+    }
+    return value;
+}
+
+static inline double ez_parse_float(ez_string s) {
+    char *end;
+    double value = strtod(s, &end);
+    if (*s == '\0' || ez_is_space(*s) || *end != '\0' || strpbrk(s, "xXiInNpP")) {
+        ez_invalid_number(s, "float");
+    }
+    return value;
+}
+
+/* sockets are the file descriptors of the system */
+static void ez_network_error(const char *action, const char *reason) {
+    char *message = malloc(strlen(action) + strlen(reason) + 16);
+    if (!message) {
+        ez_panic("out of memory");
+    }
+    sprintf(message, "couldn't %s: %s", action, reason);
+    ez_panic(message);
+}
+
+static int64_t ez_tcp_open(ez_string host, int64_t port, bool listening) {
+    char *action = malloc(strlen(host) + 48);
+    if (!action) {
+        ez_panic("out of memory");
+    }
+    sprintf(action, "%s `%s:%" PRId64 "`", listening ? "listen on" : "connect to", host, port);
+    if (port < 0 || port > 65535) {
+        ez_network_error(action, "ports go from 0 to 65535");
+    }
+
+    char service[8];
+    snprintf(service, sizeof service, "%" PRId64, port);
+    struct addrinfo hints = {0}, *addresses;
+    hints.ai_family = AF_UNSPEC;
+    hints.ai_socktype = SOCK_STREAM;
+    hints.ai_flags = listening ? AI_PASSIVE : 0;
+    int failed = getaddrinfo(host, service, &hints, &addresses);
+    if (failed) {
+        ez_network_error(action, gai_strerror(failed));
+    }
+
+    /* the first address that works is used */
+    int socket_fd = -1;
+    for (struct addrinfo *address = addresses; address; address = address->ai_next) {
+        socket_fd = socket(address->ai_family, address->ai_socktype, address->ai_protocol);
+        if (socket_fd < 0) {
+            continue;
+        }
+        if (listening) {
+            int reuse = 1;
+            setsockopt(socket_fd, SOL_SOCKET, SO_REUSEADDR, &reuse, sizeof reuse);
+            if (bind(socket_fd, address->ai_addr, address->ai_addrlen) == 0 &&
+                listen(socket_fd, 16) == 0) {
+                break;
+            }
+        } else if (connect(socket_fd, address->ai_addr, address->ai_addrlen) == 0) {
+            break;
+        }
+        close(socket_fd);
+        socket_fd = -1;
+    }
+    int error = errno;
+    freeaddrinfo(addresses);
+    if (socket_fd < 0) {
+        ez_network_error(action, strerror(error));
+    }
+
+    free(action);
+    return socket_fd;
+}
+
+static inline int64_t ez_tcp_connect(ez_string host, int64_t port) {
+    return ez_tcp_open(host, port, false);
+}
+
+static inline int64_t ez_tcp_listen(ez_string host, int64_t port) {
+    return ez_tcp_open(host, port, true);
+}
+
+static void ez_socket_error(const char *action, int64_t socket_fd) {
+    char message[64];
+    snprintf(message, sizeof message, "%s %" PRId64, action, socket_fd);
+    ez_network_error(message, strerror(errno));
+}
+
+/* waits for the next connection */
+static inline int64_t ez_tcp_accept(int64_t listener) {
+    int connection = accept((int)listener, NULL, NULL);
+    if (connection < 0) {
+        ez_socket_error("accept a connection on", listener);
+    }
+    return connection;
+}
+
+/* what arrived, at most 4096 bytes of it, and nothing once the other side
+   closed the connection */
+static inline ez_string ez_read(int64_t connection) {
+    char *buffer = malloc(4097);
+    if (!buffer) {
+        ez_panic("out of memory");
+    }
+    ssize_t length = recv((int)connection, buffer, 4096, 0);
+    if (length < 0) {
+        ez_socket_error("use the connection", connection);
+    }
+    buffer[length] = '\0';
+    return buffer;
+}
+
+static inline void ez_write(int64_t connection, ez_string text) {
+    size_t length = strlen(text);
+    while (length > 0) {
+        ssize_t written = send((int)connection, text, length, 0);
+        if (written < 0) {
+            ez_socket_error("use the connection", connection);
+        }
+        text += written;
+        length -= (size_t)written;
+    }
+}
+
+static inline void ez_close(int64_t socket_fd) {
+    if (close((int)socket_fd) != 0) {
+        ez_socket_error("close", socket_fd);
+    }
+}
+
+/* variables that aren't set are empty, like in a shell */
+static inline ez_string ez_env_get(ez_string name) {
+    const char *value = getenv(name);
+    return value ? value : "";
+}
+
+static inline void ez_env_set(ez_string name, ez_string value) {
+    if (*name == '\0' || strchr(name, '=') || setenv(name, value, 1) != 0) {
+        char *message = malloc(strlen(name) + 64);
+        if (!message) {
+            ez_panic("out of memory");
+        }
+        sprintf(message, "`%s` isn't a valid name for an environment variable", name);
+        ez_panic(message);
+    }
+}
+
+/* milliseconds since the Unix epoch */
+static inline int64_t ez_now(void) {
+    struct timespec now;
+    clock_gettime(CLOCK_REALTIME, &now);
+    return (int64_t)now.tv_sec * 1000 + now.tv_nsec / 1000000;
+}
+
+/* seconds that only go forward, counted from the first call rather than from the
+   start of the program, which only matters to programs printing the time itself */
+static inline double ez_clock(void) {
+    static struct timespec start;
+    static int started = 0;
+    struct timespec now;
+    clock_gettime(CLOCK_MONOTONIC, &now);
+    if (!started) {
+        start = now;
+        started = 1;
+    }
+    return (double)(now.tv_sec - start.tv_sec) + (double)(now.tv_nsec - start.tv_nsec) / 1e9;
+}
+
+/* sleeping for a negative time doesn't sleep at all */
+static inline void ez_sleep(int64_t millis) {
+    struct timespec time;
+    if (millis <= 0) {
+        return;
+    }
+    time.tv_sec = millis / 1000;
+    time.tv_nsec = (millis % 1000) * 1000000;
+    while (nanosleep(&time, &time) != 0 && errno == EINTR) {
+    }
+}
+
+/* splitmix64 like the interpreter, so a seed gives the same numbers. It's
+   seeded from the time on the first call unless the program calls `seed` */
+static uint64_t ez_rng_state;
+static bool ez_rng_seeded = false;
+
+static inline void ez_seed(int64_t seed) {
+    ez_rng_state = (uint64_t)seed;
+    ez_rng_seeded = true;
+}
+
+static inline uint64_t ez_rng_next(void) {
+    if (!ez_rng_seeded) {
+        struct timespec now;
+        clock_gettime(CLOCK_REALTIME, &now);
+        ez_seed((int64_t)((uint64_t)now.tv_sec * 1000000000u + (uint64_t)now.tv_nsec));
+    }
+    ez_rng_state += 0x9e3779b97f4a7c15u;
+    uint64_t z = ez_rng_state;
+    z = (z ^ (z >> 30)) * 0xbf58476d1ce4e5b9u;
+    z = (z ^ (z >> 27)) * 0x94d049bb133111ebu;
+    return z ^ (z >> 31);
+}
+
+static inline double ez_random(void) {
+    return (double)(ez_rng_next() >> 11) / 9007199254740992.0;
+}
+
+/* the numbers below the first multiple of the count are thrown away, so every
+   one is as likely */
+static inline int64_t ez_random_int(int64_t start, int64_t end) {
+    if (start >= end) {
+        char message[96];
+        snprintf(message, sizeof message, "there are no ints from %" PRId64 " up to %" PRId64,
+                 start, end);
+        ez_panic(message);
+    }
+    uint64_t count = (uint64_t)end - (uint64_t)start;
+    uint64_t skip = -count % count;
+    for (;;) {
+        uint64_t number = ez_rng_next();
+        if (number >= skip) {
+            return (int64_t)((uint64_t)start + number % count);
+        }
+    }
+}
+
+/* files are read and written whole, a failure stops the program */
+static void ez_io_error(const char *verb, ez_string path) {
+    const char *reason = strerror(errno);
+    char *message = malloc(strlen(path) + strlen(reason) + 32);
+    if (!message) {
+        ez_panic("out of memory");
+    }
+    sprintf(message, "couldn't %s `%s`: %s", verb, path, reason);
+    ez_panic(message);
+}
+
+static inline ez_string ez_read_file(ez_string path) {
+    FILE *file = fopen(path, "rb");
+    if (!file) {
+        ez_io_error("read", path);
+    }
+
+    size_t length = 0, capacity = 4096;
+    char *contents = malloc(capacity);
+    for (;;) {
+        if (!contents) {
+            ez_panic("out of memory");
+        }
+        length += fread(contents + length, 1, capacity - length - 1, file);
+        if (length < capacity - 1) {
+            break;
+        }
+        capacity *= 2;
+        contents = realloc(contents, capacity);
+    }
+
+    if (ferror(file)) {
+        ez_io_error("read", path);
+    }
+    fclose(file);
+    contents[length] = '\0';
+    return contents;
+}
+
+static inline void ez_write_file(ez_string path, ez_string contents) {
+    FILE *file = fopen(path, "wb");
+    if (!file) {
+        ez_io_error("write", path);
+    }
+    size_t length = strlen(contents);
+    if (fwrite(contents, 1, length, file) != length || fclose(file) != 0) {
+        ez_io_error("write", path);
+    }
+}
+
+/* what `print` and `println` compile to, they aren't static so the startup code
+   of the llvm backend can provide them too */
+void ez_print_string(ez_string value, bool newline) {
+    fputs(value, stdout);
+    if (newline) {
+        putchar('\n');
+    }
+}
+
+void ez_print_int(int64_t value, bool newline) {
+    ez_print_string(ez_int_to_string(value), newline);
+}
+
+void ez_print_float(double value, bool newline) {
+    ez_print_string(ez_float_to_string(value), newline);
+}
+
+void ez_print_bool(bool value, bool newline) {
+    ez_print_string(ez_bool_to_string(value), newline);
+}
+
+#endif
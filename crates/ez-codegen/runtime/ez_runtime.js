@@ -0,0 +1,509 @@
+// the runtime of ez programs compiled to JavaScript, copied to the top of every
+// file. Ints are BigInts, and like in the interpreter, arithmetic on them throws
+// when the result doesn't fit in 64 bits.
+"use strict";
+
+function $int(value, operator) {
+    if (value !== BigInt.asIntN(64, value)) {
+        throw new RangeError(`attempt to compute \`${operator}\` with overflow`);
+    }
+    return value;
+}
+
+function $div(a, b) {
+    if (b === 0n) {
+        throw new RangeError("attempt to divide by zero");
+    }
+    return $int(a / b, "/");
+}
+
+// floats are truncated, and saturate at the bounds of an int
+function $floatToInt(value) {
+    if (Number.isNaN(value)) {
+        return 0n;
+    }
+    if (value >= 9223372036854775807) {
+        return 9223372036854775807n;
+    }
+    if (value <= -9223372036854775808) {
+        return -9223372036854775808n;
+    }
+    return BigInt(Math.trunc(value));
+}
+
+// like the interpreter, the shortest digits with a `.0` when they're a whole
+// number, so they don't look like ints, and with an exponent like `1e16` or
+// `1.5e-7` below 1e-4 and from 1e16 on, where JavaScript has its own limits
+function $floatToString(value) {
+    if (value === Infinity || value === -Infinity) {
+        return value > 0 ? "inf" : "-inf";
+    }
+    if (value === 0) {
+        return Object.is(value, -0) ? "-0.0" : "0.0";
+    }
+    const magnitude = Math.abs(value);
+    if (magnitude < 1e-4 || magnitude >= 1e16) {
+        return value.toExponential().replace("e+", "e");
+    }
+    return Number.isInteger(value) ? value.toFixed(1) : String(value);
+}
+
+function $abs(value) {
+    if (typeof value === "bigint") {
+        return $int(value < 0n ? -value : value, "-");
+    }
+    return Math.abs(value);
+}
+
+// like the interpreter, a NaN is only the result when both values are NaN
+function $min(a, b) {
+    return a !== a || b < a ? b : a;
+}
+
+function $max(a, b) {
+    return a !== a || b > a ? b : a;
+}
+
+// the methods of strings count in characters rather than UTF-16 units, and only
+// change the case of ASCII letters. Lists are arrays and maps are `Map`s, which
+// keep the order their keys were inserted in, like the interpreter
+function $len(value) {
+    if (typeof value === "string") {
+        return BigInt([...value].length);
+    }
+    return BigInt(value instanceof Map ? value.size : value.length);
+}
+
+function $substring(value, start, end) {
+    const chars = [...value];
+    if (start < 0n || start > end || end > BigInt(chars.length)) {
+        throw new RangeError(`the substring from ${start} to ${end} is out of bounds`);
+    }
+    return chars.slice(Number(start), Number(end)).join("");
+}
+
+function $contains(value, part) {
+    if (typeof value === "string") {
+        return value.includes(part);
+    }
+    if (value instanceof Map) {
+        return value.has(part);
+    }
+    return value.some((item) => $eq(item, part));
+}
+
+function $toUpper(value) {
+    return value.replace(/[a-z]+/g, (letters) => letters.toUpperCase());
+}
+
+function $toLower(value) {
+    return value.replace(/[A-Z]+/g, (letters) => letters.toLowerCase());
+}
+
+function $trim(value) {
+    return value.replace(/^[ \t\n\f\r]+|[ \t\n\f\r]+$/g, "");
+}
+
+// only plain decimal numbers, without spaces around them
+function $parseInt(value) {
+    if (/^[+-]?[0-9]+$/.test(value)) {
+        const int = BigInt(value);
+        if (int === BigInt.asIntN(64, int)) {
+            return int;
+        }
+    }
+    throw new RangeError(`\`${value}\` isn't a valid \`int\``);
+}
+
+function $parseFloat(value) {
+    if (/^[+-]?([0-9]+\.?[0-9]*|\.[0-9]+)([eE][+-]?[0-9]+)?$/.test(value)) {
+        return Number(value);
+    }
+    throw new RangeError(`\`${value}\` isn't a valid \`float\``);
+}
+
+function $split(value, separator) {
+    return separator === "" ? [...value] : value.split(separator);
+}
+
+function $index(list, index) {
+    if (index < 0n || index >= BigInt(list.length)) {
+        throw new RangeError(`the index ${index} is out of bounds`);
+    }
+    return Number(index);
+}
+
+function $missingKey(key) {
+    return new RangeError(`the key ${$showNested(key)} isn't in the map`);
+}
+
+function $push(list, item) {
+    list.push(item);
+}
+
+function $pop(list) {
+    if (list.length === 0) {
+        throw new RangeError("attempt to pop from an empty list");
+    }
+    return list.pop();
+}
+
+function $get(collection, key) {
+    if (collection instanceof Map) {
+        if (!collection.has(key)) {
+            throw $missingKey(key);
+        }
+        return collection.get(key);
+    }
+    return collection[$index(collection, key)];
+}
+
+function $set(list, index, item) {
+    list[$index(list, index)] = item;
+}
+
+function $insert(map, key, value) {
+    map.set(key, value);
+}
+
+function $remove(collection, key) {
+    const value = $get(collection, key);
+    if (collection instanceof Map) {
+        collection.delete(key);
+    } else {
+        collection.splice(Number(key), 1);
+    }
+    return value;
+}
+
+function $keys(map) {
+    return [...map.keys()];
+}
+
+// lists and maps are printed like they're written, with the strings in them quoted
+function $show(value) {
+    if (Array.isArray(value)) {
+        return `[${value.map($showNested).join(", ")}]`;
+    }
+    if (value.size === 0) {
+        return "[:]";
+    }
+    const entries = [...value].map(([key, item]) => `${$showNested(key)}: ${$showNested(item)}`);
+    return `[${entries.join(", ")}]`;
+}
+
+function $showNested(value) {
+    switch (typeof value) {
+        case "string":
+            return JSON.stringify(value);
+        case "number":
+            return $floatToString(value);
+        case "object":
+            return $show(value);
+        default:
+            return String(value);
+    }
+}
+
+// the arguments after the script, in Node
+function $args() {
+    return typeof process !== "undefined" && process.argv ? process.argv.slice(2) : [];
+}
+
+// waits for the command to finish, what it printed is kept rather than shown.
+// The status of a command stopped by a signal is -1
+function $runCommand(command, args) {
+    if (typeof require === "undefined") {
+        throw new Error(`couldn't run \`${command}\`: there are no processes here`);
+    }
+    const result = require("child_process").spawnSync(command, args, { encoding: "utf8" });
+    if (result.error) {
+        throw new Error(`couldn't run \`${command}\`: ${result.error.message}`);
+    }
+    return new Map([
+        ["status", String(result.status ?? -1)],
+        ["stdout", result.stdout],
+        ["stderr", result.stderr],
+    ]);
+}
+
+// into the variants of the enum `Json`. Objects keep the order of their keys,
+// except that JavaScript puts the ones that look like indices first
+function $jsonParse(text) {
+    let parsed;
+    try {
+        parsed = JSON.parse(text);
+    } catch (error) {
+        throw new SyntaxError(`invalid JSON: ${error.message}`);
+    }
+    return $fromJson(parsed);
+}
+
+function $fromJson(value) {
+    if (value === null) {
+        return $variant("Json", "Null", 0, []);
+    }
+    switch (typeof value) {
+        case "boolean":
+            return $variant("Json", "Bool", 1, [value]);
+        case "number":
+            return $variant("Json", "Number", 2, [value]);
+        case "string":
+            return $variant("Json", "String", 3, [value]);
+    }
+    if (Array.isArray(value)) {
+        return $variant("Json", "List", 4, [value.map($fromJson)]);
+    }
+    const entries = Object.entries(value).map(([key, item]) => [key, $fromJson(item)]);
+    return $variant("Json", "Object", 5, [new Map(entries)]);
+}
+
+// without spaces, like the interpreter. Floats that JSON can't hold, like
+// infinity, are written as `null`
+function $jsonStringify(value) {
+    switch (typeof value) {
+        case "bigint":
+        case "boolean":
+            return String(value);
+        case "number":
+            return Number.isFinite(value) ? $floatToString(value) : "null";
+        case "string":
+            return JSON.stringify(value);
+    }
+    if (Array.isArray(value)) {
+        return `[${value.map($jsonStringify).join(",")}]`;
+    }
+    if (value instanceof Map) {
+        const entries = [...value].map(([key, item]) => `${JSON.stringify(key)}:${$jsonStringify(item)}`);
+        return `{${entries.join(",")}}`;
+    }
+    // the variants of `Json` other than `Null` have a single field
+    return value.fields.length === 0 ? "null" : $jsonStringify(value.fields[0]);
+}
+
+// patterns are only compiled the first time they're used, like in the
+// interpreter. JavaScript's regexes are close to the ones of Rust's regex crate,
+// but not the same, named groups are `(?<name>...)` in both
+const $regexes = new Map();
+
+function $regex(pattern) {
+    let regex = $regexes.get(pattern);
+    if (regex === undefined) {
+        try {
+            regex = new RegExp(pattern, "gu");
+        } catch (error) {
+            throw new SyntaxError(`\`${pattern}\` isn't a valid regex: ${error.message}`);
+        }
+        $regexes.set(pattern, regex);
+    }
+    return regex;
+}
+
+// `search` and `match` ignore where the last global search stopped
+function $regexMatch(pattern, text) {
+    return text.search($regex(pattern)) !== -1;
+}
+
+function $regexFindAll(pattern, text) {
+    return text.match($regex(pattern)) ?? [];
+}
+
+function $regexReplace(pattern, text, replacement) {
+    return text.replace($regex(pattern), replacement);
+}
+
+// `panic` and `assert` of the program, they also say where they were called
+function $panic(message, location) {
+    throw new Error(`the program panicked: ${message}\n --> ${location}`);
+}
+
+function $assert(condition, message, location) {
+    if (!condition) {
+        const detail = message === undefined ? "" : `: ${message}`;
+        throw new Error(`assertion failed${detail}\n --> ${location}`);
+    }
+}
+
+// what the handler of a `try` gets, the message without where it happened
+function $caught(error) {
+    return String(error?.message ?? error).split("\n --> ")[0];
+}
+
+// Node only has sockets that are used with callbacks, which ez programs can't
+// wait for
+function $sockets() {
+    throw new Error("sockets can't be used in JavaScript, run the program with `ez run` instead");
+}
+
+// variables that aren't set are empty, like in a shell. Outside of Node there's
+// no environment, so nothing is ever set
+function $envGet(name) {
+    const env = typeof process !== "undefined" && process.env ? process.env : {};
+    return env[name] ?? "";
+}
+
+function $envSet(name, value) {
+    if (name === "" || name.includes("=") || name.includes("\0") || value.includes("\0")) {
+        throw new RangeError(`\`${name}\` isn't a valid name for an environment variable`);
+    }
+    if (typeof process !== "undefined" && process.env) {
+        process.env[name] = value;
+    }
+}
+
+// milliseconds since the Unix epoch
+function $now() {
+    return BigInt(Date.now());
+}
+
+// seconds since the program started, which only go forward
+function $clock() {
+    return performance.now() / 1000;
+}
+
+// JavaScript can't block, but waiting on a shared buffer that's never notified
+// does the same
+function $sleep(millis) {
+    if (millis > 0n) {
+        Atomics.wait(new Int32Array(new SharedArrayBuffer(4)), 0, 0, Number(millis));
+    }
+}
+
+// splitmix64 like the interpreter, so a seed gives the same numbers. The state
+// is kept in 64 bits by hand
+let $rngState = BigInt.asUintN(64, BigInt(Date.now()) * 1000000n);
+
+function $seed(seed) {
+    $rngState = BigInt.asUintN(64, seed);
+}
+
+function $rngNext() {
+    $rngState = BigInt.asUintN(64, $rngState + 0x9e3779b97f4a7c15n);
+    let z = $rngState;
+    z = BigInt.asUintN(64, (z ^ (z >> 30n)) * 0xbf58476d1ce4e5b9n);
+    z = BigInt.asUintN(64, (z ^ (z >> 27n)) * 0x94d049bb133111ebn);
+    return z ^ (z >> 31n);
+}
+
+function $random() {
+    return Number($rngNext() >> 11n) / 2 ** 53;
+}
+
+// the numbers below the first multiple of the count are thrown away, so every
+// one is as likely
+function $randomInt(start, end) {
+    if (start >= end) {
+        throw new RangeError(`there are no ints from ${start} up to ${end}`);
+    }
+    const count = end - start;
+    const skip = BigInt.asUintN(64, -count) % count;
+    for (;;) {
+        const number = $rngNext();
+        if (number >= skip) {
+            return start + (number % count);
+        }
+    }
+}
+
+// files can only be used where there's a file system to use, like in Node
+function $fs(path, verb) {
+    if (typeof require === "undefined") {
+        throw new Error(`couldn't ${verb} \`${path}\`: there are no files here`);
+    }
+    return require("fs");
+}
+
+function $readFile(path) {
+    const fs = $fs(path, "read");
+    try {
+        return fs.readFileSync(path, "utf8");
+    } catch (error) {
+        throw new Error(`couldn't read \`${path}\`: ${error.message}`);
+    }
+}
+
+function $writeFile(path, contents) {
+    const fs = $fs(path, "write");
+    try {
+        fs.writeFileSync(path, contents);
+    } catch (error) {
+        throw new Error(`couldn't write \`${path}\`: ${error.message}`);
+    }
+}
+
+// lines end with `\n` or `\r\n`, the last one doesn't need to
+function $readLines(path) {
+    const lines = $readFile(path)
+        .split("\n")
+        .map((line) => (line.endsWith("\r") ? line.slice(0, -1) : line));
+    if (lines[lines.length - 1] === "") {
+        lines.pop();
+    }
+    return lines;
+}
+
+function $writeLines(path, lines) {
+    $writeFile(path, lines.map((line) => line + "\n").join(""));
+}
+
+function $variant(enumName, variant, index, fields) {
+    return { enumName, variant, index, fields };
+}
+
+// variants are equal when their fields are, lists when their items are and
+// maps when they have the same entries
+function $eq(a, b) {
+    if (typeof a !== "object" || typeof b !== "object") {
+        return a === b;
+    }
+    if (Array.isArray(a)) {
+        return a.length === b.length && a.every((item, index) => $eq(item, b[index]));
+    }
+    if (a instanceof Map) {
+        return a.size === b.size && [...a].every(([key, value]) => b.has(key) && $eq(value, b.get(key)));
+    }
+    return (
+        a.index === b.index &&
+        a.fields.length === b.fields.length &&
+        a.fields.every((field, index) => $eq(field, b.fields[index]))
+    );
+}
+
+// Node has a stdout to write to, browsers only have the console, which takes
+// whole lines
+let $line = "";
+
+function $print(text, newline) {
+    if (typeof process !== "undefined" && process.stdout) {
+        process.stdout.write(newline ? text + "\n" : text);
+        return;
+    }
+
+    $line += text;
+    if (newline) {
+        console.log($line);
+        $line = "";
+    }
+}
+
+// the levels of the `log_*` builtins in order, the messages below the one in
+// `EZ_LOG` are dropped. It's read the first time something is logged
+const $logLevels = ["debug", "info", "warn", "error", "off"];
+let $logLevel;
+
+function $log(level, text) {
+    if ($logLevel === undefined) {
+        const index = $logLevels.indexOf($envGet("EZ_LOG").toLowerCase());
+        $logLevel = index === -1 ? 1 : index;
+    }
+    if (level < $logLevel) {
+        return;
+    }
+
+    const line = `[${$logLevels[level]}] ${text}`;
+    if (typeof process !== "undefined" && process.stderr) {
+        process.stderr.write(line + "\n");
+        return;
+    }
+    console.error(line);
+}
@@ -0,0 +1,10 @@
+/* the startup code of executables built with the llvm backend, which compiles
+   the top level of the program to `ez_main`. The runtime brings the functions
+   printing values. */
+#include "ez_runtime.h"
+
+int ez_main(void);
+
+int main(void) {
+    return ez_main();
+}
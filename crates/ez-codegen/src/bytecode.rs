@@ -0,0 +1,1243 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use ez_ast::BaseType;
+use ez_check::cfg::BlockId;
+use ez_check::escape;
+use ez_check::hir::Pattern;
+use ez_check::resolver::{Builtin, DefId};
+use ez_check::ssa::{self, Constant, Op, Terminator, ValueId};
+use ez_diagnostics::SourceMap;
+use ez_lexer::{Span, Symbol, TokenKind};
+
+// the instructions of a virtual machine with registers, compiled from the SSA
+// form: every instruction names the registers it reads and the one it writes,
+// so there's none of the pushing and popping a stack machine does to get its
+// operands. A register holds one value of the function at a time, values that
+// are never needed at the same time share one
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Register(pub usize);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinaryOp {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Equals,
+    NotEquals,
+    Less,
+    LessOrEquals,
+    Greater,
+    GreaterOrEquals,
+    BitAnd,
+    BitOr,
+    BitXor,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnaryOp {
+    Negate,
+    Not,
+    BitNot,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Instr {
+    // loads a constant of the pool of the module
+    Const {
+        to: Register,
+        constant: usize,
+    },
+    Move {
+        to: Register,
+        from: Register,
+    },
+    Binary {
+        op: BinaryOp,
+        to: Register,
+        left: Register,
+        right: Register,
+    },
+    // with a constant of the pool on the right, the peephole pass fuses a
+    // `const` with the instruction using it into one
+    BinaryConst {
+        op: BinaryOp,
+        to: Register,
+        left: Register,
+        constant: usize,
+    },
+    Unary {
+        op: UnaryOp,
+        to: Register,
+        operand: Register,
+    },
+    // converts the value to the type
+    Cast {
+        to: Register,
+        from: Register,
+        basetype: BaseType,
+    },
+    // calls to functions returning nothing write no register
+    Call {
+        to: Option<Register>,
+        callee: Register,
+        args: Vec<Register>,
+    },
+    Builtin {
+        to: Option<Register>,
+        builtin: Builtin,
+        args: Vec<Register>,
+    },
+    // a function of the module, with the locals it captures. Closures, lists
+    // and maps nothing reaches once the function returns go in its frame
+    // rather than on the heap
+    Closure {
+        to: Register,
+        function: usize,
+        frame: bool,
+    },
+    Variant {
+        to: Register,
+        enum_name: Symbol,
+        index: usize,
+    },
+    List {
+        to: Register,
+        items: Vec<Register>,
+        frame: bool,
+    },
+    Map {
+        to: Register,
+        entries: Vec<(Register, Register)>,
+        frame: bool,
+    },
+    // the locals kept in memory, the ones closures capture and the ones of the
+    // functions around
+    Load {
+        to: Register,
+        local: DefId,
+    },
+    Store {
+        local: DefId,
+        from: Register,
+    },
+    // the value of a parameter, of what a pattern bound or of the error a
+    // handler caught
+    Bind {
+        to: Register,
+        local: DefId,
+    },
+    // the targets are the positions of instructions in the function
+    Jump(usize),
+    JumpIf {
+        condition: Register,
+        target: usize,
+    },
+    JumpUnless {
+        condition: Register,
+        target: usize,
+    },
+    // a comparison and a jump on it, when its result is `when`
+    JumpCompare {
+        op: BinaryOp,
+        left: Register,
+        right: Register,
+        target: usize,
+        when: bool,
+    },
+    // to the target of the first pattern matching the value
+    Match {
+        value: Register,
+        arms: Vec<(Pattern, usize)>,
+    },
+    // when an instruction between it and the next jump, return or `try` fails,
+    // to the handler instead
+    Try(usize),
+    Return(Option<Register>),
+}
+
+// a line of one of the files of the module, from 1
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Line {
+    pub file: usize,
+    pub line: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    pub name: String,
+    // the locals the arguments are bound to, in order
+    pub params: Vec<DefId>,
+    pub registers: usize,
+    pub code: Vec<Instr>,
+    // the line each instruction comes from, none for the moves and jumps
+    // between blocks and what else the compiler makes up
+    pub lines: Vec<Option<Line>>,
+}
+
+// the functions are in the order of the SSA form, the program's body first
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Module {
+    pub constants: Vec<Constant>,
+    pub functions: Vec<Function>,
+    // the names of the files the lines are in
+    pub files: Vec<String>,
+    // to print the locals and the variants, like the SSA form
+    pub locals: HashMap<DefId, Symbol>,
+    pub enums: HashMap<Symbol, Vec<(Symbol, Vec<BaseType>)>>,
+}
+
+impl BinaryOp {
+    fn from_token(operator: &TokenKind) -> BinaryOp {
+        match operator {
+            TokenKind::Plus => BinaryOp::Add,
+            TokenKind::Minus => BinaryOp::Subtract,
+            TokenKind::Times => BinaryOp::Multiply,
+            TokenKind::DividedBy => BinaryOp::Divide,
+            TokenKind::Equals => BinaryOp::Equals,
+            TokenKind::NotEquals => BinaryOp::NotEquals,
+            TokenKind::LowerThan => BinaryOp::Less,
+            TokenKind::LowerOrEquals => BinaryOp::LessOrEquals,
+            TokenKind::GreaterThan => BinaryOp::Greater,
+            TokenKind::GreaterOrEquals => BinaryOp::GreaterOrEquals,
+            TokenKind::BitAnd => BinaryOp::BitAnd,
+            TokenKind::BitOr => BinaryOp::BitOr,
+            TokenKind::BitXor => BinaryOp::BitXor,
+            operator => unreachable!("{} isn't an operator of two values", operator),
+        }
+    }
+
+    // the token of the operator, the interpreter applies it the same way
+    pub fn operator(self) -> TokenKind {
+        match self {
+            BinaryOp::Add => TokenKind::Plus,
+            BinaryOp::Subtract => TokenKind::Minus,
+            BinaryOp::Multiply => TokenKind::Times,
+            BinaryOp::Divide => TokenKind::DividedBy,
+            BinaryOp::Equals => TokenKind::Equals,
+            BinaryOp::NotEquals => TokenKind::NotEquals,
+            BinaryOp::Less => TokenKind::LowerThan,
+            BinaryOp::LessOrEquals => TokenKind::LowerOrEquals,
+            BinaryOp::Greater => TokenKind::GreaterThan,
+            BinaryOp::GreaterOrEquals => TokenKind::GreaterOrEquals,
+            BinaryOp::BitAnd => TokenKind::BitAnd,
+            BinaryOp::BitOr => TokenKind::BitOr,
+            BinaryOp::BitXor => TokenKind::BitXor,
+        }
+    }
+
+    pub fn is_comparison(self) -> bool {
+        matches!(
+            self,
+            BinaryOp::Equals
+                | BinaryOp::NotEquals
+                | BinaryOp::Less
+                | BinaryOp::LessOrEquals
+                | BinaryOp::Greater
+                | BinaryOp::GreaterOrEquals
+        )
+    }
+
+    // the operator giving the same result with the operands the other way
+    // around, `+` joins strings so it has none
+    pub fn swapped(self) -> Option<BinaryOp> {
+        match self {
+            BinaryOp::Add | BinaryOp::Subtract | BinaryOp::Divide => None,
+            BinaryOp::Less => Some(BinaryOp::Greater),
+            BinaryOp::LessOrEquals => Some(BinaryOp::GreaterOrEquals),
+            BinaryOp::Greater => Some(BinaryOp::Less),
+            BinaryOp::GreaterOrEquals => Some(BinaryOp::LessOrEquals),
+            op => Some(op),
+        }
+    }
+
+    pub fn mnemonic(self) -> &'static str {
+        match self {
+            BinaryOp::Add => "add",
+            BinaryOp::Subtract => "sub",
+            BinaryOp::Multiply => "mul",
+            BinaryOp::Divide => "div",
+            BinaryOp::Equals => "eq",
+            BinaryOp::NotEquals => "ne",
+            BinaryOp::Less => "lt",
+            BinaryOp::LessOrEquals => "le",
+            BinaryOp::Greater => "gt",
+            BinaryOp::GreaterOrEquals => "ge",
+            BinaryOp::BitAnd => "band",
+            BinaryOp::BitOr => "bor",
+            BinaryOp::BitXor => "bxor",
+        }
+    }
+}
+
+impl UnaryOp {
+    fn from_token(operator: &TokenKind) -> UnaryOp {
+        match operator {
+            TokenKind::Minus => UnaryOp::Negate,
+            TokenKind::Not => UnaryOp::Not,
+            TokenKind::BitNot => UnaryOp::BitNot,
+            operator => unreachable!("{} isn't an operator of one value", operator),
+        }
+    }
+
+    pub fn mnemonic(self) -> &'static str {
+        match self {
+            UnaryOp::Negate => "neg",
+            UnaryOp::Not => "not",
+            UnaryOp::BitNot => "bnot",
+        }
+    }
+}
+
+impl Instr {
+    // the registers the instruction reads
+    pub fn reads(&self) -> Vec<Register> {
+        match self {
+            Instr::Move { from, .. } | Instr::Cast { from, .. } | Instr::Store { from, .. } => {
+                vec![*from]
+            }
+            Instr::Binary { left, right, .. } | Instr::JumpCompare { left, right, .. } => {
+                vec![*left, *right]
+            }
+            Instr::BinaryConst { left, .. } => vec![*left],
+            Instr::Unary { operand, .. } => vec![*operand],
+            Instr::Call { callee, args, .. } => {
+                std::iter::once(*callee).chain(args.clone()).collect()
+            }
+            Instr::Builtin { args, .. } => args.clone(),
+            Instr::List { items, .. } => items.clone(),
+            Instr::Map { entries, .. } => entries
+                .iter()
+                .flat_map(|(key, value)| [*key, *value])
+                .collect(),
+            Instr::JumpIf { condition, .. } | Instr::JumpUnless { condition, .. } => {
+                vec![*condition]
+            }
+            Instr::Match { value, .. } | Instr::Return(Some(value)) => vec![*value],
+            Instr::Const { .. }
+            | Instr::Closure { .. }
+            | Instr::Variant { .. }
+            | Instr::Load { .. }
+            | Instr::Bind { .. }
+            | Instr::Jump(_)
+            | Instr::Try(_)
+            | Instr::Return(None) => vec![],
+        }
+    }
+
+    // the register the instruction writes, if any
+    pub fn writes(&self) -> Option<Register> {
+        match self {
+            Instr::Const { to, .. }
+            | Instr::Move { to, .. }
+            | Instr::Binary { to, .. }
+            | Instr::BinaryConst { to, .. }
+            | Instr::Unary { to, .. }
+            | Instr::Cast { to, .. }
+            | Instr::Closure { to, .. }
+            | Instr::Variant { to, .. }
+            | Instr::List { to, .. }
+            | Instr::Map { to, .. }
+            | Instr::Load { to, .. }
+            | Instr::Bind { to, .. } => Some(*to),
+            Instr::Call { to, .. } | Instr::Builtin { to, .. } => *to,
+            _ => None,
+        }
+    }
+
+    // the positions the instruction can go to other than the next one, the
+    // handler for a `try`
+    pub fn targets_mut(&mut self) -> Vec<&mut usize> {
+        match self {
+            Instr::Jump(target)
+            | Instr::Try(target)
+            | Instr::JumpIf { target, .. }
+            | Instr::JumpUnless { target, .. }
+            | Instr::JumpCompare { target, .. } => vec![target],
+            Instr::Match { arms, .. } => arms.iter_mut().map(|(_, target)| target).collect(),
+            _ => vec![],
+        }
+    }
+
+    pub fn targets(&self) -> Vec<usize> {
+        self.clone()
+            .targets_mut()
+            .into_iter()
+            .map(|target| *target)
+            .collect()
+    }
+
+    // whether the next instruction can run after this one
+    pub fn falls_through(&self) -> bool {
+        !matches!(
+            self,
+            Instr::Jump(_) | Instr::Match { .. } | Instr::Return(_)
+        )
+    }
+
+    // whether it ends what a `try` covers
+    pub fn ends_try(&self) -> bool {
+        !self.falls_through() || !self.targets().is_empty()
+    }
+}
+
+// the spans of the program are found in the sources, a program read from its
+// SSA form has none
+pub fn compile(program: &ssa::Program, sources: &SourceMap) -> Module {
+    let mut module = Module {
+        constants: vec![],
+        functions: vec![],
+        files: sources
+            .files()
+            .iter()
+            .map(|file| file.name.clone())
+            .collect(),
+        locals: program.locals.clone(),
+        enums: program.enums.clone(),
+    };
+
+    for function in &program.functions {
+        let registers = allocate(function);
+        let compiled =
+            FunctionCompiler::new(function, &registers, &mut module.constants, sources).compile();
+        module.functions.push(compiled);
+    }
+
+    module
+}
+
+// the values live at the end of each block, a phi reads its value at the end of
+// the predecessor it comes from. What the handler of a `try` needs stays live
+// through the block it covers, which can fail at any of its instructions
+fn live_out(function: &ssa::Function) -> Vec<HashSet<ValueId>> {
+    let count = function.blocks.len();
+    let mut successors: Vec<Vec<BlockId>> = function
+        .blocks
+        .iter()
+        .map(|block| block.successors())
+        .collect();
+    for block in &function.blocks {
+        if let Terminator::Try { body, handler } = block.terminator {
+            successors[body.0].push(handler);
+        }
+    }
+
+    // what each block reads before defining it, and what it defines
+    let mut reads = vec![HashSet::new(); count];
+    let mut defines = vec![HashSet::new(); count];
+    // what the phis of the successors read from each block
+    let mut outgoing = vec![HashSet::new(); count];
+    for (index, block) in function.blocks.iter().enumerate() {
+        for instr in &block.instrs {
+            match &instr.op {
+                Op::Phi(incoming) => {
+                    for (from, value) in incoming {
+                        outgoing[from.0].insert(*value);
+                    }
+                }
+                op => {
+                    for operand in op.operands() {
+                        if !defines[index].contains(&operand) {
+                            reads[index].insert(operand);
+                        }
+                    }
+                }
+            }
+            defines[index].insert(instr.id);
+        }
+        if let Some(operand) = block.terminator.operand() {
+            if !defines[index].contains(&operand) {
+                reads[index].insert(operand);
+            }
+        }
+    }
+
+    let mut live_in: Vec<HashSet<ValueId>> = vec![HashSet::new(); count];
+    let mut live_out = outgoing.clone();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for index in (0..count).rev() {
+            let mut out = outgoing[index].clone();
+            for successor in &successors[index] {
+                out.extend(&live_in[successor.0]);
+            }
+
+            let mut entry = reads[index].clone();
+            entry.extend(out.difference(&defines[index]));
+            live_out[index] = out;
+            if entry != live_in[index] {
+                live_in[index] = entry;
+                changed = true;
+            }
+        }
+    }
+
+    live_out
+}
+
+// gives every value with a type a register, two values live at the same time
+// never share one. A phi and the values it merges get the same register when
+// they can, so there's nothing to move on the way in
+fn allocate(function: &ssa::Function) -> HashMap<ValueId, Register> {
+    let live_out = live_out(function);
+    let mut interferes: HashMap<ValueId, HashSet<ValueId>> = HashMap::new();
+    let conflict = |interferes: &mut HashMap<ValueId, HashSet<ValueId>>, a: ValueId, b| {
+        if a != b {
+            interferes.entry(a).or_default().insert(b);
+            interferes.entry(b).or_default().insert(a);
+        }
+    };
+
+    let mut hints: HashMap<ValueId, Vec<ValueId>> = HashMap::new();
+    for (index, block) in function.blocks.iter().enumerate() {
+        let mut live = live_out[index].clone();
+        if let Some(operand) = block.terminator.operand() {
+            live.insert(operand);
+        }
+
+        let (phis, instrs): (Vec<_>, Vec<_>) = block
+            .instrs
+            .iter()
+            .partition(|instr| matches!(instr.op, Op::Phi(_)));
+        for instr in instrs.iter().rev() {
+            for value in &live {
+                conflict(&mut interferes, instr.id, *value);
+            }
+            live.remove(&instr.id);
+            live.extend(instr.op.operands());
+        }
+
+        // the phis are all defined at once, when the block is entered
+        live.extend(phis.iter().map(|phi| phi.id));
+        for phi in &phis {
+            for value in &live {
+                conflict(&mut interferes, phi.id, *value);
+            }
+            for operand in phi.op.operands() {
+                hints.entry(phi.id).or_default().push(operand);
+                hints.entry(operand).or_default().push(phi.id);
+            }
+        }
+    }
+
+    let mut registers: HashMap<ValueId, Register> = HashMap::new();
+    let instrs = function.blocks.iter().flat_map(|block| &block.instrs);
+    for instr in instrs.filter(|instr| instr.basetype != BaseType::Void) {
+        let taken: HashSet<Register> = interferes
+            .get(&instr.id)
+            .into_iter()
+            .flatten()
+            .filter_map(|value| registers.get(value).copied())
+            .collect();
+
+        let hinted = hints
+            .get(&instr.id)
+            .into_iter()
+            .flatten()
+            .filter_map(|value| registers.get(value).copied())
+            .find(|register| !taken.contains(register));
+        let register = hinted.unwrap_or_else(|| {
+            (0..)
+                .map(Register)
+                .find(|register| !taken.contains(register))
+                .expect("there's always a free register")
+        });
+        registers.insert(instr.id, register);
+    }
+
+    registers
+}
+
+// where a jump goes before the targets are known: a block, or the moves on the
+// way to one
+#[derive(Clone, Copy, PartialEq)]
+enum Label {
+    Block(BlockId),
+    Edge(usize),
+}
+
+struct FunctionCompiler<'f> {
+    function: &'f ssa::Function,
+    registers: &'f HashMap<ValueId, Register>,
+    constants: &'f mut Vec<Constant>,
+    sources: &'f SourceMap,
+    code: Vec<Instr>,
+    lines: Vec<Option<Line>>,
+    // the line of the instruction being compiled
+    line: Option<Line>,
+    // the jumps to patch once the positions of their labels are known
+    labels: Vec<(usize, Label)>,
+    starts: HashMap<BlockId, usize>,
+    // the moves of the edges that need their own code, they go at the end
+    edges: Vec<(Vec<(Register, Register)>, BlockId)>,
+    count: usize,
+    // the allocations that don't outlive the function
+    local: HashSet<ValueId>,
+}
+
+impl<'f> FunctionCompiler<'f> {
+    fn new(
+        function: &'f ssa::Function,
+        registers: &'f HashMap<ValueId, Register>,
+        constants: &'f mut Vec<Constant>,
+        sources: &'f SourceMap,
+    ) -> Self {
+        let count = registers
+            .values()
+            .map(|register| register.0 + 1)
+            .max()
+            .unwrap_or(0);
+        Self {
+            function,
+            registers,
+            constants,
+            sources,
+            code: vec![],
+            lines: vec![],
+            line: None,
+            labels: vec![],
+            starts: HashMap::new(),
+            edges: vec![],
+            count,
+            local: escape::local_allocations(function),
+        }
+    }
+
+    fn register(&self, value: ValueId) -> Register {
+        self.registers[&value]
+    }
+
+    fn registers(&self, values: &[ValueId]) -> Vec<Register> {
+        values.iter().map(|value| self.register(*value)).collect()
+    }
+
+    // the result of an instruction, none when it's void
+    fn result(&self, instr: &ssa::Instr) -> Option<Register> {
+        self.registers.get(&instr.id).copied()
+    }
+
+    fn constant(&mut self, constant: &Constant) -> usize {
+        let position = self
+            .constants
+            .iter()
+            .position(|existing| match (existing, constant) {
+                (Constant::Float(existing), Constant::Float(constant)) => {
+                    existing.to_bits() == constant.to_bits()
+                }
+                _ => existing == constant,
+            });
+        position.unwrap_or_else(|| {
+            self.constants.push(constant.clone());
+            self.constants.len() - 1
+        })
+    }
+
+    fn push(&mut self, instr: Instr) {
+        self.code.push(instr);
+        self.lines.push(self.line);
+    }
+
+    // the default span is of what the compiler made up
+    fn locate(&self, span: Span) -> Option<Line> {
+        if span == Span::default() || self.sources.files().is_empty() {
+            return None;
+        }
+        let location = self.sources.locate(span.start);
+        Some(Line {
+            file: location.file.0,
+            line: location.line,
+        })
+    }
+
+    // a jump, its target is patched at the end
+    fn jump(&mut self, instr: Instr, label: Label) {
+        self.labels.push((self.code.len(), label));
+        self.push(instr);
+    }
+
+    // the moves giving the phis of `to` their values coming from `from`
+    fn moves(&self, from: BlockId, to: BlockId) -> Vec<(Register, Register)> {
+        let mut moves = vec![];
+        for instr in &self.function.blocks[to.0].instrs {
+            let Op::Phi(incoming) = &instr.op else {
+                continue;
+            };
+            let Some((_, value)) = incoming.iter().find(|(block, _)| *block == from) else {
+                continue;
+            };
+            let (Some(to), Some(from)) = (self.result(instr), self.registers.get(value)) else {
+                continue;
+            };
+            if to != *from {
+                moves.push((to, *from));
+            }
+        }
+        moves
+    }
+
+    // the moves happen at once: a register is only written once nothing left
+    // to move reads it, and a cycle goes through a register of its own
+    fn emit_moves(&mut self, mut moves: Vec<(Register, Register)>) {
+        while !moves.is_empty() {
+            let free = moves
+                .iter()
+                .position(|(to, _)| !moves.iter().any(|(_, from)| from == to));
+            match free {
+                Some(index) => {
+                    let (to, from) = moves.remove(index);
+                    self.push(Instr::Move { to, from });
+                }
+                None => {
+                    let scratch = Register(self.count);
+                    let (to, _) = moves[0];
+                    self.push(Instr::Move {
+                        to: scratch,
+                        from: to,
+                    });
+                    for (_, from) in &mut moves {
+                        if *from == to {
+                            *from = scratch;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // where a block with more than one successor goes to reach `to`, through
+    // code of its own when there are phis to give values to
+    fn edge(&mut self, from: BlockId, to: BlockId) -> Label {
+        let moves = self.moves(from, to);
+        if moves.is_empty() {
+            return Label::Block(to);
+        }
+        self.edges.push((moves, to));
+        Label::Edge(self.edges.len() - 1)
+    }
+
+    fn compile(mut self) -> Function {
+        // what a `try` covers ends with a jump, even to the next instruction
+        let bodies: HashSet<BlockId> = self
+            .function
+            .blocks
+            .iter()
+            .filter_map(|block| match block.terminator {
+                Terminator::Try { body, .. } => Some(body),
+                _ => None,
+            })
+            .collect();
+
+        for (index, block) in self.function.blocks.iter().enumerate() {
+            let id = BlockId(index);
+            let next = BlockId(index + 1);
+            self.starts.insert(id, self.code.len());
+
+            // what ends the block is on the line of its last instruction
+            self.line = None;
+            for instr in &block.instrs {
+                self.instr(instr);
+            }
+
+            match &block.terminator {
+                Terminator::Jump(target) => {
+                    let moves = self.moves(id, *target);
+                    self.emit_moves(moves);
+                    if *target != next || bodies.contains(&id) {
+                        self.jump(Instr::Jump(0), Label::Block(*target));
+                    }
+                }
+
+                Terminator::Branch {
+                    condition,
+                    then,
+                    otherwise,
+                } => {
+                    let condition = self.register(*condition);
+                    let then = self.edge(id, *then);
+                    let otherwise = self.edge(id, *otherwise);
+                    if then == Label::Block(next) {
+                        self.jump(
+                            Instr::JumpUnless {
+                                condition,
+                                target: 0,
+                            },
+                            otherwise,
+                        );
+                    } else if otherwise == Label::Block(next) {
+                        self.jump(
+                            Instr::JumpIf {
+                                condition,
+                                target: 0,
+                            },
+                            then,
+                        );
+                    } else {
+                        self.jump(
+                            Instr::JumpIf {
+                                condition,
+                                target: 0,
+                            },
+                            then,
+                        );
+                        self.jump(Instr::Jump(0), otherwise);
+                    }
+                }
+
+                // the targets of the arms are patched one by one
+                Terminator::Match { value, arms } => {
+                    let value = self.register(*value);
+                    let labels: Vec<Label> = arms
+                        .iter()
+                        .map(|(_, target)| self.edge(id, *target))
+                        .collect();
+                    let position = self.code.len();
+                    self.push(Instr::Match {
+                        value,
+                        arms: arms
+                            .iter()
+                            .map(|(pattern, _)| (pattern.clone(), 0))
+                            .collect(),
+                    });
+                    for label in labels {
+                        self.labels.push((position, label));
+                    }
+                }
+
+                Terminator::Try { body, handler } => {
+                    let handler = self.edge(id, *handler);
+                    self.jump(Instr::Try(0), handler);
+                    if *body != next {
+                        self.jump(Instr::Jump(0), Label::Block(*body));
+                    }
+                }
+
+                Terminator::Return(value) => {
+                    let value = value.map(|value| self.register(value));
+                    self.push(Instr::Return(value));
+                }
+            }
+        }
+
+        let mut edges = vec![];
+        self.line = None;
+        for (moves, target) in std::mem::take(&mut self.edges) {
+            edges.push(self.code.len());
+            self.emit_moves(moves);
+            self.jump(Instr::Jump(0), Label::Block(target));
+        }
+
+        // the targets of an instruction are patched in order, the arms of a
+        // match one after the other
+        let mut patched: HashMap<usize, usize> = HashMap::new();
+        for (position, label) in std::mem::take(&mut self.labels) {
+            let target = match label {
+                Label::Block(block) => self.starts[&block],
+                Label::Edge(edge) => edges[edge],
+            };
+            let count = patched.entry(position).or_default();
+            *self.code[position].targets_mut()[*count] = target;
+            *count += 1;
+        }
+
+        // the scratch register of the moves is the one after the others
+        let scratch = self.count;
+        let registers = if self.code.iter().any(|instr| match instr {
+            Instr::Move { to, from } => to.0 == scratch || from.0 == scratch,
+            _ => false,
+        }) {
+            scratch + 1
+        } else {
+            scratch
+        };
+
+        Function {
+            name: self.function.name.clone(),
+            params: self
+                .function
+                .params
+                .iter()
+                .map(|(param, _)| *param)
+                .collect(),
+            registers,
+            code: self.code,
+            lines: self.lines,
+        }
+    }
+
+    fn instr(&mut self, instr: &ssa::Instr) {
+        if let Some(line) = self.locate(instr.span) {
+            self.line = Some(line);
+        }
+        let to = self.result(instr);
+        let register = || to.expect("the instruction has a value");
+        let compiled = match &instr.op {
+            // a phi gets its value from the moves at the end of its
+            // predecessors, and what a local holds before it's assigned is never
+            // read
+            Op::Phi(_) | Op::Undefined => return,
+
+            Op::Const(constant) => Instr::Const {
+                to: register(),
+                constant: self.constant(constant),
+            },
+            Op::Bind(local) => Instr::Bind {
+                to: register(),
+                local: *local,
+            },
+            Op::Binary {
+                operator,
+                left,
+                right,
+            } => Instr::Binary {
+                op: BinaryOp::from_token(operator),
+                to: register(),
+                left: self.register(*left),
+                right: self.register(*right),
+            },
+            Op::Unary { operator, operand } => Instr::Unary {
+                op: UnaryOp::from_token(operator),
+                to: register(),
+                operand: self.register(*operand),
+            },
+            Op::Call { callee, args } => Instr::Call {
+                to,
+                callee: self.register(*callee),
+                args: self.registers(args),
+            },
+            Op::Builtin { builtin, args } => Instr::Builtin {
+                to,
+                builtin: *builtin,
+                args: self.registers(args),
+            },
+            Op::Cast(value) => Instr::Cast {
+                to: register(),
+                from: self.register(*value),
+                basetype: instr.basetype.clone(),
+            },
+            Op::Function(function) => Instr::Closure {
+                to: register(),
+                function: *function,
+                frame: self.local.contains(&instr.id),
+            },
+            Op::Variant { enum_name, index } => Instr::Variant {
+                to: register(),
+                enum_name: *enum_name,
+                index: *index,
+            },
+            Op::List(items) => Instr::List {
+                to: register(),
+                items: self.registers(items),
+                frame: self.local.contains(&instr.id),
+            },
+            Op::Map(entries) => Instr::Map {
+                to: register(),
+                entries: entries
+                    .iter()
+                    .map(|(key, value)| (self.register(*key), self.register(*value)))
+                    .collect(),
+                frame: self.local.contains(&instr.id),
+            },
+            Op::Load(local) => Instr::Load {
+                to: register(),
+                local: *local,
+            },
+            Op::Store(local, value) => Instr::Store {
+                local: *local,
+                from: self.register(*value),
+            },
+        };
+
+        self.push(compiled);
+    }
+}
+
+impl fmt::Display for Register {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "r{}", self.0)
+    }
+}
+
+fn write_constant(f: &mut fmt::Formatter<'_>, constant: &Constant) -> fmt::Result {
+    match constant {
+        Constant::Int(value) => write!(f, "{}", value),
+        Constant::Float(value) => write!(f, "{:?}", value),
+        Constant::String(value) => write!(f, "{:?}", value),
+        Constant::Bool(value) => write!(f, "{}", value),
+    }
+}
+
+// what's made in the frame of the function is marked so
+fn place(frame: bool) -> &'static str {
+    if frame {
+        "frame "
+    } else {
+        ""
+    }
+}
+
+fn list(registers: &[Register]) -> String {
+    let registers: Vec<String> = registers.iter().map(Register::to_string).collect();
+    registers.join(", ")
+}
+
+impl Module {
+    // locals are printed with their id, since names can be shadowed
+    fn local_name(&self, id: DefId) -> String {
+        match self.locals.get(&id) {
+            Some(name) => format!("{}#{}", name, id.0),
+            None => format!("#{}", id.0),
+        }
+    }
+
+    fn write_pattern(&self, f: &mut fmt::Formatter<'_>, pattern: &Pattern) -> fmt::Result {
+        match pattern {
+            Pattern::Wildcard => write!(f, "_"),
+            Pattern::Binding(id) => write!(f, "{}", self.local_name(*id)),
+            Pattern::Int(value) => write!(f, "{}", value),
+            Pattern::String(value) => write!(f, "{:?}", value),
+            Pattern::Bool(value) => write!(f, "{}", value),
+            // the enum is the type of the value, only the variant is printed
+            Pattern::Variant { index, fields } => {
+                write!(f, "#{}", index)?;
+                if fields.is_empty() {
+                    return Ok(());
+                }
+
+                write!(f, "(")?;
+                for (index, field) in fields.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    self.write_pattern(f, field)?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+
+    fn write_instr(&self, f: &mut fmt::Formatter<'_>, instr: &Instr) -> fmt::Result {
+        let result = |to: &Option<Register>| match to {
+            Some(to) => format!("{}, ", to),
+            None => String::new(),
+        };
+
+        match instr {
+            Instr::Const { to, constant } => {
+                write!(f, "const {}, k{}", to, constant)?;
+                if let Some(constant) = self.constants.get(*constant) {
+                    write!(f, "  ; ")?;
+                    write_constant(f, constant)?;
+                }
+                Ok(())
+            }
+            Instr::Move { to, from } => write!(f, "move {}, {}", to, from),
+            Instr::BinaryConst {
+                op,
+                to,
+                left,
+                constant,
+            } => {
+                write!(f, "{}k {}, {}, k{}", op.mnemonic(), to, left, constant)?;
+                if let Some(constant) = self.constants.get(*constant) {
+                    write!(f, "  ; ")?;
+                    write_constant(f, constant)?;
+                }
+                Ok(())
+            }
+            Instr::Binary {
+                op,
+                to,
+                left,
+                right,
+            } => write!(f, "{} {}, {}, {}", op.mnemonic(), to, left, right),
+            Instr::Unary { op, to, operand } => write!(f, "{} {}, {}", op.mnemonic(), to, operand),
+            Instr::Cast { to, from, basetype } => write!(f, "cast {}, {}, {}", to, from, basetype),
+            Instr::Call { to, callee, args } => {
+                write!(f, "call {}{}({})", result(to), callee, list(args))
+            }
+            Instr::Builtin { to, builtin, args } => {
+                write!(f, "builtin {}{}({})", result(to), builtin, list(args))
+            }
+            Instr::Closure {
+                to,
+                function,
+                frame,
+            } => write!(f, "{}closure {}, @{}", place(*frame), to, function),
+            Instr::Variant {
+                to,
+                enum_name,
+                index,
+            } => match self
+                .enums
+                .get(enum_name)
+                .and_then(|variants| variants.get(*index))
+            {
+                Some((variant, _)) => write!(f, "variant {}, {}.{}", to, enum_name, variant),
+                None => write!(f, "variant {}, {}.#{}", to, enum_name, index),
+            },
+            Instr::List { to, items, frame } => {
+                write!(f, "{}list {}, [{}]", place(*frame), to, list(items))
+            }
+            Instr::Map { to, entries, frame } => {
+                let entries: Vec<String> = entries
+                    .iter()
+                    .map(|(key, value)| format!("{}: {}", key, value))
+                    .collect();
+                write!(f, "{}map {}, [{}]", place(*frame), to, entries.join(", "))
+            }
+            Instr::Load { to, local } => write!(f, "load {}, {}", to, self.local_name(*local)),
+            Instr::Store { local, from } => {
+                write!(f, "store {}, {}", self.local_name(*local), from)
+            }
+            Instr::Bind { to, local } => write!(f, "bind {}, {}", to, self.local_name(*local)),
+            Instr::Jump(target) => write!(f, "jump {}", target),
+            Instr::JumpIf { condition, target } => write!(f, "jumpif {}, {}", condition, target),
+            Instr::JumpUnless { condition, target } => {
+                write!(f, "jumpunless {}, {}", condition, target)
+            }
+            Instr::JumpCompare {
+                op,
+                left,
+                right,
+                target,
+                when,
+            } => {
+                let jump = if *when { "jumpif" } else { "jumpunless" };
+                write!(
+                    f,
+                    "{} {} {}, {}, {}",
+                    jump,
+                    op.mnemonic(),
+                    left,
+                    right,
+                    target
+                )
+            }
+            Instr::Match { value, arms } => {
+                write!(f, "match {}, [", value)?;
+                for (index, (pattern, target)) in arms.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    self.write_pattern(f, pattern)?;
+                    write!(f, " => {}", target)?;
+                }
+                write!(f, "]")
+            }
+            Instr::Try(handler) => write!(f, "try {}", handler),
+            Instr::Return(Some(value)) => write!(f, "return {}", value),
+            Instr::Return(None) => write!(f, "return"),
+        }
+    }
+}
+
+impl Module {
+    pub fn disassemble<'m>(&'m self, sources: &'m [Option<String>]) -> Disassembly<'m> {
+        Disassembly {
+            module: self,
+            sources,
+        }
+    }
+
+    // a function at a time, an instruction a line after its position. With
+    // the sources, the line instructions come from is above the first of them
+    fn write_functions(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        sources: Option<&[Option<String>]>,
+    ) -> fmt::Result {
+        for (index, function) in self.functions.iter().enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+
+            let params: Vec<String> = function
+                .params
+                .iter()
+                .map(|param| self.local_name(*param))
+                .collect();
+            writeln!(
+                f,
+                "fn @{} {:?}({}), {} register(s)",
+                index,
+                function.name,
+                params.join(", "),
+                function.registers
+            )?;
+            let width = function.code.len().saturating_sub(1).to_string().len();
+            let mut last = None;
+            for (position, instr) in function.code.iter().enumerate() {
+                let line = function.lines.get(position).copied().flatten();
+                if let (Some(sources), Some(line)) = (sources, line) {
+                    if last != Some(line) {
+                        self.write_line(f, sources, line)?;
+                        last = Some(line);
+                    }
+                }
+
+                write!(f, "    {:>width$}  ", position, width = width)?;
+                self.write_instr(f, instr)?;
+                writeln!(f)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // where it is, and the text of the line when its file was found
+    fn write_line(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        sources: &[Option<String>],
+        line: Line,
+    ) -> fmt::Result {
+        let name = self.files.get(line.file).map_or("?", String::as_str);
+        write!(f, "    ; {}:{}", name, line.line)?;
+        let text = sources
+            .get(line.file)
+            .and_then(Option::as_deref)
+            .and_then(|source| source.lines().nth(line.line.checked_sub(1)?))
+            .map(str::trim)
+            .filter(|text| !text.is_empty());
+        if let Some(text) = text {
+            write!(f, "  {}", text)?;
+        }
+        writeln!(f)
+    }
+}
+
+impl fmt::Display for Module {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_functions(f, None)
+    }
+}
+
+// the module for people to read, the constant pool first and then the
+// functions with the lines of the source their instructions come from. The
+// sources are the text of the files of the module, by index, the ones that
+// couldn't be found are none
+pub struct Disassembly<'m> {
+    module: &'m Module,
+    sources: &'m [Option<String>],
+}
+
+impl fmt::Display for Disassembly<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let constants = &self.module.constants;
+        if !constants.is_empty() {
+            writeln!(f, "constants")?;
+            let width = constants.len().saturating_sub(1).to_string().len();
+            for (index, constant) in constants.iter().enumerate() {
+                write!(f, "    k{:<width$}  ", index, width = width)?;
+                write_constant(f, constant)?;
+                writeln!(f)?;
+            }
+            writeln!(f)?;
+        }
+
+        self.module.write_functions(f, Some(self.sources))
+    }
+}
@@ -0,0 +1,636 @@
+use std::collections::HashSet;
+use std::fmt::Write;
+
+use ez_ast::BaseType;
+use ez_check::hir::{Hir, Stmt, Value, ValueKind};
+use ez_check::log::LogLevel;
+use ez_check::resolver::{Builtin, DefId};
+use ez_check::template;
+use ez_diagnostics::Diagnostic;
+use ez_diagnostics::SourceMap;
+use ez_lexer::{Span, TokenKind};
+
+#[derive(Debug)]
+pub enum CError {
+    // `what` is the part of the language that can't be compiled yet
+    Unsupported { what: String, span: Span },
+}
+
+impl From<CError> for Diagnostic {
+    fn from(error: CError) -> Self {
+        match error {
+            CError::Unsupported { what, span } => {
+                Diagnostic::error("E0504", format!("{} can't be compiled to C yet", what))
+                    .with_label(span, "not supported by the C backend")
+                    .with_help("use `ez run` to run the program with the interpreter instead")
+            }
+        }
+    }
+}
+
+// the helpers the generated code calls, it goes at the top of every file so the
+// output can be handed to a C compiler on its own
+pub const RUNTIME: &str = include_str!("../runtime/ez_runtime.h");
+
+// lowers the HIR to C99. Top level functions become C functions, top level
+// variables become globals, and the rest of the top level code goes in `main`.
+// Every name gets the id of its local as a suffix, which keeps shadowed names
+// apart and out of the way of C keywords and the C library.
+pub struct CBackend<'a> {
+    hir: &'a Hir,
+    // the program's files, failed assertions and panics say where they were
+    sources: &'a SourceMap,
+    functions: HashSet<DefId>,
+    out: String,
+    indent: usize,
+    // the label `continue` jumps to in each loop around the code, loops without
+    // a step can use `continue` itself
+    loops: Vec<Option<String>>,
+    labels: usize,
+    // `return` in `main` ends the program
+    in_main: bool,
+}
+
+impl<'a> CBackend<'a> {
+    pub fn new(hir: &'a Hir, sources: &'a SourceMap) -> Self {
+        Self {
+            hir,
+            sources,
+            functions: HashSet::new(),
+            out: String::new(),
+            indent: 0,
+            loops: vec![],
+            labels: 0,
+            in_main: false,
+        }
+    }
+
+    // like `file.ez:3:5`
+    fn location(&self, span: Span) -> String {
+        self.sources.location(span)
+    }
+
+    fn unsupported<T>(what: impl Into<String>, span: Span) -> Result<T, CError> {
+        Err(CError::Unsupported {
+            what: what.into(),
+            span,
+        })
+    }
+
+    fn ctype(basetype: &BaseType, span: Span) -> Result<&'static str, CError> {
+        match basetype {
+            BaseType::Int => Ok("int64_t"),
+            BaseType::Float => Ok("double"),
+            BaseType::Bool => Ok("bool"),
+            BaseType::String => Ok("ez_string"),
+            basetype => Self::unsupported(format!("`{}` values", basetype), span),
+        }
+    }
+
+    fn return_type(basetype: &BaseType, span: Span) -> Result<&'static str, CError> {
+        match basetype {
+            BaseType::Void => Ok("void"),
+            basetype => Self::ctype(basetype, span),
+        }
+    }
+
+    fn name(&self, local: DefId) -> String {
+        format!("{}_{}", self.hir.local(local).name, local.0)
+    }
+
+    fn line(&mut self, text: impl AsRef<str>) {
+        let _ = writeln!(self.out, "{}{}", "    ".repeat(self.indent), text.as_ref());
+    }
+
+    // non-ASCII characters are written as the octal escapes of their UTF-8 bytes,
+    // and `?` escaped so two of them don't start a trigraph, which C99 has
+    fn string(value: &str) -> String {
+        let mut literal = String::from("\"");
+        for byte in value.bytes() {
+            match byte {
+                b'"' => literal.push_str("\\\""),
+                b'\\' => literal.push_str("\\\\"),
+                b'\n' => literal.push_str("\\n"),
+                b'\t' => literal.push_str("\\t"),
+                b'\r' => literal.push_str("\\r"),
+                b'?' => literal.push_str("\\?"),
+                b' '..=b'~' => literal.push(byte as char),
+                byte => {
+                    let _ = write!(literal, "\\{:03o}", byte);
+                }
+            }
+        }
+        literal.push('"');
+        literal
+    }
+
+    fn binary(
+        &self,
+        operator: &TokenKind,
+        left: &Value,
+        right: &Value,
+        span: Span,
+    ) -> Result<String, CError> {
+        let (left_code, right_code) = (self.value(left)?, self.value(right)?);
+        let helper = |name: &str| Ok(format!("{}({}, {})", name, left_code, right_code));
+
+        let operator = match (&left.basetype, operator) {
+            (_, TokenKind::And) => "&&",
+            (_, TokenKind::Or) => "||",
+
+            (BaseType::String, TokenKind::Plus) => return helper("ez_concat"),
+            (BaseType::String, TokenKind::Equals) => return helper("ez_string_eq"),
+            (BaseType::String, TokenKind::NotEquals) => {
+                return Ok(format!("!ez_string_eq({}, {})", left_code, right_code))
+            }
+
+            (BaseType::Int, TokenKind::Plus) => return helper("ez_add"),
+            (BaseType::Int, TokenKind::Minus) => return helper("ez_sub"),
+            (BaseType::Int, TokenKind::Times) => return helper("ez_mul"),
+            (BaseType::Int, TokenKind::DividedBy) => return helper("ez_div"),
+            (BaseType::Int, TokenKind::BitAnd) => "&",
+            (BaseType::Int, TokenKind::BitOr) => "|",
+            (BaseType::Int, TokenKind::BitXor) => "^",
+
+            (BaseType::Float, TokenKind::Plus) => "+",
+            (BaseType::Float, TokenKind::Minus) => "-",
+            (BaseType::Float, TokenKind::Times) => "*",
+            (BaseType::Float, TokenKind::DividedBy) => "/",
+
+            (BaseType::Int | BaseType::Float | BaseType::Bool, TokenKind::Equals) => "==",
+            (BaseType::Int | BaseType::Float | BaseType::Bool, TokenKind::NotEquals) => "!=",
+            (BaseType::Int | BaseType::Float, TokenKind::GreaterThan) => ">",
+            (BaseType::Int | BaseType::Float, TokenKind::GreaterOrEquals) => ">=",
+            (BaseType::Int | BaseType::Float, TokenKind::LowerThan) => "<",
+            (BaseType::Int | BaseType::Float, TokenKind::LowerOrEquals) => "<=",
+
+            (basetype, operator) => {
+                return Self::unsupported(format!("{} on `{}`", operator, basetype), span)
+            }
+        };
+
+        Ok(format!("({} {} {})", left_code, operator, right_code))
+    }
+
+    fn cast(&self, value: &Value, to: &BaseType, span: Span) -> Result<String, CError> {
+        let code = self.value(value)?;
+
+        let cast = match (&value.basetype, to) {
+            (from, to) if from == to => code,
+            (BaseType::Int, BaseType::Float) => format!("((double){})", code),
+            (BaseType::Float, BaseType::Int) => format!("ez_float_to_int({})", code),
+            (BaseType::Bool, BaseType::Int) => format!("((int64_t){})", code),
+            (BaseType::Int, BaseType::String) => format!("ez_int_to_string({})", code),
+            (BaseType::Float, BaseType::String) => format!("ez_float_to_string({})", code),
+            (BaseType::Bool, BaseType::String) => format!("ez_bool_to_string({})", code),
+            (_, to) => return Self::unsupported(format!("casts to `{}`", to), span),
+        };
+
+        Ok(cast)
+    }
+
+    fn value(&self, value: &Value) -> Result<String, CError> {
+        let code = match &value.kind {
+            ValueKind::Int(int) if i32::try_from(*int).is_ok() => int.to_string(),
+            ValueKind::Int(int) => format!("INT64_C({})", int),
+            ValueKind::Float(float) => format!("{:?}", float),
+            ValueKind::Bool(bool) => bool.to_string(),
+            ValueKind::String(string) => Self::string(string),
+
+            ValueKind::Local(local) => {
+                if self.functions.contains(local) {
+                    return Self::unsupported("functions used as values", value.span);
+                }
+
+                self.name(*local)
+            }
+
+            ValueKind::Binary {
+                operator,
+                left,
+                right,
+            } => self.binary(operator, left, right, value.span)?,
+
+            ValueKind::Unary { operator, operand } => {
+                let operand_code = self.value(operand)?;
+                match (operator, &operand.basetype) {
+                    (TokenKind::Minus, BaseType::Float) => format!("(-{})", operand_code),
+                    (TokenKind::Minus, _) => format!("ez_neg({})", operand_code),
+                    (TokenKind::BitNot, _) => format!("(~{})", operand_code),
+                    _ => format!("(!{})", operand_code),
+                }
+            }
+
+            ValueKind::Cast { value: inner } => self.cast(inner, &value.basetype, value.span)?,
+
+            ValueKind::Call { callee, args } => {
+                let function = match callee.kind {
+                    ValueKind::Local(local) if self.functions.contains(&local) => local,
+                    _ => return Self::unsupported("calls through function values", callee.span),
+                };
+
+                let args = args
+                    .iter()
+                    .map(|arg| self.value(arg))
+                    .collect::<Result<Vec<_>, _>>()?;
+                format!("{}({})", self.name(function), args.join(", "))
+            }
+
+            ValueKind::Builtin {
+                builtin: builtin @ (Builtin::Print | Builtin::Println),
+                args,
+            } => {
+                let newline = *builtin == Builtin::Println;
+                match args.first() {
+                    Some(arg) => {
+                        let print = match arg.basetype {
+                            BaseType::Int => "ez_print_int",
+                            BaseType::Float => "ez_print_float",
+                            BaseType::String => "ez_print_string",
+                            _ => "ez_print_bool",
+                        };
+                        format!("{}({}, {})", print, self.value(arg)?, newline)
+                    }
+                    None => format!("ez_print_string(\"\", {})", newline),
+                }
+            }
+
+            // the runtime drops the messages below the level
+            ValueKind::Builtin {
+                builtin:
+                    builtin @ (Builtin::LogDebug
+                    | Builtin::LogInfo
+                    | Builtin::LogWarn
+                    | Builtin::LogError),
+                args,
+            } => {
+                let level = LogLevel::of(*builtin).expect("the builtin logs");
+                format!(
+                    "ez_log({}, {})",
+                    level as usize,
+                    self.cast(&args[0], &BaseType::String, args[0].span)?
+                )
+            }
+
+            // the template is known, so the pieces are concatenated with the
+            // arguments converted to strings, like `as string` does
+            ValueKind::Builtin {
+                builtin: Builtin::Format,
+                args,
+            } => {
+                let ValueKind::String(template) = &args[0].kind else {
+                    unreachable!("templates are string literals");
+                };
+                let pieces = template::pieces(template).expect("templates are checked");
+
+                let mut code = Self::string(&pieces[0]);
+                for (arg, piece) in args[1..].iter().zip(&pieces[1..]) {
+                    let text = self.cast(arg, &BaseType::String, arg.span)?;
+                    code = format!(
+                        "ez_concat(ez_concat({}, {}), {})",
+                        code,
+                        text,
+                        Self::string(piece)
+                    );
+                }
+                code
+            }
+
+            // an assertion without a message gets an empty one
+            ValueKind::Builtin {
+                builtin: builtin @ (Builtin::Assert | Builtin::Panic),
+                args,
+            } => {
+                let location = Self::string(&self.location(value.span));
+                match (builtin, args.as_slice()) {
+                    (Builtin::Assert, [condition]) => {
+                        format!("ez_assert({}, \"\", {})", self.value(condition)?, location)
+                    }
+                    (Builtin::Assert, [condition, message]) => format!(
+                        "ez_assert({}, {}, {})",
+                        self.value(condition)?,
+                        self.value(message)?,
+                        location
+                    ),
+                    _ => format!("ez_user_panic({}, {})", self.value(&args[0])?, location),
+                }
+            }
+
+            // `math.h` has everything on floats, the runtime has the rest, methods
+            // get the value they're called on as their first argument
+            ValueKind::Builtin { builtin, args } => {
+                let function = match (builtin, &value.basetype) {
+                    (Builtin::EnvGet, _) => "ez_env_get",
+                    (Builtin::EnvSet, _) => "ez_env_set",
+                    (Builtin::Now, _) => "ez_now",
+                    (Builtin::Clock, _) => "ez_clock",
+                    (Builtin::Sleep, _) => "ez_sleep",
+                    (Builtin::TcpConnect, _) => "ez_tcp_connect",
+                    (Builtin::TcpListen, _) => "ez_tcp_listen",
+                    (Builtin::TcpAccept, _) => "ez_tcp_accept",
+                    (Builtin::Read, _) => "ez_read",
+                    (Builtin::Write, _) => "ez_write",
+                    (Builtin::Close, _) => "ez_close",
+                    (Builtin::Random, _) => "ez_random",
+                    (Builtin::RandomInt, _) => "ez_random_int",
+                    (Builtin::Seed, _) => "ez_seed",
+                    (Builtin::ReadFile, _) => "ez_read_file",
+                    (Builtin::WriteFile, _) => "ez_write_file",
+                    (Builtin::Sqrt, _) => "sqrt",
+                    (Builtin::Pow, _) => "pow",
+                    (Builtin::Floor, _) => "floor",
+                    (Builtin::Ceil, _) => "ceil",
+                    (Builtin::Abs, BaseType::Int) => "ez_abs",
+                    (Builtin::Abs, _) => "fabs",
+                    (Builtin::Min, BaseType::Int) => "ez_min",
+                    (Builtin::Min, _) => "fmin",
+                    (Builtin::Max, BaseType::Int) => "ez_max",
+                    (Builtin::Max, _) => "fmax",
+                    (Builtin::Len, _) => "ez_len",
+                    (Builtin::Substring, _) => "ez_substring",
+                    (Builtin::Contains, _) => "ez_contains",
+                    (Builtin::ToUpper, _) => "ez_to_upper",
+                    (Builtin::ToLower, _) => "ez_to_lower",
+                    (Builtin::Trim, _) => "ez_trim",
+                    (Builtin::ParseInt, _) => "ez_parse_int",
+                    (Builtin::ParseFloat, _) => "ez_parse_float",
+                    (
+                        Builtin::Print
+                        | Builtin::Println
+                        | Builtin::Format
+                        | Builtin::Assert
+                        | Builtin::Panic
+                        | Builtin::LogDebug
+                        | Builtin::LogInfo
+                        | Builtin::LogWarn
+                        | Builtin::LogError,
+                        _,
+                    ) => unreachable!(
+                        "printing, formatting, logging, assertions and panics are above"
+                    ),
+                    // they give or take lists and maps
+                    _ => return Self::unsupported(format!("`{}`", builtin), value.span),
+                };
+
+                let args = args
+                    .iter()
+                    .map(|arg| self.value(arg))
+                    .collect::<Result<Vec<_>, _>>()?;
+                format!("{}({})", function, args.join(", "))
+            }
+
+            ValueKind::Function { .. } => return Self::unsupported("nested functions", value.span),
+
+            ValueKind::Variant { .. } => return Self::unsupported("enums", value.span),
+
+            ValueKind::List(_) => return Self::unsupported("lists", value.span),
+
+            ValueKind::Map(_) => return Self::unsupported("maps", value.span),
+        };
+
+        Ok(code)
+    }
+
+    // conditions already have parentheses around them
+    fn condition(&self, value: &Value) -> Result<String, CError> {
+        let code = self.value(value)?;
+        match value.kind {
+            ValueKind::Binary { .. } if code.starts_with('(') => {
+                Ok(code[1..code.len() - 1].to_string())
+            }
+            _ => Ok(code),
+        }
+    }
+
+    fn body(&mut self, body: &[Stmt]) -> Result<(), CError> {
+        self.indent += 1;
+        for stmt in body {
+            self.stmt(stmt)?;
+        }
+        self.indent -= 1;
+
+        Ok(())
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) -> Result<(), CError> {
+        match stmt {
+            Stmt::Let { local, value, span } => {
+                let code = self.value(value)?;
+                let ctype = Self::ctype(&self.hir.local(*local).basetype, *span)?;
+                self.line(format!("{} {} = {};", ctype, self.name(*local), code));
+            }
+
+            Stmt::Declare { local, span } => {
+                let ctype = Self::ctype(&self.hir.local(*local).basetype, *span)?;
+                self.line(format!("{} {};", ctype, self.name(*local)));
+            }
+
+            Stmt::Assign { target, value } => {
+                let code = format!("{} = {};", self.value(target)?, self.value(value)?);
+                self.line(code);
+            }
+
+            Stmt::Return(value) => match value {
+                Some(value) => {
+                    let code = format!("return {};", self.value(value)?);
+                    self.line(code);
+                }
+                None if self.in_main => self.line("return 0;"),
+                None => self.line("return;"),
+            },
+
+            Stmt::If {
+                condition,
+                body,
+                else_body,
+            } => {
+                let code = format!("if ({}) {{", self.condition(condition)?);
+                self.line(code);
+                self.body(body)?;
+                if !else_body.is_empty() {
+                    self.line("} else {");
+                    self.body(else_body)?;
+                }
+                self.line("}");
+            }
+
+            // `continue` has to run the step, so with one it jumps to a label in
+            // front of it
+            Stmt::While {
+                condition,
+                body,
+                step,
+            } => {
+                let code = format!("while ({}) {{", self.condition(condition)?);
+                self.line(code);
+
+                let label = (!step.is_empty()).then(|| {
+                    self.labels += 1;
+                    format!("continue_{}", self.labels)
+                });
+                self.loops.push(label.clone());
+                self.body(body)?;
+                self.loops.pop();
+
+                if let Some(label) = label {
+                    self.line(format!("{}:;", label));
+                    self.body(step)?;
+                }
+                self.line("}");
+            }
+
+            Stmt::Match { value, .. } => return Self::unsupported("`match`", value.span),
+            Stmt::Try { span, .. } => return Self::unsupported("`try`", *span),
+
+            Stmt::Break => self.line("break;"),
+
+            Stmt::Continue => match self.loops.last().expect("loop control is checked") {
+                Some(label) => {
+                    let code = format!("goto {};", label);
+                    self.line(code);
+                }
+                None => self.line("continue;"),
+            },
+
+            Stmt::Block(body) => {
+                self.line("{");
+                self.body(body)?;
+                self.line("}");
+            }
+
+            Stmt::Value(value) => {
+                let code = format!("{};", self.value(value)?);
+                self.line(code);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn signature(&self, local: DefId, function: &Value) -> Result<String, CError> {
+        let (ValueKind::Function { params, .. }, BaseType::Function { return_type, .. }) =
+            (&function.kind, &function.basetype)
+        else {
+            unreachable!("functions have a function type");
+        };
+
+        let params = params
+            .iter()
+            .map(|param| {
+                let ctype = Self::ctype(&self.hir.local(*param).basetype, function.span)?;
+                Ok(format!("{} {}", ctype, self.name(*param)))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let params = if params.is_empty() {
+            "void".to_string()
+        } else {
+            params.join(", ")
+        };
+
+        Ok(format!(
+            "static {} {}({})",
+            Self::return_type(return_type, function.span)?,
+            self.name(local),
+            params
+        ))
+    }
+
+    pub fn compile(mut self) -> Result<String, CError> {
+        let hir = self.hir;
+        let mut functions = vec![];
+        let mut globals = vec![];
+
+        for stmt in &hir.body {
+            match stmt {
+                Stmt::Let {
+                    local,
+                    value:
+                        value @ Value {
+                            kind: ValueKind::Function { .. },
+                            ..
+                        },
+                    ..
+                } => {
+                    self.functions.insert(*local);
+                    functions.push((*local, value));
+                }
+
+                // top level variables get their values in `main`, in order
+                Stmt::Let { local, span, .. } | Stmt::Declare { local, span } => {
+                    globals.push((*local, *span))
+                }
+
+                _ => {}
+            }
+        }
+
+        self.out.push_str(RUNTIME);
+        self.out.push('\n');
+
+        for (local, span) in &globals {
+            let ctype = Self::ctype(&hir.local(*local).basetype, *span)?;
+            self.line(format!("static {} {};", ctype, self.name(*local)));
+        }
+        if !globals.is_empty() {
+            self.line("");
+        }
+
+        // functions can be called before they're declared
+        for (local, function) in &functions {
+            let signature = self.signature(*local, function)?;
+            self.line(format!("{};", signature));
+        }
+
+        for (local, function) in &functions {
+            let ValueKind::Function { body, .. } = &function.kind else {
+                unreachable!("only functions are collected");
+            };
+
+            self.line("");
+            let signature = self.signature(*local, function)?;
+            self.line(format!("{} {{", signature));
+            self.body(body)?;
+            if !matches!(
+                &function.basetype,
+                BaseType::Function { return_type, .. } if **return_type == BaseType::Void
+            ) {
+                self.indent += 1;
+                self.line("ez_unreachable();");
+                self.indent -= 1;
+            }
+            self.line("}");
+        }
+
+        if !functions.is_empty() {
+            self.line("");
+        }
+        self.line("int main(void) {");
+        self.in_main = true;
+        self.indent += 1;
+        for stmt in &hir.body {
+            match stmt {
+                Stmt::Let {
+                    value:
+                        Value {
+                            kind: ValueKind::Function { .. },
+                            ..
+                        },
+                    ..
+                }
+                | Stmt::Declare { .. } => {}
+
+                Stmt::Let { local, value, .. } => {
+                    let code = format!("{} = {};", self.name(*local), self.value(value)?);
+                    self.line(code);
+                }
+
+                stmt => self.stmt(stmt)?,
+            }
+        }
+        self.line("return 0;");
+        self.indent -= 1;
+        self.line("}");
+
+        Ok(self.out)
+    }
+}
@@ -0,0 +1,776 @@
+use std::fmt;
+
+use ez_ast::{BaseType, Identifier, NodeId, Param};
+use ez_check::hir::Pattern;
+use ez_check::resolver::{Builtin, DefId};
+use ez_check::ssa::Constant;
+use ez_lexer::{Span, Symbol};
+
+use crate::bytecode::{BinaryOp, Function, Instr, Line, Module, Register, UnaryOp};
+
+// the bytecode of a module written as bytes, the `.ezb` files `--emit bytecode`
+// writes and `ez disasm` reads. It starts with the magic bytes and the version
+// of the format, which changes with every change to how the module is written,
+// a module written by another version isn't read. Then come the constant pool,
+// the names of the files, the locals and the enums, and the functions with the
+// line of each instruction. Numbers are varints, builtins are written by name
+pub const MAGIC: [u8; 4] = *b"ezbc";
+pub const VERSION: u16 = 2;
+
+#[derive(Debug, PartialEq)]
+pub enum DecodeError {
+    // it doesn't start with the magic bytes
+    NotBytecode,
+    // written with another version of the format
+    Version(u16),
+    // it ends in the middle of the module
+    Truncated,
+    // a byte that can't be where it is, what was being read
+    Invalid(&'static str),
+    // the module is read, but there are more bytes after it
+    Trailing,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::NotBytecode => write!(f, "not bytecode written by ez"),
+            DecodeError::Version(version) => write!(
+                f,
+                "the bytecode was written with version {} of the format, this is version {}",
+                version, VERSION
+            ),
+            DecodeError::Truncated => write!(f, "the bytecode ends too early"),
+            DecodeError::Invalid(what) => write!(f, "invalid {} in the bytecode", what),
+            DecodeError::Trailing => write!(f, "bytes left after the bytecode"),
+        }
+    }
+}
+
+// the operators, by their index in the bytes
+const BINARY_OPS: [BinaryOp; 13] = [
+    BinaryOp::Add,
+    BinaryOp::Subtract,
+    BinaryOp::Multiply,
+    BinaryOp::Divide,
+    BinaryOp::Equals,
+    BinaryOp::NotEquals,
+    BinaryOp::Less,
+    BinaryOp::LessOrEquals,
+    BinaryOp::Greater,
+    BinaryOp::GreaterOrEquals,
+    BinaryOp::BitAnd,
+    BinaryOp::BitOr,
+    BinaryOp::BitXor,
+];
+
+const UNARY_OPS: [UnaryOp; 3] = [UnaryOp::Negate, UnaryOp::Not, UnaryOp::BitNot];
+
+// the locals and enums are written in order, so the same module always gives
+// the same bytes
+pub fn encode(module: &Module) -> Vec<u8> {
+    let mut writer = Writer {
+        out: MAGIC.to_vec(),
+    };
+    writer.out.extend(VERSION.to_le_bytes());
+
+    writer.list(module.constants.iter(), Writer::constant);
+    writer.list(module.files.iter(), |this, file| this.str(file));
+
+    let mut locals: Vec<_> = module.locals.iter().collect();
+    locals.sort_by_key(|(id, _)| id.0);
+    writer.list(locals.iter(), |this, (id, name)| {
+        this.usize(id.0);
+        this.symbol(**name);
+    });
+
+    let mut enums: Vec<_> = module.enums.iter().collect();
+    enums.sort_by_key(|(name, _)| name.as_str());
+    writer.list(enums.iter(), |this, (name, variants)| {
+        this.symbol(**name);
+        this.list(variants.iter(), |this, (variant, fields)| {
+            this.symbol(*variant);
+            this.list(fields.iter(), Writer::basetype);
+        });
+    });
+
+    writer.list(module.functions.iter(), Writer::function);
+    writer.out
+}
+
+pub fn decode(bytes: &[u8]) -> Result<Module, DecodeError> {
+    if bytes.len() < MAGIC.len() || bytes[..MAGIC.len()] != MAGIC {
+        return Err(DecodeError::NotBytecode);
+    }
+    let mut reader = Reader {
+        bytes,
+        at: MAGIC.len(),
+    };
+    let version = u16::from_le_bytes([reader.byte()?, reader.byte()?]);
+    if version != VERSION {
+        return Err(DecodeError::Version(version));
+    }
+
+    let constants = reader.vec(Reader::constant)?;
+    let files = reader.vec(Reader::string)?;
+    let locals = reader.vec(|this| Ok((DefId(this.usize()?), this.symbol()?)))?;
+    let enums = reader.vec(|this| {
+        let name = this.symbol()?;
+        let variants = this.vec(|this| Ok((this.symbol()?, this.vec(Reader::basetype)?)))?;
+        Ok((name, variants))
+    })?;
+    let functions = reader.vec(Reader::function)?;
+    if reader.at != bytes.len() {
+        return Err(DecodeError::Trailing);
+    }
+
+    Ok(Module {
+        constants,
+        functions,
+        files,
+        locals: locals.into_iter().collect(),
+        enums: enums.into_iter().collect(),
+    })
+}
+
+struct Writer {
+    out: Vec<u8>,
+}
+
+impl Writer {
+    fn byte(&mut self, byte: u8) {
+        self.out.push(byte);
+    }
+
+    // seven bits a byte, the high bit says there's another
+    fn u64(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.byte(value as u8 | 0x80);
+            value >>= 7;
+        }
+        self.byte(value as u8);
+    }
+
+    fn usize(&mut self, value: usize) {
+        self.u64(value as u64);
+    }
+
+    // small negative numbers are small too
+    fn i64(&mut self, value: i64) {
+        self.u64(((value << 1) ^ (value >> 63)) as u64);
+    }
+
+    fn bool(&mut self, value: bool) {
+        self.byte(value as u8);
+    }
+
+    fn str(&mut self, text: &str) {
+        self.usize(text.len());
+        self.out.extend(text.as_bytes());
+    }
+
+    fn symbol(&mut self, symbol: Symbol) {
+        self.str(symbol.as_str());
+    }
+
+    fn register(&mut self, register: &Register) {
+        self.usize(register.0);
+    }
+
+    fn registers(&mut self, registers: &[Register]) {
+        self.list(registers.iter(), Self::register);
+    }
+
+    fn option<T>(&mut self, value: Option<T>, write: impl FnOnce(&mut Self, T)) {
+        match value {
+            Some(value) => {
+                self.byte(1);
+                write(self, value);
+            }
+            None => self.byte(0),
+        }
+    }
+
+    fn list<'t, T: 't>(
+        &mut self,
+        items: impl ExactSizeIterator<Item = &'t T>,
+        mut write: impl FnMut(&mut Self, &'t T),
+    ) {
+        self.usize(items.len());
+        for item in items {
+            write(self, item);
+        }
+    }
+
+    fn binary_op(&mut self, op: &BinaryOp) {
+        let index = BINARY_OPS
+            .iter()
+            .position(|other| other == op)
+            .expect("the operators are all in the table");
+        self.usize(index);
+    }
+
+    fn unary_op(&mut self, op: &UnaryOp) {
+        let index = UNARY_OPS
+            .iter()
+            .position(|other| other == op)
+            .expect("the operators are all in the table");
+        self.usize(index);
+    }
+
+    fn constant(&mut self, constant: &Constant) {
+        match constant {
+            Constant::Int(value) => {
+                self.byte(0);
+                self.i64(*value);
+            }
+            Constant::Float(value) => {
+                self.byte(1);
+                self.out.extend(value.to_bits().to_le_bytes());
+            }
+            Constant::String(value) => {
+                self.byte(2);
+                self.str(value);
+            }
+            Constant::Bool(value) => {
+                self.byte(3);
+                self.bool(*value);
+            }
+        }
+    }
+
+    // the parameters of a function type keep their names, like in the tree
+    fn basetype(&mut self, basetype: &BaseType) {
+        match basetype {
+            BaseType::Void => self.byte(0),
+            BaseType::Int => self.byte(1),
+            BaseType::Float => self.byte(2),
+            BaseType::String => self.byte(3),
+            BaseType::Bool => self.byte(4),
+            BaseType::Function {
+                params,
+                return_type,
+            } => {
+                self.byte(5);
+                self.list(params.iter(), |this, param| {
+                    let identifier = &param.identifier;
+                    this.symbol(identifier.name);
+                    this.usize(identifier.span.start);
+                    this.usize(identifier.span.end.saturating_sub(identifier.span.start));
+                    this.u64(identifier.id.0 as u64);
+                    this.basetype(&param.basetype);
+                });
+                self.basetype(return_type);
+            }
+            BaseType::Named(name) => {
+                self.byte(6);
+                self.symbol(*name);
+            }
+            BaseType::List(item) => {
+                self.byte(7);
+                self.basetype(item);
+            }
+            BaseType::Map(key, value) => {
+                self.byte(8);
+                self.basetype(key);
+                self.basetype(value);
+            }
+        }
+    }
+
+    fn pattern(&mut self, pattern: &Pattern) {
+        match pattern {
+            Pattern::Wildcard => self.byte(0),
+            Pattern::Binding(id) => {
+                self.byte(1);
+                self.usize(id.0);
+            }
+            Pattern::Int(value) => {
+                self.byte(2);
+                self.i64(*value);
+            }
+            Pattern::String(value) => {
+                self.byte(3);
+                self.str(value);
+            }
+            Pattern::Bool(value) => {
+                self.byte(4);
+                self.bool(*value);
+            }
+            Pattern::Variant { index, fields } => {
+                self.byte(5);
+                self.usize(*index);
+                self.list(fields.iter(), Self::pattern);
+            }
+        }
+    }
+
+    // the lines after the code, one for each instruction
+    fn function(&mut self, function: &Function) {
+        self.str(&function.name);
+        self.list(function.params.iter(), |this, param| this.usize(param.0));
+        self.usize(function.registers);
+        self.list(function.code.iter(), Self::instr);
+        self.list(function.lines.iter(), |this, line| {
+            this.option(line.as_ref(), |this, line| {
+                this.usize(line.file);
+                this.usize(line.line);
+            })
+        });
+    }
+
+    fn instr(&mut self, instr: &Instr) {
+        match instr {
+            Instr::Const { to, constant } => {
+                self.byte(0);
+                self.register(to);
+                self.usize(*constant);
+            }
+            Instr::Move { to, from } => {
+                self.byte(1);
+                self.register(to);
+                self.register(from);
+            }
+            Instr::Binary {
+                op,
+                to,
+                left,
+                right,
+            } => {
+                self.byte(2);
+                self.binary_op(op);
+                self.register(to);
+                self.register(left);
+                self.register(right);
+            }
+            Instr::BinaryConst {
+                op,
+                to,
+                left,
+                constant,
+            } => {
+                self.byte(3);
+                self.binary_op(op);
+                self.register(to);
+                self.register(left);
+                self.usize(*constant);
+            }
+            Instr::Unary { op, to, operand } => {
+                self.byte(4);
+                self.unary_op(op);
+                self.register(to);
+                self.register(operand);
+            }
+            Instr::Cast { to, from, basetype } => {
+                self.byte(5);
+                self.register(to);
+                self.register(from);
+                self.basetype(basetype);
+            }
+            Instr::Call { to, callee, args } => {
+                self.byte(6);
+                self.option(to.as_ref(), Self::register);
+                self.register(callee);
+                self.registers(args);
+            }
+            Instr::Builtin { to, builtin, args } => {
+                self.byte(7);
+                self.option(to.as_ref(), Self::register);
+                self.str(&builtin.to_string());
+                self.registers(args);
+            }
+            Instr::Closure {
+                to,
+                function,
+                frame,
+            } => {
+                self.byte(8);
+                self.register(to);
+                self.usize(*function);
+                self.bool(*frame);
+            }
+            Instr::Variant {
+                to,
+                enum_name,
+                index,
+            } => {
+                self.byte(9);
+                self.register(to);
+                self.symbol(*enum_name);
+                self.usize(*index);
+            }
+            Instr::List { to, items, frame } => {
+                self.byte(10);
+                self.register(to);
+                self.registers(items);
+                self.bool(*frame);
+            }
+            Instr::Map { to, entries, frame } => {
+                self.byte(11);
+                self.register(to);
+                self.list(entries.iter(), |this, (key, value)| {
+                    this.register(key);
+                    this.register(value);
+                });
+                self.bool(*frame);
+            }
+            Instr::Load { to, local } => {
+                self.byte(12);
+                self.register(to);
+                self.usize(local.0);
+            }
+            Instr::Store { local, from } => {
+                self.byte(13);
+                self.usize(local.0);
+                self.register(from);
+            }
+            Instr::Bind { to, local } => {
+                self.byte(14);
+                self.register(to);
+                self.usize(local.0);
+            }
+            Instr::Jump(target) => {
+                self.byte(15);
+                self.usize(*target);
+            }
+            Instr::JumpIf { condition, target } => {
+                self.byte(16);
+                self.register(condition);
+                self.usize(*target);
+            }
+            Instr::JumpUnless { condition, target } => {
+                self.byte(17);
+                self.register(condition);
+                self.usize(*target);
+            }
+            Instr::JumpCompare {
+                op,
+                left,
+                right,
+                target,
+                when,
+            } => {
+                self.byte(18);
+                self.binary_op(op);
+                self.register(left);
+                self.register(right);
+                self.usize(*target);
+                self.bool(*when);
+            }
+            Instr::Match { value, arms } => {
+                self.byte(19);
+                self.register(value);
+                self.list(arms.iter(), |this, (pattern, target)| {
+                    this.pattern(pattern);
+                    this.usize(*target);
+                });
+            }
+            Instr::Try(handler) => {
+                self.byte(20);
+                self.usize(*handler);
+            }
+            Instr::Return(value) => {
+                self.byte(21);
+                self.option(value.as_ref(), Self::register);
+            }
+        }
+    }
+}
+
+struct Reader<'b> {
+    bytes: &'b [u8],
+    at: usize,
+}
+
+impl Reader<'_> {
+    fn byte(&mut self) -> Result<u8, DecodeError> {
+        let byte = *self.bytes.get(self.at).ok_or(DecodeError::Truncated)?;
+        self.at += 1;
+        Ok(byte)
+    }
+
+    fn u64(&mut self) -> Result<u64, DecodeError> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte < 0x80 {
+                return Ok(value);
+            }
+        }
+        Err(DecodeError::Invalid("number"))
+    }
+
+    fn usize(&mut self) -> Result<usize, DecodeError> {
+        usize::try_from(self.u64()?).map_err(|_| DecodeError::Invalid("number"))
+    }
+
+    // the length of something that follows, every item of it is at least a
+    // byte, so a broken one can't make it allocate more than what's left
+    fn len(&mut self) -> Result<usize, DecodeError> {
+        let len = self.usize()?;
+        if len > self.bytes.len() - self.at {
+            return Err(DecodeError::Truncated);
+        }
+        Ok(len)
+    }
+
+    fn i64(&mut self) -> Result<i64, DecodeError> {
+        let value = self.u64()?;
+        Ok((value >> 1) as i64 ^ -((value & 1) as i64))
+    }
+
+    fn f64(&mut self) -> Result<f64, DecodeError> {
+        let mut bits = [0; 8];
+        for byte in &mut bits {
+            *byte = self.byte()?;
+        }
+        Ok(f64::from_bits(u64::from_le_bytes(bits)))
+    }
+
+    fn bool(&mut self) -> Result<bool, DecodeError> {
+        match self.byte()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(DecodeError::Invalid("bool")),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, DecodeError> {
+        let len = self.len()?;
+        let bytes = &self.bytes[self.at..self.at + len];
+        self.at += len;
+        String::from_utf8(bytes.to_vec()).map_err(|_| DecodeError::Invalid("string"))
+    }
+
+    fn symbol(&mut self) -> Result<Symbol, DecodeError> {
+        Ok(Symbol::intern(&self.string()?))
+    }
+
+    fn register(&mut self) -> Result<Register, DecodeError> {
+        Ok(Register(self.usize()?))
+    }
+
+    fn registers(&mut self) -> Result<Vec<Register>, DecodeError> {
+        self.vec(Self::register)
+    }
+
+    fn option<T>(
+        &mut self,
+        read: impl FnOnce(&mut Self) -> Result<T, DecodeError>,
+    ) -> Result<Option<T>, DecodeError> {
+        match self.byte()? {
+            0 => Ok(None),
+            1 => read(self).map(Some),
+            _ => Err(DecodeError::Invalid("option")),
+        }
+    }
+
+    fn vec<T>(
+        &mut self,
+        mut read: impl FnMut(&mut Self) -> Result<T, DecodeError>,
+    ) -> Result<Vec<T>, DecodeError> {
+        let len = self.len()?;
+        (0..len).map(|_| read(self)).collect()
+    }
+
+    fn binary_op(&mut self) -> Result<BinaryOp, DecodeError> {
+        let index = self.usize()?;
+        BINARY_OPS
+            .get(index)
+            .copied()
+            .ok_or(DecodeError::Invalid("operator"))
+    }
+
+    fn unary_op(&mut self) -> Result<UnaryOp, DecodeError> {
+        let index = self.usize()?;
+        UNARY_OPS
+            .get(index)
+            .copied()
+            .ok_or(DecodeError::Invalid("operator"))
+    }
+
+    fn constant(&mut self) -> Result<Constant, DecodeError> {
+        Ok(match self.byte()? {
+            0 => Constant::Int(self.i64()?),
+            1 => Constant::Float(self.f64()?),
+            2 => Constant::String(self.string()?),
+            3 => Constant::Bool(self.bool()?),
+            _ => return Err(DecodeError::Invalid("constant")),
+        })
+    }
+
+    fn basetype(&mut self) -> Result<BaseType, DecodeError> {
+        Ok(match self.byte()? {
+            0 => BaseType::Void,
+            1 => BaseType::Int,
+            2 => BaseType::Float,
+            3 => BaseType::String,
+            4 => BaseType::Bool,
+            5 => BaseType::Function {
+                params: self.vec(|this| {
+                    let name = this.symbol()?;
+                    let start = this.usize()?;
+                    let end = start
+                        .checked_add(this.usize()?)
+                        .ok_or(DecodeError::Invalid("span"))?;
+                    let id = u32::try_from(this.u64()?).map_err(|_| DecodeError::Invalid("id"))?;
+                    Ok(Param {
+                        identifier: Identifier {
+                            name,
+                            span: Span::new(start, end),
+                            id: NodeId(id),
+                        },
+                        basetype: this.basetype()?,
+                    })
+                })?,
+                return_type: Box::new(self.basetype()?),
+            },
+            6 => BaseType::Named(self.symbol()?),
+            7 => BaseType::List(Box::new(self.basetype()?)),
+            8 => BaseType::Map(Box::new(self.basetype()?), Box::new(self.basetype()?)),
+            _ => return Err(DecodeError::Invalid("type")),
+        })
+    }
+
+    fn pattern(&mut self) -> Result<Pattern, DecodeError> {
+        Ok(match self.byte()? {
+            0 => Pattern::Wildcard,
+            1 => Pattern::Binding(DefId(self.usize()?)),
+            2 => Pattern::Int(self.i64()?),
+            3 => Pattern::String(self.string()?),
+            4 => Pattern::Bool(self.bool()?),
+            5 => Pattern::Variant {
+                index: self.usize()?,
+                fields: self.vec(Self::pattern)?,
+            },
+            _ => return Err(DecodeError::Invalid("pattern")),
+        })
+    }
+
+    fn function(&mut self) -> Result<Function, DecodeError> {
+        let name = self.string()?;
+        let params = self.vec(|this| Ok(DefId(this.usize()?)))?;
+        let registers = self.usize()?;
+        let code = self.vec(Self::instr)?;
+        let lines = self.vec(|this| {
+            this.option(|this| {
+                Ok(Line {
+                    file: this.usize()?,
+                    line: this.usize()?,
+                })
+            })
+        })?;
+        if lines.len() != code.len() {
+            return Err(DecodeError::Invalid("lines"));
+        }
+
+        Ok(Function {
+            name,
+            params,
+            registers,
+            code,
+            lines,
+        })
+    }
+
+    fn instr(&mut self) -> Result<Instr, DecodeError> {
+        Ok(match self.byte()? {
+            0 => Instr::Const {
+                to: self.register()?,
+                constant: self.usize()?,
+            },
+            1 => Instr::Move {
+                to: self.register()?,
+                from: self.register()?,
+            },
+            2 => Instr::Binary {
+                op: self.binary_op()?,
+                to: self.register()?,
+                left: self.register()?,
+                right: self.register()?,
+            },
+            3 => Instr::BinaryConst {
+                op: self.binary_op()?,
+                to: self.register()?,
+                left: self.register()?,
+                constant: self.usize()?,
+            },
+            4 => Instr::Unary {
+                op: self.unary_op()?,
+                to: self.register()?,
+                operand: self.register()?,
+            },
+            5 => Instr::Cast {
+                to: self.register()?,
+                from: self.register()?,
+                basetype: self.basetype()?,
+            },
+            6 => Instr::Call {
+                to: self.option(Self::register)?,
+                callee: self.register()?,
+                args: self.registers()?,
+            },
+            7 => Instr::Builtin {
+                to: self.option(Self::register)?,
+                builtin: Builtin::parse(&self.string()?).ok_or(DecodeError::Invalid("builtin"))?,
+                args: self.registers()?,
+            },
+            8 => Instr::Closure {
+                to: self.register()?,
+                function: self.usize()?,
+                frame: self.bool()?,
+            },
+            9 => Instr::Variant {
+                to: self.register()?,
+                enum_name: self.symbol()?,
+                index: self.usize()?,
+            },
+            10 => Instr::List {
+                to: self.register()?,
+                items: self.registers()?,
+                frame: self.bool()?,
+            },
+            11 => Instr::Map {
+                to: self.register()?,
+                entries: self.vec(|this| Ok((this.register()?, this.register()?)))?,
+                frame: self.bool()?,
+            },
+            12 => Instr::Load {
+                to: self.register()?,
+                local: DefId(self.usize()?),
+            },
+            13 => Instr::Store {
+                local: DefId(self.usize()?),
+                from: self.register()?,
+            },
+            14 => Instr::Bind {
+                to: self.register()?,
+                local: DefId(self.usize()?),
+            },
+            15 => Instr::Jump(self.usize()?),
+            16 => Instr::JumpIf {
+                condition: self.register()?,
+                target: self.usize()?,
+            },
+            17 => Instr::JumpUnless {
+                condition: self.register()?,
+                target: self.usize()?,
+            },
+            18 => Instr::JumpCompare {
+                op: self.binary_op()?,
+                left: self.register()?,
+                right: self.register()?,
+                target: self.usize()?,
+                when: self.bool()?,
+            },
+            19 => Instr::Match {
+                value: self.register()?,
+                arms: self.vec(|this| Ok((this.pattern()?, this.usize()?)))?,
+            },
+            20 => Instr::Try(self.usize()?),
+            21 => Instr::Return(self.option(Self::register)?),
+            _ => return Err(DecodeError::Invalid("instruction")),
+        })
+    }
+}
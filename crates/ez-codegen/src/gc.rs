@@ -0,0 +1,293 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::{Rc, Weak};
+
+use crate::interp::{Closure, Env, Map, Value};
+
+// collect after this many lists, maps, closures and environments were made
+pub const DEFAULT_THRESHOLD: usize = 10_000;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GcStats {
+    pub allocated: usize,
+    pub collections: usize,
+    // the objects found in cycles nothing else refers to
+    pub freed: usize,
+    // the objects alive after the last collection
+    pub live: usize,
+}
+
+impl fmt::Display for GcStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} objects allocated, {} collections, {} objects freed, {} live after the last one",
+            self.allocated, self.collections, self.freed, self.live
+        )
+    }
+}
+
+enum Object<'a> {
+    List(Weak<RefCell<Vec<Value<'a>>>>),
+    Map(Weak<RefCell<Map<'a>>>),
+    Closure(Weak<Closure<'a>>),
+    Env(Weak<Env<'a>>),
+}
+
+// an object kept alive while it's looked at
+enum Strong<'a> {
+    List(Rc<RefCell<Vec<Value<'a>>>>),
+    Map(Rc<RefCell<Map<'a>>>),
+    Closure(Rc<Closure<'a>>),
+    Env(Rc<Env<'a>>),
+}
+
+fn address<T: ?Sized>(rc: &Rc<T>) -> usize {
+    Rc::as_ptr(rc) as *const () as usize
+}
+
+// the objects a value refers to directly, the fields of variants are part of it
+fn references(value: &Value, out: &mut Vec<usize>) {
+    match value {
+        Value::List(items) => out.push(address(items)),
+        Value::Map(map) => out.push(address(map)),
+        Value::Function(closure) => out.push(address(closure)),
+        Value::Variant { fields, .. } => {
+            for field in fields {
+                references(field, out);
+            }
+        }
+        _ => {}
+    }
+}
+
+impl<'a> Object<'a> {
+    fn upgrade(&self) -> Option<Strong<'a>> {
+        match self {
+            Object::List(list) => list.upgrade().map(Strong::List),
+            Object::Map(map) => map.upgrade().map(Strong::Map),
+            Object::Closure(closure) => closure.upgrade().map(Strong::Closure),
+            Object::Env(env) => env.upgrade().map(Strong::Env),
+        }
+    }
+}
+
+impl<'a> Strong<'a> {
+    fn downgrade(&self) -> Object<'a> {
+        match self {
+            Strong::List(list) => Object::List(Rc::downgrade(list)),
+            Strong::Map(map) => Object::Map(Rc::downgrade(map)),
+            Strong::Closure(closure) => Object::Closure(Rc::downgrade(closure)),
+            Strong::Env(env) => Object::Env(Rc::downgrade(env)),
+        }
+    }
+
+    fn address(&self) -> usize {
+        match self {
+            Strong::List(list) => address(list),
+            Strong::Map(map) => address(map),
+            Strong::Closure(closure) => address(closure),
+            Strong::Env(env) => address(env),
+        }
+    }
+
+    fn strong_count(&self) -> usize {
+        match self {
+            Strong::List(list) => Rc::strong_count(list),
+            Strong::Map(map) => Rc::strong_count(map),
+            Strong::Closure(closure) => Rc::strong_count(closure),
+            Strong::Env(env) => Rc::strong_count(env),
+        }
+    }
+
+    // `None` when the object is borrowed, the interpreter is using it then
+    fn references(&self) -> Option<Vec<usize>> {
+        let mut out = vec![];
+        match self {
+            Strong::List(list) => {
+                for item in list.try_borrow().ok()?.iter() {
+                    references(item, &mut out);
+                }
+            }
+            Strong::Map(map) => {
+                for (_, value) in map.try_borrow().ok()?.iter() {
+                    references(value, &mut out);
+                }
+            }
+            Strong::Closure(closure) => out.push(address(&closure.env)),
+            Strong::Env(env) => {
+                for value in env.values.try_borrow().ok()?.values() {
+                    references(value, &mut out);
+                }
+                if let Some(parent) = &env.parent {
+                    out.push(address(parent));
+                }
+            }
+        }
+        Some(out)
+    }
+
+    // closures and the parents of environments never change, so emptying the
+    // containers is enough to break every cycle
+    fn clear(&self) {
+        match self {
+            Strong::List(list) => list.borrow_mut().clear(),
+            Strong::Map(map) => *map.borrow_mut() = Map::default(),
+            Strong::Closure(_) => {}
+            Strong::Env(env) => env.values.borrow_mut().clear(),
+        }
+    }
+}
+
+// frees the cycles reference counting can't. Every list, map, closure and
+// environment is registered when it's made. The references an object has from
+// outside of the heap, like the values the interpreter is working with, are the
+// part of its strong count that other objects don't account for, and whatever
+// can't be reached from an object with such references is garbage. Garbage is
+// emptied, which breaks its cycles and lets reference counting free it. Strings
+// can't refer to anything, so they're always freed by their count
+pub struct Heap<'a> {
+    objects: RefCell<Vec<Object<'a>>>,
+    threshold: usize,
+    // since the last collection
+    pending: Cell<usize>,
+    stats: Cell<GcStats>,
+    // write the stats to stderr when the heap is dropped
+    report: bool,
+}
+
+impl<'a> Heap<'a> {
+    pub fn new(threshold: usize) -> Self {
+        Self {
+            objects: RefCell::default(),
+            threshold: threshold.max(1),
+            pending: Cell::new(0),
+            stats: Cell::default(),
+            report: false,
+        }
+    }
+
+    pub fn set_threshold(&mut self, threshold: usize) {
+        self.threshold = threshold.max(1);
+    }
+
+    pub fn set_report(&mut self, report: bool) {
+        self.report = report;
+    }
+
+    pub fn stats(&self) -> GcStats {
+        self.stats.get()
+    }
+
+    // the object isn't registered yet, so it's safe from the collection this
+    // might start
+    fn register(&self, object: Object<'a>) {
+        if self.pending.get() >= self.threshold {
+            self.collect();
+        }
+
+        self.pending.set(self.pending.get() + 1);
+        let mut stats = self.stats.get();
+        stats.allocated += 1;
+        self.stats.set(stats);
+        self.objects.borrow_mut().push(object);
+    }
+
+    pub fn list(&self, items: Vec<Value<'a>>) -> Value<'a> {
+        let list = Rc::new(RefCell::new(items));
+        self.register(Object::List(Rc::downgrade(&list)));
+        Value::List(list)
+    }
+
+    pub fn map(&self, map: Map<'a>) -> Value<'a> {
+        let map = Rc::new(RefCell::new(map));
+        self.register(Object::Map(Rc::downgrade(&map)));
+        Value::Map(map)
+    }
+
+    pub fn closure(&self, closure: Closure<'a>) -> Value<'a> {
+        let closure = Rc::new(closure);
+        self.register(Object::Closure(Rc::downgrade(&closure)));
+        Value::Function(closure)
+    }
+
+    pub fn env(&self, env: Env<'a>) -> Rc<Env<'a>> {
+        let env = Rc::new(env);
+        self.register(Object::Env(Rc::downgrade(&env)));
+        env
+    }
+
+    pub fn collect(&self) {
+        let live: Vec<Strong> = self
+            .objects
+            .borrow()
+            .iter()
+            .filter_map(Object::upgrade)
+            .collect();
+        *self.objects.borrow_mut() = live.iter().map(Strong::downgrade).collect();
+
+        let indices: HashMap<usize, usize> = live
+            .iter()
+            .enumerate()
+            .map(|(index, object)| (object.address(), index))
+            .collect();
+
+        // objects in use can't be looked into, they're kept along with whatever
+        // they refer to
+        let mut internal = vec![0; live.len()];
+        let mut children = vec![vec![]; live.len()];
+        let mut roots = vec![];
+        for (index, object) in live.iter().enumerate() {
+            let Some(references) = object.references() else {
+                roots.push(index);
+                continue;
+            };
+
+            for child in references.iter().filter_map(|address| indices.get(address)) {
+                internal[*child] += 1;
+                children[index].push(*child);
+            }
+        }
+
+        // `live` holds one of the references itself
+        for (index, object) in live.iter().enumerate() {
+            if object.strong_count() - 1 > internal[index] {
+                roots.push(index);
+            }
+        }
+
+        let mut marked = vec![false; live.len()];
+        while let Some(index) = roots.pop() {
+            if !std::mem::replace(&mut marked[index], true) {
+                roots.extend(&children[index]);
+            }
+        }
+
+        let mut freed = 0;
+        for (object, marked) in live.iter().zip(&marked) {
+            if !marked {
+                object.clear();
+                freed += 1;
+            }
+        }
+
+        let mut stats = self.stats.get();
+        stats.collections += 1;
+        stats.freed += freed;
+        stats.live = live.len() - freed;
+        self.stats.set(stats);
+        self.pending.set(0);
+    }
+}
+
+// what's left once the program is done, like the functions and the globals
+// they use, is collected too
+impl Drop for Heap<'_> {
+    fn drop(&mut self) {
+        self.collect();
+        if self.report {
+            eprintln!("gc: {}", self.stats());
+        }
+    }
+}
@@ -0,0 +1,236 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+use ez_ast::{BaseType, Identifier, NodeId, Param};
+use ez_lexer::Span;
+
+use crate::interp::Value;
+
+type Function = Box<dyn for<'v> Fn(&[Value<'v>]) -> Value<'v> + Send + Sync>;
+
+// a function of the program ez is embedded in, registered with
+// `Engine::register_fn`. Programs call it like one of their own functions
+pub struct HostFunction {
+    pub name: String,
+    pub params: Vec<BaseType>,
+    pub return_type: BaseType,
+    function: Function,
+}
+
+impl HostFunction {
+    pub fn new<Args, F: IntoHostFunction<Args>>(name: &str, function: F) -> Self {
+        Self {
+            name: name.to_string(),
+            params: F::params(),
+            return_type: F::return_type(),
+            function: function.into_function(),
+        }
+    }
+
+    // the type programs see it as, parameters don't have names
+    pub fn basetype(&self) -> BaseType {
+        let params = self
+            .params
+            .iter()
+            .map(|basetype| Param {
+                identifier: Identifier {
+                    name: "_".into(),
+                    span: Span::default(),
+                    id: NodeId::default(),
+                },
+                basetype: basetype.clone(),
+            })
+            .collect();
+
+        BaseType::Function {
+            params,
+            return_type: Box::new(self.return_type.clone()),
+        }
+    }
+
+    // the arguments were type checked against `params`
+    pub fn call<'v>(&self, args: &[Value<'v>]) -> Value<'v> {
+        (self.function)(args)
+    }
+}
+
+impl fmt::Debug for HostFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<host fn {}>", self.name)
+    }
+}
+
+// a Rust type with an ez type, host functions take and give back these
+pub trait EzType {
+    fn basetype() -> BaseType;
+}
+
+// the value always has the type of `basetype`, the program was type checked
+pub trait FromValue: EzType {
+    fn from_value(value: &Value) -> Self;
+}
+
+pub trait IntoValue: EzType {
+    fn into_value<'v>(self) -> Value<'v>;
+}
+
+impl EzType for () {
+    fn basetype() -> BaseType {
+        BaseType::Void
+    }
+}
+
+impl IntoValue for () {
+    fn into_value<'v>(self) -> Value<'v> {
+        Value::Void
+    }
+}
+
+impl EzType for i64 {
+    fn basetype() -> BaseType {
+        BaseType::Int
+    }
+}
+
+impl FromValue for i64 {
+    fn from_value(value: &Value) -> Self {
+        match value {
+            Value::Int(value) => *value,
+            value => unreachable!("{:?} is not an int", value),
+        }
+    }
+}
+
+impl IntoValue for i64 {
+    fn into_value<'v>(self) -> Value<'v> {
+        Value::Int(self)
+    }
+}
+
+impl EzType for f64 {
+    fn basetype() -> BaseType {
+        BaseType::Float
+    }
+}
+
+impl FromValue for f64 {
+    fn from_value(value: &Value) -> Self {
+        match value {
+            Value::Float(value) => *value,
+            value => unreachable!("{:?} is not a float", value),
+        }
+    }
+}
+
+impl IntoValue for f64 {
+    fn into_value<'v>(self) -> Value<'v> {
+        Value::Float(self)
+    }
+}
+
+impl EzType for bool {
+    fn basetype() -> BaseType {
+        BaseType::Bool
+    }
+}
+
+impl FromValue for bool {
+    fn from_value(value: &Value) -> Self {
+        match value {
+            Value::Bool(value) => *value,
+            value => unreachable!("{:?} is not a bool", value),
+        }
+    }
+}
+
+impl IntoValue for bool {
+    fn into_value<'v>(self) -> Value<'v> {
+        Value::Bool(self)
+    }
+}
+
+impl EzType for String {
+    fn basetype() -> BaseType {
+        BaseType::String
+    }
+}
+
+impl FromValue for String {
+    fn from_value(value: &Value) -> Self {
+        match value {
+            Value::String(value) => value.to_string(),
+            value => unreachable!("{:?} is not a string", value),
+        }
+    }
+}
+
+impl IntoValue for String {
+    fn into_value<'v>(self) -> Value<'v> {
+        Value::String(self.into())
+    }
+}
+
+// lists are copied, changes the host makes don't show in the program
+impl<T: EzType> EzType for Vec<T> {
+    fn basetype() -> BaseType {
+        BaseType::List(Box::new(T::basetype()))
+    }
+}
+
+impl<T: FromValue> FromValue for Vec<T> {
+    fn from_value(value: &Value) -> Self {
+        match value {
+            Value::List(items) => items.borrow().iter().map(T::from_value).collect(),
+            value => unreachable!("{:?} is not a list", value),
+        }
+    }
+}
+
+impl<T: IntoValue> IntoValue for Vec<T> {
+    fn into_value<'v>(self) -> Value<'v> {
+        let items = self.into_iter().map(T::into_value).collect();
+        Value::List(Rc::new(RefCell::new(items)))
+    }
+}
+
+// the closures `register_fn` takes, `Args` is a tuple of their parameters
+pub trait IntoHostFunction<Args> {
+    fn params() -> Vec<BaseType>;
+    fn return_type() -> BaseType;
+    fn into_function(self) -> Function;
+}
+
+macro_rules! host_function {
+    ($($arg:ident $value:ident),*) => {
+        impl<F, R, $($arg),*> IntoHostFunction<($($arg,)*)> for F
+        where
+            F: Fn($($arg),*) -> R + Send + Sync + 'static,
+            R: IntoValue,
+            $($arg: FromValue,)*
+        {
+            fn params() -> Vec<BaseType> {
+                vec![$($arg::basetype()),*]
+            }
+
+            fn return_type() -> BaseType {
+                R::basetype()
+            }
+
+            fn into_function(self) -> Function {
+                Box::new(move |args| {
+                    let [$($value),*] = args else {
+                        unreachable!("the arguments were type checked");
+                    };
+                    self($($arg::from_value($value)),*).into_value()
+                })
+            }
+        }
+    };
+}
+
+host_function!();
+host_function!(A a);
+host_function!(A a, B b);
+host_function!(A a, B b, C c);
+host_function!(A a, B b, C c, D d);
@@ -0,0 +1,1861 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use ez_ast::BaseType;
+use ez_check::hir::{self, Hir, Stmt, ValueKind};
+use ez_check::json::{self, Json};
+use ez_check::log::LogLevel;
+use ez_check::resolver::{Builtin, DefId};
+use ez_check::template;
+use ez_diagnostics::Diagnostic;
+use ez_lexer::{Span, Symbol, TokenKind};
+use regex::Regex;
+
+use crate::gc::{self, Heap};
+use crate::host::HostFunction;
+
+// how deep calls can nest before the program is stopped, so runaway recursion
+// is reported instead of overflowing the stack of the interpreter itself
+pub(crate) const MAX_DEPTH: usize = 1000;
+
+// the size of the stack the interpreter needs to run on to reach `MAX_DEPTH`,
+// every call of the program takes a handful of recursive calls here
+pub const STACK_SIZE: usize = 256 * 1024 * 1024;
+
+#[derive(Debug)]
+pub enum RuntimeError {
+    DivisionByZero(Span),
+    Overflow {
+        operator: TokenKind,
+        span: Span,
+    },
+    StackOverflow(Span),
+    SubstringOutOfBounds {
+        start: i64,
+        end: i64,
+        length: usize,
+        span: Span,
+    },
+    // `basetype` is what the text was parsed as
+    InvalidNumber {
+        text: String,
+        basetype: BaseType,
+        span: Span,
+    },
+    IndexOutOfBounds {
+        index: i64,
+        length: usize,
+        span: Span,
+    },
+    EmptyList(Span),
+    // the key as it's printed inside a map
+    MissingKey {
+        key: String,
+        span: Span,
+    },
+    // `message` says what's wrong with the text and where
+    InvalidJson {
+        message: String,
+        span: Span,
+    },
+    // `message` is what the regex crate says is wrong with the pattern
+    InvalidRegex {
+        pattern: String,
+        message: String,
+        span: Span,
+    },
+    // the command of `run_command` couldn't be started
+    Spawn {
+        command: String,
+        error: io::Error,
+        span: Span,
+    },
+    // `action` is what was being done, like "connect to `localhost:80`"
+    Network {
+        action: String,
+        error: io::Error,
+        span: Span,
+    },
+    // the socket was closed, never opened, or is a listener where a connection
+    // was needed or the other way around
+    InvalidSocket {
+        socket: i64,
+        listener: bool,
+        span: Span,
+    },
+    // `random_int` was given no numbers to pick from
+    EmptyRange {
+        start: i64,
+        end: i64,
+        span: Span,
+    },
+    // `panic` was called
+    Panic {
+        message: String,
+        span: Span,
+    },
+    // the condition given to `assert` was false, the message is optional
+    AssertionFailed {
+        message: Option<String>,
+        span: Span,
+    },
+    // names can't be empty or have a `=` or a NUL character in them
+    InvalidEnvName {
+        name: String,
+        span: Span,
+    },
+    // reading or writing a file failed, `reading` tells which
+    Io {
+        path: String,
+        reading: bool,
+        error: io::Error,
+        span: Span,
+    },
+    // a builtin that reaches outside the program, run in a sandbox
+    Sandboxed {
+        builtin: Builtin,
+        span: Span,
+    },
+    // the program ran past its deadline, it's stopped at the next call or turn
+    // of a loop. `try` doesn't catch it
+    TimedOut(Span),
+}
+
+impl From<RuntimeError> for Diagnostic {
+    fn from(error: RuntimeError) -> Self {
+        match error {
+            RuntimeError::DivisionByZero(span) => {
+                Diagnostic::error("E0401", "attempt to divide by zero")
+                    .with_label(span, "the divisor is zero")
+            }
+
+            RuntimeError::Overflow { operator, span } => Diagnostic::error(
+                "E0402",
+                format!("attempt to compute {} with overflow", operator),
+            )
+            .with_label(span, "the result doesn't fit in an `int`"),
+
+            RuntimeError::StackOverflow(span) => Diagnostic::error(
+                "E0403",
+                format!("calls nested more than {} deep", MAX_DEPTH),
+            )
+            .with_label(span, "while calling this")
+            .with_help("is there a recursion without a base case?"),
+
+            RuntimeError::SubstringOutOfBounds {
+                start,
+                end,
+                length,
+                span,
+            } => Diagnostic::error(
+                "E0404",
+                format!("the substring from {} to {} is out of bounds", start, end),
+            )
+            .with_label(span, format!("the string has {} character(s)", length)),
+
+            RuntimeError::InvalidNumber {
+                text,
+                basetype,
+                span,
+            } => Diagnostic::error("E0405", format!("`{}` isn't a valid `{}`", text, basetype))
+                .with_label(span, "parsed here")
+                .with_note("numbers can't have spaces or other characters around them"),
+
+            RuntimeError::IndexOutOfBounds {
+                index,
+                length,
+                span,
+            } => Diagnostic::error("E0406", format!("the index {} is out of bounds", index))
+                .with_label(span, format!("the list has {} item(s)", length)),
+
+            RuntimeError::EmptyList(span) => {
+                Diagnostic::error("E0407", "attempt to pop from an empty list")
+                    .with_label(span, "the list is empty")
+            }
+
+            RuntimeError::MissingKey { key, span } => {
+                Diagnostic::error("E0408", format!("the key {} isn't in the map", key))
+                    .with_label(span, "looked up here")
+                    .with_help("check for it first with `contains`")
+            }
+
+            RuntimeError::Panic { message, span } => {
+                Diagnostic::error("E0417", format!("the program panicked: {}", message))
+                    .with_label(span, "panicked here")
+            }
+
+            RuntimeError::AssertionFailed { message, span } => {
+                let title = match message {
+                    Some(message) => format!("assertion failed: {}", message),
+                    None => "assertion failed".to_string(),
+                };
+                Diagnostic::error("E0418", title).with_label(span, "asserted here")
+            }
+
+            RuntimeError::InvalidJson { message, span } => {
+                Diagnostic::error("E0415", format!("invalid JSON: {}", message))
+                    .with_label(span, "parsed here")
+            }
+
+            RuntimeError::InvalidRegex {
+                pattern,
+                message,
+                span,
+            } => Diagnostic::error("E0416", format!("`{}` isn't a valid regex", pattern))
+                .with_label(span, message),
+
+            RuntimeError::Spawn {
+                command,
+                error,
+                span,
+            } => Diagnostic::error("E0412", format!("couldn't run `{}`: {}", command, error))
+                .with_label(span, "run here"),
+
+            RuntimeError::Network {
+                action,
+                error,
+                span,
+            } => Diagnostic::error("E0413", format!("couldn't {}: {}", action, error))
+                .with_label(span, "while doing this"),
+
+            RuntimeError::InvalidSocket {
+                socket,
+                listener,
+                span,
+            } => {
+                let kind = if listener { "listener" } else { "connection" };
+                Diagnostic::error("E0414", format!("{} isn't an open {}", socket, kind))
+                    .with_label(span, format!("expected a {}", kind))
+                    .with_note(
+                        "`tcp_listen` opens listeners, `tcp_connect` and `tcp_accept` \
+                         open connections",
+                    )
+            }
+
+            RuntimeError::EmptyRange { start, end, span } => Diagnostic::error(
+                "E0411",
+                format!("there are no ints from {} up to {}", start, end),
+            )
+            .with_label(span, "picked from here")
+            .with_note("the end isn't included, so it has to be greater than the start"),
+
+            RuntimeError::InvalidEnvName { name, span } => Diagnostic::error(
+                "E0410",
+                format!("`{}` isn't a valid name for an environment variable", name),
+            )
+            .with_label(span, "set here")
+            .with_note("names can't be empty or contain `=`"),
+
+            RuntimeError::Io {
+                path,
+                reading,
+                error,
+                span,
+            } => {
+                let (verb, label) = if reading {
+                    ("read", "while reading this file")
+                } else {
+                    ("write", "while writing this file")
+                };
+
+                Diagnostic::error("E0409", format!("couldn't {} `{}`: {}", verb, path, error))
+                    .with_label(span, label)
+            }
+
+            RuntimeError::Sandboxed { builtin, span } => Diagnostic::error(
+                "E0419",
+                format!("`{}` can't be used in a sandbox", builtin.name()),
+            )
+            .with_label(span, "called here")
+            .with_note("sandboxed programs can't use files, the network, other programs, the environment or `sleep`"),
+
+            RuntimeError::TimedOut(span) => {
+                Diagnostic::error("E0420", "the program ran out of time")
+                    .with_label(span, "stopped here")
+            }
+        }
+    }
+}
+
+// std has no clock on wasm32-unknown-unknown, like in a browser, it panics
+// when it's read. There `now` and `clock` give 0, `sleep` doesn't sleep and the
+// numbers are random from a fixed seed
+const HAS_CLOCK: bool = !cfg!(all(target_arch = "wasm32", target_os = "unknown"));
+
+// splitmix64, which the runtimes of the backends implement too, so a program
+// given a seed picks the same numbers wherever it runs
+#[derive(Debug)]
+struct Rng(Cell<u64>);
+
+impl Rng {
+    // seeded from the time unless the program calls `seed`
+    fn from_time() -> Self {
+        if !HAS_CLOCK {
+            return Rng(Cell::new(0));
+        }
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_nanos() as u64);
+        Rng(Cell::new(nanos))
+    }
+
+    fn seed(&self, seed: i64) {
+        self.0.set(seed as u64);
+    }
+
+    fn next(&self) -> u64 {
+        let state = self.0.get().wrapping_add(0x9e3779b97f4a7c15);
+        self.0.set(state);
+
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    // from 0 up to 1, with the 53 bits a float has
+    fn float(&self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    // the numbers below the first multiple of `count` are thrown away, so every
+    // one is as likely
+    fn below(&self, count: u64) -> u64 {
+        let skip = count.wrapping_neg() % count;
+        loop {
+            let number = self.next();
+            if number >= skip {
+                return number % count;
+            }
+        }
+    }
+}
+
+// what the ints the program gets for sockets point at, closed ones are left
+// empty so the ints of the others don't change
+#[derive(Debug)]
+enum Socket {
+    Listener(TcpListener),
+    Connection(TcpStream),
+}
+
+// a function along with the environment it was created in
+#[derive(Debug)]
+pub struct Closure<'a> {
+    pub(crate) code: Code<'a>,
+    pub(crate) env: Rc<Env<'a>>,
+}
+
+// what a closure runs, a function of the HIR or one of a bytecode module, by
+// its index
+#[derive(Debug)]
+pub(crate) enum Code<'a> {
+    Hir {
+        params: &'a [DefId],
+        body: &'a [Stmt],
+    },
+    Bytecode(usize),
+}
+
+#[derive(Debug, Clone)]
+pub enum Value<'a> {
+    // what calling a function without a return type gives
+    Void,
+    Int(i64),
+    Float(f64),
+    String(Rc<str>),
+    Bool(bool),
+    Function(Rc<Closure<'a>>),
+    Host(Arc<HostFunction>),
+    // a variant with a payload that wasn't given its fields yet
+    Constructor {
+        enum_name: &'a str,
+        variant: &'a str,
+        index: usize,
+    },
+    Variant {
+        enum_name: &'a str,
+        variant: &'a str,
+        index: usize,
+        fields: Vec<Value<'a>>,
+    },
+    // lists and maps are shared, changes through one value show in all of them
+    List(Rc<RefCell<Vec<Value<'a>>>>),
+    Map(Rc<RefCell<Map<'a>>>),
+}
+
+// what maps are keyed by, the values that can be hashed
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Key {
+    Int(i64),
+    String(Rc<str>),
+    Bool(bool),
+}
+
+impl Key {
+    pub(crate) fn new(value: &Value) -> Self {
+        match value {
+            Value::Int(value) => Key::Int(*value),
+            Value::String(value) => Key::String(value.clone()),
+            Value::Bool(value) => Key::Bool(*value),
+            value => unreachable!("{:?} can't be a key", value),
+        }
+    }
+
+    fn value<'a>(&self) -> Value<'a> {
+        match self {
+            Key::Int(value) => Value::Int(*value),
+            Key::String(value) => Value::String(value.clone()),
+            Key::Bool(value) => Value::Bool(*value),
+        }
+    }
+}
+
+// a hash map that keeps the order its keys were inserted in, removed entries
+// leave a hole that's cleared once holes make up half of the entries
+#[derive(Debug, Default)]
+pub struct Map<'a> {
+    entries: Vec<Option<(Key, Value<'a>)>>,
+    indices: HashMap<Key, usize>,
+}
+
+impl<'a> Map<'a> {
+    fn len(&self) -> usize {
+        self.indices.len()
+    }
+
+    fn get(&self, key: &Key) -> Option<&Value<'a>> {
+        let index = *self.indices.get(key)?;
+        self.entries[index].as_ref().map(|(_, value)| value)
+    }
+
+    pub(crate) fn insert(&mut self, key: Key, value: Value<'a>) {
+        match self.indices.get(&key) {
+            Some(index) => self.entries[*index] = Some((key, value)),
+            None => {
+                self.indices.insert(key.clone(), self.entries.len());
+                self.entries.push(Some((key, value)));
+            }
+        }
+    }
+
+    fn remove(&mut self, key: &Key) -> Option<Value<'a>> {
+        let index = self.indices.remove(key)?;
+        let (_, value) = self.entries[index].take()?;
+
+        if self.indices.len() * 2 < self.entries.len() {
+            self.entries.retain(Option::is_some);
+            for (index, (key, _)) in self.entries.iter().flatten().enumerate() {
+                self.indices.insert(key.clone(), index);
+            }
+        }
+
+        Some(value)
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (&Key, &Value<'a>)> {
+        self.entries
+            .iter()
+            .flatten()
+            .map(|(key, value)| (key, value))
+    }
+}
+
+impl PartialEq for Value<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Void, Value::Void) => true,
+            (Value::Int(left), Value::Int(right)) => left == right,
+            (Value::Float(left), Value::Float(right)) => left == right,
+            (Value::String(left), Value::String(right)) => left == right,
+            (Value::Bool(left), Value::Bool(right)) => left == right,
+            // functions are only equal to themselves
+            (Value::Function(left), Value::Function(right)) => Rc::ptr_eq(left, right),
+            (Value::Host(left), Value::Host(right)) => Arc::ptr_eq(left, right),
+            (
+                Value::Constructor {
+                    enum_name, index, ..
+                },
+                Value::Constructor {
+                    enum_name: other_name,
+                    index: other_index,
+                    ..
+                },
+            ) => enum_name == other_name && index == other_index,
+            (
+                Value::Variant { index, fields, .. },
+                Value::Variant {
+                    index: other_index,
+                    fields: other_fields,
+                    ..
+                },
+            ) => index == other_index && fields == other_fields,
+            // lists are equal when their items are, maps when they have the same
+            // entries, in any order
+            (Value::List(left), Value::List(right)) => *left.borrow() == *right.borrow(),
+            (Value::Map(left), Value::Map(right)) => {
+                let (left, right) = (left.borrow(), right.borrow());
+                left.len() == right.len()
+                    && left
+                        .iter()
+                        .all(|(key, value)| right.get(key) == Some(value))
+            }
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Value<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Void => write!(f, "()"),
+            Value::Int(value) => write!(f, "{}", value),
+            Value::Float(value) => write!(f, "{:?}", value),
+            Value::String(value) => write!(f, "{}", value),
+            Value::Bool(value) => write!(f, "{}", value),
+            Value::Function(_) | Value::Host(_) => write!(f, "<fn>"),
+            Value::Constructor {
+                enum_name, variant, ..
+            } => write!(f, "<fn {}.{}>", enum_name, variant),
+            Value::Variant {
+                enum_name,
+                variant,
+                fields,
+                ..
+            } => {
+                write!(f, "{}.{}", enum_name, variant)?;
+                if fields.is_empty() {
+                    return Ok(());
+                }
+
+                write!(f, "(")?;
+                for (index, field) in fields.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    field.fmt_nested(f)?;
+                }
+                write!(f, ")")
+            }
+            Value::List(items) => {
+                write!(f, "[")?;
+                for (index, item) in items.borrow().iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    item.fmt_nested(f)?;
+                }
+                write!(f, "]")
+            }
+            Value::Map(map) if map.borrow().len() == 0 => write!(f, "[:]"),
+            Value::Map(map) => {
+                write!(f, "[")?;
+                for (index, (key, value)) in map.borrow().iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    key.value().fmt_nested(f)?;
+                    write!(f, ": ")?;
+                    value.fmt_nested(f)?;
+                }
+                write!(f, "]")
+            }
+        }
+    }
+}
+
+impl Value<'_> {
+    // strings inside other values are quoted
+    fn fmt_nested(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::String(value) => write!(f, "{:?}", value),
+            _ => write!(f, "{}", self),
+        }
+    }
+}
+
+// the locals of a call, looked up through the environments the function was
+// created in when they aren't found
+#[derive(Debug, Default)]
+pub struct Env<'a> {
+    pub(crate) values: RefCell<HashMap<DefId, Value<'a>>>,
+    pub(crate) parent: Option<Rc<Env<'a>>>,
+}
+
+impl<'a> Env<'a> {
+    pub(crate) fn child(parent: &Rc<Env<'a>>) -> Self {
+        Self {
+            values: RefCell::default(),
+            parent: Some(parent.clone()),
+        }
+    }
+
+    pub(crate) fn define(&self, local: DefId, value: Value<'a>) {
+        self.values.borrow_mut().insert(local, value);
+    }
+
+    pub(crate) fn get(&self, local: DefId) -> Value<'a> {
+        match self.values.borrow().get(&local) {
+            Some(value) => value.clone(),
+            None => self
+                .parent
+                .as_ref()
+                .expect("locals are defined before they're read")
+                .get(local),
+        }
+    }
+
+    pub(crate) fn assign(&self, local: DefId, value: Value<'a>) {
+        match self.values.borrow_mut().get_mut(&local) {
+            Some(slot) => *slot = value,
+            None => self
+                .parent
+                .as_ref()
+                .expect("locals are defined before they're assigned")
+                .assign(local, value),
+        }
+    }
+}
+
+// how a statement finished, anything but `Normal` unwinds to the enclosing loop
+// or function
+enum Flow<'a> {
+    Normal,
+    Break,
+    Continue,
+    Return(Value<'a>),
+}
+
+// runs the HIR directly, this only sees programs that passed every check, so
+// type errors can't happen and anything unexpected is a bug in the compiler
+pub struct Interpreter<'a> {
+    hir: &'a Hir,
+    pub(crate) globals: Rc<Env<'a>>,
+    pub(crate) depth: usize,
+    // what `args()` gives back
+    args: Vec<String>,
+    // `clock()` counts from here
+    start: Option<Instant>,
+    rng: Rng,
+    sockets: RefCell<Vec<Option<Socket>>>,
+    // the patterns of the `regex_*` builtins are only compiled the first time
+    // they're used, loops tend to use the same ones over and over
+    regexes: RefCell<HashMap<String, Regex>>,
+    // the least severe messages of the `log_*` builtins that are written
+    log_level: LogLevel,
+    // where `print` writes to, stdout unless it's given another
+    output: RefCell<Box<dyn Write + 'a>>,
+    // where the `log_*` builtins write to, stderr unless it's given another
+    logs: RefCell<Box<dyn Write + 'a>>,
+    // when the program is stopped, if it's still running
+    deadline: Option<Instant>,
+    // the builtins that reach outside the program fail
+    sandboxed: bool,
+    // last, so it's dropped after the globals and can free their cycles
+    pub(crate) heap: Heap<'a>,
+}
+
+impl<'a> Interpreter<'a> {
+    pub fn new(hir: &'a Hir) -> Self {
+        let heap = Heap::new(gc::DEFAULT_THRESHOLD);
+        Self {
+            hir,
+            globals: heap.env(Env::default()),
+            depth: 0,
+            args: vec![],
+            start: HAS_CLOCK.then(Instant::now),
+            rng: Rng::from_time(),
+            sockets: RefCell::default(),
+            regexes: RefCell::default(),
+            log_level: LogLevel::from_env().unwrap_or_default(),
+            output: RefCell::new(Box::new(io::stdout())),
+            logs: RefCell::new(Box::new(io::stderr())),
+            deadline: None,
+            sandboxed: false,
+            heap,
+        }
+    }
+
+    // the arguments given to the program on the command line
+    pub fn with_args(mut self, args: Vec<String>) -> Self {
+        self.args = args;
+        self
+    }
+
+    // the level given on the command line, it wins over `EZ_LOG`
+    pub fn with_log_level(mut self, log_level: LogLevel) -> Self {
+        self.log_level = log_level;
+        self
+    }
+
+    // what the program prints goes there instead of stdout
+    pub fn with_output(self, output: impl Write + 'a) -> Self {
+        *self.output.borrow_mut() = Box::new(output);
+        self
+    }
+
+    // what the program logs goes there instead of stderr
+    pub fn with_logs(self, logs: impl Write + 'a) -> Self {
+        *self.logs.borrow_mut() = Box::new(logs);
+        self
+    }
+
+    pub fn with_deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    // for programs that can't be trusted, like the ones of a playground
+    pub fn with_sandbox(mut self) -> Self {
+        self.sandboxed = true;
+        self
+    }
+
+    // how many objects are made between collections
+    pub fn with_gc_threshold(mut self, threshold: usize) -> Self {
+        self.heap.set_threshold(threshold);
+        self
+    }
+
+    // what the collector did is written to stderr when the program is done
+    pub fn with_gc_stats(mut self) -> Self {
+        self.heap.set_report(true);
+        self
+    }
+
+    // the functions of the host, given in the order they were to the checks
+    pub fn with_hosts(self, hosts: &[Arc<HostFunction>]) -> Self {
+        for (local, host) in self.hir.hosts.iter().zip(hosts) {
+            self.globals.define(*local, Value::Host(host.clone()));
+        }
+        self
+    }
+
+    // runs with the top level variables of earlier runs, for the REPL
+    pub fn with_globals(hir: &'a Hir, globals: Rc<Env<'a>>) -> Self {
+        Self {
+            globals,
+            ..Self::new(hir)
+        }
+    }
+
+    fn variant(&self, enum_name: Symbol, index: usize) -> (&'a str, bool) {
+        let variant = &self.hir.enums[&enum_name][index];
+        (&variant.identifier.name, variant.payload.is_empty())
+    }
+
+    fn eval(
+        &mut self,
+        value: &'a hir::Value,
+        env: &Rc<Env<'a>>,
+    ) -> Result<Value<'a>, RuntimeError> {
+        let result = match &value.kind {
+            ValueKind::Int(value) => Value::Int(*value),
+            ValueKind::Float(value) => Value::Float(*value),
+            ValueKind::String(value) => Value::String(value.as_str().into()),
+            ValueKind::Bool(value) => Value::Bool(*value),
+            ValueKind::Local(local) => env.get(*local),
+
+            // `and` and `or` don't evaluate their right side when the left one
+            // decides the result
+            ValueKind::Binary {
+                operator: operator @ (TokenKind::And | TokenKind::Or),
+                left,
+                right,
+            } => {
+                let left = self.eval(left, env)?;
+                match (operator, left) {
+                    (TokenKind::And, Value::Bool(false)) => Value::Bool(false),
+                    (TokenKind::Or, Value::Bool(true)) => Value::Bool(true),
+                    _ => self.eval(right, env)?,
+                }
+            }
+
+            ValueKind::Binary {
+                operator,
+                left,
+                right,
+            } => {
+                let left = self.eval(left, env)?;
+                let right = self.eval(right, env)?;
+                Self::binary(operator, left, right, value.span)?
+            }
+
+            ValueKind::Unary { operator, operand } => match (operator, self.eval(operand, env)?) {
+                (TokenKind::Minus, Value::Int(operand)) => {
+                    Value::Int(operand.checked_neg().ok_or(RuntimeError::Overflow {
+                        operator: operator.clone(),
+                        span: value.span,
+                    })?)
+                }
+                (TokenKind::Minus, Value::Float(operand)) => Value::Float(-operand),
+                (TokenKind::Not, Value::Bool(operand)) => Value::Bool(!operand),
+                (TokenKind::BitNot, Value::Int(operand)) => Value::Int(!operand),
+                (operator, operand) => {
+                    unreachable!("{} can't be applied to {:?}", operator, operand)
+                }
+            },
+
+            ValueKind::Cast { value: inner } => Self::cast(self.eval(inner, env)?, &value.basetype),
+
+            ValueKind::Call { callee, args } => {
+                let callee = self.eval(callee, env)?;
+                let args = args
+                    .iter()
+                    .map(|arg| self.eval(arg, env))
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                self.call(callee, args, value.span)?
+            }
+
+            ValueKind::Builtin { builtin, args } => {
+                let args = args
+                    .iter()
+                    .map(|arg| self.eval(arg, env))
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                self.builtin(*builtin, &args, value.span)?
+            }
+
+            ValueKind::Function { params, body } => self.heap.closure(Closure {
+                code: Code::Hir { params, body },
+                env: env.clone(),
+            }),
+
+            ValueKind::List(items) => {
+                let items = items
+                    .iter()
+                    .map(|item| self.eval(item, env))
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                self.heap.list(items)
+            }
+
+            ValueKind::Map(entries) => {
+                let mut map = Map::default();
+                for (key, value) in entries {
+                    let key = Key::new(&self.eval(key, env)?);
+                    map.insert(key, self.eval(value, env)?);
+                }
+
+                self.heap.map(map)
+            }
+
+            ValueKind::Variant { enum_name, index } => {
+                let (variant, unit) = self.variant(*enum_name, *index);
+                if unit {
+                    Value::Variant {
+                        enum_name,
+                        variant,
+                        index: *index,
+                        fields: vec![],
+                    }
+                } else {
+                    Value::Constructor {
+                        enum_name,
+                        variant,
+                        index: *index,
+                    }
+                }
+            }
+        };
+
+        Ok(result)
+    }
+
+    pub(crate) fn binary(
+        operator: &TokenKind,
+        left: Value<'a>,
+        right: Value<'a>,
+        span: Span,
+    ) -> Result<Value<'a>, RuntimeError> {
+        let overflow = || RuntimeError::Overflow {
+            operator: operator.clone(),
+            span,
+        };
+
+        let result = match (operator, left, right) {
+            (TokenKind::Equals, left, right) => Value::Bool(left == right),
+            (TokenKind::NotEquals, left, right) => Value::Bool(left != right),
+
+            (TokenKind::Plus, Value::String(left), Value::String(right)) => {
+                Value::String(format!("{}{}", left, right).into())
+            }
+
+            (TokenKind::DividedBy, Value::Int(_), Value::Int(0)) => {
+                return Err(RuntimeError::DivisionByZero(span))
+            }
+
+            (operator, Value::Int(left), Value::Int(right)) => match operator {
+                TokenKind::Plus => Value::Int(left.checked_add(right).ok_or_else(overflow)?),
+                TokenKind::Minus => Value::Int(left.checked_sub(right).ok_or_else(overflow)?),
+                TokenKind::Times => Value::Int(left.checked_mul(right).ok_or_else(overflow)?),
+                TokenKind::DividedBy => Value::Int(left.checked_div(right).ok_or_else(overflow)?),
+                TokenKind::BitAnd => Value::Int(left & right),
+                TokenKind::BitOr => Value::Int(left | right),
+                TokenKind::BitXor => Value::Int(left ^ right),
+                TokenKind::GreaterThan => Value::Bool(left > right),
+                TokenKind::GreaterOrEquals => Value::Bool(left >= right),
+                TokenKind::LowerThan => Value::Bool(left < right),
+                TokenKind::LowerOrEquals => Value::Bool(left <= right),
+                _ => unreachable!("{} can't be applied to ints", operator),
+            },
+
+            (operator, Value::Float(left), Value::Float(right)) => match operator {
+                TokenKind::Plus => Value::Float(left + right),
+                TokenKind::Minus => Value::Float(left - right),
+                TokenKind::Times => Value::Float(left * right),
+                TokenKind::DividedBy => Value::Float(left / right),
+                TokenKind::GreaterThan => Value::Bool(left > right),
+                TokenKind::GreaterOrEquals => Value::Bool(left >= right),
+                TokenKind::LowerThan => Value::Bool(left < right),
+                TokenKind::LowerOrEquals => Value::Bool(left <= right),
+                _ => unreachable!("{} can't be applied to floats", operator),
+            },
+
+            (operator, left, right) => {
+                unreachable!(
+                    "{} can't be applied to {:?} and {:?}",
+                    operator, left, right
+                )
+            }
+        };
+
+        Ok(result)
+    }
+
+    // the conversions allowed by `typecheck::can_cast`
+    pub(crate) fn cast(value: Value<'a>, to: &BaseType) -> Value<'a> {
+        match (value, to) {
+            (Value::Int(value), BaseType::Float) => Value::Float(value as f64),
+            // floats are truncated, and saturate at the bounds of an int
+            (Value::Float(value), BaseType::Int) => Value::Int(value as i64),
+            (Value::Bool(value), BaseType::Int) => Value::Int(value as i64),
+            (value @ (Value::Int(_) | Value::Float(_) | Value::Bool(_)), BaseType::String) => {
+                Value::String(value.to_string().into())
+            }
+            (value, _) => value,
+        }
+    }
+
+    pub(crate) fn check_deadline(&self, span: Span) -> Result<(), RuntimeError> {
+        match self.deadline {
+            Some(deadline) if Instant::now() >= deadline => Err(RuntimeError::TimedOut(span)),
+            _ => Ok(()),
+        }
+    }
+
+    // the output of `print` is flushed right away, so it's not lost when the
+    // program stops with an error
+    pub(crate) fn builtin(
+        &self,
+        builtin: Builtin,
+        args: &[Value<'a>],
+        span: Span,
+    ) -> Result<Value<'a>, RuntimeError> {
+        let outside = matches!(
+            builtin,
+            Builtin::ReadFile
+                | Builtin::WriteFile
+                | Builtin::ReadLines
+                | Builtin::WriteLines
+                | Builtin::RunCommand
+                | Builtin::EnvGet
+                | Builtin::EnvSet
+                | Builtin::Sleep
+                | Builtin::TcpConnect
+                | Builtin::TcpListen
+                | Builtin::TcpAccept
+                | Builtin::Read
+                | Builtin::Write
+                | Builtin::Close
+        );
+        if self.sandboxed && outside {
+            return Err(RuntimeError::Sandboxed { builtin, span });
+        }
+
+        let value = match (builtin, args) {
+            (Builtin::Print | Builtin::Println, args) => {
+                let mut output = self.output.borrow_mut();
+                for arg in args {
+                    let _ = write!(output, "{}", arg);
+                }
+                if builtin == Builtin::Println {
+                    let _ = writeln!(output);
+                }
+                let _ = output.flush();
+                Value::Void
+            }
+
+            // logs go to stderr, or where they're given to, with their level in
+            // front, after what was printed so far
+            (
+                Builtin::LogDebug | Builtin::LogInfo | Builtin::LogWarn | Builtin::LogError,
+                [message],
+            ) => {
+                let level = LogLevel::of(builtin).expect("the builtin logs");
+                if level >= self.log_level {
+                    let _ = self.output.borrow_mut().flush();
+                    let mut logs = self.logs.borrow_mut();
+                    let _ = writeln!(logs, "[{}] {}", level.name(), message);
+                    let _ = logs.flush();
+                }
+                Value::Void
+            }
+
+            (Builtin::Format, [Value::String(template), args @ ..]) => {
+                let pieces = template::pieces(template).expect("templates are checked");
+                let mut text = pieces[0].clone();
+                for (arg, piece) in args.iter().zip(&pieces[1..]) {
+                    text.push_str(&arg.to_string());
+                    text.push_str(piece);
+                }
+                Value::String(text.into())
+            }
+
+            (Builtin::Args, []) => {
+                let args = self
+                    .args
+                    .iter()
+                    .map(|arg| Value::String(arg.as_str().into()))
+                    .collect();
+                self.heap.list(args)
+            }
+
+            (Builtin::RunCommand, [Value::String(command), Value::List(args)]) => {
+                let args: Vec<String> = args.borrow().iter().map(Value::to_string).collect();
+                self.run_command(command, &args, span)?
+            }
+
+            (Builtin::TcpConnect, [Value::String(host), Value::Int(port)]) => {
+                let action = format!("connect to `{}:{}`", host, port);
+                let stream = Self::port(*port)
+                    .and_then(|port| TcpStream::connect((&**host, port)))
+                    .map_err(|error| RuntimeError::Network {
+                        action,
+                        error,
+                        span,
+                    })?;
+                self.open(Socket::Connection(stream))
+            }
+            (Builtin::TcpListen, [Value::String(host), Value::Int(port)]) => {
+                let action = format!("listen on `{}:{}`", host, port);
+                let listener = Self::port(*port)
+                    .and_then(|port| TcpListener::bind((&**host, port)))
+                    .map_err(|error| RuntimeError::Network {
+                        action,
+                        error,
+                        span,
+                    })?;
+                self.open(Socket::Listener(listener))
+            }
+            // waits for the next connection
+            (Builtin::TcpAccept, [Value::Int(socket)]) => {
+                let accepted = match self.sockets.borrow().get(*socket as usize) {
+                    Some(Some(Socket::Listener(listener))) => listener.accept(),
+                    _ => {
+                        return Err(RuntimeError::InvalidSocket {
+                            socket: *socket,
+                            listener: true,
+                            span,
+                        })
+                    }
+                };
+                let (stream, _) = accepted.map_err(|error| RuntimeError::Network {
+                    action: format!("accept a connection on {}", socket),
+                    error,
+                    span,
+                })?;
+                self.open(Socket::Connection(stream))
+            }
+            // what arrived, at most 4096 bytes of it, and nothing once the other
+            // side closed the connection
+            (Builtin::Read, [Value::Int(socket)]) => {
+                let mut buffer = [0; 4096];
+                let read =
+                    self.with_connection(*socket, span, |stream| stream.read(&mut buffer))?;
+                Value::String(String::from_utf8_lossy(&buffer[..read]).into())
+            }
+            (Builtin::Write, [Value::Int(socket), Value::String(text)]) => {
+                self.with_connection(*socket, span, |stream| stream.write_all(text.as_bytes()))?;
+                Value::Void
+            }
+            (Builtin::Close, [Value::Int(socket)]) => {
+                match self.sockets.borrow_mut().get_mut(*socket as usize) {
+                    Some(open @ Some(_)) => *open = None,
+                    _ => {
+                        return Err(RuntimeError::InvalidSocket {
+                            socket: *socket,
+                            listener: false,
+                            span,
+                        })
+                    }
+                }
+                Value::Void
+            }
+
+            (Builtin::Assert, [Value::Bool(condition), message @ ..]) => {
+                if !condition {
+                    let message = match message {
+                        [Value::String(message)] => Some(message.to_string()),
+                        _ => None,
+                    };
+                    return Err(RuntimeError::AssertionFailed { message, span });
+                }
+                Value::Void
+            }
+            (Builtin::Panic, [Value::String(message)]) => {
+                return Err(RuntimeError::Panic {
+                    message: message.to_string(),
+                    span,
+                })
+            }
+
+            (Builtin::JsonParse, [Value::String(text)]) => match Json::parse(text) {
+                Ok(json) => self.json_value(json),
+                Err(message) => return Err(RuntimeError::InvalidJson { message, span }),
+            },
+            (Builtin::JsonStringify, [value]) => {
+                let mut out = String::new();
+                Self::write_json(value, &mut out);
+                Value::String(out.into())
+            }
+
+            // a match anywhere in the text counts, `^` and `$` anchor the pattern
+            (Builtin::RegexMatch, [Value::String(pattern), Value::String(text)]) => {
+                Value::Bool(self.regex(pattern, span)?.is_match(text))
+            }
+            (Builtin::RegexFindAll, [Value::String(pattern), Value::String(text)]) => {
+                let found = self
+                    .regex(pattern, span)?
+                    .find_iter(text)
+                    .map(|found| Value::String(found.as_str().into()))
+                    .collect();
+                self.heap.list(found)
+            }
+            // every match is replaced, `$1` in the replacement is the first group
+            (
+                Builtin::RegexReplace,
+                [Value::String(pattern), Value::String(text), Value::String(replacement)],
+            ) => {
+                let regex = self.regex(pattern, span)?;
+                Value::String(regex.replace_all(text, &**replacement).into())
+            }
+
+            // variables that aren't set are empty, like in a shell
+            (Builtin::EnvGet, [Value::String(name)]) => {
+                Value::String(std::env::var(&**name).unwrap_or_default().into())
+            }
+            (Builtin::EnvSet, [Value::String(name), Value::String(value)]) => {
+                if name.is_empty() || name.contains(['=', '\0']) || value.contains('\0') {
+                    return Err(RuntimeError::InvalidEnvName {
+                        name: name.to_string(),
+                        span,
+                    });
+                }
+                std::env::set_var(&**name, &**value);
+                Value::Void
+            }
+
+            // milliseconds since the Unix epoch, the time before it is negative
+            (Builtin::Now, []) if !HAS_CLOCK => Value::Int(0),
+            (Builtin::Now, []) => {
+                let millis = match SystemTime::now().duration_since(UNIX_EPOCH) {
+                    Ok(since) => since.as_millis() as i64,
+                    Err(error) => -(error.duration().as_millis() as i64),
+                };
+                Value::Int(millis)
+            }
+            // seconds since the program started, which only go forward
+            (Builtin::Clock, []) => Value::Float(
+                self.start
+                    .map_or(0.0, |start| start.elapsed().as_secs_f64()),
+            ),
+            // sleeping for a negative time doesn't sleep at all
+            (Builtin::Sleep, [Value::Int(_)]) if !HAS_CLOCK => Value::Void,
+            (Builtin::Sleep, [Value::Int(millis)]) => {
+                std::thread::sleep(Duration::from_millis((*millis).max(0) as u64));
+                Value::Void
+            }
+
+            (Builtin::Random, []) => Value::Float(self.rng.float()),
+            // from the start up to the end, like ranges
+            (Builtin::RandomInt, [Value::Int(start), Value::Int(end)]) => {
+                if start >= end {
+                    return Err(RuntimeError::EmptyRange {
+                        start: *start,
+                        end: *end,
+                        span,
+                    });
+                }
+                let count = end.wrapping_sub(*start) as u64;
+                Value::Int(start.wrapping_add(self.rng.below(count) as i64))
+            }
+            (Builtin::Seed, [Value::Int(seed)]) => {
+                self.rng.seed(*seed);
+                Value::Void
+            }
+
+            (Builtin::ReadFile, [Value::String(path)]) => {
+                Value::String(Self::read_file(path, span)?.into())
+            }
+            (Builtin::ReadLines, [Value::String(path)]) => {
+                let lines = Self::read_file(path, span)?
+                    .lines()
+                    .map(|line| Value::String(line.into()))
+                    .collect();
+                self.heap.list(lines)
+            }
+            (Builtin::WriteFile, [Value::String(path), Value::String(contents)]) => {
+                Self::write_file(path, contents, span)?;
+                Value::Void
+            }
+            // every line ends with a `\n`, the last one too
+            (Builtin::WriteLines, [Value::String(path), Value::List(lines)]) => {
+                let mut contents = String::new();
+                for line in lines.borrow().iter() {
+                    contents.push_str(&line.to_string());
+                    contents.push('\n');
+                }
+                Self::write_file(path, &contents, span)?;
+                Value::Void
+            }
+
+            (Builtin::Sqrt, [Value::Float(value)]) => Value::Float(value.sqrt()),
+            (Builtin::Floor, [Value::Float(value)]) => Value::Float(value.floor()),
+            (Builtin::Ceil, [Value::Float(value)]) => Value::Float(value.ceil()),
+            (Builtin::Pow, [Value::Float(base), Value::Float(exponent)]) => {
+                Value::Float(base.powf(*exponent))
+            }
+
+            // like negating it, the absolute value of the smallest int doesn't fit
+            (Builtin::Abs, [Value::Int(value)]) => {
+                Value::Int(value.checked_abs().ok_or(RuntimeError::Overflow {
+                    operator: TokenKind::Minus,
+                    span,
+                })?)
+            }
+            (Builtin::Abs, [Value::Float(value)]) => Value::Float(value.abs()),
+            (Builtin::Min, [Value::Int(left), Value::Int(right)]) => Value::Int(*left.min(right)),
+            (Builtin::Min, [Value::Float(left), Value::Float(right)]) => {
+                Value::Float(left.min(*right))
+            }
+            (Builtin::Max, [Value::Int(left), Value::Int(right)]) => Value::Int(*left.max(right)),
+            (Builtin::Max, [Value::Float(left), Value::Float(right)]) => {
+                Value::Float(left.max(*right))
+            }
+
+            (Builtin::Len, [Value::String(value)]) => Value::Int(value.chars().count() as i64),
+            (Builtin::Substring, [Value::String(value), Value::Int(start), Value::Int(end)]) => {
+                let length = value.chars().count();
+                if *start < 0 || start > end || *end as usize > length {
+                    return Err(RuntimeError::SubstringOutOfBounds {
+                        start: *start,
+                        end: *end,
+                        length,
+                        span,
+                    });
+                }
+
+                let substring: String = value
+                    .chars()
+                    .skip(*start as usize)
+                    .take((end - start) as usize)
+                    .collect();
+                Value::String(substring.into())
+            }
+            (Builtin::Contains, [Value::String(value), Value::String(part)]) => {
+                Value::Bool(value.contains(&**part))
+            }
+            // only ASCII letters change case, and only ASCII spaces are trimmed
+            (Builtin::ToUpper, [Value::String(value)]) => {
+                Value::String(value.to_ascii_uppercase().into())
+            }
+            (Builtin::ToLower, [Value::String(value)]) => {
+                Value::String(value.to_ascii_lowercase().into())
+            }
+            (Builtin::Trim, [Value::String(value)]) => {
+                Value::String(value.trim_matches(|c: char| c.is_ascii_whitespace()).into())
+            }
+            (Builtin::ParseInt, [Value::String(value)]) => {
+                let int = value.parse().map_err(|_| RuntimeError::InvalidNumber {
+                    text: value.to_string(),
+                    basetype: BaseType::Int,
+                    span,
+                })?;
+                Value::Int(int)
+            }
+            // `inf` and `NaN` aren't numbers that can be written in ez
+            (Builtin::ParseFloat, [Value::String(value)]) => {
+                let float = value
+                    .parse()
+                    .ok()
+                    .filter(|_| {
+                        !value.contains(|c: char| c.is_alphabetic() && c != 'e' && c != 'E')
+                    })
+                    .ok_or_else(|| RuntimeError::InvalidNumber {
+                        text: value.to_string(),
+                        basetype: BaseType::Float,
+                        span,
+                    })?;
+                Value::Float(float)
+            }
+
+            (Builtin::Split, [Value::String(value), Value::String(separator)]) => {
+                let parts: Vec<Value> = if separator.is_empty() {
+                    value
+                        .chars()
+                        .map(|char| Value::String(char.to_string().into()))
+                        .collect()
+                } else {
+                    value
+                        .split(&**separator)
+                        .map(|part| Value::String(part.into()))
+                        .collect()
+                };
+                self.heap.list(parts)
+            }
+
+            (Builtin::Len, [Value::List(items)]) => Value::Int(items.borrow().len() as i64),
+            (Builtin::Push, [Value::List(items), item]) => {
+                items.borrow_mut().push(item.clone());
+                Value::Void
+            }
+            (Builtin::Pop, [Value::List(items)]) => items
+                .borrow_mut()
+                .pop()
+                .ok_or(RuntimeError::EmptyList(span))?,
+            (Builtin::Get, [Value::List(items), Value::Int(index)]) => {
+                let items = items.borrow();
+                items[Self::index(*index, items.len(), span)?].clone()
+            }
+            (Builtin::Set, [Value::List(items), Value::Int(index), item]) => {
+                let mut items = items.borrow_mut();
+                let index = Self::index(*index, items.len(), span)?;
+                items[index] = item.clone();
+                Value::Void
+            }
+            (Builtin::Remove, [Value::List(items), Value::Int(index)]) => {
+                let mut items = items.borrow_mut();
+                let index = Self::index(*index, items.len(), span)?;
+                items.remove(index)
+            }
+            (Builtin::Contains, [Value::List(items), item]) => {
+                Value::Bool(items.borrow().contains(item))
+            }
+
+            (Builtin::Len, [Value::Map(map)]) => Value::Int(map.borrow().len() as i64),
+            (Builtin::Insert, [Value::Map(map), key, value]) => {
+                map.borrow_mut().insert(Key::new(key), value.clone());
+                Value::Void
+            }
+            (Builtin::Get, [Value::Map(map), key]) => map
+                .borrow()
+                .get(&Key::new(key))
+                .cloned()
+                .ok_or_else(|| Self::missing_key(key, span))?,
+            (Builtin::Remove, [Value::Map(map), key]) => map
+                .borrow_mut()
+                .remove(&Key::new(key))
+                .ok_or_else(|| Self::missing_key(key, span))?,
+            (Builtin::Contains, [Value::Map(map), key]) => {
+                Value::Bool(map.borrow().get(&Key::new(key)).is_some())
+            }
+            (Builtin::Keys, [Value::Map(map)]) => {
+                let keys = map.borrow().iter().map(|(key, _)| key.value()).collect();
+                self.heap.list(keys)
+            }
+
+            (builtin, args) => unreachable!("`{}` can't be called with {:?}", builtin, args),
+        };
+
+        Ok(value)
+    }
+
+    fn read_file(path: &str, span: Span) -> Result<String, RuntimeError> {
+        std::fs::read_to_string(path).map_err(|error| RuntimeError::Io {
+            path: path.to_string(),
+            reading: true,
+            error,
+            span,
+        })
+    }
+
+    fn write_file(path: &str, contents: &str, span: Span) -> Result<(), RuntimeError> {
+        std::fs::write(path, contents).map_err(|error| RuntimeError::Io {
+            path: path.to_string(),
+            reading: false,
+            error,
+            span,
+        })
+    }
+
+    // waits for the command to finish, what it printed is kept rather than shown.
+    // The status of a command stopped by a signal is -1
+    fn run_command(
+        &self,
+        command: &str,
+        args: &[String],
+        span: Span,
+    ) -> Result<Value<'a>, RuntimeError> {
+        let output = std::process::Command::new(command)
+            .args(args)
+            .output()
+            .map_err(|error| RuntimeError::Spawn {
+                command: command.to_string(),
+                error,
+                span,
+            })?;
+
+        let status = output.status.code().unwrap_or(-1).to_string();
+        let mut result = Map::default();
+        for (key, value) in [
+            ("status", status),
+            (
+                "stdout",
+                String::from_utf8_lossy(&output.stdout).into_owned(),
+            ),
+            (
+                "stderr",
+                String::from_utf8_lossy(&output.stderr).into_owned(),
+            ),
+        ] {
+            result.insert(Key::String(key.into()), Value::String(value.into()));
+        }
+
+        Ok(self.heap.map(result))
+    }
+
+    fn json_value(&self, json: Json) -> Value<'a> {
+        let index = json.index();
+        let fields = match json {
+            Json::Null => vec![],
+            Json::Bool(value) => vec![Value::Bool(value)],
+            Json::Number(value) => vec![Value::Float(value)],
+            Json::String(value) => vec![Value::String(value.into())],
+            Json::List(items) => {
+                let items = items
+                    .into_iter()
+                    .map(|item| self.json_value(item))
+                    .collect();
+                vec![self.heap.list(items)]
+            }
+            Json::Object(entries) => {
+                let mut map = Map::default();
+                for (key, value) in entries {
+                    map.insert(Key::String(key.into()), self.json_value(value));
+                }
+                vec![self.heap.map(map)]
+            }
+        };
+
+        Value::Variant {
+            enum_name: Json::NAME,
+            variant: Json::VARIANTS[index],
+            index,
+            fields,
+        }
+    }
+
+    // without spaces, like JavaScript's `JSON.stringify`. Floats that JSON can't
+    // hold, like infinity, are written as `null`
+    fn write_json(value: &Value, out: &mut String) {
+        match value {
+            Value::Int(value) => out.push_str(&value.to_string()),
+            Value::Float(value) if value.is_finite() => out.push_str(&format!("{:?}", value)),
+            Value::Float(_) => out.push_str("null"),
+            Value::String(value) => json::write_string(value, out),
+            Value::Bool(value) => out.push_str(&value.to_string()),
+            Value::List(items) => {
+                out.push('[');
+                for (index, item) in items.borrow().iter().enumerate() {
+                    if index > 0 {
+                        out.push(',');
+                    }
+                    Self::write_json(item, out);
+                }
+                out.push(']');
+            }
+            Value::Map(map) => {
+                out.push('{');
+                for (index, (key, value)) in map.borrow().iter().enumerate() {
+                    if index > 0 {
+                        out.push(',');
+                    }
+                    Self::write_json(&key.value(), out);
+                    out.push(':');
+                    Self::write_json(value, out);
+                }
+                out.push('}');
+            }
+            // the variants of `Json` other than `Null` have a single field
+            Value::Variant { fields, .. } => match fields.first() {
+                Some(field) => Self::write_json(field, out),
+                None => out.push_str("null"),
+            },
+            value => unreachable!("{:?} can't be written as JSON", value),
+        }
+    }
+
+    // the errors of the regex crate point at the problem over a few lines, only
+    // the last one, saying what it is, is kept
+    fn regex(&self, pattern: &str, span: Span) -> Result<Regex, RuntimeError> {
+        if let Some(regex) = self.regexes.borrow().get(pattern) {
+            return Ok(regex.clone());
+        }
+
+        let regex = Regex::new(pattern).map_err(|error| {
+            let error = error.to_string();
+            let message = error.lines().last().unwrap_or_default();
+            RuntimeError::InvalidRegex {
+                pattern: pattern.to_string(),
+                message: message.trim_start_matches("error: ").to_string(),
+                span,
+            }
+        })?;
+
+        self.regexes
+            .borrow_mut()
+            .insert(pattern.to_string(), regex.clone());
+        Ok(regex)
+    }
+
+    fn port(port: i64) -> io::Result<u16> {
+        u16::try_from(port)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "ports go from 0 to 65535"))
+    }
+
+    // the int the program refers to the socket by
+    fn open(&self, socket: Socket) -> Value<'a> {
+        let mut sockets = self.sockets.borrow_mut();
+        sockets.push(Some(socket));
+        Value::Int(sockets.len() as i64 - 1)
+    }
+
+    // negative ints wrap around to indices that are never in the table
+    fn with_connection<T>(
+        &self,
+        socket: i64,
+        span: Span,
+        f: impl FnOnce(&mut TcpStream) -> io::Result<T>,
+    ) -> Result<T, RuntimeError> {
+        let mut sockets = self.sockets.borrow_mut();
+        let Some(Some(Socket::Connection(stream))) = sockets.get_mut(socket as usize) else {
+            return Err(RuntimeError::InvalidSocket {
+                socket,
+                listener: false,
+                span,
+            });
+        };
+
+        f(stream).map_err(|error| RuntimeError::Network {
+            action: format!("use the connection {}", socket),
+            error,
+            span,
+        })
+    }
+
+    // the position in a list of the given length, when the index is in bounds
+    fn index(index: i64, length: usize, span: Span) -> Result<usize, RuntimeError> {
+        if index < 0 || index as usize >= length {
+            return Err(RuntimeError::IndexOutOfBounds {
+                index,
+                length,
+                span,
+            });
+        }
+
+        Ok(index as usize)
+    }
+
+    fn missing_key(key: &Value, span: Span) -> RuntimeError {
+        let key = match key {
+            Value::String(key) => format!("{:?}", key),
+            key => key.to_string(),
+        };
+
+        RuntimeError::MissingKey { key, span }
+    }
+
+    fn call(
+        &mut self,
+        callee: Value<'a>,
+        args: Vec<Value<'a>>,
+        span: Span,
+    ) -> Result<Value<'a>, RuntimeError> {
+        let closure = match callee {
+            Value::Function(closure) => closure,
+            Value::Host(host) => return Ok(host.call(&args)),
+            Value::Constructor {
+                enum_name,
+                variant,
+                index,
+            } => {
+                return Ok(Value::Variant {
+                    enum_name,
+                    variant,
+                    index,
+                    fields: args,
+                })
+            }
+            callee => unreachable!("{:?} is not a function", callee),
+        };
+
+        let Code::Hir { params, body } = closure.code else {
+            unreachable!("the HIR only makes closures of its own functions");
+        };
+        if self.depth == MAX_DEPTH {
+            return Err(RuntimeError::StackOverflow(span));
+        }
+        self.check_deadline(span)?;
+
+        let env = self.heap.env(Env::child(&closure.env));
+        for (param, arg) in params.iter().zip(args) {
+            env.define(*param, arg);
+        }
+
+        self.depth += 1;
+        let flow = self.exec_body(body, &env);
+        self.depth -= 1;
+
+        match flow? {
+            Flow::Return(value) => Ok(value),
+            _ => Ok(Value::Void),
+        }
+    }
+
+    // binds the pattern's names when it matches the value
+    pub(crate) fn matches(pattern: &hir::Pattern, value: &Value<'a>, env: &Rc<Env<'a>>) -> bool {
+        match (pattern, value) {
+            (hir::Pattern::Wildcard, _) => true,
+            (hir::Pattern::Binding(local), value) => {
+                env.define(*local, value.clone());
+                true
+            }
+            (hir::Pattern::Int(pattern), Value::Int(value)) => pattern == value,
+            (hir::Pattern::String(pattern), Value::String(value)) => **pattern == **value,
+            (hir::Pattern::Bool(pattern), Value::Bool(value)) => pattern == value,
+            (
+                hir::Pattern::Variant {
+                    index: pattern,
+                    fields: patterns,
+                },
+                Value::Variant { index, fields, .. },
+            ) => {
+                pattern == index
+                    && patterns
+                        .iter()
+                        .zip(fields)
+                        .all(|(pattern, field)| Self::matches(pattern, field, env))
+            }
+            _ => false,
+        }
+    }
+
+    fn exec_body(&mut self, body: &'a [Stmt], env: &Rc<Env<'a>>) -> Result<Flow<'a>, RuntimeError> {
+        for stmt in body {
+            let flow = self.exec(stmt, env)?;
+            if !matches!(flow, Flow::Normal) {
+                return Ok(flow);
+            }
+        }
+
+        Ok(Flow::Normal)
+    }
+
+    fn exec(&mut self, stmt: &'a Stmt, env: &Rc<Env<'a>>) -> Result<Flow<'a>, RuntimeError> {
+        match stmt {
+            Stmt::Let { local, value, .. } => {
+                let value = self.eval(value, env)?;
+                env.define(*local, value);
+            }
+
+            // the checks make sure nothing reads the placeholder before the
+            // variable is assigned
+            Stmt::Declare { local, .. } => env.define(*local, Value::Void),
+
+            Stmt::Assign { target, value } => {
+                let value = self.eval(value, env)?;
+                let ValueKind::Local(local) = target.kind else {
+                    unreachable!("only locals can be assigned to");
+                };
+
+                env.assign(local, value);
+            }
+
+            Stmt::Return(value) => {
+                let value = match value {
+                    Some(value) => self.eval(value, env)?,
+                    None => Value::Void,
+                };
+
+                return Ok(Flow::Return(value));
+            }
+
+            Stmt::If {
+                condition,
+                body,
+                else_body,
+            } => {
+                let body = match self.eval(condition, env)? {
+                    Value::Bool(true) => body,
+                    _ => else_body,
+                };
+
+                return self.exec_body(body, env);
+            }
+
+            Stmt::While {
+                condition,
+                body,
+                step,
+            } => {
+                while let Value::Bool(true) = self.eval(condition, env)? {
+                    self.check_deadline(condition.span)?;
+                    match self.exec_body(body, env)? {
+                        Flow::Break => break,
+                        flow @ Flow::Return(_) => return Ok(flow),
+                        Flow::Normal | Flow::Continue => {}
+                    }
+
+                    self.exec_body(step, env)?;
+                }
+            }
+
+            Stmt::Match { value, arms } => {
+                let value = self.eval(value, env)?;
+                for arm in arms {
+                    if Self::matches(&arm.pattern, &value, env) {
+                        return self.exec_body(&arm.body, env);
+                    }
+                }
+            }
+
+            // the handler gets the message the error would have been reported with
+            Stmt::Try {
+                body,
+                error,
+                handler,
+                ..
+            } => {
+                return match self.exec_body(body, env) {
+                    Ok(flow) => Ok(flow),
+                    Err(error @ RuntimeError::TimedOut(_)) => Err(error),
+                    Err(caught) => {
+                        let message = Diagnostic::from(caught).message;
+                        env.define(*error, Value::String(message.into()));
+                        self.exec_body(handler, env)
+                    }
+                };
+            }
+
+            Stmt::Break => return Ok(Flow::Break),
+            Stmt::Continue => return Ok(Flow::Continue),
+            Stmt::Block(body) => return self.exec_body(body, env),
+
+            Stmt::Value(value) => {
+                self.eval(value, env)?;
+            }
+        }
+
+        Ok(Flow::Normal)
+    }
+
+    // top level functions can be called before they're declared
+    fn hoist(&mut self, body: &'a [Stmt]) -> Result<(), RuntimeError> {
+        let globals = self.globals.clone();
+        for stmt in body {
+            if let Stmt::Let { local, value, .. } = stmt {
+                if let ValueKind::Function { .. } = value.kind {
+                    let function = self.eval(value, &globals)?;
+                    globals.define(*local, function);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn run(mut self) -> Result<(), RuntimeError> {
+        let globals = self.globals.clone();
+        self.hoist(&self.hir.body)?;
+        self.exec_body(&self.hir.body, &globals)?;
+        Ok(())
+    }
+
+    // the declarations of the program, and the assignments that give them
+    // their values. Its other top level statements don't run for tests and
+    // benchmarks, they only see what the program declares
+    fn declare(&mut self) -> Result<(), RuntimeError> {
+        let globals = self.globals.clone();
+        self.hoist(&self.hir.body)?;
+        for stmt in &self.hir.body {
+            if let Stmt::Let { .. } | Stmt::Declare { .. } | Stmt::Assign { .. } = stmt {
+                self.exec(stmt, &globals)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn run_test(mut self, test: &'a hir::Test) -> Result<(), RuntimeError> {
+        self.declare()?;
+        let globals = self.globals.clone();
+        self.exec_body(&test.body, &globals)?;
+        Ok(())
+    }
+
+    // calls a function marked `@bench` `warmup` times, then `runs` times more
+    // timing each call
+    pub fn run_bench(
+        mut self,
+        bench: &hir::Bench,
+        warmup: usize,
+        runs: usize,
+    ) -> Result<Vec<Duration>, RuntimeError> {
+        self.declare()?;
+        let function = self.globals.get(bench.local);
+        for _ in 0..warmup {
+            self.call(function.clone(), vec![], bench.span)?;
+        }
+
+        let mut samples = Vec::with_capacity(runs);
+        for _ in 0..runs {
+            let start = Instant::now();
+            self.call(function.clone(), vec![], bench.span)?;
+            samples.push(start.elapsed());
+        }
+
+        Ok(samples)
+    }
+
+    // runs the top level statements from `start` on, the ones before it ran
+    // already, and gives back the values of the expressions among them that
+    // aren't void
+    pub fn run_from(mut self, start: usize) -> Result<Vec<Value<'a>>, RuntimeError> {
+        let globals = self.globals.clone();
+        let body = &self.hir.body[start..];
+        self.hoist(body)?;
+
+        let mut values = vec![];
+        for stmt in body {
+            match stmt {
+                Stmt::Value(value) => match self.eval(value, &globals)? {
+                    Value::Void => {}
+                    value => values.push(value),
+                },
+                _ => {
+                    self.exec(stmt, &globals)?;
+                }
+            }
+        }
+
+        Ok(values)
+    }
+}
@@ -0,0 +1,742 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+
+use ez_ast::BaseType;
+use ez_check::hir::{Hir, Pattern, Stmt, Value, ValueKind};
+use ez_check::log::LogLevel;
+use ez_check::resolver::{Builtin, DefId};
+use ez_check::template;
+use ez_diagnostics::SourceMap;
+use ez_lexer::{Span, TokenKind};
+
+// the helpers the generated code calls, it goes at the top of every file so the
+// output runs on its own
+pub const RUNTIME: &str = include_str!("../runtime/ez_runtime.js");
+
+// names that can't be used as is, the keywords of JavaScript and the globals
+// the runtime relies on
+const RESERVED: &[&str] = &[
+    "arguments",
+    "Array",
+    "await",
+    "BigInt",
+    "break",
+    "case",
+    "catch",
+    "class",
+    "const",
+    "continue",
+    "debugger",
+    "default",
+    "delete",
+    "do",
+    "else",
+    "enum",
+    "eval",
+    "export",
+    "extends",
+    "false",
+    "finally",
+    "for",
+    "function",
+    "if",
+    "implements",
+    "import",
+    "in",
+    "Infinity",
+    "instanceof",
+    "interface",
+    "let",
+    "Map",
+    "Math",
+    "NaN",
+    "new",
+    "null",
+    "Number",
+    "package",
+    "private",
+    "protected",
+    "public",
+    "RangeError",
+    "return",
+    "static",
+    "String",
+    "super",
+    "switch",
+    "this",
+    "throw",
+    "true",
+    "try",
+    "typeof",
+    "undefined",
+    "var",
+    "void",
+    "while",
+    "with",
+    "yield",
+];
+
+// lowers the HIR to JavaScript that reads like the program it came from. Ints
+// are BigInts and enum variants are objects built by the runtime, everything
+// else maps to its JavaScript counterpart. Names are kept, unless that would
+// shadow another local of the same function or the top level, then they get the
+// id of their local as a suffix.
+pub struct JsBackend<'a> {
+    hir: &'a Hir,
+    // the program's files, failed assertions and panics say where they were
+    sources: &'a SourceMap,
+    out: String,
+    indent: usize,
+    names: HashMap<DefId, String>,
+    // the names of the top level, and the ones taken in the function being built
+    globals: HashSet<String>,
+    taken: HashSet<String>,
+    // the label `continue` breaks out of in each loop around the code, loops
+    // without a step can use `continue` itself
+    loops: Vec<Option<String>>,
+    labels: usize,
+    matches: usize,
+}
+
+// whether `continue` is used in the body for the loop around it
+fn continues(body: &[Stmt]) -> bool {
+    body.iter().any(|stmt| match stmt {
+        Stmt::Continue => true,
+        Stmt::If {
+            body, else_body, ..
+        } => continues(body) || continues(else_body),
+        Stmt::Match { arms, .. } => arms.iter().any(|arm| continues(&arm.body)),
+        Stmt::Try { body, handler, .. } => continues(body) || continues(handler),
+        Stmt::Block(body) => continues(body),
+        _ => false,
+    })
+}
+
+fn string(value: &str) -> String {
+    let mut literal = String::from("\"");
+    for char in value.chars() {
+        match char {
+            '"' => literal.push_str("\\\""),
+            '\\' => literal.push_str("\\\\"),
+            '\n' => literal.push_str("\\n"),
+            '\t' => literal.push_str("\\t"),
+            '\r' => literal.push_str("\\r"),
+            char if char.is_control() || char == '\u{2028}' || char == '\u{2029}' => {
+                let _ = write!(literal, "\\u{{{:x}}}", char as u32);
+            }
+            char => literal.push(char),
+        }
+    }
+    literal.push('"');
+    literal
+}
+
+impl<'a> JsBackend<'a> {
+    pub fn new(hir: &'a Hir, sources: &'a SourceMap) -> Self {
+        Self {
+            hir,
+            sources,
+            out: String::new(),
+            indent: 0,
+            names: HashMap::new(),
+            globals: HashSet::new(),
+            taken: HashSet::new(),
+            loops: vec![],
+            labels: 0,
+            matches: 0,
+        }
+    }
+
+    // like `file.ez:3:5`
+    fn location(&self, span: Span) -> String {
+        self.sources.location(span)
+    }
+
+    fn name(&mut self, local: DefId) -> String {
+        if let Some(name) = self.names.get(&local) {
+            return name.clone();
+        }
+
+        let name = &self.hir.local(local).name;
+        let name = if RESERVED.contains(&name.as_str()) || self.taken.contains(name.as_str()) {
+            format!("{}_{}", name, local.0)
+        } else {
+            name.to_string()
+        };
+
+        self.taken.insert(name.clone());
+        self.names.insert(local, name.clone());
+        name
+    }
+
+    fn enum_name(name: &str) -> String {
+        if RESERVED.contains(&name) {
+            format!("{}_", name)
+        } else {
+            name.to_string()
+        }
+    }
+
+    fn line(&mut self, text: impl AsRef<str>) {
+        let _ = writeln!(self.out, "{}{}", "    ".repeat(self.indent), text.as_ref());
+    }
+
+    fn binary(&mut self, operator: &TokenKind, left: &Value, right: &Value) -> String {
+        let (left_code, right_code) = (self.value(left), self.value(right));
+
+        let operator = match (&left.basetype, operator) {
+            (_, TokenKind::And) => "&&",
+            (_, TokenKind::Or) => "||",
+
+            (BaseType::Named(_) | BaseType::List(_) | BaseType::Map(..), TokenKind::Equals) => {
+                return format!("$eq({}, {})", left_code, right_code)
+            }
+            (BaseType::Named(_) | BaseType::List(_) | BaseType::Map(..), TokenKind::NotEquals) => {
+                return format!("!$eq({}, {})", left_code, right_code)
+            }
+            (_, TokenKind::Equals) => "===",
+            (_, TokenKind::NotEquals) => "!==",
+
+            (BaseType::Int, TokenKind::DividedBy) => {
+                return format!("$div({}, {})", left_code, right_code)
+            }
+            (BaseType::Int, operator @ (TokenKind::Plus | TokenKind::Minus | TokenKind::Times)) => {
+                let operator = operator.to_string();
+                let operator = operator.trim_matches('`');
+                return format!(
+                    "$int({} {} {}, \"{}\")",
+                    left_code, operator, right_code, operator
+                );
+            }
+
+            (_, TokenKind::Plus) => "+",
+            (_, TokenKind::Minus) => "-",
+            (_, TokenKind::Times) => "*",
+            (_, TokenKind::DividedBy) => "/",
+            (_, TokenKind::BitAnd) => "&",
+            (_, TokenKind::BitOr) => "|",
+            (_, TokenKind::BitXor) => "^",
+            (_, TokenKind::GreaterThan) => ">",
+            (_, TokenKind::GreaterOrEquals) => ">=",
+            (_, TokenKind::LowerThan) => "<",
+            (_, TokenKind::LowerOrEquals) => "<=",
+            (_, operator) => unreachable!("{} is not a binary operator", operator),
+        };
+
+        format!("({} {} {})", left_code, operator, right_code)
+    }
+
+    // the value as it's printed
+    fn text(&mut self, value: &Value) -> String {
+        let code = self.value(value);
+        match value.basetype {
+            BaseType::String => code,
+            BaseType::Float => format!("$floatToString({})", code),
+            BaseType::List(_) | BaseType::Map(..) => format!("$show({})", code),
+            _ => format!("String({})", code),
+        }
+    }
+
+    fn cast(&mut self, value: &Value, to: &BaseType) -> String {
+        let code = self.value(value);
+
+        match (&value.basetype, to) {
+            (from, to) if from == to => code,
+            (BaseType::Int, BaseType::Float) => format!("Number({})", code),
+            (BaseType::Float, BaseType::Int) => format!("$floatToInt({})", code),
+            (BaseType::Bool, BaseType::Int) => format!("BigInt({})", code),
+            (BaseType::Float, BaseType::String) => format!("$floatToString({})", code),
+            (_, BaseType::String) => format!("String({})", code),
+            (from, to) => unreachable!("casts from `{}` to `{}` are rejected", from, to),
+        }
+    }
+
+    // a function expression, or a declaration when it has a name
+    fn function(&mut self, name: Option<&str>, params: &[DefId], body: &[Stmt]) -> String {
+        let params: Vec<String> = params.iter().map(|param| self.name(*param)).collect();
+        let header = match name {
+            Some(name) => format!("function {}({}) {{", name, params.join(", ")),
+            None => format!("function ({}) {{", params.join(", ")),
+        };
+
+        // the body is built on its own, then put in place
+        let outer = std::mem::take(&mut self.out);
+        let loops = std::mem::take(&mut self.loops);
+        self.body(body);
+        let inner = std::mem::replace(&mut self.out, outer);
+        self.loops = loops;
+
+        format!("{}\n{}{}}}", header, inner, "    ".repeat(self.indent))
+    }
+
+    fn value(&mut self, value: &Value) -> String {
+        match &value.kind {
+            ValueKind::Int(int) => format!("{}n", int),
+            ValueKind::Float(float) => format!("{:?}", float),
+            ValueKind::Bool(bool) => bool.to_string(),
+            ValueKind::String(value) => string(value),
+            ValueKind::Local(local) => self.name(*local),
+
+            ValueKind::Binary {
+                operator,
+                left,
+                right,
+            } => self.binary(operator, left, right),
+
+            ValueKind::Unary { operator, operand } => {
+                let operand_code = self.value(operand);
+                match (operator, &operand.basetype) {
+                    (TokenKind::Minus, BaseType::Int) => format!("$int(-{}, \"-\")", operand_code),
+                    (TokenKind::Minus, _) => format!("(-{})", operand_code),
+                    (TokenKind::BitNot, _) => format!("(~{})", operand_code),
+                    _ => format!("(!{})", operand_code),
+                }
+            }
+
+            ValueKind::Cast { value: inner } => self.cast(inner, &value.basetype),
+
+            ValueKind::Call { callee, args } => {
+                let callee_code = match callee.kind {
+                    ValueKind::Function { .. } => format!("({})", self.value(callee)),
+                    _ => self.value(callee),
+                };
+                let args: Vec<String> = args.iter().map(|arg| self.value(arg)).collect();
+                format!("{}({})", callee_code, args.join(", "))
+            }
+
+            ValueKind::Builtin {
+                builtin: builtin @ (Builtin::Print | Builtin::Println),
+                args,
+            } => {
+                let text = match args.first() {
+                    Some(arg) => self.text(arg),
+                    None => "\"\"".to_string(),
+                };
+                format!("$print({}, {})", text, *builtin == Builtin::Println)
+            }
+
+            // the runtime drops the messages below the level
+            ValueKind::Builtin {
+                builtin:
+                    builtin @ (Builtin::LogDebug
+                    | Builtin::LogInfo
+                    | Builtin::LogWarn
+                    | Builtin::LogError),
+                args,
+            } => {
+                let level = LogLevel::of(*builtin).expect("the builtin logs");
+                format!("$log({}, {})", level as usize, self.text(&args[0]))
+            }
+
+            // the template is known, so only the arguments are left to convert
+            ValueKind::Builtin {
+                builtin: Builtin::Format,
+                args,
+            } => {
+                let ValueKind::String(template) = &args[0].kind else {
+                    unreachable!("templates are string literals");
+                };
+                let pieces = template::pieces(template).expect("templates are checked");
+
+                let mut parts = vec![string(&pieces[0])];
+                for (arg, piece) in args[1..].iter().zip(&pieces[1..]) {
+                    parts.push(self.text(arg));
+                    parts.push(string(piece));
+                }
+                format!("({})", parts.join(" + "))
+            }
+
+            // the location is passed after the other arguments
+            ValueKind::Builtin {
+                builtin: builtin @ (Builtin::Assert | Builtin::Panic),
+                args,
+            } => {
+                let mut args: Vec<String> = args.iter().map(|arg| self.value(arg)).collect();
+                if *builtin == Builtin::Assert && args.len() == 1 {
+                    args.push("undefined".to_string());
+                }
+                args.push(string(&self.location(value.span)));
+
+                let function = match builtin {
+                    Builtin::Assert => "$assert",
+                    _ => "$panic",
+                };
+                format!("{}({})", function, args.join(", "))
+            }
+
+            // the runtime has the ones on files, commands, sockets, JSON, regexes,
+            // the environment, time and random numbers, the ones that
+            // also work on ints, and the methods, which get the value they're called
+            // on as their first argument
+            ValueKind::Builtin { builtin, args } => {
+                let function = match builtin {
+                    Builtin::Args => "$args",
+                    Builtin::RunCommand => "$runCommand",
+                    Builtin::JsonParse => "$jsonParse",
+                    Builtin::JsonStringify => "$jsonStringify",
+                    Builtin::RegexMatch => "$regexMatch",
+                    Builtin::RegexFindAll => "$regexFindAll",
+                    Builtin::RegexReplace => "$regexReplace",
+                    Builtin::TcpConnect
+                    | Builtin::TcpListen
+                    | Builtin::TcpAccept
+                    | Builtin::Read
+                    | Builtin::Write
+                    | Builtin::Close => "$sockets",
+                    Builtin::EnvGet => "$envGet",
+                    Builtin::EnvSet => "$envSet",
+                    Builtin::Now => "$now",
+                    Builtin::Clock => "$clock",
+                    Builtin::Sleep => "$sleep",
+                    Builtin::Random => "$random",
+                    Builtin::RandomInt => "$randomInt",
+                    Builtin::Seed => "$seed",
+                    Builtin::ReadFile => "$readFile",
+                    Builtin::WriteFile => "$writeFile",
+                    Builtin::ReadLines => "$readLines",
+                    Builtin::WriteLines => "$writeLines",
+                    Builtin::Sqrt => "Math.sqrt",
+                    Builtin::Pow => "Math.pow",
+                    Builtin::Floor => "Math.floor",
+                    Builtin::Ceil => "Math.ceil",
+                    Builtin::Abs => "$abs",
+                    Builtin::Min => "$min",
+                    Builtin::Max => "$max",
+                    Builtin::Len => "$len",
+                    Builtin::Substring => "$substring",
+                    Builtin::Contains => "$contains",
+                    Builtin::ToUpper => "$toUpper",
+                    Builtin::ToLower => "$toLower",
+                    Builtin::Trim => "$trim",
+                    Builtin::ParseInt => "$parseInt",
+                    Builtin::ParseFloat => "$parseFloat",
+                    Builtin::Split => "$split",
+                    Builtin::Push => "$push",
+                    Builtin::Pop => "$pop",
+                    Builtin::Get => "$get",
+                    Builtin::Set => "$set",
+                    Builtin::Insert => "$insert",
+                    Builtin::Remove => "$remove",
+                    Builtin::Keys => "$keys",
+                    Builtin::Print
+                    | Builtin::Println
+                    | Builtin::Format
+                    | Builtin::Assert
+                    | Builtin::Panic
+                    | Builtin::LogDebug
+                    | Builtin::LogInfo
+                    | Builtin::LogWarn
+                    | Builtin::LogError => {
+                        unreachable!(
+                            "printing, formatting, logging, assertions and panics are above"
+                        )
+                    }
+                };
+
+                let args: Vec<String> = args.iter().map(|arg| self.value(arg)).collect();
+                format!("{}({})", function, args.join(", "))
+            }
+
+            ValueKind::Function { params, body } => self.function(None, params, body),
+
+            ValueKind::List(items) => {
+                let items: Vec<String> = items.iter().map(|item| self.value(item)).collect();
+                format!("[{}]", items.join(", "))
+            }
+
+            ValueKind::Map(entries) => {
+                let entries: Vec<String> = entries
+                    .iter()
+                    .map(|(key, value)| format!("[{}, {}]", self.value(key), self.value(value)))
+                    .collect();
+                format!("new Map([{}])", entries.join(", "))
+            }
+
+            ValueKind::Variant { enum_name, index } => format!(
+                "{}.{}",
+                Self::enum_name(enum_name),
+                self.hir.enums[enum_name][*index].identifier.name
+            ),
+        }
+    }
+
+    // conditions already have parentheses around them
+    fn condition(&mut self, value: &Value) -> String {
+        let code = self.value(value);
+        match value.kind {
+            ValueKind::Binary { .. } if code.starts_with('(') => {
+                code[1..code.len() - 1].to_string()
+            }
+            _ => code,
+        }
+    }
+
+    // adds what has to hold for the pattern to match the value at `access`, and
+    // the locals it binds
+    fn pattern(
+        &mut self,
+        pattern: &Pattern,
+        access: &str,
+        conditions: &mut Vec<String>,
+        bindings: &mut Vec<(DefId, String)>,
+    ) {
+        match pattern {
+            Pattern::Wildcard => {}
+            Pattern::Binding(local) => bindings.push((*local, access.to_string())),
+            Pattern::Int(value) => conditions.push(format!("{} === {}n", access, value)),
+            Pattern::String(value) => conditions.push(format!("{} === {}", access, string(value))),
+            Pattern::Bool(value) => conditions.push(format!("{} === {}", access, value)),
+            Pattern::Variant { index, fields } => {
+                conditions.push(format!("{}.index === {}", access, index));
+                for (field_index, field) in fields.iter().enumerate() {
+                    let field_access = format!("{}.fields[{}]", access, field_index);
+                    self.pattern(field, &field_access, conditions, bindings);
+                }
+            }
+        }
+    }
+
+    fn body(&mut self, body: &[Stmt]) {
+        self.indent += 1;
+        for stmt in body {
+            self.stmt(stmt);
+        }
+        self.indent -= 1;
+    }
+
+    fn declaration(&self, local: DefId) -> &'static str {
+        if self.hir.local(local).mutable {
+            "let"
+        } else {
+            "const"
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Let {
+                local,
+                value:
+                    Value {
+                        kind: ValueKind::Function { params, body },
+                        ..
+                    },
+                ..
+            } => {
+                let name = self.name(*local);
+
+                // top level functions can't shadow the names of the top level
+                let taken = (self.indent == 0)
+                    .then(|| std::mem::replace(&mut self.taken, self.globals.clone()));
+                let function = self.function(Some(&name), params, body);
+                self.line(function);
+
+                if let Some(taken) = taken {
+                    self.taken = taken;
+                    self.line("");
+                }
+            }
+
+            Stmt::Let { local, value, .. } => {
+                let name = self.name(*local);
+                let code = self.value(value);
+                let declaration = self.declaration(*local);
+                self.line(format!("{} {} = {};", declaration, name, code));
+            }
+
+            // assigned later on, so it can't be a `const`
+            Stmt::Declare { local, .. } => {
+                let name = self.name(*local);
+                self.line(format!("let {};", name));
+            }
+
+            Stmt::Assign { target, value } => {
+                let code = format!("{} = {};", self.value(target), self.value(value));
+                self.line(code);
+            }
+
+            Stmt::Return(value) => match value {
+                Some(value) => {
+                    let code = format!("return {};", self.value(value));
+                    self.line(code);
+                }
+                None => self.line("return;"),
+            },
+
+            Stmt::If {
+                condition,
+                body,
+                else_body,
+            } => {
+                let code = format!("if ({}) {{", self.condition(condition));
+                self.line(code);
+                self.body(body);
+                if !else_body.is_empty() {
+                    self.line("} else {");
+                    self.body(else_body);
+                }
+                self.line("}");
+            }
+
+            // `continue` has to run the step, so with one the body goes in a
+            // labeled block that `continue` breaks out of
+            Stmt::While {
+                condition,
+                body,
+                step,
+            } => {
+                let code = format!("while ({}) {{", self.condition(condition));
+                self.line(code);
+
+                let label = (!step.is_empty() && continues(body)).then(|| {
+                    self.labels += 1;
+                    format!("continue_{}", self.labels)
+                });
+                self.loops.push(label.clone());
+                match &label {
+                    Some(label) => {
+                        self.indent += 1;
+                        self.line(format!("{}: {{", label));
+                        self.body(body);
+                        self.line("}");
+                        self.indent -= 1;
+                    }
+                    None => self.body(body),
+                }
+                self.loops.pop();
+
+                self.body(step);
+                self.line("}");
+            }
+
+            // the value is only evaluated once, the arms are tried in order
+            Stmt::Match { value, arms } => {
+                let access = match value.kind {
+                    ValueKind::Local(local) => self.name(local),
+                    _ => {
+                        self.matches += 1;
+                        let access = format!("$match{}", self.matches);
+                        let code = format!("const {} = {};", access, self.value(value));
+                        self.line(code);
+                        access
+                    }
+                };
+
+                for (index, arm) in arms.iter().enumerate() {
+                    let (mut conditions, mut bindings) = (vec![], vec![]);
+                    self.pattern(&arm.pattern, &access, &mut conditions, &mut bindings);
+
+                    let opening = match (index, conditions.is_empty()) {
+                        (0, true) => "{".to_string(),
+                        (_, true) => "} else {".to_string(),
+                        (0, false) => format!("if ({}) {{", conditions.join(" && ")),
+                        (_, false) => format!("}} else if ({}) {{", conditions.join(" && ")),
+                    };
+                    self.line(opening);
+
+                    self.indent += 1;
+                    for (local, access) in bindings {
+                        let declaration = self.declaration(local);
+                        let name = self.name(local);
+                        self.line(format!("{} {} = {};", declaration, name, access));
+                    }
+                    self.indent -= 1;
+                    self.body(&arm.body);
+
+                    // an arm matching anything leaves the rest unreachable
+                    if conditions.is_empty() {
+                        break;
+                    }
+                }
+                self.line("}");
+            }
+
+            Stmt::Break => self.line("break;"),
+
+            Stmt::Continue => match self.loops.last().expect("loop control is checked") {
+                Some(label) => {
+                    let code = format!("break {};", label);
+                    self.line(code);
+                }
+                None => self.line("continue;"),
+            },
+
+            Stmt::Try {
+                body,
+                error,
+                handler,
+                ..
+            } => {
+                self.line("try {");
+                self.body(body);
+                self.line("} catch ($error) {");
+                self.indent += 1;
+                let code = format!("const {} = $caught($error);", self.name(*error));
+                self.line(code);
+                self.indent -= 1;
+                self.body(handler);
+                self.line("}");
+            }
+
+            Stmt::Block(body) => {
+                self.line("{");
+                self.body(body);
+                self.line("}");
+            }
+
+            Stmt::Value(value) => {
+                let code = format!("{};", self.value(value));
+                self.line(code);
+            }
+        }
+    }
+
+    pub fn compile(mut self) -> String {
+        let hir = self.hir;
+        self.out.push_str(RUNTIME);
+
+        let mut enums: Vec<_> = hir.enums.iter().collect();
+        enums.sort_by_key(|(name, _)| *name);
+        for (name, variants) in enums {
+            let name = Self::enum_name(name);
+            self.line("");
+            self.line(format!("const {} = {{", name));
+            self.indent += 1;
+            for (index, variant) in variants.iter().enumerate() {
+                let variant_name = &variant.identifier.name;
+                let fields = if variant.payload.is_empty() {
+                    "[]"
+                } else {
+                    "fields"
+                };
+                let built = format!(
+                    "$variant(\"{}\", \"{}\", {}, {})",
+                    name, variant_name, index, fields
+                );
+                if variant.payload.is_empty() {
+                    self.line(format!("{}: {},", variant_name, built));
+                } else {
+                    self.line(format!("{}: (...fields) => {},", variant_name, built));
+                }
+            }
+            self.indent -= 1;
+            self.line("};");
+            self.taken.insert(name);
+        }
+
+        // the top level is named first, so functions know which names to avoid
+        for stmt in &hir.body {
+            if let Stmt::Let { local, .. } | Stmt::Declare { local, .. } = stmt {
+                self.name(*local);
+            }
+        }
+        self.globals = self.taken.clone();
+
+        self.line("");
+        for stmt in &hir.body {
+            self.stmt(stmt);
+        }
+
+        self.out
+    }
+}
@@ -0,0 +1,19 @@
+// the backends, the interpreter running the HIR and the compilers turning it
+// into C, JavaScript, WebAssembly and, with the `llvm` feature, native code.
+// `bytecode` compiles the SSA form into instructions working on registers,
+// which `peephole` rewrites a few at a time, `ezb` writes as bytes and `vm`
+// runs
+
+pub mod bytecode;
+pub mod c;
+pub mod ezb;
+pub mod gc;
+pub mod host;
+pub mod interp;
+pub mod js;
+pub mod link;
+#[cfg(feature = "llvm")]
+pub mod llvm;
+pub mod peephole;
+pub mod vm;
+pub mod wasm;
@@ -0,0 +1,128 @@
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use ez_diagnostics::Diagnostic;
+
+#[derive(Debug)]
+pub enum LinkError {
+    // the C compiler couldn't be started at all
+    NotFound { program: String, reason: String },
+    // `output` is what the C compiler printed
+    Failed { program: String, output: String },
+}
+
+impl From<LinkError> for Diagnostic {
+    fn from(error: LinkError) -> Self {
+        match error {
+            LinkError::NotFound { program, reason } => {
+                Diagnostic::error("E0505", format!("couldn't run `{}`: {}", program, reason))
+                    .with_help("install a C compiler, or point `CC` at one")
+            }
+
+            LinkError::Failed { program, output } => {
+                Diagnostic::error("E0506", format!("linking with `{}` failed", program))
+                    .with_note(output.trim_end().to_string())
+            }
+        }
+    }
+}
+
+// the startup code linked with the objects of the llvm backend
+#[cfg(feature = "llvm")]
+const START: &str = include_str!("../runtime/ez_start.c");
+
+// the C compiler drives the system linker, `CC` picks another one like it does
+// for most build tools
+fn compiler() -> String {
+    std::env::var("CC").unwrap_or_else(|_| "cc".into())
+}
+
+fn run<S: AsRef<OsStr>>(args: &[S]) -> Result<(), LinkError> {
+    let program = compiler();
+    let output = Command::new(&program)
+        .args(args)
+        .output()
+        .map_err(|error| LinkError::NotFound {
+            program: program.clone(),
+            reason: error.to_string(),
+        })?;
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(LinkError::Failed {
+            program,
+            output: String::from_utf8_lossy(&output.stderr).into_owned(),
+        })
+    }
+}
+
+// a directory for the files only needed while building, removed afterwards
+pub struct Scratch(PathBuf);
+
+impl Scratch {
+    pub fn new() -> Self {
+        let path = std::env::temp_dir().join(format!("ez-{}", std::process::id()));
+        fs::create_dir_all(&path).expect("failed to create a temporary directory");
+        Self(path)
+    }
+
+    pub fn path(&self, name: &str) -> PathBuf {
+        self.0.join(name)
+    }
+}
+
+impl Default for Scratch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+// links an object file of the llvm backend with the startup code
+#[cfg(feature = "llvm")]
+pub fn link(scratch: &Scratch, object: &Path, output: &Path) -> Result<(), LinkError> {
+    let start = scratch.path("ez_start.c");
+    fs::write(&start, START).expect("failed to write the startup code");
+    fs::write(scratch.path("ez_runtime.h"), crate::c::RUNTIME)
+        .expect("failed to write the runtime");
+
+    run(&[
+        object.as_os_str(),
+        start.as_os_str(),
+        "-o".as_ref(),
+        output.as_os_str(),
+        "-lm".as_ref(),
+    ])
+}
+
+// compiles the output of the C backend to an executable
+pub fn compile_c(source: &Path, output: &Path) -> Result<(), LinkError> {
+    run(&[
+        "-std=c99".as_ref(),
+        "-O2".as_ref(),
+        source.as_os_str(),
+        "-o".as_ref(),
+        output.as_os_str(),
+        "-lm".as_ref(),
+    ])
+}
+
+// compiles the output of the C backend to assembly, for looking at
+pub fn assemble_c(source: &Path, output: &Path) -> Result<(), LinkError> {
+    run(&[
+        "-std=c99".as_ref(),
+        "-O2".as_ref(),
+        "-S".as_ref(),
+        source.as_os_str(),
+        "-o".as_ref(),
+        output.as_os_str(),
+    ])
+}
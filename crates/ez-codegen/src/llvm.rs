@@ -0,0 +1,854 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use ez_ast::BaseType;
+use ez_check::hir::{Hir, Stmt, Value, ValueKind};
+use ez_check::resolver::{Builtin, DefId};
+use ez_diagnostics::Diagnostic;
+use ez_lexer::{Span, TokenKind};
+use inkwell::attributes::{Attribute, AttributeLoc};
+use inkwell::basic_block::BasicBlock;
+use inkwell::builder::{Builder, BuilderError};
+use inkwell::module::{Linkage, Module};
+use inkwell::targets::{
+    CodeModel, FileType, InitializationConfig, RelocMode, Target, TargetMachine,
+};
+use inkwell::types::{BasicMetadataTypeEnum, BasicType, BasicTypeEnum};
+use inkwell::values::{BasicMetadataValueEnum, BasicValueEnum, FunctionValue, PointerValue};
+use inkwell::{AddressSpace, FloatPredicate, IntPredicate, OptimizationLevel};
+
+// what the modules are made in, the command line makes one for each program
+pub use inkwell::context::Context;
+
+#[derive(Debug)]
+pub enum CodegenError {
+    // `what` is the part of the language that can't be compiled yet
+    Unsupported { what: String, span: Span },
+    Llvm(String),
+}
+
+impl From<BuilderError> for CodegenError {
+    fn from(error: BuilderError) -> Self {
+        CodegenError::Llvm(error.to_string())
+    }
+}
+
+impl From<CodegenError> for Diagnostic {
+    fn from(error: CodegenError) -> Self {
+        match error {
+            CodegenError::Unsupported { what, span } => {
+                Diagnostic::error("E0501", format!("{} can't be compiled with llvm yet", what))
+                    .with_label(span, "not supported by the llvm backend")
+                    .with_help("use `ez run` to run the program with the interpreter instead")
+            }
+
+            CodegenError::Llvm(message) => {
+                Diagnostic::error("E0502", format!("llvm failed: {}", message))
+            }
+        }
+    }
+}
+
+// where `break` and `continue` jump to in the innermost loop
+struct Loop<'ctx> {
+    step: BasicBlock<'ctx>,
+    exit: BasicBlock<'ctx>,
+}
+
+// lowers the HIR to an llvm module. Top level functions become llvm functions,
+// top level variables become globals, and the rest of the top level code goes in
+// `ez_main`. Ints are `i64`, floats are `f64` and bools are `i1`, the other types
+// and values that need a runtime, like strings, enums and closures, are reported
+// as unsupported.
+pub struct LlvmBackend<'ctx, 'a> {
+    context: &'ctx Context,
+    module: Module<'ctx>,
+    builder: Builder<'ctx>,
+    hir: &'a Hir,
+    functions: HashMap<DefId, FunctionValue<'ctx>>,
+    // the stack slot or global of every variable
+    variables: HashMap<DefId, PointerValue<'ctx>>,
+    loops: Vec<Loop<'ctx>>,
+    function: Option<FunctionValue<'ctx>>,
+}
+
+impl<'ctx, 'a> LlvmBackend<'ctx, 'a> {
+    pub fn new(context: &'ctx Context, hir: &'a Hir, name: &str) -> Self {
+        Self {
+            context,
+            module: context.create_module(name),
+            builder: context.create_builder(),
+            hir,
+            functions: HashMap::new(),
+            variables: HashMap::new(),
+            loops: vec![],
+            function: None,
+        }
+    }
+
+    fn unsupported<T>(what: impl Into<String>, span: Span) -> Result<T, CodegenError> {
+        Err(CodegenError::Unsupported {
+            what: what.into(),
+            span,
+        })
+    }
+
+    fn basic_type(
+        &self,
+        basetype: &BaseType,
+        span: Span,
+    ) -> Result<BasicTypeEnum<'ctx>, CodegenError> {
+        match basetype {
+            BaseType::Int => Ok(self.context.i64_type().into()),
+            BaseType::Float => Ok(self.context.f64_type().into()),
+            BaseType::Bool => Ok(self.context.bool_type().into()),
+            basetype => Self::unsupported(format!("`{}` values", basetype), span),
+        }
+    }
+
+    fn zero(&self, basetype: &BaseType, span: Span) -> Result<BasicValueEnum<'ctx>, CodegenError> {
+        match basetype {
+            BaseType::Int => Ok(self.context.i64_type().const_zero().into()),
+            BaseType::Float => Ok(self.context.f64_type().const_zero().into()),
+            BaseType::Bool => Ok(self.context.bool_type().const_zero().into()),
+            basetype => Self::unsupported(format!("`{}` values", basetype), span),
+        }
+    }
+
+    fn current_function(&self) -> FunctionValue<'ctx> {
+        self.function.expect("code is only built inside a function")
+    }
+
+    // statements after a jump go into a block nothing branches to
+    fn start_dead_block(&mut self) {
+        let block = self
+            .context
+            .append_basic_block(self.current_function(), "dead");
+        self.builder.position_at_end(block);
+    }
+
+    fn terminated(&self) -> bool {
+        self.builder
+            .get_insert_block()
+            .and_then(|block| block.get_terminator())
+            .is_some()
+    }
+
+    fn branch(&mut self, target: BasicBlock<'ctx>) -> Result<(), CodegenError> {
+        if !self.terminated() {
+            self.builder.build_unconditional_branch(target)?;
+        }
+
+        Ok(())
+    }
+
+    // variables live in stack slots at the start of the function, llvm turns
+    // them into registers when it optimizes
+    fn variable(&mut self, local: DefId, span: Span) -> Result<PointerValue<'ctx>, CodegenError> {
+        if let Some(slot) = self.variables.get(&local) {
+            return Ok(*slot);
+        }
+
+        let definition = self.hir.local(local);
+        let basetype = self.basic_type(&definition.basetype, span)?;
+        let entry = self
+            .current_function()
+            .get_first_basic_block()
+            .expect("functions start with their entry block");
+
+        let builder = self.context.create_builder();
+        match entry.get_first_instruction() {
+            Some(first) => builder.position_before(&first),
+            None => builder.position_at_end(entry),
+        }
+
+        let slot = builder.build_alloca(basetype, &definition.name)?;
+        self.variables.insert(local, slot);
+        Ok(slot)
+    }
+
+    fn declare_function(&mut self, local: DefId, value: &Value) -> Result<(), CodegenError> {
+        let ValueKind::Function { params, .. } = &value.kind else {
+            unreachable!("only functions are declared");
+        };
+        let BaseType::Function { return_type, .. } = &value.basetype else {
+            unreachable!("functions have a function type");
+        };
+
+        let params = params
+            .iter()
+            .map(|param| {
+                self.basic_type(&self.hir.local(*param).basetype, value.span)
+                    .map(BasicMetadataTypeEnum::from)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let function_type = match &**return_type {
+            BaseType::Void => self.context.void_type().fn_type(&params, false),
+            return_type => self
+                .basic_type(return_type, value.span)?
+                .fn_type(&params, false),
+        };
+
+        // internal, so they can't clash with the C library when linking
+        let name = &self.hir.local(local).name;
+        let function = self
+            .module
+            .add_function(name, function_type, Some(Linkage::Internal));
+        self.functions.insert(local, function);
+        Ok(())
+    }
+
+    fn define_function(&mut self, local: DefId, value: &Value) -> Result<(), CodegenError> {
+        let ValueKind::Function { params, body } = &value.kind else {
+            unreachable!("only functions are defined");
+        };
+
+        let function = self.functions[&local];
+        let entry = self.context.append_basic_block(function, "entry");
+        self.builder.position_at_end(entry);
+        self.function = Some(function);
+
+        for (index, param) in params.iter().enumerate() {
+            let arg = function
+                .get_nth_param(index as u32)
+                .expect("functions are declared with their parameters");
+            let slot = self.variable(*param, value.span)?;
+            self.builder.build_store(slot, arg)?;
+        }
+
+        self.build_body(body)?;
+
+        // the return checker made sure functions returning a value don't fall off
+        // their end, so whatever is left is unreachable
+        if !self.terminated() {
+            match function.get_type().get_return_type() {
+                Some(_) => self.builder.build_unreachable()?,
+                None => self.builder.build_return(None)?,
+            };
+        }
+
+        self.function = None;
+        Ok(())
+    }
+
+    fn build_call(
+        &mut self,
+        callee: &Value,
+        args: &[Value],
+    ) -> Result<Option<BasicValueEnum<'ctx>>, CodegenError> {
+        let function = match callee.kind {
+            ValueKind::Local(local) if self.functions.contains_key(&local) => {
+                self.functions[&local]
+            }
+            _ => return Self::unsupported("calls through function values", callee.span),
+        };
+
+        let args = args
+            .iter()
+            .map(|arg| self.build_value(arg).map(BasicMetadataValueEnum::from))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let call = self.builder.build_call(function, &args, "call")?;
+        Ok(call.try_as_basic_value().left())
+    }
+
+    // `print` and `println` call the runtime linked in with the startup code,
+    // with the value and whether to end the line
+    fn build_print(&mut self, builtin: Builtin, args: &[Value]) -> Result<(), CodegenError> {
+        let (name, value) = match args.first() {
+            Some(arg) => {
+                let name = match arg.basetype {
+                    BaseType::Int => "ez_print_int",
+                    BaseType::Float => "ez_print_float",
+                    BaseType::Bool => "ez_print_bool",
+                    _ => return Self::unsupported("strings", arg.span),
+                };
+                (name, self.build_value(arg)?)
+            }
+            None => {
+                let empty = self.builder.build_global_string_ptr("", "empty")?;
+                ("ez_print_string", empty.as_pointer_value().into())
+            }
+        };
+
+        let function = match self.module.get_function(name) {
+            Some(function) => function,
+            None => {
+                let param = match value {
+                    BasicValueEnum::PointerValue(_) => {
+                        self.context.ptr_type(AddressSpace::default()).into()
+                    }
+                    value => value.get_type().into(),
+                };
+                let function_type = self
+                    .context
+                    .void_type()
+                    .fn_type(&[param, self.context.bool_type().into()], false);
+                let function = self.module.add_function(name, function_type, None);
+
+                // C expects its bools extended to a whole byte
+                let zeroext = self
+                    .context
+                    .create_enum_attribute(Attribute::get_named_enum_kind_id("zeroext"), 0);
+                function.add_attribute(AttributeLoc::Param(1), zeroext);
+                function
+            }
+        };
+
+        let newline = self
+            .context
+            .bool_type()
+            .const_int((builtin == Builtin::Println) as u64, false);
+        self.builder
+            .build_call(function, &[value.into(), newline.into()], "")?;
+        Ok(())
+    }
+
+    // the functions of `math` on floats are llvm intrinsics, the ones on ints are
+    // a comparison and a `select`
+    fn build_math(
+        &mut self,
+        builtin: Builtin,
+        args: &[Value],
+    ) -> Result<BasicValueEnum<'ctx>, CodegenError> {
+        let args = args
+            .iter()
+            .map(|arg| self.build_value(arg))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if let BasicValueEnum::IntValue(value) = args[0] {
+            let built = match builtin {
+                Builtin::Abs => {
+                    let zero = self.context.i64_type().const_zero();
+                    let negative = self.builder.build_int_compare(
+                        IntPredicate::SLT,
+                        value,
+                        zero,
+                        "negative",
+                    )?;
+                    let negated = self.builder.build_int_neg(value, "neg")?;
+                    self.builder.build_select(negative, negated, value, "abs")?
+                }
+                _ => {
+                    let other = args[1].into_int_value();
+                    let predicate = match builtin {
+                        Builtin::Min => IntPredicate::SLT,
+                        _ => IntPredicate::SGT,
+                    };
+                    let pick = self
+                        .builder
+                        .build_int_compare(predicate, other, value, "pick")?;
+                    self.builder
+                        .build_select(pick, other, value, builtin.name())?
+                }
+            };
+
+            return Ok(built);
+        }
+
+        let name = match builtin {
+            Builtin::Sqrt => "llvm.sqrt.f64",
+            Builtin::Pow => "llvm.pow.f64",
+            Builtin::Floor => "llvm.floor.f64",
+            Builtin::Ceil => "llvm.ceil.f64",
+            Builtin::Abs => "llvm.fabs.f64",
+            // like the interpreter, a NaN is only the result when both values are NaN
+            Builtin::Min => "llvm.minnum.f64",
+            _ => "llvm.maxnum.f64",
+        };
+        let function = match self.module.get_function(name) {
+            Some(function) => function,
+            None => {
+                let float = self.context.f64_type();
+                let params: Vec<BasicMetadataTypeEnum> = vec![float.into(); args.len()];
+                self.module
+                    .add_function(name, float.fn_type(&params, false), None)
+            }
+        };
+
+        let args: Vec<BasicMetadataValueEnum> = args.into_iter().map(Into::into).collect();
+        let call = self.builder.build_call(function, &args, builtin.name())?;
+        Ok(call
+            .try_as_basic_value()
+            .left()
+            .expect("the intrinsics give back a float"))
+    }
+
+    // `and` and `or` only evaluate their right side when the left one doesn't
+    // decide the result
+    fn build_logic(
+        &mut self,
+        operator: &TokenKind,
+        left: &Value,
+        right: &Value,
+    ) -> Result<BasicValueEnum<'ctx>, CodegenError> {
+        let function = self.current_function();
+        let left = self.build_value(left)?.into_int_value();
+        let start = self.builder.get_insert_block().expect("there is a block");
+        let rhs = self.context.append_basic_block(function, "rhs");
+        let merge = self.context.append_basic_block(function, "merge");
+
+        let short_circuit = match operator {
+            TokenKind::And => {
+                self.builder.build_conditional_branch(left, rhs, merge)?;
+                self.context.bool_type().const_zero()
+            }
+            _ => {
+                self.builder.build_conditional_branch(left, merge, rhs)?;
+                self.context.bool_type().const_all_ones()
+            }
+        };
+
+        self.builder.position_at_end(rhs);
+        let right = self.build_value(right)?;
+        let rhs_end = self.builder.get_insert_block().expect("there is a block");
+        self.builder.build_unconditional_branch(merge)?;
+
+        self.builder.position_at_end(merge);
+        let phi = self.builder.build_phi(self.context.bool_type(), "logic")?;
+        phi.add_incoming(&[(&short_circuit, start), (&right, rhs_end)]);
+        Ok(phi.as_basic_value())
+    }
+
+    fn build_binary(
+        &mut self,
+        operator: &TokenKind,
+        left: &Value,
+        right: &Value,
+        span: Span,
+    ) -> Result<BasicValueEnum<'ctx>, CodegenError> {
+        let operand_type = left.basetype.clone();
+        let left = self.build_value(left)?;
+        let right = self.build_value(right)?;
+
+        let result: BasicValueEnum = match operand_type {
+            BaseType::Int | BaseType::Bool => {
+                let (left, right) = (left.into_int_value(), right.into_int_value());
+                let compare = |predicate| {
+                    self.builder
+                        .build_int_compare(predicate, left, right, "compare")
+                };
+
+                match operator {
+                    TokenKind::Plus => self.builder.build_int_add(left, right, "add")?.into(),
+                    TokenKind::Minus => self.builder.build_int_sub(left, right, "sub")?.into(),
+                    TokenKind::Times => self.builder.build_int_mul(left, right, "mul")?.into(),
+                    TokenKind::DividedBy => self
+                        .builder
+                        .build_int_signed_div(left, right, "div")?
+                        .into(),
+                    TokenKind::BitAnd => self.builder.build_and(left, right, "and")?.into(),
+                    TokenKind::BitOr => self.builder.build_or(left, right, "or")?.into(),
+                    TokenKind::BitXor => self.builder.build_xor(left, right, "xor")?.into(),
+                    TokenKind::Equals => compare(IntPredicate::EQ)?.into(),
+                    TokenKind::NotEquals => compare(IntPredicate::NE)?.into(),
+                    TokenKind::GreaterThan => compare(IntPredicate::SGT)?.into(),
+                    TokenKind::GreaterOrEquals => compare(IntPredicate::SGE)?.into(),
+                    TokenKind::LowerThan => compare(IntPredicate::SLT)?.into(),
+                    TokenKind::LowerOrEquals => compare(IntPredicate::SLE)?.into(),
+                    _ => unreachable!("{} can't be applied to ints", operator),
+                }
+            }
+
+            BaseType::Float => {
+                let (left, right) = (left.into_float_value(), right.into_float_value());
+                let compare = |predicate| {
+                    self.builder
+                        .build_float_compare(predicate, left, right, "compare")
+                };
+
+                match operator {
+                    TokenKind::Plus => self.builder.build_float_add(left, right, "add")?.into(),
+                    TokenKind::Minus => self.builder.build_float_sub(left, right, "sub")?.into(),
+                    TokenKind::Times => self.builder.build_float_mul(left, right, "mul")?.into(),
+                    TokenKind::DividedBy => {
+                        self.builder.build_float_div(left, right, "div")?.into()
+                    }
+                    TokenKind::Equals => compare(FloatPredicate::OEQ)?.into(),
+                    TokenKind::NotEquals => compare(FloatPredicate::UNE)?.into(),
+                    TokenKind::GreaterThan => compare(FloatPredicate::OGT)?.into(),
+                    TokenKind::GreaterOrEquals => compare(FloatPredicate::OGE)?.into(),
+                    TokenKind::LowerThan => compare(FloatPredicate::OLT)?.into(),
+                    TokenKind::LowerOrEquals => compare(FloatPredicate::OLE)?.into(),
+                    _ => unreachable!("{} can't be applied to floats", operator),
+                }
+            }
+
+            basetype => return Self::unsupported(format!("{} on `{}`", operator, basetype), span),
+        };
+
+        Ok(result)
+    }
+
+    fn build_cast(
+        &mut self,
+        value: &Value,
+        to: &BaseType,
+        span: Span,
+    ) -> Result<BasicValueEnum<'ctx>, CodegenError> {
+        let from = value.basetype.clone();
+        let built = self.build_value(value)?;
+
+        let cast = match (&from, to) {
+            (from, to) if from == to => built,
+            (BaseType::Int, BaseType::Float) => self
+                .builder
+                .build_signed_int_to_float(built.into_int_value(), self.context.f64_type(), "cast")?
+                .into(),
+            (BaseType::Float, BaseType::Int) => self
+                .builder
+                .build_float_to_signed_int(
+                    built.into_float_value(),
+                    self.context.i64_type(),
+                    "cast",
+                )?
+                .into(),
+            (BaseType::Bool, BaseType::Int) => self
+                .builder
+                .build_int_z_extend(built.into_int_value(), self.context.i64_type(), "cast")?
+                .into(),
+            (_, to) => return Self::unsupported(format!("casts to `{}`", to), span),
+        };
+
+        Ok(cast)
+    }
+
+    fn build_value(&mut self, value: &Value) -> Result<BasicValueEnum<'ctx>, CodegenError> {
+        let built = match &value.kind {
+            ValueKind::Int(int) => self.context.i64_type().const_int(*int as u64, true).into(),
+            ValueKind::Float(float) => self.context.f64_type().const_float(*float).into(),
+            ValueKind::Bool(bool) => self
+                .context
+                .bool_type()
+                .const_int(*bool as u64, false)
+                .into(),
+            ValueKind::String(_) => return Self::unsupported("strings", value.span),
+
+            ValueKind::Local(local) => {
+                if self.functions.contains_key(local) {
+                    return Self::unsupported("functions used as values", value.span);
+                }
+
+                let Some(slot) = self.variables.get(local).copied() else {
+                    return Self::unsupported("variables of enclosing functions", value.span);
+                };
+
+                let definition = self.hir.local(*local);
+                let basetype = self.basic_type(&definition.basetype, value.span)?;
+                self.builder.build_load(basetype, slot, &definition.name)?
+            }
+
+            ValueKind::Binary {
+                operator: operator @ (TokenKind::And | TokenKind::Or),
+                left,
+                right,
+            } => self.build_logic(operator, left, right)?,
+
+            ValueKind::Binary {
+                operator,
+                left,
+                right,
+            } => self.build_binary(operator, left, right, value.span)?,
+
+            ValueKind::Unary { operator, operand } => {
+                let built = self.build_value(operand)?;
+                match (operator, &operand.basetype) {
+                    (TokenKind::Minus, BaseType::Float) => self
+                        .builder
+                        .build_float_neg(built.into_float_value(), "neg")?
+                        .into(),
+                    (TokenKind::Minus, _) => self
+                        .builder
+                        .build_int_neg(built.into_int_value(), "neg")?
+                        .into(),
+                    _ => self
+                        .builder
+                        .build_not(built.into_int_value(), "not")?
+                        .into(),
+                }
+            }
+
+            ValueKind::Cast { value: inner } => {
+                self.build_cast(inner, &value.basetype, value.span)?
+            }
+
+            ValueKind::Call { callee, args } => match self.build_call(callee, args)? {
+                Some(result) => result,
+                None => {
+                    return Self::unsupported(
+                        "using the result of a function without a return type",
+                        value.span,
+                    )
+                }
+            },
+
+            ValueKind::Builtin {
+                builtin: Builtin::Print | Builtin::Println,
+                ..
+            } => {
+                return Self::unsupported(
+                    "using the result of a function without a return type",
+                    value.span,
+                )
+            }
+
+            // only the functions of `math` are left besides printing
+            ValueKind::Builtin { builtin, .. } if builtin.module().is_none() => {
+                return Self::unsupported(format!("`{}`", builtin), value.span)
+            }
+
+            ValueKind::Builtin { builtin, args } => self.build_math(*builtin, args)?,
+
+            ValueKind::Function { .. } => return Self::unsupported("nested functions", value.span),
+
+            ValueKind::Variant { .. } => return Self::unsupported("enums", value.span),
+
+            ValueKind::List(_) => return Self::unsupported("lists", value.span),
+
+            ValueKind::Map(_) => return Self::unsupported("maps", value.span),
+        };
+
+        Ok(built)
+    }
+
+    fn build_body(&mut self, body: &[Stmt]) -> Result<(), CodegenError> {
+        for stmt in body {
+            self.build_stmt(stmt)?;
+        }
+
+        Ok(())
+    }
+
+    fn build_stmt(&mut self, stmt: &Stmt) -> Result<(), CodegenError> {
+        match stmt {
+            Stmt::Let { local, value, span } => {
+                let built = self.build_value(value)?;
+                let slot = self.variable(*local, *span)?;
+                self.builder.build_store(slot, built)?;
+            }
+
+            Stmt::Declare { local, span } => {
+                self.variable(*local, *span)?;
+            }
+
+            Stmt::Assign { target, value } => {
+                let ValueKind::Local(local) = target.kind else {
+                    unreachable!("only locals can be assigned to");
+                };
+
+                let built = self.build_value(value)?;
+                let slot = self.variable(local, target.span)?;
+                self.builder.build_store(slot, built)?;
+            }
+
+            Stmt::Return(value) => {
+                match value {
+                    Some(value) => {
+                        let built = self.build_value(value)?;
+                        self.builder.build_return(Some(&built))?
+                    }
+                    None => self.builder.build_return(None)?,
+                };
+
+                self.start_dead_block();
+            }
+
+            Stmt::If {
+                condition,
+                body,
+                else_body,
+            } => {
+                let function = self.current_function();
+                let condition = self.build_value(condition)?.into_int_value();
+                let then_block = self.context.append_basic_block(function, "then");
+                let else_block = self.context.append_basic_block(function, "else");
+                let join = self.context.append_basic_block(function, "join");
+                self.builder
+                    .build_conditional_branch(condition, then_block, else_block)?;
+
+                self.builder.position_at_end(then_block);
+                self.build_body(body)?;
+                self.branch(join)?;
+
+                self.builder.position_at_end(else_block);
+                self.build_body(else_body)?;
+                self.branch(join)?;
+
+                self.builder.position_at_end(join);
+            }
+
+            Stmt::While {
+                condition,
+                body,
+                step,
+            } => {
+                let function = self.current_function();
+                let header = self.context.append_basic_block(function, "header");
+                let body_block = self.context.append_basic_block(function, "body");
+                let step_block = self.context.append_basic_block(function, "step");
+                let exit = self.context.append_basic_block(function, "exit");
+                self.builder.build_unconditional_branch(header)?;
+
+                self.builder.position_at_end(header);
+                let condition = self.build_value(condition)?.into_int_value();
+                self.builder
+                    .build_conditional_branch(condition, body_block, exit)?;
+
+                self.loops.push(Loop {
+                    step: step_block,
+                    exit,
+                });
+                self.builder.position_at_end(body_block);
+                self.build_body(body)?;
+                self.branch(step_block)?;
+                self.loops.pop();
+
+                self.builder.position_at_end(step_block);
+                self.build_body(step)?;
+                self.branch(header)?;
+
+                self.builder.position_at_end(exit);
+            }
+
+            Stmt::Match { value, .. } => return Self::unsupported("`match`", value.span),
+            Stmt::Try { span, .. } => return Self::unsupported("`try`", *span),
+
+            Stmt::Break | Stmt::Continue => {
+                let innermost = self.loops.last().expect("loop control is checked");
+                let target = match stmt {
+                    Stmt::Break => innermost.exit,
+                    _ => innermost.step,
+                };
+
+                self.builder.build_unconditional_branch(target)?;
+                self.start_dead_block();
+            }
+
+            Stmt::Block(body) => self.build_body(body)?,
+
+            Stmt::Value(Value {
+                kind: ValueKind::Call { callee, args },
+                ..
+            }) => {
+                self.build_call(callee, args)?;
+            }
+
+            Stmt::Value(Value {
+                kind:
+                    ValueKind::Builtin {
+                        builtin: builtin @ (Builtin::Print | Builtin::Println),
+                        args,
+                    },
+                ..
+            }) => self.build_print(*builtin, args)?,
+
+            Stmt::Value(value) => {
+                self.build_value(value)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn compile(mut self) -> Result<Module<'ctx>, CodegenError> {
+        // declared first so a function of the program named `ez_main` is the one
+        // getting renamed, the startup code linked in calls it from `main`
+        let main_type = self.context.i32_type().fn_type(&[], false);
+        let main = self.module.add_function("ez_main", main_type, None);
+
+        // top level functions can be called before they're declared, and top
+        // level variables can be used from any function
+        let hir = self.hir;
+        for stmt in &hir.body {
+            match stmt {
+                Stmt::Let { local, value, .. }
+                    if matches!(value.kind, ValueKind::Function { .. }) =>
+                {
+                    self.declare_function(*local, value)?
+                }
+                Stmt::Let { local, span, .. } | Stmt::Declare { local, span } => {
+                    let definition = hir.local(*local);
+                    let basetype = self.basic_type(&definition.basetype, *span)?;
+                    let global = self.module.add_global(basetype, None, &definition.name);
+                    global.set_linkage(Linkage::Internal);
+                    global.set_initializer(&self.zero(&definition.basetype, *span)?);
+                    self.variables.insert(*local, global.as_pointer_value());
+                }
+                _ => {}
+            }
+        }
+
+        for stmt in &hir.body {
+            if let Stmt::Let { local, value, .. } = stmt {
+                if let ValueKind::Function { .. } = value.kind {
+                    self.define_function(*local, value)?;
+                }
+            }
+        }
+
+        let entry = self.context.append_basic_block(main, "entry");
+        self.builder.position_at_end(entry);
+        self.function = Some(main);
+        for stmt in &hir.body {
+            match stmt {
+                Stmt::Let { value, .. } if matches!(value.kind, ValueKind::Function { .. }) => {}
+                stmt => self.build_stmt(stmt)?,
+            }
+        }
+
+        if !self.terminated() {
+            let zero = self.context.i32_type().const_zero();
+            self.builder.build_return(Some(&zero))?;
+        }
+
+        self.module
+            .verify()
+            .map_err(|error| CodegenError::Llvm(error.to_string()))?;
+
+        Ok(self.module)
+    }
+}
+
+// a target machine for the one the compiler runs on, the module is set up for it
+fn native_machine(module: &Module) -> Result<TargetMachine, CodegenError> {
+    Target::initialize_native(&InitializationConfig::default()).map_err(CodegenError::Llvm)?;
+
+    let triple = TargetMachine::get_default_triple();
+    let target =
+        Target::from_triple(&triple).map_err(|error| CodegenError::Llvm(error.to_string()))?;
+    let machine = target
+        .create_target_machine(
+            &triple,
+            &TargetMachine::get_host_cpu_name().to_string(),
+            &TargetMachine::get_host_cpu_features().to_string(),
+            OptimizationLevel::Default,
+            RelocMode::PIC,
+            CodeModel::Default,
+        )
+        .ok_or_else(|| {
+            CodegenError::Llvm(format!(
+                "no target machine for `{}`",
+                triple.as_str().to_string_lossy()
+            ))
+        })?;
+
+    module.set_triple(&triple);
+    module.set_data_layout(&machine.get_target_data().get_data_layout());
+    Ok(machine)
+}
+
+// writes the module as an object file for the machine the compiler runs on
+pub fn write_object(module: &Module, path: &Path) -> Result<(), CodegenError> {
+    native_machine(module)?
+        .write_to_file(module, FileType::Object, path)
+        .map_err(|error| CodegenError::Llvm(error.to_string()))
+}
+
+// the assembly the object file would have
+pub fn assembly(module: &Module) -> Result<String, CodegenError> {
+    let buffer = native_machine(module)?
+        .write_to_memory_buffer(module, FileType::Assembly)
+        .map_err(|error| CodegenError::Llvm(error.to_string()))?;
+    Ok(String::from_utf8_lossy(buffer.as_slice()).into_owned())
+}
@@ -0,0 +1,189 @@
+use std::collections::HashSet;
+
+use crate::bytecode::{Function, Instr, Module, Register};
+
+// rewrites of the bytecode looking at an instruction or two at a time, the ones
+// the compiler leaves behind as it goes block by block: a `const` only used by
+// the next instruction is fused with it, a comparison only used by the jump
+// after it becomes a jump comparing, and moves to the same register and jumps
+// to the next instruction are removed
+pub fn run(module: &mut Module) {
+    for function in &mut module.functions {
+        while rewrite(function) {}
+    }
+}
+
+// the handler of the `try` covering each position, if any
+fn handlers(code: &[Instr]) -> Vec<Option<usize>> {
+    let mut handlers = vec![None; code.len()];
+    let mut handler = None;
+    for (position, instr) in code.iter().enumerate() {
+        handlers[position] = handler;
+        if handler.is_some() && instr.ends_try() {
+            handler = None;
+        }
+        if let Instr::Try(target) = instr {
+            handler = Some(*target);
+        }
+    }
+    handlers
+}
+
+// the registers read after each position before they're written again
+fn live_after(code: &[Instr], handlers: &[Option<usize>]) -> Vec<HashSet<Register>> {
+    let successors = |position: usize| {
+        let instr = &code[position];
+        let mut successors = instr.targets();
+        if instr.falls_through() && position + 1 < code.len() {
+            successors.push(position + 1);
+        }
+        successors.extend(handlers[position]);
+        successors
+    };
+
+    let mut live_in: Vec<HashSet<Register>> = vec![HashSet::new(); code.len()];
+    let mut live_after = live_in.clone();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for position in (0..code.len()).rev() {
+            let after: HashSet<Register> = successors(position)
+                .into_iter()
+                .flat_map(|successor| live_in[successor].iter().copied())
+                .collect();
+
+            let instr = &code[position];
+            let mut before = after.clone();
+            if let Some(register) = instr.writes() {
+                before.remove(&register);
+            }
+            before.extend(instr.reads());
+            live_after[position] = after;
+            if before != live_in[position] {
+                live_in[position] = before;
+                changed = true;
+            }
+        }
+    }
+
+    live_after
+}
+
+// one pass over the function, whether it changed anything
+fn rewrite(function: &mut Function) -> bool {
+    let code = &function.code;
+    let handlers = handlers(code);
+    let live = live_after(code, &handlers);
+    // two instructions only become one when nothing jumps to the second
+    let targeted: HashSet<usize> = code.iter().flat_map(Instr::targets).collect();
+    let unused = |position: usize, register: &Register| !live[position].contains(register);
+
+    let mut rewritten = code.clone();
+    let mut removed = vec![false; code.len()];
+    let mut position = 0;
+    while position < code.len() {
+        let next = position + 1;
+        let pair = !targeted.contains(&next);
+        match (&code[position], code.get(next)) {
+            (Instr::Move { to, from }, _) if to == from => removed[position] = true,
+
+            // it ends what a `try` covers even then
+            (Instr::Jump(target), _) if *target == next && handlers[position].is_none() => {
+                removed[position] = true
+            }
+
+            (
+                Instr::Const {
+                    to: loaded,
+                    constant,
+                },
+                Some(Instr::Binary {
+                    op,
+                    to,
+                    left,
+                    right,
+                }),
+            ) if pair && (to == loaded || unused(next, loaded)) => {
+                let fused = if right == loaded && left != loaded {
+                    Some((*op, *left))
+                } else if left == loaded && right != loaded {
+                    op.swapped().map(|op| (op, *right))
+                } else {
+                    None
+                };
+
+                if let Some((op, left)) = fused {
+                    rewritten[next] = Instr::BinaryConst {
+                        op,
+                        to: *to,
+                        left,
+                        constant: *constant,
+                    };
+                    removed[position] = true;
+                    position += 2;
+                    continue;
+                }
+            }
+
+            (
+                Instr::Binary {
+                    op,
+                    to,
+                    left,
+                    right,
+                },
+                Some(Instr::JumpIf { condition, target } | Instr::JumpUnless { condition, target }),
+            ) if pair && op.is_comparison() && condition == to && unused(next, to) => {
+                rewritten[position] = Instr::JumpCompare {
+                    op: *op,
+                    left: *left,
+                    right: *right,
+                    target: *target,
+                    when: matches!(code[next], Instr::JumpIf { .. }),
+                };
+                removed[next] = true;
+                position += 2;
+                continue;
+            }
+
+            _ => {}
+        }
+        position += 1;
+    }
+
+    if !removed.contains(&true) {
+        return false;
+    }
+
+    // a jump to a removed instruction goes to the one after it, which does
+    // what it did
+    let mut positions = Vec::with_capacity(code.len());
+    let mut kept = 0;
+    for removed in &removed {
+        positions.push(kept);
+        if !removed {
+            kept += 1;
+        }
+    }
+
+    // what's fused is on the line of the instruction kept
+    function.lines = function
+        .lines
+        .iter()
+        .zip(&removed)
+        .filter(|(_, removed)| !**removed)
+        .map(|(line, _)| *line)
+        .collect();
+    function.code = rewritten
+        .into_iter()
+        .zip(removed)
+        .filter(|(_, removed)| !removed)
+        .map(|(mut instr, _)| {
+            for target in instr.targets_mut() {
+                *target = positions[*target];
+            }
+            instr
+        })
+        .collect();
+    true
+}
@@ -0,0 +1,445 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::rc::{Rc, Weak};
+
+use ez_check::resolver::DefId;
+use ez_check::ssa::Constant;
+use ez_diagnostics::Diagnostic;
+use ez_lexer::{Span, TokenKind};
+
+use crate::bytecode::{Instr, Module, Register, UnaryOp};
+use crate::interp::{Closure, Code, Env, Interpreter, Key, Map, RuntimeError, Value, MAX_DEPTH};
+
+// runs a bytecode module one instruction at a time, with the values, the
+// builtins and the settings of the interpreter it's given, so a program prints
+// the same whichever of the two runs it. The module has no spans, errors point
+// at nothing. What the compiler put in the frame of a function is checked to
+// be gone once the function returns
+
+// the state of a call of a function of the module
+struct Frame<'a> {
+    registers: Vec<Value<'a>>,
+    // the locals kept in memory, the ones of the function with the environments
+    // of the functions around it as parents
+    env: Rc<Env<'a>>,
+    // the parameters and what patterns bound
+    bindings: Rc<Env<'a>>,
+    // the message of the error the handler being run caught, a `bind` of a
+    // local with no binding reads it
+    caught: Option<Value<'a>>,
+    allocated: Vec<Allocation<'a>>,
+}
+
+// what was made in the frame of a call, nothing is left to refer to it once the
+// call returns
+enum Allocation<'a> {
+    List(Weak<RefCell<Vec<Value<'a>>>>),
+    Map(Weak<RefCell<Map<'a>>>),
+    Closure(Weak<Closure<'a>>),
+}
+
+// where an instruction goes on to
+enum Flow<'a> {
+    Next,
+    Jump(usize),
+    // the instructions up to the next jump, return or `try` fail to the handler
+    Try(usize),
+    Return(Value<'a>),
+}
+
+impl<'a> Allocation<'a> {
+    fn of(value: &Value<'a>) -> Option<Self> {
+        match value {
+            Value::List(list) => Some(Allocation::List(Rc::downgrade(list))),
+            Value::Map(map) => Some(Allocation::Map(Rc::downgrade(map))),
+            Value::Function(closure) => Some(Allocation::Closure(Rc::downgrade(closure))),
+            _ => None,
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            Allocation::List(_) => "list",
+            Allocation::Map(_) => "map",
+            Allocation::Closure(_) => "closure",
+        }
+    }
+
+    fn alive(&self) -> bool {
+        match self {
+            Allocation::List(list) => list.strong_count() > 0,
+            Allocation::Map(map) => map.strong_count() > 0,
+            Allocation::Closure(closure) => closure.strong_count() > 0,
+        }
+    }
+}
+
+// the locals each function keeps in memory, a call defines them. A local
+// belongs to the innermost function around every one using it, and the ones
+// the tests use to the program's body
+fn owners(module: &Module) -> Vec<Vec<DefId>> {
+    let mut parents = vec![None; module.functions.len()];
+    let mut users: HashMap<DefId, Vec<usize>> = HashMap::new();
+    for (index, function) in module.functions.iter().enumerate() {
+        for instr in &function.code {
+            match instr {
+                Instr::Closure { function, .. } => parents[*function] = Some(index),
+                Instr::Load { local, .. } | Instr::Store { local, .. } => {
+                    users.entry(*local).or_default().push(index)
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // the function and the ones around it, innermost first
+    let around = |mut index: usize| {
+        let mut around = vec![index];
+        while let Some(parent) = parents[index] {
+            around.push(parent);
+            index = parent;
+        }
+        around
+    };
+
+    let mut owned = vec![vec![]; module.functions.len()];
+    for (local, users) in users {
+        let mut common = around(users[0]);
+        for user in &users[1..] {
+            let others: HashSet<usize> = around(*user).into_iter().collect();
+            common.retain(|function| others.contains(function));
+        }
+        owned[common.first().copied().unwrap_or(0)].push(local);
+    }
+
+    owned
+}
+
+struct Machine<'m, 'a> {
+    interpreter: &'m mut Interpreter<'a>,
+    module: &'a Module,
+    // the constants of the pool as values, strings are made once
+    constants: Vec<Value<'a>>,
+    owned: Vec<Vec<DefId>>,
+}
+
+impl<'a> Interpreter<'a> {
+    // runs the program's body, the first function of the module, with the top
+    // level variables in the globals of the interpreter
+    pub fn run_module(mut self, module: &'a Module) -> Result<(), RuntimeError> {
+        let globals = self.globals.clone();
+        let mut machine = Machine {
+            interpreter: &mut self,
+            module,
+            constants: module.constants.iter().map(constant).collect(),
+            owned: owners(module),
+        };
+        machine.run(0, globals, vec![])?;
+        Ok(())
+    }
+}
+
+fn constant<'a>(constant: &Constant) -> Value<'a> {
+    match constant {
+        Constant::Int(value) => Value::Int(*value),
+        Constant::Float(value) => Value::Float(*value),
+        Constant::String(value) => Value::String(value.as_str().into()),
+        Constant::Bool(value) => Value::Bool(*value),
+    }
+}
+
+impl<'a> Machine<'_, 'a> {
+    fn call(&mut self, callee: Value<'a>, args: Vec<Value<'a>>) -> Result<Value<'a>, RuntimeError> {
+        let closure = match callee {
+            Value::Function(closure) => closure,
+            Value::Host(host) => return Ok(host.call(&args)),
+            Value::Constructor {
+                enum_name,
+                variant,
+                index,
+            } => {
+                return Ok(Value::Variant {
+                    enum_name,
+                    variant,
+                    index,
+                    fields: args,
+                })
+            }
+            callee => unreachable!("{:?} is not a function", callee),
+        };
+
+        let Code::Bytecode(function) = closure.code else {
+            unreachable!("a module only makes closures of its own functions");
+        };
+        if self.interpreter.depth == MAX_DEPTH {
+            return Err(RuntimeError::StackOverflow(Span::default()));
+        }
+        self.interpreter.check_deadline(Span::default())?;
+
+        let env = self.interpreter.heap.env(Env::child(&closure.env));
+        self.interpreter.depth += 1;
+        let result = self.run(function, env, args);
+        self.interpreter.depth -= 1;
+        result
+    }
+
+    fn run(
+        &mut self,
+        index: usize,
+        env: Rc<Env<'a>>,
+        args: Vec<Value<'a>>,
+    ) -> Result<Value<'a>, RuntimeError> {
+        let function = &self.module.functions[index];
+        for local in &self.owned[index] {
+            if !env.values.borrow().contains_key(local) {
+                env.define(*local, Value::Void);
+            }
+        }
+        let bindings = Rc::new(Env::default());
+        for (param, arg) in function.params.iter().zip(args) {
+            bindings.define(*param, arg);
+        }
+
+        let mut frame = Frame {
+            registers: vec![Value::Void; function.registers],
+            env,
+            bindings,
+            caught: None,
+            allocated: vec![],
+        };
+        let mut handler = None;
+        let mut position = 0;
+        loop {
+            let instr = &function.code[position];
+            let flow = match self.step(instr, &mut frame) {
+                Ok(flow) => flow,
+                Err(error @ RuntimeError::TimedOut(_)) => return Err(error),
+                Err(error) => match handler.take() {
+                    Some(target) => {
+                        let message = Diagnostic::from(error).message;
+                        frame.caught = Some(Value::String(message.into()));
+                        position = target;
+                        continue;
+                    }
+                    None => return Err(error),
+                },
+            };
+            if instr.ends_try() {
+                handler = None;
+            }
+
+            match flow {
+                Flow::Next => position += 1,
+                // loops are where jumps go back
+                Flow::Jump(target) => {
+                    if target <= position {
+                        self.interpreter.check_deadline(Span::default())?;
+                    }
+                    position = target;
+                }
+                Flow::Try(target) => {
+                    handler = Some(target);
+                    position += 1;
+                }
+                Flow::Return(value) => {
+                    let allocated = std::mem::take(&mut frame.allocated);
+                    drop(frame);
+                    if let Some(allocation) = allocated.iter().find(|allocation| allocation.alive())
+                    {
+                        panic!(
+                            "a {} made in the frame of `{}` outlives the call",
+                            allocation.kind(),
+                            function.name
+                        );
+                    }
+                    return Ok(value);
+                }
+            }
+        }
+    }
+
+    fn step(&mut self, instr: &Instr, frame: &mut Frame<'a>) -> Result<Flow<'a>, RuntimeError> {
+        let registers = &mut frame.registers;
+        let get = |register: &Register| registers[register.0].clone();
+        let (to, value) = match instr {
+            Instr::Const { to, constant } => (to, self.constants[*constant].clone()),
+            Instr::Move { to, from } => (to, get(from)),
+            Instr::Binary {
+                op,
+                to,
+                left,
+                right,
+            } => (
+                to,
+                Interpreter::binary(&op.operator(), get(left), get(right), Span::default())?,
+            ),
+            Instr::BinaryConst {
+                op,
+                to,
+                left,
+                constant,
+            } => {
+                let right = self.constants[*constant].clone();
+                let value = Interpreter::binary(&op.operator(), get(left), right, Span::default())?;
+                (to, value)
+            }
+            Instr::Unary { op, to, operand } => (to, unary(*op, get(operand))?),
+            Instr::Cast { to, from, basetype } => (to, Interpreter::cast(get(from), basetype)),
+
+            Instr::Call { to, callee, args } => {
+                let args = args.iter().map(get).collect();
+                let value = self.call(get(callee), args)?;
+                match to {
+                    Some(to) => (to, value),
+                    None => return Ok(Flow::Next),
+                }
+            }
+            Instr::Builtin { to, builtin, args } => {
+                let args: Vec<Value> = args.iter().map(get).collect();
+                let value = self.interpreter.builtin(*builtin, &args, Span::default())?;
+                match to {
+                    Some(to) => (to, value),
+                    None => return Ok(Flow::Next),
+                }
+            }
+
+            Instr::Closure {
+                to,
+                function,
+                frame: local,
+            } => {
+                let closure = self.interpreter.heap.closure(Closure {
+                    code: Code::Bytecode(*function),
+                    env: frame.env.clone(),
+                });
+                frame.allocate(*to, closure, *local);
+                return Ok(Flow::Next);
+            }
+            Instr::Variant {
+                to,
+                enum_name,
+                index,
+            } => {
+                let (variant, payload) = &self.module.enums[enum_name][*index];
+                let value = if payload.is_empty() {
+                    Value::Variant {
+                        enum_name: enum_name.as_str(),
+                        variant: variant.as_str(),
+                        index: *index,
+                        fields: vec![],
+                    }
+                } else {
+                    Value::Constructor {
+                        enum_name: enum_name.as_str(),
+                        variant: variant.as_str(),
+                        index: *index,
+                    }
+                };
+                (to, value)
+            }
+            Instr::List {
+                to,
+                items,
+                frame: local,
+            } => {
+                let list = self.interpreter.heap.list(items.iter().map(get).collect());
+                frame.allocate(*to, list, *local);
+                return Ok(Flow::Next);
+            }
+            Instr::Map {
+                to,
+                entries,
+                frame: local,
+            } => {
+                let mut map = Map::default();
+                for (key, value) in entries {
+                    map.insert(Key::new(&get(key)), get(value));
+                }
+                let map = self.interpreter.heap.map(map);
+                frame.allocate(*to, map, *local);
+                return Ok(Flow::Next);
+            }
+
+            Instr::Load { to, local } => (to, frame.env.get(*local)),
+            Instr::Store { local, from } => {
+                frame.env.assign(*local, get(from));
+                return Ok(Flow::Next);
+            }
+            Instr::Bind { to, local } => {
+                let bound = frame.bindings.values.borrow().get(local).cloned();
+                let value = bound
+                    .or_else(|| frame.caught.clone())
+                    .expect("locals are bound before they're read");
+                (to, value)
+            }
+
+            Instr::Jump(target) => return Ok(Flow::Jump(*target)),
+            Instr::JumpIf { condition, target } => {
+                return Ok(jump_if(get(condition), true, *target))
+            }
+            Instr::JumpUnless { condition, target } => {
+                return Ok(jump_if(get(condition), false, *target))
+            }
+            Instr::JumpCompare {
+                op,
+                left,
+                right,
+                target,
+                when,
+            } => {
+                let condition =
+                    Interpreter::binary(&op.operator(), get(left), get(right), Span::default())?;
+                return Ok(jump_if(condition, *when, *target));
+            }
+            Instr::Match { value, arms } => {
+                let value = get(value);
+                let target = arms
+                    .iter()
+                    .find(|(pattern, _)| Interpreter::matches(pattern, &value, &frame.bindings))
+                    .map(|(_, target)| *target)
+                    .expect("matches are exhaustive");
+                return Ok(Flow::Jump(target));
+            }
+            Instr::Try(handler) => return Ok(Flow::Try(*handler)),
+            Instr::Return(value) => {
+                return Ok(Flow::Return(value.as_ref().map_or(Value::Void, get)))
+            }
+        };
+
+        frame.registers[to.0] = value;
+        Ok(Flow::Next)
+    }
+}
+
+impl<'a> Frame<'a> {
+    fn allocate(&mut self, to: Register, value: Value<'a>, local: bool) {
+        if local {
+            self.allocated.extend(Allocation::of(&value));
+        }
+        self.registers[to.0] = value;
+    }
+}
+
+fn jump_if(condition: Value, when: bool, target: usize) -> Flow {
+    match condition {
+        Value::Bool(condition) if condition == when => Flow::Jump(target),
+        _ => Flow::Next,
+    }
+}
+
+fn unary(op: UnaryOp, operand: Value) -> Result<Value, RuntimeError> {
+    let value = match (op, operand) {
+        (UnaryOp::Negate, Value::Int(operand)) => {
+            Value::Int(operand.checked_neg().ok_or(RuntimeError::Overflow {
+                operator: TokenKind::Minus,
+                span: Span::default(),
+            })?)
+        }
+        (UnaryOp::Negate, Value::Float(operand)) => Value::Float(-operand),
+        (UnaryOp::Not, Value::Bool(operand)) => Value::Bool(!operand),
+        (UnaryOp::BitNot, Value::Int(operand)) => Value::Int(!operand),
+        (op, operand) => unreachable!("{:?} can't be applied to {:?}", op, operand),
+    };
+
+    Ok(value)
+}
@@ -0,0 +1,1005 @@
+use std::collections::{HashMap, HashSet};
+
+use ez_ast::BaseType;
+use ez_check::hir::{Hir, Stmt, Value, ValueKind};
+use ez_check::resolver::{Builtin, DefId};
+use ez_diagnostics::Diagnostic;
+use ez_lexer::{Span, TokenKind};
+
+#[derive(Debug)]
+pub enum WasmError {
+    // `what` is the part of the language that can't be compiled yet
+    Unsupported { what: String, span: Span },
+}
+
+impl From<WasmError> for Diagnostic {
+    fn from(error: WasmError) -> Self {
+        match error {
+            WasmError::Unsupported { what, span } => {
+                Diagnostic::error("E0503", format!("{} can't be compiled to wasm yet", what))
+                    .with_label(span, "not supported by the wasm backend")
+                    .with_help("use `ez run` to run the program with the interpreter instead")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ValType {
+    I32 = 0x7f,
+    I64 = 0x7e,
+    F64 = 0x7c,
+}
+
+// the opcodes used by the backend, from the core specification
+mod op {
+    pub const UNREACHABLE: u8 = 0x00;
+    pub const BLOCK: u8 = 0x02;
+    pub const LOOP: u8 = 0x03;
+    pub const IF: u8 = 0x04;
+    pub const ELSE: u8 = 0x05;
+    pub const END: u8 = 0x0b;
+    pub const BR: u8 = 0x0c;
+    pub const BR_IF: u8 = 0x0d;
+    pub const RETURN: u8 = 0x0f;
+    pub const CALL: u8 = 0x10;
+    pub const DROP: u8 = 0x1a;
+    pub const SELECT: u8 = 0x1b;
+    pub const LOCAL_GET: u8 = 0x20;
+    pub const LOCAL_SET: u8 = 0x21;
+    pub const GLOBAL_GET: u8 = 0x23;
+    pub const GLOBAL_SET: u8 = 0x24;
+    pub const I32_LOAD: u8 = 0x28;
+    pub const I32_STORE: u8 = 0x36;
+    pub const I32_CONST: u8 = 0x41;
+    pub const I64_CONST: u8 = 0x42;
+    pub const F64_CONST: u8 = 0x44;
+    pub const I32_EQZ: u8 = 0x45;
+    pub const I32_EQ: u8 = 0x46;
+    pub const I32_NE: u8 = 0x47;
+    pub const I64_EQ: u8 = 0x51;
+    pub const I64_NE: u8 = 0x52;
+    pub const I64_LT_S: u8 = 0x53;
+    pub const I64_GT_S: u8 = 0x55;
+    pub const I64_LE_S: u8 = 0x57;
+    pub const I64_GE_S: u8 = 0x59;
+    pub const F64_EQ: u8 = 0x61;
+    pub const F64_NE: u8 = 0x62;
+    pub const F64_LT: u8 = 0x63;
+    pub const F64_GT: u8 = 0x64;
+    pub const F64_LE: u8 = 0x65;
+    pub const F64_GE: u8 = 0x66;
+    pub const I32_ADD: u8 = 0x6a;
+    pub const I32_OR: u8 = 0x72;
+    pub const I64_ADD: u8 = 0x7c;
+    pub const I64_SUB: u8 = 0x7d;
+    pub const I64_MUL: u8 = 0x7e;
+    pub const I64_DIV_S: u8 = 0x7f;
+    pub const I64_AND: u8 = 0x83;
+    pub const I64_OR: u8 = 0x84;
+    pub const I64_XOR: u8 = 0x85;
+    pub const F64_ABS: u8 = 0x99;
+    pub const F64_NEG: u8 = 0x9a;
+    pub const F64_CEIL: u8 = 0x9b;
+    pub const F64_FLOOR: u8 = 0x9c;
+    pub const F64_SQRT: u8 = 0x9f;
+    pub const F64_ADD: u8 = 0xa0;
+    pub const F64_SUB: u8 = 0xa1;
+    pub const F64_MUL: u8 = 0xa2;
+    pub const F64_DIV: u8 = 0xa3;
+    pub const I64_EXTEND_I32_U: u8 = 0xad;
+    pub const F64_CONVERT_I64_S: u8 = 0xb9;
+    // prefixed by 0xfc
+    pub const MISC: u8 = 0xfc;
+    pub const I64_TRUNC_SAT_F64_S: u8 = 0x06;
+    pub const MEMORY_COPY: u8 = 0x0a;
+
+    // the block type of blocks leaving nothing on the stack
+    pub const EMPTY: u8 = 0x40;
+}
+
+fn unsigned(out: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn signed(out: &mut Vec<u8>, mut value: i64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        let done = (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0);
+        if done {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn name(out: &mut Vec<u8>, name: &str) {
+    unsigned(out, name.len() as u64);
+    out.extend_from_slice(name.as_bytes());
+}
+
+// a vector of already encoded items, prefixed by how many there are
+fn vector(out: &mut Vec<u8>, items: &[Vec<u8>]) {
+    unsigned(out, items.len() as u64);
+    for item in items {
+        out.extend_from_slice(item);
+    }
+}
+
+fn section(out: &mut Vec<u8>, id: u8, items: &[Vec<u8>]) {
+    let mut contents = vec![];
+    vector(&mut contents, items);
+
+    out.push(id);
+    unsigned(out, contents.len() as u64);
+    out.extend(contents);
+}
+
+// the labels of the blocks around the code being built, `br` refers to them by
+// how many blocks out they are
+#[derive(PartialEq)]
+enum Label {
+    // the end of a loop, where `break` goes
+    Exit,
+    // the end of a loop's body, where `continue` goes
+    Continue,
+    Other,
+}
+
+// the functions the host provides to print values, and `math.pow`, which has
+// no instruction, imported from the `ez` module with their parameters and
+// result. Imports come before the functions of the module, so these take the
+// first indices. Strings are passed as the address of their length.
+const IMPORTS: [(&str, &[ValType], Option<ValType>); 6] = [
+    ("print_int", &[ValType::I64], None),
+    ("print_float", &[ValType::F64], None),
+    ("print_string", &[ValType::I32], None),
+    ("print_bool", &[ValType::I32], None),
+    ("print_newline", &[], None),
+    ("pow", &[ValType::F64, ValType::F64], Some(ValType::F64)),
+];
+
+const POW: u32 = 5;
+
+struct Function {
+    type_index: u32,
+    locals: Vec<ValType>,
+    code: Vec<u8>,
+}
+
+// the size of the heap after the string literals, in 64KiB pages. Strings built
+// at runtime are bump allocated and never freed, running out of memory traps.
+const HEAP_PAGES: u32 = 16;
+
+// lowers the HIR to a wasm module. Top level functions are exported under their
+// own name, top level variables become exported globals, and the rest of the top
+// level code goes in an exported `main`. Ints are `i64`, floats are `f64`, bools
+// are `i32`, and strings are `i32` pointers into the exported memory, to their
+// length as 4 bytes followed by their UTF-8 bytes.
+pub struct WasmBackend<'a> {
+    hir: &'a Hir,
+    types: Vec<(Vec<ValType>, Option<ValType>)>,
+    functions: HashMap<DefId, u32>,
+    globals: HashMap<DefId, u32>,
+    // the string literals, starting at address 0
+    data: Vec<u8>,
+    strings: HashMap<String, u32>,
+
+    // the function being built, its parameters are its first locals
+    code: Vec<u8>,
+    locals: HashMap<DefId, u32>,
+    local_types: Vec<ValType>,
+    params: u32,
+    labels: Vec<Label>,
+}
+
+impl<'a> WasmBackend<'a> {
+    pub fn new(hir: &'a Hir) -> Self {
+        Self {
+            hir,
+            types: vec![],
+            functions: HashMap::new(),
+            globals: HashMap::new(),
+            data: vec![],
+            strings: HashMap::new(),
+            code: vec![],
+            locals: HashMap::new(),
+            local_types: vec![],
+            params: 0,
+            labels: vec![],
+        }
+    }
+
+    fn unsupported<T>(what: impl Into<String>, span: Span) -> Result<T, WasmError> {
+        Err(WasmError::Unsupported {
+            what: what.into(),
+            span,
+        })
+    }
+
+    fn valtype(basetype: &BaseType, span: Span) -> Result<ValType, WasmError> {
+        match basetype {
+            BaseType::Int => Ok(ValType::I64),
+            BaseType::Float => Ok(ValType::F64),
+            BaseType::Bool | BaseType::String => Ok(ValType::I32),
+            basetype => Self::unsupported(format!("`{}` values", basetype), span),
+        }
+    }
+
+    fn type_index(&mut self, params: Vec<ValType>, result: Option<ValType>) -> u32 {
+        let signature = (params, result);
+        let index = match self.types.iter().position(|known| *known == signature) {
+            Some(index) => index,
+            None => {
+                self.types.push(signature);
+                self.types.len() - 1
+            }
+        };
+
+        index as u32
+    }
+
+    fn emit(&mut self, bytes: &[u8]) {
+        self.code.extend_from_slice(bytes);
+    }
+
+    fn emit_index(&mut self, opcode: u8, index: u32) {
+        self.code.push(opcode);
+        unsigned(&mut self.code, index as u64);
+    }
+
+    // the address of a string literal, adding it to the data if it's new
+    fn string(&mut self, value: &str) -> u32 {
+        if let Some(address) = self.strings.get(value) {
+            return *address;
+        }
+
+        self.data.resize(self.data.len().next_multiple_of(4), 0);
+
+        let address = self.data.len() as u32;
+        self.data
+            .extend_from_slice(&(value.len() as u32).to_le_bytes());
+        self.data.extend_from_slice(value.as_bytes());
+        self.strings.insert(value.to_string(), address);
+        address
+    }
+
+    fn local(&mut self, local: DefId, span: Span) -> Result<u32, WasmError> {
+        if let Some(index) = self.locals.get(&local) {
+            return Ok(*index);
+        }
+
+        let valtype = Self::valtype(&self.hir.local(local).basetype, span)?;
+        let index = self.scratch(valtype);
+        self.locals.insert(local, index);
+        Ok(index)
+    }
+
+    // a local that isn't in the program, to keep a value around while it's used
+    // more than once
+    fn scratch(&mut self, valtype: ValType) -> u32 {
+        let index = self.params + self.local_types.len() as u32;
+        self.local_types.push(valtype);
+        index
+    }
+
+    fn get(&mut self, local: DefId, span: Span) -> Result<(), WasmError> {
+        match self.globals.get(&local).copied() {
+            Some(global) => self.emit_index(op::GLOBAL_GET, global),
+            None => {
+                let index = self.local(local, span)?;
+                self.emit_index(op::LOCAL_GET, index);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn set(&mut self, local: DefId, span: Span) -> Result<(), WasmError> {
+        match self.globals.get(&local).copied() {
+            Some(global) => self.emit_index(op::GLOBAL_SET, global),
+            None => {
+                let index = self.local(local, span)?;
+                self.emit_index(op::LOCAL_SET, index);
+            }
+        }
+
+        Ok(())
+    }
+
+    // how many blocks out the innermost label of the given kind is
+    fn depth(&self, label: Label) -> u32 {
+        self.labels
+            .iter()
+            .rev()
+            .position(|candidate| *candidate == label)
+            .expect("loop control is checked") as u32
+    }
+
+    fn build_binary(
+        &mut self,
+        operator: &TokenKind,
+        left: &Value,
+        right: &Value,
+        span: Span,
+    ) -> Result<(), WasmError> {
+        // `and` and `or` only evaluate their right side when the left one doesn't
+        // decide the result
+        if let TokenKind::And | TokenKind::Or = operator {
+            self.build_value(left)?;
+            self.emit(&[op::IF, ValType::I32 as u8]);
+            self.labels.push(Label::Other);
+            if *operator == TokenKind::And {
+                self.build_value(right)?;
+                self.emit(&[op::ELSE, op::I32_CONST, 0]);
+            } else {
+                self.emit(&[op::I32_CONST, 1, op::ELSE]);
+                self.build_value(right)?;
+            }
+            self.emit(&[op::END]);
+            self.labels.pop();
+            return Ok(());
+        }
+
+        self.build_value(left)?;
+        self.build_value(right)?;
+
+        let opcode = match (&left.basetype, operator) {
+            // joining strings needs to allocate, that's done by a helper function
+            // added after the ones of the program
+            (BaseType::String, TokenKind::Plus) => {
+                let concat = (IMPORTS.len() + self.functions.len()) as u32;
+                self.emit_index(op::CALL, concat);
+                return Ok(());
+            }
+
+            (BaseType::Int, TokenKind::Plus) => op::I64_ADD,
+            (BaseType::Int, TokenKind::Minus) => op::I64_SUB,
+            (BaseType::Int, TokenKind::Times) => op::I64_MUL,
+            (BaseType::Int, TokenKind::DividedBy) => op::I64_DIV_S,
+            (BaseType::Int, TokenKind::BitAnd) => op::I64_AND,
+            (BaseType::Int, TokenKind::BitOr) => op::I64_OR,
+            (BaseType::Int, TokenKind::BitXor) => op::I64_XOR,
+            (BaseType::Int, TokenKind::Equals) => op::I64_EQ,
+            (BaseType::Int, TokenKind::NotEquals) => op::I64_NE,
+            (BaseType::Int, TokenKind::GreaterThan) => op::I64_GT_S,
+            (BaseType::Int, TokenKind::GreaterOrEquals) => op::I64_GE_S,
+            (BaseType::Int, TokenKind::LowerThan) => op::I64_LT_S,
+            (BaseType::Int, TokenKind::LowerOrEquals) => op::I64_LE_S,
+
+            (BaseType::Float, TokenKind::Plus) => op::F64_ADD,
+            (BaseType::Float, TokenKind::Minus) => op::F64_SUB,
+            (BaseType::Float, TokenKind::Times) => op::F64_MUL,
+            (BaseType::Float, TokenKind::DividedBy) => op::F64_DIV,
+            (BaseType::Float, TokenKind::Equals) => op::F64_EQ,
+            (BaseType::Float, TokenKind::NotEquals) => op::F64_NE,
+            (BaseType::Float, TokenKind::GreaterThan) => op::F64_GT,
+            (BaseType::Float, TokenKind::GreaterOrEquals) => op::F64_GE,
+            (BaseType::Float, TokenKind::LowerThan) => op::F64_LT,
+            (BaseType::Float, TokenKind::LowerOrEquals) => op::F64_LE,
+
+            (BaseType::Bool, TokenKind::Equals) => op::I32_EQ,
+            (BaseType::Bool, TokenKind::NotEquals) => op::I32_NE,
+
+            (basetype, operator) => {
+                return Self::unsupported(format!("{} on `{}`", operator, basetype), span)
+            }
+        };
+
+        self.emit(&[opcode]);
+        Ok(())
+    }
+
+    fn build_cast(&mut self, value: &Value, to: &BaseType, span: Span) -> Result<(), WasmError> {
+        self.build_value(value)?;
+
+        match (&value.basetype, to) {
+            (from, to) if from == to => {}
+            (BaseType::Int, BaseType::Float) => self.emit(&[op::F64_CONVERT_I64_S]),
+            // like the interpreter, floats saturate at the bounds of an int
+            (BaseType::Float, BaseType::Int) => self.emit(&[op::MISC, op::I64_TRUNC_SAT_F64_S]),
+            (BaseType::Bool, BaseType::Int) => self.emit(&[op::I64_EXTEND_I32_U]),
+            (_, to) => return Self::unsupported(format!("casts to `{}`", to), span),
+        }
+
+        Ok(())
+    }
+
+    // `abs` on ints, `min` and `max` pick one of two values with `select`, the
+    // rest are instructions, except `pow`, which comes from the host
+    fn build_math(&mut self, builtin: Builtin, args: &[Value]) -> Result<(), WasmError> {
+        let float = args[0].basetype == BaseType::Float;
+        match builtin {
+            Builtin::Sqrt | Builtin::Floor | Builtin::Ceil | Builtin::Abs if float => {
+                self.build_value(&args[0])?;
+                self.emit(&[match builtin {
+                    Builtin::Sqrt => op::F64_SQRT,
+                    Builtin::Floor => op::F64_FLOOR,
+                    Builtin::Ceil => op::F64_CEIL,
+                    _ => op::F64_ABS,
+                }]);
+            }
+
+            Builtin::Pow => {
+                self.build_value(&args[0])?;
+                self.build_value(&args[1])?;
+                self.emit_index(op::CALL, POW);
+            }
+
+            // `select(0 - x, x, x < 0)`
+            Builtin::Abs => {
+                let value = self.scratch(ValType::I64);
+                self.build_value(&args[0])?;
+                self.emit_index(op::LOCAL_SET, value);
+                self.emit(&[op::I64_CONST, 0]);
+                self.emit_index(op::LOCAL_GET, value);
+                self.emit(&[op::I64_SUB]);
+                self.emit_index(op::LOCAL_GET, value);
+                self.emit_index(op::LOCAL_GET, value);
+                self.emit(&[op::I64_CONST, 0, op::I64_LT_S, op::SELECT]);
+            }
+
+            // `select(b, a, b < a)` for `min`, like the interpreter a NaN is only
+            // picked when both values are NaN
+            _ => {
+                let valtype = if float { ValType::F64 } else { ValType::I64 };
+                let (a, b) = (self.scratch(valtype), self.scratch(valtype));
+                self.build_value(&args[0])?;
+                self.emit_index(op::LOCAL_SET, a);
+                self.build_value(&args[1])?;
+                self.emit_index(op::LOCAL_SET, b);
+
+                self.emit_index(op::LOCAL_GET, b);
+                self.emit_index(op::LOCAL_GET, a);
+                self.emit_index(op::LOCAL_GET, b);
+                self.emit_index(op::LOCAL_GET, a);
+                self.emit(&[match (builtin, float) {
+                    (Builtin::Min, false) => op::I64_LT_S,
+                    (Builtin::Min, true) => op::F64_LT,
+                    (_, false) => op::I64_GT_S,
+                    (_, true) => op::F64_GT,
+                }]);
+                if float {
+                    self.emit_index(op::LOCAL_GET, a);
+                    self.emit_index(op::LOCAL_GET, a);
+                    self.emit(&[op::F64_NE, op::I32_OR]);
+                }
+                self.emit(&[op::SELECT]);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn build_call(&mut self, callee: &Value, args: &[Value]) -> Result<(), WasmError> {
+        let function = match callee.kind {
+            ValueKind::Local(local) if self.functions.contains_key(&local) => {
+                self.functions[&local]
+            }
+            _ => return Self::unsupported("calls through function values", callee.span),
+        };
+
+        for arg in args {
+            self.build_value(arg)?;
+        }
+
+        self.emit_index(op::CALL, function);
+        Ok(())
+    }
+
+    fn build_value(&mut self, value: &Value) -> Result<(), WasmError> {
+        match &value.kind {
+            ValueKind::Int(int) => {
+                self.code.push(op::I64_CONST);
+                signed(&mut self.code, *int);
+            }
+            ValueKind::Float(float) => {
+                self.code.push(op::F64_CONST);
+                self.code.extend_from_slice(&float.to_le_bytes());
+            }
+            ValueKind::Bool(bool) => self.emit(&[op::I32_CONST, *bool as u8]),
+            ValueKind::String(string) => {
+                let address = self.string(string);
+                self.code.push(op::I32_CONST);
+                signed(&mut self.code, address as i64);
+            }
+
+            ValueKind::Local(local) => {
+                if self.functions.contains_key(local) {
+                    return Self::unsupported("functions used as values", value.span);
+                }
+
+                self.get(*local, value.span)?;
+            }
+
+            ValueKind::Binary {
+                operator,
+                left,
+                right,
+            } => self.build_binary(operator, left, right, value.span)?,
+
+            ValueKind::Unary { operator, operand } => match (operator, &operand.basetype) {
+                (TokenKind::Minus, BaseType::Float) => {
+                    self.build_value(operand)?;
+                    self.emit(&[op::F64_NEG]);
+                }
+                // `0 - x`
+                (TokenKind::Minus, _) => {
+                    self.emit(&[op::I64_CONST, 0]);
+                    self.build_value(operand)?;
+                    self.emit(&[op::I64_SUB]);
+                }
+                // `x ^ -1`
+                (TokenKind::BitNot, _) => {
+                    self.build_value(operand)?;
+                    self.emit(&[op::I64_CONST, 0x7f, op::I64_XOR]);
+                }
+                _ => {
+                    self.build_value(operand)?;
+                    self.emit(&[op::I32_EQZ]);
+                }
+            },
+
+            ValueKind::Cast { value: inner } => {
+                self.build_cast(inner, &value.basetype, value.span)?
+            }
+
+            ValueKind::Call { callee, args } => self.build_call(callee, args)?,
+
+            ValueKind::Builtin {
+                builtin: builtin @ (Builtin::Print | Builtin::Println),
+                args,
+            } => {
+                for arg in args {
+                    self.build_value(arg)?;
+                    let import = match arg.basetype {
+                        BaseType::Int => 0,
+                        BaseType::Float => 1,
+                        BaseType::String => 2,
+                        _ => 3,
+                    };
+                    self.emit_index(op::CALL, import);
+                }
+
+                if *builtin == Builtin::Println {
+                    self.emit_index(op::CALL, 4);
+                }
+            }
+
+            // only the functions of `math` are left besides printing
+            ValueKind::Builtin { builtin, .. } if builtin.module().is_none() => {
+                return Self::unsupported(format!("`{}`", builtin), value.span)
+            }
+
+            ValueKind::Builtin { builtin, args } => self.build_math(*builtin, args)?,
+
+            ValueKind::Function { .. } => return Self::unsupported("nested functions", value.span),
+
+            ValueKind::Variant { .. } => return Self::unsupported("enums", value.span),
+
+            ValueKind::List(_) => return Self::unsupported("lists", value.span),
+
+            ValueKind::Map(_) => return Self::unsupported("maps", value.span),
+        }
+
+        Ok(())
+    }
+
+    fn build_body(&mut self, body: &[Stmt]) -> Result<(), WasmError> {
+        for stmt in body {
+            self.build_stmt(stmt)?;
+        }
+
+        Ok(())
+    }
+
+    fn build_stmt(&mut self, stmt: &Stmt) -> Result<(), WasmError> {
+        match stmt {
+            Stmt::Let { local, value, span } => {
+                self.build_value(value)?;
+                self.set(*local, *span)?;
+            }
+
+            // locals start out as zero, the checks make sure that's never read
+            Stmt::Declare { local, span } => {
+                if !self.globals.contains_key(local) {
+                    self.local(*local, *span)?;
+                }
+            }
+
+            Stmt::Assign { target, value } => {
+                let ValueKind::Local(local) = target.kind else {
+                    unreachable!("only locals can be assigned to");
+                };
+
+                self.build_value(value)?;
+                self.set(local, target.span)?;
+            }
+
+            Stmt::Return(value) => {
+                if let Some(value) = value {
+                    self.build_value(value)?;
+                }
+                self.emit(&[op::RETURN]);
+            }
+
+            Stmt::If {
+                condition,
+                body,
+                else_body,
+            } => {
+                self.build_value(condition)?;
+                self.emit(&[op::IF, op::EMPTY]);
+                self.labels.push(Label::Other);
+                self.build_body(body)?;
+                if !else_body.is_empty() {
+                    self.emit(&[op::ELSE]);
+                    self.build_body(else_body)?;
+                }
+                self.emit(&[op::END]);
+                self.labels.pop();
+            }
+
+            // block $exit
+            //     loop $header
+            //         br_if $exit (not condition)
+            //         block $continue body end
+            //         step
+            //         br $header
+            //     end
+            // end
+            Stmt::While {
+                condition,
+                body,
+                step,
+            } => {
+                self.emit(&[op::BLOCK, op::EMPTY, op::LOOP, op::EMPTY]);
+                self.labels.push(Label::Exit);
+                self.labels.push(Label::Other);
+
+                self.build_value(condition)?;
+                self.emit(&[op::I32_EQZ, op::BR_IF, 1]);
+
+                self.emit(&[op::BLOCK, op::EMPTY]);
+                self.labels.push(Label::Continue);
+                self.build_body(body)?;
+                self.emit(&[op::END]);
+                self.labels.pop();
+
+                self.build_body(step)?;
+                self.emit(&[op::BR, 0, op::END, op::END]);
+                self.labels.pop();
+                self.labels.pop();
+            }
+
+            Stmt::Match { value, .. } => return Self::unsupported("`match`", value.span),
+            Stmt::Try { span, .. } => return Self::unsupported("`try`", *span),
+
+            Stmt::Break => {
+                let depth = self.depth(Label::Exit);
+                self.emit_index(op::BR, depth);
+            }
+
+            Stmt::Continue => {
+                let depth = self.depth(Label::Continue);
+                self.emit_index(op::BR, depth);
+            }
+
+            Stmt::Block(body) => self.build_body(body)?,
+
+            Stmt::Value(value) => {
+                self.build_value(value)?;
+                if value.basetype != BaseType::Void {
+                    self.emit(&[op::DROP]);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // builds a function, `params` are its first locals
+    fn build_function(
+        &mut self,
+        type_index: u32,
+        params: &[DefId],
+        body: &[Stmt],
+        returns: bool,
+    ) -> Result<Function, WasmError> {
+        self.locals = params
+            .iter()
+            .enumerate()
+            .map(|(index, param)| (*param, index as u32))
+            .collect();
+        self.params = params.len() as u32;
+
+        self.build_body(body)?;
+
+        // the return checker made sure functions returning a value don't fall off
+        // their end
+        if returns {
+            self.emit(&[op::UNREACHABLE]);
+        }
+        self.emit(&[op::END]);
+
+        Ok(Function {
+            type_index,
+            locals: std::mem::take(&mut self.local_types),
+            code: std::mem::take(&mut self.code),
+        })
+    }
+
+    // `concat(left, right)` copies both strings after each other at the end of
+    // the heap, which starts at the address in global 0
+    fn concat(&mut self) -> Function {
+        let type_index = self.type_index(vec![ValType::I32, ValType::I32], Some(ValType::I32));
+        let (left, right, left_len, right_len, result) = (0, 1, 2, 3, 4);
+        let copy = [op::MISC, op::MEMORY_COPY, 0, 0];
+        let load = [op::I32_LOAD, 2, 0];
+        let store = [op::I32_STORE, 2, 0];
+
+        let mut code = vec![];
+        code.extend([op::LOCAL_GET, left]);
+        code.extend(load);
+        code.extend([op::LOCAL_SET, left_len, op::LOCAL_GET, right]);
+        code.extend(load);
+        code.extend([op::LOCAL_SET, right_len]);
+
+        code.extend([op::GLOBAL_GET, 0, op::LOCAL_SET, result]);
+        code.extend([op::GLOBAL_GET, 0, op::I32_CONST, 4, op::I32_ADD]);
+        code.extend([op::LOCAL_GET, left_len, op::I32_ADD]);
+        code.extend([op::LOCAL_GET, right_len, op::I32_ADD, op::GLOBAL_SET, 0]);
+
+        code.extend([op::LOCAL_GET, result, op::LOCAL_GET, left_len]);
+        code.extend([op::LOCAL_GET, right_len, op::I32_ADD]);
+        code.extend(store);
+
+        code.extend([op::LOCAL_GET, result, op::I32_CONST, 4, op::I32_ADD]);
+        code.extend([op::LOCAL_GET, left, op::I32_CONST, 4, op::I32_ADD]);
+        code.extend([op::LOCAL_GET, left_len]);
+        code.extend(copy);
+
+        code.extend([op::LOCAL_GET, result, op::I32_CONST, 4, op::I32_ADD]);
+        code.extend([op::LOCAL_GET, left_len, op::I32_ADD]);
+        code.extend([op::LOCAL_GET, right, op::I32_CONST, 4, op::I32_ADD]);
+        code.extend([op::LOCAL_GET, right_len]);
+        code.extend(copy);
+
+        code.extend([op::LOCAL_GET, result, op::END]);
+
+        Function {
+            type_index,
+            locals: vec![ValType::I32; 3],
+            code,
+        }
+    }
+
+    pub fn compile(mut self) -> Result<Vec<u8>, WasmError> {
+        let hir = self.hir;
+        let mut program_functions = vec![];
+        let mut exports: Vec<(&str, u8, u32)> = vec![];
+
+        // top level functions can be called before they're declared, and top
+        // level variables can be used from any function. Global 0 is the end of
+        // the heap.
+        let imports: Vec<u32> = IMPORTS
+            .iter()
+            .map(|(_, params, result)| self.type_index(params.to_vec(), *result))
+            .collect();
+
+        let mut global_types = vec![ValType::I32];
+        for stmt in &hir.body {
+            match stmt {
+                Stmt::Let {
+                    local,
+                    value:
+                        value @ Value {
+                            kind: ValueKind::Function { params, body },
+                            ..
+                        },
+                    ..
+                } => {
+                    let BaseType::Function { return_type, .. } = &value.basetype else {
+                        unreachable!("functions have a function type");
+                    };
+
+                    let param_types = params
+                        .iter()
+                        .map(|param| Self::valtype(&hir.local(*param).basetype, value.span))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    let result = match &**return_type {
+                        BaseType::Void => None,
+                        return_type => Some(Self::valtype(return_type, value.span)?),
+                    };
+
+                    let type_index = self.type_index(param_types, result);
+                    let index = (IMPORTS.len() + program_functions.len()) as u32;
+                    self.functions.insert(*local, index);
+                    program_functions.push((type_index, params, body, result.is_some()));
+                    exports.push((hir.local(*local).name.as_str(), 0, index));
+                }
+
+                Stmt::Let { local, span, .. } | Stmt::Declare { local, span } => {
+                    let valtype = Self::valtype(&hir.local(*local).basetype, *span)?;
+                    let index = global_types.len() as u32;
+                    global_types.push(valtype);
+                    self.globals.insert(*local, index);
+                    exports.push((hir.local(*local).name.as_str(), 3, index));
+                }
+
+                _ => {}
+            }
+        }
+
+        let mut functions = vec![];
+        for (type_index, params, body, returns) in program_functions {
+            functions.push(self.build_function(type_index, params, body, returns)?);
+        }
+
+        functions.push(self.concat());
+
+        let main_type = self.type_index(vec![], None);
+        let top_level: Vec<&Stmt> = hir
+            .body
+            .iter()
+            .filter(|stmt| {
+                !matches!(
+                    stmt,
+                    Stmt::Let {
+                        value: Value {
+                            kind: ValueKind::Function { .. },
+                            ..
+                        },
+                        ..
+                    }
+                )
+            })
+            .collect();
+        self.locals.clear();
+        self.params = 0;
+        for stmt in top_level {
+            self.build_stmt(stmt)?;
+        }
+        self.emit(&[op::END]);
+        functions.push(Function {
+            type_index: main_type,
+            locals: std::mem::take(&mut self.local_types),
+            code: std::mem::take(&mut self.code),
+        });
+
+        Ok(self.encode(imports, functions, global_types, exports))
+    }
+
+    fn encode(
+        &self,
+        imports: Vec<u32>,
+        functions: Vec<Function>,
+        global_types: Vec<ValType>,
+        exports: Vec<(&str, u8, u32)>,
+    ) -> Vec<u8> {
+        let mut module = b"\0asm".to_vec();
+        module.extend(1u32.to_le_bytes());
+
+        let types: Vec<Vec<u8>> = self
+            .types
+            .iter()
+            .map(|(params, result)| {
+                let mut encoded = vec![0x60];
+                let params: Vec<Vec<u8>> = params.iter().map(|param| vec![*param as u8]).collect();
+                vector(&mut encoded, &params);
+                let results: Vec<Vec<u8>> =
+                    result.iter().map(|result| vec![*result as u8]).collect();
+                vector(&mut encoded, &results);
+                encoded
+            })
+            .collect();
+        section(&mut module, 1, &types);
+
+        let imports: Vec<Vec<u8>> = IMPORTS
+            .iter()
+            .zip(imports)
+            .map(|((field, ..), type_index)| {
+                let mut encoded = vec![];
+                name(&mut encoded, "ez");
+                name(&mut encoded, field);
+                encoded.push(0x00);
+                unsigned(&mut encoded, type_index as u64);
+                encoded
+            })
+            .collect();
+        section(&mut module, 2, &imports);
+
+        let declarations: Vec<Vec<u8>> = functions
+            .iter()
+            .map(|function| {
+                let mut encoded = vec![];
+                unsigned(&mut encoded, function.type_index as u64);
+                encoded
+            })
+            .collect();
+        section(&mut module, 3, &declarations);
+
+        let heap_start = self.data.len().next_multiple_of(4) as u32;
+        let mut memory = vec![0x00];
+        unsigned(&mut memory, (heap_start / 65536 + HEAP_PAGES) as u64);
+        section(&mut module, 5, &[memory]);
+
+        let globals: Vec<Vec<u8>> = global_types
+            .iter()
+            .enumerate()
+            .map(|(index, valtype)| {
+                let mut encoded = vec![*valtype as u8, 0x01];
+                match valtype {
+                    ValType::I32 => {
+                        encoded.push(op::I32_CONST);
+                        // the heap starts after the string literals
+                        signed(&mut encoded, if index == 0 { heap_start as i64 } else { 0 });
+                    }
+                    ValType::I64 => encoded.extend([op::I64_CONST, 0]),
+                    ValType::F64 => {
+                        encoded.push(op::F64_CONST);
+                        encoded.extend(0f64.to_le_bytes());
+                    }
+                }
+                encoded.push(op::END);
+                encoded
+            })
+            .collect();
+        section(&mut module, 6, &globals);
+
+        // names can be shadowed at the top level, the last declaration wins
+        let main = (IMPORTS.len() + functions.len()) as u32 - 1;
+        let mut exported = HashSet::from(["main", "memory"]);
+        let mut export_entries = vec![];
+        for (export, kind, index) in [("main", 0, main), ("memory", 2, 0)].into_iter().chain(
+            exports
+                .into_iter()
+                .rev()
+                .filter(|(export, ..)| exported.insert(*export)),
+        ) {
+            let mut encoded = vec![];
+            name(&mut encoded, export);
+            encoded.push(kind);
+            unsigned(&mut encoded, index as u64);
+            export_entries.push(encoded);
+        }
+        section(&mut module, 7, &export_entries);
+
+        let bodies: Vec<Vec<u8>> = functions
+            .into_iter()
+            .map(|function| {
+                let mut body = vec![];
+                let locals: Vec<Vec<u8>> = function
+                    .locals
+                    .iter()
+                    .map(|local| vec![1, *local as u8])
+                    .collect();
+                vector(&mut body, &locals);
+                body.extend(function.code);
+
+                let mut encoded = vec![];
+                unsigned(&mut encoded, body.len() as u64);
+                encoded.extend(body);
+                encoded
+            })
+            .collect();
+        section(&mut module, 10, &bodies);
+
+        let mut segment = vec![0x00, op::I32_CONST, 0, op::END];
+        unsigned(&mut segment, self.data.len() as u64);
+        segment.extend_from_slice(&self.data);
+        section(&mut module, 11, &[segment]);
+
+        module
+    }
+}
@@ -0,0 +1,357 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use ez_lexer::Span;
+
+use crate::render::Renderer;
+
+pub mod render;
+mod source;
+
+pub use crate::source::{compatible, FileId, Location, Position, SourceFile, SourceMap};
+
+// Error codes are stable, once a code is given out it keeps its meaning and is
+// never reused. They're grouped by the stage that reports them:
+//   E00xx lexing and parsing
+//   E01xx name resolution
+//   E02xx type checking
+//   E03xx mutability and control flow
+//   W00xx lints
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+    // the primary label points at the cause of the diagnostic, secondary ones
+    // give context, like where something was declared
+    pub primary: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NoteKind {
+    Note,
+    Help,
+}
+
+#[derive(Debug, Clone)]
+pub struct Note {
+    pub kind: NoteKind,
+    pub message: String,
+    // the edit a help suggests
+    pub suggestion: Option<Suggestion>,
+}
+
+// an edit that fixes what a diagnostic is about, the text of the span is
+// replaced. Only the edits that are right whatever the program is are given, so
+// tools can apply them without asking, an insertion is an empty span
+#[derive(Debug, Clone, PartialEq)]
+pub struct Suggestion {
+    pub span: Span,
+    pub replacement: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub code: &'static str,
+    pub severity: Severity,
+    pub message: String,
+    pub spans: Vec<Label>,
+    pub notes: Vec<Note>,
+    // the lint that reported this, lints can be allowed or denied by name
+    pub lint: Option<&'static str>,
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            severity,
+            message: message.into(),
+            spans: vec![],
+            notes: vec![],
+            lint: None,
+        }
+    }
+
+    pub fn error(code: &'static str, message: impl Into<String>) -> Self {
+        Self::new(Severity::Error, code, message)
+    }
+
+    pub fn warning(code: &'static str, message: impl Into<String>) -> Self {
+        Self::new(Severity::Warning, code, message)
+    }
+
+    pub fn with_label(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.spans.push(Label {
+            span,
+            message: message.into(),
+            primary: true,
+        });
+        self
+    }
+
+    pub fn with_secondary(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.spans.push(Label {
+            span,
+            message: message.into(),
+            primary: false,
+        });
+        self
+    }
+
+    pub fn with_note(mut self, message: impl Into<String>) -> Self {
+        self.notes.push(Note {
+            kind: NoteKind::Note,
+            message: message.into(),
+            suggestion: None,
+        });
+        self
+    }
+
+    pub fn with_help(mut self, message: impl Into<String>) -> Self {
+        self.notes.push(Note {
+            kind: NoteKind::Help,
+            message: message.into(),
+            suggestion: None,
+        });
+        self
+    }
+
+    // a help with the edit it suggests
+    pub fn with_suggestion(
+        mut self,
+        message: impl Into<String>,
+        span: Span,
+        replacement: impl Into<String>,
+    ) -> Self {
+        self.notes.push(Note {
+            kind: NoteKind::Help,
+            message: message.into(),
+            suggestion: Some(Suggestion {
+                span,
+                replacement: replacement.into(),
+            }),
+        });
+        self
+    }
+
+    // the edits of its helps, with what they're for
+    pub fn suggestions(&self) -> impl Iterator<Item = (&str, &Suggestion)> {
+        self.notes.iter().filter_map(|note| {
+            let suggestion = note.suggestion.as_ref()?;
+            Some((note.message.as_str(), suggestion))
+        })
+    }
+
+    pub fn for_lint(mut self, lint: &'static str) -> Self {
+        self.lint = Some(lint);
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Allow,
+    Warn,
+    Deny,
+}
+
+impl Level {
+    pub fn parse(name: &str) -> Option<Level> {
+        match name {
+            "allow" => Some(Level::Allow),
+            "warn" => Some(Level::Warn),
+            "deny" => Some(Level::Deny),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Level::Allow => write!(f, "allow"),
+            Level::Warn => write!(f, "warn"),
+            Level::Deny => write!(f, "deny"),
+        }
+    }
+}
+
+// the level of every lint, lints default to `Warn`
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct LintLevels {
+    levels: HashMap<&'static str, Level>,
+    // set by `warnings`, applies to every lint without a level of its own
+    all: Option<Level>,
+}
+
+impl LintLevels {
+    pub fn set(&mut self, lint: &'static str, level: Level) {
+        self.levels.insert(lint, level);
+    }
+
+    // setting every lint overrides the levels that were set before
+    pub fn set_all(&mut self, level: Level) {
+        self.levels.clear();
+        self.all = Some(level);
+    }
+
+    pub fn level(&self, lint: &str) -> Level {
+        self.levels
+            .get(lint)
+            .copied()
+            .or(self.all)
+            .unwrap_or(Level::Warn)
+    }
+
+    // the levels in the same order every time, to tell whether they changed
+    pub fn key(&self) -> String {
+        let mut levels: Vec<String> = self
+            .levels
+            .iter()
+            .map(|(lint, level)| format!("{}={}", lint, level))
+            .collect();
+        levels.sort();
+        if let Some(all) = self.all {
+            levels.insert(0, format!("warnings={}", all));
+        }
+        levels.join(",")
+    }
+
+    // whether the level was changed from the default
+    fn is_set(&self, lint: &str) -> bool {
+        self.levels.contains_key(lint) || self.all.is_some()
+    }
+}
+
+// the 1-based line and column of a byte offset
+pub fn line_col(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset.min(source.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.chars().rev().take_while(|&c| c != '\n').count() + 1;
+
+    (line, column)
+}
+
+// collects the diagnostics of every stage, so they can all be reported together
+#[derive(Debug, Default)]
+pub struct DiagnosticSink {
+    diagnostics: Vec<Diagnostic>,
+    levels: LintLevels,
+}
+
+impl DiagnosticSink {
+    pub fn with_levels(levels: LintLevels) -> Self {
+        Self {
+            diagnostics: vec![],
+            levels,
+        }
+    }
+
+    // lints are dropped when they're allowed, and become errors when denied
+    pub fn push(&mut self, diagnostic: impl Into<Diagnostic>) {
+        let mut diagnostic = diagnostic.into();
+
+        if let Some(lint) = diagnostic.lint {
+            match self.levels.level(lint) {
+                Level::Allow => return,
+                Level::Warn => {}
+                Level::Deny => diagnostic.severity = Severity::Error,
+            }
+        }
+
+        self.diagnostics.push(diagnostic);
+    }
+
+    pub fn extend<T: Into<Diagnostic>>(&mut self, diagnostics: impl IntoIterator<Item = T>) {
+        for diagnostic in diagnostics {
+            self.push(diagnostic);
+        }
+    }
+
+    // in the order they were reported, without the lints that are allowed
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    pub fn count(&self, severity: Severity) -> usize {
+        self.diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.severity == severity)
+            .count()
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.count(Severity::Error) > 0
+    }
+
+    // diagnostics that don't come from lints go first, in the order they were
+    // reported, then the lints grouped by name. The first diagnostic of every
+    // lint says where its level comes from
+    pub fn render(&self, renderer: &Renderer, sources: &SourceMap) -> String {
+        let render = |diagnostic: &Diagnostic| {
+            let (diagnostic, file) = sources.localize(diagnostic);
+            renderer.render(&diagnostic, &file.name, &file.source)
+        };
+
+        let mut lints: Vec<&'static str> = vec![];
+        for lint in self
+            .diagnostics
+            .iter()
+            .filter_map(|diagnostic| diagnostic.lint)
+        {
+            if !lints.contains(&lint) {
+                lints.push(lint);
+            }
+        }
+
+        let mut output: String = self
+            .diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.lint.is_none())
+            .map(|diagnostic| render(diagnostic) + "\n")
+            .collect();
+
+        for lint in lints {
+            let level = self.levels.level(lint);
+            let origin = if self.levels.is_set(lint) {
+                format!("`{}({})` was set on the command line", level, lint)
+            } else {
+                format!("`{}({})` is on by default", level, lint)
+            };
+
+            let group = self
+                .diagnostics
+                .iter()
+                .filter(|diagnostic| diagnostic.lint == Some(lint));
+
+            for (index, diagnostic) in group.enumerate() {
+                let rendered = if index == 0 {
+                    render(&diagnostic.clone().with_note(origin.clone()))
+                } else {
+                    render(diagnostic)
+                };
+
+                output.push_str(&rendered);
+                output.push('\n');
+            }
+        }
+
+        output
+    }
+}
@@ -0,0 +1,300 @@
+use crate::{line_col, Diagnostic, Label, NoteKind, Severity};
+
+const TAB_WIDTH: usize = 4;
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const BLUE: &str = "\x1b[1;34m";
+const CYAN: &str = "\x1b[1;36m";
+
+// `--color`, with `auto` the output is colored when it goes to a terminal and
+// `NO_COLOR` isn't set
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ColorChoice {
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    pub fn parse(name: &str) -> Option<ColorChoice> {
+        match name {
+            "auto" => Some(ColorChoice::Auto),
+            "always" => Some(ColorChoice::Always),
+            "never" => Some(ColorChoice::Never),
+            _ => None,
+        }
+    }
+
+    // `terminal` is whether the output goes to one
+    pub fn enabled(self, terminal: bool) -> bool {
+        match self {
+            ColorChoice::Auto => terminal && std::env::var_os("NO_COLOR").is_none(),
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        }
+    }
+}
+
+// renders diagnostics the way rustc does, with the offending source lines and
+// carets under the labeled spans
+pub struct Renderer {
+    pub color: bool,
+    // lines wider than this get cut around the labels
+    pub width: usize,
+}
+
+impl Default for Renderer {
+    fn default() -> Self {
+        Self {
+            color: false,
+            width: 100,
+        }
+    }
+}
+
+// a source line as displayed, with tabs expanded
+struct Line {
+    number: usize,
+    text: String,
+}
+
+impl Renderer {
+    // picks up the terminal width from `COLUMNS`, since there's no portable way
+    // to ask the terminal without extra dependencies
+    pub fn for_terminal(color: bool) -> Self {
+        let width = std::env::var("COLUMNS")
+            .ok()
+            .and_then(|columns| columns.parse().ok())
+            .unwrap_or(Self::default().width);
+
+        Self { color, width }
+    }
+
+    fn paint(&self, style: &str, text: &str) -> String {
+        if self.color {
+            format!("{}{}{}", style, text, RESET)
+        } else {
+            text.to_string()
+        }
+    }
+
+    fn severity_style(severity: Severity) -> &'static str {
+        match severity {
+            Severity::Error => RED,
+            Severity::Warning => YELLOW,
+        }
+    }
+
+    // a message without a code or source, like the count of errors at the end
+    pub fn summary(&self, severity: Severity, message: &str) -> String {
+        format!(
+            "{}{}\n",
+            self.paint(Self::severity_style(severity), &severity.to_string()),
+            self.paint(BOLD, &format!(": {}", message))
+        )
+    }
+
+    fn source_line(source: &str, number: usize) -> Line {
+        let text = source.lines().nth(number - 1).unwrap_or_default();
+
+        Line {
+            number,
+            text: text.replace('\t', &" ".repeat(TAB_WIDTH)),
+        }
+    }
+
+    // the display column of a byte offset, counting expanded tabs
+    fn display_column(source: &str, offset: usize) -> usize {
+        let offset = offset.min(source.len());
+        let line_start = source[..offset].rfind('\n').map_or(0, |i| i + 1);
+
+        source[line_start..offset]
+            .chars()
+            .map(|c| if c == '\t' { TAB_WIDTH } else { 1 })
+            .sum()
+    }
+
+    // the display columns a label covers on its first line, multi-line spans are
+    // underlined until the end of that line
+    fn label_columns(source: &str, label: &Label, line: &Line) -> (usize, usize) {
+        let start = Self::display_column(source, label.span.start);
+        let (start_line, _) = line_col(source, label.span.start);
+        let (end_line, _) = line_col(source, label.span.end);
+
+        let end = if end_line == start_line {
+            Self::display_column(source, label.span.end)
+        } else {
+            line.text.chars().count()
+        };
+
+        (start, end.max(start + 1))
+    }
+
+    // cuts the line so the labeled columns are visible within the width, returns
+    // the visible text and how many columns were cut from the left
+    fn fit_line(&self, text: &str, gutter: usize, first_column: usize) -> (String, usize) {
+        let available = self.width.saturating_sub(gutter + 3).max(20);
+        let chars: Vec<char> = text.chars().collect();
+
+        if chars.len() <= available {
+            return (text.to_string(), 0);
+        }
+
+        let margin = available / 4;
+        // skipping only a few columns isn't worth the "..."
+        let skip = match first_column.saturating_sub(margin).min(chars.len()) {
+            skip if skip <= 3 => 0,
+            skip => skip,
+        };
+        let take = available - 6;
+
+        let mut visible = String::new();
+        if skip > 0 {
+            visible.push_str("...");
+        }
+        visible.extend(chars.iter().skip(skip).take(take));
+        if skip + take < chars.len() {
+            visible.push_str("...");
+        }
+
+        // the leading "..." takes up 3 columns of its own
+        let shift = if skip > 0 { skip - 3 } else { 0 };
+        (visible, shift)
+    }
+
+    pub fn render(&self, diagnostic: &Diagnostic, file: &str, source: &str) -> String {
+        let severity_style = Self::severity_style(diagnostic.severity);
+        let mut output = format!(
+            "{}{}\n",
+            self.paint(
+                severity_style,
+                &format!("{}[{}]", diagnostic.severity, diagnostic.code)
+            ),
+            self.paint(BOLD, &format!(": {}", diagnostic.message))
+        );
+
+        let mut labels: Vec<(usize, &Label)> = diagnostic
+            .spans
+            .iter()
+            .map(|label| (line_col(source, label.span.start).0, label))
+            .collect();
+        labels.sort_by_key(|(line, label)| (*line, label.span.start));
+
+        let gutter = labels
+            .iter()
+            .map(|(line, _)| line.to_string().len())
+            .max()
+            .unwrap_or(0);
+        let pad = " ".repeat(gutter);
+        let bar = self.paint(BLUE, "|");
+
+        let location = diagnostic
+            .spans
+            .iter()
+            .find(|label| label.primary)
+            .or(diagnostic.spans.first());
+
+        match location {
+            Some(label) => {
+                let (line, column) = line_col(source, label.span.start);
+                output.push_str(&format!(
+                    "{}{} {}:{}:{}\n",
+                    pad,
+                    self.paint(BLUE, "-->"),
+                    file,
+                    line,
+                    column
+                ));
+            }
+            None => output.push_str(&format!("{}{} {}\n", pad, self.paint(BLUE, "-->"), file)),
+        }
+
+        if !labels.is_empty() {
+            output.push_str(&format!("{} {}\n", pad, bar));
+        }
+
+        let mut previous_line: Option<usize> = None;
+        let mut index = 0;
+        while index < labels.len() {
+            let number = labels[index].0;
+            let on_line: Vec<&Label> = labels[index..]
+                .iter()
+                .take_while(|(line, _)| *line == number)
+                .map(|(_, label)| *label)
+                .collect();
+            index += on_line.len();
+
+            if let Some(previous) = previous_line {
+                if number > previous + 1 {
+                    output.push_str(&format!("{}\n", self.paint(BLUE, "...")));
+                }
+            }
+            previous_line = Some(number);
+
+            let line = Self::source_line(source, number);
+            let first_column = Self::label_columns(source, on_line[0], &line).0;
+            let (text, shift) = self.fit_line(&line.text, gutter, first_column);
+
+            output.push_str(&format!(
+                "{} {} {}\n",
+                self.paint(BLUE, &format!("{:>gutter$}", line.number)),
+                bar,
+                text
+            ));
+
+            for label in on_line {
+                let (start, end) = Self::label_columns(source, label, &line);
+                let start = start.saturating_sub(shift);
+                let end = end.saturating_sub(shift).max(start + 1);
+
+                let (marker, style) = if label.primary {
+                    ("^", severity_style)
+                } else {
+                    ("-", BLUE)
+                };
+
+                let mut underline = marker.repeat(end - start);
+                if !label.message.is_empty() {
+                    underline.push(' ');
+                    underline.push_str(&label.message);
+                }
+
+                output.push_str(&format!(
+                    "{} {} {}{}\n",
+                    pad,
+                    bar,
+                    " ".repeat(start),
+                    self.paint(style, &underline)
+                ));
+            }
+        }
+
+        for note in &diagnostic.notes {
+            let kind = match note.kind {
+                NoteKind::Note => "note",
+                NoteKind::Help => "help",
+            };
+
+            output.push_str(&format!(
+                "{} {} {}: {}\n",
+                pad,
+                self.paint(BLUE, "="),
+                self.paint(
+                    if note.kind == NoteKind::Help {
+                        CYAN
+                    } else {
+                        BOLD
+                    },
+                    kind
+                ),
+                note.message
+            ));
+        }
+
+        output
+    }
+}
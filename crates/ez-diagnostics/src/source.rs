@@ -0,0 +1,292 @@
+use ez_lexer::Span;
+
+use crate::{Diagnostic, Note, NoteKind, Suggestion};
+
+// a file of the map, numbered in the order it was added, the entry is 0
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct FileId(pub usize);
+
+impl FileId {
+    pub const ENTRY: FileId = FileId(0);
+}
+
+// where an offset is, lines and columns start at 1
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Location {
+    pub file: FileId,
+    pub line: usize,
+    pub column: usize,
+}
+
+// where an offset is for an editor, lines from 0 and characters in UTF-16 code
+// units, the way the language server protocol counts them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    pub line: usize,
+    pub character: usize,
+}
+
+#[derive(Debug)]
+pub struct SourceFile {
+    pub id: FileId,
+    pub name: String,
+    pub source: String,
+    // the offset its spans start at
+    pub start: usize,
+    // the offset each line starts at, in the file
+    lines: Vec<usize>,
+}
+
+// the offsets of a file are from its start, add `start` for the ones of the map
+impl SourceFile {
+    fn new(id: FileId, name: String, source: String, start: usize) -> Self {
+        let lines = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(newline, _)| newline + 1))
+            .collect();
+
+        Self {
+            id,
+            name,
+            source,
+            start,
+            lines,
+        }
+    }
+
+    // the line an offset is on, from 0, and the offset that line starts at. An
+    // offset past the end or inside a character is moved back to one
+    fn line_of(&self, offset: usize) -> (usize, usize, usize) {
+        let mut offset = offset.min(self.source.len());
+        while !self.source.is_char_boundary(offset) {
+            offset -= 1;
+        }
+
+        let line = self.lines.partition_point(|&start| start <= offset) - 1;
+        (line, self.lines[line], offset)
+    }
+
+    // the text of a line from 0, without its newline
+    fn line(&self, line: usize) -> Option<(usize, &str)> {
+        let start = *self.lines.get(line)?;
+        let end = self
+            .lines
+            .get(line + 1)
+            .map_or(self.source.len(), |next| next - 1);
+        Some((start, &self.source[start..end]))
+    }
+
+    // lines and columns from 1, the columns counted in characters
+    pub fn line_col(&self, offset: usize) -> (usize, usize) {
+        let (line, start, offset) = self.line_of(offset);
+        let column = self.source[start..offset].chars().count() + 1;
+        (line + 1, column)
+    }
+
+    // the offset of a line and column from 1, the column after the last
+    // character of a line is its end
+    pub fn offset(&self, line: usize, column: usize) -> Option<usize> {
+        let (start, text) = self.line(line.checked_sub(1)?)?;
+        let column = column.checked_sub(1)?;
+        match text.char_indices().nth(column) {
+            Some((index, _)) => Some(start + index),
+            None if column == text.chars().count() => Some(start + text.len()),
+            None => None,
+        }
+    }
+
+    pub fn position(&self, offset: usize) -> Position {
+        let (line, start, offset) = self.line_of(offset);
+        let character = self.source[start..offset]
+            .chars()
+            .map(char::len_utf16)
+            .sum();
+        Position { line, character }
+    }
+
+    // editors send positions past the end of a line for its end, and in the
+    // middle of a character that takes two units for its start
+    pub fn offset_at(&self, position: Position) -> Option<usize> {
+        let (start, text) = self.line(position.line)?;
+        let mut units = 0;
+        for (index, c) in text.char_indices() {
+            units += c.len_utf16();
+            if units > position.character {
+                return Some(start + index);
+            }
+        }
+        Some(start + text.len())
+    }
+}
+
+// the files of a session. Each file gets a range of offsets of its own, so a
+// span says which file it's in without carrying the file around
+#[derive(Debug, Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // a map of a single file, like the one the repl compiles
+    pub fn single(name: impl Into<String>, source: impl Into<String>) -> Self {
+        let mut sources = Self::new();
+        sources.add(name, source);
+        sources
+    }
+
+    // gives back the offset the file starts at. Files are a byte apart, so a
+    // span at the end of one isn't at the start of the next
+    pub fn add(&mut self, name: impl Into<String>, source: impl Into<String>) -> usize {
+        let start = self
+            .files
+            .last()
+            .map_or(0, |file| file.start + file.source.len() + 1);
+
+        let id = FileId(self.files.len());
+        self.files
+            .push(SourceFile::new(id, name.into(), source.into(), start));
+        start
+    }
+
+    pub fn files(&self) -> &[SourceFile] {
+        &self.files
+    }
+
+    // the file the offset is in, the first file starts at 0 so there's always one
+    pub fn file(&self, offset: usize) -> &SourceFile {
+        self.files
+            .iter()
+            .rev()
+            .find(|file| file.start <= offset)
+            .expect("the map should have a file")
+    }
+
+    pub fn get(&self, id: FileId) -> &SourceFile {
+        &self.files[id.0]
+    }
+
+    pub fn locate(&self, offset: usize) -> Location {
+        let file = self.file(offset);
+        let (line, column) = file.line_col(offset - file.start);
+        Location {
+            file: file.id,
+            line,
+            column,
+        }
+    }
+
+    // the offset of a location, where `locate` would find it
+    pub fn offset(&self, location: Location) -> Option<usize> {
+        let file = self.files.get(location.file.0)?;
+        Some(file.start + file.offset(location.line, location.column)?)
+    }
+
+    pub fn position(&self, offset: usize) -> (FileId, Position) {
+        let file = self.file(offset);
+        (file.id, file.position(offset - file.start))
+    }
+
+    // the offset of a position in one of the files, like one an editor sent
+    pub fn offset_at(&self, file: FileId, position: Position) -> Option<usize> {
+        let file = self.files.get(file.0)?;
+        Some(file.start + file.offset_at(position)?)
+    }
+
+    // like `file.ez:3:5`
+    pub fn location(&self, span: Span) -> String {
+        let location = self.locate(span.start);
+        let file = self.get(location.file);
+        format!("{}:{}:{}", file.name, location.line, location.column)
+    }
+
+    // a diagnostic is shown in a single file, the one of its primary label. Its
+    // spans are made relative to that file, and labels in other files become
+    // notes saying where they are
+    pub fn localize(&self, diagnostic: &Diagnostic) -> (Diagnostic, &SourceFile) {
+        let location = diagnostic
+            .spans
+            .iter()
+            .find(|label| label.primary)
+            .or(diagnostic.spans.first());
+        let file = self.file(location.map_or(0, |label| label.span.start));
+        let end = file.start + file.source.len();
+
+        let mut localized = diagnostic.clone();
+        localized.spans.clear();
+        // an edit of another file can't be made from this one, its help stays
+        for note in &mut localized.notes {
+            note.suggestion = note.suggestion.take().and_then(|suggestion| {
+                let span = suggestion.span;
+                (file.start <= span.start && span.end <= end).then(|| Suggestion {
+                    span: Span::new(span.start - file.start, span.end - file.start),
+                    ..suggestion
+                })
+            });
+        }
+        let mut notes = vec![];
+        for label in &diagnostic.spans {
+            let span = label.span;
+            if file.start <= span.start && span.end <= end {
+                let mut label = label.clone();
+                label.span = Span::new(span.start - file.start, span.end - file.start);
+                localized.spans.push(label);
+            } else {
+                notes.push(Note {
+                    kind: NoteKind::Note,
+                    message: format!("{}, at {}", label.message, self.location(span)),
+                    suggestion: None,
+                });
+            }
+        }
+        localized.notes.splice(0..0, notes);
+
+        (localized, file)
+    }
+
+    // the files the suggestions change, with the suggestions made. Two edits
+    // of the same text can't both be made, the first one given is
+    pub fn apply<'s>(
+        &self,
+        suggestions: impl IntoIterator<Item = &'s Suggestion>,
+    ) -> Vec<(FileId, String)> {
+        let mut edits = compatible(suggestions);
+        edits.sort_by_key(|edit| edit.span.start);
+
+        let mut changed: Vec<(FileId, String)> = vec![];
+        // from the end of each file, so the spans before an edit stay right
+        for edit in edits.into_iter().rev() {
+            let file = self.file(edit.span.start);
+            if !changed.iter().any(|(id, _)| *id == file.id) {
+                changed.push((file.id, file.source.clone()));
+            }
+            let (_, text) = changed.iter_mut().find(|(id, _)| *id == file.id).unwrap();
+            let start = edit.span.start - file.start;
+            let end = edit.span.end - file.start;
+            text.replace_range(start..end, &edit.replacement);
+        }
+
+        changed.sort_by_key(|(id, _)| *id);
+        changed
+    }
+}
+
+// the suggestions that can all be made, in the order given. Of two edits of the
+// same text, the first one given is
+pub fn compatible<'s>(
+    suggestions: impl IntoIterator<Item = &'s Suggestion>,
+) -> Vec<&'s Suggestion> {
+    let mut edits: Vec<&Suggestion> = vec![];
+    for suggestion in suggestions {
+        let overlaps = edits.iter().any(|edit| {
+            suggestion.span.start < edit.span.end && edit.span.start < suggestion.span.end
+                || suggestion.span.start == edit.span.start
+        });
+        if !overlaps {
+            edits.push(suggestion);
+        }
+    }
+    edits
+}
@@ -0,0 +1,570 @@
+use std::{collections::HashMap, fmt, sync::OnceLock};
+
+pub use crate::symbol::Symbol;
+
+mod symbol;
+
+#[derive(Clone)]
+pub struct Lexer<'a> {
+    content: &'a str,
+    pos: usize,
+    // where the content starts among the files of a session, added to spans
+    offset: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+
+    pub fn to(self, other: Span) -> Span {
+        Span::new(self.start, other.end)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenKind {
+    Unknown,
+    Identifier,
+
+    // Keywords
+    Fn,
+    Mut,
+    If,
+    Else,
+    Return,
+    While,
+    Break,
+    Continue,
+    True,
+    False,
+    Enum,
+    Match,
+    For,
+    In,
+    As,
+    Try,
+    Catch,
+    Use,
+
+    // Primitives
+    Integer,
+    Float,
+    String,
+
+    // Symbols and Operators
+    LeftCurly,
+    RightCurly,
+    LeftParen,
+    RightParen,
+    LeftBracket,
+    RightBracket,
+    Dot,
+    DotDot,
+    Comma,
+    Colon,
+    Semi,
+    DeclAssign,
+    Assign,
+    PlusAssign,
+    MinusAssign,
+    TimesAssign,
+    DividedByAssign,
+    // starts an attribute, like `@bench`
+    At,
+
+    // Math Operators
+    Plus,
+    Minus,
+    Times,
+    DividedBy,
+
+    // Conditional Operators
+    Equals,
+    Not,
+    NotEquals,
+    GreaterThan,
+    GreaterOrEquals,
+    LowerThan,
+    LowerOrEquals,
+    Or,
+    And,
+
+    // Bitwise Operator
+    BitAnd,
+    BitOr,
+    BitXor,
+    BitNot,
+}
+
+const KEYWORDS: [(&str, TokenKind); 18] = [
+    ("fn", TokenKind::Fn),
+    ("mut", TokenKind::Mut),
+    ("if", TokenKind::If),
+    ("else", TokenKind::Else),
+    ("return", TokenKind::Return),
+    ("while", TokenKind::While),
+    ("break", TokenKind::Break),
+    ("continue", TokenKind::Continue),
+    ("true", TokenKind::True),
+    ("false", TokenKind::False),
+    ("enum", TokenKind::Enum),
+    ("match", TokenKind::Match),
+    ("for", TokenKind::For),
+    ("in", TokenKind::In),
+    ("as", TokenKind::As),
+    ("try", TokenKind::Try),
+    ("catch", TokenKind::Catch),
+    ("use", TokenKind::Use),
+];
+
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            TokenKind::Unknown => "unknown token",
+            TokenKind::Identifier => "identifier",
+            TokenKind::Integer => "integer",
+            TokenKind::Float => "float",
+            TokenKind::String => "string",
+            kind => {
+                let keyword = KEYWORDS
+                    .iter()
+                    .find(|(_, k)| k == kind)
+                    .map(|(word, _)| *word);
+                let symbol = Lexer::symbol_table()
+                    .iter()
+                    .find(|(_, token)| token.kind == *kind)
+                    .map(|(symbol, _)| symbol.as_str());
+
+                return match keyword.or(symbol) {
+                    Some(text) => write!(f, "`{}`", text),
+                    None => write!(f, "{:?}", kind),
+                };
+            }
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub value: TokenValue,
+    pub span: Span,
+}
+
+// the text of a token. Names, keywords and operators are interned, a program
+// only has so many of them, while the text of literals and of what isn't a
+// token is kept by the token, so a process lexing programs for long, like the
+// language server, doesn't keep every number and string it ever saw
+#[derive(Clone, PartialEq)]
+pub enum TokenValue {
+    Symbol(Symbol),
+    Text(Box<str>),
+}
+
+impl TokenValue {
+    pub fn as_str(&self) -> &str {
+        match self {
+            TokenValue::Symbol(symbol) => symbol.as_str(),
+            TokenValue::Text(text) => text,
+        }
+    }
+
+    // the name, interned when it's the text of a literal
+    pub fn symbol(&self) -> Symbol {
+        match self {
+            TokenValue::Symbol(symbol) => *symbol,
+            TokenValue::Text(text) => Symbol::intern(text),
+        }
+    }
+}
+
+impl std::ops::Deref for TokenValue {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl PartialEq<str> for TokenValue {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for TokenValue {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl PartialEq<TokenValue> for Symbol {
+    fn eq(&self, other: &TokenValue) -> bool {
+        match other {
+            TokenValue::Symbol(symbol) => self == symbol,
+            TokenValue::Text(text) => self.as_str() == &**text,
+        }
+    }
+}
+
+impl fmt::Display for TokenValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+// like the string it stands for, the same as a symbol
+impl fmt::Debug for TokenValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl<'a> Lexer<'a> {
+    pub fn new(content: &'a str) -> Self {
+        Self {
+            content,
+            pos: 0,
+            offset: 0,
+        }
+    }
+
+    pub fn with_offset(mut self, offset: usize) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    // positions are byte offsets into the content, so they can be used as spans
+    fn current(&self) -> Option<char> {
+        self.content[self.pos..].chars().next()
+    }
+
+    fn advance(&mut self) -> Option<char> {
+        if let Some(c) = self.current() {
+            self.pos += c.len_utf8();
+        }
+
+        self.current()
+    }
+
+    fn span_from(&self, start: usize) -> Span {
+        Span::new(self.offset + start, self.offset + self.pos)
+    }
+
+    // comments run to the end of the line, the newline isn't part of them
+    fn at_comment(&self) -> bool {
+        self.content[self.pos..].starts_with("//")
+    }
+
+    fn skip_comment(&mut self) {
+        while self.current().is_some_and(|c| c != '\n') {
+            self.advance();
+        }
+    }
+
+    fn is_number_token(c: char) -> bool {
+        c.is_ascii_digit() || c == '.'
+    }
+
+    fn tokenize_number(&mut self) -> Token {
+        let start = self.pos;
+        let mut kind = TokenKind::Integer;
+        loop {
+            if self.current() == Some('.') {
+                kind = TokenKind::Float;
+            }
+
+            let Some(next) = self.advance() else { break };
+            // `0..10` is a range, not a float
+            if !Self::is_number_token(next) || self.content[self.pos..].starts_with("..") {
+                break;
+            }
+        }
+
+        Token {
+            kind,
+            value: self.text_from(start),
+            span: self.span_from(start),
+        }
+    }
+
+    fn text_from(&self, start: usize) -> TokenValue {
+        TokenValue::Text(self.content[start..self.pos].into())
+    }
+
+    fn tokenize_unknown(&mut self) -> Token {
+        let start = self.pos;
+        while self.advance().is_some_and(|c| !c.is_whitespace()) {}
+
+        Token {
+            kind: TokenKind::Unknown,
+            value: self.text_from(start),
+            span: self.span_from(start),
+        }
+    }
+
+    fn tokenize_identifier(&mut self) -> Token {
+        let start = self.pos;
+        while self
+            .advance()
+            .is_some_and(|c| c.is_ascii_alphanumeric() || c == '_')
+        {}
+
+        let value = &self.content[start..self.pos];
+        let kind = match KEYWORDS.iter().find(|(word, _)| *word == value) {
+            Some((_, kind)) => kind.clone(),
+            None => TokenKind::Identifier,
+        };
+
+        // names are interned, identifiers are the same few over and over
+        Token {
+            kind,
+            value: TokenValue::Symbol(Symbol::intern(value)),
+            span: self.span_from(start),
+        }
+    }
+
+    fn tokenize_string(&mut self) -> Token {
+        let start = self.pos;
+        // if the string is not closed, it will return an Unknown token
+        let mut kind = TokenKind::Unknown;
+        while let Some(c) = self.advance() {
+            if c == '"' {
+                kind = TokenKind::String;
+                break;
+            }
+        }
+
+        // without the quotes
+        let value = self.text_from(start + 1);
+        if kind == TokenKind::String {
+            self.advance();
+        }
+
+        Token {
+            kind,
+            value,
+            span: self.span_from(start),
+        }
+    }
+
+    fn symbol_table() -> &'static HashMap<String, Token> {
+        static TABLE: OnceLock<HashMap<String, Token>> = OnceLock::new();
+        TABLE.get_or_init(|| {
+            let token_pairs = [
+                // Symbols and Operators
+                ("{", TokenKind::LeftCurly),
+                ("}", TokenKind::RightCurly),
+                ("(", TokenKind::LeftParen),
+                (")", TokenKind::RightParen),
+                ("[", TokenKind::LeftBracket),
+                ("]", TokenKind::RightBracket),
+                (".", TokenKind::Dot),
+                ("..", TokenKind::DotDot),
+                (",", TokenKind::Comma),
+                (":", TokenKind::Colon),
+                (";", TokenKind::Semi),
+                (":=", TokenKind::DeclAssign),
+                ("=", TokenKind::Assign),
+                ("+=", TokenKind::PlusAssign),
+                ("-=", TokenKind::MinusAssign),
+                ("*=", TokenKind::TimesAssign),
+                ("/=", TokenKind::DividedByAssign),
+                ("@", TokenKind::At),
+                // Math Operators
+                ("+", TokenKind::Plus),
+                ("-", TokenKind::Minus),
+                ("*", TokenKind::Times),
+                ("/", TokenKind::DividedBy),
+                // Conditional Operators
+                ("==", TokenKind::Equals),
+                ("!", TokenKind::Not),
+                ("!=", TokenKind::NotEquals),
+                ("||", TokenKind::Or),
+                ("&&", TokenKind::And),
+                (">", TokenKind::GreaterThan),
+                (">=", TokenKind::GreaterOrEquals),
+                ("<", TokenKind::LowerThan),
+                ("<=", TokenKind::LowerOrEquals),
+                // Bitwise Operators
+                ("&", TokenKind::BitAnd),
+                ("|", TokenKind::BitOr),
+                ("^", TokenKind::BitXor),
+                ("~", TokenKind::BitNot),
+            ];
+            let mut table = HashMap::new();
+
+            for pair in token_pairs {
+                table.insert(
+                    pair.0.to_string(),
+                    Token {
+                        kind: pair.1,
+                        value: TokenValue::Symbol(Symbol::intern(pair.0)),
+                        span: Span::default(),
+                    },
+                );
+            }
+
+            table
+        })
+    }
+
+    // the keywords and symbols with the kinds of their tokens, for what has to
+    // agree with the lexer on them, like the grammar for tree-sitter
+    pub fn keywords() -> impl Iterator<Item = (&'static str, &'static TokenKind)> {
+        KEYWORDS.iter().map(|(word, kind)| (*word, kind))
+    }
+
+    // sorted by their text, the table has no order of its own
+    pub fn symbols() -> Vec<(&'static str, &'static TokenKind)> {
+        let mut symbols: Vec<_> = Self::symbol_table()
+            .iter()
+            .map(|(symbol, token)| (symbol.as_str(), &token.kind))
+            .collect();
+        symbols.sort_by_key(|(symbol, _)| *symbol);
+        symbols
+    }
+
+    fn is_symbol_token(c: char) -> bool {
+        static SYMBOL_CHARS: OnceLock<String> = OnceLock::new();
+        let symbol_chars = SYMBOL_CHARS.get_or_init(|| {
+            Self::symbol_table()
+                .keys()
+                .map(|s| s.as_str())
+                .collect::<Vec<&str>>()
+                .join("")
+        });
+
+        symbol_chars.chars().find(|&search| search == c).is_some()
+    }
+
+    fn tokenize_symbol(&mut self) -> Token {
+        let start = self.pos;
+        let mut token = self.match_symbol();
+        token.span = self.span_from(start);
+
+        token
+    }
+
+    fn match_symbol(&mut self) -> Token {
+        let c = self.current().unwrap();
+        let table = Self::symbol_table();
+
+        let token = match c {
+            ':' | '=' | '>' | '<' | '!' | '+' | '-' | '*' | '/' => {
+                let regular = &table[&c.to_string()];
+
+                let Some(next) = self.advance() else {
+                    return regular.to_owned();
+                };
+
+                if next != '=' {
+                    return regular.to_owned();
+                }
+
+                table[&format!("{}=", c)].clone()
+            }
+
+            '&' | '|' | '.' => {
+                let regular = &table[&c.to_string()];
+
+                let Some(next) = self.advance() else {
+                    return regular.to_owned();
+                };
+
+                if next != c {
+                    return regular.to_owned();
+                }
+
+                table[&format!("{}{}", c, c)].clone()
+            }
+
+            c => {
+                let char_str = c.to_string();
+                if table.contains_key(&char_str) {
+                    table[&char_str].to_owned()
+                } else {
+                    Token {
+                        kind: TokenKind::Unknown,
+                        value: TokenValue::Text(c.to_string().into()),
+                        span: Span::default(),
+                    }
+                }
+            }
+        };
+
+        self.advance();
+
+        token
+    }
+
+    pub fn tokenize(&mut self) -> Option<Token> {
+        loop {
+            let c = self.current()?;
+            if c.is_whitespace() {
+                self.advance();
+                continue;
+            }
+
+            if self.at_comment() {
+                self.skip_comment();
+                continue;
+            }
+
+            // numbers can contain a dot, but they can't start with one
+            if c.is_ascii_digit() {
+                return Some(self.tokenize_number());
+            }
+
+            if c.is_ascii_alphabetic() || c == '_' {
+                return Some(self.tokenize_identifier());
+            }
+
+            if c == '"' {
+                return Some(self.tokenize_string());
+            }
+
+            if Self::is_symbol_token(c) {
+                return Some(self.tokenize_symbol());
+            }
+
+            return Some(self.tokenize_unknown());
+        }
+    }
+
+    // the comments of the content, the tokens around them are skipped. `///`
+    // ones are documentation, for what comes after them
+    pub fn comments(mut self) -> Vec<Span> {
+        let mut comments = vec![];
+        while let Some(c) = self.current() {
+            if c.is_whitespace() {
+                self.advance();
+            } else if self.at_comment() {
+                let start = self.pos;
+                self.skip_comment();
+                comments.push(self.span_from(start));
+            } else {
+                self.tokenize();
+            }
+        }
+
+        comments
+    }
+}
+
+impl Iterator for Lexer<'_> {
+    type Item = Token;
+
+    fn next(&mut self) -> Option<Token> {
+        self.tokenize()
+    }
+}
@@ -0,0 +1,98 @@
+use std::fmt;
+
+// the syntax of an edition never changes once it's out, programs written for
+// it keep parsing. New syntax starts as a feature a program has to ask for,
+// and becomes part of the next edition once it's settled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Edition {
+    #[default]
+    E2024,
+}
+
+pub const EDITIONS: [Edition; 1] = [Edition::E2024];
+
+impl Edition {
+    pub fn parse(name: &str) -> Option<Edition> {
+        EDITIONS.into_iter().find(|edition| edition.name() == name)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Edition::E2024 => "2024",
+        }
+    }
+}
+
+impl fmt::Display for Edition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+// syntax that's still being tried out, it's only parsed when it's enabled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Feature {
+    // a `,` after the last item of a list, a map, the arguments of a call, the
+    // parameters of a function or the fields of a variant, like enums have
+    TrailingCommas,
+}
+
+pub const FEATURES: [Feature; 1] = [Feature::TrailingCommas];
+
+impl Feature {
+    // features can be spelled with dashes too, like lints
+    pub fn parse(name: &str) -> Option<Feature> {
+        let name = name.replace('-', "_");
+        FEATURES.into_iter().find(|feature| feature.name() == name)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Feature::TrailingCommas => "trailing_commas",
+        }
+    }
+
+    // what the syntax is, for the error when it isn't enabled
+    pub fn description(self) -> &'static str {
+        match self {
+            Feature::TrailingCommas => "trailing commas",
+        }
+    }
+
+    // the edition it's part of without being asked for, none until it's settled
+    pub fn stable_in(self) -> Option<Edition> {
+        match self {
+            Feature::TrailingCommas => None,
+        }
+    }
+}
+
+impl fmt::Display for Feature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+// what a file is parsed as, from the command line or the project
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ParserOptions {
+    pub edition: Edition,
+    pub features: Vec<Feature>,
+}
+
+impl ParserOptions {
+    pub fn enabled(&self, feature: Feature) -> bool {
+        feature
+            .stable_in()
+            .is_some_and(|edition| edition <= self.edition)
+            || self.features.contains(&feature)
+    }
+
+    // the options written the same way every time, to tell whether they changed
+    pub fn key(&self) -> String {
+        let mut features: Vec<&str> = self.features.iter().map(|feature| feature.name()).collect();
+        features.sort();
+        features.dedup();
+        format!("edition={} features={}", self.edition, features.join(","))
+    }
+}
@@ -0,0 +1,747 @@
+use ez_ast::{Arena, BaseType, Expr, Pattern, Program, ValueExpr};
+use ez_diagnostics::Diagnostic;
+use ez_lexer::{Lexer, Span, TokenKind};
+
+use crate::{ParseError, Parser, ParserOptions};
+
+// lines longer than this get their lists and arguments one per line
+const WIDTH: usize = 100;
+const INDENT: &str = "    ";
+
+// prints a program back the canonical way, the same program formats the same
+// whatever it looked like. What the tree doesn't keep comes from the source:
+// numbers are written like they were, a blank line between two statements is
+// kept, and short blocks that were on one line stay there. Comments stay above
+// the statements they were above, or after the ones they were after
+pub struct Formatter<'a> {
+    source: &'a str,
+    comments: Vec<Span>,
+}
+
+#[derive(Debug)]
+pub enum FormatError {
+    Parse(ParseError),
+    // a comment inside an expression, there's no line of its own to keep it on
+    Comment(Span),
+}
+
+impl From<ParseError> for FormatError {
+    fn from(error: ParseError) -> Self {
+        FormatError::Parse(error)
+    }
+}
+
+impl From<FormatError> for Diagnostic {
+    fn from(error: FormatError) -> Self {
+        match error {
+            FormatError::Parse(error) => error.into(),
+            FormatError::Comment(span) => {
+                Diagnostic::error("E0007", "the comment would be lost by formatting")
+                    .with_label(span, "inside an expression")
+                    .with_help("move it above the statement")
+            }
+        }
+    }
+}
+
+// the source parsed and printed back
+pub fn format(source: &str) -> Result<String, FormatError> {
+    format_with(source, ParserOptions::default())
+}
+
+// `format` for a file parsed with features of its own, a trailing comma
+// is dropped like the formatter drops it from an enum
+pub fn format_with(source: &str, options: ParserOptions) -> Result<String, FormatError> {
+    let arena = Arena::new();
+    let program = Parser::new(source, &arena)
+        .with_options(options)
+        .parse_program()?;
+    let formatted = Formatter::new(source).format(&program);
+
+    // the comments are all printed again, or one of them had nowhere to go
+    let mut kept: Vec<&str> = Lexer::new(&formatted)
+        .comments()
+        .into_iter()
+        .map(|span| &formatted[span.start..span.end])
+        .collect();
+    for comment in Lexer::new(source).comments() {
+        let text = &source[comment.start..comment.end];
+        match kept.iter().position(|kept| *kept == text) {
+            Some(i) => _ = kept.swap_remove(i),
+            None => return Err(FormatError::Comment(comment)),
+        }
+    }
+
+    Ok(formatted)
+}
+
+fn indent(depth: usize) -> String {
+    INDENT.repeat(depth)
+}
+
+// the column text starting at `column` ends at, its lines after the first are
+// indented already
+fn end_column(column: usize, text: &str) -> usize {
+    match text.rfind('\n') {
+        Some(newline) => text.len() - newline - 1,
+        None => column + text.len(),
+    }
+}
+
+fn operator(kind: &TokenKind) -> &'static str {
+    match kind {
+        TokenKind::Plus => "+",
+        TokenKind::Minus => "-",
+        TokenKind::Times => "*",
+        TokenKind::DividedBy => "/",
+        TokenKind::Equals => "==",
+        TokenKind::NotEquals => "!=",
+        TokenKind::GreaterThan => ">",
+        TokenKind::GreaterOrEquals => ">=",
+        TokenKind::LowerThan => "<",
+        TokenKind::LowerOrEquals => "<=",
+        TokenKind::Or => "||",
+        TokenKind::And => "&&",
+        TokenKind::BitAnd => "&",
+        TokenKind::BitOr => "|",
+        TokenKind::BitXor => "^",
+        TokenKind::Not => "!",
+        TokenKind::BitNot => "~",
+        kind => unreachable!("{} isn't an operator", kind),
+    }
+}
+
+fn basetype(basetype: &BaseType) -> String {
+    match basetype {
+        BaseType::Function {
+            params,
+            return_type,
+        } => {
+            let params: Vec<String> = params
+                .iter()
+                .map(|param| {
+                    format!(
+                        "{}: {}",
+                        param.identifier.name,
+                        self::basetype(&param.basetype)
+                    )
+                })
+                .collect();
+
+            match **return_type {
+                BaseType::Void => format!("fn ({})", params.join(", ")),
+                ref return_type => {
+                    format!("fn ({}) {}", params.join(", "), self::basetype(return_type))
+                }
+            }
+        }
+        BaseType::List(item) => format!("[{}]", self::basetype(item)),
+        BaseType::Map(key, value) => {
+            format!("[{}: {}]", self::basetype(key), self::basetype(value))
+        }
+        basetype => basetype.to_string(),
+    }
+}
+
+fn pattern(pattern: &Pattern, source: &str) -> String {
+    match pattern {
+        Pattern::Wildcard(_) => "_".to_string(),
+        Pattern::Binding(identifier) => identifier.name.to_string(),
+        Pattern::Int { span, .. } => source[span.start..span.end].to_string(),
+        Pattern::String { value, .. } => format!("\"{}\"", value),
+        Pattern::Bool { value, .. } => value.to_string(),
+        Pattern::Variant {
+            enum_name,
+            variant,
+            fields: [],
+            ..
+        } => format!("{}.{}", enum_name.name, variant.name),
+        Pattern::Variant {
+            enum_name,
+            variant,
+            fields,
+            ..
+        } => {
+            let fields: Vec<String> = fields
+                .iter()
+                .map(|field| self::pattern(field, source))
+                .collect();
+            format!("{}.{}({})", enum_name.name, variant.name, fields.join(", "))
+        }
+    }
+}
+
+// values that can't be the operand of a binary operator without parentheses
+fn binary_precedence(value: &ValueExpr) -> Option<u8> {
+    match value {
+        ValueExpr::Binary { operator, .. } => Parser::precedence(operator),
+        _ => None,
+    }
+}
+
+impl<'a> Formatter<'a> {
+    pub fn new(source: &'a str) -> Self {
+        Self {
+            source,
+            comments: Lexer::new(source).comments(),
+        }
+    }
+
+    fn text(&self, span: Span) -> &str {
+        &self.source[span.start..span.end]
+    }
+
+    fn on_one_line(&self, span: Span) -> bool {
+        !self.text(span).contains('\n')
+    }
+
+    pub fn format(&self, program: &Program) -> String {
+        let mut out = String::new();
+        let mut previous = None;
+        for (i, using) in program.uses.iter().enumerate() {
+            self.comments_before(using.span.start, previous, 0, &mut out);
+            previous = Some(using.span.end);
+
+            let path: Vec<&str> = using
+                .path
+                .iter()
+                .map(|identifier| identifier.name.as_str())
+                .collect();
+            out.push_str(&format!("use {};", path.join(".")));
+
+            let next = match program.uses.get(i + 1) {
+                Some(next) => Some(next.span.start),
+                None => program.body.first().map(|expr| self.start(expr)),
+            };
+            if let Some(comment) = self.comment_after(using.span.end, next) {
+                out.push(' ');
+                out.push_str(self.text(comment));
+                previous = Some(comment.end);
+            }
+            out.push('\n');
+        }
+
+        if !program.uses.is_empty() && !program.body.is_empty() {
+            out.push('\n');
+        }
+
+        self.statements(program.body, 0, &mut out);
+        if program.body.is_empty() {
+            self.comments_after(previous.unwrap_or(0), 0, &mut out);
+        }
+        out
+    }
+
+    // where a statement starts, a mutable declaration's span starts at its name
+    fn start(&self, expr: &Expr) -> usize {
+        let start = expr.span().start;
+        match expr {
+            Expr::Declaration { mutable: true, .. } => {
+                self.source[..start].trim_end().len() - "mut".len()
+            }
+            _ => start,
+        }
+    }
+
+    // only whitespace, or the semicolons ending statements
+    fn is_blank(&self, start: usize, end: usize) -> bool {
+        self.source[start..end]
+            .chars()
+            .all(|c| c.is_whitespace() || c == ';')
+    }
+
+    // a blank line where there was at least one since the previous line printed
+    fn separate(&self, previous: Option<usize>, start: usize, out: &mut String) {
+        if previous.is_some_and(|end| self.source[end..start].matches('\n').count() > 1) {
+            out.push('\n');
+        }
+    }
+
+    // a comment on a line of its own, or after the brace opening a block, the
+    // others are after a statement and printed with it
+    fn is_own_line(&self, comment: Span) -> bool {
+        let line = self.source[..comment.start]
+            .rsplit('\n')
+            .next()
+            .unwrap_or("");
+        let line = line.trim();
+        line.is_empty() || line.ends_with('{')
+    }
+
+    // the comments on the lines right above a statement starting at `start`,
+    // without the ones printed already, up to `previous`. Gives back where the
+    // last one printed ends
+    fn comments_before(
+        &self,
+        start: usize,
+        previous: Option<usize>,
+        depth: usize,
+        out: &mut String,
+    ) -> Option<usize> {
+        let mut end = start;
+        let mut comments = vec![];
+        for comment in self
+            .comments
+            .iter()
+            .rev()
+            .filter(|comment| comment.end <= start)
+        {
+            if !self.is_blank(comment.end, end)
+                || !self.is_own_line(*comment)
+                || previous.is_some_and(|p| comment.start < p)
+            {
+                break;
+            }
+            comments.push(*comment);
+            end = comment.start;
+        }
+
+        let mut previous = previous;
+        for comment in comments.into_iter().rev() {
+            previous = self.comment(comment, previous, depth, out);
+        }
+        previous
+    }
+
+    fn comment(
+        &self,
+        comment: Span,
+        previous: Option<usize>,
+        depth: usize,
+        out: &mut String,
+    ) -> Option<usize> {
+        self.separate(previous, comment.start, out);
+        out.push_str(&indent(depth));
+        out.push_str(self.text(comment));
+        out.push('\n');
+        Some(comment.end)
+    }
+
+    // the comment after a statement ending at `end`, on its line. Not when the
+    // block it's in was closed first, or the next statement started
+    fn comment_after(&self, end: usize, next: Option<usize>) -> Option<Span> {
+        let comment = self.comments.iter().find(|comment| comment.start >= end)?;
+        let gap = &self.source[end..comment.start];
+        let before_next = next.is_none_or(|next| comment.start < next);
+        (!gap.contains(['\n', '}']) && before_next).then_some(*comment)
+    }
+
+    // one a line, with a blank line where there was at least one, and the
+    // comments between them
+    fn statements(&self, body: &[Expr], depth: usize, out: &mut String) {
+        let mut previous = None;
+        for (i, expr) in body.iter().enumerate() {
+            let start = self.start(expr);
+            previous = self.comments_before(start, previous, depth, out);
+            self.separate(previous, start, out);
+
+            out.push_str(&indent(depth));
+            out.push_str(&self.statement(expr, depth));
+            previous = Some(expr.span().end);
+
+            let next = body.get(i + 1).map(|next| self.start(next));
+            if let Some(comment) = self.comment_after(expr.span().end, next) {
+                out.push(' ');
+                out.push_str(self.text(comment));
+                previous = Some(comment.end);
+            }
+            out.push('\n');
+        }
+
+        // the ones after the last statement, before the block ends
+        if let Some(end) = previous {
+            self.comments_after(end, depth, out);
+        }
+    }
+
+    // the comments from `end` until something else than a comment
+    fn comments_after(&self, end: usize, depth: usize, out: &mut String) {
+        let mut previous = Some(end);
+        for comment in self.comments.iter().filter(|comment| comment.start >= end) {
+            if !previous.is_some_and(|end| self.is_blank(end, comment.start)) {
+                break;
+            }
+            previous = self.comment(*comment, previous, depth, out);
+        }
+    }
+
+    fn block(&self, body: &[Expr], depth: usize) -> String {
+        if body.is_empty() {
+            return "{}".to_string();
+        }
+
+        let mut out = "{\n".to_string();
+        self.statements(body, depth + 1, &mut out);
+        out.push_str(&indent(depth));
+        out.push('}');
+        out
+    }
+
+    // `{ statement }` when the block was written on one line and still fits,
+    // `column` is where it starts
+    fn short_block(&self, body: &[Expr], span: Span, depth: usize, column: usize) -> String {
+        if let [expr] = body {
+            let statement = self.statement(expr, depth + 1);
+            let short = format!("{{ {} }}", statement);
+            if self.on_one_line(span) && !statement.contains('\n') && column + short.len() <= WIDTH
+            {
+                return short;
+            }
+        }
+
+        self.block(body, depth)
+    }
+
+    fn statement(&self, expr: &Expr, depth: usize) -> String {
+        let column = depth * INDENT.len();
+        match expr {
+            Expr::Declaration {
+                identifier,
+                mutable,
+                basetype,
+                value,
+                attributes,
+            } => {
+                let mut out = String::new();
+                for attribute in attributes.iter() {
+                    out.push_str(&format!("@{}\n{}", attribute.name, indent(depth)));
+                }
+                if *mutable {
+                    out.push_str("mut ");
+                }
+                out.push_str(&identifier.name);
+                if let Some(basetype) = basetype {
+                    out.push(' ');
+                    out.push_str(&self::basetype(basetype));
+                }
+
+                match value {
+                    Some(value) => {
+                        out.push_str(" := ");
+                        out.push_str(&self.value(value, depth, column + out.len()));
+                        self.terminate(value, &mut out);
+                    }
+                    None => out.push(';'),
+                }
+                out
+            }
+
+            Expr::Assign {
+                target,
+                operator,
+                value,
+            } => {
+                let mut out = self.value(target, depth, column);
+                match operator {
+                    Some(operator) => out.push_str(&format!(" {}= ", self::operator(operator))),
+                    None => out.push_str(" = "),
+                }
+                out.push_str(&self.value(value, depth, end_column(column, &out)));
+                out.push(';');
+                out
+            }
+
+            Expr::Return { value: None, .. } => "return;".to_string(),
+            Expr::Return {
+                value: Some(value), ..
+            } => format!("return {};", self.value(value, depth, column + 7)),
+
+            Expr::If {
+                condition,
+                body,
+                else_body,
+                span,
+            } => {
+                let mut out = format!("if {} ", self.value(condition, depth, column + 3));
+                match else_body {
+                    None => out.push_str(&self.short_block(
+                        body,
+                        *span,
+                        depth,
+                        end_column(column, &out),
+                    )),
+                    Some(else_body) => {
+                        out.push_str(&self.block(body, depth));
+                        out.push_str(" else ");
+                        match else_body {
+                            [else_if @ Expr::If { .. }] => {
+                                out.push_str(&self.statement(else_if, depth))
+                            }
+                            else_body => out.push_str(&self.block(else_body, depth)),
+                        }
+                    }
+                }
+                out
+            }
+
+            Expr::While {
+                condition,
+                body,
+                span,
+            } => {
+                let out = format!("while {} ", self.value(condition, depth, column + 6));
+                let block = self.short_block(body, *span, depth, end_column(column, &out));
+                out + &block
+            }
+
+            Expr::For {
+                binding,
+                start,
+                end,
+                body,
+                span,
+            } => {
+                let mut out = format!("for {} in ", binding.name);
+                out.push_str(&self.value(start, depth, end_column(column, &out)));
+                if let Some(end) = end {
+                    out.push_str("..");
+                    out.push_str(&self.value(end, depth, end_column(column, &out)));
+                }
+                out.push(' ');
+                let block = self.short_block(body, *span, depth, end_column(column, &out));
+                out + &block
+            }
+
+            Expr::Break(_) => "break;".to_string(),
+            Expr::Continue(_) => "continue;".to_string(),
+
+            Expr::Enum {
+                identifier,
+                variants,
+                span,
+            } => {
+                let variants: Vec<String> = variants
+                    .iter()
+                    .map(|variant| {
+                        if variant.payload.is_empty() {
+                            return variant.identifier.name.to_string();
+                        }
+
+                        let payload: Vec<String> =
+                            variant.payload.iter().map(self::basetype).collect();
+                        format!("{}({})", variant.identifier.name, payload.join(", "))
+                    })
+                    .collect();
+
+                if variants.is_empty() {
+                    return format!("enum {} {{}}", identifier.name);
+                }
+
+                let short = format!("enum {} {{ {} }}", identifier.name, variants.join(", "));
+                if self.on_one_line(*span) && column + short.len() <= WIDTH {
+                    return short;
+                }
+
+                let mut out = format!("enum {} {{\n", identifier.name);
+                for variant in variants {
+                    out.push_str(&format!("{}{},\n", indent(depth + 1), variant));
+                }
+                out.push_str(&indent(depth));
+                out.push('}');
+                out
+            }
+
+            Expr::Match { value, arms, .. } => {
+                let mut out = format!("match {} {{\n", self.value(value, depth, column + 6));
+                for arm in arms.iter() {
+                    let pattern = self::pattern(&arm.pattern, self.source);
+                    let arm_column = (depth + 1) * INDENT.len() + pattern.len() + 1;
+                    let body = self.short_block(arm.body, arm.span, depth + 1, arm_column);
+                    out.push_str(&format!("{}{} {}\n", indent(depth + 1), pattern, body));
+                }
+                out.push_str(&indent(depth));
+                out.push('}');
+                out
+            }
+
+            Expr::Block { body, .. } => self.block(body, depth),
+
+            Expr::Try {
+                body,
+                error,
+                handler,
+                ..
+            } => format!(
+                "try {} catch {} {}",
+                self.block(body, depth),
+                error.name,
+                self.block(handler, depth)
+            ),
+
+            Expr::Test { name, body, .. } => {
+                format!("test \"{}\" {}", name, self.block(body, depth))
+            }
+
+            Expr::Value(value) => {
+                let mut out = self.value(value, depth, column);
+                self.terminate(value, &mut out);
+                out
+            }
+        }
+    }
+
+    // functions end with their curly, everything else with a semicolon
+    fn terminate(&self, value: &ValueExpr, out: &mut String) {
+        if !matches!(value, ValueExpr::Function { .. }) {
+            out.push(';');
+        }
+    }
+
+    // in parentheses when it would be parsed differently without
+    fn operand(&self, value: &ValueExpr, parens: bool, depth: usize, column: usize) -> String {
+        if parens {
+            return format!("({})", self.value(value, depth, column + 1));
+        }
+
+        self.value(value, depth, column)
+    }
+
+    // what calls and fields are applied to, anything but a primary needs
+    // parentheses, and so do numbers, their dot would be read as part of them
+    fn postfix(&self, value: &ValueExpr, depth: usize, column: usize) -> String {
+        let parens = matches!(
+            value,
+            ValueExpr::Binary { .. }
+                | ValueExpr::Unary { .. }
+                | ValueExpr::Cast { .. }
+                | ValueExpr::Int { .. }
+                | ValueExpr::Float { .. }
+        );
+        self.operand(value, parens, depth, column)
+    }
+
+    // `open` and `close` around the items, on one line when they fit and only
+    // the last one spans lines, like a function given last. One a line
+    // otherwise
+    fn sequence<T>(
+        &self,
+        (open, close): (&str, &str),
+        items: &[T],
+        depth: usize,
+        column: usize,
+        item: impl Fn(&T, usize, usize) -> String,
+    ) -> String {
+        let mut flat = open.to_string();
+        let mut broken_early = false;
+        for (i, value) in items.iter().enumerate() {
+            if i > 0 {
+                flat.push_str(", ");
+            }
+
+            let text = item(value, depth, end_column(column, &flat));
+            broken_early |= text.contains('\n') && i + 1 < items.len();
+            flat.push_str(&text);
+        }
+        flat.push_str(close);
+
+        let first_line = flat.lines().next().unwrap_or_default();
+        if items.is_empty() || (!broken_early && column + first_line.len() <= WIDTH) {
+            return flat;
+        }
+
+        let mut out = format!("{}\n", open);
+        for (i, value) in items.iter().enumerate() {
+            out.push_str(&indent(depth + 1));
+            out.push_str(&item(value, depth + 1, (depth + 1) * INDENT.len()));
+            if i + 1 < items.len() {
+                out.push(',');
+            }
+            out.push('\n');
+        }
+        out.push_str(&indent(depth));
+        out.push_str(close);
+        out
+    }
+
+    // `column` is where the value starts, for wrapping, `depth` how deep the
+    // statement it's in is
+    fn value(&self, value: &ValueExpr, depth: usize, column: usize) -> String {
+        match value {
+            ValueExpr::Int { span, .. } | ValueExpr::Float { span, .. } => {
+                self.text(*span).to_string()
+            }
+            ValueExpr::String { value, .. } => format!("\"{}\"", value),
+            ValueExpr::Bool { value, .. } => value.to_string(),
+            ValueExpr::Identifier(identifier) => identifier.name.to_string(),
+
+            // the right side binds tighter, `a - b - c` is `(a - b) - c`
+            ValueExpr::Binary {
+                left,
+                right,
+                operator,
+                ..
+            } => {
+                let precedence = Parser::precedence(operator);
+                let parens = binary_precedence(left).is_some_and(|left| Some(left) < precedence);
+                let mut out = self.operand(left, parens, depth, column);
+                out.push_str(&format!(" {} ", self::operator(operator)));
+
+                let parens =
+                    binary_precedence(right).is_some_and(|right| Some(right) <= precedence);
+                out.push_str(&self.operand(right, parens, depth, end_column(column, &out)));
+                out
+            }
+
+            ValueExpr::Unary {
+                operand, operator, ..
+            } => {
+                let parens = matches!(**operand, ValueExpr::Binary { .. } | ValueExpr::Cast { .. });
+                let operator = self::operator(operator);
+                operator.to_string()
+                    + &self.operand(operand, parens, depth, column + operator.len())
+            }
+
+            ValueExpr::Call { callee, args, .. } => {
+                let callee = self.postfix(callee, depth, column);
+                let open = format!("{}(", callee);
+                self.sequence((&open, ")"), args, depth, column, |arg, depth, column| {
+                    self.value(arg, depth, column)
+                })
+            }
+
+            ValueExpr::Cast {
+                value, basetype, ..
+            } => {
+                let parens = matches!(**value, ValueExpr::Binary { .. });
+                let value = self.operand(value, parens, depth, column);
+                format!("{} as {}", value, self::basetype(basetype))
+            }
+
+            ValueExpr::Field { value, field, .. } => {
+                format!("{}.{}", self.postfix(value, depth, column), field.name)
+            }
+
+            ValueExpr::List { items, .. } => {
+                self.sequence(("[", "]"), items, depth, column, |item, depth, column| {
+                    self.value(item, depth, column)
+                })
+            }
+
+            ValueExpr::Map { entries: [], .. } => "[:]".to_string(),
+            ValueExpr::Map { entries, .. } => self.sequence(
+                ("[", "]"),
+                entries,
+                depth,
+                column,
+                |(key, value), depth, column| {
+                    let key = self.value(key, depth, column);
+                    let value = self.value(value, depth, end_column(column, &key) + 2);
+                    format!("{}: {}", key, value)
+                },
+            ),
+
+            ValueExpr::Function {
+                params,
+                return_type,
+                body,
+                ..
+            } => {
+                let signature = self::basetype(&BaseType::Function {
+                    params: params.to_vec(),
+                    return_type: Box::new(return_type.clone()),
+                });
+                format!("{} {}", signature, self.block(body, depth))
+            }
+        }
+    }
+}
@@ -0,0 +1,1425 @@
+use std::error::Error;
+use std::fmt;
+
+use ez_ast::{
+    Arena, BaseType, Expr, Identifier, MatchArm, Param, Pattern, Program, Use, ValueExpr, Variant,
+};
+use ez_diagnostics::Diagnostic;
+use ez_lexer::{Lexer, Span, Token, TokenKind};
+
+pub use crate::edition::{Edition, Feature, ParserOptions};
+
+pub mod edition;
+pub mod formatter;
+pub mod tree_sitter;
+
+pub struct Parser<'s, 'a> {
+    current: Option<Token>,
+    // where the last consumed token ends, for values whose end isn't a token of
+    // their own, like the type of a cast
+    previous_end: usize,
+    tokens: Tokens<'s>,
+    // the text of the file, without the offset its spans have
+    source: &'s str,
+    offset: usize,
+    // where the nodes go, the program lives as long as it does
+    arena: &'a Arena,
+    options: ParserOptions,
+    // how many values, statements, types and patterns the current one is in
+    depth: usize,
+    // how deep the tree of the value being parsed goes, its first operand can
+    // end up in more nodes after it's parsed, like `a` in `a.b.c` or `a + b + c`
+    reached: usize,
+}
+
+// parsing recurses for every level of nesting, past this the stack could run
+// out, long before a program written by hand gets there
+const MAX_DEPTH: usize = 128;
+
+// where the tokens come from, the lexer as they're needed, or the tokens of a
+// file lexed before, with the offset of the file added to their spans
+#[derive(Clone)]
+enum Tokens<'s> {
+    Lexer(Lexer<'s>),
+    Lexed { tokens: &'s [Token], offset: usize },
+}
+
+impl Tokens<'_> {
+    fn next(&mut self) -> Option<Token> {
+        match self {
+            Tokens::Lexer(lexer) => lexer.tokenize(),
+            Tokens::Lexed { tokens, offset } => {
+                let (first, rest) = tokens.split_first()?;
+                *tokens = rest;
+                let span = Span::new(first.span.start + *offset, first.span.end + *offset);
+                Some(Token {
+                    span,
+                    ..first.clone()
+                })
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ParseError {
+    NoMoreTokens, // "soft" error (will happen at the EOF)
+    MissingTokenAfter(Token),
+    UnexpectedToken(Token),
+    // a token of another kind where one kind was needed, like a `;`, `after`
+    // is where the token before it ends, when the one found is on a later line
+    ExpectedToken {
+        expected: TokenKind,
+        found: Token,
+        after: Option<usize>,
+    },
+    // the file ended where a `;` was needed, after the token ending there
+    MissingSemi(usize),
+    // the `{` of a block, an enum or a match the file ended in
+    UnterminatedBlock(Token),
+    InvalidNumber(Token),
+    // syntax of a feature that isn't enabled, at its first token
+    Gated {
+        feature: Feature,
+        token: Token,
+    },
+    // the first token past the deepest nesting there can be
+    TooDeep(Token),
+}
+
+impl ParseError {
+    // the token it's about, there's none at the end of a file
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            ParseError::NoMoreTokens => None,
+            ParseError::MissingSemi(after) => Some(Span::new(*after, *after)),
+            ParseError::MissingTokenAfter(token)
+            | ParseError::UnexpectedToken(token)
+            | ParseError::ExpectedToken { found: token, .. }
+            | ParseError::UnterminatedBlock(token)
+            | ParseError::InvalidNumber(token)
+            | ParseError::Gated { token, .. }
+            | ParseError::TooDeep(token) => Some(token.span),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::NoMoreTokens => write!(f, "unexpected end of file"),
+            ParseError::MissingSemi(_) => write!(f, "unexpected end of file, expected `;`"),
+            ParseError::MissingTokenAfter(token) => {
+                write!(f, "unexpected end of file after {}", token.kind)
+            }
+            ParseError::UnexpectedToken(token) if token.kind == TokenKind::Unknown => {
+                write!(f, "unknown token `{}`", token.value)
+            }
+            ParseError::UnexpectedToken(token) => write!(f, "unexpected {}", token.kind),
+            ParseError::ExpectedToken {
+                expected, found, ..
+            } => {
+                write!(f, "expected {}, found {}", expected, found.kind)
+            }
+            ParseError::UnterminatedBlock(_) => {
+                write!(f, "unexpected end of file, a `{{` isn't closed")
+            }
+            ParseError::InvalidNumber(token) => write!(f, "invalid number `{}`", token.value),
+            ParseError::Gated { feature, .. } => {
+                write!(f, "{} are experimental", feature.description())
+            }
+            ParseError::TooDeep(_) => write!(f, "nested too deeply"),
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+impl From<ParseError> for Diagnostic {
+    fn from(error: ParseError) -> Self {
+        match error {
+            ParseError::NoMoreTokens => Diagnostic::error("E0003", "unexpected end of file"),
+
+            ParseError::MissingSemi(after) => {
+                let span = Span::new(after, after);
+                Diagnostic::error("E0003", "unexpected end of file")
+                    .with_label(span, "expected `;` here")
+                    .with_suggestion("add a `;`", span, ";")
+            }
+
+            ParseError::MissingTokenAfter(token) => {
+                Diagnostic::error("E0003", "unexpected end of file").with_label(
+                    token.span,
+                    format!("expected something after {}", token.kind),
+                )
+            }
+
+            ParseError::UnexpectedToken(token) | ParseError::ExpectedToken { found: token, .. }
+                if token.kind == TokenKind::Unknown =>
+            {
+                Diagnostic::error("E0001", format!("unknown token `{}`", token.value))
+                    .with_label(token.span, "not recognized")
+            }
+
+            ParseError::UnexpectedToken(token) => {
+                Diagnostic::error("E0002", format!("unexpected {}", token.kind))
+                    .with_label(token.span, "unexpected here")
+            }
+
+            ParseError::ExpectedToken {
+                expected,
+                found,
+                after,
+            } => {
+                let diagnostic = Diagnostic::error(
+                    "E0002",
+                    format!("expected {}, found {}", expected, found.kind),
+                )
+                .with_label(found.span, format!("expected {} here", expected));
+
+                // a statement the `;` of was forgotten at the end of its line ends
+                // where it was needed. On the same line, the rest of the line can
+                // be what's wrong, like the `e5` of `1e5`
+                match after {
+                    Some(after) if expected == TokenKind::Semi => {
+                        diagnostic.with_suggestion("add a `;`", Span::new(after, after), ";")
+                    }
+                    _ => diagnostic,
+                }
+            }
+
+            ParseError::UnterminatedBlock(left_curly) => {
+                Diagnostic::error("E0003", "unexpected end of file")
+                    .with_label(left_curly.span, "this `{` isn't closed")
+                    .with_help("add a `}` where it ends")
+            }
+
+            ParseError::InvalidNumber(token) => {
+                Diagnostic::error("E0004", format!("invalid number `{}`", token.value))
+                    .with_label(token.span, "can't be represented")
+            }
+
+            ParseError::Gated { feature, token } => Diagnostic::error(
+                "E0008",
+                format!("{} are experimental", feature.description()),
+            )
+            .with_label(token.span, format!("needs the `{}` feature", feature))
+            .with_help(format!(
+                "enable it with `--feature {}`, or with `features = [\"{}\"]` in the \
+                 `[package]` of `ez.toml`",
+                feature, feature
+            )),
+
+            ParseError::TooDeep(token) => Diagnostic::error("E0009", "nested too deeply")
+                .with_label(token.span, format!("more than {} levels deep", MAX_DEPTH))
+                .with_help("move some of it out to variables or functions"),
+        }
+    }
+}
+
+impl<'s, 'a> Parser<'s, 'a> {
+    pub fn new(content: &'s str, arena: &'a Arena) -> Self {
+        Self {
+            tokens: Tokens::Lexer(Lexer::new(content)),
+            source: content,
+            offset: 0,
+            current: None,
+            previous_end: 0,
+            arena,
+            options: ParserOptions::default(),
+            depth: 0,
+            reached: 0,
+        }
+    }
+
+    // the tokens of a file lexed from its start, and its text
+    pub fn lexed(source: &'s str, tokens: &'s [Token], arena: &'a Arena) -> Self {
+        Self {
+            tokens: Tokens::Lexed { tokens, offset: 0 },
+            source,
+            offset: 0,
+            current: None,
+            previous_end: 0,
+            arena,
+            options: ParserOptions::default(),
+            depth: 0,
+            reached: 0,
+        }
+    }
+
+    // for a file of a session, its spans start where the file does
+    pub fn with_offset(mut self, offset: usize) -> Self {
+        self.tokens = match self.tokens {
+            Tokens::Lexer(lexer) => Tokens::Lexer(lexer.with_offset(offset)),
+            Tokens::Lexed { tokens, .. } => Tokens::Lexed { tokens, offset },
+        };
+        self.previous_end = offset;
+        self.offset = offset;
+        self
+    }
+
+    // the edition and the features the file is parsed with
+    pub fn with_options(mut self, options: ParserOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    fn current(&self) -> &Option<Token> {
+        &self.current
+    }
+
+    fn advance(&mut self) -> &Option<Token> {
+        if let Some(current) = &self.current {
+            self.previous_end = current.span.end;
+        }
+
+        self.current = self.tokens.next();
+
+        &self.current
+    }
+
+    fn peek(&self) -> Option<Token> {
+        self.tokens.clone().next()
+    }
+
+    // what `parse` finds one level deeper than the current one, an error
+    // instead when there are too many of them
+    fn nested<T>(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<T, ParseError>,
+    ) -> Result<T, ParseError> {
+        if self.depth == MAX_DEPTH {
+            if let Some(token) = self.current().clone() {
+                return Err(ParseError::TooDeep(token));
+            }
+        }
+
+        self.depth += 1;
+        self.reached = self.reached.max(self.depth);
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+
+    // what was parsed since `start` ended up in one more node
+    fn wrap(&mut self, token: &Token) -> Result<(), ParseError> {
+        self.reached += 1;
+        if self.reached > MAX_DEPTH {
+            return Err(ParseError::TooDeep(token.clone()));
+        }
+
+        Ok(())
+    }
+
+    // a value that can be wrapped starts here, what it reached is kept until it
+    // ends, for the values it's in
+    fn start(&mut self) -> usize {
+        std::mem::replace(&mut self.reached, self.depth)
+    }
+
+    fn end(&mut self, outer: usize) {
+        self.reached = self.reached.max(outer);
+    }
+
+    fn current_is(&self, kind: TokenKind) -> bool {
+        matches!(self.current(), Some(token) if token.kind == kind)
+    }
+
+    // consumes the current token if it is of the given kind, `after` is only used for errors
+    fn expect(&mut self, kind: TokenKind, after: &Token) -> Result<Token, ParseError> {
+        let Some(token) = self.current().clone() else {
+            if kind == TokenKind::Semi {
+                return Err(ParseError::MissingSemi(self.previous_end));
+            }
+            return Err(ParseError::MissingTokenAfter(after.clone()));
+        };
+
+        if token.kind != kind {
+            let between = (self.previous_end - self.offset)..(token.span.start - self.offset);
+            let line_break = self
+                .source
+                .get(between)
+                .is_some_and(|between| between.contains('\n'));
+            return Err(ParseError::ExpectedToken {
+                expected: kind,
+                found: token,
+                after: line_break.then_some(self.previous_end),
+            });
+        }
+
+        self.advance();
+
+        Ok(token)
+    }
+
+    fn gate(&self, feature: Feature, token: &Token) -> Result<(), ParseError> {
+        if self.options.enabled(feature) {
+            return Ok(());
+        }
+
+        Err(ParseError::Gated {
+            feature,
+            token: token.clone(),
+        })
+    }
+
+    // whether the `,` just consumed was the last of the items, before `close`
+    fn trailing_comma(&self, comma: &Token, close: TokenKind) -> Result<bool, ParseError> {
+        if !self.current_is(close) {
+            return Ok(false);
+        }
+
+        self.gate(Feature::TrailingCommas, comma)?;
+        Ok(true)
+    }
+
+    fn parse_identifier_token(&mut self, after: &Token) -> Result<Identifier, ParseError> {
+        let token = self.expect(TokenKind::Identifier, after)?;
+
+        Ok(Identifier {
+            name: token.value.symbol(),
+            span: token.span,
+            id: self.arena.next_id(),
+        })
+    }
+
+    fn parse_type(&mut self, after: &Token) -> Result<BaseType, ParseError> {
+        let Some(token) = self.current().clone() else {
+            return Err(ParseError::MissingTokenAfter(after.clone()));
+        };
+
+        if token.kind == TokenKind::Fn {
+            self.advance();
+            let (params, return_type) = self.nested(|parser| parser.parse_signature(&token))?;
+
+            return Ok(BaseType::Function {
+                params,
+                return_type: Box::new(return_type),
+            });
+        }
+
+        if token.kind == TokenKind::LeftBracket {
+            self.advance();
+            let item = self.nested(|parser| parser.parse_type(&token))?;
+
+            let basetype = if self.current_is(TokenKind::Colon) {
+                let colon = self.expect(TokenKind::Colon, &token)?;
+                let value = self.nested(|parser| parser.parse_type(&colon))?;
+                BaseType::Map(Box::new(item), Box::new(value))
+            } else {
+                BaseType::List(Box::new(item))
+            };
+
+            self.expect(TokenKind::RightBracket, &token)?;
+            return Ok(basetype);
+        }
+
+        let basetype = match token.value.as_str() {
+            "void" if token.kind == TokenKind::Identifier => BaseType::Void,
+            "int" if token.kind == TokenKind::Identifier => BaseType::Int,
+            "float" if token.kind == TokenKind::Identifier => BaseType::Float,
+            "string" if token.kind == TokenKind::Identifier => BaseType::String,
+            "bool" if token.kind == TokenKind::Identifier => BaseType::Bool,
+            _ if token.kind == TokenKind::Identifier => BaseType::Named(token.value.symbol()),
+            _ => return Err(ParseError::UnexpectedToken(token)),
+        };
+
+        self.advance();
+
+        Ok(basetype)
+    }
+
+    // parses `(a: type, b: type) return_type`, the return type is optional and defaults to void
+    fn parse_signature(&mut self, after: &Token) -> Result<(Vec<Param>, BaseType), ParseError> {
+        let mut params = vec![];
+        let mut previous = self.expect(TokenKind::LeftParen, after)?;
+
+        while !self.current_is(TokenKind::RightParen) {
+            if !params.is_empty() {
+                previous = self.expect(TokenKind::Comma, &previous)?;
+                if self.trailing_comma(&previous, TokenKind::RightParen)? {
+                    break;
+                }
+            }
+
+            let identifier = self.parse_identifier_token(&previous)?;
+            previous = self.expect(TokenKind::Colon, &previous)?;
+            let basetype = self.parse_type(&previous)?;
+
+            params.push(Param {
+                identifier,
+                basetype,
+            });
+        }
+
+        let right_paren = self.expect(TokenKind::RightParen, &previous)?;
+
+        let return_type = match self.current() {
+            Some(token)
+                if matches!(
+                    token.kind,
+                    TokenKind::Identifier | TokenKind::Fn | TokenKind::LeftBracket
+                ) =>
+            {
+                self.parse_type(&right_paren)?
+            }
+            _ => BaseType::Void,
+        };
+
+        Ok((params, return_type))
+    }
+
+    // parses statements until the closing curly, which is consumed
+    fn parse_block_body(
+        &mut self,
+        left_curly: &Token,
+    ) -> Result<(&'a [Expr<'a>], Token), ParseError> {
+        let mut body = vec![];
+
+        loop {
+            let Some(token) = self.current().clone() else {
+                return Err(ParseError::UnterminatedBlock(left_curly.clone()));
+            };
+
+            if token.kind == TokenKind::RightCurly {
+                self.advance();
+                return Ok((self.arena.alloc_slice(body), token));
+            }
+
+            body.push(self.nested(Self::parse)?);
+        }
+    }
+
+    fn parse_function(&mut self) -> Result<ValueExpr<'a>, ParseError> {
+        let fn_token = self.current().clone().unwrap();
+        self.advance();
+
+        let (params, return_type) = self.parse_signature(&fn_token)?;
+        let left_curly = self.expect(TokenKind::LeftCurly, &fn_token)?;
+        let (body, right_curly) = self.parse_block_body(&left_curly)?;
+
+        Ok(ValueExpr::Function {
+            params: self.arena.alloc_slice(params),
+            return_type,
+            body,
+            span: fn_token.span.to(right_curly.span),
+            id: self.arena.next_id(),
+        })
+    }
+
+    // `[a, b]` is a list and `[key: value]` a map, the first item decides which
+    fn parse_collection(&mut self) -> Result<ValueExpr<'a>, ParseError> {
+        let left_bracket = self.current().clone().unwrap();
+        self.advance();
+
+        // `[:]`
+        if self.current_is(TokenKind::Colon) {
+            self.advance();
+            let right_bracket = self.expect(TokenKind::RightBracket, &left_bracket)?;
+
+            return Ok(ValueExpr::Map {
+                entries: &[],
+                span: left_bracket.span.to(right_bracket.span),
+                id: self.arena.next_id(),
+            });
+        }
+
+        let mut items = vec![];
+        let mut entries = vec![];
+        let mut previous = left_bracket.clone();
+        while !self.current_is(TokenKind::RightBracket) {
+            if !items.is_empty() || !entries.is_empty() {
+                previous = self.expect(TokenKind::Comma, &previous)?;
+                if self.trailing_comma(&previous, TokenKind::RightBracket)? {
+                    break;
+                }
+            }
+
+            if self.current().is_none() {
+                return Err(ParseError::MissingTokenAfter(previous));
+            }
+
+            let item = self.parse_value()?;
+            let is_map =
+                !entries.is_empty() || (items.is_empty() && self.current_is(TokenKind::Colon));
+            if is_map {
+                previous = self.expect(TokenKind::Colon, &previous)?;
+                if self.current().is_none() {
+                    return Err(ParseError::MissingTokenAfter(previous));
+                }
+
+                entries.push((item, self.parse_value()?));
+            } else {
+                items.push(item);
+            }
+        }
+
+        let right_bracket = self.expect(TokenKind::RightBracket, &previous)?;
+        let span = left_bracket.span.to(right_bracket.span);
+
+        if entries.is_empty() {
+            let items = self.arena.alloc_slice(items);
+            Ok(ValueExpr::List {
+                items,
+                span,
+                id: self.arena.next_id(),
+            })
+        } else {
+            let entries = self.arena.alloc_slice(entries);
+            Ok(ValueExpr::Map {
+                entries,
+                span,
+                id: self.arena.next_id(),
+            })
+        }
+    }
+
+    fn parse_primary(&mut self) -> Result<ValueExpr<'a>, ParseError> {
+        let token = self.current().clone().unwrap();
+        match token.kind {
+            TokenKind::Integer => {
+                let Ok(number) = token.value.parse::<i64>() else {
+                    return Err(ParseError::InvalidNumber(token));
+                };
+
+                self.advance();
+
+                Ok(ValueExpr::Int {
+                    value: number,
+                    span: token.span,
+                    id: self.arena.next_id(),
+                })
+            }
+
+            TokenKind::Float => {
+                let Ok(number) = token.value.parse::<f64>() else {
+                    return Err(ParseError::InvalidNumber(token));
+                };
+
+                self.advance();
+
+                Ok(ValueExpr::Float {
+                    value: number,
+                    span: token.span,
+                    id: self.arena.next_id(),
+                })
+            }
+
+            TokenKind::String => {
+                self.advance();
+                Ok(ValueExpr::String {
+                    value: token.value.to_string(),
+                    span: token.span,
+                    id: self.arena.next_id(),
+                })
+            }
+
+            TokenKind::True | TokenKind::False => {
+                self.advance();
+                Ok(ValueExpr::Bool {
+                    value: token.kind == TokenKind::True,
+                    span: token.span,
+                    id: self.arena.next_id(),
+                })
+            }
+
+            TokenKind::Identifier => {
+                self.advance();
+                Ok(ValueExpr::Identifier(Identifier {
+                    name: token.value.symbol(),
+                    span: token.span,
+                    id: self.arena.next_id(),
+                }))
+            }
+
+            TokenKind::LeftParen => {
+                if self.advance().is_none() {
+                    return Err(ParseError::MissingTokenAfter(token));
+                }
+                let value = self.parse_value()?;
+                self.expect(TokenKind::RightParen, &token)?;
+
+                Ok(value)
+            }
+
+            TokenKind::Fn => self.parse_function(),
+
+            TokenKind::LeftBracket => self.parse_collection(),
+
+            _ => Err(ParseError::UnexpectedToken(token)),
+        }
+    }
+
+    // calls and field accesses
+    fn parse_postfix(&mut self) -> Result<ValueExpr<'a>, ParseError> {
+        let outer = self.start();
+        let mut value = self.parse_primary()?;
+
+        while let Some(left_paren) = self.current().clone() {
+            if left_paren.kind == TokenKind::Dot {
+                self.wrap(&left_paren)?;
+                self.advance();
+                let field = self.parse_identifier_token(&left_paren)?;
+                value = ValueExpr::Field {
+                    value: self.arena.alloc(value),
+                    field,
+                    id: self.arena.next_id(),
+                };
+
+                continue;
+            }
+
+            if left_paren.kind != TokenKind::LeftParen {
+                break;
+            }
+
+            self.wrap(&left_paren)?;
+            self.advance();
+
+            let mut args = vec![];
+            let mut previous = left_paren;
+            while !self.current_is(TokenKind::RightParen) {
+                if !args.is_empty() {
+                    previous = self.expect(TokenKind::Comma, &previous)?;
+                    if self.trailing_comma(&previous, TokenKind::RightParen)? {
+                        break;
+                    }
+                }
+
+                if self.current().is_none() {
+                    return Err(ParseError::MissingTokenAfter(previous));
+                }
+
+                args.push(self.parse_value()?);
+            }
+
+            let right_paren = self.expect(TokenKind::RightParen, &previous)?;
+
+            value = ValueExpr::Call {
+                span: value.span().to(right_paren.span),
+                callee: self.arena.alloc(value),
+                args: self.arena.alloc_slice(args),
+                id: self.arena.next_id(),
+            };
+        }
+
+        self.end(outer);
+        Ok(value)
+    }
+
+    fn is_unary(kind: &TokenKind) -> bool {
+        matches!(kind, TokenKind::Minus | TokenKind::Not | TokenKind::BitNot)
+    }
+
+    fn parse_unary(&mut self) -> Result<ValueExpr<'a>, ParseError> {
+        let token = self.current().clone().unwrap();
+        match token.kind {
+            ref kind if Self::is_unary(kind) => {
+                if self.advance().is_none() {
+                    return Err(ParseError::MissingTokenAfter(token));
+                }
+
+                let operand = self.nested(Self::parse_unary)?;
+
+                Ok(ValueExpr::Unary {
+                    span: token.span.to(operand.span()),
+                    operand: self.arena.alloc(operand),
+                    operator: token.kind,
+                    id: self.arena.next_id(),
+                })
+            }
+
+            _ => self.parse_postfix(),
+        }
+    }
+
+    // `as` binds tighter than binary operators but looser than unary ones, so
+    // `-x as float * y` is `((-x) as float) * y`
+    fn parse_cast(&mut self) -> Result<ValueExpr<'a>, ParseError> {
+        let outer = self.start();
+        let mut value = self.parse_unary()?;
+
+        while let Some(as_token) = self.current().clone() {
+            if as_token.kind != TokenKind::As {
+                break;
+            }
+
+            self.wrap(&as_token)?;
+            self.advance();
+            let basetype = self.parse_type(&as_token)?;
+            value = ValueExpr::Cast {
+                span: Span::new(value.span().start, self.previous_end),
+                value: self.arena.alloc(value),
+                basetype,
+                id: self.arena.next_id(),
+            };
+        }
+
+        self.end(outer);
+        Ok(value)
+    }
+
+    // binding power of binary operators, higher binds tighter
+    pub fn precedence(kind: &TokenKind) -> Option<u8> {
+        let precedence = match kind {
+            TokenKind::Or => 1,
+            TokenKind::And => 2,
+            TokenKind::BitOr => 3,
+            TokenKind::BitXor => 4,
+            TokenKind::BitAnd => 5,
+            TokenKind::Equals | TokenKind::NotEquals => 6,
+            TokenKind::GreaterThan
+            | TokenKind::GreaterOrEquals
+            | TokenKind::LowerThan
+            | TokenKind::LowerOrEquals => 7,
+            TokenKind::Plus | TokenKind::Minus => 8,
+            TokenKind::Times | TokenKind::DividedBy => 9,
+            _ => return None,
+        };
+
+        Some(precedence)
+    }
+
+    fn parse_binary(&mut self, min_precedence: u8) -> Result<ValueExpr<'a>, ParseError> {
+        let outer = self.start();
+        let mut left = self.parse_cast()?;
+
+        while let Some(operator) = self.current().clone() {
+            let Some(precedence) = Self::precedence(&operator.kind) else {
+                break;
+            };
+
+            if precedence < min_precedence {
+                break;
+            }
+
+            self.wrap(&operator)?;
+            if self.advance().is_none() {
+                return Err(ParseError::MissingTokenAfter(operator));
+            }
+
+            let right = self.parse_binary(precedence + 1)?;
+            left = ValueExpr::Binary {
+                left: self.arena.alloc(left),
+                right: self.arena.alloc(right),
+                operator: operator.kind,
+                id: self.arena.next_id(),
+            };
+        }
+
+        self.end(outer);
+        Ok(left)
+    }
+
+    fn parse_value(&mut self) -> Result<ValueExpr<'a>, ParseError> {
+        self.nested(|parser| parser.parse_binary(0))
+    }
+
+    // values that aren't functions need to be terminated by a semicolon
+    fn expect_semi(&mut self, value: &ValueExpr, after: &Token) -> Result<(), ParseError> {
+        if let ValueExpr::Function { .. } = value {
+            return Ok(());
+        }
+
+        self.expect(TokenKind::Semi, after)?;
+
+        Ok(())
+    }
+
+    // a declaration is an identifier followed by either `:=` or a type
+    fn is_declaration(&self) -> bool {
+        let Some(next) = self.peek() else {
+            return false;
+        };
+
+        matches!(
+            next.kind,
+            TokenKind::DeclAssign | TokenKind::Identifier | TokenKind::Fn | TokenKind::LeftBracket
+        )
+    }
+
+    // `name := value;` or with an explicit type, `name type := value;`, or
+    // without a value, `name type;`
+    fn parse_declaration(&mut self, mutable: bool) -> Result<Expr<'a>, ParseError> {
+        let ident = self.current().to_owned().unwrap();
+        self.advance();
+
+        let basetype = if self.current_is(TokenKind::DeclAssign) {
+            None
+        } else {
+            Some(self.parse_type(&ident)?)
+        };
+
+        let identifier = Identifier {
+            name: ident.value.symbol(),
+            span: ident.span,
+            id: self.arena.next_id(),
+        };
+
+        if basetype.is_some() && self.current_is(TokenKind::Semi) {
+            self.advance();
+
+            return Ok(Expr::Declaration {
+                identifier,
+                mutable,
+                basetype,
+                value: None,
+                attributes: &[],
+            });
+        }
+
+        let decl_assign = self.expect(TokenKind::DeclAssign, &ident)?;
+        if self.current().is_none() {
+            return Err(ParseError::MissingTokenAfter(decl_assign));
+        };
+
+        let value_expr = self.parse_value()?;
+        self.expect_semi(&value_expr, &decl_assign)?;
+
+        Ok(Expr::Declaration {
+            identifier,
+            mutable,
+            basetype,
+            value: Some(self.arena.alloc(value_expr)),
+            attributes: &[],
+        })
+    }
+
+    fn parse_mut_declaration(&mut self) -> Result<Expr<'a>, ParseError> {
+        let mut_token = self.current().to_owned().unwrap();
+        self.advance();
+
+        match self.current() {
+            Some(token) if token.kind == TokenKind::Identifier && self.is_declaration() => {
+                self.parse_declaration(true)
+            }
+            Some(token) => Err(ParseError::UnexpectedToken(token.clone())),
+            None => Err(ParseError::MissingTokenAfter(mut_token)),
+        }
+    }
+
+    fn assign_operator(kind: &TokenKind) -> Option<Option<TokenKind>> {
+        let operator = match kind {
+            TokenKind::Assign => None,
+            TokenKind::PlusAssign => Some(TokenKind::Plus),
+            TokenKind::MinusAssign => Some(TokenKind::Minus),
+            TokenKind::TimesAssign => Some(TokenKind::Times),
+            TokenKind::DividedByAssign => Some(TokenKind::DividedBy),
+            _ => return None,
+        };
+
+        Some(operator)
+    }
+
+    fn parse_assignment(&mut self, target: ValueExpr<'a>) -> Result<Expr<'a>, ParseError> {
+        let assign = self.current().to_owned().unwrap();
+        let operator = Self::assign_operator(&assign.kind).unwrap();
+
+        if self.advance().is_none() {
+            return Err(ParseError::MissingTokenAfter(assign));
+        }
+
+        let value = self.parse_value()?;
+        self.expect(TokenKind::Semi, &assign)?;
+
+        Ok(Expr::Assign {
+            target,
+            operator,
+            value,
+        })
+    }
+
+    fn parse_if(&mut self) -> Result<Expr<'a>, ParseError> {
+        let if_token = self.current().to_owned().unwrap();
+        if self.advance().is_none() {
+            return Err(ParseError::MissingTokenAfter(if_token));
+        }
+
+        let condition = self.parse_value()?;
+        let left_curly = self.expect(TokenKind::LeftCurly, &if_token)?;
+        let (body, mut end) = self.parse_block_body(&left_curly)?;
+
+        let mut else_body = None;
+        if let Some(else_token) = self.current().clone() {
+            if else_token.kind == TokenKind::Else {
+                match self.advance().clone() {
+                    Some(token) if token.kind == TokenKind::If => {
+                        let else_if = self.nested(Self::parse_if)?;
+                        if let Expr::If { span, .. } = &else_if {
+                            end.span = *span;
+                        }
+
+                        else_body = Some(self.arena.alloc_slice(vec![else_if]));
+                    }
+
+                    Some(token) => {
+                        let left_curly = self.expect(TokenKind::LeftCurly, &token)?;
+                        let (body, right_curly) = self.parse_block_body(&left_curly)?;
+                        end = right_curly;
+                        else_body = Some(body);
+                    }
+
+                    None => return Err(ParseError::MissingTokenAfter(else_token)),
+                }
+            }
+        }
+
+        Ok(Expr::If {
+            condition,
+            body,
+            else_body,
+            span: if_token.span.to(end.span),
+        })
+    }
+
+    fn parse_while(&mut self) -> Result<Expr<'a>, ParseError> {
+        let while_token = self.current().to_owned().unwrap();
+        if self.advance().is_none() {
+            return Err(ParseError::MissingTokenAfter(while_token));
+        }
+
+        let condition = self.parse_value()?;
+        let left_curly = self.expect(TokenKind::LeftCurly, &while_token)?;
+        let (body, right_curly) = self.parse_block_body(&left_curly)?;
+
+        Ok(Expr::While {
+            condition,
+            body,
+            span: while_token.span.to(right_curly.span),
+        })
+    }
+
+    fn parse_for(&mut self) -> Result<Expr<'a>, ParseError> {
+        let for_token = self.current().to_owned().unwrap();
+        self.advance();
+
+        let binding = self.parse_identifier_token(&for_token)?;
+        let in_token = self.expect(TokenKind::In, &for_token)?;
+        if self.current().is_none() {
+            return Err(ParseError::MissingTokenAfter(in_token));
+        }
+
+        let start = self.parse_value()?;
+
+        let mut end = None;
+        let mut previous = in_token;
+        if self.current_is(TokenKind::DotDot) {
+            previous = self.expect(TokenKind::DotDot, &previous)?;
+            if self.current().is_none() {
+                return Err(ParseError::MissingTokenAfter(previous));
+            }
+
+            end = Some(self.parse_value()?);
+        }
+
+        let left_curly = self.expect(TokenKind::LeftCurly, &previous)?;
+        let (body, right_curly) = self.parse_block_body(&left_curly)?;
+
+        Ok(Expr::For {
+            binding,
+            start,
+            end,
+            body,
+            span: for_token.span.to(right_curly.span),
+        })
+    }
+
+    fn parse_try(&mut self) -> Result<Expr<'a>, ParseError> {
+        let try_token = self.current().to_owned().unwrap();
+        self.advance();
+
+        let left_curly = self.expect(TokenKind::LeftCurly, &try_token)?;
+        let (body, right_curly) = self.parse_block_body(&left_curly)?;
+
+        let catch_token = self.expect(TokenKind::Catch, &right_curly)?;
+        let error = self.parse_identifier_token(&catch_token)?;
+        let left_curly = self.expect(TokenKind::LeftCurly, &catch_token)?;
+        let (handler, right_curly) = self.parse_block_body(&left_curly)?;
+
+        Ok(Expr::Try {
+            body,
+            error,
+            handler,
+            span: try_token.span.to(right_curly.span),
+        })
+    }
+
+    // `break;` and `continue;`
+    fn parse_loop_control(&mut self) -> Result<Expr<'a>, ParseError> {
+        let token = self.current().to_owned().unwrap();
+        self.advance();
+        let semi = self.expect(TokenKind::Semi, &token)?;
+        let span = token.span.to(semi.span);
+
+        match token.kind {
+            TokenKind::Break => Ok(Expr::Break(span)),
+            _ => Ok(Expr::Continue(span)),
+        }
+    }
+
+    fn parse_enum(&mut self) -> Result<Expr<'a>, ParseError> {
+        let enum_token = self.current().to_owned().unwrap();
+        self.advance();
+
+        let identifier = self.parse_identifier_token(&enum_token)?;
+        let left_curly = self.expect(TokenKind::LeftCurly, &enum_token)?;
+        let mut previous = left_curly.clone();
+
+        let mut variants = vec![];
+        while !self.current_is(TokenKind::RightCurly) {
+            if self.current().is_none() {
+                return Err(ParseError::UnterminatedBlock(left_curly));
+            }
+
+            if !variants.is_empty() {
+                previous = self.expect(TokenKind::Comma, &previous)?;
+
+                // trailing comma
+                if self.current_is(TokenKind::RightCurly) {
+                    break;
+                }
+            }
+
+            let variant = self.parse_identifier_token(&previous)?;
+
+            let mut payload = vec![];
+            if let Some(left_paren) = self.current().clone() {
+                if left_paren.kind == TokenKind::LeftParen {
+                    self.advance();
+                    previous = left_paren;
+                    while !self.current_is(TokenKind::RightParen) {
+                        if !payload.is_empty() {
+                            previous = self.expect(TokenKind::Comma, &previous)?;
+                            if self.trailing_comma(&previous, TokenKind::RightParen)? {
+                                break;
+                            }
+                        }
+
+                        payload.push(self.parse_type(&previous)?);
+                    }
+
+                    previous = self.expect(TokenKind::RightParen, &previous)?;
+                }
+            }
+
+            variants.push(Variant {
+                identifier: variant,
+                payload,
+            });
+        }
+
+        let right_curly = self.expect(TokenKind::RightCurly, &previous)?;
+
+        Ok(Expr::Enum {
+            identifier,
+            variants: self.arena.alloc_slice(variants),
+            span: enum_token.span.to(right_curly.span),
+        })
+    }
+
+    fn parse_pattern(&mut self, after: &Token) -> Result<Pattern<'a>, ParseError> {
+        let Some(token) = self.current().clone() else {
+            return Err(ParseError::MissingTokenAfter(after.clone()));
+        };
+
+        let literal = match self.parse_primary() {
+            Ok(ValueExpr::Int { value, span, .. }) => Pattern::Int { value, span },
+            Ok(ValueExpr::String { value, span, .. }) => Pattern::String { value, span },
+            Ok(ValueExpr::Bool { value, span, .. }) => Pattern::Bool { value, span },
+            Ok(ValueExpr::Identifier(identifier)) => {
+                if !self.current_is(TokenKind::Dot) {
+                    if identifier.name == "_" {
+                        return Ok(Pattern::Wildcard(identifier.span));
+                    }
+
+                    return Ok(Pattern::Binding(identifier));
+                }
+
+                let dot = self.expect(TokenKind::Dot, &token)?;
+                let variant = self.parse_identifier_token(&dot)?;
+                let mut span = identifier.span.to(variant.span);
+
+                let mut fields = vec![];
+                if let Some(left_paren) = self.current().clone() {
+                    if left_paren.kind == TokenKind::LeftParen {
+                        self.advance();
+                        let mut previous = left_paren;
+                        while !self.current_is(TokenKind::RightParen) {
+                            if !fields.is_empty() {
+                                previous = self.expect(TokenKind::Comma, &previous)?;
+                                if self.trailing_comma(&previous, TokenKind::RightParen)? {
+                                    break;
+                                }
+                            }
+
+                            fields.push(self.nested(|parser| parser.parse_pattern(&previous))?);
+                        }
+
+                        span = span.to(self.expect(TokenKind::RightParen, &previous)?.span);
+                    }
+                }
+
+                return Ok(Pattern::Variant {
+                    enum_name: identifier,
+                    variant,
+                    fields: self.arena.alloc_slice(fields),
+                    span,
+                });
+            }
+            _ => return Err(ParseError::UnexpectedToken(token)),
+        };
+
+        Ok(literal)
+    }
+
+    // `match value { pattern { body } ... }`
+    fn parse_match(&mut self) -> Result<Expr<'a>, ParseError> {
+        let match_token = self.current().to_owned().unwrap();
+        if self.advance().is_none() {
+            return Err(ParseError::MissingTokenAfter(match_token));
+        }
+
+        let value = self.parse_value()?;
+        let left_curly = self.expect(TokenKind::LeftCurly, &match_token)?;
+        let mut previous = left_curly.clone();
+
+        let mut arms = vec![];
+        while !self.current_is(TokenKind::RightCurly) {
+            if self.current().is_none() {
+                return Err(ParseError::UnterminatedBlock(left_curly));
+            }
+
+            let pattern = self.parse_pattern(&previous)?;
+            let left_curly = self.expect(TokenKind::LeftCurly, &previous)?;
+            let (body, right_curly) = self.parse_block_body(&left_curly)?;
+
+            arms.push(MatchArm {
+                span: pattern.span().to(right_curly.span),
+                pattern,
+                body,
+            });
+            previous = right_curly;
+        }
+
+        let right_curly = self.expect(TokenKind::RightCurly, &previous)?;
+
+        Ok(Expr::Match {
+            value,
+            arms: self.arena.alloc_slice(arms),
+            span: match_token.span.to(right_curly.span),
+        })
+    }
+
+    fn parse_return(&mut self) -> Result<Expr<'a>, ParseError> {
+        let return_token = self.current().to_owned().unwrap();
+        self.advance();
+
+        if let Some(semi) = self.current().clone() {
+            if semi.kind == TokenKind::Semi {
+                self.advance();
+                return Ok(Expr::Return {
+                    value: None,
+                    span: return_token.span.to(semi.span),
+                });
+            }
+        }
+
+        if self.current().is_none() {
+            return Err(ParseError::MissingTokenAfter(return_token));
+        }
+
+        let value = self.parse_value()?;
+        let semi = self.expect(TokenKind::Semi, &return_token)?;
+
+        Ok(Expr::Return {
+            value: Some(value),
+            span: return_token.span.to(semi.span),
+        })
+    }
+
+    pub fn parse(&mut self) -> Result<Expr<'a>, ParseError> {
+        if self.current().is_none() {
+            self.advance();
+        }
+
+        let Some(token) = self.current().clone() else {
+            return Err(ParseError::NoMoreTokens);
+        };
+
+        match token.kind {
+            TokenKind::Identifier if self.is_declaration() => self.parse_declaration(false),
+
+            TokenKind::Mut => self.parse_mut_declaration(),
+
+            TokenKind::Return => self.parse_return(),
+
+            TokenKind::If => self.parse_if(),
+
+            TokenKind::While => self.parse_while(),
+
+            TokenKind::For => self.parse_for(),
+
+            TokenKind::Enum => self.parse_enum(),
+
+            TokenKind::Match => self.parse_match(),
+
+            TokenKind::Try => self.parse_try(),
+
+            TokenKind::Break | TokenKind::Continue => self.parse_loop_control(),
+
+            TokenKind::LeftCurly => {
+                self.advance();
+                let (body, right_curly) = self.parse_block_body(&token)?;
+
+                Ok(Expr::Block {
+                    body,
+                    span: token.span.to(right_curly.span),
+                })
+            }
+
+            TokenKind::Unknown => Err(ParseError::UnexpectedToken(token)),
+
+            _ => {
+                let value = self.parse_value()?;
+
+                if let Some(next) = self.current() {
+                    if Self::assign_operator(&next.kind).is_some() {
+                        return self.parse_assignment(value);
+                    }
+                }
+
+                self.expect_semi(&value, &token)?;
+
+                Ok(Expr::Value(value))
+            }
+        }
+    }
+
+    fn parse_use(&mut self) -> Result<Use, ParseError> {
+        let use_token = self.current().to_owned().unwrap();
+        self.advance();
+
+        let mut path = vec![];
+        let mut previous = use_token.clone();
+        loop {
+            previous = self.expect(TokenKind::Identifier, &previous)?;
+            path.push(Identifier {
+                name: previous.value.symbol(),
+                span: previous.span,
+                id: self.arena.next_id(),
+            });
+
+            if !self.current_is(TokenKind::Dot) {
+                break;
+            }
+            previous = self.expect(TokenKind::Dot, &previous)?;
+        }
+
+        let semi = self.expect(TokenKind::Semi, &previous)?;
+
+        Ok(Use {
+            path,
+            span: use_token.span.to(semi.span),
+            module: None,
+        })
+    }
+
+    // `test` is only a keyword at the start of a test, it can still name things
+    fn is_test(&self) -> bool {
+        let Some(token) = self.current() else {
+            return false;
+        };
+
+        token.kind == TokenKind::Identifier
+            && token.value == "test"
+            && self
+                .peek()
+                .is_some_and(|next| next.kind == TokenKind::String)
+    }
+
+    fn parse_test(&mut self) -> Result<Expr<'a>, ParseError> {
+        let test_token = self.current().to_owned().unwrap();
+        self.advance();
+
+        let name = self.expect(TokenKind::String, &test_token)?;
+        let left_curly = self.expect(TokenKind::LeftCurly, &name)?;
+        let (body, right_curly) = self.parse_block_body(&left_curly)?;
+
+        Ok(Expr::Test {
+            name: name.value.to_string(),
+            body,
+            span: test_token.span.to(right_curly.span),
+        })
+    }
+
+    // `@name` lines above a declaration
+    fn parse_attributes(&mut self) -> Result<Expr<'a>, ParseError> {
+        let mut attributes = vec![];
+        let mut last = None;
+        while let Some(at) = self
+            .current()
+            .clone()
+            .filter(|token| token.kind == TokenKind::At)
+        {
+            self.advance();
+            last = self.current().clone();
+            attributes.push(self.parse_identifier_token(&at)?);
+        }
+
+        let Some(token) = self.current().clone() else {
+            let name = last.expect("an attribute has a name");
+            return Err(ParseError::MissingTokenAfter(name));
+        };
+        match self.parse()? {
+            Expr::Declaration {
+                identifier,
+                mutable,
+                basetype,
+                value,
+                ..
+            } => Ok(Expr::Declaration {
+                identifier,
+                mutable,
+                basetype,
+                value,
+                attributes: self.arena.alloc_slice(attributes),
+            }),
+            _ => Err(ParseError::UnexpectedToken(token)),
+        }
+    }
+
+    // uses, tests and attributes can only be at the top level
+    pub fn parse_program(&mut self) -> Result<Program<'a>, ParseError> {
+        let mut program = Program::default();
+        let mut body = vec![];
+        loop {
+            if self.current().is_none() {
+                self.advance();
+            }
+
+            if self.current_is(TokenKind::Use) {
+                program.uses.push(self.parse_use()?);
+                continue;
+            }
+
+            if self.is_test() {
+                body.push(self.parse_test()?);
+                continue;
+            }
+
+            if self.current_is(TokenKind::At) {
+                body.push(self.parse_attributes()?);
+                continue;
+            }
+
+            match self.parse() {
+                Ok(expr) => body.push(expr),
+                Err(ParseError::NoMoreTokens) => break,
+                Err(e) => return Err(e),
+            }
+        }
+
+        program.body = self.arena.alloc_slice(body);
+        Ok(program)
+    }
+}
@@ -0,0 +1,386 @@
+use std::fmt::Write;
+
+use ez_lexer::{Lexer, TokenKind};
+
+use crate::Parser;
+
+// the grammar of ez for tree-sitter, so editors can highlight it as it's typed.
+// The rules follow the ones of the parser, and the operators, their precedence
+// and the keywords come from the lexer and the parser themselves, so the two
+// can't disagree on them. `ez grammar` writes it, `tree-sitter generate` turns
+// it into a parser
+pub fn grammar() -> String {
+    let mut binary = String::new();
+    let mut highest = 0;
+    for (symbol, kind) in Lexer::symbols() {
+        if let Some(precedence) = Parser::precedence(kind) {
+            let _ = writeln!(binary, "  [{}, {}],", quoted(symbol), precedence);
+            highest = highest.max(precedence);
+        }
+    }
+
+    let unary = list(
+        Lexer::symbols()
+            .into_iter()
+            .filter(|(_, kind)| Parser::is_unary(kind)),
+        ", ",
+    );
+    let assign = list(
+        Lexer::symbols()
+            .into_iter()
+            .filter(|(_, kind)| Parser::assign_operator(kind).is_some()),
+        ", ",
+    );
+
+    // `as` binds looser than unary operators, which bind looser than calls
+    GRAMMAR
+        .replace("{binary}", &binary)
+        .replace("{unary}", &unary)
+        .replace("{assign}", &assign)
+        .replace("{cast}", &(highest + 1).to_string())
+        .replace("{prefix}", &(highest + 2).to_string())
+        .replace("{postfix}", &(highest + 3).to_string())
+}
+
+// `queries/highlights.scm`, what the nodes of the grammar are for an editor
+pub fn highlights() -> String {
+    // `test` is only a keyword in front of a test, the lexer doesn't know it
+    let keywords = list(
+        Lexer::keywords()
+            .filter(|(_, kind)| !matches!(kind, TokenKind::True | TokenKind::False))
+            .chain([("test", &TokenKind::Identifier)]),
+        " ",
+    );
+    let symbols = Lexer::symbols();
+    let of = |class: Class| {
+        list(
+            symbols
+                .iter()
+                .copied()
+                .filter(|(_, kind)| Class::of(kind) == class),
+            " ",
+        )
+    };
+
+    HIGHLIGHTS
+        .replace("{keywords}", &keywords)
+        .replace("{brackets}", &of(Class::Bracket))
+        .replace("{delimiters}", &of(Class::Delimiter))
+        .replace("{operators}", &of(Class::Operator))
+}
+
+#[derive(PartialEq, Clone, Copy)]
+enum Class {
+    Bracket,
+    Delimiter,
+    // `@` is highlighted with the name of the attribute
+    Attribute,
+    Operator,
+}
+
+impl Class {
+    fn of(kind: &TokenKind) -> Class {
+        match kind {
+            TokenKind::LeftCurly
+            | TokenKind::RightCurly
+            | TokenKind::LeftParen
+            | TokenKind::RightParen
+            | TokenKind::LeftBracket
+            | TokenKind::RightBracket => Class::Bracket,
+            TokenKind::Dot
+            | TokenKind::DotDot
+            | TokenKind::Comma
+            | TokenKind::Colon
+            | TokenKind::Semi => Class::Delimiter,
+            TokenKind::At => Class::Attribute,
+            _ => Class::Operator,
+        }
+    }
+}
+
+// a string of javascript, and one of a query, are written the same
+fn quoted(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+// the items of an array are separated by commas in javascript, and by spaces
+// in a query
+fn list<'k>(
+    tokens: impl Iterator<Item = (&'static str, &'k TokenKind)>,
+    separator: &str,
+) -> String {
+    tokens
+        .map(|(text, _)| quoted(text))
+        .collect::<Vec<_>>()
+        .join(separator)
+}
+
+const GRAMMAR: &str = r#"// the grammar of ez for tree-sitter, written by `ez grammar` from the tokens
+// of the lexer and the precedence of the parser. Write it again instead of
+// changing it
+
+const BINARY = [
+{binary}];
+
+const UNARY = [{unary}];
+
+const ASSIGN = [{assign}];
+
+const PREC = {
+  cast: {cast},
+  prefix: {prefix},
+  postfix: {postfix},
+};
+
+module.exports = grammar({
+  name: 'ez',
+
+  extras: $ => [/\s/, $.comment],
+
+  word: $ => $.identifier,
+
+  rules: {
+    source_file: $ => repeat(choice($.use_declaration, $.test, $._statement)),
+
+    use_declaration: $ => seq('use', sep1($.identifier, '.'), ';'),
+
+    test: $ => seq('test', field('name', $.string), field('body', $.block)),
+
+    _statement: $ => choice(
+      $.declaration,
+      $.assignment,
+      $.if_statement,
+      $.while_statement,
+      $.for_statement,
+      $.try_statement,
+      $.match_statement,
+      $.enum_declaration,
+      $.return_statement,
+      $.break_statement,
+      $.continue_statement,
+      $.block,
+      $.expression_statement,
+    ),
+
+    attribute: $ => seq('@', field('name', $.identifier)),
+
+    declaration: $ => seq(
+      repeat($.attribute),
+      optional('mut'),
+      field('name', $.identifier),
+      choice(
+        seq(field('type', $._type), ';'),
+        seq(optional(field('type', $._type)), ':=', field('value', $._value), ';'),
+        // a function needs no `;`, but what follows it is still part of it
+        prec(-1, seq(optional(field('type', $._type)), ':=', field('value', $.function))),
+      ),
+    ),
+
+    assignment: $ => seq(
+      field('target', $._value),
+      field('operator', choice(...ASSIGN)),
+      field('value', $._value),
+      ';',
+    ),
+
+    if_statement: $ => seq(
+      'if',
+      field('condition', $._value),
+      field('body', $.block),
+      optional(seq('else', field('else', choice($.if_statement, $.block)))),
+    ),
+
+    while_statement: $ => seq('while', field('condition', $._value), field('body', $.block)),
+
+    for_statement: $ => seq(
+      'for',
+      field('binding', $.identifier),
+      'in',
+      field('start', $._value),
+      optional(seq('..', field('end', $._value))),
+      field('body', $.block),
+    ),
+
+    try_statement: $ => seq(
+      'try',
+      field('body', $.block),
+      'catch',
+      field('error', $.identifier),
+      field('handler', $.block),
+    ),
+
+    match_statement: $ => seq('match', field('value', $._value), '{', repeat($.match_arm), '}'),
+
+    match_arm: $ => seq(field('pattern', $._pattern), field('body', $.block)),
+
+    _pattern: $ => choice(
+      $.integer,
+      $.string,
+      $.boolean,
+      $.identifier,
+      $.variant_pattern,
+    ),
+
+    variant_pattern: $ => seq(
+      field('enum', $.identifier),
+      '.',
+      field('variant', $.identifier),
+      optional(seq('(', sep($._pattern, ','), ')')),
+    ),
+
+    enum_declaration: $ => seq(
+      'enum',
+      field('name', $.identifier),
+      '{',
+      optional(seq(sep1($.variant, ','), optional(','))),
+      '}',
+    ),
+
+    variant: $ => seq(
+      field('name', $.identifier),
+      optional(seq('(', sep($._type, ','), ')')),
+    ),
+
+    return_statement: $ => seq('return', optional(field('value', $._value)), ';'),
+
+    break_statement: $ => seq('break', ';'),
+
+    continue_statement: $ => seq('continue', ';'),
+
+    block: $ => seq('{', repeat($._statement), '}'),
+
+    expression_statement: $ => choice(seq($._value, ';'), prec(-1, $.function)),
+
+    _type: $ => choice(
+      alias($.identifier, $.type_identifier),
+      $.function_type,
+      $.list_type,
+      $.map_type,
+    ),
+
+    function_type: $ => seq('fn', $.parameters, optional(field('return_type', $._type))),
+
+    list_type: $ => seq('[', $._type, ']'),
+
+    map_type: $ => seq('[', field('key', $._type), ':', field('value', $._type), ']'),
+
+    parameters: $ => seq('(', sep($.parameter, ','), ')'),
+
+    parameter: $ => seq(field('name', $.identifier), ':', field('type', $._type)),
+
+    _value: $ => choice(
+      $.identifier,
+      $.integer,
+      $.float,
+      $.string,
+      $.boolean,
+      $.function,
+      $.list,
+      $.map,
+      $.parenthesized,
+      $.call,
+      $.field_expression,
+      $.unary_expression,
+      $.cast_expression,
+      $.binary_expression,
+    ),
+
+    function: $ => seq(
+      'fn',
+      field('parameters', $.parameters),
+      optional(field('return_type', $._type)),
+      field('body', $.block),
+    ),
+
+    list: $ => seq('[', sep($._value, ','), ']'),
+
+    map: $ => seq('[', choice(':', sep1($.pair, ',')), ']'),
+
+    pair: $ => seq(field('key', $._value), ':', field('value', $._value)),
+
+    parenthesized: $ => seq('(', $._value, ')'),
+
+    call: $ => prec(PREC.postfix, seq(field('function', $._value), $.arguments)),
+
+    arguments: $ => seq('(', sep($._value, ','), ')'),
+
+    field_expression: $ => prec(PREC.postfix, seq(
+      field('value', $._value),
+      '.',
+      field('field', $.identifier),
+    )),
+
+    unary_expression: $ => prec(PREC.prefix, seq(
+      field('operator', choice(...UNARY)),
+      field('operand', $._value),
+    )),
+
+    cast_expression: $ => prec.left(PREC.cast, seq(
+      field('value', $._value),
+      'as',
+      field('type', $._type),
+    )),
+
+    binary_expression: $ => choice(...BINARY.map(([operator, precedence]) =>
+      prec.left(precedence, seq(
+        field('left', $._value),
+        field('operator', operator),
+        field('right', $._value),
+      )),
+    )),
+
+    boolean: $ => choice('true', 'false'),
+
+    identifier: $ => /[A-Za-z_][A-Za-z0-9_]*/,
+
+    integer: $ => /[0-9]+/,
+
+    float: $ => /[0-9]+\.[0-9]+/,
+
+    // there are no escapes, a string ends at the next quote
+    string: $ => /"[^"]*"/,
+
+    comment: $ => token(seq('//', /.*/)),
+  },
+});
+
+function sep(rule, separator) {
+  return optional(sep1(rule, separator));
+}
+
+function sep1(rule, separator) {
+  return seq(rule, repeat(seq(separator, rule)));
+}
+"#;
+
+const HIGHLIGHTS: &str = r#"; written by `ez grammar` along with the grammar
+
+(comment) @comment
+(string) @string
+(integer) @number
+(float) @number
+(boolean) @boolean
+
+[{keywords}] @keyword
+
+[{operators}] @operator
+
+[{brackets}] @punctuation.bracket
+
+[{delimiters}] @punctuation.delimiter
+
+(attribute "@" @attribute name: (identifier) @attribute)
+
+(type_identifier) @type
+(enum_declaration name: (identifier) @type)
+(variant name: (identifier) @constructor)
+(variant_pattern enum: (identifier) @type variant: (identifier) @constructor)
+
+(declaration name: (identifier) @function value: (function))
+(call function: (identifier) @function.call)
+(call function: (field_expression field: (identifier) @function.method.call))
+(parameter name: (identifier) @variable.parameter)
+(field_expression field: (identifier) @property)
+
+(identifier) @variable
+"#;
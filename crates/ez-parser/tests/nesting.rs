@@ -0,0 +1,72 @@
+// programs nested deeper than the parser goes are an error, not a stack that
+// runs out, and the ones that aren't still parse
+
+use ez_ast::Arena;
+use ez_parser::{ParseError, Parser};
+
+fn parse(source: &str) -> Result<(), ParseError> {
+    let arena = Arena::new();
+    Parser::new(source, &arena).parse_program().map(|_| ())
+}
+
+// `open` and `close` around `inside`, `levels` times, in a statement
+fn nested(
+    before: &str,
+    open: &str,
+    inside: &str,
+    close: &str,
+    after: &str,
+    levels: usize,
+) -> String {
+    format!(
+        "{}{}{}{}{}",
+        before,
+        open.repeat(levels),
+        inside,
+        close.repeat(levels),
+        after
+    )
+}
+
+#[test]
+fn too_deep() {
+    let sources = [
+        nested("x := ", "(", "1", ")", ";", 100_000),
+        nested("x := ", "-", "1", "", ";", 100_000),
+        nested("x := ", "[", "1", "]", ";", 100_000),
+        nested("x := ", "f(", "1", ")", ";", 100_000),
+        nested("", "{", "", "}", "", 100_000),
+        nested("x ", "[", "int", "]", ";", 100_000),
+        nested("x ", "fn (a: ", "int", ")", ";", 100_000),
+        nested("if a {} ", "else if a {} ", "", "", "", 100_000),
+        nested("match a { ", "A.B(", "_", ")", " {} }", 100_000),
+        // a chain of operators, casts, calls or fields is as deep as it's long
+        nested("x := 1", "", "", " + 1", ";", 100_000),
+        nested("x := 1", "", "", " as int", ";", 100_000),
+        nested("x := f", "", "", "()", ";", 100_000),
+        nested("x := a", "", "", ".b", ";", 100_000),
+        nested("x := ", "(", "1", " + 1 + 1 + 1)", ";", 100),
+    ];
+
+    for source in &sources {
+        match parse(source) {
+            Err(ParseError::TooDeep(_)) => {}
+            other => panic!("{:?} for {}...", other, &source[..40]),
+        }
+    }
+}
+
+#[test]
+fn deep() {
+    let sources = [
+        nested("x := ", "(", "1", ")", ";", 100),
+        nested("", "{", "", "}", "", 100),
+        nested("x ", "[", "int", "]", ";", 100),
+        nested("x := 1", "", "", " + 1", ";", 100),
+        nested("x := ", "f(", "a.b", ")", ";", 100),
+    ];
+
+    for source in &sources {
+        assert!(parse(source).is_ok(), "{}...", &source[..40]);
+    }
+}
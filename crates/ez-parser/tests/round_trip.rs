@@ -0,0 +1,196 @@
+// random values are written with every operation in parentheses, parsed, and
+// formatted, which drops the parentheses it doesn't need. Parsing what the
+// formatter printed has to give the same tree, or one of the two got the
+// precedence of something wrong
+
+use ez_ast::{Arena, Expr, ValueExpr};
+use ez_lexer::TokenKind;
+use ez_parser::formatter;
+use ez_parser::Parser;
+use proptest::prelude::*;
+
+// a value without spans or ids, what's compared between the parses
+#[derive(Debug, Clone, PartialEq)]
+enum Tree {
+    Int(i64),
+    Float(String),
+    String(String),
+    Bool(bool),
+    Name(String),
+    Unary(TokenKind, Box<Tree>),
+    Binary(TokenKind, Box<Tree>, Box<Tree>),
+    Call(Box<Tree>, Vec<Tree>),
+    Cast(Box<Tree>, String),
+    Field(Box<Tree>, String),
+    List(Vec<Tree>),
+}
+
+// anything but `"`, ez strings have no escapes. What would start a block or a
+// comment outside of one, and what isn't ASCII, stays in the string
+const LETTERS: &[char] = &[
+    'a', 'z', ' ', '1', '{', '}', '(', ')', '/', '*', ';', '\\', 'é', 'λ', '日', '🦀',
+];
+const NAMES: &[&str] = &["a", "b", "total", "x1", "_tmp"];
+const TYPES: &[&str] = &["int", "float", "string", "bool"];
+const UNARY: &[(&str, TokenKind)] = &[
+    ("-", TokenKind::Minus),
+    ("!", TokenKind::Not),
+    ("~", TokenKind::BitNot),
+];
+const BINARY: &[(&str, TokenKind)] = &[
+    ("||", TokenKind::Or),
+    ("&&", TokenKind::And),
+    ("|", TokenKind::BitOr),
+    ("^", TokenKind::BitXor),
+    ("&", TokenKind::BitAnd),
+    ("==", TokenKind::Equals),
+    ("!=", TokenKind::NotEquals),
+    (">", TokenKind::GreaterThan),
+    (">=", TokenKind::GreaterOrEquals),
+    ("<", TokenKind::LowerThan),
+    ("<=", TokenKind::LowerOrEquals),
+    ("+", TokenKind::Plus),
+    ("-", TokenKind::Minus),
+    ("*", TokenKind::Times),
+    ("/", TokenKind::DividedBy),
+];
+
+fn text(operators: &[(&'static str, TokenKind)], kind: &TokenKind) -> &'static str {
+    operators.iter().find(|(_, other)| other == kind).unwrap().0
+}
+
+fn leaf() -> impl Strategy<Value = Tree> {
+    prop_oneof![
+        (0..1000i64).prop_map(Tree::Int),
+        (0..100u32, 0..100u32).prop_map(|(whole, part)| Tree::Float(format!("{whole}.{part}"))),
+        prop::collection::vec(prop::sample::select(LETTERS), 0..24)
+            .prop_map(|chars| Tree::String(chars.into_iter().collect())),
+        any::<bool>().prop_map(Tree::Bool),
+        prop::sample::select(NAMES).prop_map(|name| Tree::Name(name.to_string())),
+    ]
+}
+
+fn value() -> impl Strategy<Value = Tree> {
+    leaf().prop_recursive(5, 48, 4, |inner| {
+        prop_oneof![
+            (prop::sample::select(UNARY), inner.clone())
+                .prop_map(|((_, kind), operand)| Tree::Unary(kind, Box::new(operand))),
+            (prop::sample::select(BINARY), inner.clone(), inner.clone()).prop_map(
+                |((_, kind), left, right)| Tree::Binary(kind, Box::new(left), Box::new(right))
+            ),
+            (inner.clone(), prop::collection::vec(inner.clone(), 0..3))
+                .prop_map(|(callee, args)| Tree::Call(Box::new(callee), args)),
+            (inner.clone(), prop::sample::select(TYPES))
+                .prop_map(|(value, basetype)| Tree::Cast(Box::new(value), basetype.to_string())),
+            (inner.clone(), prop::sample::select(NAMES))
+                .prop_map(|(value, field)| Tree::Field(Box::new(value), field.to_string())),
+            prop::collection::vec(inner, 1..4).prop_map(Tree::List),
+        ]
+    })
+}
+
+// every operation in parentheses, so the source says what the tree is whatever
+// the precedence of its operators
+fn write(tree: &Tree) -> String {
+    let list = |items: &[Tree]| items.iter().map(write).collect::<Vec<_>>().join(", ");
+    match tree {
+        Tree::Int(value) => format!("({})", value),
+        Tree::Float(text) => format!("({})", text),
+        Tree::String(value) => format!("\"{}\"", value),
+        Tree::Bool(value) => value.to_string(),
+        Tree::Name(name) => name.clone(),
+        Tree::Unary(kind, operand) => format!("({}{})", text(UNARY, kind), write(operand)),
+        Tree::Binary(kind, left, right) => {
+            format!("({} {} {})", write(left), text(BINARY, kind), write(right))
+        }
+        Tree::Call(callee, args) => format!("({}({}))", write(callee), list(args)),
+        Tree::Cast(value, basetype) => format!("({} as {})", write(value), basetype),
+        Tree::Field(value, field) => format!("({}.{})", write(value), field),
+        Tree::List(items) => format!("[{}]", list(items)),
+    }
+}
+
+fn tree(value: &ValueExpr) -> Tree {
+    let boxed = |value: &ValueExpr| Box::new(tree(value));
+    match value {
+        ValueExpr::Int { value, .. } => Tree::Int(*value),
+        // the text is compared, the tree only has the number
+        ValueExpr::Float { value, .. } => Tree::Float(format!("{value:?}")),
+        ValueExpr::String { value, .. } => Tree::String(value.clone()),
+        ValueExpr::Bool { value, .. } => Tree::Bool(*value),
+        ValueExpr::Identifier(identifier) => Tree::Name(identifier.name.to_string()),
+        ValueExpr::Unary {
+            operand, operator, ..
+        } => Tree::Unary(operator.clone(), boxed(operand)),
+        ValueExpr::Binary {
+            left,
+            right,
+            operator,
+            ..
+        } => Tree::Binary(operator.clone(), boxed(left), boxed(right)),
+        ValueExpr::Call { callee, args, .. } => {
+            Tree::Call(boxed(callee), args.iter().map(tree).collect())
+        }
+        ValueExpr::Cast {
+            value, basetype, ..
+        } => Tree::Cast(boxed(value), basetype.to_string()),
+        ValueExpr::Field { value, field, .. } => Tree::Field(boxed(value), field.name.to_string()),
+        ValueExpr::List { items, .. } => Tree::List(items.iter().map(tree).collect()),
+        value => panic!("no tree is made of {:?}", value),
+    }
+}
+
+// the values of the declarations of the source, in order
+fn parse(source: &str) -> Vec<Tree> {
+    let arena = Arena::new();
+    let program = match Parser::new(source, &arena).parse_program() {
+        Ok(program) => program,
+        Err(error) => panic!("{} doesn't parse: {}", source, error),
+    };
+
+    program
+        .statements()
+        .map(|statement| match statement {
+            Expr::Declaration {
+                value: Some(value), ..
+            } => tree(value),
+            statement => panic!("not a declaration: {:?}", statement),
+        })
+        .collect()
+}
+
+// the floats are written as they're parsed back, so they compare the same
+fn normalize(tree: Tree) -> Tree {
+    let boxed = |tree: Box<Tree>| Box::new(normalize(*tree));
+    let all = |trees: Vec<Tree>| trees.into_iter().map(normalize).collect();
+    match tree {
+        Tree::Float(text) => Tree::Float(format!("{:?}", text.parse::<f64>().unwrap())),
+        Tree::Unary(kind, operand) => Tree::Unary(kind, boxed(operand)),
+        Tree::Binary(kind, left, right) => Tree::Binary(kind, boxed(left), boxed(right)),
+        Tree::Call(callee, args) => Tree::Call(boxed(callee), all(args)),
+        Tree::Cast(value, basetype) => Tree::Cast(boxed(value), basetype),
+        Tree::Field(value, field) => Tree::Field(boxed(value), field),
+        Tree::List(items) => Tree::List(all(items)),
+        tree => tree,
+    }
+}
+
+proptest! {
+    #[test]
+    fn formatting_keeps_the_tree(values in prop::collection::vec(value(), 1..4)) {
+        let source: String = values
+            .iter()
+            .enumerate()
+            .map(|(i, value)| format!("v{} := {};\n", i, write(value)))
+            .collect();
+        let parsed = parse(&source);
+        let expected: Vec<Tree> = values.into_iter().map(normalize).collect();
+        prop_assert_eq!(&parsed, &expected, "the parser misread {}", source);
+
+        let formatted = match formatter::format(&source) {
+            Ok(formatted) => formatted,
+            Err(error) => panic!("{} doesn't format: {:?}", source, error),
+        };
+        prop_assert_eq!(parse(&formatted), parsed, "formatting changed {}", source);
+    }
+}
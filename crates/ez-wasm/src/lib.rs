@@ -0,0 +1,30 @@
+// the compiler for a playground in a browser, without a server. It's the `ez`
+// library built for `wasm32-unknown-unknown`, with two functions JavaScript
+// can call: `compile` checks a program and `run` runs it in the interpreter,
+// like `ez serve` does. There's no clock to stop a program that runs for too
+// long, the page can run it in a worker and end that instead
+
+use ez::playground::{self, Limits};
+use wasm_bindgen::prelude::*;
+
+// the diagnostics of the program, like `ez check` shows them without colors,
+// nothing when there are none
+#[wasm_bindgen]
+pub fn compile(source: &str) -> String {
+    playground::compile(source).diagnostics
+}
+
+// what `ez run` shows: the warnings and what the program printed, or the
+// errors that kept it from running or stopped it
+#[wasm_bindgen]
+pub fn run(source: &str) -> String {
+    let limits = Limits {
+        time: None,
+        ..Limits::default()
+    };
+    let outcome = playground::run(source, limits);
+    match outcome.passed {
+        true => outcome.diagnostics + &outcome.output,
+        false => outcome.output + &outcome.diagnostics,
+    }
+}
@@ -0,0 +1,27 @@
+#![no_main]
+
+use ez_ast::{binary, Arena};
+use libfuzzer_sys::fuzz_target;
+
+// any bytes read as a tree or an error without a panic, and a tree that's read
+// reads back the same once it's written again
+fuzz_target!(|data: &[u8]| {
+    // the magic bytes and the version, so more of the inputs get to the tree
+    let mut bytes = binary::MAGIC.to_vec();
+    bytes.extend(binary::VERSION.to_le_bytes());
+    bytes.extend(data);
+
+    let arena = Arena::new();
+    match binary::decode(&bytes, &arena) {
+        Ok(program) => {
+            let again = binary::encode(&program);
+            let arena = Arena::new();
+            let reread =
+                binary::decode(&again, &arena).expect("a tree that was written reads back");
+            assert_eq!(format!("{:?}", program), format!("{:?}", reread));
+        }
+        Err(error) => {
+            let _ = error.to_string();
+        }
+    }
+});
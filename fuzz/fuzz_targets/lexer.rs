@@ -0,0 +1,29 @@
+#![no_main]
+
+use ez_lexer::Lexer;
+use libfuzzer_sys::fuzz_target;
+
+// any text lexes without a panic, into tokens that come one after the other,
+// each at least a character long, so there are never more tokens than bytes
+fuzz_target!(|data: &[u8]| {
+    let source = String::from_utf8_lossy(data);
+    let mut end = 0;
+    for (count, token) in Lexer::new(&source).take(source.len() + 1).enumerate() {
+        assert!(count < source.len(), "more tokens than bytes");
+
+        let span = token.span;
+        assert!(
+            end <= span.start,
+            "{:?} overlaps the token before it",
+            token
+        );
+        assert!(span.start < span.end, "{:?} is empty", token);
+        assert!(span.end <= source.len(), "{:?} is past the end", token);
+        assert!(
+            source.is_char_boundary(span.start) && source.is_char_boundary(span.end),
+            "{:?} splits a character",
+            token
+        );
+        end = span.end;
+    }
+});
@@ -0,0 +1,47 @@
+#![no_main]
+
+use ez_ast::Arena;
+use ez_diagnostics::Diagnostic;
+use ez_lexer::Span;
+use ez_parser::Parser;
+use libfuzzer_sys::fuzz_target;
+
+fn check_span(span: Span, source: &str, what: &str) {
+    assert!(
+        span.start <= span.end,
+        "{} at {:?} ends before it starts",
+        what,
+        span
+    );
+    assert!(
+        span.end <= source.len(),
+        "{} at {:?} is past the end",
+        what,
+        span
+    );
+}
+
+// any text parses into a program or an error without a panic, and the spans of
+// both are in the text
+fuzz_target!(|data: &[u8]| {
+    let source = String::from_utf8_lossy(data);
+    let arena = Arena::new();
+    match Parser::new(&source, &arena).parse_program() {
+        Ok(program) => {
+            for statement in program.statements() {
+                check_span(statement.span(), &source, "a statement");
+            }
+            for using in &program.uses {
+                check_span(using.span, &source, "a use");
+            }
+        }
+        Err(error) => {
+            if let Some(span) = error.span() {
+                check_span(span, &source, "an error");
+            }
+            // errors are reported as diagnostics
+            let _ = error.to_string();
+            let _ = Diagnostic::from(error);
+        }
+    }
+});